@@ -144,11 +144,21 @@ pub fn exception(_: TokenStream, item: TokenStream) -> TokenStream {
 }
 
 /// This macro is used to signal that a function is an IRQ handler. This macro will automatically
-/// create 16 different functions, one for each IRQ, with name `irq_0`, `irq_1`, etc. These
-/// functions will call the original function after the usual interrupt handling code (saving the
-/// state of the CPU, swapping user GS with kernel GS, etc.)
+/// create one function for each IRQ, with name `irq_0`, `irq_1`, etc, and an `IRQ_STUBS` array
+/// containing all of them in order. The number of IRQs is given as argument, and is 16 if omitted.
+/// These functions will call the original function after the usual interrupt handling code
+/// (saving the state of the CPU, swapping user GS with kernel GS, etc.)
 #[proc_macro_attribute]
-pub fn irq_handler(_: TokenStream, item: TokenStream) -> TokenStream {
+pub fn irq_handler(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let count = if attr.is_empty() {
+        16
+    } else {
+        match parse_macro_input!(attr as syn::LitInt).base10_parse::<usize>() {
+            Ok(count) => count,
+            Err(error) => return error.to_compile_error().into(),
+        }
+    };
+
     let mut func = parse_macro_input!(item as ItemFn);
     let handler = func.sig.ident.clone();
 
@@ -156,8 +166,9 @@ pub fn irq_handler(_: TokenStream, item: TokenStream) -> TokenStream {
     // We make sure that the function ABI is set to C, so we can have an stable ABI
     func.sig.abi = Some(syn::parse_quote!(extern "C"));
     let mut functions = Vec::new();
+    let mut names = Vec::new();
 
-    for irq in 0..16 {
+    for irq in 0..count {
         let func_name = syn::Ident::new(&format!("irq_{}", irq), func.sig.ident.clone().span());
 
         functions.push(quote::quote! {
@@ -175,10 +186,12 @@ pub fn irq_handler(_: TokenStream, item: TokenStream) -> TokenStream {
                 );
             }
         });
+        names.push(func_name);
     }
 
     TokenStream::from(quote::quote!(
         #(#functions)*
+        const IRQ_STUBS: [unsafe extern "C" fn(); #count] = [#(#names),*];
         #func
     ))
 }
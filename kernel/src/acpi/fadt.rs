@@ -0,0 +1,198 @@
+//! The fixed ACPI description table (FADT, signature `FACP`). It describes the
+//! fixed hardware registers of ACPI: the power management blocks used to put
+//! the computer to sleep or to turn it off, the power management timer, and the
+//! register used to reset the computer. It also tells which legacy devices are
+//! present on the system.
+//!
+//! The table grew with each revision of ACPI, and the 32-bit addresses of the
+//! first revision were replaced by generic address structures: the fields that
+//! are missing from older tables are read as zero, and the extended addresses
+//! are used instead of the legacy ones when they are present.
+use super::{get_u16, get_u32, get_u64, AddressSpace, GenericAddress};
+use crate::x86_64::io::Port;
+use bitflags::bitflags;
+
+/// The size of the FADT of ACPI 6, the largest revision read by the kernel.
+const FADT_SIZE: usize = 276;
+
+/// The flag of the FADT set if the power management timer is 32 bits wide,
+/// instead of 24 bits.
+const TMR_VAL_EXT: u32 = 1 << 8;
+
+/// The flag of the FADT set if the reset register is supported.
+const RESET_REG_SUP: u32 = 1 << 10;
+
+/// The flag of the FADT set if the system does not implement the fixed
+/// hardware of ACPI, and only uses the ACPI tables.
+const HW_REDUCED_ACPI: u32 = 1 << 20;
+
+/// The FADT, parsed by [`setup`] when the ACPI tables are found.
+static FADT: Once<Fadt> = Once::new();
+
+bitflags! {
+    /// The IA-PC boot architecture flags of the FADT, describing the legacy
+    /// devices of the system.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct BootFlags : u16 {
+        /// The system has legacy ISA devices, such as the serial ports.
+        const LEGACY_DEVICES = 1 << 0;
+
+        /// The system has an i8042 PS/2 controller.
+        const I8042 = 1 << 1;
+
+        /// The VGA registers must not be probed.
+        const NO_VGA = 1 << 2;
+
+        /// The message signaled interrupts must not be enabled.
+        const NO_MSI = 1 << 3;
+
+        /// The OS must not enable the active state power management of PCI
+        /// Express.
+        const NO_ASPM = 1 << 4;
+
+        /// The system has no CMOS real-time clock.
+        const NO_CMOS_RTC = 1 << 5;
+    }
+}
+
+/// The register used to reset the computer, and the value to write to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Reset {
+    pub register: GenericAddress,
+    pub value: u8,
+}
+
+/// The content of the FADT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fadt {
+    /// The physical address of the differentiated system description table,
+    /// which contains the AML code describing the devices of the system.
+    pub dsdt: u64,
+
+    /// The interrupt of the system control interrupt (SCI), as an ISA interrupt
+    /// on systems with a 8259 PIC, or as a GSI.
+    pub sci_interrupt: u16,
+
+    /// The I/O port of the SMI command register, and the values to write to it
+    /// to enable or disable ACPI. The port is zero if ACPI is always enabled.
+    pub smi_command: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+
+    /// The event and control registers of the power management 1 blocks. The
+    /// `B` blocks are optional.
+    pub pm1a_event: Option<GenericAddress>,
+    pub pm1b_event: Option<GenericAddress>,
+    pub pm1a_control: Option<GenericAddress>,
+    pub pm1b_control: Option<GenericAddress>,
+
+    /// The power management timer, running at 3.579545 MHz, and whether it is
+    /// 32 bits wide instead of 24 bits.
+    pub pm_timer: Option<GenericAddress>,
+    pub pm_timer_32bit: bool,
+
+    /// The index of the century register of the CMOS, or zero if there is none.
+    pub century: u8,
+
+    /// The legacy devices of the system.
+    pub boot_flags: BootFlags,
+
+    /// The register used to reset the computer, if supported.
+    pub reset: Option<Reset>,
+
+    /// Set if the system does not implement the fixed hardware of ACPI, in
+    /// which case the power management blocks are absent.
+    pub hardware_reduced: bool,
+}
+
+impl Fadt {
+    /// Parse the FADT from the given data of the table, including its header.
+    #[must_use]
+    pub fn parse(table: &[u8]) -> Self {
+        let mut data = [0; FADT_SIZE];
+        let len = table.len().min(FADT_SIZE);
+        data[..len].copy_from_slice(&table[..len]);
+
+        let flags = get_u32(&data, 112);
+        let dsdt = match get_u64(&data, 140) {
+            0 => u64::from(get_u32(&data, 40)),
+            address => address,
+        };
+
+        // The reset register was added in ACPI 2.0, and must only be used if
+        // the firmware sets the flag telling that it is supported.
+        let reset = GenericAddress::parse(&data, 116)
+            .filter(|_| flags & RESET_REG_SUP != 0)
+            .map(|register| Reset {
+                register,
+                value: data[128],
+            });
+
+        Self {
+            dsdt,
+            sci_interrupt: get_u16(&data, 46),
+            smi_command: get_u32(&data, 48),
+            acpi_enable: data[52],
+            acpi_disable: data[53],
+            pm1a_event: block(&data, 148, 56, data[88]),
+            pm1b_event: block(&data, 160, 60, data[88]),
+            pm1a_control: block(&data, 172, 64, data[89]),
+            pm1b_control: block(&data, 184, 68, data[89]),
+            pm_timer: block(&data, 208, 76, data[91]),
+            pm_timer_32bit: flags & TMR_VAL_EXT != 0,
+            century: data[108],
+            boot_flags: BootFlags::from_bits_truncate(get_u16(&data, 109)),
+            reset,
+            hardware_reduced: flags & HW_REDUCED_ACPI != 0,
+        }
+    }
+}
+
+/// Returns the register block described by the generic address structure at the
+/// given extended offset of the FADT or, if there is none, by the I/O port at
+/// the given legacy offset and the given length in bytes.
+fn block(data: &[u8], extended: usize, legacy: usize, len: u8) -> Option<GenericAddress> {
+    GenericAddress::parse(data, extended).or_else(|| {
+        let port = get_u32(data, legacy);
+        (port != 0).then_some(GenericAddress {
+            space: AddressSpace::SystemIo,
+            bit_width: len.saturating_mul(8),
+            bit_offset: 0,
+            access_size: 0,
+            address: u64::from(port),
+        })
+    })
+}
+
+/// Parse the FADT if the firmware provided one. This is called by
+/// [`super::setup`] once the ACPI tables are found.
+#[init]
+pub(super) fn setup() {
+    if let Some(table) = super::find(b"FACP") {
+        FADT.call_once(|| Fadt::parse(table.data));
+    }
+}
+
+/// Returns the FADT, or `None` if the firmware did not provide one or if the
+/// ACPI tables were not found yet by [`super::setup`].
+#[must_use]
+pub fn get() -> Option<&'static Fadt> {
+    FADT.get()
+}
+
+/// Reset the computer with the reset register of the FADT. This only returns
+/// if the register is not supported, or is not in the I/O address space, the
+/// only one supported by the kernel, or if the reset did not happen.
+///
+/// # Safety
+/// The computer is reset without stopping anything, so the caller must ensure
+/// that all data that must be kept was written to the disks.
+#[allow(clippy::cast_possible_truncation)]
+pub unsafe fn reset() {
+    let Some(reset) = get().and_then(|fadt| fadt.reset) else {
+        return;
+    };
+    if reset.register.space == AddressSpace::SystemIo {
+        Port::<u8>::new(reset.register.address as u16).write(reset.value);
+    }
+}
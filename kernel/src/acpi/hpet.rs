@@ -0,0 +1,68 @@
+//! The high precision event timer description table (HPET). It gives the
+//! address of the registers of the HPET, a timer more precise than the PIT with
+//! a 64-bit main counter and several comparators, and its capabilities.
+use super::{get_u16, get_u32, GenericAddress};
+
+/// The HPET table, parsed the first time it is needed.
+static HPET: Lazy<Option<Hpet>> =
+    Lazy::new(|| super::find(b"HPET").and_then(|table| Hpet::parse(table.data)));
+
+/// The content of the HPET table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hpet {
+    /// The hardware revision of the timer.
+    pub revision: u8,
+
+    /// The number of comparators of the timer.
+    pub comparators: u8,
+
+    /// Set if the main counter is 64 bits wide, instead of 32 bits.
+    pub counter_64bit: bool,
+
+    /// Set if the timer can replace the PIT and the periodic interrupt of the
+    /// real-time clock, on IRQ 0 and 8.
+    pub legacy_replacement: bool,
+
+    /// The PCI vendor ID of the timer.
+    pub vendor: u16,
+
+    /// The location of the registers of the timer, in memory.
+    pub address: GenericAddress,
+
+    /// The sequence number of the timer.
+    pub number: u8,
+
+    /// The minimum number of ticks of the main counter that can be used for a
+    /// periodic interrupt without losing interrupts.
+    pub minimum_tick: u16,
+}
+
+impl Hpet {
+    /// Parse the HPET table from the given data of the table, including its
+    /// header, or return `None` if the table is too short or has no address.
+    #[must_use]
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 56 {
+            return None;
+        }
+
+        let id = get_u32(data, 36);
+        Some(Self {
+            revision: data[36],
+            comparators: (data[37] & 0x1F) + 1,
+            counter_64bit: id & (1 << 13) != 0,
+            legacy_replacement: id & (1 << 15) != 0,
+            vendor: get_u16(data, 38),
+            address: GenericAddress::parse(data, 40)?,
+            number: data[52],
+            minimum_tick: get_u16(data, 53),
+        })
+    }
+}
+
+/// Returns the HPET table, or `None` if the firmware did not provide one. It
+/// must not be called before the ACPI tables are found by [`super::setup`].
+#[must_use]
+pub fn get() -> Option<&'static Hpet> {
+    HPET.as_ref()
+}
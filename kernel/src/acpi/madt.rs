@@ -0,0 +1,255 @@
+//! The multiple APIC description table (MADT, signature `APIC`). It gives the
+//! address of the local APICs, lists the processors with their local APIC ID
+//! and the I/O APICs with the range of global system interrupts (GSI) they
+//! handle, and describes how the ISA interrupts are connected to those GSIs
+//! when they are not identity mapped.
+use super::{get_u16, get_u32, get_u64};
+
+/// The size of the fields that precede the entries of the table.
+const FIELDS_SIZE: usize = 8;
+
+/// The types of the entries of the table.
+const LOCAL_APIC: u8 = 0;
+const IO_APIC: u8 = 1;
+const INTERRUPT_OVERRIDE: u8 = 2;
+const LOCAL_APIC_NMI: u8 = 4;
+const LOCAL_APIC_ADDRESS: u8 = 5;
+const LOCAL_X2APIC: u8 = 9;
+const LOCAL_X2APIC_NMI: u8 = 10;
+
+/// The MADT, parsed the first time it is needed.
+static MADT: Lazy<Option<Madt>> = Lazy::new(|| {
+    super::find(b"APIC")
+        .filter(|table| table.content().len() >= FIELDS_SIZE)
+        .map(|table| Madt::parse(table.content()))
+});
+
+/// The polarity of an interrupt signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Polarity {
+    /// The polarity conforms to the specification of the bus: active high for
+    /// the ISA bus.
+    Conforming,
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// The trigger mode of an interrupt signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// The trigger mode conforms to the specification of the bus: edge
+    /// triggered for the ISA bus.
+    Conforming,
+    Edge,
+    Level,
+}
+
+/// Returns the polarity and the trigger mode encoded in the given MPS INTI
+/// flags of an entry.
+fn inti_flags(flags: u16) -> (Polarity, Trigger) {
+    let polarity = match flags & 0b11 {
+        0b01 => Polarity::ActiveHigh,
+        0b11 => Polarity::ActiveLow,
+        _ => Polarity::Conforming,
+    };
+    let trigger = match (flags >> 2) & 0b11 {
+        0b01 => Trigger::Edge,
+        0b11 => Trigger::Level,
+        _ => Trigger::Conforming,
+    };
+    (polarity, trigger)
+}
+
+/// A processor, with its local APIC or `x2APIC`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Processor {
+    /// The ACPI processor UID, used by the NMI entries and the AML code.
+    pub uid: u32,
+
+    /// The ID of the local APIC of the processor.
+    pub apic_id: u32,
+
+    /// Set if the processor can be used, and set if a disabled processor can be
+    /// enabled later.
+    pub enabled: bool,
+    pub online_capable: bool,
+}
+
+/// An I/O APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IoApic {
+    /// The ID of the I/O APIC.
+    pub id: u8,
+
+    /// The physical address of the registers of the I/O APIC.
+    pub address: u64,
+
+    /// The first global system interrupt handled by the I/O APIC.
+    pub gsi_base: u32,
+}
+
+/// The connection of an ISA interrupt to a global system interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterruptOverride {
+    /// The ISA interrupt.
+    pub source: u8,
+
+    /// The global system interrupt the ISA interrupt is connected to.
+    pub gsi: u32,
+
+    /// The polarity and the trigger mode of the interrupt.
+    pub polarity: Polarity,
+    pub trigger: Trigger,
+}
+
+/// A local interrupt pin of local APICs connected to the NMI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalNmi {
+    /// The ACPI processor UID of the processor, or `None` for all processors.
+    pub processor: Option<u32>,
+
+    /// The local interrupt pin, `LINT0` or `LINT1`.
+    pub lint: u8,
+
+    /// The polarity and the trigger mode of the interrupt.
+    pub polarity: Polarity,
+    pub trigger: Trigger,
+}
+
+/// The content of the MADT.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Madt {
+    /// The physical address of the local APIC of each processor.
+    pub local_apic: u64,
+
+    /// Set if the system also has a dual 8259 PIC, which must be masked before
+    /// using the I/O APICs.
+    pub legacy_pic: bool,
+
+    pub processors: Vec<Processor>,
+    pub io_apics: Vec<IoApic>,
+    pub overrides: Vec<InterruptOverride>,
+    pub nmis: Vec<LocalNmi>,
+}
+
+impl Madt {
+    /// Parse the given content of the MADT. Unknown entries are ignored, as
+    /// well as the entries too short for their type.
+    #[must_use]
+    pub fn parse(content: &[u8]) -> Self {
+        let mut madt = Self {
+            local_apic: u64::from(get_u32(content, 0)),
+            legacy_pic: get_u32(content, 4) & 1 != 0,
+            processors: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+            nmis: Vec::new(),
+        };
+
+        let mut entries = content.get(FIELDS_SIZE..).unwrap_or_default();
+        while let [kind, length, ..] = *entries {
+            let length = usize::from(length);
+            let Some(entry) = entries.get(..length).filter(|_| length >= 2) else {
+                break;
+            };
+            entries = &entries[length..];
+            madt.parse_entry(kind, entry);
+        }
+        madt
+    }
+
+    /// Parse the given entry of the given type.
+    fn parse_entry(&mut self, kind: u8, entry: &[u8]) {
+        match (kind, entry.len()) {
+            (LOCAL_APIC, 8..) => {
+                let flags = get_u32(entry, 4);
+                self.processors.push(Processor {
+                    uid: u32::from(entry[2]),
+                    apic_id: u32::from(entry[3]),
+                    enabled: flags & 1 != 0,
+                    online_capable: flags & 2 != 0,
+                });
+            }
+            (LOCAL_X2APIC, 16..) => {
+                let flags = get_u32(entry, 8);
+                self.processors.push(Processor {
+                    uid: get_u32(entry, 12),
+                    apic_id: get_u32(entry, 4),
+                    enabled: flags & 1 != 0,
+                    online_capable: flags & 2 != 0,
+                });
+            }
+            (IO_APIC, 12..) => self.io_apics.push(IoApic {
+                id: entry[2],
+                address: u64::from(get_u32(entry, 4)),
+                gsi_base: get_u32(entry, 8),
+            }),
+            (INTERRUPT_OVERRIDE, 10..) => {
+                let (polarity, trigger) = inti_flags(get_u16(entry, 8));
+                self.overrides.push(InterruptOverride {
+                    source: entry[3],
+                    gsi: get_u32(entry, 4),
+                    polarity,
+                    trigger,
+                });
+            }
+            (LOCAL_APIC_NMI, 6..) => {
+                let (polarity, trigger) = inti_flags(get_u16(entry, 3));
+                self.nmis.push(LocalNmi {
+                    processor: (entry[2] != 0xFF).then_some(u32::from(entry[2])),
+                    lint: entry[5],
+                    polarity,
+                    trigger,
+                });
+            }
+            (LOCAL_X2APIC_NMI, 12..) => {
+                let (polarity, trigger) = inti_flags(get_u16(entry, 2));
+                let uid = get_u32(entry, 4);
+                self.nmis.push(LocalNmi {
+                    processor: (uid != u32::MAX).then_some(uid),
+                    lint: entry[8],
+                    polarity,
+                    trigger,
+                });
+            }
+            (LOCAL_APIC_ADDRESS, 12..) => self.local_apic = get_u64(entry, 4),
+            _ => {}
+        }
+    }
+
+    /// Returns how the given ISA interrupt is connected to the I/O APICs: the
+    /// override of the interrupt if there is one, or else the GSI with the
+    /// same number and the conforming polarity and trigger mode.
+    #[must_use]
+    pub fn isa_interrupt(&self, irq: u8) -> InterruptOverride {
+        self.overrides
+            .iter()
+            .find(|entry| entry.source == irq)
+            .copied()
+            .unwrap_or(InterruptOverride {
+                source: irq,
+                gsi: u32::from(irq),
+                polarity: Polarity::Conforming,
+                trigger: Trigger::Conforming,
+            })
+    }
+
+    /// Returns the I/O APIC handling the given global system interrupt, if any.
+    /// The number of interrupts handled by an I/O APIC is only known by reading
+    /// its registers, so this returns the I/O APIC with the greatest base not
+    /// above the interrupt.
+    #[must_use]
+    pub fn io_apic(&self, gsi: u32) -> Option<&IoApic> {
+        self.io_apics
+            .iter()
+            .filter(|io_apic| io_apic.gsi_base <= gsi)
+            .max_by_key(|io_apic| io_apic.gsi_base)
+    }
+}
+
+/// Returns the MADT, or `None` if the firmware did not provide one. It must
+/// not be called before the ACPI tables are found by [`super::setup`].
+#[must_use]
+pub fn get() -> Option<&'static Madt> {
+    MADT.as_ref()
+}
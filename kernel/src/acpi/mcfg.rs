@@ -0,0 +1,53 @@
+//! The PCI Express memory mapped configuration table (MCFG). It gives, for each
+//! range of PCI buses, the physical address of the memory region through which
+//! the configuration space of their functions is accessed (ECAM).
+use super::{get_u16, get_u64};
+
+/// The size of the reserved field that precedes the entries of the table, and
+/// the size of an entry.
+const RESERVED_SIZE: usize = 8;
+const ENTRY_SIZE: usize = 16;
+
+/// A memory region mapping the configuration space of a range of PCI buses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+    /// The physical address of the configuration space of the first bus.
+    pub base: u64,
+
+    /// The PCI segment group of the buses.
+    pub segment: u16,
+
+    /// The first and the last bus mapped by the region.
+    pub start_bus: u8,
+    pub end_bus: u8,
+}
+
+impl Region {
+    /// Returns `true` if the given bus of the given segment is mapped by this
+    /// region.
+    #[must_use]
+    pub fn contains(&self, segment: u16, bus: u8) -> bool {
+        self.segment == segment && (self.start_bus..=self.end_bus).contains(&bus)
+    }
+}
+
+/// Returns the regions described by the MCFG, or an empty list if the firmware
+/// did not provide one, which is the case of systems without PCI Express.
+#[must_use]
+pub fn regions() -> Vec<Region> {
+    let Some(table) = super::find(b"MCFG") else {
+        return Vec::new();
+    };
+    table
+        .content()
+        .get(RESERVED_SIZE..)
+        .unwrap_or_default()
+        .chunks_exact(ENTRY_SIZE)
+        .map(|entry| Region {
+            base: get_u64(entry, 0),
+            segment: get_u16(entry, 8),
+            start_bus: entry[10],
+            end_bus: entry[11],
+        })
+        .collect()
+}
@@ -0,0 +1,274 @@
+//! ACPI tables. The firmware describes the hardware that cannot be discovered by
+//! other means in tables found from the root system description pointer (RSDP)
+//! given by Limine: the RSDP points to the XSDT, or to the RSDT on systems only
+//! supporting ACPI 1.0, which lists the physical address of every other table.
+//!
+//! Every table starts with a common header and is protected by a checksum: the
+//! sum of all its bytes must be zero. Tables with an invalid checksum are ignored.
+//! The tables are stored in memory reserved by the firmware, which is never used
+//! by the kernel, so they are read in place through the HHDM.
+use crate::limine::LIMINE_RSDP;
+use addr::{phys::Physical, virt::Virtual};
+
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+pub mod mcfg;
+
+/// The size of the header common to all the system description tables.
+pub const HEADER_SIZE: usize = 36;
+
+/// The signature of the RSDP.
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// The size of the RSDP of ACPI 1.0, and of the extended RSDP of later revisions,
+/// which are covered by their own checksum.
+const RSDP_SIZE: usize = 20;
+const XSDP_SIZE: usize = 36;
+
+/// The size of a generic address structure.
+const GAS_SIZE: usize = 12;
+
+/// The tables found at boot, in the order of the XSDT or RSDT.
+static TABLES: Once<Vec<Table>> = Once::new();
+
+/// A system description table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Table {
+    /// The signature of the table, identifying its type.
+    pub signature: [u8; 4],
+
+    /// The revision of the structure of the table.
+    pub revision: u8,
+
+    /// The content of the table, including its header.
+    pub data: &'static [u8],
+}
+
+impl Table {
+    /// Returns the content of the table that follows its header.
+    #[must_use]
+    pub fn content(&self) -> &'static [u8] {
+        &self.data[HEADER_SIZE..]
+    }
+}
+
+/// The address space of a register described by a [`GenericAddress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressSpace {
+    SystemMemory,
+    SystemIo,
+    PciConfig,
+    EmbeddedController,
+    SmBus,
+    FunctionalFixedHardware,
+    Other(u8),
+}
+
+impl From<u8> for AddressSpace {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Self::SystemMemory,
+            1 => Self::SystemIo,
+            2 => Self::PciConfig,
+            3 => Self::EmbeddedController,
+            4 => Self::SmBus,
+            0x7F => Self::FunctionalFixedHardware,
+            id => Self::Other(id),
+        }
+    }
+}
+
+/// The location of a register, in memory or in another address space, as
+/// described by the generic address structure (GAS) of the ACPI tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GenericAddress {
+    /// The address space of the register.
+    pub space: AddressSpace,
+
+    /// The size of the register and the offset of its first bit, in bits.
+    pub bit_width: u8,
+    pub bit_offset: u8,
+
+    /// The size of the accesses to the register: 1 for bytes, 2 for words, 3
+    /// for double words and 4 for quad words, or 0 if undefined.
+    pub access_size: u8,
+
+    /// The address of the register in its address space.
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// Read the generic address structure at the given offset of the given
+    /// data, or return `None` if its address is zero, which means that the
+    /// register does not exist.
+    #[must_use]
+    pub fn parse(data: &[u8], offset: usize) -> Option<Self> {
+        let data = &data[offset..offset + GAS_SIZE];
+        let address = get_u64(data, 4);
+        (address != 0).then_some(Self {
+            space: AddressSpace::from(data[0]),
+            bit_width: data[1],
+            bit_offset: data[2],
+            access_size: data[3],
+            address,
+        })
+    }
+}
+
+/// Returns all the valid tables listed by the firmware.
+#[must_use]
+pub fn tables() -> &'static [Table] {
+    TABLES.get().map_or(&[], Vec::as_slice)
+}
+
+/// Returns the first table with the given signature, if any.
+#[must_use]
+pub fn find(signature: &[u8; 4]) -> Option<Table> {
+    tables()
+        .iter()
+        .find(|table| &table.signature == signature)
+        .copied()
+}
+
+/// Find the tables listed in the XSDT or the RSDT, and check their checksum.
+///
+/// # Safety
+/// The RSDP given by Limine and the tables it points to must be valid, and must
+/// be mapped in the HHDM.
+#[init]
+pub unsafe fn setup() {
+    let tables = read_root().unwrap_or_else(|| {
+        log::warn!("acpi: no valid RSDP, ACPI tables are not available");
+        Vec::new()
+    });
+
+    let signatures: Vec<_> = tables
+        .iter()
+        .map(|table| String::from_utf8_lossy(&table.signature).into_owned())
+        .collect();
+    log::info!("acpi: tables: {}", signatures.join(" "));
+    TABLES.call_once(|| tables);
+    fadt::setup();
+
+    if let Some(madt) = madt::get() {
+        log::info!(
+            "acpi: {} processors, {} I/O APICs, {} interrupt overrides",
+            madt.processors.len(),
+            madt.io_apics.len(),
+            madt.overrides.len()
+        );
+    }
+    if let Some(hpet) = hpet::get() {
+        log::info!(
+            "acpi: HPET at {:#x} with {} comparators",
+            hpet.address.address,
+            hpet.comparators
+        );
+    }
+}
+
+/// Read the RSDP and the table it points to, and return the tables it lists, or
+/// `None` if the RSDP or the root table is invalid.
+///
+/// # Safety
+/// See [`setup`].
+unsafe fn read_root() -> Option<Vec<Table>> {
+    let pointer = LIMINE_RSDP.get_response()?.address().cast::<u8>();
+    let rsdp = core::slice::from_raw_parts(pointer, RSDP_SIZE);
+    if &rsdp[..8] != RSDP_SIGNATURE || !checksum(rsdp) {
+        return None;
+    }
+
+    // ACPI 2.0 and later add the 64-bit address of the XSDT, which must be used
+    // instead of the RSDT when present.
+    let (root, entry_size) = if rsdp[15] >= 2 {
+        let extended = core::slice::from_raw_parts(pointer, XSDP_SIZE);
+        if !checksum(extended) {
+            return None;
+        }
+        (get_u64(extended, 24), 8)
+    } else {
+        (u64::from(get_u32(rsdp, 16)), 4)
+    };
+
+    let root = read_table(root)?;
+    let tables = root
+        .content()
+        .chunks_exact(entry_size)
+        .filter_map(|entry| {
+            let address = match entry_size {
+                8 => get_u64(entry, 0),
+                _ => u64::from(get_u32(entry, 0)),
+            };
+            read_table(address)
+        })
+        .collect();
+    Some(tables)
+}
+
+/// Read the table at the given physical address, and return it if its checksum
+/// is valid.
+///
+/// # Safety
+/// See [`setup`].
+#[allow(clippy::cast_possible_truncation)]
+unsafe fn read_table(address: u64) -> Option<Table> {
+    let header = physical(address, HEADER_SIZE);
+    let length = get_u32(header, 4) as usize;
+    if length < HEADER_SIZE {
+        return None;
+    }
+
+    let data = physical(address, length);
+    let mut signature = [0; 4];
+    signature.copy_from_slice(&data[..4]);
+    if !checksum(data) {
+        log::warn!(
+            "acpi: table {} has an invalid checksum, ignored",
+            String::from_utf8_lossy(&signature)
+        );
+        return None;
+    }
+
+    Some(Table {
+        signature,
+        revision: data[8],
+        data,
+    })
+}
+
+/// Returns the given number of bytes of physical memory starting at the given
+/// physical address.
+///
+/// # Safety
+/// The memory must be mapped in the HHDM, and must never be modified.
+#[allow(clippy::cast_possible_truncation)]
+unsafe fn physical(address: u64, len: usize) -> &'static [u8] {
+    let virt = Virtual::from(Physical::new(address as usize));
+    core::slice::from_raw_parts(virt.as_ptr(), len)
+}
+
+/// Returns `true` if the sum of the given bytes is zero.
+fn checksum(data: &[u8]) -> bool {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+/// Read a little-endian `u16` at the given offset of the given data.
+fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Read a little-endian `u32` at the given offset of the given data.
+fn get_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Read a little-endian `u64` at the given offset of the given data.
+fn get_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,69 @@
+//! The kernel command line. It is given by Limine from the `CMDLINE` option of the
+//! configuration file, and is made of options separated by spaces. An option is
+//! either a flag (`quiet`) or a key and a value separated by an equal sign
+//! (`brd.rd_size=8192`). Options specific to a driver are prefixed by the name of
+//! the driver, followed by a dot.
+use crate::limine::LIMINE_KERNEL_FILE;
+use core::str::FromStr;
+
+/// The kernel command line.
+static CMDLINE: Once<String> = Once::new();
+
+/// Retrieve the kernel command line from Limine. If Limine did not give us the
+/// kernel file, the command line is empty.
+#[init]
+pub fn setup() {
+    let cmdline = LIMINE_KERNEL_FILE
+        .get_response()
+        .map(|response| String::from_utf8_lossy(response.file().cmdline()).into_owned())
+        .unwrap_or_default();
+
+    if !cmdline.is_empty() {
+        log::info!("Kernel command line: {}", cmdline);
+    }
+    CMDLINE.call_once(|| cmdline);
+}
+
+/// Returns an iterator over the options of the command line, as key and value
+/// pairs. Flags have no value.
+pub fn options() -> impl Iterator<Item = (&'static str, Option<&'static str>)> {
+    CMDLINE
+        .get()
+        .map_or("", String::as_str)
+        .split_ascii_whitespace()
+        .map(|option| match option.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (option, None),
+        })
+}
+
+/// Returns `true` if an option with the given key was passed on the command
+/// line, with or without a value.
+#[must_use]
+pub fn contains(key: &str) -> bool {
+    options().any(|(k, _)| k == key)
+}
+
+/// Returns the value of the given option, or `None` if the option was not passed
+/// or has no value. If the option was passed several times, the last value is
+/// returned, allowing to override an option by appending it to the command line.
+#[must_use]
+pub fn get(key: &str) -> Option<&'static str> {
+    options()
+        .filter(|(k, _)| *k == key)
+        .filter_map(|(_, value)| value)
+        .last()
+}
+
+/// Returns the value of the given option parsed as a `T`, or `None` if the
+/// option was not passed. If the value cannot be parsed, a warning is logged and
+/// `None` is returned, so that the caller falls back to its default value.
+#[must_use]
+pub fn parse<T: FromStr>(key: &str) -> Option<T> {
+    let value = get(key)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        log::warn!("cmdline: invalid value for {}: {}", key, value);
+    }
+    parsed
+}
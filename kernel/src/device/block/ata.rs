@@ -0,0 +1,712 @@
+//! ATA/IDE drives on the legacy primary and secondary channels, accessed with
+//! programmed I/O. Each channel has a master and a slave drive, which are probed
+//! at boot with the IDENTIFY command: ATA disks are read and written with LBA28
+//! commands, or LBA48 commands when a request goes beyond the first 2^28 sectors,
+//! and ATAPI drives (CD-ROMs) are read with the SCSI `READ(10)` command sent in
+//! a packet.
+//!
+//! A channel executes one command at a time: requests submitted to its drives are
+//! queued, and the next one is started when the current one completes. A command
+//! makes progress on each interrupt of the channel (IRQ 14 or 15): for example,
+//! the drive raises an interrupt each time a sector is ready to be read. Large
+//! requests are split into several commands.
+//!
+//! Drives are named and numbered like the IDE drives on Linux: `hda` and `hdb`
+//! are the master and slave drives of the primary channel, and `hdc` and `hdd`
+//! those of the secondary channel, where QEMU attaches its CD-ROM drive.
+use super::{BlockDevice, IoError, Operation, Request};
+use crate::{
+    device::Identifier,
+    x86_64::{self, io::Port, irq::Affinity},
+};
+use alloc::{collections::VecDeque, format};
+
+/// The major numbers of the drives of the primary and secondary channels, the
+/// same as on Linux. The master drive of a channel has the minor number 0, and
+/// the slave drive [`SLAVE_MINOR`].
+pub const PRIMARY_MAJOR: u32 = 3;
+pub const SECONDARY_MAJOR: u32 = 22;
+pub const SLAVE_MINOR: u32 = 64;
+
+/// The IRQ numbers of the primary and secondary channels.
+pub const PRIMARY_IRQ: u8 = 14;
+pub const SECONDARY_IRQ: u8 = 15;
+
+/// The size of a sector of an ATA disk, and of an ATAPI drive.
+const SECTOR_SIZE: usize = 512;
+const ATAPI_SECTOR_SIZE: usize = 2048;
+
+/// The maximal number of sectors transferred by a single command.
+const MAX_SECTORS: usize = 256;
+
+/// The number of sectors that can be addressed by LBA28 commands.
+const LBA28_LIMIT: u64 = 1 << 28;
+
+/// The maximal number of bytes transferred each time an ATAPI drive is ready to
+/// transfer data, which is a multiple of the sector size of ATAPI drives.
+const BYTE_COUNT_LIMIT: usize = 0xF800;
+
+/// The number of times the status of a drive is read while waiting for it before
+/// giving up.
+const TIMEOUT: usize = 1_000_000;
+
+/// The number of times `READ CAPACITY` is sent to an ATAPI drive. The first
+/// command sent to a drive after a reset or a medium change fails with an unit
+/// attention condition.
+const CAPACITY_RETRIES: usize = 3;
+
+/// The bits of the status register.
+const STATUS_ERR: u8 = 0x01;
+const STATUS_DRQ: u8 = 0x08;
+const STATUS_DF: u8 = 0x20;
+const STATUS_BSY: u8 = 0x80;
+
+/// The bit of the device control register that disables interrupts.
+const CONTROL_NIEN: u8 = 0x02;
+
+/// The bits of the drive register: the bits that must always be set, the bit
+/// that enables LBA addressing, and the bit that selects the slave drive.
+const DRIVE_DEFAULT: u8 = 0xA0;
+const DRIVE_LBA: u8 = 0x40;
+const DRIVE_SLAVE: u8 = 0x10;
+
+/// The ATA commands used by the driver.
+const COMMAND_READ_SECTORS: u8 = 0x20;
+const COMMAND_READ_SECTORS_EXT: u8 = 0x24;
+const COMMAND_WRITE_SECTORS: u8 = 0x30;
+const COMMAND_WRITE_SECTORS_EXT: u8 = 0x34;
+const COMMAND_PACKET: u8 = 0xA0;
+const COMMAND_IDENTIFY_PACKET: u8 = 0xA1;
+const COMMAND_FLUSH_CACHE: u8 = 0xE7;
+const COMMAND_FLUSH_CACHE_EXT: u8 = 0xEA;
+const COMMAND_IDENTIFY: u8 = 0xEC;
+
+/// The SCSI commands sent to ATAPI drives.
+const SCSI_READ_CAPACITY: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+
+/// The signatures left in the LBA mid and high registers by ATAPI drives after
+/// an IDENTIFY command, for parallel and serial ATA drives.
+const ATAPI_SIGNATURES: [(u8, u8); 2] = [(0x14, 0xEB), (0x69, 0x96)];
+
+/// The legacy ATA channels.
+static CHANNELS: [Channel; 2] = [
+    Channel::new(0x1F0, 0x3F6, PRIMARY_IRQ),
+    Channel::new(0x170, 0x376, SECONDARY_IRQ),
+];
+
+/// The kind of a drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    /// An ATA disk, which may support LBA48 commands.
+    Ata { lba48: bool },
+
+    /// An ATAPI drive, which is accessed with SCSI commands sent in packets.
+    Atapi,
+}
+
+impl Kind {
+    /// The size of a sector of drives of this kind.
+    const fn sector_size(self) -> usize {
+        match self {
+            Kind::Ata { .. } => SECTOR_SIZE,
+            Kind::Atapi => ATAPI_SECTOR_SIZE,
+        }
+    }
+}
+
+/// A drive of a channel, targeted by a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Target {
+    /// Whether the drive is the slave drive of the channel.
+    slave: bool,
+
+    /// The kind of the drive.
+    kind: Kind,
+}
+
+impl Target {
+    /// The value of the drive register selecting this drive.
+    const fn select(self) -> u8 {
+        if self.slave {
+            DRIVE_DEFAULT | DRIVE_SLAVE
+        } else {
+            DRIVE_DEFAULT
+        }
+    }
+}
+
+/// A request being executed by a channel.
+struct Transfer {
+    /// The drive executing the request.
+    target: Target,
+
+    /// The request.
+    request: Arc<Request>,
+
+    /// The number of bytes of the request transferred so far.
+    done: usize,
+
+    /// The number of bytes of the request transferred once the current command
+    /// is completed.
+    end: usize,
+}
+
+/// The state of a channel.
+struct State {
+    /// The requests waiting for the current one to complete.
+    queue: VecDeque<(Target, Arc<Request>)>,
+
+    /// The request being executed.
+    current: Option<Transfer>,
+}
+
+/// An ATA channel, with up to two drives.
+struct Channel {
+    data: Port<u16>,
+    error_features: Port<u8>,
+    sector_count: Port<u8>,
+    lba_low: Port<u8>,
+    lba_mid: Port<u8>,
+    lba_high: Port<u8>,
+    drive: Port<u8>,
+    status_command: Port<u8>,
+    control: Port<u8>,
+
+    /// The IRQ of the channel.
+    irq: u8,
+
+    /// The requests of the channel. This lock is taken by the interrupt handler,
+    /// so interrupts must be disabled while holding it.
+    state: Spinlock<State>,
+}
+
+impl Channel {
+    /// Create a channel using the given I/O ports and IRQ.
+    const fn new(base: u16, control: u16, irq: u8) -> Self {
+        Self {
+            data: Port::new(base),
+            error_features: Port::new(base + 1),
+            sector_count: Port::new(base + 2),
+            lba_low: Port::new(base + 3),
+            lba_mid: Port::new(base + 4),
+            lba_high: Port::new(base + 5),
+            drive: Port::new(base + 6),
+            status_command: Port::new(base + 7),
+            control: Port::new(control),
+            irq,
+            state: Spinlock::new(State {
+                queue: VecDeque::new(),
+                current: None,
+            }),
+        }
+    }
+
+    /// Wait about 400 nanoseconds, which is the time needed by a drive to update
+    /// its status after a command or a drive selection.
+    unsafe fn delay(&self) {
+        for _ in 0..4 {
+            let _ = self.control.read();
+        }
+    }
+
+    /// Select the given drive, with the given high bits of the drive register.
+    unsafe fn select(&self, target: Target, bits: u8) {
+        self.drive.write(target.select() | bits);
+        self.delay();
+    }
+
+    /// Send the given command, and wait for the drive to update its status.
+    unsafe fn command(&self, command: u8) {
+        self.status_command.write(command);
+        self.delay();
+    }
+
+    /// Wait until the selected drive is not busy, and return its status.
+    ///
+    /// # Errors
+    /// Returns `IoError::DeviceError` if the drive is still busy after a while.
+    unsafe fn wait(&self) -> Result<u8, IoError> {
+        for _ in 0..TIMEOUT {
+            let status = self.control.read();
+            if status & STATUS_BSY == 0 {
+                return Ok(status);
+            }
+            core::hint::spin_loop();
+        }
+        Err(IoError::DeviceError)
+    }
+
+    /// Wait until the selected drive is ready to transfer data.
+    ///
+    /// # Errors
+    /// Returns `IoError::DeviceError` if the drive reported an error, or if it
+    /// is not ready after a while.
+    unsafe fn wait_data(&self) -> Result<(), IoError> {
+        let status = self.wait()?;
+        if status & (STATUS_ERR | STATUS_DF) != 0 || status & STATUS_DRQ == 0 {
+            return Err(IoError::DeviceError);
+        }
+        Ok(())
+    }
+
+    /// Read data from the data register into the given buffer.
+    unsafe fn read_data(&self, buffer: &mut [u8]) {
+        for word in buffer.chunks_exact_mut(2) {
+            word.copy_from_slice(&self.data.read().to_le_bytes());
+        }
+    }
+
+    /// Write the given buffer to the data register, and wait for the drive to
+    /// update its status.
+    unsafe fn write_data(&self, buffer: &[u8]) {
+        for word in buffer.chunks_exact(2) {
+            self.data.write(u16::from_le_bytes([word[0], word[1]]));
+        }
+        self.delay();
+    }
+
+    /// Returns the number of bytes an ATAPI drive is ready to transfer.
+    unsafe fn byte_count(&self) -> usize {
+        usize::from(self.lba_mid.read()) | usize::from(self.lba_high.read()) << 8
+    }
+
+    /// Select the given ATAPI drive and send it the given SCSI command, which
+    /// transfers the given number of bytes.
+    ///
+    /// # Errors
+    /// Returns `IoError::DeviceError` if the drive did not accept the packet.
+    #[allow(clippy::cast_possible_truncation)]
+    unsafe fn send_packet(
+        &self,
+        target: Target,
+        packet: &[u8; 12],
+        len: usize,
+    ) -> Result<(), IoError> {
+        let limit = core::cmp::min(len, BYTE_COUNT_LIMIT);
+        self.select(target, 0);
+        self.error_features.write(0);
+        self.lba_mid.write(limit as u8);
+        self.lba_high.write((limit >> 8) as u8);
+        self.command(COMMAND_PACKET);
+        self.wait_data()?;
+        self.write_data(packet);
+        Ok(())
+    }
+
+    /// Send the given SCSI command to the given ATAPI drive and read its data
+    /// into the given buffer, polling the drive instead of waiting for its
+    /// interrupts.
+    ///
+    /// # Errors
+    /// Returns `IoError::DeviceError` if the drive reported an error, or if it
+    /// did not transfer exactly the size of the buffer.
+    unsafe fn packet_polled(
+        &self,
+        target: Target,
+        packet: &[u8; 12],
+        buffer: &mut [u8],
+    ) -> Result<(), IoError> {
+        self.send_packet(target, packet, buffer.len())?;
+        let mut done = 0;
+        loop {
+            let status = self.wait()?;
+            let _ = self.status_command.read();
+            if status & (STATUS_ERR | STATUS_DF) != 0 {
+                return Err(IoError::DeviceError);
+            }
+            if status & STATUS_DRQ == 0 {
+                break;
+            }
+            let count = self.byte_count();
+            if done + count > buffer.len() {
+                return Err(IoError::DeviceError);
+            }
+            self.read_data(&mut buffer[done..done + count]);
+            self.delay();
+            done += count;
+        }
+
+        if done == buffer.len() {
+            Ok(())
+        } else {
+            Err(IoError::DeviceError)
+        }
+    }
+
+    /// Send the IDENTIFY command, or IDENTIFY PACKET for an ATAPI drive, to the
+    /// master or slave drive of the channel. Returns the kind of the drive and
+    /// its identification data, or `None` if there is no usable drive.
+    unsafe fn identify(&self, slave: bool) -> Option<(Target, [u16; 256])> {
+        let mut target = Target {
+            slave,
+            kind: Kind::Atapi,
+        };
+        self.select(target, 0);
+        self.sector_count.write(0);
+        self.lba_low.write(0);
+        self.lba_mid.write(0);
+        self.lba_high.write(0);
+        self.command(COMMAND_IDENTIFY);
+        if self.control.read() == 0 {
+            return None;
+        }
+
+        self.wait().ok()?;
+        let signature = (self.lba_mid.read(), self.lba_high.read());
+        if ATAPI_SIGNATURES.contains(&signature) {
+            self.command(COMMAND_IDENTIFY_PACKET);
+        } else if signature == (0, 0) {
+            target.kind = Kind::Ata { lba48: false };
+        } else {
+            return None;
+        }
+
+        self.wait_data().ok()?;
+        let mut data = [0; 512];
+        self.read_data(&mut data);
+        let _ = self.status_command.read();
+
+        let mut words = [0; 256];
+        for (word, bytes) in words.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        Some((target, words))
+    }
+
+    /// Returns the number of sectors of the medium of the given ATAPI drive, or
+    /// zero if it has no medium or if its sectors are not 2048 bytes large.
+    unsafe fn atapi_capacity(&self, target: Target) -> u64 {
+        let mut packet = [0; 12];
+        packet[0] = SCSI_READ_CAPACITY;
+        let mut data = [0; 8];
+        for _ in 0..CAPACITY_RETRIES {
+            if self.packet_polled(target, &packet, &mut data).is_ok() {
+                let last = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                let size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                if size as usize != ATAPI_SECTOR_SIZE {
+                    log::warn!("ata: unsupported ATAPI sector size of {} bytes", size);
+                    return 0;
+                }
+                return u64::from(last) + 1;
+            }
+        }
+        0
+    }
+
+    /// Queue the given request for the given drive, and start it if the channel
+    /// is idle.
+    fn submit(&self, target: Target, request: Arc<Request>) {
+        x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            state.queue.push_back((target, request));
+            if state.current.is_none() {
+                self.start(&mut state);
+            }
+        });
+    }
+
+    /// Start the next queued request. Requests that fail to start, and requests
+    /// with nothing to transfer, are completed immediately.
+    fn start(&self, state: &mut State) {
+        while let Some((target, request)) = state.queue.pop_front() {
+            let mut transfer = Transfer {
+                target,
+                request,
+                done: 0,
+                end: 0,
+            };
+            match unsafe { self.issue(&mut transfer) } {
+                Ok(true) => {
+                    state.current = Some(transfer);
+                    return;
+                }
+                Ok(false) => transfer.request.complete(Ok(())),
+                Err(error) => transfer.request.complete(Err(error)),
+            }
+        }
+    }
+
+    /// Send the command transferring the next sectors of the given request, and
+    /// returns `true`, or returns `false` if there is nothing to do.
+    ///
+    /// # Errors
+    /// Returns `IoError::DeviceError` if the drive did not accept the command.
+    #[allow(clippy::cast_possible_truncation)]
+    unsafe fn issue(&self, transfer: &mut Transfer) -> Result<bool, IoError> {
+        let target = transfer.target;
+        let sector_size = target.kind.sector_size();
+        let remaining = transfer.request.len() - transfer.done;
+        let count = core::cmp::min(remaining / sector_size, MAX_SECTORS);
+        let sector = transfer.request.sector() + (transfer.done / sector_size) as u64;
+        transfer.end = transfer.done + count * sector_size;
+
+        match (target.kind, transfer.request.operation()) {
+            (Kind::Atapi, Operation::Read) if count > 0 => {
+                let mut packet = [0; 12];
+                packet[0] = SCSI_READ_10;
+                packet[2..6].copy_from_slice(&(sector as u32).to_be_bytes());
+                packet[7..9].copy_from_slice(&(count as u16).to_be_bytes());
+                self.send_packet(target, &packet, count * sector_size)?;
+                Ok(true)
+            }
+            (Kind::Ata { lba48 }, operation @ (Operation::Read | Operation::Write))
+                if count > 0 =>
+            {
+                let extended = lba48 && sector + count as u64 > LBA28_LIMIT;
+                if extended {
+                    self.select(target, DRIVE_LBA);
+                    self.sector_count.write((count >> 8) as u8);
+                    self.lba_low.write((sector >> 24) as u8);
+                    self.lba_mid.write((sector >> 32) as u8);
+                    self.lba_high.write((sector >> 40) as u8);
+                } else {
+                    self.select(target, DRIVE_LBA | (sector >> 24) as u8 & 0x0F);
+                }
+                // A count of 256 sectors is written as zero.
+                self.sector_count.write(count as u8);
+                self.lba_low.write(sector as u8);
+                self.lba_mid.write((sector >> 8) as u8);
+                self.lba_high.write((sector >> 16) as u8);
+                self.command(match (operation, extended) {
+                    (Operation::Read, false) => COMMAND_READ_SECTORS,
+                    (Operation::Read, true) => COMMAND_READ_SECTORS_EXT,
+                    (_, false) => COMMAND_WRITE_SECTORS,
+                    (_, true) => COMMAND_WRITE_SECTORS_EXT,
+                });
+
+                // The first sector is written without waiting for an interrupt.
+                if operation == Operation::Write {
+                    self.wait_data()?;
+                    let done = transfer.done;
+                    self.write_data(&transfer.request.buffer()[done..done + SECTOR_SIZE]);
+                    transfer.done += SECTOR_SIZE;
+                }
+                Ok(true)
+            }
+            (Kind::Ata { lba48 }, Operation::Flush) => {
+                self.select(target, 0);
+                self.command(if lba48 {
+                    COMMAND_FLUSH_CACHE_EXT
+                } else {
+                    COMMAND_FLUSH_CACHE
+                });
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Make progress on the given transfer, now that the drive is no longer busy
+    /// and has the given status. Returns `true` if the request is completed.
+    ///
+    /// # Errors
+    /// Returns `IoError::DeviceError` if the drive reported an error, or if it
+    /// transferred an unexpected amount of data.
+    unsafe fn step(&self, transfer: &mut Transfer, status: u8) -> Result<bool, IoError> {
+        if status & (STATUS_ERR | STATUS_DF) != 0 {
+            return Err(IoError::DeviceError);
+        }
+
+        let done = transfer.done;
+        match (transfer.target.kind, transfer.request.operation()) {
+            (Kind::Ata { .. }, Operation::Flush) => return Ok(true),
+            (Kind::Ata { .. }, Operation::Read) => {
+                if status & STATUS_DRQ == 0 {
+                    return Err(IoError::DeviceError);
+                }
+                self.read_data(&mut transfer.request.buffer()[done..done + SECTOR_SIZE]);
+                transfer.done += SECTOR_SIZE;
+                if transfer.done < transfer.end {
+                    return Ok(false);
+                }
+            }
+            (Kind::Ata { .. }, Operation::Write) if done < transfer.end => {
+                if status & STATUS_DRQ == 0 {
+                    return Err(IoError::DeviceError);
+                }
+                self.write_data(&transfer.request.buffer()[done..done + SECTOR_SIZE]);
+                transfer.done += SECTOR_SIZE;
+                return Ok(false);
+            }
+            (Kind::Ata { .. }, Operation::Write) => {}
+            (Kind::Atapi, _) if status & STATUS_DRQ != 0 => {
+                let count = self.byte_count();
+                if done + count > transfer.end {
+                    return Err(IoError::DeviceError);
+                }
+                self.read_data(&mut transfer.request.buffer()[done..done + count]);
+                self.delay();
+                transfer.done += count;
+                return Ok(false);
+            }
+            (Kind::Atapi, _) => {
+                if done != transfer.end {
+                    return Err(IoError::DeviceError);
+                }
+            }
+        }
+
+        // The current command is completed: start the next one if the request is
+        // not entirely transferred yet.
+        if transfer.done == transfer.request.len() {
+            Ok(true)
+        } else {
+            self.issue(transfer).map(|issued| !issued)
+        }
+    }
+
+    /// Make progress on the current request if the drive is no longer busy. This
+    /// is called by the interrupt handler of the channel, and when polling the
+    /// channel during the kernel initialization. Reading the status register
+    /// acknowledges the interrupt.
+    fn service(&self) {
+        let mut state = self.state.lock();
+        let Some(mut transfer) = state.current.take() else {
+            let _ = unsafe { self.status_command.read() };
+            return;
+        };
+        if unsafe { self.control.read() } & STATUS_BSY != 0 {
+            state.current = Some(transfer);
+            return;
+        }
+
+        let status = unsafe { self.status_command.read() };
+        match unsafe { self.step(&mut transfer, status) } {
+            Ok(false) => {
+                state.current = Some(transfer);
+                return;
+            }
+            Ok(true) => transfer.request.complete(Ok(())),
+            Err(error) => transfer.request.complete(Err(error)),
+        }
+        self.start(&mut state);
+    }
+}
+
+/// A drive of an ATA channel.
+pub struct Drive {
+    /// The name of the drive.
+    name: String,
+
+    /// The channel of the drive.
+    channel: &'static Channel,
+
+    /// The position and the kind of the drive on its channel.
+    target: Target,
+
+    /// The number of sectors of the drive.
+    capacity: u64,
+}
+
+impl BlockDevice for Drive {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.target.kind.sector_size()
+    }
+
+    fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    fn read_only(&self) -> bool {
+        self.target.kind == Kind::Atapi
+    }
+
+    fn submit(&self, request: Arc<Request>) {
+        self.channel.submit(self.target, request);
+    }
+
+    fn poll(&self) {
+        x86_64::irq::without(|| self.channel.service());
+    }
+}
+
+/// Returns the model of a drive from its identification data, where the two
+/// characters of each word are swapped.
+fn model(words: &[u16; 256]) -> String {
+    let bytes: Vec<u8> = words[27..47]
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .collect();
+    String::from_utf8_lossy(&bytes).trim().to_string()
+}
+
+/// Probe the given drive of the given channel, and returns it if it is an ATA
+/// disk supporting LBA or an ATAPI drive.
+unsafe fn probe(channel: &'static Channel, slave: bool, name: String) -> Option<Drive> {
+    let (mut target, words) = channel.identify(slave)?;
+    let capacity = match target.kind {
+        Kind::Ata { .. } => {
+            if words[49] & (1 << 9) == 0 {
+                log::warn!("ata: {} does not support LBA, ignored", name);
+                return None;
+            }
+            let lba48 = words[83] & (1 << 10) != 0;
+            target.kind = Kind::Ata { lba48 };
+            if lba48 {
+                words[100..104]
+                    .iter()
+                    .rev()
+                    .fold(0, |capacity, &word| capacity << 16 | u64::from(word))
+            } else {
+                u64::from(words[60]) | u64::from(words[61]) << 16
+            }
+        }
+        Kind::Atapi => channel.atapi_capacity(target),
+    };
+
+    log::info!("ata: {}: {} ({:?})", name, model(&words), target.kind);
+    Some(Drive {
+        name,
+        channel,
+        target,
+        capacity,
+    })
+}
+
+/// Handle an interrupt of the channel using the given IRQ.
+pub fn interrupt(irq: u8) {
+    if let Some(channel) = CHANNELS.iter().find(|channel| channel.irq == irq) {
+        channel.service();
+    }
+}
+
+/// Probe the drives of the legacy ATA channels, and register them as block
+/// devices. The interrupts of a channel are disabled while probing its drives.
+#[init]
+pub fn setup() {
+    for (index, channel) in CHANNELS.iter().enumerate() {
+        // A channel without any drive has a floating bus, read as all ones.
+        if unsafe { channel.control.read() } == 0xFF {
+            continue;
+        }
+
+        unsafe {
+            channel.control.write(CONTROL_NIEN);
+        }
+        let drives = [false, true].map(|slave| {
+            let letter = char::from(b"abcd"[index * 2 + usize::from(slave)]);
+            unsafe { probe(channel, slave, format!("hd{letter}")) }
+        });
+        unsafe {
+            channel.control.write(0);
+        }
+        if let Err(error) = x86_64::irq::request(channel.irq, interrupt, Affinity::Bsp) {
+            log::warn!("ata: failed to request IRQ {}: {:?}", channel.irq, error);
+        }
+
+        let major = [PRIMARY_MAJOR, SECONDARY_MAJOR][index];
+        for drive in drives.into_iter().flatten() {
+            let minor = if drive.target.slave { SLAVE_MINOR } else { 0 };
+            let id = Identifier { major, minor };
+            let name = drive.name.clone();
+            if let Err(error) = super::register(id, Arc::new(drive)) {
+                log::warn!("ata: failed to register {}: {:?}", name, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! The buffer cache. It keeps in memory the blocks recently read from the block
+//! devices, so that filesystems can read and modify their metadata without going
+//! to the device every time. Modified buffers are marked dirty and written back to
+//! the device later, either explicitly with [`sync_device`] or by the writeback
+//! thread once they have been dirty for too long.
+//!
+//! A buffer is identified by its device, its size and its block number. The size
+//! of a block is chosen by the user of the cache (usually a filesystem) and must
+//! be a multiple of the sector size of the device.
+use super::{BlockDevice, IoError};
+use crate::{
+    device::Identifier,
+    time::{units::Nanosecond, uptime_fast},
+    vfs::lru::Lru,
+};
+use alloc::{collections::BTreeMap, vec};
+
+/// The key of a buffer in the cache: its device, its size and its block number.
+type Key = (Identifier, usize, u64);
+
+/// The buffer cache.
+static CACHE: Spinlock<Cache> = Spinlock::new(Cache::new());
+
+/// A block of a block device, cached in memory.
+#[derive(Debug)]
+pub struct Buffer {
+    /// The device to which the block belongs.
+    device: Identifier,
+
+    /// The number of the block, in units of `size` bytes.
+    block: u64,
+
+    /// The size of the block in bytes.
+    size: usize,
+
+    /// The data of the block.
+    data: Spinlock<Box<[u8]>>,
+
+    /// The time at which the buffer was first dirtied since its last writeback,
+    /// or `None` if the buffer is clean.
+    dirty: Spinlock<Option<Nanosecond>>,
+}
+
+impl Buffer {
+    /// The device to which this buffer belongs.
+    #[must_use]
+    pub const fn device(&self) -> Identifier {
+        self.device
+    }
+
+    /// The block number of this buffer, in units of its size.
+    #[must_use]
+    pub const fn block(&self) -> u64 {
+        self.block
+    }
+
+    /// The size of this buffer in bytes.
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Lock the data of this buffer and return it. If the data is modified, the
+    /// buffer must be marked dirty with [`Buffer::mark_dirty`] for the changes
+    /// to be written back to the device.
+    pub fn data(&self) -> MutexGuard<'_, Box<[u8]>> {
+        self.data.lock()
+    }
+
+    /// Mark this buffer as dirty, meaning that its data has been modified and must
+    /// be written back to the device. If it is already dirty, the time at which it
+    /// was first dirtied is kept.
+    pub fn mark_dirty(&self) {
+        self.dirty.lock().get_or_insert_with(uptime_fast);
+    }
+
+    /// Returns `true` if this buffer has been modified since its last writeback.
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.lock().is_some()
+    }
+
+    /// Write this buffer to the device if it is dirty. The buffer is marked clean
+    /// before being written, so that modifications made during the write are not
+    /// lost. If the write fails, the buffer is marked dirty again.
+    ///
+    /// # Errors
+    /// Returns an error if the device is not registered anymore, or if the write
+    /// failed.
+    pub fn sync(&self) -> Result<(), IoError> {
+        let Some(dirtied) = self.dirty.lock().take() else {
+            return Ok(());
+        };
+
+        let result = super::get(self.device)
+            .ok_or(IoError::NoSuchDevice)
+            .and_then(|device| {
+                let sector = self.first_sector(device.as_ref());
+                super::write(&device, sector, self.data.lock().to_vec())
+            });
+
+        if result.is_err() {
+            self.dirty.lock().get_or_insert(dirtied);
+        }
+        result
+    }
+
+    /// Returns the first sector of the device covered by this buffer.
+    fn first_sector(&self, device: &dyn BlockDevice) -> u64 {
+        self.block * (self.size / device.sector_size()) as u64
+    }
+}
+
+/// The buffers of the cache, and their usage order.
+struct Cache {
+    buffers: BTreeMap<Key, Arc<Buffer>>,
+    lru: Lru<Key>,
+}
+
+impl Cache {
+    const fn new() -> Self {
+        Self {
+            buffers: BTreeMap::new(),
+            lru: Lru::new(),
+        }
+    }
+
+    /// Returns the buffer with the given key if it is cached, and mark it as the
+    /// most recently used one.
+    fn get(&mut self, key: Key) -> Option<Arc<Buffer>> {
+        let buffer = self.buffers.get(&key).cloned()?;
+        self.lru.touch(key);
+        Some(buffer)
+    }
+
+    /// Insert the given buffer in the cache, unless another buffer with the same
+    /// key was inserted in the meantime, in which case that buffer is returned.
+    fn insert(&mut self, buffer: Buffer) -> Arc<Buffer> {
+        let key = (buffer.device, buffer.size, buffer.block);
+        let buffer = Arc::clone(self.buffers.entry(key).or_insert_with(|| Arc::new(buffer)));
+        self.lru.touch(key);
+        buffer
+    }
+
+    /// Returns the buffers matching the given predicate.
+    fn filter(&self, predicate: impl Fn(&Buffer) -> bool) -> Vec<Arc<Buffer>> {
+        self.buffers
+            .values()
+            .filter(|buffer| predicate(buffer))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Check that a block of the given size can be cached for the given device.
+fn check(device: &dyn BlockDevice, block: u64, size: usize) -> Result<(), IoError> {
+    if size == 0 || size % device.sector_size() != 0 {
+        return Err(IoError::Unaligned);
+    }
+    let sectors = (size / device.sector_size()) as u64;
+    match block
+        .checked_mul(sectors)
+        .and_then(|s| s.checked_add(sectors))
+    {
+        Some(end) if end <= device.capacity() => Ok(()),
+        _ => Err(IoError::OutOfRange),
+    }
+}
+
+/// Returns the buffer containing the given block of the given device, reading it
+/// from the device if it is not cached yet.
+///
+/// # Errors
+/// Returns an error if the device is not registered, if the block is out of the
+/// device or if the read failed.
+pub fn read(id: Identifier, block: u64, size: usize) -> Result<Arc<Buffer>, IoError> {
+    if let Some(buffer) = CACHE.lock().get((id, size, block)) {
+        return Ok(buffer);
+    }
+
+    let device = super::get(id).ok_or(IoError::NoSuchDevice)?;
+    check(device.as_ref(), block, size)?;
+
+    // The block is read without holding the cache lock, since it may sleep. If
+    // another task read the same block in the meantime, its buffer is kept and
+    // ours is discarded.
+    let sector = block * (size / device.sector_size()) as u64;
+    let data = super::read(&device, sector, size)?;
+    Ok(CACHE.lock().insert(Buffer {
+        device: id,
+        block,
+        size,
+        data: Spinlock::new(data.into_boxed_slice()),
+        dirty: Spinlock::new(None),
+    }))
+}
+
+/// Returns the buffer containing the given block of the given device, filled
+/// with zeroes instead of being read from the device. This is useful when the whole block is about to be overwritten,
+/// for example when a filesystem allocates a new block.
+///
+/// # Errors
+/// Returns an error if the device is not registered or if the block is out of
+/// the device.
+pub fn get_zeroed(id: Identifier, block: u64, size: usize) -> Result<Arc<Buffer>, IoError> {
+    let mut cache = CACHE.lock();
+    if let Some(buffer) = cache.get((id, size, block)) {
+        buffer.data().fill(0);
+        return Ok(buffer);
+    }
+
+    let device = super::get(id).ok_or(IoError::NoSuchDevice)?;
+    check(device.as_ref(), block, size)?;
+    Ok(cache.insert(Buffer {
+        device: id,
+        block,
+        size,
+        data: Spinlock::new(vec![0; size].into_boxed_slice()),
+        dirty: Spinlock::new(None),
+    }))
+}
+
+/// Write all the dirty buffers of the given device, then flush the volatile
+/// write cache of the device. All buffers are written even if some of them
+/// fail, and the first error is returned.
+///
+/// # Errors
+/// Returns an error if a buffer could not be written or if the flush failed.
+pub fn sync_device(id: Identifier) -> Result<(), IoError> {
+    let dirty = CACHE
+        .lock()
+        .filter(|buffer| buffer.device == id && buffer.is_dirty());
+
+    let result = dirty
+        .iter()
+        .map(|buffer| buffer.sync())
+        .fold(Ok(()), Result::and);
+    match super::get(id) {
+        Some(device) => result.and(super::flush(&device)),
+        None => result,
+    }
+}
+
+/// Write the dirty buffers of all devices, and flush the write cache of every
+/// device. Errors are logged but not reported to the caller.
+pub fn sync_all() {
+    for (id, _) in super::devices() {
+        if let Err(error) = sync_device(id) {
+            log::warn!(
+                "block: failed to sync device {}:{}: {error:?}",
+                id.major,
+                id.minor
+            );
+        }
+    }
+}
+
+/// Write back the buffers that have been dirty for longer than the given age.
+/// Errors are logged but not reported to the caller, and the buffers that failed
+/// to be written stay dirty.
+pub fn sync_expired(age: Nanosecond) {
+    let now = uptime_fast();
+    let expired = CACHE.lock().filter(|buffer| {
+        buffer
+            .dirty
+            .lock()
+            .map_or(false, |dirtied| now.saturated_sub(dirtied) >= age)
+    });
+
+    for buffer in expired {
+        if let Err(error) = buffer.sync() {
+            log::warn!(
+                "block: failed to write block {} of device {}:{}: {error:?}",
+                buffer.block,
+                buffer.device.major,
+                buffer.device.minor
+            );
+        }
+    }
+}
+
+/// Remove all the buffers of the given device from the cache, without writing
+/// them back. This is used when a device is removed.
+pub fn invalidate(id: Identifier) {
+    let mut cache = CACHE.lock();
+    let keys = cache
+        .buffers
+        .keys()
+        .filter(|(device, _, _)| *device == id)
+        .copied()
+        .collect::<Vec<_>>();
+
+    for key in keys {
+        cache.buffers.remove(&key);
+        cache.lru.remove(&key);
+    }
+}
+
+/// Evict at most `count` unused and clean buffers from the cache, starting with
+/// the least recently used ones, and return the number of evicted buffers. This
+/// is the scan function of the buffer cache shrinker.
+pub fn shrink(count: usize) -> usize {
+    let mut cache = CACHE.lock();
+    let victims = cache
+        .lru
+        .oldest()
+        .filter(|key| {
+            cache.buffers.get(key).map_or(true, |buffer| {
+                Arc::strong_count(buffer) == 1 && !buffer.is_dirty()
+            })
+        })
+        .take(count)
+        .copied()
+        .collect::<Vec<_>>();
+
+    for key in &victims {
+        cache.buffers.remove(key);
+        cache.lru.remove(key);
+    }
+    victims.len()
+}
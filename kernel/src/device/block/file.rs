@@ -0,0 +1,174 @@
+//! The file operations of block device nodes. Reads and writes go through the
+//! buffer cache with buffers of the size of a sector, so that any byte range of
+//! the device can be read or written. Written data is only marked dirty in the
+//! cache, and reaches the device when the device is synchronized.
+use super::{cache, BlockDevice};
+use crate::{
+    device::Identifier,
+    user,
+    vfs::{
+        self,
+        file::{File, FileOperation, IoctlError, Offset, ReadError, SeekError, Whence, WriteError},
+        inode::Kind,
+    },
+};
+
+/// The operation table of an opened block device node.
+pub static FILE_OPS: FileOperation = FileOperation {
+    write,
+    read,
+    seek,
+    poll: vfs::poll::always_ready,
+    ioctl,
+    mmap: vfs::file::no_mmap,
+};
+
+/// Get the read-only flag of the device, as an `int`.
+pub const BLKROGET: usize = 0x125E;
+
+/// Get the size of the device in 512-byte sectors, as an `unsigned long`.
+pub const BLKGETSIZE: usize = 0x1260;
+
+/// Write back the dirty buffers of the device and drop its buffers from the
+/// buffer cache.
+pub const BLKFLSBUF: usize = 0x1261;
+
+/// Get the sector size of the device, as an `int`.
+pub const BLKSSZGET: usize = 0x1268;
+
+/// Get the size of the device in bytes, as an `u64`.
+pub const BLKGETSIZE64: usize = 0x8008_1272;
+
+/// Returns the identifier of the device opened by the given file, and the device
+/// itself, or `None` if the device has been unregistered since the node was
+/// opened.
+fn device(file: &File) -> Option<(Identifier, Arc<dyn BlockDevice>)> {
+    match file.dentry.as_ref()?.inode().kind {
+        Kind::BlockDevice(id) => super::get(id).map(|device| (id, device)),
+        _ => None,
+    }
+}
+
+/// Returns the size of the given device in bytes.
+#[allow(clippy::cast_possible_truncation)]
+fn size(device: &dyn BlockDevice) -> usize {
+    (device.capacity() as usize).saturating_mul(device.sector_size())
+}
+
+/// Read from the device at the given offset into the given buffer, and return
+/// the number of bytes read. Reading stops at the end of the device.
+///
+/// # Errors
+/// Returns `ReadError::IoError` if the device does not exist anymore, or if a
+/// sector could not be read.
+fn read(file: &File, buf: &mut [u8], offset: Offset) -> Result<usize, ReadError> {
+    let (id, device) = device(file).ok_or(ReadError::IoError)?;
+    let sector = device.sector_size();
+    let len = core::cmp::min(buf.len(), size(device.as_ref()).saturating_sub(offset.0));
+
+    let mut done = 0;
+    while done < len {
+        let position = offset.0 + done;
+        let start = position % sector;
+        let count = core::cmp::min(sector - start, len - done);
+        let buffer =
+            cache::read(id, (position / sector) as u64, sector).map_err(|_| ReadError::IoError)?;
+
+        buf[done..done + count].copy_from_slice(&buffer.data()[start..start + count]);
+        done += count;
+    }
+    Ok(len)
+}
+
+/// Write the given buffer to the device at the given offset, and return the
+/// number of bytes written. Sectors entirely overwritten are not read from the
+/// device beforehand.
+///
+/// # Errors
+/// - `WriteError::NotImplemented`: The device is read-only.
+/// - `WriteError::NoSpace`: The write goes beyond the end of the device.
+/// - `WriteError::IoError`: The device does not exist anymore, or a sector that
+///   is partially overwritten could not be read.
+fn write(file: &File, buf: &[u8], offset: Offset) -> Result<usize, WriteError> {
+    let (id, device) = device(file).ok_or(WriteError::IoError)?;
+    let sector = device.sector_size();
+    if device.read_only() {
+        return Err(WriteError::NotImplemented);
+    }
+    if offset.0.saturating_add(buf.len()) > size(device.as_ref()) {
+        return Err(WriteError::NoSpace);
+    }
+
+    let mut done = 0;
+    while done < buf.len() {
+        let position = offset.0 + done;
+        let block = (position / sector) as u64;
+        let start = position % sector;
+        let count = core::cmp::min(sector - start, buf.len() - done);
+        let buffer = if count == sector {
+            cache::get_zeroed(id, block, sector)
+        } else {
+            cache::read(id, block, sector)
+        }
+        .map_err(|_| WriteError::IoError)?;
+
+        buffer.data()[start..start + count].copy_from_slice(&buf[done..done + count]);
+        buffer.mark_dirty();
+        done += count;
+    }
+    Ok(buf.len())
+}
+
+/// Seek into the device and return the new offset. Seeking beyond the end of the
+/// device is allowed, but reads will return nothing and writes will fail.
+///
+/// # Errors
+/// - `SeekError::Overflow`: The new offset overflows.
+/// - `SeekError::NotSeekable`: The device does not exist anymore.
+fn seek(file: &File, offset: isize, whence: Whence) -> Result<Offset, SeekError> {
+    let base = match whence {
+        Whence::Start => 0,
+        Whence::Current => file.state.lock().offset.0,
+        Whence::End => size(device(file).ok_or(SeekError::NotSeekable)?.1.as_ref()),
+    };
+
+    base.checked_add_signed(offset)
+        .map(Offset)
+        .ok_or(SeekError::Overflow)
+}
+
+/// Handle the ioctl requests common to all block devices, and forward the other
+/// requests to the driver of the device.
+///
+/// # Errors
+/// See [`IoctlError`] for the possible errors.
+fn ioctl(file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+    let (id, device) = device(file).ok_or(IoctlError::NoSuchDevice)?;
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    match request {
+        BLKROGET => put(arg, &i32::from(device.read_only())),
+        BLKGETSIZE => put(arg, &(size(device.as_ref()) / 512)),
+        BLKSSZGET => put(arg, &(device.sector_size() as i32)),
+        BLKGETSIZE64 => put(arg, &(size(device.as_ref()) as u64)),
+        BLKFLSBUF => {
+            cache::sync_device(id).map_err(|_| IoctlError::IoError)?;
+            cache::invalidate(id);
+            Ok(0)
+        }
+        _ => device.ioctl(request, arg),
+    }
+}
+
+/// Write the given value at the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not a valid userland
+/// address.
+fn put<T: Copy>(arg: usize, value: &T) -> Result<usize, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    unsafe {
+        user::Object::write(&ptr, value);
+    }
+    Ok(0)
+}
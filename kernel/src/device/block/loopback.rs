@@ -0,0 +1,297 @@
+//! Loop devices. A loop device exposes a regular file as a block device, which
+//! allows to mount a filesystem image stored in a file. A loop device is unbound
+//! when created, and has a capacity of zero: a file is bound to it with the
+//! [`LOOP_SET_FD`] ioctl on its node, and unbound with [`LOOP_CLR_FD`].
+//!
+//! Requests are handled synchronously in the context of the task that submits
+//! them, by reading or writing the bound file through its operation table.
+use super::{cache, BlockDevice, IoError, Operation, Request};
+use crate::{
+    device::Identifier,
+    user::scheduler::{Scheduler, SCHEDULER},
+    vfs::{
+        self,
+        file::{File, IoctlError, Offset, OpenFlags},
+        inode::Kind,
+    },
+};
+use alloc::format;
+
+/// The major number of loop devices, the same as on Linux.
+pub const MAJOR: u32 = 7;
+
+/// Bind the file referred by the file descriptor given as argument to the loop
+/// device. The device is read-only if the file was not opened for writing.
+pub const LOOP_SET_FD: usize = 0x4C00;
+
+/// Unbind the file bound to the loop device, after writing back the dirty
+/// buffers of the device.
+pub const LOOP_CLR_FD: usize = 0x4C01;
+
+/// Update the capacity of the loop device after the bound file was resized.
+pub const LOOP_SET_CAPACITY: usize = 0x4C07;
+
+/// The number of loop devices created at boot.
+const COUNT: u32 = 8;
+
+/// The size of a sector of a loop device.
+const SECTOR_SIZE: usize = 512;
+
+/// A loop device.
+pub struct Loop {
+    /// The identifier of the loop device.
+    id: Identifier,
+
+    /// The name of the loop device.
+    name: String,
+
+    /// The file bound to the loop device, if any.
+    backing: Spinlock<Option<Backing>>,
+}
+
+/// A file bound to a loop device.
+struct Backing {
+    /// The bound file.
+    file: Arc<File>,
+
+    /// The number of sectors of the loop device, computed from the size of the
+    /// file when it was bound. A trailing partial sector is ignored.
+    capacity: u64,
+
+    /// Whether the loop device is read-only, because the file was not opened for
+    /// writing.
+    read_only: bool,
+}
+
+impl Backing {
+    /// Create a backing for the given file, or return `None` if the file is not
+    /// a regular file.
+    fn new(file: Arc<File>) -> Option<Self> {
+        let dentry = file.dentry.as_ref()?;
+        if dentry.inode().kind != Kind::File {
+            return None;
+        }
+
+        let size = dentry.inode().metadata.lock().size;
+        Some(Self {
+            capacity: (size / SECTOR_SIZE) as u64,
+            read_only: !file.open_flags.contains(OpenFlags::WRITE),
+            file,
+        })
+    }
+}
+
+impl Loop {
+    /// Create a new unbound loop device.
+    #[must_use]
+    pub fn new(id: Identifier, name: String) -> Self {
+        Self {
+            id,
+            name,
+            backing: Spinlock::new(None),
+        }
+    }
+
+    /// Bind the file referred by the given file descriptor of the current task to
+    /// this loop device.
+    ///
+    /// # Errors
+    /// - `IoctlError::Busy`: A file is already bound to this loop device.
+    /// - `IoctlError::BadFileDescriptor`: The file descriptor is invalid.
+    /// - `IoctlError::InvalidArgument`: The file is not a regular file.
+    fn set_fd(&self, fd: usize) -> Result<usize, IoctlError> {
+        let file = SCHEDULER
+            .current_task()
+            .files()
+            .lock()
+            .get(vfs::fd::Descriptor(fd))
+            .cloned()
+            .ok_or(IoctlError::BadFileDescriptor)?;
+
+        let mut backing = self.backing.lock();
+        if backing.is_some() {
+            return Err(IoctlError::Busy);
+        }
+
+        let bound = Backing::new(file).ok_or(IoctlError::InvalidArgument)?;
+        log::info!(
+            "loop: {} bound to a file of {} sectors{}",
+            self.name,
+            bound.capacity,
+            if bound.read_only { " (read-only)" } else { "" }
+        );
+
+        // Drop the buffers that may remain from a previous binding, since they
+        // do not correspond to the content of the new file.
+        cache::invalidate(self.id);
+        *backing = Some(bound);
+        Ok(0)
+    }
+
+    /// Unbind the file bound to this loop device, after writing back the dirty
+    /// buffers of the device to the file.
+    ///
+    /// # Errors
+    /// - `IoctlError::InvalidArgument`: No file is bound to this loop device.
+    /// - `IoctlError::IoError`: The dirty buffers could not be written back.
+    fn clear_fd(&self) -> Result<usize, IoctlError> {
+        if self.backing.lock().is_none() {
+            return Err(IoctlError::InvalidArgument);
+        }
+
+        cache::sync_device(self.id).map_err(|_| IoctlError::IoError)?;
+        cache::invalidate(self.id);
+        *self.backing.lock() = None;
+        log::info!("loop: {} unbound", self.name);
+        Ok(0)
+    }
+
+    /// Update the capacity of this loop device from the current size of the bound
+    /// file.
+    ///
+    /// # Errors
+    /// Returns `IoctlError::InvalidArgument` if no file is bound to this loop device.
+    fn set_capacity(&self) -> Result<usize, IoctlError> {
+        let mut backing = self.backing.lock();
+        let backing = backing.as_mut().ok_or(IoctlError::InvalidArgument)?;
+        let size = backing
+            .file
+            .dentry
+            .as_ref()
+            .expect("Loop device bound to a file without dentry")
+            .inode()
+            .metadata
+            .lock()
+            .size;
+        backing.capacity = (size / SECTOR_SIZE) as u64;
+        Ok(0)
+    }
+
+    /// Read the bound file at the given offset into the given buffer. The part of
+    /// the buffer beyond the end of the file, if it was truncated after being
+    /// bound, is filled with zeroes.
+    fn read(file: &File, offset: usize, buffer: &mut [u8]) -> Result<(), IoError> {
+        let operation = file.as_file().ok_or(IoError::DeviceError)?;
+        let size = file
+            .dentry
+            .as_ref()
+            .map_or(0, |dentry| dentry.inode().metadata.lock().size);
+        let available = core::cmp::min(buffer.len(), size.saturating_sub(offset));
+        buffer[available..].fill(0);
+
+        let mut done = 0;
+        while done < available {
+            let count = operation
+                .read(file, &mut buffer[done..available], Offset(offset + done))
+                .map_err(|_| IoError::DeviceError)?;
+            if count == 0 {
+                buffer[done..available].fill(0);
+                break;
+            }
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Write the given buffer to the bound file at the given offset.
+    fn write(file: &File, offset: usize, buffer: &[u8]) -> Result<(), IoError> {
+        let operation = file.as_file().ok_or(IoError::DeviceError)?;
+        let mut done = 0;
+        while done < buffer.len() {
+            done += operation
+                .write(file, &buffer[done..], Offset(offset + done))
+                .ok()
+                .filter(|&count| count > 0)
+                .ok_or(IoError::DeviceError)?;
+        }
+
+        if let Some(dentry) = &file.dentry {
+            dentry.dirtying_inode();
+        }
+        Ok(())
+    }
+
+    /// Write back the inode of the bound file to its filesystem.
+    fn flush(file: &File) -> Result<(), IoError> {
+        let Some(dentry) = &file.dentry else {
+            return Ok(());
+        };
+        let inode = dentry.inode();
+        inode
+            .superblock
+            .upgrade()
+            .ok_or(IoError::DeviceError)?
+            .sync_inode(inode)
+            .map_err(|_| IoError::DeviceError)
+    }
+}
+
+impl BlockDevice for Loop {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn capacity(&self) -> u64 {
+        self.backing
+            .lock()
+            .as_ref()
+            .map_or(0, |backing| backing.capacity)
+    }
+
+    fn read_only(&self) -> bool {
+        self.backing
+            .lock()
+            .as_ref()
+            .map_or(false, |backing| backing.read_only)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn submit(&self, request: Arc<Request>) {
+        // The file is cloned to avoid holding the lock during the I/O, which may
+        // sleep and would prevent the device from being unbound meanwhile.
+        let file = self
+            .backing
+            .lock()
+            .as_ref()
+            .map(|backing| Arc::clone(&backing.file));
+
+        let result = file.ok_or(IoError::NoSuchDevice).and_then(|file| {
+            let offset = request.sector() as usize * SECTOR_SIZE;
+            match request.operation() {
+                Operation::Read => Self::read(&file, offset, &mut request.buffer()),
+                Operation::Write => Self::write(&file, offset, &request.buffer()),
+                Operation::Flush => Self::flush(&file),
+            }
+        });
+        request.complete(result);
+    }
+
+    fn ioctl(&self, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        match request {
+            LOOP_SET_FD => self.set_fd(arg),
+            LOOP_CLR_FD => self.clear_fd(),
+            LOOP_SET_CAPACITY => self.set_capacity(),
+            _ => Err(IoctlError::NotSupported),
+        }
+    }
+}
+
+/// Create and register the loop devices, which are unbound until a file is bound
+/// to them with the [`LOOP_SET_FD`] ioctl.
+#[init]
+pub fn setup() {
+    for minor in 0..COUNT {
+        let id = Identifier {
+            major: MAJOR,
+            minor,
+        };
+        let device = Loop::new(id, format!("loop{minor}"));
+        if let Err(error) = super::register(id, Arc::new(device)) {
+            log::warn!("loop: failed to register loop{}: {:?}", minor, error);
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use super::{node, Device, Identifier};
+use crate::{
+    mm::shrinker::{self, Shrinker},
+    user::{
+        scheduler::{Scheduler, SCHEDULER},
+        task::queue::WaitQueue,
+    },
+    vfs::file::IoctlError,
+};
+use alloc::{collections::BTreeMap, vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub mod ata;
+pub mod cache;
+pub mod file;
+pub mod loopback;
+pub mod partition;
+pub mod ram;
+pub mod virtio;
+
+/// The list of all registered block devices, indexed by their identifier.
+static DEVICES: Spinlock<BTreeMap<Identifier, Arc<dyn BlockDevice>>> =
+    Spinlock::new(BTreeMap::new());
+
+/// The shrinker of the buffer cache.
+static BUFFER_SHRINKER: Shrinker = Shrinker {
+    name: "buffer",
+    scan: cache::shrink,
+};
+
+/// A block device: a device that stores data in fixed-size sectors that can be
+/// read and written in any order, such as a disk or a partition.
+///
+/// Requests are asynchronous: [`BlockDevice::submit`] only queues the request,
+/// and the driver completes it later with [`Request::complete`], possibly from an
+/// interrupt handler. A driver can have several requests in flight, and can
+/// complete them in any order.
+pub trait BlockDevice: Send + Sync {
+    /// The name of the device, only used for debugging purposes.
+    fn name(&self) -> &str;
+
+    /// The size of a sector in bytes. It must be a power of two, and is usually
+    /// 512 bytes.
+    fn sector_size(&self) -> usize;
+
+    /// The number of sectors of the device.
+    fn capacity(&self) -> u64;
+
+    /// Returns `true` if the device cannot be written.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    /// Queue the given request. The request was already checked to be within the
+    /// bounds of the device, and its buffer is a multiple of the sector size. The
+    /// driver must eventually complete the request with [`Request::complete`].
+    fn submit(&self, request: Arc<Request>);
+
+    /// Make progress on the pending requests without relying on interrupts. This
+    /// is called when waiting for a request while no task is running yet, during
+    /// the kernel initialization, where the current context cannot sleep. Drivers
+    /// that complete requests in their interrupt handler must override it.
+    fn poll(&self) {}
+
+    /// Perform a driver-specific ioctl request on the device. This is called for
+    /// the requests that are not handled by the block layer itself.
+    ///
+    /// # Errors
+    /// Returns `IoctlError::NotSupported` by default.
+    fn ioctl(&self, _request: usize, _arg: usize) -> Result<usize, IoctlError> {
+        Err(IoctlError::NotSupported)
+    }
+}
+
+/// The operation performed by a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Read sectors from the device into the buffer of the request.
+    Read,
+
+    /// Write the buffer of the request to sectors of the device.
+    Write,
+
+    /// Make sure that all previously completed writes are stored on a persistent
+    /// medium, and not only in a volatile cache of the device.
+    Flush,
+}
+
+/// A request to a block device.
+pub struct Request {
+    /// The operation to perform.
+    operation: Operation,
+
+    /// The first sector affected by the request. It is shifted when the request
+    /// is forwarded to the device containing the one it was submitted to.
+    sector: AtomicU64,
+
+    /// The data of the request. Its length is a multiple of the sector size of
+    /// the device. For a read, it is filled by the driver, and for a write, it
+    /// contains the data to write.
+    buffer: Spinlock<Vec<u8>>,
+
+    /// The result of the request, or `None` if it is not completed yet.
+    result: Spinlock<Option<Result<(), IoError>>>,
+
+    /// The tasks waiting for the request to be completed.
+    waiters: WaitQueue,
+}
+
+impl Request {
+    /// Create a request to read `count` bytes starting at the given sector.
+    #[must_use]
+    pub fn read(sector: u64, count: usize) -> Arc<Self> {
+        Self::new(Operation::Read, sector, vec![0; count])
+    }
+
+    /// Create a request to write the given data starting at the given sector.
+    #[must_use]
+    pub fn write(sector: u64, data: Vec<u8>) -> Arc<Self> {
+        Self::new(Operation::Write, sector, data)
+    }
+
+    /// Create a request to flush the volatile write cache of the device.
+    #[must_use]
+    pub fn flush() -> Arc<Self> {
+        Self::new(Operation::Flush, 0, Vec::new())
+    }
+
+    fn new(operation: Operation, sector: u64, buffer: Vec<u8>) -> Arc<Self> {
+        Arc::new(Self {
+            operation,
+            sector: AtomicU64::new(sector),
+            buffer: Spinlock::new(buffer),
+            result: Spinlock::new(None),
+            waiters: WaitQueue::new(),
+        })
+    }
+
+    /// The operation performed by this request.
+    #[must_use]
+    pub const fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// The first sector affected by this request.
+    #[must_use]
+    pub fn sector(&self) -> u64 {
+        self.sector.load(Ordering::Relaxed)
+    }
+
+    /// Shift this request by the given number of sectors, before forwarding it
+    /// to the device that contains the device it was submitted to, like a disk
+    /// containing a partition. The request is completed by that device.
+    pub fn remap(&self, offset: u64) {
+        self.sector.fetch_add(offset, Ordering::Relaxed);
+    }
+
+    /// The number of bytes affected by this request.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    /// Returns `true` if this request does not affect any byte, which is the
+    /// case of flush requests.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lock and return the buffer of this request.
+    pub fn buffer(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.buffer.lock()
+    }
+
+    /// Take the buffer of this request, leaving an empty buffer in its place.
+    /// This is used to retrieve the data read once the request is completed.
+    #[must_use]
+    pub fn take_buffer(&self) -> Vec<u8> {
+        core::mem::take(&mut self.buffer.lock())
+    }
+
+    /// Returns the result of this request, or `None` if it is not completed yet.
+    #[must_use]
+    pub fn result(&self) -> Option<Result<(), IoError>> {
+        *self.result.lock()
+    }
+
+    /// Complete this request with the given result, and wake up the tasks waiting
+    /// for it. This can be called from an interrupt handler.
+    pub fn complete(&self, result: Result<(), IoError>) {
+        *self.result.lock() = Some(result);
+        self.waiters.wake_up_all();
+    }
+
+    /// Wait until this request submitted to the given device is completed, and
+    /// return its result. If no task is running yet, the device is polled until
+    /// the request is completed instead of putting the current task to sleep.
+    ///
+    /// # Errors
+    /// Returns the error reported by the driver when completing the request.
+    pub fn wait(&self, device: &dyn BlockDevice) -> Result<(), IoError> {
+        if SCHEDULER.try_current_task().is_none() {
+            loop {
+                if let Some(result) = self.result() {
+                    return result;
+                }
+                device.poll();
+                core::hint::spin_loop();
+            }
+        }
+
+        // The result is checked again after the task is added to the wait queue,
+        // so that a request completed in the meantime, maybe by the interrupt
+        // handler of another CPU, does not make the task sleep forever.
+        self.waiters.sleep_until(|| self.result())
+    }
+}
+
+impl core::fmt::Debug for Request {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Request")
+            .field("operation", &self.operation)
+            .field("sector", &self.sector())
+            .field("result", &self.result)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Check that the given request can be submitted to the given device, and submit
+/// it. If the request is invalid, it is completed with an error without being
+/// submitted to the device.
+///
+/// # Errors
+/// - `IoError::Unaligned`: The buffer of the request is not a multiple of the
+///   sector size of the device.
+/// - `IoError::OutOfRange`: The request goes beyond the end of the device.
+/// - `IoError::ReadOnly`: The request writes to a read-only device.
+pub fn submit(device: &Arc<dyn BlockDevice>, request: &Arc<Request>) -> Result<(), IoError> {
+    let len = request.len();
+    let sectors = (len / device.sector_size()) as u64;
+
+    let check = if len % device.sector_size() != 0 {
+        Err(IoError::Unaligned)
+    } else if request.sector().saturating_add(sectors) > device.capacity() {
+        Err(IoError::OutOfRange)
+    } else if request.operation == Operation::Write && device.read_only() {
+        Err(IoError::ReadOnly)
+    } else {
+        Ok(())
+    };
+
+    if let Err(error) = check {
+        request.complete(Err(error));
+        return Err(error);
+    }
+    device.submit(Arc::clone(request));
+    Ok(())
+}
+
+/// Read `count` bytes from the given device, starting at the given sector, and
+/// wait for the data to be read.
+///
+/// # Errors
+/// See [`submit`] and the errors reported by the driver.
+pub fn read(device: &Arc<dyn BlockDevice>, sector: u64, count: usize) -> Result<Vec<u8>, IoError> {
+    let request = Request::read(sector, count);
+    submit(device, &request)?;
+    request.wait(device.as_ref())?;
+    Ok(request.take_buffer())
+}
+
+/// Write the given data to the given device, starting at the given sector, and
+/// wait for the data to be written.
+///
+/// # Errors
+/// See [`submit`] and the errors reported by the driver.
+pub fn write(device: &Arc<dyn BlockDevice>, sector: u64, data: Vec<u8>) -> Result<(), IoError> {
+    let request = Request::write(sector, data);
+    submit(device, &request)?;
+    request.wait(device.as_ref())
+}
+
+/// Flush the volatile write cache of the given device, and wait for the flush to
+/// be completed.
+///
+/// # Errors
+/// Returns the error reported by the driver.
+pub fn flush(device: &Arc<dyn BlockDevice>) -> Result<(), IoError> {
+    let request = Request::flush();
+    submit(device, &request)?;
+    request.wait(device.as_ref())
+}
+
+/// Register a block device with the given identifier, and create a node for it
+/// in `/dev` named after the device. The partition table of the device is then
+/// scanned, and a block device is registered for each of its partitions.
+///
+/// # Errors
+/// Returns `RegisterError::AlreadyExists` if a device is already registered with
+/// the same identifier.
+pub fn register(id: Identifier, device: Arc<dyn BlockDevice>) -> Result<(), RegisterError> {
+    let device = add(id, device)?;
+    partition::scan(id, &device);
+    Ok(())
+}
+
+/// Register a block device with the given identifier and create its node in
+/// `/dev`, without scanning its partition table. The device is returned.
+///
+/// # Errors
+/// Returns `RegisterError::AlreadyExists` if a device is already registered with
+/// the same identifier.
+fn add(
+    id: Identifier,
+    device: Arc<dyn BlockDevice>,
+) -> Result<Arc<dyn BlockDevice>, RegisterError> {
+    let mut devices = DEVICES.lock();
+    if devices.contains_key(&id) {
+        return Err(RegisterError::AlreadyExists);
+    }
+
+    log::info!(
+        "block: {} ({}:{}): {} sectors of {} bytes",
+        device.name(),
+        id.major,
+        id.minor,
+        device.capacity(),
+        device.sector_size()
+    );
+    devices.insert(id, Arc::clone(&device));
+    drop(devices);
+
+    if let Err(error) = node::create(device.name(), Device::Block(id)) {
+        log::warn!("block: failed to create /dev/{}: {:?}", device.name(), error);
+    }
+    Ok(device)
+}
+
+/// Unregister the block device with the given identifier and its partitions, and
+/// return it. The dirty buffers of the device are written back before it is
+/// removed from the buffer cache, and its node in `/dev` is removed.
+pub fn unregister(id: Identifier) -> Option<Arc<dyn BlockDevice>> {
+    partition::remove(id);
+    if let Err(error) = cache::sync_device(id) {
+        log::warn!(
+            "block: failed to sync device {}:{}: {error:?}",
+            id.major,
+            id.minor
+        );
+    }
+    cache::invalidate(id);
+    let device = DEVICES.lock().remove(&id)?;
+    if let Err(error) = node::remove(device.name()) {
+        log::warn!("block: failed to remove /dev/{}: {:?}", device.name(), error);
+    }
+    Some(device)
+}
+
+/// Returns the block device with the given identifier, if any.
+#[must_use]
+pub fn get(id: Identifier) -> Option<Arc<dyn BlockDevice>> {
+    DEVICES.lock().get(&id).cloned()
+}
+
+/// Returns all the registered block devices with their identifier.
+#[must_use]
+pub fn devices() -> Vec<(Identifier, Arc<dyn BlockDevice>)> {
+    DEVICES
+        .lock()
+        .iter()
+        .map(|(id, device)| (*id, Arc::clone(device)))
+        .collect()
+}
+
+/// Setup the block device layer.
+#[init]
+pub fn setup() {
+    shrinker::register(&BUFFER_SHRINKER);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IoError {
+    /// There is no block device with the given identifier.
+    NoSuchDevice,
+
+    /// The request goes beyond the end of the device.
+    OutOfRange,
+
+    /// The size of the request is not a multiple of the sector size.
+    Unaligned,
+
+    /// The device cannot be written.
+    ReadOnly,
+
+    /// The device reported an error while processing the request.
+    DeviceError,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterError {
+    /// A device is already registered with the same identifier.
+    AlreadyExists,
+}
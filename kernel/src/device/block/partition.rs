@@ -0,0 +1,468 @@
+//! Partition tables. When a block device is registered, its first sector is read
+//! to find a partition table, and each partition found is registered as a block
+//! device of its own: a window onto a range of sectors of its parent, to which
+//! its requests are forwarded.
+//!
+//! Two formats are supported:
+//! - The MBR, with its four primary partitions, and the logical partitions of
+//!   an extended partition, chained by the extended boot records (EBR) stored
+//!   before each of them. Logical partitions are numbered from 5, like on Linux.
+//! - The GUID partition table (GPT), announced by a protective MBR. Its header
+//!   and its partition entries are protected by CRC32 checksums, and a backup of
+//!   both is stored at the end of the device, used if the primary copy is
+//!   corrupted.
+//!
+//! Partitions are named after their parent followed by their number, with a `p`
+//! in between if the name of the parent ends with a digit (`ram0p1`), and are
+//! unregistered with their parent.
+use super::{BlockDevice, IoError, Request};
+use crate::device::Identifier;
+use alloc::{collections::BTreeMap, format};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The major number of partitions, the same as the extended block devices on
+/// Linux. Their minor number is allocated when they are registered.
+pub const MAJOR: u32 = 259;
+
+/// The size of a MBR or an EBR, at the start of their sector.
+const MBR_SIZE: usize = 512;
+
+/// The offset of the partition entries in a MBR, the size of an entry, and the
+/// offset of the signature.
+const MBR_ENTRIES: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_SIGNATURE: usize = 510;
+
+/// The type of an unused MBR entry.
+const MBR_EMPTY: u8 = 0x00;
+
+/// The types of an extended partition (CHS, LBA and Linux).
+const MBR_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+/// The type of the partition of a protective MBR, covering a GPT disk.
+const MBR_PROTECTIVE: u8 = 0xEE;
+
+/// The maximal number of logical partitions, to avoid looping on a corrupted
+/// chain of extended boot records.
+const MAX_LOGICAL: usize = 64;
+
+/// The signature at the start of a GPT header.
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// The size of the fields of a GPT header covered by its checksum in the first
+/// revision, which is the minimal size of a header.
+const GPT_HEADER_SIZE: usize = 92;
+
+/// The size of the fields of a GPT partition entry, which is the minimal size
+/// of an entry.
+const GPT_ENTRY_SIZE: usize = 128;
+
+/// The maximal number of GPT partition entries. Partitioning tools create 128
+/// entries.
+const MAX_GPT_ENTRIES: usize = 1024;
+
+/// The maximal size of a GPT partition entry, and of the whole partition table,
+/// in bytes. Larger values are only found on corrupted disks, and would make
+/// the table too large to be read at once.
+const MAX_GPT_ENTRY_SIZE: usize = 4096;
+const MAX_GPT_TABLE_SIZE: usize = 1024 * 1024;
+
+/// The next minor number given to a partition.
+static NEXT_MINOR: AtomicU32 = AtomicU32::new(0);
+
+/// The identifiers of the partitions registered for each device.
+static PARTITIONS: Spinlock<BTreeMap<Identifier, Vec<Identifier>>> = Spinlock::new(BTreeMap::new());
+
+/// A partition of a block device.
+pub struct Partition {
+    /// The name of the partition.
+    name: String,
+
+    /// The device containing the partition.
+    parent: Arc<dyn BlockDevice>,
+
+    /// The first sector of the partition on its parent.
+    start: u64,
+
+    /// The number of sectors of the partition.
+    length: u64,
+}
+
+impl BlockDevice for Partition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.parent.sector_size()
+    }
+
+    fn capacity(&self) -> u64 {
+        self.length
+    }
+
+    fn read_only(&self) -> bool {
+        self.parent.read_only()
+    }
+
+    /// The request is shifted to the sectors of the partition on its parent and
+    /// submitted to it, which completes the request. If the parent rejects it,
+    /// the request is already completed with the error.
+    fn submit(&self, request: Arc<Request>) {
+        request.remap(self.start);
+        let _ = super::submit(&self.parent, &request);
+    }
+
+    fn poll(&self) {
+        self.parent.poll();
+    }
+}
+
+/// A partition found in a partition table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Entry {
+    /// The number of the partition, starting at 1.
+    number: usize,
+
+    /// The first sector and the number of sectors of the partition.
+    start: u64,
+    length: u64,
+}
+
+/// Scan the partition table of the device with the given identifier, and register
+/// a block device for each of its partitions. Nothing is registered if the device
+/// has no partition table, or if it could not be read.
+pub fn scan(parent: Identifier, device: &Arc<dyn BlockDevice>) {
+    let entries = match read_table(device) {
+        Ok(entries) => entries,
+        Err(error) => {
+            log::warn!(
+                "partition: failed to read the partition table of {}: {:?}",
+                device.name(),
+                error
+            );
+            return;
+        }
+    };
+
+    let mut partitions = Vec::new();
+    for entry in entries {
+        let end = entry.start.checked_add(entry.length);
+        if entry.length == 0 || end.map_or(true, |end| end > device.capacity()) {
+            log::warn!(
+                "partition: {} partition {} is out of the device, ignored",
+                device.name(),
+                entry.number
+            );
+            continue;
+        }
+
+        let name = if device.name().ends_with(|c: char| c.is_ascii_digit()) {
+            format!("{}p{}", device.name(), entry.number)
+        } else {
+            format!("{}{}", device.name(), entry.number)
+        };
+        let id = Identifier {
+            major: MAJOR,
+            minor: NEXT_MINOR.fetch_add(1, Ordering::Relaxed),
+        };
+        let partition = Partition {
+            name,
+            parent: Arc::clone(device),
+            start: entry.start,
+            length: entry.length,
+        };
+        match super::add(id, Arc::new(partition)) {
+            Ok(_) => partitions.push(id),
+            Err(error) => log::warn!("partition: failed to register a partition: {:?}", error),
+        }
+    }
+
+    if !partitions.is_empty() {
+        PARTITIONS.lock().insert(parent, partitions);
+    }
+}
+
+/// Unregister the partitions of the device with the given identifier, which is
+/// being unregistered.
+pub fn remove(parent: Identifier) {
+    let partitions = PARTITIONS.lock().remove(&parent).unwrap_or_default();
+    for id in partitions {
+        super::unregister(id);
+    }
+}
+
+/// Read the partition table of the given device, and return its partitions.
+///
+/// # Errors
+/// Returns an error if a sector of the partition table could not be read.
+fn read_table(device: &Arc<dyn BlockDevice>) -> Result<Vec<Entry>, IoError> {
+    if device.capacity() == 0 || device.sector_size() < MBR_SIZE {
+        return Ok(Vec::new());
+    }
+    let Some(mbr) = read_mbr(device, 0)? else {
+        return Ok(Vec::new());
+    };
+
+    if mbr.iter().any(|entry| entry.kind == MBR_PROTECTIVE) {
+        return read_gpt(device);
+    }
+
+    let mut entries = Vec::new();
+    for (index, entry) in mbr.iter().enumerate() {
+        if entry.kind == MBR_EMPTY || entry.length == 0 {
+            continue;
+        }
+        if MBR_EXTENDED.contains(&entry.kind) {
+            read_logical(device, entry.start, &mut entries)?;
+        } else {
+            entries.push(Entry {
+                number: index + 1,
+                start: entry.start,
+                length: entry.length,
+            });
+        }
+    }
+    log::info!(
+        "partition: {}: MBR with {} partitions",
+        device.name(),
+        entries.len()
+    );
+    Ok(entries)
+}
+
+/// An entry of a MBR or of an EBR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct MbrEntry {
+    /// The type of the partition.
+    kind: u8,
+
+    /// The first sector of the partition, relative to the sector given by the
+    /// kind of table, and its number of sectors.
+    start: u64,
+    length: u64,
+}
+
+/// Read the MBR or the EBR stored in the given sector, and return its entries,
+/// or `None` if the sector does not contain a valid table. A table is rejected
+/// if its boot indicators are invalid or if all its entries are unused, which is
+/// the case of the boot sector of most filesystems.
+///
+/// # Errors
+/// Returns an error if the sector could not be read.
+fn read_mbr(device: &Arc<dyn BlockDevice>, sector: u64) -> Result<Option<[MbrEntry; 4]>, IoError> {
+    let data = super::read(device, sector, device.sector_size())?;
+    if data[MBR_SIGNATURE..MBR_SIZE] != [0x55, 0xAA] {
+        return Ok(None);
+    }
+
+    let mut entries = [MbrEntry {
+        kind: MBR_EMPTY,
+        start: 0,
+        length: 0,
+    }; 4];
+    for (index, entry) in entries.iter_mut().enumerate() {
+        let raw = &data[MBR_ENTRIES + index * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+        if raw[0] != 0x00 && raw[0] != 0x80 {
+            return Ok(None);
+        }
+        *entry = MbrEntry {
+            kind: raw[4],
+            start: u64::from(get_u32(raw, 8)),
+            length: u64::from(get_u32(raw, 12)),
+        };
+    }
+
+    if entries.iter().all(|entry| entry.kind == MBR_EMPTY) {
+        return Ok(None);
+    }
+    Ok(Some(entries))
+}
+
+/// Read the logical partitions of the extended partition starting at the given
+/// sector, and append them to the given list. Each EBR describes a logical
+/// partition, relative to the EBR itself, and the next EBR, relative to the
+/// start of the extended partition.
+///
+/// # Errors
+/// Returns an error if an EBR could not be read.
+fn read_logical(
+    device: &Arc<dyn BlockDevice>,
+    base: u64,
+    entries: &mut Vec<Entry>,
+) -> Result<(), IoError> {
+    let mut sector = base;
+    let mut number = 5;
+    for _ in 0..MAX_LOGICAL {
+        if sector >= device.capacity() {
+            log::warn!("partition: {}: EBR out of the device", device.name());
+            break;
+        }
+        let Some([logical, next, ..]) = read_mbr(device, sector)? else {
+            break;
+        };
+
+        if logical.kind != MBR_EMPTY && logical.length != 0 {
+            entries.push(Entry {
+                number,
+                start: sector + logical.start,
+                length: logical.length,
+            });
+            number += 1;
+        }
+        if !MBR_EXTENDED.contains(&next.kind) || next.start == 0 {
+            break;
+        }
+        sector = base + next.start;
+    }
+    Ok(())
+}
+
+/// Read the GPT of the given device, and return its partitions. The backup
+/// header, in the last sector of the device, is used if the primary header or
+/// its partition entries are corrupted or could not be read.
+///
+/// # Errors
+/// Returns an error if the backup header or its partition entries could not be
+/// read.
+fn read_gpt(device: &Arc<dyn BlockDevice>) -> Result<Vec<Entry>, IoError> {
+    match read_gpt_header(device, 1) {
+        Ok(Some(entries)) => {
+            log::info!(
+                "partition: {}: GPT with {} partitions",
+                device.name(),
+                entries.len()
+            );
+            return Ok(entries);
+        }
+        Ok(None) => log::warn!(
+            "partition: {}: primary GPT is corrupted, using the backup",
+            device.name()
+        ),
+        Err(_) => log::warn!(
+            "partition: {}: primary GPT could not be read, using the backup",
+            device.name()
+        ),
+    }
+    if let Some(entries) = read_gpt_header(device, device.capacity() - 1)? {
+        log::info!(
+            "partition: {}: backup GPT with {} partitions",
+            device.name(),
+            entries.len()
+        );
+        return Ok(entries);
+    }
+
+    log::warn!("partition: {}: backup GPT is corrupted", device.name());
+    Ok(Vec::new())
+}
+
+/// Read the GPT header stored in the given sector and its partition entries,
+/// and return the partitions in use, or `None` if the header or the entries are
+/// invalid.
+///
+/// # Errors
+/// Returns an error if the header or the partition entries could not be read.
+#[allow(clippy::cast_possible_truncation)]
+fn read_gpt_header(
+    device: &Arc<dyn BlockDevice>,
+    sector: u64,
+) -> Result<Option<Vec<Entry>>, IoError> {
+    let sector_size = device.sector_size();
+    let mut header = super::read(device, sector, sector_size)?;
+    if &header[..8] != GPT_SIGNATURE {
+        return Ok(None);
+    }
+
+    // The checksum of the header is computed with its own field set to zero.
+    let size = get_u32(&header, 12) as usize;
+    let checksum = get_u32(&header, 16);
+    if !(GPT_HEADER_SIZE..=sector_size).contains(&size) {
+        return Ok(None);
+    }
+    header[16..20].fill(0);
+    if crc32(&header[..size]) != checksum || get_u64(&header, 24) != sector {
+        return Ok(None);
+    }
+
+    let first_usable = get_u64(&header, 40);
+    let last_usable = get_u64(&header, 48);
+    let table = get_u64(&header, 72);
+    let count = get_u32(&header, 80) as usize;
+    let entry_size = get_u32(&header, 84) as usize;
+    let table_checksum = get_u32(&header, 88);
+    if !(GPT_ENTRY_SIZE..=MAX_GPT_ENTRY_SIZE).contains(&entry_size)
+        || !entry_size.is_power_of_two()
+        || count > MAX_GPT_ENTRIES
+    {
+        return Ok(None);
+    }
+
+    let bytes = count * entry_size;
+    if bytes > MAX_GPT_TABLE_SIZE {
+        return Ok(None);
+    }
+    let sectors = ((bytes + sector_size - 1) / sector_size) as u64;
+    if table.saturating_add(sectors) > device.capacity() {
+        return Ok(None);
+    }
+    let data = super::read(device, table, sectors as usize * sector_size)?;
+    if crc32(&data[..bytes]) != table_checksum {
+        return Ok(None);
+    }
+
+    let mut entries = Vec::new();
+    for (index, raw) in data[..bytes].chunks_exact(entry_size).enumerate() {
+        // Unused entries have a null partition type.
+        if raw[..16].iter().all(|&byte| byte == 0) {
+            continue;
+        }
+        let first = get_u64(raw, 32);
+        let last = get_u64(raw, 40);
+        if first > last || first < first_usable || last > last_usable {
+            log::warn!(
+                "partition: {} partition {} is out of the usable sectors, ignored",
+                device.name(),
+                index + 1
+            );
+            continue;
+        }
+        entries.push(Entry {
+            number: index + 1,
+            start: first,
+            length: last - first + 1,
+        });
+    }
+    Ok(Some(entries))
+}
+
+/// Returns the CRC32 of the given data, with the polynomial used by Ethernet and
+/// zlib, which is the one used by the GPT.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    !crc
+}
+
+/// Read a little-endian `u32` at the given offset of the given data.
+fn get_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Read a little-endian `u64` at the given offset of the given data.
+fn get_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
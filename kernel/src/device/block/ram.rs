@@ -0,0 +1,176 @@
+//! RAM disks. A RAM disk is a block device whose sectors are stored in memory,
+//! which is useful to test disk filesystems without real hardware. Its memory is
+//! allocated lazily, page by page, when a sector is written for the first time:
+//! sectors that were never written read as zeroes and do not use any memory.
+//!
+//! The number and the size of the empty RAM disks are given by the `brd.rd_nr`
+//! and `brd.rd_size` options of the kernel command line, the size being given in
+//! kibibytes like on Linux. If Limine loaded the [`IMAGE`] module, an additional
+//! RAM disk is created with the content of the module, so that a filesystem image
+//! shipped inside the ISO can be mounted directly.
+use super::{BlockDevice, Operation, Request};
+use crate::{cmdline, device::Identifier, module};
+use alloc::{collections::BTreeMap, format};
+
+/// The major number of RAM disks, the same as on Linux.
+pub const MAJOR: u32 = 1;
+
+/// The path of the Limine module used to create a RAM disk with its content.
+pub const IMAGE: &str = "/boot/ramdisk.img";
+
+/// The size of a sector of a RAM disk.
+const SECTOR_SIZE: usize = 512;
+
+/// The size of a page of a RAM disk, which is the unit of allocation.
+const PAGE_SIZE: usize = 4096;
+
+/// The number of empty RAM disks created when `brd.rd_nr` is not given.
+const DEFAULT_COUNT: u32 = 1;
+
+/// The size, in kibibytes, of the empty RAM disks when `brd.rd_size` is not
+/// given.
+const DEFAULT_SIZE: u64 = 4096;
+
+/// A block device stored in memory.
+pub struct RamDisk {
+    /// The name of the RAM disk.
+    name: String,
+
+    /// The number of sectors of the RAM disk.
+    capacity: u64,
+
+    /// The pages of the RAM disk that have been written at least once, indexed
+    /// by their page number.
+    pages: Spinlock<BTreeMap<u64, Box<[u8; PAGE_SIZE]>>>,
+}
+
+impl RamDisk {
+    /// Create a new empty RAM disk with the given name and number of sectors.
+    #[must_use]
+    pub fn new(name: String, capacity: u64) -> Self {
+        Self {
+            name,
+            capacity,
+            pages: Spinlock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Create a new RAM disk with the given name filled with the given data. The
+    /// size of the RAM disk is rounded up to a multiple of the sector size, and
+    /// the remaining bytes are zeroed.
+    #[must_use]
+    pub fn with_content(name: String, data: &[u8]) -> Self {
+        let disk = Self::new(name, ((data.len() + SECTOR_SIZE - 1) / SECTOR_SIZE) as u64);
+        disk.copy_in(0, data);
+        disk
+    }
+
+    /// Copy the given data into the RAM disk at the given byte offset, allocating
+    /// the pages as needed. Pages entirely filled with zeroes are not allocated.
+    #[allow(clippy::cast_possible_truncation)]
+    fn copy_in(&self, offset: u64, data: &[u8]) {
+        let mut pages = self.pages.lock();
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset + done as u64;
+            let start = (position % PAGE_SIZE as u64) as usize;
+            let count = core::cmp::min(PAGE_SIZE - start, data.len() - done);
+            let chunk = &data[done..done + count];
+            let index = position / PAGE_SIZE as u64;
+
+            match pages.get_mut(&index) {
+                Some(page) => page[start..start + count].copy_from_slice(chunk),
+                None if chunk.iter().all(|&byte| byte == 0) => {}
+                None => {
+                    let mut page = Box::new([0; PAGE_SIZE]);
+                    page[start..start + count].copy_from_slice(chunk);
+                    pages.insert(index, page);
+                }
+            }
+            done += count;
+        }
+    }
+
+    /// Copy the content of the RAM disk at the given byte offset into the given
+    /// buffer. Pages that were never allocated read as zeroes.
+    #[allow(clippy::cast_possible_truncation)]
+    fn copy_out(&self, offset: u64, buffer: &mut [u8]) {
+        let pages = self.pages.lock();
+        let mut done = 0;
+        while done < buffer.len() {
+            let position = offset + done as u64;
+            let start = (position % PAGE_SIZE as u64) as usize;
+            let count = core::cmp::min(PAGE_SIZE - start, buffer.len() - done);
+            let chunk = &mut buffer[done..done + count];
+
+            match pages.get(&(position / PAGE_SIZE as u64)) {
+                Some(page) => chunk.copy_from_slice(&page[start..start + count]),
+                None => chunk.fill(0),
+            }
+            done += count;
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Requests are completed immediately, since the data is already in memory.
+    fn submit(&self, request: Arc<Request>) {
+        let offset = request.sector() * SECTOR_SIZE as u64;
+        match request.operation() {
+            Operation::Read => self.copy_out(offset, &mut request.buffer()),
+            Operation::Write => self.copy_in(offset, &request.buffer()),
+            Operation::Flush => {}
+        }
+        request.complete(Ok(()));
+    }
+}
+
+/// Create the RAM disks requested on the kernel command line, and the RAM disk
+/// built from the [`IMAGE`] module if Limine loaded it. The RAM disk built from
+/// the module is always the first one, named `ram0`.
+#[init]
+pub fn setup() {
+    let mut disks = Vec::new();
+    if let Some(image) = module::read(IMAGE) {
+        disks.push(RamDisk::with_content(String::from("ram0"), image));
+
+        // SAFETY: The module is not used anymore since its content was copied
+        // into the RAM disk.
+        unsafe {
+            module::free(IMAGE);
+        }
+    }
+
+    let count = cmdline::parse("brd.rd_nr").unwrap_or(DEFAULT_COUNT);
+    let size = cmdline::parse("brd.rd_size").unwrap_or(DEFAULT_SIZE);
+    if let Some(bytes) = size.checked_mul(1024) {
+        for _ in 0..count {
+            let name = format!("ram{}", disks.len());
+            disks.push(RamDisk::new(name, bytes / SECTOR_SIZE as u64));
+        }
+    } else {
+        log::warn!("brd: RAM disks of {} KiB are too large", size);
+    }
+
+    for (minor, disk) in (0..).zip(disks) {
+        let id = Identifier {
+            major: MAJOR,
+            minor,
+        };
+        if let Err(error) = super::register(id, Arc::new(disk)) {
+            log::warn!("brd: failed to register RAM disk {}: {:?}", minor, error);
+        }
+    }
+}
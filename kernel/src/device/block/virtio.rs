@@ -0,0 +1,397 @@
+//! Virtio block devices, attached to the PCI bus with the modern virtio
+//! transport. This is the fastest disk emulated by QEMU, attached with
+//! `-drive if=virtio,format=raw,file=disk.img`.
+//!
+//! A disk has a single virtqueue. Each request is a chain of three buffers: a
+//! header read by the device giving the operation and the first sector, the
+//! data of the request, and a status byte written by the device when the
+//! request is completed. Several requests can be in flight at the same time:
+//! requests are queued in software only when the virtqueue is full, and the
+//! device completes them in any order. The legacy interrupt of the disk is
+//! raised when the device has used some chains.
+//!
+//! The data buffer of a request is given to the device directly. The kernel
+//! heap is a single range of physically contiguous frames mapped by the HHDM,
+//! so a buffer is physically contiguous and needs a single descriptor.
+//!
+//! Disks are named and numbered like on Linux: `vda`, `vdb`, etc, with 16
+//! minor numbers reserved for each disk.
+use super::{BlockDevice, IoError, Operation, Request};
+use crate::{
+    device::{
+        pci::{self, ProbeError},
+        virtio::{
+            self,
+            queue::{Buffer, Queue},
+            Transport,
+        },
+        Identifier,
+    },
+    mm::{
+        frame::{allocator::Allocator, owned::OwnedMemory, AllocationFlags},
+        FRAME_ALLOCATOR,
+    },
+    x86_64::{self, irq::Affinity},
+};
+use addr::{frame::Frame, phys::Physical, virt::Virtual};
+use alloc::{collections::VecDeque, format};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The major number of virtio disks. It is allocated dynamically on Linux, but
+/// usually ends up being this one.
+pub const MAJOR: u32 = 254;
+
+/// The number of minor numbers reserved for each disk.
+const MINORS_PER_DISK: u32 = 16;
+
+/// The maximal number of disks, named from `vda` to `vdz`.
+const MAX_DISKS: u32 = 26;
+
+/// The number of descriptors of the virtqueue of a disk, unless the device
+/// supports less. Each request uses at most three descriptors.
+const QUEUE_SIZE: u16 = 128;
+
+/// The features of virtio block devices used by the driver.
+const F_RO: u64 = 1 << 5;
+const F_BLK_SIZE: u64 = 1 << 6;
+const F_FLUSH: u64 = 1 << 9;
+
+/// The offsets of the fields of the configuration of a virtio block device.
+const CONFIG_CAPACITY: usize = 0x00;
+const CONFIG_BLK_SIZE: usize = 0x14;
+
+/// The types of requests.
+const TYPE_IN: u32 = 0;
+const TYPE_OUT: u32 = 1;
+const TYPE_FLUSH: u32 = 4;
+
+/// The status written by the device for a successful request.
+const STATUS_OK: u8 = 0;
+
+/// The size of the header of a request, and the size of the sectors used in the
+/// headers, whatever the size of the sectors of the disk.
+const HEADER_SIZE: usize = core::mem::size_of::<Header>();
+const PROTOCOL_SECTOR_SIZE: usize = 512;
+
+/// The PCI identifiers of virtio block devices: the transitional one, which
+/// also implements the modern interface under QEMU, and the modern one.
+static IDS: [pci::Id; 2] = [
+    pci::Id::device(virtio::VENDOR, 0x1001),
+    pci::Id::device(virtio::VENDOR, 0x1042),
+];
+
+/// The driver of virtio block devices.
+static DRIVER: pci::Driver = pci::Driver {
+    name: "virtio-blk",
+    ids: &IDS,
+    probe,
+};
+
+/// The index of the next disk found.
+static NEXT_INDEX: AtomicU32 = AtomicU32::new(0);
+
+/// The disks found, used to find the disks that raised an interrupt.
+static DISKS: Spinlock<Vec<Arc<Disk>>> = Spinlock::new(Vec::new());
+
+/// A virtio block device.
+pub struct Disk {
+    /// The name of the disk.
+    name: String,
+
+    /// The registers of the device.
+    transport: Transport,
+
+    /// The legacy interrupt line of the device.
+    irq: u8,
+
+    /// The number of sectors of the disk, and their size.
+    capacity: u64,
+    sector_size: usize,
+
+    /// Whether the disk is read-only, and whether it has a volatile write cache
+    /// that can be flushed.
+    read_only: bool,
+    flush: bool,
+
+    /// The state of the requests, shared with the interrupt handler.
+    state: Spinlock<State>,
+}
+
+/// The state of the requests of a disk.
+struct State {
+    /// The virtqueue of the disk.
+    queue: Queue,
+
+    /// The headers and the status bytes of the requests, indexed by the first
+    /// descriptor of their chain. The headers come first, then the statuses.
+    headers: OwnedMemory,
+
+    /// The requests given to the device, indexed by the first descriptor of
+    /// their chain.
+    requests: Vec<Option<Arc<Request>>>,
+
+    /// The requests waiting for free descriptors.
+    pending: VecDeque<Arc<Request>>,
+}
+
+/// The header of a request, read by the device.
+#[repr(C)]
+struct Header {
+    kind: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+impl State {
+    /// Returns a pointer to the header of the request whose chain starts with
+    /// the given descriptor, and its physical address.
+    fn header(&self, head: usize) -> (*mut Header, Physical) {
+        let physical = self.headers.start.addr() + HEADER_SIZE * head;
+        (Virtual::from(physical).as_mut_ptr(), physical)
+    }
+
+    /// Returns a pointer to the status of the request whose chain starts with
+    /// the given descriptor, and its physical address.
+    fn status(&self, head: usize) -> (*mut u8, Physical) {
+        let size = usize::from(self.queue.size());
+        let physical = self.headers.start.addr() + HEADER_SIZE * size + head;
+        (Virtual::from(physical).as_mut_ptr(), physical)
+    }
+}
+
+impl Disk {
+    /// Queue the given request, and give it to the device if there are enough
+    /// free descriptors.
+    fn submit(&self, request: Arc<Request>) {
+        // Without a volatile write cache, writes are persistent as soon as they
+        // are completed.
+        if request.operation() == Operation::Flush && !self.flush {
+            request.complete(Ok(()));
+            return;
+        }
+
+        x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            state.pending.push_back(request);
+            self.start(&mut state);
+        });
+    }
+
+    /// Give the pending requests to the device while there are enough free
+    /// descriptors, and notify the device if any was given.
+    #[allow(clippy::cast_possible_truncation)]
+    fn start(&self, state: &mut State) {
+        let mut started = false;
+
+        while let Some(request) = state.pending.front() {
+            let needed = if request.is_empty() { 2 } else { 3 };
+            if state.queue.available() < needed {
+                break;
+            }
+
+            let request = state.pending.pop_front().unwrap();
+            let kind = match request.operation() {
+                Operation::Read => TYPE_IN,
+                Operation::Write => TYPE_OUT,
+                Operation::Flush => TYPE_FLUSH,
+            };
+            let sector = match request.operation() {
+                Operation::Flush => 0,
+                _ => request.sector() * (self.sector_size / PROTOCOL_SECTOR_SIZE) as u64,
+            };
+
+            // The header and the status of a chain are found from its first
+            // descriptor, and the header must be written before the chain is
+            // given to the device.
+            let head = usize::from(state.queue.next_head().unwrap());
+            let (header, header_address) = state.header(head);
+            let (_, status_address) = state.status(head);
+            unsafe {
+                header.write_volatile(Header {
+                    kind,
+                    reserved: 0,
+                    sector,
+                });
+            }
+
+            let mut buffers = Vec::with_capacity(needed);
+            buffers.push(Buffer {
+                address: header_address,
+                len: HEADER_SIZE as u32,
+                writable: false,
+            });
+            if !request.is_empty() {
+                let data = request.buffer();
+                buffers.push(Buffer {
+                    address: Physical::from(Virtual::from_ptr(data.as_ptr())),
+                    len: data.len() as u32,
+                    writable: request.operation() == Operation::Read,
+                });
+            }
+            buffers.push(Buffer {
+                address: status_address,
+                len: 1,
+                writable: true,
+            });
+
+            let pushed = state.queue.push(&buffers);
+            debug_assert_eq!(pushed.map(usize::from), Some(head));
+            state.requests[head] = Some(request);
+            started = true;
+        }
+
+        if started {
+            state.queue.notify();
+        }
+    }
+
+    /// Complete the requests used by the device, and give it the pending ones.
+    fn service(&self) {
+        let mut state = self.state.lock();
+        while let Some((head, _)) = state.queue.pop() {
+            let head = usize::from(head);
+            let (status, _) = state.status(head);
+            let status = unsafe { status.read_volatile() };
+            if let Some(request) = state.requests[head].take() {
+                match status {
+                    STATUS_OK => request.complete(Ok(())),
+                    _ => request.complete(Err(IoError::DeviceError)),
+                }
+            }
+        }
+        self.start(&mut state);
+    }
+}
+
+impl BlockDevice for Disk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn submit(&self, request: Arc<Request>) {
+        Disk::submit(self, request);
+    }
+
+    fn poll(&self) {
+        x86_64::irq::without(|| self.service());
+    }
+}
+
+/// Handle an interrupt on the given line, shared by one or several disks. The
+/// disks that did not raise the interrupt are not serviced.
+pub fn interrupt(irq: u8) {
+    for disk in DISKS.lock().iter().filter(|disk| disk.irq == irq) {
+        if disk.transport.interrupted() {
+            disk.service();
+        }
+    }
+}
+
+/// Initialize the given virtio block device, and register it as a block device.
+#[allow(clippy::cast_possible_truncation)]
+fn probe(device: &Arc<pci::Device>) -> Result<(), ProbeError> {
+    let irq = device.interrupt_line;
+    if device.interrupt_pin == 0 {
+        log::warn!("virtio-blk: {} has no legacy interrupt", device.address);
+        return Err(ProbeError::NotSupported);
+    }
+
+    let (transport, features) = Transport::new(device, F_RO | F_BLK_SIZE | F_FLUSH)?;
+    let queue = transport.setup_queue(0, QUEUE_SIZE).map_err(|error| {
+        transport.fail();
+        error
+    })?;
+
+    let size = usize::from(queue.size());
+    let frames = ((HEADER_SIZE + 1) * size + Frame::SIZE - 1) / Frame::SIZE;
+    let headers = unsafe {
+        FRAME_ALLOCATOR
+            .lock()
+            .allocate_range(frames, AllocationFlags::KERNEL | AllocationFlags::ZEROED)
+    };
+    let Some(headers) = headers else {
+        transport.fail();
+        return Err(ProbeError::OutOfMemory);
+    };
+
+    // The capacity is always given in 512-byte sectors, whatever the size of
+    // the logical blocks of the disk.
+    let sector_size = match transport.config_u32(CONFIG_BLK_SIZE) as usize {
+        size if features & F_BLK_SIZE != 0 && size.is_power_of_two() && size >= 512 => size,
+        _ => PROTOCOL_SECTOR_SIZE,
+    };
+    let capacity =
+        transport.config_u64(CONFIG_CAPACITY) / (sector_size / PROTOCOL_SECTOR_SIZE) as u64;
+
+    let index = NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+    if index >= MAX_DISKS {
+        transport.fail();
+        return Err(ProbeError::NotSupported);
+    }
+
+    transport.ready();
+    let disk = Arc::new(Disk {
+        name: format!("vd{}", char::from(b'a' + index as u8)),
+        transport,
+        irq,
+        capacity,
+        sector_size,
+        read_only: features & F_RO != 0,
+        flush: features & F_FLUSH != 0,
+        state: Spinlock::new(State {
+            queue,
+            headers,
+            requests: (0..size).map(|_| None).collect(),
+            pending: VecDeque::new(),
+        }),
+    });
+
+    // The interrupt line is only requested once, even if shared by several
+    // disks, since the handler services all of them.
+    let requested = x86_64::irq::without(|| {
+        let mut disks = DISKS.lock();
+        if disks.iter().all(|other| other.irq != irq) {
+            x86_64::irq::request(irq, interrupt, Affinity::Bsp)?;
+        }
+        disks.push(Arc::clone(&disk));
+        Ok::<_, x86_64::irq::RequestError>(())
+    });
+    if let Err(error) = requested {
+        log::warn!("virtio-blk: failed to request IRQ {}: {:?}", irq, error);
+        disk.transport.fail();
+        return Err(ProbeError::NotSupported);
+    }
+
+    let id = Identifier {
+        major: MAJOR,
+        minor: index * MINORS_PER_DISK,
+    };
+    if let Err(error) = super::register(id, disk) {
+        log::warn!(
+            "virtio-blk: failed to register {}: {:?}",
+            device.address,
+            error
+        );
+        return Err(ProbeError::DeviceError);
+    }
+    Ok(())
+}
+
+/// Register the driver of virtio block devices. The devices already found on the
+/// PCI bus are probed immediately.
+#[init]
+pub fn setup() {
+    pci::register(&DRIVER);
+}
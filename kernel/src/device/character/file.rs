@@ -0,0 +1,86 @@
+//! The file operations of character device nodes, which forward the requests to
+//! the driver of the device. Character devices are not seekable, and the offset
+//! of the file is ignored.
+use super::CharDevice;
+use crate::vfs::{
+    file::{
+        File, FileOperation, IoctlError, MmapError, Offset, ReadError, SeekError, Whence,
+        WriteError,
+    },
+    inode::Kind,
+    poll::Events,
+};
+use addr::phys::Physical;
+
+/// The operation table of an opened character device node.
+pub static FILE_OPS: FileOperation = FileOperation {
+    write,
+    read,
+    seek,
+    poll,
+    ioctl,
+    mmap,
+};
+
+/// Returns the device opened by the given file, or `None` if the device has
+/// been unregistered since the node was opened.
+fn device(file: &File) -> Option<Arc<dyn CharDevice>> {
+    match file.dentry.as_ref()?.inode().kind {
+        Kind::CharDevice(id) => super::get(id),
+        _ => None,
+    }
+}
+
+/// Read from the device into the given buffer.
+///
+/// # Errors
+/// Returns `ReadError::IoError` if the device does not exist anymore, or the
+/// error returned by the driver.
+fn read(file: &File, buf: &mut [u8], _: Offset) -> Result<usize, ReadError> {
+    device(file).ok_or(ReadError::IoError)?.read(file, buf)
+}
+
+/// Write the given buffer to the device.
+///
+/// # Errors
+/// Returns `WriteError::IoError` if the device does not exist anymore, or the
+/// error returned by the driver.
+fn write(file: &File, buf: &[u8], _: Offset) -> Result<usize, WriteError> {
+    device(file).ok_or(WriteError::IoError)?.write(file, buf)
+}
+
+/// Character devices are not seekable.
+///
+/// # Errors
+/// Always returns `SeekError::NotSeekable`.
+fn seek(_: &File, _: isize, _: Whence) -> Result<Offset, SeekError> {
+    Err(SeekError::NotSeekable)
+}
+
+/// Returns the events ready on the device. A device that does not exist anymore
+/// is reported as hung up.
+fn poll(file: &File) -> Events {
+    device(file).map_or(Events::HUP, |device| device.poll(file))
+}
+
+/// Forward the ioctl request to the driver of the device.
+///
+/// # Errors
+/// Returns `IoctlError::NoSuchDevice` if the device does not exist anymore, or
+/// the error returned by the driver.
+fn ioctl(file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+    device(file)
+        .ok_or(IoctlError::NoSuchDevice)?
+        .ioctl(file, request, arg)
+}
+
+/// Returns the memory of the device to map for the given range of the device.
+///
+/// # Errors
+/// Returns `MmapError::NoSuchDevice` if the device does not exist anymore, or
+/// the error returned by the driver.
+fn mmap(file: &File, offset: Offset, len: usize) -> Result<Physical, MmapError> {
+    device(file)
+        .ok_or(MmapError::NoSuchDevice)?
+        .mmap(file, offset, len)
+}
@@ -0,0 +1,171 @@
+//! Character devices: devices read and written as a stream of bytes, such as
+//! terminals. Unlike block devices, the requests are not cached nor split by
+//! the kernel, and are given directly to the driver of the device, which can
+//! make the calling task sleep until the request can be served.
+use super::{node, tty::Tty, Device, Identifier};
+use crate::vfs::{
+    file::{File, IoctlError, MmapError, Offset, OpenFlags, ReadError, WriteError},
+    poll::Events,
+};
+use addr::phys::Physical;
+use alloc::collections::BTreeMap;
+use core::any::Any;
+
+pub mod file;
+
+/// The list of all registered character devices, indexed by their identifier.
+static DEVICES: Spinlock<BTreeMap<Identifier, Arc<dyn CharDevice>>> =
+    Spinlock::new(BTreeMap::new());
+
+/// The data attached to an opened file by the driver of a character device.
+pub type OpenData = Box<dyn Any + Send + Sync>;
+
+/// A character device. The file given to the operations is the one opened on
+/// the node of the device, whose `data` field contains the data returned by
+/// [`CharDevice::open`]. The file is dropped when the last descriptor referring
+/// to it is closed, which allows drivers to release per-file resources in the
+/// `Drop` implementation of their data.
+pub trait CharDevice: Send + Sync {
+    /// The name of the device, also used for its node in `/dev`.
+    fn name(&self) -> &str;
+
+    /// Called when a node of the device is opened by the current task, and
+    /// returns the data attached to the opened file.
+    ///
+    /// # Errors
+    /// Returns `OpenError::NoSuchDevice` if the device cannot be opened, or
+    /// `OpenError::IoError` if it cannot be opened now.
+    fn open(&self, _flags: OpenFlags) -> Result<OpenData, OpenError> {
+        Ok(Box::new(()))
+    }
+
+    /// Read from the device into the given buffer, and returns the number of
+    /// bytes read. This may put the current task to sleep until some data is
+    /// available.
+    ///
+    /// # Errors
+    /// Returns `ReadError::NotImplemented` by default.
+    fn read(&self, _file: &File, _buf: &mut [u8]) -> Result<usize, ReadError> {
+        Err(ReadError::NotImplemented)
+    }
+
+    /// Write the given buffer to the device, and returns the number of bytes
+    /// written.
+    ///
+    /// # Errors
+    /// Returns `WriteError::NotImplemented` by default.
+    fn write(&self, _file: &File, _buf: &[u8]) -> Result<usize, WriteError> {
+        Err(WriteError::NotImplemented)
+    }
+
+    /// Returns the events currently ready on the device. Drivers that may block
+    /// must call [`crate::vfs::poll::notify`] when their state changes.
+    fn poll(&self, _file: &File) -> Events {
+        Events::IN | Events::OUT
+    }
+
+    /// Perform a driver-specific ioctl request on the device.
+    ///
+    /// # Errors
+    /// Returns `IoctlError::NotSupported` by default.
+    fn ioctl(&self, _file: &File, _request: usize, _arg: usize) -> Result<usize, IoctlError> {
+        Err(IoctlError::NotSupported)
+    }
+
+    /// Returns the physical address of the memory of the device to map in the
+    /// userland for the given range of the device. The memory must not belong
+    /// to the frame allocator, since it is mapped without being owned.
+    ///
+    /// # Errors
+    /// Returns `MmapError::NotSupported` by default.
+    fn mmap(&self, _file: &File, _offset: Offset, _len: usize) -> Result<Physical, MmapError> {
+        Err(MmapError::NotSupported)
+    }
+
+    /// Returns the terminal behind the device, if the device is a terminal.
+    fn tty(&self) -> Option<&Tty> {
+        None
+    }
+}
+
+/// Register a character device with the given identifier and create its node
+/// in `/dev`.
+///
+/// # Errors
+/// Returns `RegisterError::AlreadyExists` if a device is already registered with
+/// the same identifier.
+pub fn register(id: Identifier, device: Arc<dyn CharDevice>) -> Result<(), RegisterError> {
+    let name = String::from(device.name());
+    add(id, device)?;
+    log::info!("char: {} ({}:{})", name, id.major, id.minor);
+
+    if let Err(error) = node::create(&name, Device::Char(id)) {
+        log::warn!("char: failed to create /dev/{}: {:?}", name, error);
+    }
+    Ok(())
+}
+
+/// Unregister the character device with the given identifier and remove its
+/// node, and return the device. Files already opened on the device keep it
+/// alive until they are closed.
+#[allow(clippy::must_use_candidate)]
+pub fn unregister(id: Identifier) -> Option<Arc<dyn CharDevice>> {
+    let device = remove(id)?;
+    _ = node::remove(device.name());
+    Some(device)
+}
+
+/// Register a character device with the given identifier without creating a
+/// node in `/dev`, for devices whose nodes are provided by a filesystem, like
+/// the pseudo-terminals in `devpts`.
+///
+/// # Errors
+/// Returns `RegisterError::AlreadyExists` if a device is already registered with
+/// the same identifier.
+pub fn add(id: Identifier, device: Arc<dyn CharDevice>) -> Result<(), RegisterError> {
+    let mut devices = DEVICES.lock();
+    if devices.contains_key(&id) {
+        return Err(RegisterError::AlreadyExists);
+    }
+    devices.insert(id, device);
+    Ok(())
+}
+
+/// Unregister the character device with the given identifier registered with
+/// [`add`], and return the device.
+#[allow(clippy::must_use_candidate)]
+pub fn remove(id: Identifier) -> Option<Arc<dyn CharDevice>> {
+    DEVICES.lock().remove(&id)
+}
+
+/// Returns the character device with the given identifier, if any.
+#[must_use]
+pub fn get(id: Identifier) -> Option<Arc<dyn CharDevice>> {
+    DEVICES.lock().get(&id).cloned()
+}
+
+/// Open the character device with the given identifier, and returns the data to
+/// attach to the opened file.
+///
+/// # Errors
+/// Returns `OpenError::NoSuchDevice` if there is no device registered with the
+/// given identifier, or the error returned by the driver.
+pub fn open(id: Identifier, flags: OpenFlags) -> Result<OpenData, OpenError> {
+    get(id).ok_or(OpenError::NoSuchDevice)?.open(flags)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterError {
+    /// A device is already registered with the same identifier.
+    AlreadyExists,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpenError {
+    /// There is no such device, or it cannot be opened.
+    NoSuchDevice,
+
+    /// The device exists but cannot be opened now, like a locked or hung up
+    /// pseudo-terminal.
+    IoError,
+}
@@ -0,0 +1,284 @@
+//! The framebuffer set up by the bootloader, exposed as `/dev/fb0` with the
+//! interface of the Linux framebuffer devices: the `FBIOGET_VSCREENINFO` and
+//! `FBIOGET_FSCREENINFO` ioctl requests describe its geometry and its pixel
+//! format, and its memory can be mapped in the userland with `mmap` to draw on
+//! the screen. The framebuffer cannot be reconfigured. The informations given
+//! by the bootloader are copied the first time they are needed, which must be
+//! during the boot since they are stored in memory reclaimed before jumping to
+//! the userland: [`setup`] makes sure of it.
+use super::{
+    character::{self, CharDevice},
+    Identifier,
+};
+use crate::{
+    limine::LIMINE_FRAMEBUFFER,
+    user,
+    vfs::file::{File, IoctlError, MmapError, Offset},
+};
+use addr::{phys::Physical, virt::Virtual};
+
+/// The identifier of `/dev/fb0`, the same as on Linux.
+const FB0: Identifier = Identifier {
+    major: 29,
+    minor: 0,
+};
+
+/// Get the variable informations of the framebuffer, as a [`VarScreenInfo`].
+pub const FBIOGET_VSCREENINFO: usize = 0x4600;
+
+/// Get the fixed informations of the framebuffer, as a [`FixScreenInfo`].
+pub const FBIOGET_FSCREENINFO: usize = 0x4602;
+
+/// The framebuffer stores the pixels one after the other.
+const FB_TYPE_PACKED_PIXELS: u32 = 0;
+
+/// The pixels are made of the intensity of each color channel.
+const FB_VISUAL_TRUECOLOR: u32 = 2;
+
+/// The identifier of the driver given in the fixed informations.
+const ID: &[u8; 16] = b"limine-fb\0\0\0\0\0\0\0";
+
+/// The framebuffer set up by the bootloader, if any.
+static FRAMEBUFFER: Lazy<Option<Framebuffer>> = Lazy::new(detect);
+
+/// A color channel of the pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Channel {
+    /// The position of the least significant bit of the channel in a pixel.
+    pub shift: u8,
+
+    /// The number of bits of the channel.
+    pub size: u8,
+}
+
+/// The framebuffer set up by the bootloader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Framebuffer {
+    /// The address of the framebuffer in the kernel address space.
+    pub address: Virtual,
+
+    /// The physical address of the framebuffer.
+    pub physical: Physical,
+
+    /// The width and height of the framebuffer, in pixels.
+    pub width: usize,
+    pub height: usize,
+
+    /// The number of bytes between the start of two lines.
+    pub pitch: usize,
+
+    /// The number of bits of a pixel.
+    pub bpp: usize,
+
+    /// The color channels of the pixels.
+    pub red: Channel,
+    pub green: Channel,
+    pub blue: Channel,
+}
+
+impl Framebuffer {
+    /// Returns the size of the framebuffer in bytes.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.pitch * self.height
+    }
+}
+
+/// The position of a color channel in a pixel, as used by [`VarScreenInfo`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Bitfield {
+    pub offset: u32,
+    pub length: u32,
+    pub msb_right: u32,
+}
+
+/// The variable informations of a framebuffer, with the layout of the Linux
+/// `fb_var_screeninfo` structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct VarScreenInfo {
+    pub xres: u32,
+    pub yres: u32,
+    pub xres_virtual: u32,
+    pub yres_virtual: u32,
+    pub xoffset: u32,
+    pub yoffset: u32,
+    pub bits_per_pixel: u32,
+    pub grayscale: u32,
+    pub red: Bitfield,
+    pub green: Bitfield,
+    pub blue: Bitfield,
+    pub transp: Bitfield,
+    pub nonstd: u32,
+    pub activate: u32,
+    pub height: u32,
+    pub width: u32,
+    pub accel_flags: u32,
+    pub pixclock: u32,
+    pub left_margin: u32,
+    pub right_margin: u32,
+    pub upper_margin: u32,
+    pub lower_margin: u32,
+    pub hsync_len: u32,
+    pub vsync_len: u32,
+    pub sync: u32,
+    pub vmode: u32,
+    pub rotate: u32,
+    pub colorspace: u32,
+    pub reserved: [u32; 4],
+}
+
+/// The fixed informations of a framebuffer, with the layout of the Linux
+/// `fb_fix_screeninfo` structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct FixScreenInfo {
+    pub id: [u8; 16],
+    pub smem_start: u64,
+    pub smem_len: u32,
+    pub kind: u32,
+    pub type_aux: u32,
+    pub visual: u32,
+    pub xpanstep: u16,
+    pub ypanstep: u16,
+    pub ywrapstep: u16,
+    pub line_length: u32,
+    pub mmio_start: u64,
+    pub mmio_len: u32,
+    pub accel: u32,
+    pub capabilities: u16,
+    pub reserved: [u16; 2],
+}
+
+/// The `/dev/fb0` character device.
+struct Node;
+
+impl CharDevice for Node {
+    fn name(&self) -> &str {
+        "fb0"
+    }
+
+    /// Handle the `FBIOGET_VSCREENINFO` and `FBIOGET_FSCREENINFO` requests.
+    #[allow(clippy::cast_possible_truncation)]
+    fn ioctl(&self, _: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        let fb = framebuffer().ok_or(IoctlError::NoSuchDevice)?;
+        let channel = |channel: Channel| Bitfield {
+            offset: u32::from(channel.shift),
+            length: u32::from(channel.size),
+            msb_right: 0,
+        };
+
+        match request {
+            FBIOGET_VSCREENINFO => put(
+                arg,
+                &VarScreenInfo {
+                    xres: fb.width as u32,
+                    yres: fb.height as u32,
+                    xres_virtual: fb.width as u32,
+                    yres_virtual: fb.height as u32,
+                    bits_per_pixel: fb.bpp as u32,
+                    red: channel(fb.red),
+                    green: channel(fb.green),
+                    blue: channel(fb.blue),
+                    height: u32::MAX,
+                    width: u32::MAX,
+                    ..VarScreenInfo::default()
+                },
+            ),
+            FBIOGET_FSCREENINFO => put(
+                arg,
+                &FixScreenInfo {
+                    id: *ID,
+                    smem_start: fb.physical.as_u64(),
+                    smem_len: fb.size() as u32,
+                    kind: FB_TYPE_PACKED_PIXELS,
+                    visual: FB_VISUAL_TRUECOLOR,
+                    line_length: fb.pitch as u32,
+                    ..FixScreenInfo::default()
+                },
+            ),
+            _ => Err(IoctlError::NotSupported),
+        }
+    }
+
+    /// Returns the physical address of the given range of the framebuffer. The
+    /// last page of the framebuffer can be mapped entirely even if the end of
+    /// the framebuffer is not page aligned.
+    fn mmap(&self, _: &File, offset: Offset, len: usize) -> Result<Physical, MmapError> {
+        let fb = framebuffer().ok_or(MmapError::NoSuchDevice)?;
+        let size = Physical::new(fb.size()).page_align_up().as_usize();
+        match offset.0.checked_add(len) {
+            Some(end) if end <= size => Ok(fb.physical + offset.0),
+            _ => Err(MmapError::InvalidRange),
+        }
+    }
+}
+
+/// Returns the framebuffer set up by the bootloader, or `None` if there is no
+/// framebuffer.
+#[must_use]
+pub fn framebuffer() -> Option<&'static Framebuffer> {
+    FRAMEBUFFER.as_ref()
+}
+
+/// Create `/dev/fb0`. Nothing is done if the bootloader did not set up a
+/// framebuffer.
+#[init]
+pub fn setup() {
+    let Some(fb) = framebuffer() else {
+        log::info!("fb: no framebuffer");
+        return;
+    };
+
+    log::info!(
+        "fb: {}x{}x{} framebuffer at {}",
+        fb.width,
+        fb.height,
+        fb.bpp,
+        fb.physical
+    );
+    if let Err(error) = character::register(FB0, Arc::new(Node)) {
+        log::warn!("fb: failed to register /dev/fb0: {:?}", error);
+    }
+}
+
+/// Returns the framebuffer set up by the bootloader, if any.
+#[allow(clippy::cast_possible_truncation)]
+fn detect() -> Option<Framebuffer> {
+    let fb = LIMINE_FRAMEBUFFER.get_response()?.framebuffers().next()?;
+
+    let address = Virtual::from_ptr(fb.addr());
+    Some(Framebuffer {
+        address,
+        physical: Physical::from(address),
+        width: fb.width() as usize,
+        height: fb.height() as usize,
+        pitch: fb.pitch() as usize,
+        bpp: usize::from(fb.bpp()),
+        red: Channel {
+            shift: fb.red_mask_shift(),
+            size: fb.red_mask_size(),
+        },
+        green: Channel {
+            shift: fb.green_mask_shift(),
+            size: fb.green_mask_size(),
+        },
+        blue: Channel {
+            shift: fb.blue_mask_shift(),
+            size: fb.blue_mask_size(),
+        },
+    })
+}
+
+/// Write the given value to the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not in the userland.
+fn put<T: Copy>(arg: usize, value: &T) -> Result<usize, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    unsafe {
+        user::Object::write(&ptr, value);
+    }
+    Ok(0)
+}
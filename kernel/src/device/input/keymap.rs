@@ -0,0 +1,205 @@
+//! The translation of the keys pressed on a keyboard into the bytes given to the
+//! console, with the US layout. The special keys send the escape sequences of
+//! the Linux console, and the keys pressed with `Alt` are prefixed by `ESC`, so
+//! that programs expecting `TERM=linux` work unmodified.
+use super::keycode::{
+    KEY_102ND, KEY_2, KEY_CAPSLOCK, KEY_DELETE, KEY_DOWN, KEY_END, KEY_F1, KEY_F10, KEY_F11,
+    KEY_F12, KEY_F2, KEY_F3, KEY_F4, KEY_F5, KEY_F6, KEY_F7, KEY_F8, KEY_F9, KEY_HOME, KEY_INSERT,
+    KEY_KP0, KEY_KP1, KEY_KP2, KEY_KP3, KEY_KP4, KEY_KP5, KEY_KP6, KEY_KP7, KEY_KP8, KEY_KP9,
+    KEY_KPDOT, KEY_KPENTER, KEY_KPMINUS, KEY_KPPLUS, KEY_KPSLASH, KEY_LEFT, KEY_LEFTALT,
+    KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_NUMLOCK, KEY_PAGEDOWN, KEY_PAGEUP, KEY_RIGHT, KEY_RIGHTALT,
+    KEY_RIGHTCTRL, KEY_RIGHTSHIFT, KEY_SCROLLLOCK, KEY_SPACE, KEY_UP,
+};
+use bitflags::bitflags;
+
+/// The bytes sent by the keys of the main block, indexed by their code, without
+/// and with `Shift`. A null byte means that the key sends nothing by itself.
+const NORMAL: &[u8; 58] =
+    b"\0\x1b1234567890-=\x7f\tqwertyuiop[]\r\0asdfghjkl;'`\0\\zxcvbnm,./\0*\0 ";
+const SHIFTED: &[u8; 58] =
+    b"\0\x1b!@#$%^&*()_+\x7f\tQWERTYUIOP{}\r\0ASDFGHJKL:\"~\0|ZXCVBNM<>?\0*\0 ";
+
+bitflags! {
+    /// The modifier keys held and the lock keys enabled.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Modifiers: u16 {
+        const LEFT_SHIFT = 1 << 0;
+        const RIGHT_SHIFT = 1 << 1;
+        const LEFT_CTRL = 1 << 2;
+        const RIGHT_CTRL = 1 << 3;
+        const LEFT_ALT = 1 << 4;
+        const RIGHT_ALT = 1 << 5;
+        const CAPS_LOCK = 1 << 6;
+        const NUM_LOCK = 1 << 7;
+        const SCROLL_LOCK = 1 << 8;
+    }
+}
+
+impl Modifiers {
+    /// Returns `true` if one of the `Shift` keys is held.
+    #[must_use]
+    pub fn shift(self) -> bool {
+        self.intersects(Self::LEFT_SHIFT | Self::RIGHT_SHIFT)
+    }
+
+    /// Returns `true` if one of the `Ctrl` keys is held.
+    #[must_use]
+    pub fn ctrl(self) -> bool {
+        self.intersects(Self::LEFT_CTRL | Self::RIGHT_CTRL)
+    }
+
+    /// Returns `true` if one of the `Alt` keys is held.
+    #[must_use]
+    pub fn alt(self) -> bool {
+        self.intersects(Self::LEFT_ALT | Self::RIGHT_ALT)
+    }
+
+    /// Update the modifiers after the key with the given code was pressed or
+    /// released. The lock keys are toggled when they are pressed. Returns `true`
+    /// if a lock key was toggled, so that the LEDs of the keyboard must be
+    /// updated.
+    pub fn update(&mut self, code: u16, pressed: bool) -> bool {
+        let (modifier, lock) = match code {
+            KEY_LEFTSHIFT => (Self::LEFT_SHIFT, false),
+            KEY_RIGHTSHIFT => (Self::RIGHT_SHIFT, false),
+            KEY_LEFTCTRL => (Self::LEFT_CTRL, false),
+            KEY_RIGHTCTRL => (Self::RIGHT_CTRL, false),
+            KEY_LEFTALT => (Self::LEFT_ALT, false),
+            KEY_RIGHTALT => (Self::RIGHT_ALT, false),
+            KEY_CAPSLOCK => (Self::CAPS_LOCK, true),
+            KEY_NUMLOCK => (Self::NUM_LOCK, true),
+            KEY_SCROLLLOCK => (Self::SCROLL_LOCK, true),
+            _ => return false,
+        };
+
+        match (lock, pressed) {
+            (true, true) => self.toggle(modifier),
+            (true, false) => return false,
+            (false, _) => self.set(modifier, pressed),
+        }
+        lock
+    }
+}
+
+/// What a key sends to the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Output {
+    /// A single byte, which is prefixed by `ESC` if `Alt` is held.
+    Byte(u8),
+
+    /// An escape sequence.
+    Sequence(&'static [u8]),
+}
+
+/// Returns what the key with the given code sends to the console when it is
+/// pressed with the given modifiers, or `None` if it sends nothing, like the
+/// modifier keys.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn translate(code: u16, modifiers: Modifiers) -> Option<Output> {
+    let numbers = modifiers.contains(Modifiers::NUM_LOCK) && !modifiers.shift();
+    if let Some(sequence) = sequence(code).or_else(|| keypad(code).filter(|_| !numbers)) {
+        return Some(Output::Sequence(sequence));
+    }
+
+    let byte = match code {
+        KEY_KP7 | KEY_KP8 | KEY_KP9 => b'7' + (code - KEY_KP7) as u8,
+        KEY_KP4 | KEY_KP5 | KEY_KP6 => b'4' + (code - KEY_KP4) as u8,
+        KEY_KP1 | KEY_KP2 | KEY_KP3 => b'1' + (code - KEY_KP1) as u8,
+        KEY_KP0 => b'0',
+        KEY_KPDOT => b'.',
+        KEY_KPMINUS => b'-',
+        KEY_KPPLUS => b'+',
+        KEY_KPSLASH => b'/',
+        KEY_KPENTER => b'\r',
+        KEY_102ND if modifiers.shift() => b'>',
+        KEY_102ND => b'<',
+        code if usize::from(code) < NORMAL.len() => {
+            let index = usize::from(code);
+            let mut byte = if modifiers.shift() {
+                SHIFTED[index]
+            } else {
+                NORMAL[index]
+            };
+            if modifiers.contains(Modifiers::CAPS_LOCK) && byte.is_ascii_alphabetic() {
+                byte ^= 0x20;
+            }
+            if modifiers.ctrl() {
+                byte = control(NORMAL[index]).unwrap_or(byte);
+            }
+            byte
+        }
+        _ => return None,
+    };
+
+    match (byte, code) {
+        (0, KEY_SPACE | KEY_2) => Some(Output::Byte(0)),
+        (0, _) => None,
+        (byte, _) => Some(Output::Byte(byte)),
+    }
+}
+
+/// Returns the control character sent by the key sending the given byte without
+/// modifiers when it is pressed with `Ctrl`, or `None` if it sends the same byte.
+fn control(byte: u8) -> Option<u8> {
+    match byte {
+        b'a'..=b'z' => Some(byte & 0x1F),
+        b' ' | b'2' => Some(0),
+        b'[' | b'3' => Some(0x1B),
+        b'\\' | b'4' => Some(0x1C),
+        b']' | b'5' => Some(0x1D),
+        b'6' => Some(0x1E),
+        b'-' | b'/' | b'7' => Some(0x1F),
+        b'8' => Some(0x7F),
+        _ => None,
+    }
+}
+
+/// Returns the escape sequence sent by the cursor, editing or function key with
+/// the given code.
+fn sequence(code: u16) -> Option<&'static [u8]> {
+    Some(match code {
+        KEY_UP => b"\x1b[A",
+        KEY_DOWN => b"\x1b[B",
+        KEY_RIGHT => b"\x1b[C",
+        KEY_LEFT => b"\x1b[D",
+        KEY_HOME => b"\x1b[1~",
+        KEY_INSERT => b"\x1b[2~",
+        KEY_DELETE => b"\x1b[3~",
+        KEY_END => b"\x1b[4~",
+        KEY_PAGEUP => b"\x1b[5~",
+        KEY_PAGEDOWN => b"\x1b[6~",
+        KEY_F1 => b"\x1b[[A",
+        KEY_F2 => b"\x1b[[B",
+        KEY_F3 => b"\x1b[[C",
+        KEY_F4 => b"\x1b[[D",
+        KEY_F5 => b"\x1b[[E",
+        KEY_F6 => b"\x1b[17~",
+        KEY_F7 => b"\x1b[18~",
+        KEY_F8 => b"\x1b[19~",
+        KEY_F9 => b"\x1b[20~",
+        KEY_F10 => b"\x1b[21~",
+        KEY_F11 => b"\x1b[23~",
+        KEY_F12 => b"\x1b[24~",
+        _ => return None,
+    })
+}
+
+/// Returns the escape sequence sent by the key of the numeric keypad with the
+/// given code when `Num Lock` is disabled.
+fn keypad(code: u16) -> Option<&'static [u8]> {
+    Some(match code {
+        KEY_KP7 => b"\x1b[1~",
+        KEY_KP8 => b"\x1b[A",
+        KEY_KP9 => b"\x1b[5~",
+        KEY_KP4 => b"\x1b[D",
+        KEY_KP5 => b"\x1b[G",
+        KEY_KP6 => b"\x1b[C",
+        KEY_KP1 => b"\x1b[4~",
+        KEY_KP2 => b"\x1b[B",
+        KEY_KP3 => b"\x1b[6~",
+        KEY_KP0 => b"\x1b[2~",
+        KEY_KPDOT => b"\x1b[3~",
+        _ => return None,
+    })
+}
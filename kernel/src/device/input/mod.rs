@@ -0,0 +1,534 @@
+//! Input devices, exposed to the userland like the evdev interface of Linux.
+//! Each input device has a node `/dev/input/eventN`, from which the events of
+//! the device are read as `input_event` records: a timestamp, a type, a code and
+//! a value. The events are reported by the drivers in packets, each packet being
+//! terminated by a `SYN_REPORT` event.
+//!
+//! Each file opened on a device has its own queue of events. When a queue is
+//! full, its events are dropped and replaced by a `SYN_DROPPED` event, so that
+//! the reader knows it must resynchronize its state with the `EVIOCGKEY` ioctl.
+//! A file can grab the device with `EVIOCGRAB`, to be the only one receiving
+//! its events: a grabbed keyboard does not feed the console anymore.
+use super::{
+    character::{self, CharDevice, OpenData, OpenError},
+    Identifier,
+};
+use crate::{
+    time::{date, units::Microsecond},
+    user::{self, buffer::UserStandardBuffer, task::queue::WaitQueue},
+    vfs::{
+        self,
+        file::{File, IoctlError, OpenFlags, ReadError, WriteError},
+        poll::Events,
+    },
+    x86_64,
+};
+use alloc::{collections::BTreeMap, format, vec};
+use circular_buffer::CircularBuffer;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+pub mod keycode;
+pub mod keymap;
+pub mod ps2;
+
+/// The major number of the input devices, the same as on Linux. The minor
+/// numbers of the event devices start at 64.
+const MAJOR: u32 = 13;
+const FIRST_MINOR: u32 = 64;
+
+/// The number of events that can be queued for a file opened on a device.
+const QUEUE_SIZE: usize = 256;
+
+/// The maximal number of events in a packet, without the `SYN_REPORT` event.
+/// The events reported beyond are dropped.
+const PACKET_SIZE: usize = 16;
+
+/// The size of an `input_event` record.
+pub const EVENT_SIZE: usize = core::mem::size_of::<Event>();
+
+/// The version of the evdev protocol, the same as Linux.
+const VERSION: i32 = 0x01_00_01;
+
+/// The types of events.
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const EV_MAX: u16 = 0x1F;
+
+/// The codes of the `EV_SYN` events.
+pub const SYN_REPORT: u16 = 0;
+pub const SYN_DROPPED: u16 = 3;
+
+/// The codes of the `EV_REL` events.
+pub const REL_X: u16 = 0x00;
+pub const REL_Y: u16 = 0x01;
+pub const REL_WHEEL: u16 = 0x08;
+pub const REL_MAX: u16 = 0x0F;
+
+/// The bus type of the devices connected to the i8042 controller.
+pub const BUS_I8042: u16 = 0x11;
+
+/// Get the version of the evdev protocol, as an `int`.
+pub const EVIOCGVERSION: usize = 0x8004_4501;
+
+/// Get the identifier of the device, as an [`InputId`].
+pub const EVIOCGID: usize = 0x8008_4502;
+
+/// Grab (with a non-zero argument) or release the device.
+pub const EVIOCGRAB: usize = 0x4004_4590;
+
+/// The numbers of the variable length requests, whose argument is a buffer
+/// whose length is encoded in the request. `EVIOCGNAME` gets the name of the
+/// device, `EVIOCGKEY` the bitmap of the keys pressed, and `EVIOCGBIT` the
+/// bitmap of the event types supported (for the type 0) or of the codes
+/// supported for the given type (`EVIOCGBIT + type`).
+const EVIOCGNAME: usize = 0x06;
+const EVIOCGKEY: usize = 0x18;
+const EVIOCGBIT: usize = 0x20;
+
+/// The identifiers of the devices, which are also their number.
+static NEXT_NUMBER: AtomicU32 = AtomicU32::new(0);
+
+/// The identifiers of the files opened on the devices.
+static NEXT_CLIENT: AtomicUsize = AtomicUsize::new(0);
+
+/// An input event, with the layout of the Linux `input_event` structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Event {
+    /// The time of the event since the Unix epoch.
+    pub seconds: u64,
+    pub microseconds: u64,
+
+    /// The type of the event, like `EV_KEY`.
+    pub kind: u16,
+
+    /// The code of the event, like the code of the key for `EV_KEY` events.
+    pub code: u16,
+
+    /// The value of the event, like 1 when a key is pressed, 0 when it is
+    /// released and 2 when it is repeated.
+    pub value: i32,
+}
+
+impl Event {
+    /// Write the event at the start of the given buffer, which must be at least
+    /// [`EVENT_SIZE`] bytes long.
+    fn encode(&self, buf: &mut [u8]) {
+        buf[0..8].copy_from_slice(&self.seconds.to_ne_bytes());
+        buf[8..16].copy_from_slice(&self.microseconds.to_ne_bytes());
+        buf[16..18].copy_from_slice(&self.kind.to_ne_bytes());
+        buf[18..20].copy_from_slice(&self.code.to_ne_bytes());
+        buf[20..24].copy_from_slice(&self.value.to_ne_bytes());
+    }
+}
+
+/// The identifier of the hardware of an input device, with the layout of the
+/// Linux `input_id` structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct InputId {
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+}
+
+/// The description of an input device given by its driver when registering it.
+pub struct DeviceInfo {
+    /// The name of the device given to the userland, like `AT Translated Set 2
+    /// keyboard`.
+    pub name: &'static str,
+
+    /// The identifier of the hardware.
+    pub id: InputId,
+
+    /// The types of events reported by the device, with the codes reported for
+    /// each type. `EV_SYN` is always supported and does not need to be given.
+    pub capabilities: Vec<(u16, Vec<u16>)>,
+}
+
+/// An input device, whose events are read from its node `/dev/input/eventN`.
+pub struct InputDevice {
+    /// The name of the node of the device, like `input/event0`.
+    node: String,
+
+    /// The description of the device.
+    info: DeviceInfo,
+
+    /// The state of the device, shared with the interrupt handler of its driver.
+    state: Spinlock<State>,
+
+    /// The tasks waiting for events.
+    readers: WaitQueue,
+}
+
+/// The state of an input device.
+struct State {
+    /// The queues of the files opened on the device, indexed by the identifier
+    /// of the file.
+    clients: BTreeMap<usize, Box<CircularBuffer<QUEUE_SIZE, Event>>>,
+
+    /// The file that grabbed the device, if any.
+    grab: Option<usize>,
+
+    /// The events of the packet being reported. It is not allocated on the
+    /// heap since events are reported by interrupt handlers.
+    packet: CircularBuffer<PACKET_SIZE, Event>,
+
+    /// The bitmap of the keys currently pressed.
+    keys: [u8; bitmap_len(keycode::KEY_MAX)],
+}
+
+impl InputDevice {
+    /// Returns the name of the device given to the userland.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.info.name
+    }
+
+    /// Add an event to the packet being reported. The packet is only given to
+    /// the readers by [`InputDevice::sync`]. For `EV_KEY` events, a key pressed
+    /// again without being released is reported as repeated, and a key released
+    /// without being pressed is ignored. This can be called from an interrupt
+    /// handler.
+    pub fn report(&self, kind: u16, code: u16, value: i32) {
+        x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            let mut value = value;
+            if kind == EV_KEY && code <= keycode::KEY_MAX {
+                let (byte, bit) = (usize::from(code / 8), code % 8);
+                let pressed = state.keys[byte] & (1 << bit) != 0;
+                match (value, pressed) {
+                    (0, false) => return,
+                    (0, true) => state.keys[byte] &= !(1 << bit),
+                    (_, true) => value = 2,
+                    (_, false) => state.keys[byte] |= 1 << bit,
+                }
+            }
+            _ = state.packet.try_push_back(Event {
+                kind,
+                code,
+                value,
+                ..Event::default()
+            });
+        });
+    }
+
+    /// Terminate the packet being reported with a `SYN_REPORT` event, and give
+    /// it to the files opened on the device, or only to the one that grabbed it.
+    /// Nothing is done if the packet is empty. This can be called from an
+    /// interrupt handler.
+    pub fn sync(&self) {
+        let (seconds, microseconds) = timestamp();
+        let stamp = |event: Event| Event {
+            seconds,
+            microseconds,
+            ..event
+        };
+
+        let reported = x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            if state.packet.is_empty() {
+                return false;
+            }
+
+            let State {
+                clients,
+                grab,
+                packet,
+                ..
+            } = &mut *state;
+            let report = Event {
+                kind: EV_SYN,
+                code: SYN_REPORT,
+                ..Event::default()
+            };
+
+            let queues = clients
+                .iter_mut()
+                .filter(|(id, _)| grab.map_or(true, |grab| grab == **id));
+            for (_, queue) in queues {
+                if QUEUE_SIZE - queue.len() <= packet.len() {
+                    queue.clear();
+                    queue.push_back(stamp(Event {
+                        kind: EV_SYN,
+                        code: SYN_DROPPED,
+                        ..Event::default()
+                    }));
+                }
+                for event in packet.iter().chain(core::iter::once(&report)) {
+                    _ = queue.try_push_back(stamp(*event));
+                }
+            }
+
+            packet.clear();
+            true
+        });
+
+        if reported {
+            self.readers.wake_up_all();
+            vfs::poll::notify();
+        }
+    }
+
+    /// Returns `true` if a file grabbed the device.
+    #[must_use]
+    pub fn grabbed(&self) -> bool {
+        x86_64::irq::without(|| self.state.lock().grab.is_some())
+    }
+
+    /// Returns the bitmap of the codes supported for the given event type, or
+    /// of the event types supported for the type 0.
+    fn capabilities(&self, kind: u16) -> Vec<u8> {
+        let (max, codes): (u16, Vec<u16>) = match kind {
+            EV_SYN => {
+                let kinds = self.info.capabilities.iter().map(|(kind, _)| *kind);
+                (EV_MAX, core::iter::once(EV_SYN).chain(kinds).collect())
+            }
+            EV_KEY => (keycode::KEY_MAX, self.codes(kind)),
+            EV_REL => (REL_MAX, self.codes(kind)),
+            _ => (0, Vec::new()),
+        };
+
+        let mut bitmap = vec![0; bitmap_len(max)];
+        for code in codes.into_iter().filter(|&code| code <= max) {
+            bitmap[usize::from(code / 8)] |= 1 << (code % 8);
+        }
+        bitmap
+    }
+
+    /// Returns the codes supported for the given event type.
+    fn codes(&self, kind: u16) -> Vec<u16> {
+        self.info
+            .capabilities
+            .iter()
+            .filter(|(supported, _)| *supported == kind)
+            .flat_map(|(_, codes)| codes.iter().copied())
+            .collect()
+    }
+
+    /// Create the queue of a new file opened on the device, and returns the
+    /// identifier of the file.
+    fn connect(&self) -> usize {
+        let id = NEXT_CLIENT.fetch_add(1, Ordering::Relaxed);
+        let queue = CircularBuffer::boxed();
+        x86_64::irq::without(|| self.state.lock().clients.insert(id, queue));
+        id
+    }
+
+    /// Remove the queue of the given file, and release the device if the file
+    /// grabbed it.
+    fn disconnect(&self, client: usize) {
+        x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            state.clients.remove(&client);
+            if state.grab == Some(client) {
+                state.grab = None;
+            }
+        });
+    }
+
+    /// Read as many events of the queue of the given file as possible into the
+    /// given buffer. If there is no event, the task sleeps until an event is
+    /// reported.
+    ///
+    /// # Errors
+    /// - `ReadError::BufferTooSmall`: The buffer cannot hold a single event.
+    /// - `ReadError::Interrupted`: The task was interrupted while waiting.
+    fn read(&self, client: usize, buf: &mut [u8]) -> Result<usize, ReadError> {
+        if buf.len() < EVENT_SIZE {
+            return Err(ReadError::BufferTooSmall);
+        }
+
+        self.readers
+            .sleep_interruptible_until(|| {
+                let mut state = self.state.lock();
+                let Some(queue) = state.clients.get_mut(&client) else {
+                    return Some(Err(ReadError::IoError));
+                };
+                if queue.is_empty() {
+                    return None;
+                }
+                let count = queue.len().min(buf.len() / EVENT_SIZE);
+                for (chunk, event) in buf.chunks_exact_mut(EVENT_SIZE).zip(queue.drain(..count)) {
+                    event.encode(chunk);
+                }
+                Some(Ok(count * EVENT_SIZE))
+            })
+            .map_err(|_| ReadError::Interrupted)?
+    }
+
+    /// Returns the events ready for the given file.
+    fn poll(&self, client: usize) -> Events {
+        x86_64::irq::without(|| {
+            let state = self.state.lock();
+            match state.clients.get(&client) {
+                Some(queue) if !queue.is_empty() => Events::IN,
+                Some(_) => Events::empty(),
+                None => Events::HUP,
+            }
+        })
+    }
+
+    /// Handle the ioctl requests of the given file.
+    ///
+    /// # Errors
+    /// See [`IoctlError`] for the possible errors.
+    fn ioctl(&self, client: usize, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        match request {
+            EVIOCGVERSION => put(arg, &VERSION),
+            EVIOCGID => put(arg, &self.info.id),
+            EVIOCGRAB => x86_64::irq::without(|| {
+                let mut state = self.state.lock();
+                match (arg, state.grab) {
+                    (0, Some(grab)) if grab == client => state.grab = None,
+                    (0, _) => return Err(IoctlError::InvalidArgument),
+                    (_, Some(_)) => return Err(IoctlError::Busy),
+                    (_, None) => state.grab = Some(client),
+                }
+                Ok(0)
+            }),
+            _ => {
+                // The variable length requests: `_IOC(_IOC_READ, 'E', nr, len)`
+                let (direction, len) = (request >> 30, (request >> 16) & 0x3FFF);
+                let (kind, nr) = ((request >> 8) & 0xFF, request & 0xFF);
+                if direction != 2 || kind != usize::from(b'E') {
+                    return Err(IoctlError::NotSupported);
+                }
+
+                #[allow(clippy::cast_possible_truncation)]
+                let data = match nr {
+                    EVIOCGNAME => {
+                        let mut name = self.info.name.as_bytes().to_vec();
+                        name.push(0);
+                        name
+                    }
+                    EVIOCGKEY => x86_64::irq::without(|| self.state.lock().keys.to_vec()),
+                    nr if (EVIOCGBIT..=EVIOCGBIT + usize::from(EV_MAX)).contains(&nr) => {
+                        self.capabilities((nr - EVIOCGBIT) as u16)
+                    }
+                    _ => return Err(IoctlError::NotSupported),
+                };
+                copy(arg, &data[..data.len().min(len)])
+            }
+        }
+    }
+}
+
+/// The node of an input device.
+struct Node(Arc<InputDevice>);
+
+/// The data attached to a file opened on an input device. The queue of the
+/// file is removed when it is closed.
+struct Client {
+    device: Arc<InputDevice>,
+    id: usize,
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.device.disconnect(self.id);
+    }
+}
+
+/// Returns the identifier of the file opened on an input device.
+fn client(file: &File) -> Option<usize> {
+    file.data.downcast_ref::<Client>().map(|client| client.id)
+}
+
+impl CharDevice for Node {
+    fn name(&self) -> &str {
+        &self.0.node
+    }
+
+    fn open(&self, _: OpenFlags) -> Result<OpenData, OpenError> {
+        let id = self.0.connect();
+        Ok(Box::new(Client {
+            device: Arc::clone(&self.0),
+            id,
+        }))
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        self.0.read(client(file).ok_or(ReadError::IoError)?, buf)
+    }
+
+    fn write(&self, _: &File, _: &[u8]) -> Result<usize, WriteError> {
+        Err(WriteError::NotImplemented)
+    }
+
+    fn poll(&self, file: &File) -> Events {
+        client(file).map_or(Events::HUP, |client| self.0.poll(client))
+    }
+
+    fn ioctl(&self, file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        let client = client(file).ok_or(IoctlError::NoSuchDevice)?;
+        self.0.ioctl(client, request, arg)
+    }
+}
+
+/// Register a new input device, and create its node `/dev/input/eventN`, `N`
+/// being the number of input devices registered before it.
+///
+/// # Errors
+/// Returns an error if the node of the device could not be registered.
+pub fn register(info: DeviceInfo) -> Result<Arc<InputDevice>, character::RegisterError> {
+    let number = NEXT_NUMBER.fetch_add(1, Ordering::Relaxed);
+    let device = Arc::new(InputDevice {
+        node: format!("input/event{number}"),
+        info,
+        state: Spinlock::new(State {
+            clients: BTreeMap::new(),
+            grab: None,
+            packet: CircularBuffer::new(),
+            keys: [0; bitmap_len(keycode::KEY_MAX)],
+        }),
+        readers: WaitQueue::new(),
+    });
+
+    let id = Identifier {
+        major: MAJOR,
+        minor: FIRST_MINOR + number,
+    };
+    character::register(id, Arc::new(Node(Arc::clone(&device))))?;
+    log::info!("input: {} as /dev/{}", device.info.name, device.node);
+    Ok(device)
+}
+
+/// Returns the length in bytes of a bitmap holding the codes up to the given
+/// maximal code.
+const fn bitmap_len(max: u16) -> usize {
+    max as usize / 8 + 1
+}
+
+/// Returns the current time since the Unix epoch, in seconds and microseconds.
+fn timestamp() -> (u64, u64) {
+    let now = Microsecond::from(date::realtime()).0;
+    (now / 1_000_000, now % 1_000_000)
+}
+
+/// Write the given value at the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not a valid userland
+/// address.
+fn put<T: Copy>(arg: usize, value: &T) -> Result<usize, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    unsafe {
+        user::Object::write(&ptr, value);
+    }
+    Ok(0)
+}
+
+/// Copy the given bytes to the given userland buffer, and returns the number of
+/// bytes copied.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the buffer is not in the userland.
+fn copy(arg: usize, data: &[u8]) -> Result<usize, IoctlError> {
+    let mut buffer =
+        UserStandardBuffer::new(arg, data.len()).map_err(|_| IoctlError::BadAddress)?;
+    buffer.write_buffered(data).ok_or(IoctlError::BadAddress)?;
+    Ok(data.len())
+}
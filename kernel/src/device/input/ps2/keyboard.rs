@@ -0,0 +1,354 @@
+//! The PS/2 keyboard. The scancodes sent by the keyboard are decoded into key
+//! codes, reported as `EV_KEY` events, and translated into bytes for the console
+//! with the current modifiers unless the keyboard is grabbed.
+//!
+//! The keyboard sends the scancode set 2, which is translated to the scancode
+//! set 1 by the controller when the translation is enabled, as it is by most
+//! firmwares. Both sets are decoded: the set 2 scancodes are translated to set 1
+//! like the controller does, so that a single table is needed.
+use super::super::{
+    keycode::{
+        KEY_102ND, KEY_COMPOSE, KEY_DELETE, KEY_DOWN, KEY_END, KEY_F11, KEY_F12, KEY_HOME,
+        KEY_INSERT, KEY_KPENTER, KEY_KPSLASH, KEY_LEFT, KEY_LEFTMETA, KEY_PAGEDOWN, KEY_PAGEUP,
+        KEY_PAUSE, KEY_RIGHT, KEY_RIGHTALT, KEY_RIGHTCTRL, KEY_RIGHTMETA, KEY_SYSRQ, KEY_UP,
+    },
+    keymap::{self, Modifiers, Output},
+    InputDevice, EV_KEY,
+};
+use crate::{
+    device::tty,
+    x86_64::{
+        self,
+        i8042::{self, Channel, ACK},
+    },
+};
+
+/// The command that sets the LEDs of the keyboard, followed by the state of the
+/// LEDs.
+const SET_LEDS: u8 = 0xED;
+
+/// The bits of the state of the LEDs.
+const LED_SCROLL_LOCK: u8 = 1 << 0;
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+/// The prefix of the scancodes of the extended keys.
+const EXTENDED: u8 = 0xE0;
+
+/// The prefix of the sequence sent by the `Pause` key, which has no release.
+const PAUSE: u8 = 0xE1;
+
+/// The prefix of the scancodes of the keys released in the scancode set 2.
+const RELEASE: u8 = 0xF0;
+
+/// The bytes sent by the keyboard which are not scancodes: the errors and the
+/// responses to the commands.
+const IGNORED: [u8; 3] = [0x00, 0xFF, i8042::RESEND];
+
+/// The scancode sets that the keyboard can send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScancodeSet {
+    /// The scancode set 1, sent when the controller translates the scancodes.
+    Set1,
+
+    /// The scancode set 2, the one actually sent by the keyboard.
+    Set2,
+}
+
+/// A PS/2 keyboard and its input device.
+pub struct Keyboard {
+    /// The input device of the keyboard.
+    device: Arc<InputDevice>,
+
+    /// The state of the keyboard, shared with the interrupt handler.
+    state: Spinlock<State>,
+}
+
+/// The state of a keyboard.
+struct State {
+    /// The scancode set sent by the keyboard.
+    set: ScancodeSet,
+
+    /// The scancode being decoded is the one of an extended key.
+    extended: bool,
+
+    /// The scancode being decoded is the one of a key released.
+    released: bool,
+
+    /// The number of bytes left in the sequence of the `Pause` key.
+    pause: u8,
+
+    /// The last key pressed while it is held, to recognize the repeated key
+    /// presses sent by the keyboard.
+    held: Option<u16>,
+
+    /// The modifiers held and the locks enabled.
+    modifiers: Modifiers,
+
+    /// The state of the LEDs to send once the keyboard acknowledged the
+    /// `SET_LEDS` command.
+    leds: Option<u8>,
+}
+
+impl State {
+    /// Decode the given byte sent by the keyboard, and returns the code of the
+    /// key pressed or released with `true` if it was pressed, or `None` if the
+    /// scancode is not complete yet or unknown.
+    fn decode(&mut self, byte: u8) -> Option<(u16, bool)> {
+        if self.pause > 0 {
+            self.pause -= 1;
+            return (self.pause == 0).then_some((KEY_PAUSE, true));
+        }
+
+        match (self.set, byte) {
+            (_, EXTENDED) => self.extended = true,
+            (ScancodeSet::Set1, PAUSE) => self.pause = 5,
+            (ScancodeSet::Set2, PAUSE) => self.pause = 7,
+            (ScancodeSet::Set2, RELEASE) => self.released = true,
+            (ScancodeSet::Set1, byte) => {
+                let extended = core::mem::take(&mut self.extended);
+                return key(byte & 0x7F, extended).map(|code| (code, byte & 0x80 == 0));
+            }
+            (ScancodeSet::Set2, byte) => {
+                let extended = core::mem::take(&mut self.extended);
+                let released = core::mem::take(&mut self.released);
+                return translate(byte)
+                    .and_then(|byte| key(byte, extended))
+                    .map(|code| (code, !released));
+            }
+        }
+        None
+    }
+
+    /// Returns the state of the LEDs for the current locks.
+    fn leds(&self) -> u8 {
+        let mut leds = 0;
+        if self.modifiers.contains(Modifiers::SCROLL_LOCK) {
+            leds |= LED_SCROLL_LOCK;
+        }
+        if self.modifiers.contains(Modifiers::NUM_LOCK) {
+            leds |= LED_NUM_LOCK;
+        }
+        if self.modifiers.contains(Modifiers::CAPS_LOCK) {
+            leds |= LED_CAPS_LOCK;
+        }
+        leds
+    }
+}
+
+impl Keyboard {
+    /// Create the driver of the keyboard sending the given scancode set, which
+    /// reports its events to the given input device.
+    #[must_use]
+    pub fn new(device: Arc<InputDevice>, set: ScancodeSet) -> Self {
+        Self {
+            device,
+            state: Spinlock::new(State {
+                set,
+                extended: false,
+                released: false,
+                pause: 0,
+                held: None,
+                modifiers: Modifiers::empty(),
+                leds: None,
+            }),
+        }
+    }
+
+    /// Handle a byte sent by the keyboard. This is called by the interrupt
+    /// handler of the controller.
+    pub fn receive(&self, byte: u8) {
+        let decoded = x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            if byte == ACK {
+                // Send the state of the LEDs once `SET_LEDS` is acknowledged
+                if let Some(leds) = state.leds.take() {
+                    // SAFETY: The keyboard waits for the state of the LEDs.
+                    _ = unsafe { i8042::send(Channel::First, leds) };
+                }
+                return None;
+            }
+            if IGNORED.contains(&byte) && !state.extended && !state.released {
+                return None;
+            }
+
+            let (code, pressed) = state.decode(byte)?;
+            let repeated = pressed && state.held == Some(code);
+            state.held = pressed.then_some(code);
+            if !repeated && state.modifiers.update(code, pressed) {
+                state.leds = Some(state.leds());
+                // SAFETY: Setting the LEDs has no effect on the system.
+                _ = unsafe { i8042::send(Channel::First, SET_LEDS) };
+            }
+            Some((code, pressed, state.modifiers))
+        });
+
+        if let Some((code, pressed, modifiers)) = decoded {
+            self.device.report(EV_KEY, code, i32::from(pressed));
+            if code == KEY_PAUSE {
+                // The `Pause` key has no release
+                self.device.report(EV_KEY, code, 0);
+            }
+            self.device.sync();
+
+            if pressed && !self.device.grabbed() {
+                console(code, modifiers);
+            }
+        }
+    }
+}
+
+/// Give the bytes sent by the key with the given code pressed with the given
+/// modifiers to the console: the framebuffer console if there is one, or else
+/// the serial console.
+fn console(code: u16, modifiers: Modifiers) {
+    let Some(tty) = tty::vt::tty().or_else(tty::serial::tty) else {
+        return;
+    };
+    match keymap::translate(code, modifiers) {
+        Some(Output::Byte(byte)) if modifiers.alt() => tty.receive(&[0x1B, byte]),
+        Some(Output::Byte(byte)) => tty.receive(&[byte]),
+        Some(Output::Sequence(sequence)) => tty.receive(sequence),
+        None => (),
+    }
+}
+
+/// Returns the codes of the keys that can be reported by a keyboard.
+#[must_use]
+pub fn keys() -> Vec<u16> {
+    let base = (0..0x80).filter_map(|code| key(code, false));
+    let extended = (0..0x80).filter_map(|code| key(code, true));
+    let mut keys: Vec<u16> = base.chain(extended).collect();
+    keys.push(KEY_PAUSE);
+    keys
+}
+
+/// Returns the code of the key with the given scancode of the scancode set 1,
+/// without the release bit, or `None` if the scancode is unknown.
+fn key(scancode: u8, extended: bool) -> Option<u16> {
+    let code = match (extended, scancode) {
+        (false, 0x01..=0x53) => u16::from(scancode),
+        (false, 0x56) => KEY_102ND,
+        (false, 0x57) => KEY_F11,
+        (false, 0x58) => KEY_F12,
+        (true, 0x1C) => KEY_KPENTER,
+        (true, 0x1D) => KEY_RIGHTCTRL,
+        (true, 0x35) => KEY_KPSLASH,
+        (true, 0x37) => KEY_SYSRQ,
+        (true, 0x38) => KEY_RIGHTALT,
+        (true, 0x47) => KEY_HOME,
+        (true, 0x48) => KEY_UP,
+        (true, 0x49) => KEY_PAGEUP,
+        (true, 0x4B) => KEY_LEFT,
+        (true, 0x4D) => KEY_RIGHT,
+        (true, 0x4F) => KEY_END,
+        (true, 0x50) => KEY_DOWN,
+        (true, 0x51) => KEY_PAGEDOWN,
+        (true, 0x52) => KEY_INSERT,
+        (true, 0x53) => KEY_DELETE,
+        (true, 0x5B) => KEY_LEFTMETA,
+        (true, 0x5C) => KEY_RIGHTMETA,
+        (true, 0x5D) => KEY_COMPOSE,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Translate a scancode of the scancode set 2 to the scancode set 1, like the
+/// controller does when the translation is enabled. Returns `None` for the
+/// scancodes of the keys that are not on a standard PC keyboard.
+fn translate(scancode: u8) -> Option<u8> {
+    let translated = match scancode {
+        0x01 => 0x43,
+        0x03 => 0x3F,
+        0x04 => 0x3D,
+        0x05 => 0x3B,
+        0x06 => 0x3C,
+        0x07 => 0x58,
+        0x09 => 0x44,
+        0x0A => 0x42,
+        0x0B => 0x40,
+        0x0C => 0x3E,
+        0x0D => 0x0F,
+        0x0E => 0x29,
+        0x11 => 0x38,
+        0x12 => 0x2A,
+        0x14 => 0x1D,
+        0x15 => 0x10,
+        0x16 => 0x02,
+        0x1A => 0x2C,
+        0x1B => 0x1F,
+        0x1C => 0x1E,
+        0x1D => 0x11,
+        0x1E => 0x03,
+        0x1F => 0x5B,
+        0x21 => 0x2E,
+        0x22 => 0x2D,
+        0x23 => 0x20,
+        0x24 => 0x12,
+        0x25 => 0x05,
+        0x26 => 0x04,
+        0x27 => 0x5C,
+        0x29 => 0x39,
+        0x2A => 0x2F,
+        0x2B => 0x21,
+        0x2C => 0x14,
+        0x2D => 0x13,
+        0x2E => 0x06,
+        0x2F => 0x5D,
+        0x31 => 0x31,
+        0x32 => 0x30,
+        0x33 => 0x23,
+        0x34 => 0x22,
+        0x35 => 0x15,
+        0x36 => 0x07,
+        0x3A => 0x32,
+        0x3B => 0x24,
+        0x3C => 0x16,
+        0x3D => 0x08,
+        0x3E => 0x09,
+        0x41 => 0x33,
+        0x42 => 0x25,
+        0x43 => 0x17,
+        0x44 => 0x18,
+        0x45 => 0x0B,
+        0x46 => 0x0A,
+        0x49 => 0x34,
+        0x4A => 0x35,
+        0x4B => 0x26,
+        0x4C => 0x27,
+        0x4D => 0x19,
+        0x4E => 0x0C,
+        0x52 => 0x28,
+        0x54 => 0x1A,
+        0x55 => 0x0D,
+        0x58 => 0x3A,
+        0x59 => 0x36,
+        0x5A => 0x1C,
+        0x5B => 0x1B,
+        0x5D => 0x2B,
+        0x61 => 0x56,
+        0x66 => 0x0E,
+        0x69 => 0x4F,
+        0x6B => 0x4B,
+        0x6C => 0x47,
+        0x70 => 0x52,
+        0x71 => 0x53,
+        0x72 => 0x50,
+        0x73 => 0x4C,
+        0x74 => 0x4D,
+        0x75 => 0x48,
+        0x76 => 0x01,
+        0x77 => 0x45,
+        0x78 => 0x57,
+        0x79 => 0x4E,
+        0x7A => 0x51,
+        0x7B => 0x4A,
+        0x7C => 0x37,
+        0x7D => 0x49,
+        0x7E => 0x46,
+        0x83 => 0x41,
+        _ => return None,
+    };
+    Some(translated)
+}
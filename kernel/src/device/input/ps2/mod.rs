@@ -0,0 +1,226 @@
+//! The driver of the i8042 PS/2 controller, with a keyboard on its first port
+//! and a mouse on its second port. The devices are set up with polling while
+//! their interrupts are disabled, then each device gets an input device and the
+//! interrupts are enabled: IRQ 1 for the keyboard, and IRQ 12 for the mouse.
+use super::{keycode, DeviceInfo, InputId, BUS_I8042, EV_KEY, EV_REL, REL_WHEEL, REL_X, REL_Y};
+use crate::x86_64::{
+    self,
+    i8042::{self, Channel, Config, Error, KEYBOARD_IRQ, MOUSE_IRQ},
+    irq::Affinity,
+};
+use alloc::vec;
+use keyboard::{Keyboard, ScancodeSet};
+use mouse::Mouse;
+
+pub mod keyboard;
+pub mod mouse;
+
+/// The commands of the devices.
+const RESET: u8 = 0xFF;
+const SET_DEFAULTS: u8 = 0xF6;
+const ENABLE_REPORTING: u8 = 0xF4;
+const SET_SAMPLE_RATE: u8 = 0xF3;
+const GET_ID: u8 = 0xF2;
+
+/// The response of a device to a successful reset.
+const RESET_PASSED: u8 = 0xAA;
+
+/// The number of times the response of a device to a reset is waited for,
+/// since the self test of a keyboard can take about half a second.
+const RESET_RETRIES: usize = 10;
+
+/// The sample rates that enable the wheel of an `IntelliMouse` when set in this
+/// order, and the identifier of the mouse once its wheel is enabled.
+const WHEEL_SEQUENCE: [u8; 3] = [200, 100, 80];
+const WHEEL_ID: u8 = 3;
+
+/// The keyboard connected to the first port, if any.
+static KEYBOARD: Once<Keyboard> = Once::new();
+
+/// The mouse connected to the second port, if any.
+static MOUSE: Once<Mouse> = Once::new();
+
+/// Set up the controller and the devices connected to it, register their input
+/// devices and enable their interrupts. Nothing is done if there is no
+/// controller.
+#[init]
+pub fn setup() {
+    // SAFETY: The controller is only used by this driver, and the interrupts of
+    // its ports are only enabled once the devices are set up.
+    if let Err(error) = x86_64::irq::without(|| unsafe { initialize() }) {
+        log::warn!("ps2: failed to initialize the controller: {:?}", error);
+    }
+}
+
+/// Initialize the controller, its devices and their interrupts.
+///
+/// # Errors
+/// Returns an error if there is no controller or if it does not work. Errors of
+/// the devices are only logged, since the other device may work.
+///
+/// # Safety
+/// The controller must not be used by anything else, and the interrupts must be
+/// disabled.
+unsafe fn initialize() -> Result<(), Error> {
+    i8042::enable(Channel::First, false)?;
+    i8042::enable(Channel::Second, false)?;
+    i8042::flush();
+
+    let mut config = i8042::config()?;
+    config.remove(Config::FIRST_INTERRUPT | Config::SECOND_INTERRUPT);
+    i8042::set_config(config)?;
+    i8042::self_test()?;
+    i8042::set_config(config)?;
+
+    // The second port exists if its clock is enabled when it is enabled
+    i8042::enable(Channel::Second, true)?;
+    let dual = !i8042::config()?.contains(Config::SECOND_CLOCK_DISABLED);
+    i8042::enable(Channel::Second, false)?;
+
+    let mut interrupts = Config::empty();
+    if i8042::test(Channel::First).is_ok() {
+        i8042::enable(Channel::First, true)?;
+        let translation = config.contains(Config::TRANSLATION);
+        match keyboard(translation) {
+            Ok(()) => interrupts |= Config::FIRST_INTERRUPT,
+            Err(error) => log::warn!("ps2: failed to initialize the keyboard: {:?}", error),
+        }
+    }
+    if dual && i8042::test(Channel::Second).is_ok() {
+        i8042::enable(Channel::Second, true)?;
+        match mouse() {
+            Ok(()) => interrupts |= Config::SECOND_INTERRUPT,
+            Err(error) => log::warn!("ps2: failed to initialize the mouse: {:?}", error),
+        }
+    }
+
+    for irq in [KEYBOARD_IRQ, MOUSE_IRQ] {
+        if let Err(error) = x86_64::irq::request(irq, interrupt, Affinity::Bsp) {
+            log::warn!("ps2: failed to request IRQ {}: {:?}", irq, error);
+        }
+    }
+    i8042::flush();
+
+    // Enabling a port changes the configuration, so it is read again
+    let config = i8042::config()?;
+    i8042::set_config(config | interrupts)
+}
+
+/// Set up the keyboard and register its input device.
+///
+/// # Errors
+/// Returns an error if the keyboard does not respond as expected.
+///
+/// # Safety
+/// See [`initialize`].
+unsafe fn keyboard(translation: bool) -> Result<(), Error> {
+    reset(Channel::First)?;
+    i8042::execute(Channel::First, ENABLE_REPORTING)?;
+
+    let (set, name, version) = if translation {
+        (ScancodeSet::Set1, "AT Translated Set 2 keyboard", 0xAB41)
+    } else {
+        (ScancodeSet::Set2, "AT Raw Set 2 keyboard", 0xAB83)
+    };
+    let info = DeviceInfo {
+        name,
+        id: InputId {
+            bustype: BUS_I8042,
+            vendor: 0x0001,
+            product: 0x0001,
+            version,
+        },
+        capabilities: vec![(EV_KEY, keyboard::keys())],
+    };
+
+    match super::register(info) {
+        Ok(device) => _ = KEYBOARD.call_once(|| Keyboard::new(device, set)),
+        Err(error) => log::warn!("ps2: failed to register the keyboard: {:?}", error),
+    }
+    Ok(())
+}
+
+/// Set up the mouse, enable its wheel if it has one, and register its input
+/// device.
+///
+/// # Errors
+/// Returns an error if the mouse does not respond as expected.
+///
+/// # Safety
+/// See [`initialize`].
+unsafe fn mouse() -> Result<(), Error> {
+    reset(Channel::Second)?;
+    // The mouse sends its identifier after its reset
+    _ = i8042::read();
+    i8042::execute(Channel::Second, SET_DEFAULTS)?;
+
+    for rate in WHEEL_SEQUENCE {
+        i8042::execute(Channel::Second, SET_SAMPLE_RATE)?;
+        i8042::execute(Channel::Second, rate)?;
+    }
+    i8042::execute(Channel::Second, GET_ID)?;
+    let wheel = i8042::read()? == WHEEL_ID;
+    i8042::execute(Channel::Second, ENABLE_REPORTING)?;
+
+    let (name, product) = if wheel {
+        ("ImPS/2 Generic Wheel Mouse", 0x0003)
+    } else {
+        ("PS/2 Generic Mouse", 0x0001)
+    };
+    let buttons = vec![keycode::BTN_LEFT, keycode::BTN_RIGHT, keycode::BTN_MIDDLE];
+    let mut axes = vec![REL_X, REL_Y];
+    if wheel {
+        axes.push(REL_WHEEL);
+    }
+    let info = DeviceInfo {
+        name,
+        id: InputId {
+            bustype: BUS_I8042,
+            vendor: 0x0002,
+            product,
+            version: 0x0000,
+        },
+        capabilities: vec![(EV_KEY, buttons), (EV_REL, axes)],
+    };
+
+    match super::register(info) {
+        Ok(device) => _ = MOUSE.call_once(|| Mouse::new(device, wheel)),
+        Err(error) => log::warn!("ps2: failed to register the mouse: {:?}", error),
+    }
+    Ok(())
+}
+
+/// Reset the device connected to the given port, and wait until its self test
+/// passed.
+///
+/// # Errors
+/// Returns an error if the device did not respond, or if its self test failed.
+///
+/// # Safety
+/// See [`initialize`].
+unsafe fn reset(channel: Channel) -> Result<(), Error> {
+    i8042::execute(channel, RESET)?;
+    for _ in 0..RESET_RETRIES {
+        match i8042::read() {
+            Ok(RESET_PASSED) => return Ok(()),
+            Ok(response) => return Err(Error::UnexpectedResponse(response)),
+            Err(Error::Timeout) => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(Error::Timeout)
+}
+
+/// Handle the interrupts of the controller, and give the bytes received to the
+/// device that sent them.
+fn interrupt(_: u8) {
+    // SAFETY: Once the interrupts are enabled, the bytes sent by the devices are
+    // only read by this handler.
+    while let Some((channel, byte)) = unsafe { i8042::receive() } {
+        match (channel, KEYBOARD.get(), MOUSE.get()) {
+            (Channel::First, Some(keyboard), _) => keyboard.receive(byte),
+            (Channel::Second, _, Some(mouse)) => mouse.receive(byte),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! The PS/2 mouse. The mouse sends a packet of 3 bytes each time it moves or a
+//! button changes, or 4 bytes with the movement of the wheel once the wheel was
+//! enabled with the `IntelliMouse` sequence. The packets are reported as `EV_REL`
+//! events for the movements and `EV_KEY` events for the buttons.
+use super::super::{
+    keycode::{BTN_LEFT, BTN_MIDDLE, BTN_RIGHT},
+    InputDevice, EV_KEY, EV_REL, REL_WHEEL, REL_X, REL_Y,
+};
+use crate::x86_64;
+
+/// The bits of the first byte of a packet.
+const LEFT_BUTTON: u8 = 1 << 0;
+const RIGHT_BUTTON: u8 = 1 << 1;
+const MIDDLE_BUTTON: u8 = 1 << 2;
+const ALWAYS_SET: u8 = 1 << 3;
+const X_SIGN: u8 = 1 << 4;
+const Y_SIGN: u8 = 1 << 5;
+const X_OVERFLOW: u8 = 1 << 6;
+const Y_OVERFLOW: u8 = 1 << 7;
+
+/// The buttons of the mouse, with their bit in the first byte of a packet.
+const BUTTONS: [(u8, u16); 3] = [
+    (LEFT_BUTTON, BTN_LEFT),
+    (RIGHT_BUTTON, BTN_RIGHT),
+    (MIDDLE_BUTTON, BTN_MIDDLE),
+];
+
+/// A PS/2 mouse and its input device.
+pub struct Mouse {
+    /// The input device of the mouse.
+    device: Arc<InputDevice>,
+
+    /// The state of the mouse, shared with the interrupt handler.
+    state: Spinlock<State>,
+}
+
+/// The state of a mouse.
+struct State {
+    /// The bytes of the packet being received.
+    packet: [u8; 4],
+
+    /// The number of bytes of the packet received.
+    received: usize,
+
+    /// The size of the packets, 4 if the mouse has a wheel or 3 otherwise.
+    size: usize,
+
+    /// The buttons pressed, with the bits of the first byte of a packet.
+    buttons: u8,
+}
+
+/// A packet decoded.
+struct Packet {
+    /// The buttons that changed, with their new state.
+    buttons: u8,
+    changed: u8,
+
+    /// The movement of the mouse, the Y axis going down like on the screen, and
+    /// the movement of the wheel, positive when scrolling up.
+    dx: i32,
+    dy: i32,
+    wheel: i32,
+}
+
+impl State {
+    /// Add the given byte to the packet being received, and decode the packet
+    /// once it is complete.
+    fn push(&mut self, byte: u8) -> Option<Packet> {
+        // Resynchronize on the first byte of a packet, which has a bit always set
+        if self.received == 0 && byte & ALWAYS_SET == 0 {
+            return None;
+        }
+        self.packet[self.received] = byte;
+        self.received += 1;
+        if self.received < self.size {
+            return None;
+        }
+        self.received = 0;
+
+        let [flags, x, y, z] = self.packet;
+        let buttons = flags & (LEFT_BUTTON | RIGHT_BUTTON | MIDDLE_BUTTON);
+        let changed = buttons ^ self.buttons;
+        self.buttons = buttons;
+
+        let overflow = flags & (X_OVERFLOW | Y_OVERFLOW) != 0;
+        let delta = |value: u8, sign: u8| match (overflow, flags & sign != 0) {
+            (true, _) => 0,
+            (false, true) => i32::from(value) - 256,
+            (false, false) => i32::from(value),
+        };
+
+        // The wheel movement is a signed 4 bits value, positive when scrolling
+        // down, the opposite of the evdev convention.
+        #[allow(clippy::cast_possible_wrap)]
+        let wheel = match self.size {
+            4 => -i32::from(((z << 4) as i8) >> 4),
+            _ => 0,
+        };
+
+        Some(Packet {
+            buttons,
+            changed,
+            dx: delta(x, X_SIGN),
+            dy: -delta(y, Y_SIGN),
+            wheel,
+        })
+    }
+}
+
+impl Mouse {
+    /// Create the driver of the mouse, which reports its events to the given
+    /// input device. The packets have 4 bytes if the wheel of the mouse was
+    /// enabled.
+    #[must_use]
+    pub fn new(device: Arc<InputDevice>, wheel: bool) -> Self {
+        Self {
+            device,
+            state: Spinlock::new(State {
+                packet: [0; 4],
+                received: 0,
+                size: if wheel { 4 } else { 3 },
+                buttons: 0,
+            }),
+        }
+    }
+
+    /// Handle a byte sent by the mouse. This is called by the interrupt handler
+    /// of the controller.
+    pub fn receive(&self, byte: u8) {
+        let Some(packet) = x86_64::irq::without(|| self.state.lock().push(byte)) else {
+            return;
+        };
+
+        for (bit, code) in BUTTONS {
+            if packet.changed & bit != 0 {
+                self.device
+                    .report(EV_KEY, code, i32::from(packet.buttons & bit != 0));
+            }
+        }
+        for (code, value) in [
+            (REL_X, packet.dx),
+            (REL_Y, packet.dy),
+            (REL_WHEEL, packet.wheel),
+        ] {
+            if value != 0 {
+                self.device.report(EV_REL, code, value);
+            }
+        }
+        self.device.sync();
+    }
+}
@@ -1,7 +1,18 @@
+pub mod block;
+pub mod character;
+pub mod fb;
+pub mod input;
+pub mod node;
+pub mod pci;
+pub mod random;
+pub mod rtc;
+pub mod tty;
+pub mod virtio;
+
 /// An device identifier. It is composed of a 32 bits major number and a 32 bits minor number.
 /// The major number identifies the type of the device (for example, a disk driver) and the minor
 /// number identifies the specific device (for example, the first disk is 0, the second is 1, etc).
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Identifier {
     pub major: u32,
     pub minor: u32,
@@ -48,6 +59,33 @@ impl Device {
         }
     }
 
+    /// Returns the device identifier encoded like the Linux `dev_t` type, as produced by
+    /// the `makedev` macro of the GNU C library. This encoding is the one expected by
+    /// the userland in the `st_dev` and `st_rdev` fields of the `stat` structure.
+    #[must_use]
+    pub fn encode(&self) -> u64 {
+        let major = u64::from(self.major());
+        let minor = u64::from(self.minor());
+        (major & 0xFFFF_F000) << 32
+            | (major & 0x0000_0FFF) << 8
+            | (minor & 0xFFFF_FF00) << 12
+            | (minor & 0x0000_00FF)
+    }
+
+    /// Extracts the major number from a device identifier encoded with [`Device::encode`].
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn decode_major(dev: u64) -> u32 {
+        (((dev >> 32) & 0xFFFF_F000) | ((dev >> 8) & 0x0000_0FFF)) as u32
+    }
+
+    /// Extracts the minor number from a device identifier encoded with [`Device::encode`].
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn decode_minor(dev: u64) -> u32 {
+        (((dev >> 12) & 0xFFFF_FF00) | (dev & 0x0000_00FF)) as u32
+    }
+
     /// Return the next device identifier. It will share the same major number, but the minor
     /// number will be incremented by one.
     ///
@@ -0,0 +1,136 @@
+//! Device nodes. When a driver registers a device, a node is created for it in
+//! the `/dev` directory of the root filesystem, so that the userland can open the
+//! device without having to know its major and minor numbers. This plays the role
+//! of `devtmpfs` on Linux, but the nodes are created directly in the root ramfs.
+use super::Device;
+use crate::vfs::{self, dentry::Dentry};
+
+/// The name of the directory, under the root directory, that contains the nodes.
+const DIRECTORY: &str = "dev";
+
+/// Create a node with the given name for the given device. The name may contain
+/// slashes to put the node in a subdirectory of `/dev`, like `input/event0`. The
+/// `/dev` directory and the subdirectories are created if they do not exist yet.
+///
+/// # Errors
+/// See [`NodeError`] for the possible errors.
+pub fn create(name: &str, device: Device) -> Result<(), NodeError> {
+    let (directory, name) = parent(name)?;
+    let name = vfs::Name::new(String::from(name)).map_err(|_| NodeError::InvalidName)?;
+
+    directory
+        .inode()
+        .as_directory()
+        .ok_or(NodeError::NotADirectory)?
+        .mknod(directory.inode(), name.as_str(), device)
+        .map_err(|_| NodeError::AlreadyExists)?;
+    directory.forget_negative(&name);
+    directory.dirtying_inode();
+    Ok(())
+}
+
+/// Remove the node with the given name. Tasks that opened the node keep their
+/// file open, but requests made through it will fail since the device is gone.
+///
+/// # Errors
+/// See [`NodeError`] for the possible errors.
+pub fn remove(name: &str) -> Result<(), NodeError> {
+    let (directory, name) = parent(name)?;
+    let name = vfs::Name::new(String::from(name)).map_err(|_| NodeError::InvalidName)?;
+
+    directory
+        .inode()
+        .as_directory()
+        .ok_or(NodeError::NotADirectory)?
+        .unlink(directory.inode(), name.as_str())
+        .map_err(|_| NodeError::NotFound)?;
+    _ = directory.disconnect_child(&name);
+    directory.dirtying_inode();
+    Ok(())
+}
+
+/// Create a directory with the given name in `/dev` if it does not exist yet,
+/// and returns its dentry. This is used for the mountpoints of the filesystems
+/// that provide device nodes, like `/dev/pts`.
+///
+/// # Errors
+/// See [`NodeError`] for the possible errors.
+pub fn create_directory(name: &str) -> Result<Arc<Dentry>, NodeError> {
+    subdirectory(&directory()?, name)
+}
+
+/// Returns the dentry of the directory that contains the node with the given
+/// name, creating it if needed, and the name of the node in this directory.
+///
+/// # Errors
+/// See [`NodeError`] for the possible errors.
+fn parent(name: &str) -> Result<(Arc<Dentry>, &str), NodeError> {
+    let mut directory = directory()?;
+    let mut components = name.split('/').peekable();
+    while let Some(component) = components.next() {
+        if components.peek().is_none() {
+            return Ok((directory, component));
+        }
+        directory = subdirectory(&directory, component)?;
+    }
+    Err(NodeError::InvalidName)
+}
+
+/// Returns the dentry of the `/dev` directory, creating it if needed.
+///
+/// # Errors
+/// Returns an error if the root filesystem is not mounted yet, or if `/dev`
+/// exists but is not a directory.
+fn directory() -> Result<Arc<Dentry>, NodeError> {
+    let root = vfs::dentry::ROOT.get().ok_or(NodeError::NoRoot)?;
+    subdirectory(root, DIRECTORY)
+}
+
+/// Returns the dentry of the directory with the given name in the given
+/// directory, creating it if needed.
+///
+/// # Errors
+/// Returns an error if the name is invalid, or if an entry with this name
+/// exists but is not a directory.
+fn subdirectory(parent: &Arc<Dentry>, name: &str) -> Result<Arc<Dentry>, NodeError> {
+    let name = vfs::Name::new(String::from(name)).map_err(|_| NodeError::InvalidName)?;
+
+    let directory = match Dentry::fetch(parent, &name) {
+        Ok(dentry) => dentry,
+        Err(vfs::dentry::FetchError::NotFound) => {
+            // Another task may have created the directory in the meantime: the
+            // error is ignored and the fetch below will tell if it exists.
+            _ = parent
+                .inode()
+                .as_directory()
+                .ok_or(NodeError::NotADirectory)?
+                .mkdir(parent.inode(), name.as_str());
+            parent.forget_negative(&name);
+            Dentry::fetch(parent, &name).map_err(|_| NodeError::NotADirectory)?
+        }
+        Err(_) => return Err(NodeError::NotADirectory),
+    };
+
+    if directory.inode().kind != vfs::inode::Kind::Directory {
+        return Err(NodeError::NotADirectory);
+    }
+    Ok(directory)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeError {
+    /// The root filesystem is not mounted yet.
+    NoRoot,
+
+    /// The name of the node is not a valid file name.
+    InvalidName,
+
+    /// `/dev`, or the directory to create in it, is not a directory.
+    NotADirectory,
+
+    /// A node with the same name already exists.
+    AlreadyExists,
+
+    /// There is no node with the given name.
+    NotFound,
+}
@@ -0,0 +1,178 @@
+//! Access to the configuration space of PCI functions. When the ACPI MCFG table
+//! describes the bus of a function, its configuration space is accessed through
+//! the memory mapped ECAM region of the bus, which exposes the 4 KiB extended
+//! configuration space of PCI Express. Otherwise, the legacy mechanism is used:
+//! the address of a register is written to the `0xCF8` port, and the register is
+//! then accessed through the `0xCFC` port. It only reaches the first 256 bytes
+//! of the configuration space of the functions of the first segment.
+use super::Address;
+use crate::{
+    acpi::mcfg,
+    x86_64::io::{Port, IO},
+};
+use addr::{phys::Physical, virt::Virtual};
+
+/// The size of the configuration space reachable with the legacy mechanism, and
+/// of the extended configuration space of PCI Express.
+pub const LEGACY_SIZE: u16 = 256;
+pub const EXTENDED_SIZE: u16 = 4096;
+
+/// The ports of the legacy mechanism.
+const LEGACY_ADDRESS: Port<u32> = Port::new(0xCF8);
+const LEGACY_DATA: u16 = 0xCFC;
+
+/// The bit of the legacy address that enables the access.
+const LEGACY_ENABLE: u32 = 1 << 31;
+
+/// The physical memory that is always mapped by the HHDM. ECAM regions above it
+/// are not used.
+pub const HHDM_LIMIT: u64 = 4 * 1024 * 1024 * 1024;
+
+/// The ECAM regions, read from the MCFG table.
+static REGIONS: Once<Vec<mcfg::Region>> = Once::new();
+
+/// Serializes the accesses with the legacy mechanism, which need to write the
+/// address port before accessing the data port.
+static LEGACY: Spinlock<()> = Spinlock::new(());
+
+/// Read the ECAM regions from the MCFG table. Regions outside of the memory
+/// mapped by the HHDM are ignored.
+pub fn setup() {
+    REGIONS.call_once(|| {
+        mcfg::regions()
+            .into_iter()
+            .filter(|region| {
+                let buses = u64::from(region.end_bus.saturating_sub(region.start_bus)) + 1;
+                let end = region.base + (buses << 20);
+                let mapped = end <= HHDM_LIMIT;
+                if !mapped {
+                    log::warn!("pci: ECAM region at {:#x} is not mapped", region.base);
+                }
+                mapped
+            })
+            .inspect(|region| {
+                log::info!(
+                    "pci: ECAM for segment {} buses {:02x}-{:02x} at {:#x}",
+                    region.segment,
+                    region.start_bus,
+                    region.end_bus,
+                    region.base
+                );
+            })
+            .collect()
+    });
+}
+
+/// Returns the size of the configuration space of the given function that can
+/// be accessed.
+#[must_use]
+pub fn size(address: Address) -> u16 {
+    match region(address) {
+        Some(_) => EXTENDED_SIZE,
+        None => LEGACY_SIZE,
+    }
+}
+
+/// Returns the ECAM region mapping the configuration space of the given function.
+fn region(address: Address) -> Option<&'static mcfg::Region> {
+    REGIONS
+        .get()?
+        .iter()
+        .find(|region| region.contains(address.segment, address.bus))
+}
+
+/// Returns a pointer to the given register of the given function in its ECAM
+/// region, if any.
+#[allow(clippy::cast_possible_truncation)]
+fn ecam<T>(address: Address, offset: u16) -> Option<*mut T> {
+    let region = region(address)?;
+    let physical = region.base
+        + (u64::from(address.bus - region.start_bus) << 20)
+        + (u64::from(address.device) << 15)
+        + (u64::from(address.function) << 12)
+        + u64::from(offset);
+    Some(Virtual::from(Physical::new(physical as usize)).as_mut_ptr())
+}
+
+/// Read the register of the given type at the given offset of the configuration
+/// space of the given function. The offset must be aligned on the size of the
+/// register. Returns `None` if the register is beyond the accessible
+/// configuration space.
+fn read<T: IO + Copy>(address: Address, offset: u16) -> Option<T> {
+    if let Some(pointer) = ecam::<T>(address, offset) {
+        return Some(unsafe { pointer.read_volatile() });
+    }
+    if address.segment != 0 || offset >= LEGACY_SIZE {
+        return None;
+    }
+
+    let _guard = LEGACY.lock();
+    unsafe {
+        LEGACY_ADDRESS.write(legacy_address(address, offset));
+        Some(T::read(LEGACY_DATA + (offset & 3)))
+    }
+}
+
+/// Write the register of the given type at the given offset of the configuration
+/// space of the given function. Writes beyond the accessible configuration space
+/// are ignored.
+fn write<T: IO + Copy>(address: Address, offset: u16, value: T) {
+    if let Some(pointer) = ecam::<T>(address, offset) {
+        unsafe { pointer.write_volatile(value) };
+        return;
+    }
+    if address.segment != 0 || offset >= LEGACY_SIZE {
+        return;
+    }
+
+    let _guard = LEGACY.lock();
+    unsafe {
+        LEGACY_ADDRESS.write(legacy_address(address, offset));
+        T::write(LEGACY_DATA + (offset & 3), value);
+    }
+}
+
+/// Returns the value written to the address port of the legacy mechanism to
+/// access the given register.
+fn legacy_address(address: Address, offset: u16) -> u32 {
+    LEGACY_ENABLE
+        | u32::from(address.bus) << 16
+        | u32::from(address.device) << 11
+        | u32::from(address.function) << 8
+        | u32::from(offset & 0xFC)
+}
+
+/// Read the 8-bit register at the given offset of the given function. Registers
+/// beyond the accessible configuration space read as ones, like the registers
+/// of a function that does not exist.
+#[must_use]
+pub fn read_u8(address: Address, offset: u16) -> u8 {
+    read(address, offset).unwrap_or(u8::MAX)
+}
+
+/// Read the 16-bit register at the given offset of the given function.
+#[must_use]
+pub fn read_u16(address: Address, offset: u16) -> u16 {
+    read(address, offset).unwrap_or(u16::MAX)
+}
+
+/// Read the 32-bit register at the given offset of the given function.
+#[must_use]
+pub fn read_u32(address: Address, offset: u16) -> u32 {
+    read(address, offset).unwrap_or(u32::MAX)
+}
+
+/// Write the 8-bit register at the given offset of the given function.
+pub fn write_u8(address: Address, offset: u16, value: u8) {
+    write(address, offset, value);
+}
+
+/// Write the 16-bit register at the given offset of the given function.
+pub fn write_u16(address: Address, offset: u16, value: u16) {
+    write(address, offset, value);
+}
+
+/// Write the 32-bit register at the given offset of the given function.
+pub fn write_u32(address: Address, offset: u16, value: u32) {
+    write(address, offset, value);
+}
@@ -0,0 +1,696 @@
+//! The PCI bus. Its functions are enumerated at boot, starting from the root bus
+//! and following the PCI-to-PCI bridges to the buses behind them. For each
+//! function, the base address registers (BARs) are decoded and sized, and the
+//! capability list is read, which tells whether the function supports MSI or
+//! MSI-X interrupts.
+//!
+//! Drivers register themselves with [`register`], giving the identifiers of the
+//! functions they support: the probe function of a driver is called for each
+//! matching function without a driver, whether the function is enumerated before
+//! or after the driver is registered.
+use crate::x86_64::paging::{self, table::PageEntryFlags, MapError, KERNEL_PML4};
+use addr::{frame::Frame, phys::Physical, virt::Virtual};
+use core::fmt;
+
+pub mod config;
+
+/// The offsets of the registers of the common header of the configuration space.
+pub const VENDOR_ID: u16 = 0x00;
+pub const DEVICE_ID: u16 = 0x02;
+pub const COMMAND: u16 = 0x04;
+pub const STATUS: u16 = 0x06;
+pub const REVISION_ID: u16 = 0x08;
+pub const PROG_IF: u16 = 0x09;
+pub const SUBCLASS: u16 = 0x0A;
+pub const CLASS: u16 = 0x0B;
+pub const HEADER_TYPE: u16 = 0x0E;
+pub const BAR0: u16 = 0x10;
+pub const CAPABILITIES: u16 = 0x34;
+pub const INTERRUPT_LINE: u16 = 0x3C;
+pub const INTERRUPT_PIN: u16 = 0x3D;
+
+/// The offset of the secondary bus number register of a PCI-to-PCI bridge.
+pub const SECONDARY_BUS: u16 = 0x19;
+
+/// The bits of the command register.
+pub const COMMAND_IO: u16 = 1 << 0;
+pub const COMMAND_MEMORY: u16 = 1 << 1;
+pub const COMMAND_BUS_MASTER: u16 = 1 << 2;
+pub const COMMAND_INTX_DISABLE: u16 = 1 << 10;
+
+/// The bit of the status register telling that the function has a capability
+/// list.
+pub const STATUS_CAPABILITIES: u16 = 1 << 4;
+
+/// The identifiers of the capabilities used by the kernel.
+pub const CAPABILITY_MSI: u8 = 0x05;
+pub const CAPABILITY_VENDOR: u8 = 0x09;
+pub const CAPABILITY_MSIX: u8 = 0x11;
+
+/// The header types of a function, in the low bits of the header type register,
+/// and the bit telling that a device has several functions.
+const HEADER_GENERAL: u8 = 0x00;
+const HEADER_BRIDGE: u8 = 0x01;
+const HEADER_MULTIFUNCTION: u8 = 0x80;
+
+/// The number of devices on a bus, and of functions of a device.
+const DEVICES_PER_BUS: u8 = 32;
+const FUNCTIONS_PER_DEVICE: u8 = 8;
+
+/// The maximal number of capabilities read, to avoid looping on a corrupted
+/// capability list.
+const MAX_CAPABILITIES: usize = 48;
+
+/// The functions found on the PCI bus.
+static DEVICES: Spinlock<Vec<Arc<Device>>> = Spinlock::new(Vec::new());
+
+/// The registered drivers.
+static DRIVERS: Spinlock<Vec<&'static Driver>> = Spinlock::new(Vec::new());
+
+/// The location of a function on the PCI bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address {
+    pub segment: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{}",
+            self.segment, self.bus, self.device, self.function
+        )
+    }
+}
+
+/// A decoded base address register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bar {
+    /// A memory region, which may use two BARs if its address is 64-bit wide.
+    Memory {
+        address: u64,
+        size: u64,
+        prefetchable: bool,
+        wide: bool,
+    },
+
+    /// A range of I/O ports.
+    Io { port: u16, size: u16 },
+}
+
+/// A capability of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capability {
+    /// The identifier of the capability.
+    pub id: u8,
+
+    /// The offset of the capability in the configuration space.
+    pub offset: u16,
+}
+
+/// The MSI capability of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Msi {
+    /// The offset of the capability in the configuration space.
+    pub offset: u16,
+
+    /// The number of vectors the function can use.
+    pub vectors: u8,
+
+    /// Whether the message address can be 64-bit wide.
+    pub wide: bool,
+
+    /// Whether each vector can be masked.
+    pub maskable: bool,
+}
+
+/// The MSI-X capability of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Msix {
+    /// The offset of the capability in the configuration space.
+    pub offset: u16,
+
+    /// The number of entries of the table.
+    pub size: u16,
+
+    /// The BAR containing the table of vectors, and the offset of the table in it.
+    pub table_bar: u8,
+    pub table_offset: u32,
+
+    /// The BAR containing the pending bit array, and the offset of the array in it.
+    pub pba_bar: u8,
+    pub pba_offset: u32,
+}
+
+/// A function of a PCI device.
+pub struct Device {
+    /// The location of the function.
+    pub address: Address,
+
+    /// The vendor and the device identifiers of the function.
+    pub vendor: u16,
+    pub device: u16,
+
+    /// The class, subclass and programming interface of the function, and its
+    /// revision.
+    pub class: u8,
+    pub subclass: u8,
+    pub interface: u8,
+    pub revision: u8,
+
+    /// The header type of the function, without the multi-function bit.
+    pub header: u8,
+
+    /// The legacy interrupt line and pin of the function. A pin of zero means
+    /// that the function does not use legacy interrupts.
+    pub interrupt_line: u8,
+    pub interrupt_pin: u8,
+
+    /// The base address registers of the function. The second register used by
+    /// a 64-bit memory BAR is `None`.
+    pub bars: [Option<Bar>; 6],
+
+    /// The capabilities of the function.
+    pub capabilities: Vec<Capability>,
+
+    /// The driver bound to the function, if any.
+    driver: Spinlock<Option<&'static Driver>>,
+}
+
+impl Device {
+    /// Read the 8-bit register at the given offset of the configuration space.
+    #[must_use]
+    pub fn read_u8(&self, offset: u16) -> u8 {
+        config::read_u8(self.address, offset)
+    }
+
+    /// Read the 16-bit register at the given offset of the configuration space.
+    #[must_use]
+    pub fn read_u16(&self, offset: u16) -> u16 {
+        config::read_u16(self.address, offset)
+    }
+
+    /// Read the 32-bit register at the given offset of the configuration space.
+    #[must_use]
+    pub fn read_u32(&self, offset: u16) -> u32 {
+        config::read_u32(self.address, offset)
+    }
+
+    /// Write the 8-bit register at the given offset of the configuration space.
+    pub fn write_u8(&self, offset: u16, value: u8) {
+        config::write_u8(self.address, offset, value);
+    }
+
+    /// Write the 16-bit register at the given offset of the configuration space.
+    pub fn write_u16(&self, offset: u16, value: u16) {
+        config::write_u16(self.address, offset, value);
+    }
+
+    /// Write the 32-bit register at the given offset of the configuration space.
+    pub fn write_u32(&self, offset: u16, value: u32) {
+        config::write_u32(self.address, offset, value);
+    }
+
+    /// Set the given bits of the command register, for example to enable the
+    /// decoding of the memory BARs and bus mastering.
+    pub fn enable(&self, bits: u16) {
+        let command = self.read_u16(COMMAND);
+        self.write_u16(COMMAND, command | bits);
+    }
+
+    /// Clear the given bits of the command register.
+    pub fn disable(&self, bits: u16) {
+        let command = self.read_u16(COMMAND);
+        self.write_u16(COMMAND, command & !bits);
+    }
+
+    /// Returns the capabilities of the function with the given identifier.
+    pub fn capabilities(&self, id: u8) -> impl Iterator<Item = Capability> + '_ {
+        self.capabilities
+            .iter()
+            .copied()
+            .filter(move |capability| capability.id == id)
+    }
+
+    /// Returns the MSI capability of the function, if any.
+    #[must_use]
+    pub fn msi(&self) -> Option<Msi> {
+        let offset = self.capabilities(CAPABILITY_MSI).next()?.offset;
+        let control = self.read_u16(offset + 2);
+        Some(Msi {
+            offset,
+            vectors: 1 << ((control >> 1) & 0x7).min(5),
+            wide: control & (1 << 7) != 0,
+            maskable: control & (1 << 8) != 0,
+        })
+    }
+
+    /// Returns the MSI-X capability of the function, if any.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn msix(&self) -> Option<Msix> {
+        let offset = self.capabilities(CAPABILITY_MSIX).next()?.offset;
+        let control = self.read_u16(offset + 2);
+        let table = self.read_u32(offset + 4);
+        let pba = self.read_u32(offset + 8);
+        Some(Msix {
+            offset,
+            size: (control & 0x7FF) + 1,
+            table_bar: (table & 0x7) as u8,
+            table_offset: table & !0x7,
+            pba_bar: (pba & 0x7) as u8,
+            pba_offset: pba & !0x7,
+        })
+    }
+
+    /// Returns the virtual address at which the memory BAR with the given index
+    /// can be accessed, or `None` if it is not a memory BAR. The HHDM only maps
+    /// the first 4 GiB of physical memory: the pages of a BAR above this limit
+    /// are mapped in the HHDM window without caching, as expected for registers.
+    ///
+    /// # Errors
+    /// Returns `ProbeError::OutOfMemory` if the page tables needed to map the BAR
+    /// cannot be allocated.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn map_bar(&self, index: usize) -> Result<Option<Virtual>, ProbeError> {
+        let Some(Bar::Memory { address, size, .. }) = self.bars.get(index).copied().flatten()
+        else {
+            return Ok(None);
+        };
+
+        let physical = Physical::new(address as usize);
+        if address + size > config::HHDM_LIMIT {
+            let flags = PageEntryFlags::WRITABLE
+                | PageEntryFlags::NO_CACHE
+                | PageEntryFlags::NO_EXECUTE;
+            let end = Physical::new((address + size) as usize);
+            for frame in Frame::truncate(physical)..Frame::upper(end) {
+                let page = Virtual::from(frame.addr());
+                match unsafe { paging::map(&KERNEL_PML4, page, frame, flags) } {
+                    Ok(()) | Err(MapError::AlreadyMapped) => {}
+                    Err(MapError::OutOfMemory) => return Err(ProbeError::OutOfMemory),
+                }
+            }
+        }
+        Ok(Some(Virtual::from(physical)))
+    }
+
+    /// Returns the name of the driver bound to the function, if any.
+    #[must_use]
+    pub fn driver(&self) -> Option<&'static str> {
+        self.driver.lock().map(|driver| driver.name)
+    }
+}
+
+impl fmt::Debug for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Device")
+            .field("address", &self.address)
+            .field("vendor", &self.vendor)
+            .field("device", &self.device)
+            .field("class", &self.class)
+            .field("subclass", &self.subclass)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An identifier of the functions supported by a driver. A field set to `None`
+/// matches any value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Id {
+    pub vendor: Option<u16>,
+    pub device: Option<u16>,
+    pub class: Option<u8>,
+    pub subclass: Option<u8>,
+}
+
+impl Id {
+    /// An identifier matching the functions with the given vendor and device
+    /// identifiers.
+    #[must_use]
+    pub const fn device(vendor: u16, device: u16) -> Self {
+        Self {
+            vendor: Some(vendor),
+            device: Some(device),
+            class: None,
+            subclass: None,
+        }
+    }
+
+    /// An identifier matching the functions with the given class and subclass.
+    #[must_use]
+    pub const fn class(class: u8, subclass: u8) -> Self {
+        Self {
+            vendor: None,
+            device: None,
+            class: Some(class),
+            subclass: Some(subclass),
+        }
+    }
+
+    /// Returns `true` if the given function matches this identifier.
+    #[must_use]
+    pub fn matches(&self, device: &Device) -> bool {
+        self.vendor.map_or(true, |vendor| vendor == device.vendor)
+            && self.device.map_or(true, |id| id == device.device)
+            && self.class.map_or(true, |class| class == device.class)
+            && self
+                .subclass
+                .map_or(true, |subclass| subclass == device.subclass)
+    }
+}
+
+/// A driver of PCI functions.
+pub struct Driver {
+    /// The name of the driver.
+    pub name: &'static str,
+
+    /// The identifiers of the functions supported by the driver.
+    pub ids: &'static [Id],
+
+    /// Initialize the driver for the given function. If an error is returned,
+    /// the function is left without a driver.
+    pub probe: fn(device: &Arc<Device>) -> Result<(), ProbeError>,
+}
+
+/// Register a driver, and probe it for each matching function that does not have
+/// a driver yet.
+pub fn register(driver: &'static Driver) {
+    DRIVERS.lock().push(driver);
+    for device in devices() {
+        if driver.ids.iter().any(|id| id.matches(&device)) {
+            bind(&device, driver);
+        }
+    }
+}
+
+/// Returns the functions found on the PCI bus.
+#[must_use]
+pub fn devices() -> Vec<Arc<Device>> {
+    DEVICES.lock().clone()
+}
+
+/// Probe the given driver for the given function, and bind them if the probe
+/// succeeds. Nothing is done if the function already has a driver.
+fn bind(device: &Arc<Device>, driver: &'static Driver) {
+    if device.driver.lock().is_some() {
+        return;
+    }
+    match (driver.probe)(device) {
+        Ok(()) => {
+            log::info!("pci: {} bound to {}", device.address, driver.name);
+            *device.driver.lock() = Some(driver);
+        }
+        Err(error) => {
+            log::warn!(
+                "pci: {} failed to probe {}: {:?}",
+                driver.name,
+                device.address,
+                error
+            );
+        }
+    }
+}
+
+/// Read the header of the function at the given address.
+fn read_device(address: Address) -> Device {
+    let header = config::read_u8(address, HEADER_TYPE) & !HEADER_MULTIFUNCTION;
+    let mut device = Device {
+        address,
+        vendor: config::read_u16(address, VENDOR_ID),
+        device: config::read_u16(address, DEVICE_ID),
+        class: config::read_u8(address, CLASS),
+        subclass: config::read_u8(address, SUBCLASS),
+        interface: config::read_u8(address, PROG_IF),
+        revision: config::read_u8(address, REVISION_ID),
+        header,
+        interrupt_line: config::read_u8(address, INTERRUPT_LINE),
+        interrupt_pin: config::read_u8(address, INTERRUPT_PIN),
+        bars: [None; 6],
+        capabilities: read_capabilities(address),
+        driver: Spinlock::new(None),
+    };
+
+    // General devices have 6 BARs, and bridges only 2.
+    let count = match header {
+        HEADER_GENERAL => 6,
+        HEADER_BRIDGE => 2,
+        _ => 0,
+    };
+    let mut index = 0;
+    while index < count {
+        let bar = read_bar(address, index);
+        device.bars[index] = bar;
+        index += match bar {
+            Some(Bar::Memory { wide: true, .. }) => 2,
+            _ => 1,
+        };
+    }
+    device
+}
+
+/// Decode and size the BAR at the given index of the given function. The size
+/// is found by writing ones to the BAR and reading back the bits that stuck,
+/// while the decoding of the BARs is disabled. Returns `None` if the BAR is
+/// not implemented.
+#[allow(clippy::cast_possible_truncation)]
+fn read_bar(address: Address, index: usize) -> Option<Bar> {
+    let offset = BAR0 + 4 * index as u16;
+    let low = config::read_u32(address, offset);
+    let io = low & 1 != 0;
+    let wide = !io && (low >> 1) & 0x3 == 0x2;
+
+    let command = config::read_u16(address, COMMAND);
+    config::write_u16(address, COMMAND, command & !(COMMAND_IO | COMMAND_MEMORY));
+
+    config::write_u32(address, offset, u32::MAX);
+    let low_mask = config::read_u32(address, offset);
+    config::write_u32(address, offset, low);
+
+    let (high, high_mask) = if wide {
+        let high = config::read_u32(address, offset + 4);
+        config::write_u32(address, offset + 4, u32::MAX);
+        let mask = config::read_u32(address, offset + 4);
+        config::write_u32(address, offset + 4, high);
+        (high, mask)
+    } else {
+        (0, u32::MAX)
+    };
+    config::write_u16(address, COMMAND, command);
+
+    if io {
+        let mask = low_mask & !0x3;
+        if mask == 0 {
+            return None;
+        }
+        let size = (!mask).wrapping_add(1) & 0xFFFF;
+        return Some(Bar::Io {
+            port: (low & !0x3) as u16,
+            size: size as u16,
+        });
+    }
+
+    let mask = u64::from(high_mask) << 32 | u64::from(low_mask & !0xF);
+    if mask == 0 || mask == u64::from(u32::MAX) << 32 {
+        return None;
+    }
+    Some(Bar::Memory {
+        address: u64::from(high) << 32 | u64::from(low & !0xF),
+        size: (!mask).wrapping_add(1),
+        prefetchable: low & 0x8 != 0,
+        wide,
+    })
+}
+
+/// Read the capability list of the function at the given address.
+fn read_capabilities(address: Address) -> Vec<Capability> {
+    let mut capabilities = Vec::new();
+    if config::read_u16(address, STATUS) & STATUS_CAPABILITIES == 0 {
+        return capabilities;
+    }
+
+    // The two low bits of the pointers are reserved.
+    let mut offset = u16::from(config::read_u8(address, CAPABILITIES) & !0x3);
+    while offset != 0 && capabilities.len() < MAX_CAPABILITIES {
+        capabilities.push(Capability {
+            id: config::read_u8(address, offset),
+            offset,
+        });
+        offset = u16::from(config::read_u8(address, offset + 1) & !0x3);
+    }
+    capabilities
+}
+
+/// Enumerate the devices of the given bus, and recursively the buses behind the
+/// bridges found on it. The buses already scanned are skipped.
+fn scan_bus(segment: u16, bus: u8, scanned: &mut [bool; 256], devices: &mut Vec<Arc<Device>>) {
+    if core::mem::replace(&mut scanned[usize::from(bus)], true) {
+        return;
+    }
+
+    for slot in 0..DEVICES_PER_BUS {
+        let first = Address {
+            segment,
+            bus,
+            device: slot,
+            function: 0,
+        };
+        if config::read_u16(first, VENDOR_ID) == u16::MAX {
+            continue;
+        }
+
+        let functions = if config::read_u8(first, HEADER_TYPE) & HEADER_MULTIFUNCTION == 0 {
+            1
+        } else {
+            FUNCTIONS_PER_DEVICE
+        };
+        for function in 0..functions {
+            let address = Address { function, ..first };
+            if config::read_u16(address, VENDOR_ID) == u16::MAX {
+                continue;
+            }
+
+            let device = read_device(address);
+            if device.header == HEADER_BRIDGE {
+                let secondary = config::read_u8(address, SECONDARY_BUS);
+                scan_bus(segment, secondary, scanned, devices);
+            }
+            devices.push(Arc::new(device));
+        }
+    }
+}
+
+/// Returns a human-readable name of the given class.
+#[must_use]
+pub fn class_name(class: u8) -> &'static str {
+    match class {
+        0x01 => "mass storage controller",
+        0x02 => "network controller",
+        0x03 => "display controller",
+        0x04 => "multimedia controller",
+        0x05 => "memory controller",
+        0x06 => "bridge",
+        0x07 => "communication controller",
+        0x08 => "system peripheral",
+        0x09 => "input device controller",
+        0x0C => "serial bus controller",
+        0x0D => "wireless controller",
+        0x10 => "encryption controller",
+        0x11 => "signal processing controller",
+        _ => "unclassified device",
+    }
+}
+
+/// Log the given function and its BARs.
+fn log_device(device: &Device) {
+    log::info!(
+        "pci: {} [{:04x}:{:04x}] class {:02x}{:02x}{:02x}: {}",
+        device.address,
+        device.vendor,
+        device.device,
+        device.class,
+        device.subclass,
+        device.interface,
+        class_name(device.class)
+    );
+    for (index, bar) in device.bars.iter().enumerate() {
+        match bar {
+            Some(Bar::Memory {
+                address,
+                size,
+                prefetchable,
+                wide,
+            }) => log::info!(
+                "pci:   BAR{}: memory at {:#x} ({} bytes{}{})",
+                index,
+                address,
+                size,
+                if *wide { ", 64-bit" } else { "" },
+                if *prefetchable { ", prefetchable" } else { "" },
+            ),
+            Some(Bar::Io { port, size }) => {
+                log::info!("pci:   BAR{}: I/O ports at {:#x} ({})", index, port, size);
+            }
+            None => {}
+        }
+    }
+    if let Some(msi) = device.msi() {
+        log::info!("pci:   MSI with {} vectors", msi.vectors);
+    }
+    if let Some(msix) = device.msix() {
+        log::info!("pci:   MSI-X with {} vectors", msix.size);
+    }
+}
+
+/// Enumerate the functions on the PCI bus and log them. The buses of each
+/// segment described by the MCFG table are enumerated, or only the first
+/// segment if there is no MCFG table.
+#[init]
+pub fn setup() {
+    config::setup();
+
+    let mut roots: Vec<(u16, u8)> = crate::acpi::mcfg::regions()
+        .iter()
+        .map(|region| (region.segment, region.start_bus))
+        .collect();
+    if roots.is_empty() {
+        roots.push((0, 0));
+    }
+
+    let mut devices = Vec::new();
+    for (segment, bus) in roots {
+        let mut scanned = [false; 256];
+        scan_bus(segment, bus, &mut scanned, &mut devices);
+
+        // A host bridge with several functions means that there are several host
+        // controllers, each function giving access to the bus of its number.
+        let host = Address {
+            segment,
+            bus,
+            device: 0,
+            function: 0,
+        };
+        if config::read_u8(host, HEADER_TYPE) & HEADER_MULTIFUNCTION != 0 {
+            for function in 1..FUNCTIONS_PER_DEVICE {
+                let address = Address { function, ..host };
+                if config::read_u16(address, VENDOR_ID) != u16::MAX {
+                    scan_bus(segment, function, &mut scanned, &mut devices);
+                }
+            }
+        }
+    }
+
+    devices.sort_by_key(|device| device.address);
+    for device in &devices {
+        log_device(device);
+    }
+    *DEVICES.lock() = devices.clone();
+
+    // Bind the drivers registered before the enumeration.
+    let drivers = DRIVERS.lock().clone();
+    for device in &devices {
+        if let Some(driver) = drivers
+            .iter()
+            .find(|driver| driver.ids.iter().any(|id| id.matches(device)))
+        {
+            bind(device, driver);
+        }
+    }
+}
+
+/// The errors that can occur when probing a driver for a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProbeError {
+    /// The function is not supported by the driver, for example because it lacks
+    /// a feature needed by the driver.
+    NotSupported,
+
+    /// The function did not behave as expected during its initialization.
+    DeviceError,
+
+    /// There is not enough memory to initialize the driver.
+    OutOfMemory,
+}
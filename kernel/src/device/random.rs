@@ -0,0 +1,67 @@
+//! The `/dev/random` and `/dev/urandom` devices, which return the random bytes
+//! of the kernel random number generator (see [`crate::random`]). Reading
+//! `/dev/random` blocks until the generator is ready, while `/dev/urandom`
+//! never blocks. The bytes written to either device are added to the entropy
+//! pool, without being credited as entropy.
+use super::{
+    character::{self, CharDevice},
+    Identifier,
+};
+use crate::{
+    random,
+    vfs::{
+        file::{File, ReadError, WriteError},
+        poll::Events,
+    },
+};
+
+/// The identifiers of `/dev/random` and `/dev/urandom`, the same as on Linux.
+const RANDOM: Identifier = Identifier { major: 1, minor: 8 };
+const URANDOM: Identifier = Identifier { major: 1, minor: 9 };
+
+/// A random device.
+struct Random {
+    /// The name of the device.
+    name: &'static str,
+
+    /// Set if reading the device waits for the generator to be ready.
+    blocking: bool,
+}
+
+impl CharDevice for Random {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn read(&self, _: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        if self.blocking {
+            random::wait().map_err(|_| ReadError::Interrupted)?;
+        }
+        random::fill(buf);
+        Ok(buf.len())
+    }
+
+    fn write(&self, _: &File, buf: &[u8]) -> Result<usize, WriteError> {
+        random::add_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self, _: &File) -> Events {
+        if !self.blocking || random::ready() {
+            Events::IN | Events::OUT
+        } else {
+            Events::OUT
+        }
+    }
+}
+
+/// Create `/dev/random` and `/dev/urandom`.
+#[init]
+pub fn setup() {
+    let devices = [(RANDOM, "random", true), (URANDOM, "urandom", false)];
+    for (id, name, blocking) in devices {
+        if let Err(error) = character::register(id, Arc::new(Random { name, blocking })) {
+            log::warn!("random: failed to register /dev/{}: {:?}", name, error);
+        }
+    }
+}
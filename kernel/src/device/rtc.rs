@@ -0,0 +1,352 @@
+//! The real-time clock of the CMOS, exposed as `/dev/rtc` with the interface of
+//! the Linux RTC driver: the `RTC_RD_TIME` and `RTC_SET_TIME` ioctl requests
+//! read and write the date of the clock, the `RTC_ALM_READ` and `RTC_ALM_SET`
+//! requests its daily alarm, and the interrupts of the clock are enabled with
+//! the `RTC_{AIE,UIE,PIE}_{ON,OFF}` requests. Reading the device waits for an
+//! interrupt, and returns an `unsigned long` whose low byte holds the causes
+//! of the interrupts received since the last read, and the other bytes their
+//! number. The date of the clock is not the time of the system, which is only
+//! written to the clock when it is set with `clock_settime`.
+use super::{
+    character::{self, CharDevice},
+    Identifier,
+};
+use crate::{
+    time::date::Date,
+    user::{self, task::queue::WaitQueue},
+    vfs::{
+        self,
+        file::{File, IoctlError, ReadError},
+        poll::Events,
+    },
+    x86_64::{
+        self,
+        cmos::{self, Alarm, Interrupts, StatusB},
+        irq::Affinity,
+    },
+};
+
+/// The identifier of `/dev/rtc`, the same as on Linux.
+const RTC: Identifier = Identifier {
+    major: 10,
+    minor: 135,
+};
+
+/// Enable or disable the alarm interrupt.
+pub const RTC_AIE_ON: usize = 0x7001;
+pub const RTC_AIE_OFF: usize = 0x7002;
+
+/// Enable or disable the update interrupt, raised every second.
+pub const RTC_UIE_ON: usize = 0x7003;
+pub const RTC_UIE_OFF: usize = 0x7004;
+
+/// Enable or disable the periodic interrupt.
+pub const RTC_PIE_ON: usize = 0x7005;
+pub const RTC_PIE_OFF: usize = 0x7006;
+
+/// Set or read the time of the alarm, as a [`RtcTime`] whose date is ignored.
+/// A negative field matches any value.
+pub const RTC_ALM_SET: usize = 0x4024_7007;
+pub const RTC_ALM_READ: usize = 0x8024_7008;
+
+/// Read or set the date of the clock, as a [`RtcTime`].
+pub const RTC_RD_TIME: usize = 0x8024_7009;
+pub const RTC_SET_TIME: usize = 0x4024_700A;
+
+/// Read the frequency of the periodic interrupt as an `unsigned long`, or set
+/// it to the value of the argument, which must be a power of two between 2 and
+/// 8192 Hz.
+pub const RTC_IRQP_READ: usize = 0x8008_700B;
+pub const RTC_IRQP_SET: usize = 0x4008_700C;
+
+/// The frequency of the periodic interrupt set during the boot, in Hz.
+const DEFAULT_FREQUENCY: u32 = 1024;
+
+/// The range of the frequencies of the periodic interrupt, in Hz.
+const FREQUENCIES: core::ops::RangeInclusive<u32> = 2..=8192;
+
+/// The interrupts received since the last read.
+static STATE: Spinlock<State> = Spinlock::new(State {
+    count: 0,
+    causes: Interrupts::empty(),
+    frequency: DEFAULT_FREQUENCY,
+});
+
+/// The tasks waiting for an interrupt.
+static READERS: Lazy<WaitQueue> = Lazy::new(WaitQueue::new);
+
+/// A date, with the layout of the Linux `rtc_time` structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct RtcTime {
+    pub sec: i32,
+    pub min: i32,
+    pub hour: i32,
+
+    /// The day of the month, from 1.
+    pub mday: i32,
+
+    /// The month, from 0.
+    pub mon: i32,
+
+    /// The number of years since 1900.
+    pub year: i32,
+
+    /// The day of the week, from 0 for Sunday, and the day of the year, from 0.
+    pub wday: i32,
+    pub yday: i32,
+
+    pub isdst: i32,
+}
+
+impl RtcTime {
+    /// Converts the date to a [`Date`], or returns `None` if it is not a valid
+    /// date between the years 1970 and 2099.
+    fn to_date(self) -> Option<Date> {
+        let field = |value: i32, range: core::ops::RangeInclusive<i32>| {
+            range
+                .contains(&value)
+                .then(|| u8::try_from(value).ok())
+                .flatten()
+        };
+        let year = self
+            .year
+            .checked_add(1900)
+            .and_then(|year| u16::try_from(year).ok())
+            .filter(|year| (1970..2100).contains(year))?;
+        let month = field(self.mon, 0..=11)? + 1;
+        let days = i32::from(Date::days_in_month(year, month));
+        Some(Date {
+            year,
+            month,
+            day: field(self.mday, 1..=days)?,
+            hour: field(self.hour, 0..=23)?,
+            minute: field(self.min, 0..=59)?,
+            second: field(self.sec, 0..=59)?,
+        })
+    }
+}
+
+impl From<Date> for RtcTime {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn from(date: Date) -> Self {
+        let days = |date: Date| u64::from(date.to_unix_time()) / (60 * 60 * 24);
+        let january = Date {
+            month: 1,
+            day: 1,
+            ..date
+        };
+        Self {
+            sec: i32::from(date.second),
+            min: i32::from(date.minute),
+            hour: i32::from(date.hour),
+            mday: i32::from(date.day),
+            mon: i32::from(date.month) - 1,
+            year: i32::from(date.year) - 1900,
+            // The 1st of January 1970 was a Thursday.
+            wday: ((days(date) + 4) % 7) as i32,
+            yday: (days(date) - days(january)) as i32,
+            isdst: 0,
+        }
+    }
+}
+
+/// The interrupts received since the last read of the device.
+struct State {
+    /// The number of interrupts.
+    count: u64,
+
+    /// The causes of the interrupts.
+    causes: Interrupts,
+
+    /// The frequency of the periodic interrupt, in Hz.
+    frequency: u32,
+}
+
+/// The `/dev/rtc` character device.
+struct Node;
+
+impl CharDevice for Node {
+    fn name(&self) -> &str {
+        "rtc"
+    }
+
+    /// Wait for an interrupt of the clock, and returns the number and the
+    /// causes of the interrupts received since the last read.
+    fn read(&self, _: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        // The buffer is checked before waiting, otherwise the interrupts consumed
+        // by the wait would be lost.
+        let buf = buf
+            .get_mut(..core::mem::size_of::<u64>())
+            .ok_or(ReadError::BufferTooSmall)?;
+        let data = READERS
+            .sleep_interruptible_until(|| {
+                let mut state = STATE.lock();
+                if state.count == 0 {
+                    return None;
+                }
+                let data = state.count << 8 | u64::from(state.causes.bits());
+                state.count = 0;
+                state.causes = Interrupts::empty();
+                Some(data)
+            })
+            .map_err(|_| ReadError::Interrupted)?;
+
+        buf.copy_from_slice(&data.to_ne_bytes());
+        Ok(buf.len())
+    }
+
+    fn poll(&self, _: &File) -> Events {
+        if x86_64::irq::without(|| STATE.lock().count) > 0 {
+            Events::IN
+        } else {
+            Events::empty()
+        }
+    }
+
+    fn ioctl(&self, _: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        match request {
+            RTC_RD_TIME => put(arg, &RtcTime::from(cmos::read_date())),
+            RTC_SET_TIME => {
+                let date = get::<RtcTime>(arg)?
+                    .to_date()
+                    .ok_or(IoctlError::InvalidArgument)?;
+                cmos::write_date(&date).map_err(|_| IoctlError::InvalidArgument)?;
+                Ok(0)
+            }
+            RTC_ALM_READ => {
+                let alarm = cmos::read_alarm();
+                let field = |value: Option<u8>| value.map_or(-1, i32::from);
+                put(
+                    arg,
+                    &RtcTime {
+                        sec: field(alarm.second),
+                        min: field(alarm.minute),
+                        hour: field(alarm.hour),
+                        ..RtcTime::default()
+                    },
+                )
+            }
+            RTC_ALM_SET => {
+                let time = get::<RtcTime>(arg)?;
+                let field = |value: i32, max: i32| match value {
+                    ..=-1 => Ok(None),
+                    value if value < max => Ok(u8::try_from(value).ok()),
+                    _ => Err(IoctlError::InvalidArgument),
+                };
+                cmos::write_alarm(&Alarm {
+                    hour: field(time.hour, 24)?,
+                    minute: field(time.min, 60)?,
+                    second: field(time.sec, 60)?,
+                });
+                Ok(0)
+            }
+            RTC_AIE_ON | RTC_AIE_OFF => {
+                cmos::set_interrupts(StatusB::ALARM_INTERRUPT, request == RTC_AIE_ON);
+                Ok(0)
+            }
+            RTC_UIE_ON | RTC_UIE_OFF => {
+                cmos::set_interrupts(StatusB::UPDATE_INTERRUPT, request == RTC_UIE_ON);
+                Ok(0)
+            }
+            RTC_PIE_ON | RTC_PIE_OFF => {
+                cmos::set_interrupts(StatusB::PERIODIC_INTERRUPT, request == RTC_PIE_ON);
+                Ok(0)
+            }
+            RTC_IRQP_READ => {
+                let frequency = x86_64::irq::without(|| STATE.lock().frequency);
+                put(arg, &u64::from(frequency))
+            }
+            RTC_IRQP_SET => {
+                let frequency = u32::try_from(arg)
+                    .ok()
+                    .filter(|frequency| frequency.is_power_of_two())
+                    .filter(|frequency| FREQUENCIES.contains(frequency))
+                    .ok_or(IoctlError::InvalidArgument)?;
+                set_frequency(frequency);
+                Ok(0)
+            }
+            _ => Err(IoctlError::NotSupported),
+        }
+    }
+}
+
+/// Sets the frequency of the periodic interrupt, which must be a power of two
+/// in [`FREQUENCIES`].
+#[allow(clippy::cast_possible_truncation)]
+fn set_frequency(frequency: u32) {
+    // The frequency is `BASE_FREQUENCY >> (rate - 1)`.
+    let rate = (cmos::BASE_FREQUENCY / frequency).trailing_zeros() + 1;
+    cmos::set_rate(rate as u8);
+    x86_64::irq::without(|| STATE.lock().frequency = frequency);
+}
+
+/// Handle the interrupt of the clock, and wake up the tasks waiting for it.
+fn interrupt(_: u8) {
+    let causes = cmos::acknowledge();
+    if !causes.contains(Interrupts::IRQ) {
+        return;
+    }
+
+    {
+        let mut state = STATE.lock();
+        state.count += 1;
+        state.causes |= causes - Interrupts::IRQ;
+    }
+    READERS.wake_up_all();
+    vfs::poll::notify();
+}
+
+/// Read the given value from the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not a valid userland
+/// address.
+fn get<T: Copy>(arg: usize) -> Result<T, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    Ok(unsafe { user::Object::read(&ptr) })
+}
+
+/// Write the given value at the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not a valid userland
+/// address.
+fn put<T: Copy>(arg: usize, value: &T) -> Result<usize, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    unsafe {
+        user::Object::write(&ptr, value);
+    }
+    Ok(0)
+}
+
+/// Create `/dev/rtc` and handle the interrupts of the clock. The interrupts
+/// are disabled until they are enabled through the device, and the frequency
+/// of the periodic interrupt is set to its default.
+#[init]
+pub fn setup() {
+    Lazy::force(&READERS);
+    cmos::set_interrupts(
+        StatusB::UPDATE_INTERRUPT | StatusB::ALARM_INTERRUPT | StatusB::PERIODIC_INTERRUPT,
+        false,
+    );
+    set_frequency(DEFAULT_FREQUENCY);
+    _ = cmos::acknowledge();
+    if let Err(error) = x86_64::irq::request(cmos::IRQ, interrupt, Affinity::Bsp) {
+        log::warn!("rtc: failed to request IRQ {}: {:?}", cmos::IRQ, error);
+    }
+
+    let date = cmos::read_date();
+    log::info!(
+        "rtc: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        date.year,
+        date.month,
+        date.day,
+        date.hour,
+        date.minute,
+        date.second
+    );
+    if let Err(error) = character::register(RTC, Arc::new(Node)) {
+        log::warn!("rtc: failed to register /dev/rtc: {:?}", error);
+    }
+}
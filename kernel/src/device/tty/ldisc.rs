@@ -0,0 +1,478 @@
+//! The `N_TTY` line discipline, which sits between the driver of a terminal and
+//! the tasks using it. It processes the bytes received from the terminal as
+//! configured by the attributes of the terminal:
+//!
+//! - In canonical mode, the input is edited line by line: the erase, word
+//!   erase and kill characters remove the end of the line being typed, and a
+//!   line is only made available to readers once it is terminated by a newline,
+//!   an end-of-line character or the end-of-file character.
+//! - In non-canonical mode, the bytes are made available as soon as they are
+//!   received, and the `VMIN` and `VTIME` control characters tell readers how
+//!   long to wait for them (see [`super::Tty::read`]).
+//! - The received bytes are echoed back to the terminal, with control characters
+//!   shown as `^X`, and the characters generating signals are recognized.
+//!
+//! The output is also processed here, mainly to translate newlines into the
+//! carriage return and newline pair expected by terminals.
+use super::termios::{self, Termios};
+use crate::user::task::signal::Signal;
+use alloc::collections::VecDeque;
+
+/// The maximal number of bytes buffered in the line discipline. Bytes received
+/// when the buffer is full are dropped.
+pub const BUFFER_SIZE: usize = 4096;
+
+/// The byte ringing the bell of the terminal.
+const BELL: u8 = 0x07;
+
+/// The byte moving the cursor one column to the left.
+const BACKSPACE: u8 = 0x08;
+
+/// The byte deleting the character before the cursor, usually sent by the
+/// backspace key.
+const DELETE: u8 = 0x7F;
+
+/// What is erased by an erase operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Erase {
+    /// The last character of the line.
+    Character,
+
+    /// The last word of the line, and the blanks after it.
+    Word,
+
+    /// The whole line.
+    Line,
+}
+
+/// The state of the line discipline of a terminal.
+#[derive(Debug, Default)]
+pub struct Ldisc {
+    /// The lines completed in canonical mode and not read yet. A line contains
+    /// its terminating character, except if it was terminated by the end-of-file
+    /// character. An empty line is an end of file for readers.
+    lines: VecDeque<Vec<u8>>,
+
+    /// The line being edited in canonical mode.
+    line: Vec<u8>,
+
+    /// The bytes received in non-canonical mode and not read yet.
+    raw: VecDeque<u8>,
+
+    /// Set when the next byte must be inserted without being interpreted,
+    /// after the literal next character (`VLNEXT`).
+    literal: bool,
+
+    /// Set when the output was stopped with the stop character (`VSTOP`).
+    stopped: bool,
+
+    /// The column of the cursor of the terminal, and the column where the line
+    /// being edited started, used to erase tabulations.
+    column: usize,
+    line_column: usize,
+}
+
+impl Ldisc {
+    /// Create a new line discipline with nothing buffered.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process the given bytes received from the terminal. The bytes to echo to
+    /// the terminal are appended to `echo`, already processed for output, and
+    /// the signals generated are appended to `signals`.
+    pub fn receive(
+        &mut self,
+        termios: &Termios,
+        data: &[u8],
+        echo: &mut Vec<u8>,
+        signals: &mut Vec<Signal>,
+    ) {
+        for &byte in data {
+            let mut c = byte;
+            if termios.input(termios::ISTRIP) {
+                c &= 0x7F;
+            }
+
+            if self.literal {
+                self.literal = false;
+                if termios.local(termios::ECHO | termios::ECHOCTL) {
+                    // Erase the `^` echoed for the literal next character.
+                    self.output(termios, &[BACKSPACE], echo);
+                }
+                self.insert(termios, c, echo);
+                continue;
+            }
+
+            if c == b'\r' {
+                if termios.input(termios::IGNCR) {
+                    continue;
+                }
+                if termios.input(termios::ICRNL) {
+                    c = b'\n';
+                }
+            } else if c == b'\n' && termios.input(termios::INLCR) {
+                c = b'\r';
+            }
+
+            if termios.local(termios::ISIG) {
+                let signal = if termios.is(c, termios::VINTR) {
+                    Some(Signal::Interrupt)
+                } else if termios.is(c, termios::VQUIT) {
+                    Some(Signal::Quit)
+                } else if termios.is(c, termios::VSUSP) {
+                    Some(Signal::TerminalStop)
+                } else {
+                    None
+                };
+
+                if let Some(signal) = signal {
+                    if !termios.local(termios::NOFLSH) {
+                        self.flush();
+                    }
+                    self.stopped = false;
+                    self.echo(termios, c, echo);
+                    signals.push(signal);
+                    continue;
+                }
+            }
+
+            if termios.input(termios::IXON) {
+                if termios.is(c, termios::VSTOP) {
+                    self.stopped = true;
+                    continue;
+                }
+                if termios.is(c, termios::VSTART) {
+                    self.stopped = false;
+                    continue;
+                }
+            }
+
+            if termios.canonical() {
+                self.edit(termios, c, echo);
+            } else {
+                self.insert(termios, c, echo);
+            }
+        }
+    }
+
+    /// Process a byte received in canonical mode.
+    fn edit(&mut self, termios: &Termios, c: u8, echo: &mut Vec<u8>) {
+        let extended = termios.local(termios::IEXTEN);
+
+        if termios.is(c, termios::VERASE) {
+            self.erase(termios, Erase::Character, echo);
+        } else if termios.is(c, termios::VKILL) {
+            self.erase(termios, Erase::Line, echo);
+        } else if extended && termios.is(c, termios::VWERASE) {
+            self.erase(termios, Erase::Word, echo);
+        } else if extended && termios.is(c, termios::VLNEXT) {
+            self.literal = true;
+            if termios.local(termios::ECHO | termios::ECHOCTL) {
+                self.output(termios, b"^", echo);
+            }
+        } else if extended && termios.is(c, termios::VREPRINT) {
+            self.reprint(termios, c, echo);
+        } else if termios.is(c, termios::VEOF) {
+            let line = core::mem::take(&mut self.line);
+            self.lines.push_back(line);
+        } else if c == b'\n'
+            || termios.is(c, termios::VEOL)
+            || (extended && termios.is(c, termios::VEOL2))
+        {
+            self.line.push(c);
+            if c == b'\n' && termios.local(termios::ECHONL) && !termios.local(termios::ECHO) {
+                self.output(termios, b"\n", echo);
+            } else {
+                self.echo(termios, c, echo);
+            }
+            let line = core::mem::take(&mut self.line);
+            self.lines.push_back(line);
+        } else {
+            self.insert(termios, c, echo);
+        }
+    }
+
+    /// Insert a byte at the end of the line being edited in canonical mode, or
+    /// in the received bytes in non-canonical mode, and echo it. In canonical
+    /// mode, room is always kept for the byte terminating the line.
+    fn insert(&mut self, termios: &Termios, c: u8, echo: &mut Vec<u8>) {
+        if !termios.canonical() {
+            if self.raw.len() < BUFFER_SIZE {
+                self.raw.push_back(c);
+                self.echo(termios, c, echo);
+            }
+            return;
+        }
+
+        if self.pending() + self.line.len() + 1 >= BUFFER_SIZE {
+            if termios.input(termios::IMAXBEL) {
+                self.output(termios, &[BELL], echo);
+            }
+            return;
+        }
+
+        if self.line.is_empty() {
+            self.line_column = self.column;
+        }
+        self.line.push(c);
+        self.echo(termios, c, echo);
+    }
+
+    /// Erase the end of the line being edited, and erase it from the terminal
+    /// if echo is enabled.
+    fn erase(&mut self, termios: &Termios, erase: Erase, echo: &mut Vec<u8>) {
+        if self.line.is_empty() {
+            return;
+        }
+
+        // Without the visual erase of the killed line, the kill character is
+        // echoed, followed by a newline if requested.
+        if erase == Erase::Line
+            && !termios.local(termios::ECHO | termios::ECHOE | termios::ECHOK | termios::ECHOKE)
+        {
+            self.line.clear();
+            if termios.local(termios::ECHO) {
+                self.echo(termios, termios.cc[termios::VKILL], echo);
+                if termios.local(termios::ECHOK) {
+                    self.output(termios, b"\n", echo);
+                }
+            }
+            return;
+        }
+
+        let mut seen_word = false;
+        while let Some(&last) = self.line.last() {
+            if erase == Erase::Word {
+                let blank = last == b' ' || last == b'\t';
+                if blank && seen_word {
+                    break;
+                }
+                seen_word |= !blank;
+            }
+
+            // A whole UTF-8 character is erased at once.
+            let mut len = 1;
+            if termios.input(termios::IUTF8) {
+                while len < self.line.len() && self.line[self.line.len() - len] & 0xC0 == 0x80 {
+                    len += 1;
+                }
+            }
+            self.line.truncate(self.line.len() - len);
+
+            if termios.local(termios::ECHO) {
+                if !termios.local(termios::ECHOE) {
+                    self.echo(termios, termios.cc[termios::VERASE], echo);
+                } else if last == b'\t' {
+                    // The width of a tabulation depends on where it started.
+                    let count = self.column.saturating_sub(self.line_width(termios));
+                    self.output(termios, &alloc::vec![BACKSPACE; count], echo);
+                } else {
+                    let width = if len > 1 {
+                        1
+                    } else {
+                        Self::width(termios, last)
+                    };
+                    for _ in 0..width {
+                        self.output(termios, b"\x08 \x08", echo);
+                    }
+                }
+            }
+
+            if erase == Erase::Character {
+                break;
+            }
+        }
+    }
+
+    /// Echo the reprint character, then the line being edited on a new line.
+    fn reprint(&mut self, termios: &Termios, c: u8, echo: &mut Vec<u8>) {
+        if !termios.local(termios::ECHO) {
+            return;
+        }
+        self.echo(termios, c, echo);
+        self.output(termios, b"\n", echo);
+        self.line_column = self.column;
+        for c in core::mem::take(&mut self.line) {
+            self.echo(termios, c, echo);
+            self.line.push(c);
+        }
+    }
+
+    /// Echo a received byte if echo is enabled. Control characters are shown as
+    /// `^X` if requested, except the tabulation and the newline.
+    fn echo(&mut self, termios: &Termios, c: u8, echo: &mut Vec<u8>) {
+        if !termios.local(termios::ECHO) {
+            return;
+        }
+        if termios.local(termios::ECHOCTL) && Self::control(c) && c != b'\t' && c != b'\n' {
+            self.output(termios, &[b'^', c ^ 0x40], echo);
+        } else {
+            self.output(termios, &[c], echo);
+        }
+    }
+
+    /// Returns the column where the cursor would be after echoing the line being
+    /// edited, used to know how many columns a tabulation took.
+    fn line_width(&self, termios: &Termios) -> usize {
+        self.line.iter().fold(self.line_column, |column, &c| {
+            if c == b'\t' {
+                (column | 7) + 1
+            } else {
+                column + Self::width(termios, c)
+            }
+        })
+    }
+
+    /// Returns the number of columns taken by the echo of the given byte, which
+    /// is not a tabulation.
+    fn width(termios: &Termios, c: u8) -> usize {
+        if Self::control(c) {
+            if termios.local(termios::ECHOCTL) {
+                2
+            } else {
+                0
+            }
+        } else {
+            usize::from(!Self::continuation(termios, c))
+        }
+    }
+
+    /// Returns `true` if the given byte is an ASCII control character.
+    const fn control(c: u8) -> bool {
+        c < 0x20 || c == DELETE
+    }
+
+    /// Returns `true` if the given byte continues an UTF-8 character, and does
+    /// not take a column on its own.
+    const fn continuation(termios: &Termios, c: u8) -> bool {
+        termios.input(termios::IUTF8) && c & 0xC0 == 0x80
+    }
+
+    /// Process the given bytes written to the terminal, and append the bytes
+    /// to send to the terminal to `out`. The column of the cursor is tracked to
+    /// be able to erase tabulations.
+    pub fn output(&mut self, termios: &Termios, data: &[u8], out: &mut Vec<u8>) {
+        if !termios.output(termios::OPOST) {
+            out.extend_from_slice(data);
+            return;
+        }
+
+        for &c in data {
+            match c {
+                b'\n' => {
+                    if termios.output(termios::ONLCR) {
+                        out.push(b'\r');
+                        self.column = 0;
+                    }
+                    if termios.output(termios::ONLRET) {
+                        self.column = 0;
+                    }
+                    out.push(b'\n');
+                }
+                b'\r' => {
+                    if termios.output(termios::ONOCR) && self.column == 0 {
+                        continue;
+                    }
+                    if termios.output(termios::OCRNL) {
+                        out.push(b'\n');
+                        if termios.output(termios::ONLRET) {
+                            self.column = 0;
+                        }
+                    } else {
+                        out.push(b'\r');
+                        self.column = 0;
+                    }
+                }
+                b'\t' => {
+                    self.column = (self.column | 7) + 1;
+                    out.push(c);
+                }
+                BACKSPACE => {
+                    self.column = self.column.saturating_sub(1);
+                    out.push(c);
+                }
+                _ => {
+                    if !(Self::control(c) || Self::continuation(termios, c)) {
+                        self.column += 1;
+                    }
+                    out.push(c);
+                }
+            }
+        }
+    }
+
+    /// Called when the attributes of the terminal change. When the canonical
+    /// mode is disabled, the line being edited and the lines not read yet are
+    /// made available as raw bytes. When it is enabled, the raw bytes not read
+    /// yet are made available as a line.
+    pub fn set_termios(&mut self, old: &Termios, new: &Termios) {
+        if old.canonical() && !new.canonical() {
+            self.raw.extend(self.lines.drain(..).flatten());
+            self.raw.extend(self.line.drain(..));
+        } else if !old.canonical() && new.canonical() && !self.raw.is_empty() {
+            self.lines.push_back(self.raw.drain(..).collect());
+        }
+        if !new.input(termios::IXON) {
+            self.stopped = false;
+        }
+    }
+
+    /// Discard all the input not read yet, including the line being edited.
+    pub fn flush(&mut self) {
+        self.lines.clear();
+        self.line.clear();
+        self.raw.clear();
+        self.literal = false;
+    }
+
+    /// Returns the number of bytes that can be read, the line being edited
+    /// excluded.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.lines.iter().map(Vec::len).sum::<usize>() + self.raw.len()
+    }
+
+    /// Returns `true` if a read would return immediately: a line is completed in
+    /// canonical mode, or some bytes were received in non-canonical mode.
+    #[must_use]
+    pub fn readable(&self, termios: &Termios) -> bool {
+        if termios.canonical() {
+            !self.lines.is_empty()
+        } else {
+            !self.raw.is_empty()
+        }
+    }
+
+    /// Returns `true` if the output was stopped with the stop character.
+    #[must_use]
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Read the input into the given buffer, and returns the number of bytes
+    /// read. In canonical mode, at most one line is read, and reading an empty
+    /// line (an end of file typed at the start of a line) returns 0.
+    pub fn read(&mut self, termios: &Termios, buf: &mut [u8]) -> usize {
+        if !termios.canonical() {
+            let count = core::cmp::min(buf.len(), self.raw.len());
+            for (dst, src) in buf.iter_mut().zip(self.raw.drain(..count)) {
+                *dst = src;
+            }
+            return count;
+        }
+
+        let Some(line) = self.lines.front_mut() else {
+            return 0;
+        };
+        let count = core::cmp::min(buf.len(), line.len());
+        buf[..count].copy_from_slice(&line[..count]);
+        if count == line.len() {
+            self.lines.pop_front();
+        } else {
+            line.drain(..count);
+        }
+        count
+    }
+}
@@ -0,0 +1,564 @@
+//! Terminals. A tty connects a driver, which sends bytes to a terminal and
+//! receives the bytes typed on it, to the tasks using the terminal through
+//! the `N_TTY` line discipline (see [`ldisc`]), configured by the attributes
+//! of the terminal (see [`termios`]).
+//!
+//! The input is given to the tty by its driver with [`Tty::receive`], usually
+//! from an interrupt handler: the state of a tty is therefore always locked with
+//! interrupts disabled. The output is given to the driver, which must not sleep
+//! but can buffer it: writers sleep while the buffer of the driver is full, until
+//! the driver calls [`Tty::wake_up_writers`].
+//!
+//! There are no process groups nor sessions yet: the foreground "process group"
+//! of a terminal is a single task, which receives the signals generated by the
+//! terminal. It is the first task opening the terminal, unless another task is
+//! made the foreground with the `TIOCSPGRP` ioctl request. Since there are no
+//! sessions either, a terminal controls the tasks that have it opened, and the
+//! foreground can only be given to one of them by one of them.
+use super::character::{self, CharDevice};
+use crate::{
+    time::{timer::Timer, units::Nanosecond, uptime_fast},
+    user::{
+        self,
+        scheduler::{Scheduler, SCHEDULER},
+        task::{
+            self,
+            queue::{Interrupted, WaitQueue},
+            signal::Signal,
+            Task,
+        },
+    },
+    vfs::{
+        self,
+        file::{File, IoctlError, ReadError, WriteError},
+        poll::Events,
+    },
+    x86_64,
+};
+use alloc::sync::Weak;
+use ldisc::Ldisc;
+use termios::{Termios, WindowSize};
+
+pub mod ldisc;
+pub mod pty;
+pub mod serial;
+pub mod termios;
+pub mod vt;
+
+/// The number of bytes written to the driver at once. The state of the tty is
+/// locked with interrupts disabled while writing, so large writes are split to
+/// avoid delaying interrupts for too long.
+const WRITE_CHUNK: usize = 64;
+
+/// The number of bytes a byte can be expanded to at most when processed for
+/// output (a newline becomes a carriage return and a newline with `ONLCR`).
+const OUTPUT_EXPANSION: usize = 2;
+
+/// The driver of a terminal.
+pub trait Driver: Send + Sync {
+    /// Send the given bytes to the terminal. This is called with the state of
+    /// the tty locked and interrupts disabled, possibly from the interrupt
+    /// handler of the driver when echoing the input, and must not sleep. The
+    /// bytes that do not fit in the room left (see [`Driver::write_room`]) may
+    /// be discarded.
+    fn write(&self, data: &[u8]);
+
+    /// Returns the number of bytes that can be written without being discarded.
+    fn write_room(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Returns the number of bytes written but not sent to the terminal yet.
+    fn pending(&self) -> usize {
+        0
+    }
+
+    /// Discard the bytes written but not sent to the terminal yet.
+    fn flush(&self) {}
+
+    /// Called when the attributes of the terminal change, so that the driver
+    /// can apply the ones that concern the hardware, such as the speed of the
+    /// line.
+    fn set_termios(&self, _termios: &Termios) {}
+}
+
+/// A terminal.
+pub struct Tty {
+    /// The name of the terminal.
+    name: String,
+
+    /// The driver of the terminal.
+    driver: Arc<dyn Driver>,
+
+    /// The state of the terminal, shared with the interrupt handler of the
+    /// driver.
+    state: Spinlock<State>,
+
+    /// The tasks waiting for input, or for the output to be restarted.
+    readers: WaitQueue,
+    writers: WaitQueue,
+}
+
+/// The state of a terminal.
+struct State {
+    /// The attributes of the terminal.
+    termios: Termios,
+
+    /// The size of the window of the terminal.
+    window: WindowSize,
+
+    /// The line discipline of the terminal.
+    ldisc: Ldisc,
+
+    /// The task in the foreground of the terminal, which receives the signals
+    /// generated by the terminal.
+    foreground: Option<Weak<Task>>,
+
+    /// Set when the terminal has been hung up: it cannot be written anymore,
+    /// and reading it returns the end of file once the input is consumed.
+    hung_up: bool,
+}
+
+impl State {
+    /// Returns the foreground task of the terminal, if it is still alive.
+    fn foreground(&self) -> Option<Arc<Task>> {
+        self.foreground
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .filter(|task| task.state() != task::State::Terminated)
+    }
+}
+
+impl Tty {
+    /// Create a new terminal with the given name, driver and attributes.
+    #[must_use]
+    pub fn new(name: &str, driver: Arc<dyn Driver>, termios: Termios) -> Self {
+        Self {
+            name: String::from(name),
+            driver,
+            state: Spinlock::new(State {
+                termios,
+                window: WindowSize::default(),
+                ldisc: Ldisc::new(),
+                foreground: None,
+                hung_up: false,
+            }),
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        }
+    }
+
+    /// Give the bytes received from the terminal to the line discipline. The
+    /// input is echoed and the readers are woken up, and the signals generated
+    /// are sent to the foreground task. This can be called from an interrupt
+    /// handler.
+    pub fn receive(&self, data: &[u8]) {
+        let mut echo = Vec::new();
+        let mut signals = Vec::new();
+
+        let foreground = x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            let termios = state.termios;
+            state.ldisc.receive(&termios, data, &mut echo, &mut signals);
+            if !echo.is_empty() {
+                self.driver.write(&echo);
+            }
+            state.foreground()
+        });
+
+        if let Some(task) = foreground {
+            for signal in signals {
+                task.send_signal(signal);
+            }
+        }
+
+        self.readers.wake_up_all();
+        self.writers.wake_up_all();
+        vfs::poll::notify();
+    }
+
+    /// Read the input of the terminal into the given buffer, and returns the
+    /// number of bytes read. In canonical mode, the task sleeps until a line is
+    /// available, and at most one line is read. In non-canonical mode, the wait
+    /// depends on the `VMIN` and `VTIME` control characters, `VTIME` being in
+    /// tenths of a second:
+    ///
+    /// - `VMIN = 0, VTIME = 0`: the available bytes are read without waiting.
+    /// - `VMIN > 0, VTIME = 0`: the task waits until `VMIN` bytes are available.
+    /// - `VMIN = 0, VTIME > 0`: the task waits until a byte is available, or
+    ///   until `VTIME` has elapsed.
+    /// - `VMIN > 0, VTIME > 0`: the task waits until a byte is available, then
+    ///   until `VMIN` bytes are available or no byte was received for `VTIME`.
+    ///
+    /// Once the terminal is hung up, the available bytes are read without
+    /// waiting, and reading returns 0 (end of file) once there are none left.
+    ///
+    /// # Errors
+    /// Returns `ReadError::Interrupted` if the task was interrupted while
+    /// waiting for input.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, ReadError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut deadline = None;
+        let mut received = 0;
+
+        loop {
+            let armed = deadline;
+            let done = sleep_until(&self.readers, armed, || {
+                let mut state = self.state.lock();
+                let termios = state.termios;
+                let pending = state.ldisc.pending();
+
+                let min = core::cmp::min(usize::from(termios.cc[termios::VMIN]), buf.len());
+                let time = Nanosecond::new(u64::from(termios.cc[termios::VTIME]) * 100_000_000);
+                let ready = if termios.canonical() {
+                    state.ldisc.readable(&termios)
+                } else if time.0 == 0 {
+                    pending >= min
+                } else {
+                    // Without a minimum, the timer starts with the read, and
+                    // otherwise it is restarted each time a byte is received.
+                    if min == 0 && deadline.is_none() || min > 0 && pending > received {
+                        deadline = Some(uptime_fast() + time);
+                    }
+                    let expired = deadline.map_or(false, |deadline| uptime_fast() >= deadline);
+                    pending >= min.max(1) || expired
+                };
+                received = pending;
+
+                if ready || state.hung_up {
+                    return Some(Some(state.ldisc.read(&termios, buf)));
+                }
+
+                // The timer must be armed again if the deadline was changed.
+                (deadline != armed).then_some(None)
+            });
+
+            match done {
+                Ok(Some(Some(read))) => return Ok(read),
+                Ok(_) => (),
+                Err(Interrupted) => return Err(ReadError::Interrupted),
+            }
+        }
+    }
+
+    /// Write the given buffer to the terminal, after processing it for output.
+    /// The task sleeps while the output is stopped, or while there is not
+    /// enough room left in the buffer of the driver.
+    ///
+    /// # Errors
+    /// Returns `WriteError::IoError` if the terminal has been hung up, or if the
+    /// task was interrupted while waiting to write, and nothing was written yet.
+    pub fn write(&self, buf: &[u8]) -> Result<usize, WriteError> {
+        let mut written = 0;
+        let mut out = Vec::with_capacity(WRITE_CHUNK * OUTPUT_EXPANSION);
+
+        while written < buf.len() {
+            let result = self
+                .writers
+                .sleep_interruptible_until(|| {
+                    let mut state = self.state.lock();
+                    if state.hung_up {
+                        return Some(Err(WriteError::IoError));
+                    }
+                    let room = self.driver.write_room() / OUTPUT_EXPANSION;
+                    if state.ldisc.stopped() || room == 0 {
+                        return None;
+                    }
+                    let len = (buf.len() - written).min(WRITE_CHUNK).min(room);
+                    let termios = state.termios;
+                    out.clear();
+                    state
+                        .ldisc
+                        .output(&termios, &buf[written..written + len], &mut out);
+                    self.driver.write(&out);
+                    Some(Ok(len))
+                })
+                .unwrap_or(Err(WriteError::IoError));
+
+            match result {
+                Ok(len) => written += len,
+                Err(_) if written > 0 => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(written)
+    }
+
+    /// Wake up the tasks waiting to write to the terminal. This must be called
+    /// by the driver when room is made in its buffer, and when its buffer is
+    /// emptied. This can be called from an interrupt handler.
+    pub fn wake_up_writers(&self) {
+        self.writers.wake_up_all();
+        vfs::poll::notify();
+    }
+
+    /// Hang up the terminal, as done when the master side of a pseudo-terminal
+    /// is closed: the foreground task receives `SIGHUP` and is detached from
+    /// the terminal, and the tasks waiting on the terminal are woken up.
+    pub fn hang_up(&self) {
+        let foreground = self.with_state(|state| {
+            state.hung_up = true;
+            state.foreground.take().and_then(|task| task.upgrade())
+        });
+
+        if let Some(task) = foreground {
+            task.send_signal(Signal::Hangup);
+        }
+        self.readers.wake_up_all();
+        self.wake_up_writers();
+    }
+
+    /// Wait until all the bytes written to the terminal have been sent by the
+    /// driver.
+    fn drain(&self) -> Result<(), Interrupted> {
+        self.writers.sleep_interruptible_until(|| {
+            (self.driver.pending() == 0 || self.state.lock().hung_up).then_some(())
+        })
+    }
+
+    /// Returns the events ready on the terminal.
+    #[must_use]
+    pub fn poll(&self) -> Events {
+        x86_64::irq::without(|| {
+            let state = self.state.lock();
+            let mut events = Events::empty();
+            if state.hung_up {
+                return Events::IN | Events::HUP;
+            }
+            if state.ldisc.readable(&state.termios) {
+                events |= Events::IN;
+            }
+            if !state.ldisc.stopped() && self.driver.write_room() >= OUTPUT_EXPANSION {
+                events |= Events::OUT;
+            }
+            events
+        })
+    }
+
+    /// Make the current task the foreground task of the terminal if there is
+    /// none, as done when a task opens the terminal.
+    pub fn attach(&self) {
+        let current = SCHEDULER.current_task();
+        x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            if state.foreground().is_none() {
+                state.foreground = Some(Arc::downgrade(&current));
+            }
+        });
+    }
+
+    /// Set the size of the window of the terminal, and send the `WindowChange`
+    /// signal to the foreground task if it changed.
+    pub fn resize(&self, window: WindowSize) {
+        let (changed, foreground) = self.with_state(|state| {
+            let old = core::mem::replace(&mut state.window, window);
+            (old != window, state.foreground())
+        });
+        if let (true, Some(task)) = (changed, foreground) {
+            task.send_signal(Signal::WindowChange);
+        }
+    }
+
+    /// Handle the ioctl requests of terminals.
+    ///
+    /// # Errors
+    /// See [`IoctlError`] for the possible errors.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn ioctl(&self, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        match request {
+            termios::TCGETS => put(arg, &self.with_state(|state| state.termios)),
+            termios::TCSETS | termios::TCSETSW | termios::TCSETSF => {
+                let termios = get::<Termios>(arg)?;
+                if request != termios::TCSETS {
+                    self.drain().map_err(|_| IoctlError::IoError)?;
+                }
+                self.with_state(|state| {
+                    if request == termios::TCSETSF {
+                        state.ldisc.flush();
+                    }
+                    let old = core::mem::replace(&mut state.termios, termios);
+                    state.ldisc.set_termios(&old, &termios);
+                    self.driver.set_termios(&termios);
+                });
+                self.readers.wake_up_all();
+                self.writers.wake_up_all();
+                vfs::poll::notify();
+                Ok(0)
+            }
+            termios::TCFLSH => match arg {
+                termios::TCIFLUSH => {
+                    self.with_state(|state| state.ldisc.flush());
+                    Ok(0)
+                }
+                termios::TCIOFLUSH => {
+                    self.with_state(|state| {
+                        state.ldisc.flush();
+                        self.driver.flush();
+                    });
+                    self.wake_up_writers();
+                    Ok(0)
+                }
+                termios::TCOFLUSH => {
+                    x86_64::irq::without(|| self.driver.flush());
+                    self.wake_up_writers();
+                    Ok(0)
+                }
+                _ => Err(IoctlError::InvalidArgument),
+            },
+            termios::TIOCGWINSZ => put(arg, &self.with_state(|state| state.window)),
+            termios::TIOCSWINSZ => {
+                self.resize(get::<WindowSize>(arg)?);
+                Ok(0)
+            }
+            termios::TIOCGPGRP => {
+                let foreground = self.with_state(|state| state.foreground());
+                let id = foreground.map_or(0, |task| task.id().0 as i32);
+                put(arg, &id)
+            }
+            termios::TIOCSPGRP => {
+                if !self.controls(&SCHEDULER.current_task()) {
+                    return Err(IoctlError::NotSupported);
+                }
+                let id = get::<i32>(arg)?;
+                let id = u64::try_from(id).map_err(|_| IoctlError::InvalidArgument)?;
+                let task = task::get(task::Identifier(id))
+                    .filter(|task| self.controls(task))
+                    .ok_or(IoctlError::InvalidArgument)?;
+                self.with_state(|state| state.foreground = Some(Arc::downgrade(&task)));
+                Ok(0)
+            }
+            termios::TIOCSCTTY => {
+                let current = SCHEDULER.current_task();
+                self.with_state(|state| state.foreground = Some(Arc::downgrade(&current)));
+                Ok(0)
+            }
+            termios::TIOCNOTTY => {
+                let current = SCHEDULER.current_task();
+                self.with_state(|state| {
+                    if state
+                        .foreground()
+                        .is_some_and(|task| task.id() == current.id())
+                    {
+                        state.foreground = None;
+                    }
+                });
+                Ok(0)
+            }
+            termios::FIONREAD => put(
+                arg,
+                &(self.with_state(|state| state.ldisc.pending()) as i32),
+            ),
+            termios::TIOCOUTQ => {
+                let pending = x86_64::irq::without(|| self.driver.pending());
+                put(arg, &(pending as i32))
+            }
+            _ => Err(IoctlError::NotSupported),
+        }
+    }
+
+    /// Returns true if the given task has the terminal opened. Since there are
+    /// no sessions yet, this is how the terminal controlling a task is found.
+    fn controls(&self, task: &Task) -> bool {
+        task.files().lock().iter().any(|file| {
+            let Some(vfs::inode::Kind::CharDevice(id)) =
+                file.dentry.as_ref().map(|dentry| dentry.inode().kind)
+            else {
+                return false;
+            };
+            character::get(id)
+                .is_some_and(|device| device.tty().is_some_and(|tty| core::ptr::eq(tty, self)))
+        })
+    }
+
+    /// Run the given function with the state of the terminal locked.
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        x86_64::irq::without(|| f(&mut self.state.lock()))
+    }
+}
+
+impl CharDevice for Tty {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn open(
+        &self,
+        _: vfs::file::OpenFlags,
+    ) -> Result<super::character::OpenData, super::character::OpenError> {
+        self.attach();
+        Ok(Box::new(()))
+    }
+
+    fn read(&self, _: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        Tty::read(self, buf)
+    }
+
+    fn write(&self, _: &File, buf: &[u8]) -> Result<usize, WriteError> {
+        Tty::write(self, buf)
+    }
+
+    fn poll(&self, _: &File) -> Events {
+        Tty::poll(self)
+    }
+
+    fn ioctl(&self, _: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        Tty::ioctl(self, request, arg)
+    }
+
+    fn tty(&self) -> Option<&Tty> {
+        Some(self)
+    }
+}
+
+/// Read a value at the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not a valid userland
+/// address.
+fn get<T: Copy>(arg: usize) -> Result<T, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    Ok(unsafe { user::Object::read(&ptr) })
+}
+
+/// Write the given value at the given userland address.
+///
+/// # Errors
+/// Returns `IoctlError::BadAddress` if the address is not a valid userland
+/// address.
+fn put<T: Copy>(arg: usize, value: &T) -> Result<usize, IoctlError> {
+    let ptr = user::Pointer::<T>::from_usize(arg).ok_or(IoctlError::BadAddress)?;
+    unsafe {
+        user::Object::write(&ptr, value);
+    }
+    Ok(0)
+}
+
+/// Put the current task to sleep in the given queue until the given condition
+/// returns `Some`, or until the given deadline is reached, if any, in which case
+/// `None` is returned. The condition is called with interrupts disabled, and
+/// checked again once the task is in the queue, so that the task cannot miss a
+/// wake up from the interrupt handler of the driver.
+fn sleep_until<T>(
+    queue: &WaitQueue,
+    deadline: Option<Nanosecond>,
+    mut condition: impl FnMut() -> Option<T>,
+) -> Result<Option<T>, Interrupted> {
+    let Some(deadline) = deadline else {
+        return queue.sleep_interruptible_until(condition).map(Some);
+    };
+
+    x86_64::irq::without(|| {
+        let current = SCHEDULER.current_task();
+        let _timer = Timer::new(deadline, move |_| {
+            current.change_state_if(task::State::Blocked, task::State::Ready);
+        });
+        queue.sleep_interruptible_until(|| match condition() {
+            Some(value) => Some(Some(value)),
+            None if uptime_fast() >= deadline => Some(None),
+            None => None,
+        })
+    })
+}
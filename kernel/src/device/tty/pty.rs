@@ -0,0 +1,406 @@
+//! Pseudo-terminals. A pseudo-terminal is a pair of character devices: the
+//! master side, obtained by opening `/dev/ptmx`, and the slave side, a terminal
+//! whose node `/dev/pts/N` is provided by the `devpts` filesystem (see
+//! [`crate::fs::devpts`]). What is written to the master side is the input of
+//! the terminal, and the output of the terminal is read from the master side.
+//!
+//! Like on Linux, the slave side is locked when the pair is created, and must be
+//! unlocked with the `TIOCSPTLCK` ioctl request on the master side (which is
+//! what `unlockpt` does) before being opened. `grantpt` has nothing to do since
+//! there are no file owners yet, and `ptsname` gets the number of the pair with
+//! the `TIOCGPTN` ioctl request.
+//!
+//! Closing the master side hangs up the terminal. Once the last file opened on
+//! the slave side is closed, the master side is hung up too: reading it fails
+//! with an I/O error once the output has been read, and polling it reports a
+//! hang up. A pair is released once both sides are closed.
+use super::{
+    termios::{self, Termios},
+    Driver, Tty,
+};
+use crate::{
+    device::{
+        character::{self, CharDevice, OpenData, OpenError},
+        node, Device, Identifier,
+    },
+    fs::devpts,
+    user::task::queue::{Interrupted, WaitQueue},
+    vfs::{
+        self,
+        file::{File, IoctlError, OpenFlags, ReadError, WriteError},
+        poll::Events,
+    },
+};
+use alloc::{collections::BTreeMap, format};
+use circular_buffer::CircularBuffer;
+
+/// The identifier of `/dev/ptmx`, the same as on Linux.
+const PTMX: Identifier = Identifier { major: 5, minor: 2 };
+
+/// The major number of the slave sides, the same as on Linux. The minor number
+/// of a slave side is the number of its pair.
+pub const SLAVE_MAJOR: u32 = 136;
+
+/// The maximal number of pairs in use at the same time.
+const MAX_PAIRS: u32 = 256;
+
+/// The size of the buffer holding the output of a terminal until it is read
+/// from the master side.
+const BUFFER_SIZE: usize = 4096;
+
+/// The pairs in use, indexed by their number.
+static PAIRS: Spinlock<BTreeMap<u32, Arc<Pair>>> = Spinlock::new(BTreeMap::new());
+
+/// A pseudo-terminal.
+pub struct Pair {
+    /// The number of the pair, which is also the name of its slave side.
+    number: u32,
+
+    /// The terminal of the slave side.
+    tty: Arc<Tty>,
+
+    /// The output of the terminal, read from the master side.
+    output: Arc<Output>,
+
+    /// Which sides of the pair are opened.
+    state: Spinlock<State>,
+}
+
+/// The state of the sides of a pair.
+struct State {
+    /// The slave side cannot be opened while the pair is locked.
+    locked: bool,
+
+    /// Whether the master side is still opened.
+    master: bool,
+
+    /// The number of files opened on the slave side.
+    slaves: usize,
+
+    /// Whether the last file opened on the slave side has been closed. It is
+    /// cleared when the slave side is opened again.
+    hung_up: bool,
+}
+
+/// The driver of the terminal of a pair, which keeps the output of the terminal
+/// until it is read from the master side.
+struct Output {
+    /// The bytes written to the terminal and not read yet.
+    buffer: Spinlock<Box<CircularBuffer<BUFFER_SIZE, u8>>>,
+
+    /// The tasks waiting to read from the master side.
+    readers: WaitQueue,
+}
+
+impl Driver for Output {
+    fn write(&self, data: &[u8]) {
+        let mut buffer = self.buffer.lock();
+        let room = BUFFER_SIZE - buffer.len();
+        buffer.extend_from_slice(&data[..data.len().min(room)]);
+        drop(buffer);
+
+        self.readers.wake_up_all();
+        vfs::poll::notify();
+    }
+
+    fn write_room(&self) -> usize {
+        BUFFER_SIZE - self.buffer.lock().len()
+    }
+
+    fn pending(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    fn flush(&self) {
+        self.buffer.lock().clear();
+    }
+}
+
+impl Pair {
+    /// Create a new pair with the first free number, and register its slave
+    /// side. Returns `None` if all the numbers are used.
+    fn allocate() -> Option<Arc<Self>> {
+        let mut pairs = PAIRS.lock();
+        let number = (0..MAX_PAIRS).find(|number| !pairs.contains_key(number))?;
+
+        let output = Arc::new(Output {
+            buffer: Spinlock::new(CircularBuffer::boxed()),
+            readers: WaitQueue::new(),
+        });
+        let termios = Termios::new(termios::B38400 | termios::CS8 | termios::CREAD);
+        let name = format!("pts/{number}");
+        let tty = Arc::new(Tty::new(
+            &name,
+            Arc::clone(&output) as Arc<dyn Driver>,
+            termios,
+        ));
+
+        let pair = Arc::new(Self {
+            number,
+            tty,
+            output,
+            state: Spinlock::new(State {
+                locked: true,
+                master: true,
+                slaves: 0,
+                hung_up: false,
+            }),
+        });
+
+        let slave = Arc::new(Slave(Arc::clone(&pair)));
+        character::add(pair.slave_id(), slave).ok()?;
+        pairs.insert(number, Arc::clone(&pair));
+        drop(pairs);
+
+        devpts::created(number);
+        Some(pair)
+    }
+
+    /// Returns the identifier of the slave side.
+    fn slave_id(&self) -> Identifier {
+        Identifier {
+            major: SLAVE_MAJOR,
+            minor: self.number,
+        }
+    }
+
+    /// Read the output of the terminal into the given buffer, and returns the
+    /// number of bytes read. The task sleeps until some output is available.
+    ///
+    /// # Errors
+    /// - `ReadError::IoError`: There is no output left and the slave side has
+    ///   been closed.
+    /// - `ReadError::Interrupted`: The task was interrupted while waiting.
+    fn read(&self, buf: &mut [u8]) -> Result<usize, ReadError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let result = self
+            .output
+            .readers
+            .sleep_interruptible_until(|| {
+                let mut buffer = self.output.buffer.lock();
+                if buffer.is_empty() {
+                    drop(buffer);
+                    return self.state.lock().hung_up.then_some(Err(ReadError::IoError));
+                }
+                let len = buf.len().min(buffer.len());
+                for (dst, src) in buf.iter_mut().zip(buffer.drain(..len)) {
+                    *dst = src;
+                }
+                Some(Ok(len))
+            })
+            .map_err(|Interrupted| ReadError::Interrupted)?;
+
+        if result.is_ok() {
+            self.tty.wake_up_writers();
+        }
+        result
+    }
+
+    /// Returns the events ready on the master side.
+    fn poll(&self) -> Events {
+        let mut events = Events::OUT;
+        if !self.output.buffer.lock().is_empty() {
+            events |= Events::IN;
+        }
+        if self.state.lock().hung_up {
+            events |= Events::HUP;
+        }
+        events
+    }
+
+    /// Handle the ioctl requests of the master side. The requests that are not
+    /// specific to the master side are handled by the terminal.
+    ///
+    /// # Errors
+    /// See [`IoctlError`] for the possible errors.
+    fn ioctl(&self, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        match request {
+            termios::TIOCGPTN => super::put(arg, &self.number),
+            termios::TIOCSPTLCK => {
+                let lock = super::get::<i32>(arg)?;
+                self.state.lock().locked = lock != 0;
+                Ok(0)
+            }
+            termios::TIOCGPTLCK => {
+                let locked = self.state.lock().locked;
+                super::put(arg, &i32::from(locked))
+            }
+            _ => self.tty.ioctl(request, arg),
+        }
+    }
+
+    /// Release the pair once both sides are closed.
+    fn release(&self) {
+        PAIRS.lock().remove(&self.number);
+        _ = character::remove(self.slave_id());
+        devpts::removed(self.number);
+    }
+}
+
+/// The data attached to a file opened on the master side. The terminal is hung
+/// up when it is closed.
+struct Master(Arc<Pair>);
+
+impl Drop for Master {
+    fn drop(&mut self) {
+        let pair = &self.0;
+        let release = {
+            let mut state = pair.state.lock();
+            state.master = false;
+            state.slaves == 0
+        };
+
+        pair.tty.hang_up();
+        if release {
+            pair.release();
+        }
+    }
+}
+
+/// The data attached to a file opened on the slave side. The master side is
+/// hung up when the last of them is closed.
+struct SlaveFile(Arc<Pair>);
+
+impl Drop for SlaveFile {
+    fn drop(&mut self) {
+        let pair = &self.0;
+        let (last, release) = {
+            let mut state = pair.state.lock();
+            state.slaves -= 1;
+            state.hung_up = state.slaves == 0;
+            (state.hung_up, state.hung_up && !state.master)
+        };
+
+        if last {
+            pair.output.readers.wake_up_all();
+            vfs::poll::notify();
+        }
+        if release {
+            pair.release();
+        }
+    }
+}
+
+/// The `/dev/ptmx` device, which creates a new pair each time it is opened and
+/// is the master side of that pair.
+struct Ptmx;
+
+impl Ptmx {
+    /// Returns the pair whose master side is the given file.
+    fn pair(file: &File) -> Option<&Arc<Pair>> {
+        file.data.downcast_ref::<Master>().map(|master| &master.0)
+    }
+}
+
+impl CharDevice for Ptmx {
+    fn name(&self) -> &str {
+        "ptmx"
+    }
+
+    fn open(&self, _: OpenFlags) -> Result<OpenData, OpenError> {
+        let pair = Pair::allocate().ok_or(OpenError::IoError)?;
+        Ok(Box::new(Master(pair)))
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        Self::pair(file).ok_or(ReadError::IoError)?.read(buf)
+    }
+
+    fn write(&self, file: &File, buf: &[u8]) -> Result<usize, WriteError> {
+        let pair = Self::pair(file).ok_or(WriteError::IoError)?;
+        pair.tty.receive(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self, file: &File) -> Events {
+        Self::pair(file).map_or(Events::HUP, |pair| pair.poll())
+    }
+
+    fn ioctl(&self, file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        Self::pair(file)
+            .ok_or(IoctlError::NoSuchDevice)?
+            .ioctl(request, arg)
+    }
+}
+
+/// The slave side of a pair, a terminal that can only be opened while the pair
+/// is unlocked and its master side is opened.
+struct Slave(Arc<Pair>);
+
+impl CharDevice for Slave {
+    fn name(&self) -> &str {
+        CharDevice::name(self.0.tty.as_ref())
+    }
+
+    fn open(&self, flags: OpenFlags) -> Result<OpenData, OpenError> {
+        let pair = &self.0;
+        {
+            let mut state = pair.state.lock();
+            if state.locked || !state.master {
+                return Err(OpenError::IoError);
+            }
+            state.slaves += 1;
+            state.hung_up = false;
+        }
+
+        // The file data is created first so that the count of the slave files
+        // is kept right if the terminal refuses to be opened.
+        let file = SlaveFile(Arc::clone(pair));
+        CharDevice::open(pair.tty.as_ref(), flags)?;
+        Ok(Box::new(file))
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        CharDevice::read(self.0.tty.as_ref(), file, buf)
+    }
+
+    fn write(&self, file: &File, buf: &[u8]) -> Result<usize, WriteError> {
+        CharDevice::write(self.0.tty.as_ref(), file, buf)
+    }
+
+    fn poll(&self, file: &File) -> Events {
+        CharDevice::poll(self.0.tty.as_ref(), file)
+    }
+
+    fn ioctl(&self, file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        CharDevice::ioctl(self.0.tty.as_ref(), file, request, arg)
+    }
+
+    fn tty(&self) -> Option<&Tty> {
+        Some(&self.0.tty)
+    }
+}
+
+/// Returns `true` if a pair with the given number is in use.
+#[must_use]
+pub fn exists(number: u32) -> bool {
+    PAIRS.lock().contains_key(&number)
+}
+
+/// Returns the numbers of the pairs in use, in increasing order.
+#[must_use]
+pub fn numbers() -> Vec<u32> {
+    PAIRS.lock().keys().copied().collect()
+}
+
+/// Register `/dev/ptmx` and mount the `devpts` filesystem on `/dev/pts`.
+#[init]
+pub fn setup() {
+    if let Err(error) = character::register(PTMX, Arc::new(Ptmx)) {
+        log::warn!("pty: failed to register ptmx: {:?}", error);
+    }
+
+    let mountpoint = match node::create_directory("pts") {
+        Ok(mountpoint) => mountpoint,
+        Err(error) => {
+            log::warn!("pty: failed to create /dev/pts: {:?}", error);
+            return;
+        }
+    };
+    if let Err(error) = vfs::fs::mount("devpts", Device::None, &mountpoint) {
+        log::warn!("pty: failed to mount devpts on /dev/pts: {:?}", error);
+    }
+}
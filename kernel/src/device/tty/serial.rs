@@ -0,0 +1,436 @@
+//! The serial ports, driven by their interrupts, as the terminals `ttyS0` to
+//! `ttyS3`. The first serial port is the serial console: it is also used by the
+//! kernel logger, and reachable through `/dev/console`. With QEMU started with
+//! `-nographic`, this is the terminal QEMU runs in.
+//!
+//! Each port has a receive and a transmit ring buffer. The interrupt handler
+//! moves the bytes received by the port into the receive buffer, then gives them
+//! to the tty, and refills the transmit FIFO of the port from the transmit buffer
+//! each time it becomes empty. The kernel logs go through the transmit buffer of
+//! the console, so that they are not mixed with the output of the tty, but are
+//! sent with polling when the buffer is full, and when the kernel panics.
+use super::{
+    termios::{self, Termios},
+    Driver, Tty,
+};
+use crate::{
+    device::{
+        character::{self, CharDevice, OpenData, OpenError},
+        Identifier,
+    },
+    logger::SERIAL,
+    vfs::{
+        file::{File, IoctlError, OpenFlags, ReadError, WriteError},
+        poll::Events,
+    },
+    x86_64::{
+        self,
+        irq::Affinity,
+        serial::{Config, Interrupts, Parity, Port, Serial, FIFO_SIZE},
+    },
+};
+use alloc::format;
+use circular_buffer::CircularBuffer;
+
+/// The major number of the serial ports, the same as on Linux. The minor
+/// numbers of `ttyS0` to `ttyS3` start at 64.
+const MAJOR: u32 = 4;
+const FIRST_MINOR: u32 = 64;
+
+/// The identifier of `/dev/console`, the same as on Linux.
+const CONSOLE: Identifier = Identifier { major: 5, minor: 1 };
+
+/// The size of the receive buffer of a port. The received bytes are given to
+/// the tty as soon as the interrupt is handled, so it does not need to be large.
+const RECEIVE_BUFFER_SIZE: usize = 256;
+
+/// The size of the transmit buffer of a port.
+const TRANSMIT_BUFFER_SIZE: usize = 4096;
+
+/// The number of bytes left in the transmit buffer under which the tasks
+/// waiting to write are woken up.
+const WAKE_UP_THRESHOLD: usize = TRANSMIT_BUFFER_SIZE / 2;
+
+/// The number of bytes given at most to the tty at once by the interrupt
+/// handler.
+const RECEIVE_CHUNK: usize = 64;
+
+/// The serial ports found, indexed by their number.
+static CHANNELS: [Once<Channel>; 4] = [Once::new(), Once::new(), Once::new(), Once::new()];
+
+/// A serial port found on the system and its terminal.
+struct Channel {
+    uart: Arc<Uart>,
+    tty: Arc<Tty>,
+}
+
+/// The driver of a 16550 UART.
+pub struct Uart {
+    /// The serial port driven.
+    port: Port,
+
+    /// The state of the port, shared with its interrupt handler.
+    state: Spinlock<State>,
+}
+
+/// The state of a serial port.
+struct State {
+    /// The registers of the port.
+    serial: Serial,
+
+    /// The interrupts enabled on the port.
+    interrupts: Interrupts,
+
+    /// The configuration of the line.
+    config: Config,
+
+    /// Discard the bytes received, when the receiver is disabled by clearing
+    /// `CREAD` in the attributes of the terminal.
+    ignore_input: bool,
+
+    /// The bytes received and not given to the tty yet.
+    receive: Box<CircularBuffer<RECEIVE_BUFFER_SIZE, u8>>,
+
+    /// The bytes written and not sent yet.
+    transmit: Box<CircularBuffer<TRANSMIT_BUFFER_SIZE, u8>>,
+}
+
+impl State {
+    /// Write as many bytes of the transmit buffer as possible to the transmit
+    /// FIFO of the port, without waiting, and returns `true` if some bytes were
+    /// written.
+    fn refill(&mut self) -> bool {
+        if !self.serial.is_transmit_empty() || self.transmit.is_empty() {
+            return false;
+        }
+        for byte in self.transmit.drain(..FIFO_SIZE.min(self.transmit.len())) {
+            self.serial.send(byte);
+        }
+        true
+    }
+
+    /// Start or continue the transmission of the transmit buffer. The transmit
+    /// interrupt is enabled until the transmit FIFO is empty and there is no
+    /// more byte to send.
+    fn transmit(&mut self) {
+        let sending = self.refill() || !self.transmit.is_empty();
+        let mut interrupts = self.interrupts;
+        interrupts.set(Interrupts::TRANSMIT, sending);
+        self.set_interrupts(interrupts);
+    }
+
+    /// Push a byte to the transmit buffer, sending the oldest bytes with polling
+    /// while it is full.
+    fn push_polled(&mut self, byte: u8) {
+        while self.transmit.is_full() {
+            while !self.serial.is_transmit_empty() {
+                core::hint::spin_loop();
+            }
+            self.refill();
+        }
+        self.transmit.push_back(byte);
+    }
+
+    /// Enable the given interrupts on the port, if they are not already.
+    fn set_interrupts(&mut self, interrupts: Interrupts) {
+        if self.interrupts != interrupts {
+            self.interrupts = interrupts;
+            self.serial.set_interrupts(interrupts);
+        }
+    }
+}
+
+impl core::fmt::Write for State {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        s.bytes().for_each(|byte| self.push_polled(byte));
+        Ok(())
+    }
+}
+
+impl Uart {
+    /// Create the driver of the given port.
+    fn new(port: Port, serial: Serial) -> Self {
+        Self {
+            port,
+            state: Spinlock::new(State {
+                serial,
+                interrupts: Interrupts::empty(),
+                config: Config::DEFAULT,
+                ignore_input: false,
+                receive: CircularBuffer::boxed(),
+                transmit: CircularBuffer::boxed(),
+            }),
+        }
+    }
+
+    /// Write the given kernel log message to the port. The message is sent with
+    /// polling while the transmit buffer is full, so that log messages are never
+    /// lost.
+    pub fn log(&self, args: core::fmt::Arguments) {
+        x86_64::irq::without(|| {
+            let mut state = self.state.lock();
+            _ = core::fmt::Write::write_fmt(&mut *state, args);
+            state.transmit();
+        });
+    }
+
+    /// Handle the interrupts pending on the port: move the received bytes to the
+    /// receive buffer and refill the transmit FIFO. Returns `true` if the tasks
+    /// waiting to write must be woken up, because room was made in the transmit
+    /// buffer or because everything has been sent.
+    fn service(&self) -> bool {
+        let mut state = self.state.lock();
+        let before = state.transmit.len();
+        let mut handled = false;
+
+        while state.serial.interrupt_pending() {
+            while let Some(byte) = state.serial.receive() {
+                if !state.ignore_input {
+                    // Drop the byte if the tty is not fast enough.
+                    _ = state.receive.try_push_back(byte);
+                }
+            }
+            state.transmit();
+            handled = true;
+        }
+
+        let after = state.transmit.len();
+        let sending = state.interrupts.contains(Interrupts::TRANSMIT);
+        handled && (before > WAKE_UP_THRESHOLD && after <= WAKE_UP_THRESHOLD || !sending)
+    }
+
+    /// Take the bytes received into the given buffer, and returns the number of
+    /// bytes taken.
+    fn take_received(&self, buf: &mut [u8]) -> usize {
+        let mut state = self.state.lock();
+        let len = buf.len().min(state.receive.len());
+        for (dst, src) in buf.iter_mut().zip(state.receive.drain(..len)) {
+            *dst = src;
+        }
+        len
+    }
+
+    /// Enable the interrupts of the port.
+    fn enable(&self) {
+        self.state.lock().set_interrupts(Interrupts::RECEIVE);
+    }
+}
+
+impl Driver for Uart {
+    fn write(&self, data: &[u8]) {
+        let mut state = self.state.lock();
+        let room = TRANSMIT_BUFFER_SIZE - state.transmit.len();
+        state
+            .transmit
+            .extend_from_slice(&data[..data.len().min(room)]);
+        state.transmit();
+    }
+
+    fn write_room(&self) -> usize {
+        TRANSMIT_BUFFER_SIZE - self.state.lock().transmit.len()
+    }
+
+    fn pending(&self) -> usize {
+        let state = self.state.lock();
+        state.transmit.len() + usize::from(!state.serial.is_transmit_empty())
+    }
+
+    fn flush(&self) {
+        self.state.lock().transmit.clear();
+    }
+
+    fn set_termios(&self, termios: &Termios) {
+        let mut state = self.state.lock();
+        let config = Config {
+            baud_rate: termios.baud_rate().unwrap_or(state.config.baud_rate),
+            data_bits: termios.data_bits(),
+            parity: match (
+                termios.control(termios::PARENB),
+                termios.control(termios::PARODD),
+            ) {
+                (false, _) => Parity::None,
+                (true, false) => Parity::Even,
+                (true, true) => Parity::Odd,
+            },
+            two_stop_bits: termios.control(termios::CSTOPB),
+        };
+
+        state.ignore_input = !termios.control(termios::CREAD);
+        if config != state.config {
+            state.serial.configure(config);
+            state.config = config;
+
+            // The logs may go through this port, which must be unlocked first.
+            drop(state);
+            log_config(self.port, config);
+        }
+    }
+}
+
+/// The `/dev/console` device, which forwards everything to the terminal of the
+/// serial console.
+struct Console(Arc<Tty>);
+
+impl CharDevice for Console {
+    fn name(&self) -> &str {
+        "console"
+    }
+
+    fn open(&self, flags: OpenFlags) -> Result<OpenData, OpenError> {
+        CharDevice::open(self.0.as_ref(), flags)
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> Result<usize, ReadError> {
+        CharDevice::read(self.0.as_ref(), file, buf)
+    }
+
+    fn write(&self, file: &File, buf: &[u8]) -> Result<usize, WriteError> {
+        CharDevice::write(self.0.as_ref(), file, buf)
+    }
+
+    fn poll(&self, file: &File) -> Events {
+        CharDevice::poll(self.0.as_ref(), file)
+    }
+
+    fn ioctl(&self, file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        CharDevice::ioctl(self.0.as_ref(), file, request, arg)
+    }
+
+    fn tty(&self) -> Option<&Tty> {
+        Some(&self.0)
+    }
+}
+
+/// Returns the terminal of the serial console, or `None` if there is no serial
+/// console.
+#[must_use]
+pub fn tty() -> Option<&'static Arc<Tty>> {
+    CHANNELS[0].get().map(|channel| &channel.tty)
+}
+
+/// Returns the driver of the serial console, or `None` if it is not set up yet.
+#[must_use]
+pub fn console() -> Option<&'static Uart> {
+    CHANNELS[0].get().map(|channel| channel.uart.as_ref())
+}
+
+/// Log the new configuration of the line of the given port.
+fn log_config(port: Port, config: Config) {
+    let parity = match config.parity {
+        Parity::None => 'N',
+        Parity::Odd => 'O',
+        Parity::Even => 'E',
+    };
+    let stop_bits = if config.two_stop_bits { 2 } else { 1 };
+    log::debug!(
+        "serial: {:?} at {} bauds, {}{}{}",
+        port,
+        config.baud_rate,
+        config.data_bits,
+        parity,
+        stop_bits
+    );
+}
+
+/// Handle the interrupts of the serial ports sharing the given IRQ, and give
+/// the bytes received to their terminal.
+fn interrupt(irq: u8) {
+    let channels = CHANNELS
+        .iter()
+        .filter_map(Once::get)
+        .filter(|channel| channel.uart.port.irq() == irq);
+
+    for channel in channels {
+        if channel.uart.service() {
+            channel.tty.wake_up_writers();
+        }
+
+        let mut data = [0; RECEIVE_CHUNK];
+        loop {
+            let len = channel.uart.take_received(&mut data);
+            if len == 0 {
+                break;
+            }
+            channel.tty.receive(&data[..len]);
+        }
+    }
+}
+
+/// Called when the kernel panics. This function force the unlock of the serial
+/// console, disables its interrupts, and sends the bytes left in its transmit
+/// buffer with polling, before the panic message is written with polling too.
+///
+/// # Safety
+/// This function is unsafe because it force the unlock of the serial console,
+/// which could cause a undefined behavior if it is used by several threads after
+/// this function call.
+#[cold]
+pub unsafe fn on_panic() {
+    if let Some(channel) = CHANNELS[0].get() {
+        channel.uart.state.force_unlock();
+        let mut state = channel.uart.state.lock();
+        state.set_interrupts(Interrupts::empty());
+        while let Some(byte) = state.transmit.pop_front() {
+            state.serial.write(byte);
+        }
+    }
+}
+
+/// Find the serial ports, create their terminal and their device nodes, and
+/// enable their interrupts. The first serial port is already used by the kernel
+/// logger and is assumed to exist, and its terminal is also the serial console.
+#[init]
+pub fn setup() {
+    let termios = Termios::new(termios::B38400 | termios::CS8 | termios::CREAD | termios::CLOCAL);
+
+    for (index, port) in Port::ALL.into_iter().enumerate() {
+        let serial = if port == Port::COM1 {
+            x86_64::irq::without(|| SERIAL.lock().clone())
+        } else {
+            // SAFETY: The serial ports other than the first one are not used by
+            // any other kernel component, and writing to the ports of a missing
+            // serial port has no effect.
+            let serial = unsafe { Serial::new(port) };
+            if !serial.exists() {
+                continue;
+            }
+            serial
+        };
+
+        let uart = Arc::new(Uart::new(port, serial));
+        let name = format!("ttyS{index}");
+        let tty = Arc::new(Tty::new(
+            &name,
+            Arc::clone(&uart) as Arc<dyn Driver>,
+            termios,
+        ));
+        let channel = CHANNELS[index].call_once(|| Channel { uart, tty });
+
+        #[allow(clippy::cast_possible_truncation)]
+        let id = Identifier {
+            major: MAJOR,
+            minor: FIRST_MINOR + index as u32,
+        };
+        if let Err(error) = character::register(id, Arc::clone(&channel.tty) as Arc<dyn CharDevice>)
+        {
+            log::warn!("tty: failed to register {}: {:?}", name, error);
+        }
+        if index == 0 {
+            let console = Arc::new(Console(Arc::clone(&channel.tty)));
+            if let Err(error) = character::register(CONSOLE, console) {
+                log::warn!("tty: failed to register the console: {:?}", error);
+            }
+        }
+    }
+
+    x86_64::irq::without(|| {
+        for irq in [Port::COM1.irq(), Port::COM2.irq()] {
+            if let Err(error) = x86_64::irq::request(irq, interrupt, Affinity::Bsp) {
+                log::warn!("tty: failed to request IRQ {}: {:?}", irq, error);
+            }
+        }
+        for channel in CHANNELS.iter().filter_map(Once::get) {
+            channel.uart.enable();
+        }
+    });
+}
@@ -0,0 +1,282 @@
+//! The terminal attributes and the window size of a tty, with the layouts used
+//! by the Linux ioctl requests, so that the userland can use them unmodified.
+//! The values of the flags and the indexes of the control characters are the
+//! ones of Linux on `x86_64`.
+
+/// The number of control characters in the `cc` field of [`Termios`].
+pub const NCCS: usize = 19;
+
+/// The value of a control character that is disabled.
+pub const DISABLED: u8 = 0;
+
+/// The indexes of the control characters.
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VTIME: usize = 5;
+pub const VMIN: usize = 6;
+pub const VSTART: usize = 8;
+pub const VSTOP: usize = 9;
+pub const VSUSP: usize = 10;
+pub const VEOL: usize = 11;
+pub const VREPRINT: usize = 12;
+pub const VWERASE: usize = 14;
+pub const VLNEXT: usize = 15;
+pub const VEOL2: usize = 16;
+
+/// The input flags.
+pub const IGNBRK: u32 = 0o000_001;
+pub const BRKINT: u32 = 0o000_002;
+pub const ISTRIP: u32 = 0o000_040;
+pub const INLCR: u32 = 0o000_100;
+pub const IGNCR: u32 = 0o000_200;
+pub const ICRNL: u32 = 0o000_400;
+pub const IXON: u32 = 0o002_000;
+pub const IXOFF: u32 = 0o010_000;
+pub const IMAXBEL: u32 = 0o020_000;
+pub const IUTF8: u32 = 0o040_000;
+
+/// The output flags.
+pub const OPOST: u32 = 0o000_001;
+pub const ONLCR: u32 = 0o000_004;
+pub const OCRNL: u32 = 0o000_010;
+pub const ONOCR: u32 = 0o000_020;
+pub const ONLRET: u32 = 0o000_040;
+
+/// The control flags.
+pub const CBAUD: u32 = 0o010_017;
+pub const B0: u32 = 0o000_000;
+pub const B50: u32 = 0o000_001;
+pub const B75: u32 = 0o000_002;
+pub const B110: u32 = 0o000_003;
+pub const B134: u32 = 0o000_004;
+pub const B150: u32 = 0o000_005;
+pub const B200: u32 = 0o000_006;
+pub const B300: u32 = 0o000_007;
+pub const B600: u32 = 0o000_010;
+pub const B1200: u32 = 0o000_011;
+pub const B1800: u32 = 0o000_012;
+pub const B2400: u32 = 0o000_013;
+pub const B4800: u32 = 0o000_014;
+pub const B9600: u32 = 0o000_015;
+pub const B19200: u32 = 0o000_016;
+pub const B38400: u32 = 0o000_017;
+pub const B57600: u32 = 0o010_001;
+pub const B115200: u32 = 0o010_002;
+pub const CSIZE: u32 = 0o000_060;
+pub const CS5: u32 = 0o000_000;
+pub const CS6: u32 = 0o000_020;
+pub const CS7: u32 = 0o000_040;
+pub const CS8: u32 = 0o000_060;
+pub const CSTOPB: u32 = 0o000_100;
+pub const CREAD: u32 = 0o000_200;
+pub const PARENB: u32 = 0o000_400;
+pub const PARODD: u32 = 0o001_000;
+pub const HUPCL: u32 = 0o002_000;
+pub const CLOCAL: u32 = 0o004_000;
+
+/// The local flags.
+pub const ISIG: u32 = 0o000_001;
+pub const ICANON: u32 = 0o000_002;
+pub const ECHO: u32 = 0o000_010;
+pub const ECHOE: u32 = 0o000_020;
+pub const ECHOK: u32 = 0o000_040;
+pub const ECHONL: u32 = 0o000_100;
+pub const NOFLSH: u32 = 0o000_200;
+pub const TOSTOP: u32 = 0o000_400;
+pub const ECHOCTL: u32 = 0o001_000;
+pub const ECHOKE: u32 = 0o004_000;
+pub const IEXTEN: u32 = 0o100_000;
+
+/// Get the attributes of the terminal, as a [`Termios`].
+pub const TCGETS: usize = 0x5401;
+
+/// Set the attributes of the terminal immediately.
+pub const TCSETS: usize = 0x5402;
+
+/// Set the attributes of the terminal once the output has been sent.
+pub const TCSETSW: usize = 0x5403;
+
+/// Set the attributes of the terminal once the output has been sent, and
+/// discard the input not read yet.
+pub const TCSETSF: usize = 0x5404;
+
+/// Discard the input not read yet (`TCIFLUSH`), the output not sent yet
+/// (`TCOFLUSH`), or both (`TCIOFLUSH`).
+pub const TCFLSH: usize = 0x540B;
+pub const TCIFLUSH: usize = 0;
+pub const TCOFLUSH: usize = 1;
+pub const TCIOFLUSH: usize = 2;
+
+/// Make the terminal the controlling terminal of the current task.
+pub const TIOCSCTTY: usize = 0x540E;
+
+/// Get and set the foreground task of the terminal, as an `int`.
+pub const TIOCGPGRP: usize = 0x540F;
+pub const TIOCSPGRP: usize = 0x5410;
+
+/// Get the number of bytes not sent yet, as an `int`.
+pub const TIOCOUTQ: usize = 0x5411;
+
+/// Get and set the window size, as a [`WindowSize`].
+pub const TIOCGWINSZ: usize = 0x5413;
+pub const TIOCSWINSZ: usize = 0x5414;
+
+/// Get the number of bytes that can be read, as an `int`.
+pub const FIONREAD: usize = 0x541B;
+
+/// Give up the controlling terminal of the current task.
+pub const TIOCNOTTY: usize = 0x5422;
+
+/// Get the number of a pseudo-terminal from its master side, as an `unsigned
+/// int`.
+pub const TIOCGPTN: usize = 0x8004_5430;
+
+/// Lock (with a non-zero `int`) or unlock the slave side of a pseudo-terminal
+/// from its master side.
+pub const TIOCSPTLCK: usize = 0x4004_5431;
+
+/// Get whether the slave side of a pseudo-terminal is locked, as an `int`.
+pub const TIOCGPTLCK: usize = 0x8004_5439;
+
+/// The attributes of a terminal, with the layout of the `termios` structure
+/// used by the Linux ioctl requests (which differs from the structure of the C
+/// library, which has more control characters and the speeds).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Termios {
+    /// The input flags.
+    pub iflag: u32,
+
+    /// The output flags.
+    pub oflag: u32,
+
+    /// The control flags, including the speed of the line.
+    pub cflag: u32,
+
+    /// The local flags, controlling the line discipline.
+    pub lflag: u32,
+
+    /// The line discipline, always `N_TTY` (0).
+    pub line: u8,
+
+    /// The control characters.
+    pub cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// The control characters used by default, the same as Linux: `^C` to
+    /// interrupt, `^\` to quit, DEL to erase, `^U` to kill the line, `^D` for
+    /// the end of file, `^Q` and `^S` to start and stop the output, `^Z` to
+    /// suspend, `^R` to reprint the line, `^W` to erase a word and `^V` to insert
+    /// the next character literally.
+    const DEFAULT_CC: [u8; NCCS] = [
+        0x03, 0x1C, 0x7F, 0x15, 0x04, 0, 1, 0, 0x11, 0x13, 0x1A, 0, 0x12, 0x0F, 0x17, 0x16, 0, 0, 0,
+    ];
+
+    /// Returns the attributes used by default for a terminal with the given
+    /// control flags: canonical mode with echo, signals, and the translation of
+    /// carriage returns and newlines expected by most terminal emulators.
+    #[must_use]
+    pub const fn new(cflag: u32) -> Self {
+        Self {
+            iflag: ICRNL | IXON | IUTF8,
+            oflag: OPOST | ONLCR,
+            cflag,
+            lflag: ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL | ECHOKE | IEXTEN,
+            line: 0,
+            cc: Self::DEFAULT_CC,
+        }
+    }
+
+    /// Returns `true` if all the given input flags are set.
+    #[must_use]
+    pub const fn input(&self, flags: u32) -> bool {
+        self.iflag & flags == flags
+    }
+
+    /// Returns `true` if all the given output flags are set.
+    #[must_use]
+    pub const fn output(&self, flags: u32) -> bool {
+        self.oflag & flags == flags
+    }
+
+    /// Returns `true` if all the given local flags are set.
+    #[must_use]
+    pub const fn local(&self, flags: u32) -> bool {
+        self.lflag & flags == flags
+    }
+
+    /// Returns `true` if all the given control flags are set.
+    #[must_use]
+    pub const fn control(&self, flags: u32) -> bool {
+        self.cflag & flags == flags
+    }
+
+    /// Returns the speed of the line in bits per second, or `None` if the
+    /// speed is `B0` (hang up) or not a standard speed.
+    #[must_use]
+    pub const fn baud_rate(&self) -> Option<u32> {
+        match self.cflag & CBAUD {
+            B50 => Some(50),
+            B75 => Some(75),
+            B110 => Some(110),
+            B134 => Some(134),
+            B150 => Some(150),
+            B200 => Some(200),
+            B300 => Some(300),
+            B600 => Some(600),
+            B1200 => Some(1200),
+            B1800 => Some(1800),
+            B2400 => Some(2400),
+            B4800 => Some(4800),
+            B9600 => Some(9600),
+            B19200 => Some(19200),
+            B38400 => Some(38400),
+            B57600 => Some(57600),
+            B115200 => Some(115_200),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of data bits of each character, between 5 and 8.
+    #[must_use]
+    pub const fn data_bits(&self) -> u8 {
+        match self.cflag & CSIZE {
+            CS5 => 5,
+            CS6 => 6,
+            CS7 => 7,
+            _ => 8,
+        }
+    }
+
+    /// Returns `true` if the line discipline is in canonical mode, where the
+    /// input is edited and made available line by line.
+    #[must_use]
+    pub const fn canonical(&self) -> bool {
+        self.local(ICANON)
+    }
+
+    /// Returns `true` if the given byte is the control character at the given
+    /// index. Disabled control characters never match.
+    #[must_use]
+    pub const fn is(&self, byte: u8, index: usize) -> bool {
+        self.cc[index] != DISABLED && self.cc[index] == byte
+    }
+}
+
+/// The size of the window of a terminal. It is not used by the kernel, but
+/// stored for the userland, which is told when it changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct WindowSize {
+    /// The number of rows and columns, in characters.
+    pub rows: u16,
+    pub columns: u16,
+
+    /// The width and height of the window in pixels, usually unused.
+    pub xpixel: u16,
+    pub ypixel: u16,
+}
@@ -0,0 +1,114 @@
+//! PC Screen Fonts, the bitmap fonts of the Linux console. A PSF font is a
+//! header followed by the bitmaps of its glyphs, each row of a glyph being
+//! padded to a whole number of bytes, the most significant bit being the
+//! leftmost pixel. Both versions of the format are supported, but the optional
+//! Unicode table mapping characters to glyphs is ignored: the character with
+//! the code point `n` is drawn with the glyph `n`.
+//!
+//! The built-in font has 256 glyphs of 8x16 pixels for the Latin-1 characters,
+//! rendered from `DejaVu Sans Mono`. The glyph 0 is a box drawn for the
+//! characters the font does not have.
+
+/// The built-in font.
+pub static BUILTIN: Lazy<Font<'static>> =
+    Lazy::new(|| Font::parse(include_bytes!("font.psf")).expect("Invalid built-in font"));
+
+/// The magic numbers of the two versions of the format.
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+
+/// The size of the header of the first version of the format.
+const PSF1_HEADER_SIZE: usize = 4;
+
+/// The mode flag of the first version of the format set when the font has 512
+/// glyphs instead of 256.
+const PSF1_MODE_512: u8 = 0x01;
+
+/// The glyph drawn for the characters that the font does not have.
+const MISSING_GLYPH: usize = 0;
+
+/// A bitmap font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Font<'a> {
+    /// The bitmaps of the glyphs, one after the other.
+    glyphs: &'a [u8],
+
+    /// The number of glyphs of the font.
+    count: usize,
+
+    /// The width and height of a glyph, in pixels.
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Font<'a> {
+    /// Parse the given PSF font, and returns `None` if it is not a valid font.
+    #[must_use]
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let (offset, count, width, height) = if data.starts_with(&PSF1_MAGIC) {
+            let mode = *data.get(2)?;
+            let count = if mode & PSF1_MODE_512 == 0 { 256 } else { 512 };
+            (PSF1_HEADER_SIZE, count, 8, usize::from(*data.get(3)?))
+        } else if data.starts_with(&PSF2_MAGIC) {
+            let field = |index: usize| -> Option<usize> {
+                let bytes = data.get(index * 4..index * 4 + 4)?;
+                usize::try_from(u32::from_le_bytes(bytes.try_into().ok()?)).ok()
+            };
+            (field(2)?, field(4)?, field(7)?, field(6)?)
+        } else {
+            return None;
+        };
+
+        if count == 0 || width == 0 || height == 0 {
+            return None;
+        }
+        let size = (width + 7) / 8 * height * count;
+        let glyphs = data.get(offset..offset.checked_add(size)?)?;
+        Some(Self {
+            glyphs,
+            count,
+            width,
+            height,
+        })
+    }
+
+    /// Returns the width of a glyph, in pixels.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of a glyph, in pixels.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of bytes of a row of a glyph.
+    #[must_use]
+    pub fn pitch(&self) -> usize {
+        (self.width + 7) / 8
+    }
+
+    /// Returns the index of the glyph drawn for the given character.
+    #[must_use]
+    pub fn index(&self, c: char) -> usize {
+        match usize::try_from(u32::from(c)) {
+            Ok(index) if index < self.count => index,
+            _ => MISSING_GLYPH,
+        }
+    }
+
+    /// Returns the bitmap of the glyph with the given index, or of the missing
+    /// glyph if there is no such glyph.
+    #[must_use]
+    pub fn glyph(&self, index: usize) -> &'a [u8] {
+        let index = if index < self.count {
+            index
+        } else {
+            MISSING_GLYPH
+        };
+        let size = self.pitch() * self.height;
+        &self.glyphs[index * size..(index + 1) * size]
+    }
+}
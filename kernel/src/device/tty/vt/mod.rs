@@ -0,0 +1,716 @@
+//! The framebuffer console, a text terminal drawn on the framebuffer set up by
+//! the bootloader with the built-in font (see [`font`]). It is a second sink for
+//! the kernel logs, drawn as soon as the memory manager is set up so that the
+//! boot can be followed without a serial cable, and the terminal `tty0`, whose
+//! input is typed on the PS/2 keyboard.
+//!
+//! The output is parsed as the output of a VT100 (see [`parser`]): the escape
+//! sequences moving the cursor, erasing and editing the screen, setting the
+//! scrolling region and the colors are supported, like the ones of the logger.
+//! The characters of the screen are kept in a grid of cells allocated when the
+//! console is created, since the output can be written from interrupt handlers
+//! and must never allocate. The state of the console is therefore always locked
+//! with interrupts disabled.
+use super::{
+    termios::{self, Termios, WindowSize},
+    Driver, Tty,
+};
+use crate::{
+    device::{
+        character::{self, CharDevice},
+        fb, Identifier,
+    },
+    x86_64,
+};
+use alloc::vec;
+use font::BUILTIN;
+use parser::{Action, Parser, Sequence};
+use screen::Screen;
+
+pub mod font;
+pub mod parser;
+pub mod screen;
+
+/// The identifier of `/dev/tty0`, the same as on Linux.
+const TTY0: Identifier = Identifier { major: 4, minor: 0 };
+
+/// The distance between two tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// The colors of the VGA text mode, used for the 16 first indexed colors.
+const PALETTE: [u32; 16] = [
+    0x00_0000, 0xAA_0000, 0x00_AA00, 0xAA_5500, 0x00_00AA, 0xAA_00AA, 0x00_AAAA, 0xAA_AAAA,
+    0x55_5555, 0xFF_5555, 0x55_FF55, 0xFF_FF55, 0x55_55FF, 0xFF_55FF, 0x55_FFFF, 0xFF_FFFF,
+];
+
+/// The intensities of the components of the 6x6x6 color cube of the indexed
+/// colors 16 to 231.
+const CUBE: [u32; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+
+/// The framebuffer console, if there is a framebuffer.
+static CONSOLE: Once<Arc<Console>> = Once::new();
+
+/// The terminal of the framebuffer console.
+static TTY: Once<Arc<Tty>> = Once::new();
+
+/// A color of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Color {
+    /// A color of the 256 colors palette of the xterm terminal, the 16 first
+    /// being the colors of the VGA text mode.
+    Indexed(u8),
+
+    /// A color given by its components, as `0xRRGGBB`.
+    Rgb(u32),
+}
+
+impl Color {
+    /// The default foreground and background colors.
+    const FOREGROUND: Self = Self::Indexed(7);
+    const BACKGROUND: Self = Self::Indexed(0);
+
+    /// Returns the components of the color, as `0xRRGGBB`. The 8 first indexed
+    /// colors are replaced by their bright variant if `bright` is set.
+    fn rgb(self, bright: bool) -> u32 {
+        match self {
+            Self::Indexed(index @ 0..=7) if bright => PALETTE[usize::from(index) + 8],
+            Self::Indexed(index @ 0..=15) => PALETTE[usize::from(index)],
+            Self::Indexed(index @ 16..=231) => {
+                let index = usize::from(index - 16);
+                (CUBE[index / 36] << 16) | (CUBE[index / 6 % 6] << 8) | CUBE[index % 6]
+            }
+            Self::Indexed(index) => {
+                let level = 8 + 10 * u32::from(index - 232);
+                (level << 16) | (level << 8) | level
+            }
+            Self::Rgb(rgb) => rgb,
+        }
+    }
+}
+
+/// The attributes of a cell, set by the `SGR` control sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Attributes {
+    foreground: Color,
+    background: Color,
+    bold: bool,
+    underline: bool,
+    reverse: bool,
+}
+
+impl Attributes {
+    /// The attributes of the console when it is reset.
+    const DEFAULT: Self = Self {
+        foreground: Color::FOREGROUND,
+        background: Color::BACKGROUND,
+        bold: false,
+        underline: false,
+        reverse: false,
+    };
+}
+
+/// A cell of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Cell {
+    /// The index of the glyph of the character of the cell.
+    glyph: usize,
+
+    /// The attributes of the cell.
+    attributes: Attributes,
+}
+
+/// The position of the cursor and the attributes saved by `DECSC`, and restored
+/// by `DECRC`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Saved {
+    x: usize,
+    y: usize,
+    attributes: Attributes,
+}
+
+/// The state of the framebuffer console.
+struct Terminal {
+    /// The screen the console is drawn on.
+    screen: Screen,
+
+    /// The parser of the output.
+    parser: Parser,
+
+    /// The cells of the screen, row after row.
+    cells: Vec<Cell>,
+
+    /// The position of the cursor.
+    x: usize,
+    y: usize,
+
+    /// Set when a character has been printed in the last column: the cursor
+    /// stays there, and the next character printed goes to the next line.
+    wrap: bool,
+
+    /// The attributes of the characters printed.
+    attributes: Attributes,
+
+    /// The position of the cursor and the attributes saved.
+    saved: Saved,
+
+    /// The first row of the scrolling region, and the row after its last row.
+    top: usize,
+    bottom: usize,
+
+    /// Set if the cursor is shown.
+    cursor: bool,
+
+    /// The position where the cursor is drawn, if it is.
+    drawn: Option<(usize, usize)>,
+}
+
+impl Terminal {
+    /// Create a terminal drawn on the given screen, and clear the screen.
+    fn new(screen: Screen) -> Self {
+        let (columns, rows) = (screen.columns(), screen.rows());
+        let blank = Cell {
+            glyph: screen.font().index(' '),
+            attributes: Attributes::DEFAULT,
+        };
+        let mut terminal = Self {
+            screen,
+            parser: Parser::new(),
+            cells: vec![blank; columns * rows],
+            x: 0,
+            y: 0,
+            wrap: false,
+            attributes: Attributes::DEFAULT,
+            saved: Saved {
+                x: 0,
+                y: 0,
+                attributes: Attributes::DEFAULT,
+            },
+            top: 0,
+            bottom: rows,
+            cursor: true,
+            drawn: None,
+        };
+        terminal.erase(0, columns * rows);
+        terminal
+    }
+
+    fn columns(&self) -> usize {
+        self.screen.columns()
+    }
+
+    fn rows(&self) -> usize {
+        self.screen.rows()
+    }
+
+    /// Returns the blank cell written when erasing, which keeps the background
+    /// color of the current attributes.
+    fn blank(&self) -> Cell {
+        Cell {
+            glyph: self.screen.font().index(' '),
+            attributes: Attributes {
+                background: self.attributes.background,
+                ..Attributes::DEFAULT
+            },
+        }
+    }
+
+    /// Draw the cell at the given position, with its colors swapped if
+    /// `inverted` is set.
+    fn draw(&self, x: usize, y: usize, inverted: bool) {
+        let cell = self.cells[y * self.columns() + x];
+        let attributes = cell.attributes;
+        let mut foreground = attributes.foreground.rgb(attributes.bold);
+        let mut background = attributes.background.rgb(false);
+        if attributes.reverse != inverted {
+            core::mem::swap(&mut foreground, &mut background);
+        }
+        self.screen.draw(
+            x,
+            y,
+            cell.glyph,
+            self.screen.pixel(foreground),
+            self.screen.pixel(background),
+            attributes.underline,
+        );
+    }
+
+    /// Erase the cells with the given indexes in the grid, from `start` to
+    /// `end` excluded.
+    fn erase(&mut self, start: usize, end: usize) {
+        let blank = self.blank();
+        let columns = self.columns();
+        for index in start..end.min(self.cells.len()) {
+            self.cells[index] = blank;
+            self.draw(index % columns, index / columns, false);
+        }
+    }
+
+    /// Scroll the rows from `top` to `bottom` excluded up by the given number
+    /// of rows, and erase the rows revealed at the bottom.
+    fn scroll_up(&mut self, top: usize, bottom: usize, count: usize) {
+        let columns = self.columns();
+        let count = count.min(bottom - top);
+        self.cells
+            .copy_within((top + count) * columns..bottom * columns, top * columns);
+        self.screen
+            .move_rows(top + count, top, bottom - top - count);
+        self.erase((bottom - count) * columns, bottom * columns);
+    }
+
+    /// Scroll the rows from `top` to `bottom` excluded down by the given number
+    /// of rows, and erase the rows revealed at the top.
+    fn scroll_down(&mut self, top: usize, bottom: usize, count: usize) {
+        let columns = self.columns();
+        let count = count.min(bottom - top);
+        self.cells.copy_within(
+            top * columns..(bottom - count) * columns,
+            (top + count) * columns,
+        );
+        self.screen
+            .move_rows(top, top + count, bottom - top - count);
+        self.erase(top * columns, (top + count) * columns);
+    }
+
+    /// Move the cursor to the given position, clamped to the screen.
+    fn goto(&mut self, x: usize, y: usize) {
+        self.x = x.min(self.columns() - 1);
+        self.y = y.min(self.rows() - 1);
+        self.wrap = false;
+    }
+
+    /// Move the cursor to the next line, scrolling the scrolling region if the
+    /// cursor is on its last row.
+    fn line_feed(&mut self) {
+        if self.y + 1 == self.bottom {
+            self.scroll_up(self.top, self.bottom, 1);
+        } else if self.y + 1 < self.rows() {
+            self.y += 1;
+        }
+        self.wrap = false;
+    }
+
+    /// Move the cursor to the previous line, scrolling the scrolling region if
+    /// the cursor is on its first row.
+    fn reverse_index(&mut self) {
+        if self.y == self.top {
+            self.scroll_down(self.top, self.bottom, 1);
+        } else if self.y > 0 {
+            self.y -= 1;
+        }
+        self.wrap = false;
+    }
+
+    /// Reset the console to its initial state, and clear the screen.
+    fn reset(&mut self) {
+        self.attributes = Attributes::DEFAULT;
+        self.saved = Saved {
+            x: 0,
+            y: 0,
+            attributes: Attributes::DEFAULT,
+        };
+        self.top = 0;
+        self.bottom = self.rows();
+        self.cursor = true;
+        self.goto(0, 0);
+        self.erase(0, self.cells.len());
+    }
+
+    /// Erase the cursor drawn on the screen, if it is.
+    fn hide_cursor(&mut self) {
+        if let Some((x, y)) = self.drawn.take() {
+            self.draw(x, y, false);
+        }
+    }
+
+    /// Draw the cursor on the screen, if it is shown.
+    fn show_cursor(&mut self) {
+        if self.cursor {
+            self.draw(self.x, self.y, true);
+            self.drawn = Some((self.x, self.y));
+        }
+    }
+
+    /// Parse the given byte of the output, and execute what it completes.
+    fn advance(&mut self, byte: u8) {
+        match self.parser.advance(byte) {
+            Some(Action::Print(c)) => self.print(c),
+            Some(Action::Execute(byte)) => self.execute(byte),
+            Some(Action::Escape(byte)) => self.escape(byte),
+            Some(Action::Control(sequence)) => self.control(&sequence),
+            None => (),
+        }
+    }
+
+    /// Print the given character at the position of the cursor, and move the
+    /// cursor forward.
+    fn print(&mut self, c: char) {
+        if self.wrap {
+            self.x = 0;
+            self.line_feed();
+        }
+
+        let index = self.y * self.columns() + self.x;
+        self.cells[index] = Cell {
+            glyph: self.screen.font().index(c),
+            attributes: self.attributes,
+        };
+        self.draw(self.x, self.y, false);
+
+        if self.x + 1 == self.columns() {
+            self.wrap = true;
+        } else {
+            self.x += 1;
+        }
+    }
+
+    /// Execute the given control character.
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            // Backspace
+            0x08 => self.goto(self.x.saturating_sub(1), self.y),
+            // Horizontal tab
+            0x09 => self.goto((self.x / TAB_WIDTH + 1) * TAB_WIDTH, self.y),
+            // Line feed, vertical tab and form feed
+            0x0A..=0x0C => self.line_feed(),
+            // Carriage return
+            0x0D => self.goto(0, self.y),
+            _ => (),
+        }
+    }
+
+    /// Execute the escape sequence with the given final byte.
+    fn escape(&mut self, byte: u8) {
+        match byte {
+            b'c' => self.reset(),
+            b'7' => self.save(),
+            b'8' => self.restore(),
+            b'D' => self.line_feed(),
+            b'E' => {
+                self.x = 0;
+                self.line_feed();
+            }
+            b'M' => self.reverse_index(),
+            _ => (),
+        }
+    }
+
+    /// Save the position of the cursor and the attributes.
+    fn save(&mut self) {
+        self.saved = Saved {
+            x: self.x,
+            y: self.y,
+            attributes: self.attributes,
+        };
+    }
+
+    /// Restore the position of the cursor and the attributes saved.
+    fn restore(&mut self) {
+        self.attributes = self.saved.attributes;
+        self.goto(self.saved.x, self.saved.y);
+    }
+
+    /// Execute the given control sequence. The device status reports are not
+    /// supported, since they would need to answer through the input of the
+    /// terminal while its output is being written.
+    fn control(&mut self, sequence: &Sequence) {
+        let (x, y) = (self.x, self.y);
+        let count = usize::from(sequence.param(0, 1));
+        let mode = sequence.params().first().copied().unwrap_or(0);
+        let columns = self.columns();
+
+        match (sequence.private, sequence.function) {
+            (true, b'h' | b'l') => {
+                if sequence.params().contains(&25) {
+                    self.cursor = sequence.function == b'h';
+                }
+            }
+            (false, b'A') => self.goto(x, y.saturating_sub(count)),
+            (false, b'B') => self.goto(x, y.saturating_add(count)),
+            (false, b'C') => self.goto(x.saturating_add(count), y),
+            (false, b'D') => self.goto(x.saturating_sub(count), y),
+            (false, b'E') => self.goto(0, y.saturating_add(count)),
+            (false, b'F') => self.goto(0, y.saturating_sub(count)),
+            (false, b'G') => self.goto(count - 1, y),
+            (false, b'd') => self.goto(x, count - 1),
+            (false, b'H' | b'f') => {
+                let column = usize::from(sequence.param(1, 1));
+                self.goto(column - 1, count - 1);
+            }
+            (false, b'J') => {
+                let cursor = y * columns + x;
+                match mode {
+                    0 => self.erase(cursor, self.cells.len()),
+                    1 => self.erase(0, cursor + 1),
+                    2 | 3 => self.erase(0, self.cells.len()),
+                    _ => (),
+                }
+            }
+            (false, b'K') => {
+                let line = y * columns;
+                match mode {
+                    0 => self.erase(line + x, line + columns),
+                    1 => self.erase(line, line + x + 1),
+                    2 => self.erase(line, line + columns),
+                    _ => (),
+                }
+            }
+            (false, b'X') => {
+                let start = y * columns + x;
+                self.erase(start, start + count.min(columns - x));
+            }
+            (false, b'L') if (self.top..self.bottom).contains(&y) => {
+                self.scroll_down(y, self.bottom, count);
+                self.goto(0, y);
+            }
+            (false, b'M') if (self.top..self.bottom).contains(&y) => {
+                self.scroll_up(y, self.bottom, count);
+                self.goto(0, y);
+            }
+            (false, b'@') => self.shift_right(count),
+            (false, b'P') => self.shift_left(count),
+            (false, b'm') => self.select_graphic_rendition(sequence.params()),
+            (false, b'r') => {
+                let top = usize::from(sequence.param(0, 1)) - 1;
+                let bottom = usize::from(sequence.param(1, u16::MAX)).min(self.rows());
+                if top + 1 < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.goto(0, 0);
+                }
+            }
+            (false, b's') => self.save(),
+            (false, b'u') => self.restore(),
+            _ => (),
+        }
+    }
+
+    /// Insert the given number of blank cells at the position of the cursor,
+    /// shifting the end of the line to the right.
+    fn shift_right(&mut self, count: usize) {
+        let columns = self.columns();
+        let line = self.y * columns;
+        let count = count.min(columns - self.x);
+        self.cells
+            .copy_within(line + self.x..line + columns - count, line + self.x + count);
+        self.erase(line + self.x, line + self.x + count);
+        for x in self.x + count..columns {
+            self.draw(x, self.y, false);
+        }
+        self.wrap = false;
+    }
+
+    /// Delete the given number of cells at the position of the cursor, shifting
+    /// the end of the line to the left.
+    fn shift_left(&mut self, count: usize) {
+        let columns = self.columns();
+        let line = self.y * columns;
+        let count = count.min(columns - self.x);
+        self.cells
+            .copy_within(line + self.x + count..line + columns, line + self.x);
+        for x in self.x..columns - count {
+            self.draw(x, self.y, false);
+        }
+        self.erase(line + columns - count, line + columns);
+        self.wrap = false;
+    }
+
+    /// Set the attributes of the characters printed with the given parameters
+    /// of the `SGR` control sequence. The colors of the parameters 38 and 48
+    /// are given either by an index in the 256 colors palette (`5;n`), or by
+    /// their components (`2;r;g;b`).
+    #[allow(clippy::cast_possible_truncation)]
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            self.attributes = Attributes::DEFAULT;
+        }
+
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            let attributes = &mut self.attributes;
+            match param {
+                0 => *attributes = Attributes::DEFAULT,
+                1 => attributes.bold = true,
+                4 => attributes.underline = true,
+                7 => attributes.reverse = true,
+                22 => attributes.bold = false,
+                24 => attributes.underline = false,
+                27 => attributes.reverse = false,
+                30..=37 => attributes.foreground = Color::Indexed((param - 30) as u8),
+                38 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        attributes.foreground = color;
+                    }
+                }
+                39 => attributes.foreground = Color::FOREGROUND,
+                40..=47 => attributes.background = Color::Indexed((param - 40) as u8),
+                48 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        attributes.background = color;
+                    }
+                }
+                49 => attributes.background = Color::BACKGROUND,
+                90..=97 => attributes.foreground = Color::Indexed((param - 90 + 8) as u8),
+                100..=107 => attributes.background = Color::Indexed((param - 100 + 8) as u8),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Writes the kernel logs to the terminal, the newlines being preceded by a
+/// carriage return like the `ONLCR` flag of the terminals does.
+struct Log<'a>(&'a mut Terminal);
+
+impl core::fmt::Write for Log<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.0.advance(b'\r');
+            }
+            self.0.advance(byte);
+        }
+        Ok(())
+    }
+}
+
+/// The framebuffer console.
+pub struct Console {
+    /// The state of the console, locked with interrupts disabled.
+    terminal: Spinlock<Terminal>,
+}
+
+impl Console {
+    /// Returns the size of the console, in characters and in pixels.
+    #[allow(clippy::cast_possible_truncation)]
+    fn window(&self) -> WindowSize {
+        x86_64::irq::without(|| {
+            let terminal = self.terminal.lock();
+            let font = terminal.screen.font();
+            WindowSize {
+                rows: terminal.rows() as u16,
+                columns: terminal.columns() as u16,
+                xpixel: (terminal.columns() * font.width()) as u16,
+                ypixel: (terminal.rows() * font.height()) as u16,
+            }
+        })
+    }
+
+    /// Lock the console with interrupts disabled and call the given closure.
+    /// The cursor is hidden while the closure runs, and drawn again after.
+    fn with_terminal(&self, f: impl FnOnce(&mut Terminal)) {
+        x86_64::irq::without(|| {
+            let mut terminal = self.terminal.lock();
+            terminal.hide_cursor();
+            f(&mut terminal);
+            terminal.show_cursor();
+        });
+    }
+
+    /// Write the given kernel log message to the console.
+    pub fn log(&self, args: core::fmt::Arguments) {
+        self.with_terminal(|terminal| {
+            _ = core::fmt::Write::write_fmt(&mut Log(terminal), args);
+        });
+    }
+}
+
+impl Driver for Console {
+    fn write(&self, data: &[u8]) {
+        self.with_terminal(|terminal| {
+            for &byte in data {
+                terminal.advance(byte);
+            }
+        });
+    }
+}
+
+/// Parse the color of the parameters 38 and 48 of the `SGR` control sequence
+/// from the given parameters following them.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match params.next()? {
+        5 => u8::try_from(params.next()?).ok().map(Color::Indexed),
+        2 => {
+            let mut component = || params.next().map(|value| u32::from(value.min(0xFF)));
+            let (red, green, blue) = (component()?, component()?, component()?);
+            Some(Color::Rgb((red << 16) | (green << 8) | blue))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the framebuffer console, or `None` if there is no framebuffer or if
+/// it is not set up yet.
+#[must_use]
+pub fn console() -> Option<&'static Arc<Console>> {
+    CONSOLE.get()
+}
+
+/// Returns the terminal of the framebuffer console, or `None` if there is no
+/// framebuffer console or if it is not set up yet.
+#[must_use]
+pub fn tty() -> Option<&'static Arc<Tty>> {
+    TTY.get()
+}
+
+/// Called when the kernel panics. This function force the unlock of the
+/// framebuffer console, so that the panic message can be drawn.
+///
+/// # Safety
+/// This function is unsafe because it force the unlock of the console, which
+/// could cause a undefined behavior if it is used by several threads after this
+/// function call.
+#[cold]
+pub unsafe fn on_panic() {
+    if let Some(console) = CONSOLE.get() {
+        console.terminal.force_unlock();
+    }
+}
+
+/// Create the framebuffer console, so that the kernel logs are drawn on the
+/// screen. This must be called once the memory manager is set up. Nothing is
+/// done if there is no framebuffer, or if its pixels are not supported.
+#[init]
+pub fn early_setup() {
+    let Some(fb) = fb::framebuffer() else {
+        return;
+    };
+    let Some(screen) = Screen::new(fb, &BUILTIN) else {
+        log::warn!(
+            "vt: unsupported {}x{}x{} framebuffer",
+            fb.width,
+            fb.height,
+            fb.bpp
+        );
+        return;
+    };
+
+    let (columns, rows) = (screen.columns(), screen.rows());
+    CONSOLE.call_once(|| {
+        Arc::new(Console {
+            terminal: Spinlock::new(Terminal::new(screen)),
+        })
+    });
+    log::info!("vt: {}x{} framebuffer console", columns, rows);
+}
+
+/// Create the terminal of the framebuffer console and `/dev/tty0`.
+#[init]
+pub fn setup() {
+    let Some(console) = CONSOLE.get() else {
+        return;
+    };
+
+    let termios = Termios::new(termios::B38400 | termios::CS8 | termios::CREAD | termios::CLOCAL);
+    let tty = TTY.call_once(|| {
+        Arc::new(Tty::new(
+            "tty0",
+            Arc::clone(console) as Arc<dyn Driver>,
+            termios,
+        ))
+    });
+    tty.resize(console.window());
+
+    if let Err(error) = character::register(TTY0, Arc::clone(tty) as Arc<dyn CharDevice>) {
+        log::warn!("tty: failed to register tty0: {:?}", error);
+    }
+}
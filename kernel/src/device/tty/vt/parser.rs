@@ -0,0 +1,238 @@
+//! The parser of the output of a terminal, which splits the bytes written into
+//! the characters to print, encoded in UTF-8, the control characters, and the
+//! escape sequences of the VT100 and its successors (ECMA-48): the escape
+//! sequences `ESC <final>`, the control sequences `ESC [ <params> <final>`
+//! (CSI), and the operating system commands `ESC ] ... BEL`, which are ignored.
+//! The parser never allocates, since the output can be written from interrupt
+//! handlers when the input is echoed.
+
+/// The number of parameters of a control sequence kept at most. The parameters
+/// after them are ignored.
+pub const MAX_PARAMS: usize = 16;
+
+/// The control characters that abort an escape sequence.
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1A;
+
+/// The control characters that start an escape sequence, and that end an
+/// operating system command.
+const ESC: u8 = 0x1B;
+const BEL: u8 = 0x07;
+
+/// The delete character, which is ignored.
+const DEL: u8 = 0x7F;
+
+/// What the terminal must do with the bytes parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Print the given character.
+    Print(char),
+
+    /// Execute the given control character, like a line feed.
+    Execute(u8),
+
+    /// Execute the escape sequence with the given final byte.
+    Escape(u8),
+
+    /// Execute the given control sequence.
+    Control(Sequence),
+}
+
+/// A control sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sequence {
+    /// The parameters of the sequence. An omitted parameter is zero.
+    params: [u16; MAX_PARAMS],
+
+    /// The number of parameters of the sequence.
+    count: usize,
+
+    /// Set if the parameters start with `?`, for the private sequences of the
+    /// DEC terminals, like the one showing the cursor.
+    pub private: bool,
+
+    /// The final byte of the sequence, which identifies its function.
+    pub function: u8,
+}
+
+impl Sequence {
+    /// Returns the parameters of the sequence.
+    #[must_use]
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count.min(MAX_PARAMS)]
+    }
+
+    /// Returns the parameter with the given index, or the given default value
+    /// if it is omitted or zero.
+    #[must_use]
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+}
+
+/// The state of the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum State {
+    /// Printing characters. The fields are the code point of the character
+    /// being decoded, and the number of its bytes still expected.
+    Ground(u32, usize),
+
+    /// After `ESC`.
+    Escape,
+
+    /// After the intermediate bytes of an escape sequence, like the `(` of the
+    /// sequences choosing a character set, which are ignored.
+    EscapeIntermediate,
+
+    /// In a control sequence.
+    Control,
+
+    /// In a control sequence with unexpected bytes, ignored until its end.
+    ControlIgnore,
+
+    /// In an operating system command, ignored until its end.
+    Command,
+}
+
+/// The parser of the output of a terminal.
+#[derive(Debug, Clone)]
+pub struct Parser {
+    state: State,
+
+    /// The control sequence being parsed.
+    sequence: Sequence,
+}
+
+impl Parser {
+    /// Create a new parser.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground(0, 0),
+            sequence: Sequence {
+                params: [0; MAX_PARAMS],
+                count: 0,
+                private: false,
+                function: 0,
+            },
+        }
+    }
+
+    /// Parse the given byte, and returns what the terminal must do, if the
+    /// byte completes something.
+    pub fn advance(&mut self, byte: u8) -> Option<Action> {
+        match (self.state, byte) {
+            (State::Command, BEL) | (_, CAN | SUB) => {
+                self.state = State::Ground(0, 0);
+                None
+            }
+            (_, ESC) => {
+                self.state = State::Escape;
+                None
+            }
+            (State::Command, _) => None,
+            (_, 0x00..=0x1F) => Some(Action::Execute(byte)),
+            (State::Ground(code, expected), _) => self.decode(code, expected, byte),
+            (State::Escape, b'[') => {
+                self.state = State::Control;
+                self.sequence.params = [0; MAX_PARAMS];
+                self.sequence.count = 0;
+                self.sequence.private = false;
+                None
+            }
+            (State::Escape, b']') => {
+                self.state = State::Command;
+                None
+            }
+            (State::Escape | State::EscapeIntermediate, 0x20..=0x2F) => {
+                self.state = State::EscapeIntermediate;
+                None
+            }
+            (State::Escape, 0x30..=0x7E) => {
+                self.state = State::Ground(0, 0);
+                Some(Action::Escape(byte))
+            }
+            (State::Control | State::ControlIgnore, 0x40..=0x7E) => {
+                let ignored = self.state == State::ControlIgnore;
+                self.state = State::Ground(0, 0);
+                self.sequence.function = byte;
+                (!ignored).then_some(Action::Control(self.sequence))
+            }
+            (State::Control, b'0'..=b'9') => {
+                if self.sequence.count == 0 {
+                    self.sequence.count = 1;
+                }
+                if let Some(param) = self.sequence.params.get_mut(self.sequence.count - 1) {
+                    *param = param
+                        .saturating_mul(10)
+                        .saturating_add(u16::from(byte - b'0'));
+                }
+                None
+            }
+            (State::Control, b';') => {
+                self.sequence.count = (self.sequence.count.max(1) + 1).min(MAX_PARAMS + 1);
+                None
+            }
+            (State::Control, b'?') if self.sequence.count == 0 && !self.sequence.private => {
+                self.sequence.private = true;
+                None
+            }
+            (State::Control | State::ControlIgnore, _) => {
+                self.state = State::ControlIgnore;
+                None
+            }
+            // The end of the ignored escape sequences with intermediate bytes,
+            // and the unexpected bytes.
+            (_, _) => {
+                self.state = State::Ground(0, 0);
+                None
+            }
+        }
+    }
+
+    /// Decode the given byte of a character encoded in UTF-8, given the code
+    /// point decoded so far and the number of bytes still expected. Invalid
+    /// bytes are printed as the replacement character.
+    fn decode(&mut self, code: u32, expected: usize, byte: u8) -> Option<Action> {
+        self.state = State::Ground(0, 0);
+        match (expected, byte) {
+            (0, DEL) => None,
+            (0, 0x00..=0x7F) => Some(Action::Print(char::from(byte))),
+            (0, 0xC2..=0xDF) => {
+                self.state = State::Ground(u32::from(byte & 0x1F), 1);
+                None
+            }
+            (0, 0xE0..=0xEF) => {
+                self.state = State::Ground(u32::from(byte & 0x0F), 2);
+                None
+            }
+            (0, 0xF0..=0xF4) => {
+                self.state = State::Ground(u32::from(byte & 0x07), 3);
+                None
+            }
+            (0, _) => Some(Action::Print(char::REPLACEMENT_CHARACTER)),
+            (_, 0x80..=0xBF) => {
+                let code = (code << 6) | u32::from(byte & 0x3F);
+                if expected > 1 {
+                    self.state = State::Ground(code, expected - 1);
+                    return None;
+                }
+                Some(Action::Print(
+                    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+                ))
+            }
+            // The character is truncated and dropped, and the byte is parsed
+            // again since it may start another character.
+            (_, _) => self.decode(0, 0, byte),
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
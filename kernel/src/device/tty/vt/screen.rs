@@ -0,0 +1,151 @@
+//! The drawing of a text console on the framebuffer. The framebuffer is split
+//! into cells of the size of the glyphs of the font, and each cell is drawn with
+//! the glyph of its character over its background. The pixels left on the right
+//! and at the bottom of the framebuffer are never drawn.
+use super::font::Font;
+use crate::device::fb::{Channel, Framebuffer};
+
+/// A text screen drawn on the framebuffer.
+pub struct Screen {
+    /// The framebuffer drawn.
+    fb: &'static Framebuffer,
+
+    /// The font used to draw the characters.
+    font: &'static Font<'static>,
+
+    /// The number of bytes of a pixel.
+    bytes: usize,
+
+    /// The number of cells of a row, and the number of rows.
+    columns: usize,
+    rows: usize,
+}
+
+impl Screen {
+    /// Create a screen drawn on the given framebuffer with the given font, or
+    /// returns `None` if the pixels of the framebuffer are not supported or if
+    /// the framebuffer is smaller than a single cell.
+    #[must_use]
+    pub fn new(fb: &'static Framebuffer, font: &'static Font<'static>) -> Option<Self> {
+        let bytes = match fb.bpp {
+            16 | 24 | 32 => fb.bpp / 8,
+            _ => return None,
+        };
+        let columns = fb.width / font.width();
+        let rows = fb.height / font.height();
+        if columns == 0 || rows == 0 {
+            return None;
+        }
+        Some(Self {
+            fb,
+            font,
+            bytes,
+            columns,
+            rows,
+        })
+    }
+
+    /// Returns the number of cells of a row.
+    #[must_use]
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Returns the number of rows of cells.
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the font used to draw the characters.
+    #[must_use]
+    pub fn font(&self) -> &'static Font<'static> {
+        self.font
+    }
+
+    /// Returns the value of the pixel with the given color, as `0xRRGGBB`, in
+    /// the format of the framebuffer.
+    #[must_use]
+    pub fn pixel(&self, rgb: u32) -> u32 {
+        let component = |value: u32, channel: Channel| {
+            let size = u32::from(channel.size);
+            let value = if size <= 8 {
+                value >> (8 - size)
+            } else {
+                value << (size - 8)
+            };
+            value << channel.shift
+        };
+        component((rgb >> 16) & 0xFF, self.fb.red)
+            | component((rgb >> 8) & 0xFF, self.fb.green)
+            | component(rgb & 0xFF, self.fb.blue)
+    }
+
+    /// Draw the glyph with the given index in the cell at the given position,
+    /// with the given foreground and background pixels. The last row of pixels
+    /// of the glyph above the bottom of the cell is drawn with the foreground
+    /// if the cell is underlined.
+    pub fn draw(
+        &self,
+        column: usize,
+        row: usize,
+        glyph: usize,
+        foreground: u32,
+        background: u32,
+        underline: bool,
+    ) {
+        let (width, height) = (self.font.width(), self.font.height());
+        let pitch = self.font.pitch();
+        let bitmap = self.font.glyph(glyph);
+
+        for y in 0..height {
+            let bits = &bitmap[y * pitch..(y + 1) * pitch];
+            let line = (row * height + y) * self.fb.pitch + column * width * self.bytes;
+            for x in 0..width {
+                let set = bits[x / 8] & (0x80 >> (x % 8)) != 0;
+                let pixel = if set || (underline && y == height - 2) {
+                    foreground
+                } else {
+                    background
+                };
+                self.put(line + x * self.bytes, pixel);
+            }
+        }
+    }
+
+    /// Move the given number of rows of cells starting at the row `from` to the
+    /// row `to`. The rows may overlap.
+    pub fn move_rows(&self, from: usize, to: usize, count: usize) {
+        let size = self.font.height() * self.fb.pitch;
+        debug_assert!(from.max(to) + count <= self.rows);
+
+        // SAFETY: The rows are inside the framebuffer, and `copy` handles the
+        // overlapping rows.
+        unsafe {
+            let base = self.fb.address.as_mut_ptr::<u8>();
+            core::ptr::copy(base.add(from * size), base.add(to * size), count * size);
+        }
+    }
+
+    /// Write the given pixel at the given offset in the framebuffer. The pixels
+    /// of 2 and 4 bytes are aligned since the framebuffer and its pitch are.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_ptr_alignment)]
+    fn put(&self, offset: usize, pixel: u32) {
+        debug_assert!(offset + self.bytes <= self.fb.size());
+
+        // SAFETY: The offset is inside the framebuffer, which is mapped in the
+        // kernel address space and is only drawn by the console.
+        unsafe {
+            let ptr = self.fb.address.as_mut_ptr::<u8>().add(offset);
+            match self.bytes {
+                4 => ptr.cast::<u32>().write_volatile(pixel),
+                2 => ptr.cast::<u16>().write_volatile(pixel as u16),
+                _ => {
+                    for (index, byte) in pixel.to_le_bytes()[..self.bytes].iter().enumerate() {
+                        ptr.add(index).write_volatile(*byte);
+                    }
+                }
+            }
+        }
+    }
+}
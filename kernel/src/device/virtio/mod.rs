@@ -0,0 +1,307 @@
+//! The virtio PCI transport, as defined by the version 1.0 of the virtio
+//! specification (the "modern" interface). The registers of a virtio device are
+//! found with vendor-specific PCI capabilities, each one giving the BAR and the
+//! offset of a structure: the common configuration (features, status and the
+//! configuration of the virtqueues), the notification area where the driver
+//! tells the device that a virtqueue has new buffers, the ISR status read to
+//! acknowledge a legacy interrupt, and the configuration specific to the type
+//! of the device.
+//!
+//! The device is initialized with the sequence required by the specification:
+//! it is reset, the driver acknowledges it and negotiates the features, then
+//! sets up its virtqueues before telling the device that it is ready. Devices
+//! that only implement the legacy interface are not supported.
+use crate::device::pci::{self, ProbeError};
+use addr::virt::Virtual;
+use queue::Queue;
+
+pub mod queue;
+
+/// The vendor identifier of virtio devices.
+pub const VENDOR: u16 = 0x1AF4;
+
+/// The feature telling that the device implements the version 1.0 of the
+/// specification. It is required by the kernel.
+pub const F_VERSION_1: u64 = 1 << 32;
+
+/// The bits of the device status register.
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FEATURES_OK: u8 = 8;
+const STATUS_FAILED: u8 = 128;
+
+/// The types of the structures described by the vendor-specific capabilities.
+const CAP_COMMON: u8 = 1;
+const CAP_NOTIFY: u8 = 2;
+const CAP_ISR: u8 = 3;
+const CAP_DEVICE: u8 = 4;
+
+/// The offsets of the registers of the common configuration structure.
+const COMMON_DEVICE_FEATURE_SELECT: usize = 0x00;
+const COMMON_DEVICE_FEATURE: usize = 0x04;
+const COMMON_DRIVER_FEATURE_SELECT: usize = 0x08;
+const COMMON_DRIVER_FEATURE: usize = 0x0C;
+const COMMON_NUM_QUEUES: usize = 0x12;
+const COMMON_DEVICE_STATUS: usize = 0x14;
+const COMMON_CONFIG_GENERATION: usize = 0x15;
+const COMMON_QUEUE_SELECT: usize = 0x16;
+const COMMON_QUEUE_SIZE: usize = 0x18;
+const COMMON_QUEUE_ENABLE: usize = 0x1C;
+const COMMON_QUEUE_NOTIFY_OFF: usize = 0x1E;
+const COMMON_QUEUE_DESC: usize = 0x20;
+const COMMON_QUEUE_DRIVER: usize = 0x28;
+const COMMON_QUEUE_DEVICE: usize = 0x30;
+
+/// The bit of the ISR status telling that a virtqueue was used by the device.
+const ISR_QUEUE: u8 = 1 << 0;
+
+/// The number of times the device status is read while waiting for the reset of
+/// the device to complete before giving up.
+const RESET_TIMEOUT: usize = 1_000_000;
+
+/// The registers of a virtio device accessed through the PCI transport.
+pub struct Transport {
+    /// The common configuration structure.
+    common: Virtual,
+
+    /// The start of the notification area, and the multiplier applied to the
+    /// notification offset of a virtqueue to find its notification register.
+    notify: Virtual,
+    notify_multiplier: u32,
+
+    /// The ISR status register.
+    isr: Virtual,
+
+    /// The configuration structure specific to the type of the device, if any.
+    device: Option<Virtual>,
+}
+
+impl Transport {
+    /// Find the structures of the given virtio device, reset it and negotiate
+    /// its features: the accepted features are those supported by both the
+    /// device and the driver. Returns the transport and the accepted features.
+    /// Bus mastering is enabled, since the device accesses the virtqueues in
+    /// memory, as well as the legacy interrupts of the device.
+    ///
+    /// # Errors
+    /// - `ProbeError::NotSupported`: The device does not have the capabilities of
+    ///   the modern interface, or does not implement the version 1.0.
+    /// - `ProbeError::DeviceError`: The device did not complete its reset, or did
+    ///   not accept the negotiated features.
+    /// - `ProbeError::OutOfMemory`: A BAR of the device could not be mapped.
+    pub fn new(device: &pci::Device, features: u64) -> Result<(Self, u64), ProbeError> {
+        let mut common = None;
+        let mut notify = None;
+        let mut isr = None;
+        let mut specific = None;
+        let mut notify_multiplier = 0;
+
+        for capability in device.capabilities(pci::CAPABILITY_VENDOR) {
+            let kind = device.read_u8(capability.offset + 3);
+            let bar = usize::from(device.read_u8(capability.offset + 4));
+            let offset = device.read_u32(capability.offset + 8) as usize;
+            let Some(base) = device.map_bar(bar)? else {
+                continue;
+            };
+
+            let address = Some(base + offset);
+            match kind {
+                CAP_COMMON if common.is_none() => common = address,
+                CAP_NOTIFY if notify.is_none() => {
+                    notify = address;
+                    notify_multiplier = device.read_u32(capability.offset + 16);
+                }
+                CAP_ISR if isr.is_none() => isr = address,
+                CAP_DEVICE if specific.is_none() => specific = address,
+                _ => {}
+            }
+        }
+
+        let (Some(common), Some(notify), Some(isr)) = (common, notify, isr) else {
+            return Err(ProbeError::NotSupported);
+        };
+
+        device.enable(pci::COMMAND_MEMORY | pci::COMMAND_BUS_MASTER);
+        device.disable(pci::COMMAND_INTX_DISABLE);
+
+        let transport = Self {
+            common,
+            notify,
+            notify_multiplier,
+            isr,
+            device: specific,
+        };
+        let features = transport
+            .negotiate(features | F_VERSION_1)
+            .map_err(|error| {
+                transport.fail();
+                error
+            })?;
+        Ok((transport, features))
+    }
+
+    /// Reset the device, acknowledge it and negotiate the given features.
+    fn negotiate(&self, supported: u64) -> Result<u64, ProbeError> {
+        self.write_u8(COMMON_DEVICE_STATUS, 0);
+        if !(0..RESET_TIMEOUT).any(|_| self.read_u8(COMMON_DEVICE_STATUS) == 0) {
+            return Err(ProbeError::DeviceError);
+        }
+        self.add_status(STATUS_ACKNOWLEDGE);
+        self.add_status(STATUS_DRIVER);
+
+        let offered = (0..2).fold(0, |features, select| {
+            self.write_u32(COMMON_DEVICE_FEATURE_SELECT, select);
+            features | u64::from(self.read_u32(COMMON_DEVICE_FEATURE)) << (32 * select)
+        });
+        let accepted = offered & supported;
+        if accepted & F_VERSION_1 == 0 {
+            return Err(ProbeError::NotSupported);
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        for select in 0..2 {
+            self.write_u32(COMMON_DRIVER_FEATURE_SELECT, select);
+            self.write_u32(COMMON_DRIVER_FEATURE, (accepted >> (32 * select)) as u32);
+        }
+
+        // The device clears the bit if it does not accept the features.
+        self.add_status(STATUS_FEATURES_OK);
+        if self.read_u8(COMMON_DEVICE_STATUS) & STATUS_FEATURES_OK == 0 {
+            return Err(ProbeError::DeviceError);
+        }
+        Ok(accepted)
+    }
+
+    /// Returns the number of virtqueues of the device.
+    #[must_use]
+    pub fn queues(&self) -> u16 {
+        self.read_u16(COMMON_NUM_QUEUES)
+    }
+
+    /// Set up the virtqueue with the given index, with at most the given number
+    /// of descriptors, and enable it.
+    ///
+    /// # Errors
+    /// - `ProbeError::NotSupported`: The virtqueue does not exist.
+    /// - `ProbeError::OutOfMemory`: The virtqueue could not be allocated.
+    pub fn setup_queue(&self, index: u16, size: u16) -> Result<Queue, ProbeError> {
+        if index >= self.queues() {
+            return Err(ProbeError::NotSupported);
+        }
+
+        self.write_u16(COMMON_QUEUE_SELECT, index);
+        let max = self.read_u16(COMMON_QUEUE_SIZE);
+        if max == 0 {
+            return Err(ProbeError::NotSupported);
+        }
+
+        // The size of a split virtqueue must be a power of two.
+        let size = 1u16 << (15 - size.min(max).leading_zeros());
+        let offset = self.read_u16(COMMON_QUEUE_NOTIFY_OFF);
+        let notify = self.notify + offset as usize * self.notify_multiplier as usize;
+        let queue = Queue::new(index, size, notify).ok_or(ProbeError::OutOfMemory)?;
+
+        let (desc, driver, device) = queue.addresses();
+        self.write_u16(COMMON_QUEUE_SIZE, size);
+        self.write_u64(COMMON_QUEUE_DESC, desc);
+        self.write_u64(COMMON_QUEUE_DRIVER, driver);
+        self.write_u64(COMMON_QUEUE_DEVICE, device);
+        self.write_u16(COMMON_QUEUE_ENABLE, 1);
+        Ok(queue)
+    }
+
+    /// Tell the device that the driver is ready to use it. The device must not
+    /// be used before this is called, but its virtqueues must be set up before.
+    pub fn ready(&self) {
+        self.add_status(STATUS_DRIVER_OK);
+    }
+
+    /// Tell the device that the driver gave up on it.
+    pub fn fail(&self) {
+        self.add_status(STATUS_FAILED);
+    }
+
+    /// Read the ISR status, which acknowledges the legacy interrupt of the
+    /// device. Returns `true` if the interrupt was raised because a virtqueue
+    /// was used, which is how the driver knows if an interrupt shared with other
+    /// devices was raised by this device.
+    #[must_use]
+    pub fn interrupted(&self) -> bool {
+        unsafe { self.isr.as_ptr::<u8>().read_volatile() & ISR_QUEUE != 0 }
+    }
+
+    /// Read the 32-bit field at the given offset of the configuration specific
+    /// to the type of the device. Returns 0 if the device does not have such a
+    /// configuration.
+    #[must_use]
+    pub fn config_u32(&self, offset: usize) -> u32 {
+        self.device.map_or(0, |device| unsafe {
+            (device + offset).as_ptr::<u32>().read_volatile()
+        })
+    }
+
+    /// Read the 64-bit field at the given offset of the configuration specific
+    /// to the type of the device. The field is read with two 32-bit accesses,
+    /// and read again if the device changed its configuration in between.
+    #[must_use]
+    pub fn config_u64(&self, offset: usize) -> u64 {
+        loop {
+            let generation = self.read_u8(COMMON_CONFIG_GENERATION);
+            let low = self.config_u32(offset);
+            let high = self.config_u32(offset + 4);
+            if self.read_u8(COMMON_CONFIG_GENERATION) == generation {
+                return u64::from(high) << 32 | u64::from(low);
+            }
+        }
+    }
+
+    fn add_status(&self, status: u8) {
+        let current = self.read_u8(COMMON_DEVICE_STATUS);
+        self.write_u8(COMMON_DEVICE_STATUS, current | status);
+    }
+
+    fn read_u8(&self, offset: usize) -> u8 {
+        unsafe { (self.common + offset).as_ptr::<u8>().read_volatile() }
+    }
+
+    fn read_u16(&self, offset: usize) -> u16 {
+        unsafe { (self.common + offset).as_ptr::<u16>().read_volatile() }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        unsafe { (self.common + offset).as_ptr::<u32>().read_volatile() }
+    }
+
+    fn write_u8(&self, offset: usize, value: u8) {
+        unsafe {
+            (self.common + offset)
+                .as_mut_ptr::<u8>()
+                .write_volatile(value);
+        }
+    }
+
+    fn write_u16(&self, offset: usize, value: u16) {
+        unsafe {
+            (self.common + offset)
+                .as_mut_ptr::<u16>()
+                .write_volatile(value);
+        }
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) {
+        unsafe {
+            (self.common + offset)
+                .as_mut_ptr::<u32>()
+                .write_volatile(value);
+        }
+    }
+
+    /// Write a 64-bit register as two 32-bit accesses, the low half first, as
+    /// the specification allows drivers to do.
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_u64(&self, offset: usize, value: u64) {
+        self.write_u32(offset, value as u32);
+        self.write_u32(offset + 4, (value >> 32) as u32);
+    }
+}
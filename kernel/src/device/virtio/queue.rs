@@ -0,0 +1,231 @@
+//! Split virtqueues. A virtqueue is made of three areas shared with the device:
+//! the descriptor table, where each descriptor gives the physical address and
+//! the length of a buffer, the available ring, where the driver puts the first
+//! descriptor of the chains of buffers given to the device, and the used ring,
+//! where the device puts the chains it has processed.
+//!
+//! The three areas are allocated in physically contiguous frames from the frame
+//! allocator, and accessed through the HHDM. A virtqueue does not synchronize
+//! itself: the driver must serialize the accesses to it, including the ones made
+//! from its interrupt handler.
+use crate::mm::{
+    frame::{allocator::Allocator, owned::OwnedMemory, AllocationFlags},
+    FRAME_ALLOCATOR,
+};
+use addr::{frame::Frame, phys::Physical, virt::Virtual};
+use core::sync::atomic::{fence, Ordering};
+
+/// The flags of a descriptor.
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+/// The size of a descriptor, and of an entry of the used ring.
+const DESC_SIZE: usize = 16;
+const USED_ELEM_SIZE: usize = 8;
+
+/// The size of the fields that surround the rings of the available and the used
+/// areas: the flags and the index before the ring, and the event index after.
+const RING_OVERHEAD: usize = 6;
+
+/// A buffer given to the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Buffer {
+    /// The physical address of the buffer.
+    pub address: Physical,
+
+    /// The length of the buffer in bytes.
+    pub len: u32,
+
+    /// Whether the buffer is written by the device, or only read.
+    pub writable: bool,
+}
+
+/// A split virtqueue.
+pub struct Queue {
+    /// The index of the virtqueue in the device.
+    index: u16,
+
+    /// The number of descriptors of the virtqueue.
+    size: u16,
+
+    /// The notification register of the virtqueue.
+    notify: Virtual,
+
+    /// The memory containing the three areas of the virtqueue.
+    memory: OwnedMemory,
+
+    /// The offsets of the available and used areas in the memory.
+    avail: usize,
+    used: usize,
+
+    /// The descriptors that are not used by a chain given to the device.
+    free: Vec<u16>,
+
+    /// The index of the next entry of the available ring written by the driver,
+    /// and of the next entry of the used ring to be read by the driver.
+    next_avail: u16,
+    next_used: u16,
+}
+
+impl Queue {
+    /// Allocate a virtqueue with the given index and the given number of
+    /// descriptors, which must be a power of two. Returns `None` if the memory
+    /// of the virtqueue could not be allocated.
+    #[must_use]
+    pub fn new(index: u16, size: u16, notify: Virtual) -> Option<Self> {
+        let count = usize::from(size);
+        let avail = DESC_SIZE * count;
+        let used = (avail + RING_OVERHEAD + 2 * count + 3) & !3;
+        let len = used + RING_OVERHEAD + USED_ELEM_SIZE * count;
+
+        let frames = (len + Frame::SIZE - 1) / Frame::SIZE;
+        let memory = unsafe {
+            FRAME_ALLOCATOR
+                .lock()
+                .allocate_range(frames, AllocationFlags::KERNEL | AllocationFlags::ZEROED)?
+        };
+
+        Some(Self {
+            index,
+            size,
+            notify,
+            memory,
+            avail,
+            used,
+            free: (0..size).rev().collect(),
+            next_avail: 0,
+            next_used: 0,
+        })
+    }
+
+    /// Returns the physical addresses of the descriptor table, of the available
+    /// area and of the used area, given to the device.
+    #[must_use]
+    pub fn addresses(&self) -> (u64, u64, u64) {
+        let base = self.memory.start.addr().as_u64();
+        (base, base + self.avail as u64, base + self.used as u64)
+    }
+
+    /// The number of descriptors of the virtqueue.
+    #[must_use]
+    pub const fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// The number of descriptors that are not used by a chain given to the device.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Returns the index of the first descriptor of the next chain pushed, or
+    /// `None` if all the descriptors are used.
+    #[must_use]
+    pub fn next_head(&self) -> Option<u16> {
+        self.free.last().copied()
+    }
+
+    /// Give a chain of the given buffers to the device, and returns the index of
+    /// its first descriptor, which identifies the chain when the device has used
+    /// it. The buffers read by the device must come before the ones it writes.
+    /// Returns `None` if there are not enough free descriptors. The device is not
+    /// notified: [`Queue::notify`] must be called after adding one or several
+    /// chains.
+    pub fn push(&mut self, buffers: &[Buffer]) -> Option<u16> {
+        if buffers.is_empty() || buffers.len() > self.free.len() {
+            return None;
+        }
+
+        let descriptors: Vec<u16> = (0..buffers.len()).filter_map(|_| self.free.pop()).collect();
+        for (i, (buffer, &descriptor)) in buffers.iter().zip(&descriptors).enumerate() {
+            let next = descriptors.get(i + 1).copied();
+            let mut flags = 0;
+            if buffer.writable {
+                flags |= DESC_F_WRITE;
+            }
+            if next.is_some() {
+                flags |= DESC_F_NEXT;
+            }
+
+            let offset = DESC_SIZE * usize::from(descriptor);
+            self.write(offset, buffer.address.as_u64());
+            self.write(offset + 8, buffer.len);
+            self.write(offset + 12, flags);
+            self.write(offset + 14, next.unwrap_or(0));
+        }
+
+        // The descriptors must be visible to the device before the entry of the
+        // available ring, and the entry before the new index.
+        let head = descriptors[0];
+        let slot = usize::from(self.next_avail % self.size);
+        fence(Ordering::SeqCst);
+        self.write(self.avail + 4 + 2 * slot, head);
+        fence(Ordering::SeqCst);
+        self.next_avail = self.next_avail.wrapping_add(1);
+        self.write(self.avail + 2, self.next_avail);
+        Some(head)
+    }
+
+    /// Tell the device that new chains were added to the virtqueue.
+    pub fn notify(&self) {
+        fence(Ordering::SeqCst);
+        unsafe {
+            self.notify.as_mut_ptr::<u16>().write_volatile(self.index);
+        }
+    }
+
+    /// Returns the next chain used by the device, if any, as the index of its
+    /// first descriptor and the number of bytes written by the device. The
+    /// descriptors of the chain are freed. Entries whose descriptor does not
+    /// exist are skipped.
+    pub fn pop(&mut self) -> Option<(u16, u32)> {
+        let (head, len) = loop {
+            let index: u16 = self.read(self.used + 2);
+            if index == self.next_used {
+                return None;
+            }
+
+            // The entry must not be read before the index that covers it.
+            fence(Ordering::SeqCst);
+            let slot = usize::from(self.next_used % self.size);
+            let entry = self.used + 4 + USED_ELEM_SIZE * slot;
+            let id: u32 = self.read(entry);
+            let len: u32 = self.read(entry + 4);
+            self.next_used = self.next_used.wrapping_add(1);
+
+            match u16::try_from(id) {
+                Ok(head) if head < self.size => break (head, len),
+                _ => log::warn!(
+                    "virtio: queue {} used the invalid descriptor {}",
+                    self.index,
+                    id
+                ),
+            }
+        };
+
+        let mut descriptor = head;
+        loop {
+            self.free.push(descriptor);
+            let offset = DESC_SIZE * usize::from(descriptor);
+            let flags: u16 = self.read(offset + 12);
+            if flags & DESC_F_NEXT == 0 {
+                break;
+            }
+            descriptor = self.read(offset + 14);
+        }
+        Some((head, len))
+    }
+
+    /// Returns a pointer to the given offset of the memory of the virtqueue.
+    fn pointer<T>(&self, offset: usize) -> *mut T {
+        (Virtual::from(self.memory.start.addr()) + offset).as_mut_ptr()
+    }
+
+    fn read<T: Copy>(&self, offset: usize) -> T {
+        unsafe { self.pointer::<T>(offset).read_volatile() }
+    }
+
+    fn write<T: Copy>(&self, offset: usize, value: T) {
+        unsafe { self.pointer::<T>(offset).write_volatile(value) }
+    }
+}
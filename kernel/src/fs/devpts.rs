@@ -0,0 +1,332 @@
+//! The `devpts` filesystem, which contains the nodes of the slave sides of the
+//! pseudo-terminals in use (see [`crate::device::tty::pty`]), named after their
+//! number. It has no content of its own: its root directory lists the pairs in
+//! use, and nodes cannot be created nor removed by the userland. It is mounted
+//! on `/dev/pts` during the boot, and can be mounted elsewhere with the `devpts`
+//! filesystem type and no source device.
+//!
+//! The root directory has the identifier 1, and the node of the pair `N` has the
+//! identifier `N + 2`.
+use crate::{
+    device::{self, tty::pty, Device},
+    time::unix::UnixTime,
+    vfs::{
+        self,
+        dentry::Dentry,
+        inode::{
+            CreateError, Identifier, Inode, InodeCreateInfo, InodeMetadata, Kind, LinkError,
+            LookupError, MkdirError, RenameError, RmdirError, UnlinkError,
+        },
+        mount::{ReadInodeError, Super, SuperCreationInfo, WriteInodeError},
+    },
+};
+use alloc::sync::Weak;
+
+/// Operations that can be performed on the filesystem.
+pub static FS_OPS: vfs::fs::Operation = vfs::fs::Operation { read_super };
+
+/// Operations that can be performed on the superblock.
+pub static SUPER_OPS: vfs::mount::Operation = vfs::mount::Operation {
+    write_super,
+    write_inode,
+    read_inode,
+};
+
+/// Operations that can be performed on the root directory.
+pub static INODE_OPS: vfs::inode::DirectoryOperation = vfs::inode::DirectoryOperation {
+    mknod,
+    create,
+    lookup,
+    unlink,
+    mkdir,
+    rmdir,
+    link,
+    rename,
+    symlink,
+};
+
+/// Operations that can be performed on the opened root directory.
+pub static FILE_OPS: vfs::file::DirectoryOperation = vfs::file::DirectoryOperation { readdir };
+
+/// Operations that can be performed on a node.
+pub static INODE_FILE_OPS: vfs::inode::FileOperation = vfs::inode::FileOperation { truncate };
+
+/// The identifier of the root directory.
+const ROOT: Identifier = Identifier(1);
+
+/// A mounted `devpts` filesystem.
+pub struct Devpts {
+    /// The VFS superblock of the filesystem, needed to create VFS inodes.
+    superblock: Once<Weak<Super>>,
+}
+
+/// Register the `devpts` filesystem into the VFS.
+pub fn register() {
+    vfs::fs::register(vfs::fs::Filesystem::new("devpts", &FS_OPS, Box::new(())));
+}
+
+/// Returns the `devpts` filesystem of the given VFS superblock.
+///
+/// # Panics
+/// Panics if the superblock is not a `devpts` superblock.
+#[must_use]
+pub fn devpts(superblock: &Super) -> &Arc<Devpts> {
+    superblock
+        .data()
+        .downcast_ref::<Arc<Devpts>>()
+        .expect("Superblock is not a devpts superblock")
+}
+
+/// Called when the pair with the given number is created, to forget the
+/// lookups of its node that failed in the mounted `devpts` filesystems.
+pub fn created(number: u32) {
+    for_each_root(number, |root, name| root.forget_negative(name));
+}
+
+/// Called when the pair with the given number is released, to remove its node
+/// from the dentry cache of the mounted `devpts` filesystems.
+pub fn removed(number: u32) {
+    for_each_root(number, |root, name| _ = root.disconnect_child(name));
+}
+
+/// Call the given closure with the root dentry of each mounted `devpts`
+/// filesystem and the name of the node of the given pair.
+fn for_each_root(number: u32, f: impl Fn(&Arc<Dentry>, &vfs::Name)) {
+    let Ok(name) = vfs::Name::new(number.to_string()) else {
+        return;
+    };
+    let supers = vfs::fs::get("devpts")
+        .map(|fs| fs.supers())
+        .unwrap_or_default();
+    for root in supers
+        .iter()
+        .filter_map(|superblock| superblock.root_dentry())
+    {
+        f(&root, &name);
+    }
+}
+
+/// Returns the identifier of the node of the pair with the given number.
+fn identifier(number: u32) -> Identifier {
+    Identifier(u64::from(number) + 2)
+}
+
+/// Returns the number of the pair whose node has the given identifier, or
+/// `None` if it is the root directory.
+fn number(id: Identifier) -> Option<u32> {
+    id.0.checked_sub(2)
+        .and_then(|number| u32::try_from(number).ok())
+}
+
+/// Create a new `devpts` filesystem. It does not use any device.
+///
+/// # Errors
+/// This function never fails.
+#[allow(clippy::unnecessary_wraps)]
+fn read_super(
+    _: &vfs::fs::Filesystem,
+    device: Device,
+) -> Result<Arc<Super>, vfs::fs::ReadSuperError> {
+    let devpts = Arc::new(Devpts {
+        superblock: Once::new(),
+    });
+    let superblock = Arc::new(Super::new(SuperCreationInfo {
+        device,
+        operation: &SUPER_OPS,
+        root: ROOT,
+        data: Box::new(Arc::clone(&devpts)),
+    }));
+    devpts.superblock.call_once(|| Arc::downgrade(&superblock));
+    Ok(superblock)
+}
+
+/// Nothing is ever written.
+///
+/// # Errors
+/// This function never fails.
+#[allow(clippy::unnecessary_wraps)]
+fn write_super(_: &Super) -> Result<(), vfs::mount::WriteSuperError> {
+    Ok(())
+}
+
+/// Nothing is ever written: changes to the metadata of an inode only last while
+/// it is in the inode cache.
+///
+/// # Errors
+/// This function never fails.
+#[allow(clippy::unnecessary_wraps)]
+fn write_inode(_: &Inode) -> Result<(), WriteInodeError> {
+    Ok(())
+}
+
+/// Create the VFS inode of the root directory or of the node of a pair.
+///
+/// # Errors
+/// Returns `ReadInodeError::DoesNotExist` if the identifier is not the one of
+/// the root directory nor of the node of a pair in use.
+fn read_inode(superblock: &Super, id: Identifier) -> Result<Arc<Inode>, ReadInodeError> {
+    let fs = devpts(superblock);
+    let (kind, inode_ops, file_ops, links, mode) = match number(id) {
+        None if id == ROOT => (
+            Kind::Directory,
+            vfs::inode::Operation::Directory(&INODE_OPS),
+            vfs::file::Operation::Directory(&FILE_OPS),
+            2,
+            0o755,
+        ),
+        Some(number) if pty::exists(number) => (
+            Kind::CharDevice(device::Identifier {
+                major: pty::SLAVE_MAJOR,
+                minor: number,
+            }),
+            vfs::inode::Operation::File(&INODE_FILE_OPS),
+            vfs::file::Operation::File(&device::character::file::FILE_OPS),
+            1,
+            0o620,
+        ),
+        _ => return Err(ReadInodeError::DoesNotExist),
+    };
+
+    let now = UnixTime::now();
+    Ok(Arc::new(Inode::new(
+        fs.superblock.get().cloned().unwrap_or_default(),
+        InodeCreateInfo {
+            id,
+            device: superblock.device(),
+            kind,
+            inode_ops,
+            file_ops,
+            metadata: InodeMetadata {
+                modification_time: now,
+                access_time: now,
+                change_time: now,
+                links,
+                size: 0,
+                mode,
+            },
+            data: Box::new(()),
+        },
+    )))
+}
+
+/// Look up the node of the pair with the given number.
+///
+/// # Errors
+/// Returns `LookupError::NoSuchEntry` if the name is not the number of a pair
+/// in use.
+fn lookup(_: &Inode, name: &str) -> Result<Identifier, LookupError> {
+    name.parse::<u32>()
+        .ok()
+        .filter(|number| number.to_string() == name && pty::exists(*number))
+        .map(identifier)
+        .ok_or(LookupError::NoSuchEntry)
+}
+
+/// Read the entry at the given index of the root directory. The `.` and `..`
+/// entries are the two first entries, followed by the nodes of the pairs in use
+/// in increasing order.
+///
+/// # Errors
+/// Returns `ReaddirError::EndOfDirectory` if there is no entry at the given
+/// index.
+fn readdir(
+    file: &vfs::file::File,
+    offset: vfs::file::Offset,
+) -> Result<vfs::dirent::DirectoryEntry, vfs::file::ReaddirError> {
+    let dentry = file.dentry.as_ref().expect("Open file without dentry");
+    let dot = |name: &str, inode| vfs::dirent::DirectoryEntry {
+        name: String::from(name),
+        offset: 1,
+        kind: vfs::dirent::Kind::Directory,
+        inode,
+    };
+
+    match offset.0 {
+        0 => Ok(dot(".", ROOT)),
+        1 => {
+            let parent = dentry.parent().map_or(ROOT, |parent| parent.inode().id);
+            Ok(dot("..", parent))
+        }
+        index => pty::numbers()
+            .get(index - 2)
+            .map(|&number| vfs::dirent::DirectoryEntry {
+                name: number.to_string(),
+                offset: 1,
+                kind: vfs::dirent::Kind::CharDevice,
+                inode: identifier(number),
+            })
+            .ok_or(vfs::file::ReaddirError::EndOfDirectory),
+    }
+}
+
+/// Nodes are only created by the pseudo-terminals.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn mknod(_: &Inode, _: &str, _: Device) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// Nodes are only created by the pseudo-terminals.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn create(_: &Inode, _: &str) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// Nodes are only created by the pseudo-terminals.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn symlink(_: &Inode, _: &str, _: &str) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// Nodes are only removed when their pseudo-terminal is released.
+///
+/// # Errors
+/// Always returns `UnlinkError::IoError`.
+fn unlink(_: &Inode, _: &str) -> Result<(), UnlinkError> {
+    Err(UnlinkError::IoError)
+}
+
+/// There are no subdirectories.
+///
+/// # Errors
+/// Always returns `MkdirError::IoError`.
+fn mkdir(_: &Inode, _: &str) -> Result<Identifier, MkdirError> {
+    Err(MkdirError::IoError)
+}
+
+/// There are no subdirectories.
+///
+/// # Errors
+/// Always returns `RmdirError::IoError`.
+fn rmdir(_: &Inode, _: &str) -> Result<(), RmdirError> {
+    Err(RmdirError::IoError)
+}
+
+/// Nodes are only created by the pseudo-terminals.
+///
+/// # Errors
+/// Always returns `LinkError::NotSupported`.
+fn link(_: &Inode, _: &str, _: &Inode) -> Result<(), LinkError> {
+    Err(LinkError::NotSupported)
+}
+
+/// Nodes are named after their pseudo-terminal.
+///
+/// # Errors
+/// Always returns `RenameError::IoError`.
+fn rename(_: &Inode, _: &str, _: &str) -> Result<(), RenameError> {
+    Err(RenameError::IoError)
+}
+
+/// Nodes have no content.
+///
+/// # Errors
+/// Always returns `TruncateError::IoError`.
+fn truncate(_: &Inode, _: usize) -> Result<usize, vfs::inode::TruncateError> {
+    Err(vfs::inode::TruncateError::IoError)
+}
@@ -0,0 +1,202 @@
+//! Allocation of blocks and inodes. Each group has a bitmap of its blocks and a
+//! bitmap of its inodes, and the number of free blocks and inodes is stored both
+//! in the descriptor of each group and in the superblock: all of them are updated
+//! together, while holding the allocation lock of the filesystem.
+use super::{
+    disk::{self, group, Superblock},
+    Error, Ext2,
+};
+use crate::device::block::cache::Buffer;
+
+impl Ext2 {
+    /// Allocate a block, as close as possible to the `goal` block, and return
+    /// its number. The content of the block is not initialized.
+    ///
+    /// # Errors
+    /// - `Error::NoSpace`: There is no free block left.
+    /// - `Error::IoError`: A bitmap or a group descriptor could not be read.
+    pub fn alloc_block(&self, goal: u32) -> Result<u32, Error> {
+        let _guard = self.allocation.lock();
+        let goal = goal.clamp(self.first_data_block, self.blocks_count - 1) - self.first_data_block;
+        let first = goal / self.blocks_per_group;
+
+        for i in 0..self.groups {
+            let group = (first + i) % self.groups;
+            let (descriptor, offset) = self.group_descriptor(group)?;
+            let (free, bitmap) = {
+                let data = descriptor.data();
+                let free = disk::get_u16(&data, offset + group::FREE_BLOCKS_COUNT);
+                let bitmap = disk::get_u32(&data, offset + group::BLOCK_BITMAP);
+                (free, bitmap)
+            };
+            if free == 0 {
+                continue;
+            }
+
+            let start = if i == 0 {
+                goal % self.blocks_per_group
+            } else {
+                0
+            };
+            let bitmap = self.block(bitmap)?;
+            let Some(bit) = take_bit(&bitmap, start, self.blocks_in_group(group)) else {
+                continue;
+            };
+
+            add_u16(&descriptor, offset + group::FREE_BLOCKS_COUNT, -1);
+            self.add_free_count(Superblock::FREE_BLOCKS_COUNT, -1)?;
+            return Ok(self.first_data_block + group * self.blocks_per_group + bit);
+        }
+        Err(Error::NoSpace)
+    }
+
+    /// Release the given block.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The block is outside of the filesystem or is
+    ///   already free.
+    /// - `Error::IoError`: The bitmap or the group descriptor could not be read.
+    pub fn free_block(&self, block: u32) -> Result<(), Error> {
+        if block < self.first_data_block || block >= self.blocks_count {
+            return Err(Error::Corrupted);
+        }
+
+        let _guard = self.allocation.lock();
+        let index = block - self.first_data_block;
+        let group = index / self.blocks_per_group;
+        let (descriptor, offset) = self.group_descriptor(group)?;
+        let bitmap = disk::get_u32(&descriptor.data(), offset + group::BLOCK_BITMAP);
+        let bitmap = self.block(bitmap)?;
+
+        clear_bit(&bitmap, index % self.blocks_per_group)?;
+        add_u16(&descriptor, offset + group::FREE_BLOCKS_COUNT, 1);
+        self.add_free_count(Superblock::FREE_BLOCKS_COUNT, 1)
+    }
+
+    /// Allocate an inode, preferably in the given group, and return its number.
+    /// The inode itself is not initialized.
+    ///
+    /// # Errors
+    /// - `Error::NoSpace`: There is no free inode left.
+    /// - `Error::IoError`: A bitmap or a group descriptor could not be read.
+    pub fn alloc_inode(&self, goal: u32, directory: bool) -> Result<u32, Error> {
+        let _guard = self.allocation.lock();
+        for i in 0..self.groups {
+            let group = (goal + i) % self.groups;
+            let (descriptor, offset) = self.group_descriptor(group)?;
+            let (free, bitmap) = {
+                let data = descriptor.data();
+                let free = disk::get_u16(&data, offset + group::FREE_INODES_COUNT);
+                let bitmap = disk::get_u32(&data, offset + group::INODE_BITMAP);
+                (free, bitmap)
+            };
+            if free == 0 {
+                continue;
+            }
+
+            // The reserved inodes are always marked as used in the bitmap of the
+            // first group, but are skipped anyway in case the bitmap is wrong.
+            let first = group * self.inodes_per_group;
+            let count = core::cmp::min(self.inodes_per_group, self.inodes_count - first);
+            let start = core::cmp::min(self.first_inode.saturating_sub(first + 1), count);
+            let bitmap = self.block(bitmap)?;
+            let Some(bit) = take_bit(&bitmap, start, count) else {
+                continue;
+            };
+
+            add_u16(&descriptor, offset + group::FREE_INODES_COUNT, -1);
+            if directory {
+                add_u16(&descriptor, offset + group::USED_DIRS_COUNT, 1);
+            }
+            self.add_free_count(Superblock::FREE_INODES_COUNT, -1)?;
+            return Ok(first + bit + 1);
+        }
+        Err(Error::NoSpace)
+    }
+
+    /// Release the given inode.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The inode is reserved, outside of the filesystem or
+    ///   already free.
+    /// - `Error::IoError`: The bitmap or the group descriptor could not be read.
+    pub fn free_inode(&self, inode: u32, directory: bool) -> Result<(), Error> {
+        if inode < self.first_inode || inode > self.inodes_count {
+            return Err(Error::Corrupted);
+        }
+
+        let _guard = self.allocation.lock();
+        let group = (inode - 1) / self.inodes_per_group;
+        let (descriptor, offset) = self.group_descriptor(group)?;
+        let bitmap = disk::get_u32(&descriptor.data(), offset + group::INODE_BITMAP);
+        let bitmap = self.block(bitmap)?;
+
+        clear_bit(&bitmap, (inode - 1) % self.inodes_per_group)?;
+        add_u16(&descriptor, offset + group::FREE_INODES_COUNT, 1);
+        if directory {
+            add_u16(&descriptor, offset + group::USED_DIRS_COUNT, -1);
+        }
+        self.add_free_count(Superblock::FREE_INODES_COUNT, 1)
+    }
+
+    /// Returns the number of blocks in the given group. All groups have the same
+    /// number of blocks, except the last one that may be smaller.
+    fn blocks_in_group(&self, group: u32) -> u32 {
+        let first = self.first_data_block + group * self.blocks_per_group;
+        core::cmp::min(self.blocks_per_group, self.blocks_count - first)
+    }
+
+    /// Add the given delta to the free blocks or free inodes counter of the
+    /// superblock, at the given offset.
+    fn add_free_count(&self, field: usize, delta: i32) -> Result<(), Error> {
+        let (buffer, offset) = self.superblock_buffer()?;
+        let mut data = buffer.data();
+        let count = disk::get_u32(&data, offset + field).wrapping_add_signed(delta);
+        disk::set_u32(&mut data, offset + field, count);
+        drop(data);
+        buffer.mark_dirty();
+        Ok(())
+    }
+}
+
+/// Find the first clear bit of the bitmap among its `count` first bits, starting
+/// the search at the `start` bit and wrapping around, set it and return its index.
+fn take_bit(bitmap: &Buffer, start: u32, count: u32) -> Option<u32> {
+    let mut data = bitmap.data();
+    let bit = (start..count)
+        .chain(0..start)
+        .find(|&bit| data[bit as usize / 8] & (1 << (bit % 8)) == 0)?;
+
+    data[bit as usize / 8] |= 1 << (bit % 8);
+    drop(data);
+    bitmap.mark_dirty();
+    Some(bit)
+}
+
+/// Clear the given bit of the bitmap.
+///
+/// # Errors
+/// Returns `Error::Corrupted` if the bit was already clear, meaning that the
+/// block or the inode was freed twice.
+fn clear_bit(bitmap: &Buffer, bit: u32) -> Result<(), Error> {
+    let mut data = bitmap.data();
+    let byte = &mut data[bit as usize / 8];
+    if *byte & (1 << (bit % 8)) == 0 {
+        return Err(Error::Corrupted);
+    }
+
+    *byte &= !(1 << (bit % 8));
+    drop(data);
+    bitmap.mark_dirty();
+    Ok(())
+}
+
+/// Add the given delta to the 16 bits counter at the given offset of a group
+/// descriptor buffer.
+fn add_u16(buffer: &Buffer, offset: usize, delta: i16) {
+    let mut data = buffer.data();
+    let value = disk::get_u16(&data, offset).wrapping_add_signed(delta);
+    disk::set_u16(&mut data, offset, value);
+    drop(data);
+    buffer.mark_dirty();
+}
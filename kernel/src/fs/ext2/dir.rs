@@ -0,0 +1,758 @@
+//! Directories of an ext2 filesystem. A directory is a file made of blocks of
+//! variable-length entries, each entry covering the space up to the next one:
+//! an entry is removed by merging it into the previous entry of its block, and
+//! a new entry is inserted in the unused space at the end of an existing entry,
+//! or in a new block appended to the directory.
+//!
+//! All the operations on a directory are serialized by the lock of its state.
+//! When an operation also modifies a child inode, the child is locked after its
+//! parent directory.
+use super::{
+    disk::{self, DirectoryEntry},
+    inode::{self, info, InodeInfo, InodeState},
+    vfs_now, Error, Ext2,
+};
+use crate::{
+    device::Device,
+    vfs::{
+        self,
+        inode::{
+            CreateError, Identifier, Inode, LinkError, LookupError, MkdirError, RenameError,
+            RmdirError, UnlinkError,
+        },
+    },
+};
+
+/// Operations that can be performed on a directory inode.
+pub static INODE_OPS: vfs::inode::DirectoryOperation = vfs::inode::DirectoryOperation {
+    mknod,
+    create,
+    lookup,
+    unlink,
+    mkdir,
+    rmdir,
+    link,
+    rename,
+    symlink,
+};
+
+/// Operations that can be performed on an opened directory.
+pub static FILE_OPS: vfs::file::DirectoryOperation = vfs::file::DirectoryOperation { readdir };
+
+/// The maximal number of links to an inode, the same limit as Linux.
+const MAX_LINKS: u64 = 32000;
+
+/// The position of an entry in a directory.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// The block of the device containing the entry.
+    block: u32,
+
+    /// The offset of the entry in its block.
+    offset: usize,
+
+    /// The position of the entry in the directory, in bytes.
+    position: usize,
+
+    /// The offset of the previous entry in the same block, if any.
+    previous: Option<usize>,
+
+    /// The header of the entry.
+    entry: DirectoryEntry,
+}
+
+/// Call the given closure on each used entry of the directory starting at or
+/// after the given position, with the block containing the entry, until the
+/// closure returns a value. Returns that value, or `None` if the closure
+/// returned `None` for all entries.
+///
+/// # Errors
+/// - `Error::Corrupted`: The directory contains a hole or a malformed entry.
+/// - `Error::IoError`: A block of the directory could not be read.
+fn scan<T>(
+    info: &InodeInfo,
+    state: &mut InodeState,
+    size: usize,
+    start: usize,
+    mut f: impl FnMut(&Slot, &[u8]) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    let block_size = info.fs.block_size();
+    for index in start / block_size..size / block_size {
+        let block = info.map(state, index as u64, false)?.ok_or(Error::Corrupted)?;
+        let buffer = info.fs.block(block)?;
+        let data = buffer.data();
+
+        let mut offset = 0;
+        let mut previous = None;
+        while offset < block_size {
+            let entry = DirectoryEntry::parse(&data, offset).ok_or(Error::Corrupted)?;
+            let slot = Slot {
+                block,
+                offset,
+                position: index * block_size + offset,
+                previous,
+                entry,
+            };
+            if entry.inode != 0 && slot.position >= start {
+                if let Some(value) = f(&slot, &data) {
+                    return Ok(Some(value));
+                }
+            }
+            previous = Some(offset);
+            offset += entry.rec_len;
+        }
+    }
+    Ok(None)
+}
+
+/// Find the entry with the given name in the directory.
+///
+/// # Errors
+/// See [`scan`].
+fn find(
+    info: &InodeInfo,
+    state: &mut InodeState,
+    size: usize,
+    name: &str,
+) -> Result<Option<Slot>, Error> {
+    scan(info, state, size, 0, |slot, data| {
+        (slot.entry.name(data, slot.offset) == name.as_bytes()).then_some(*slot)
+    })
+}
+
+/// Add an entry with the given name pointing to the given inode to the directory.
+/// The entry is inserted in the first entry with enough unused space, or in a new
+/// block appended to the directory. The size of the directory is updated in its
+/// VFS inode, but the inode is not written.
+///
+/// # Errors
+/// - `Error::NoSpace`: A new block is needed but could not be allocated.
+/// - `Error::Corrupted`: The directory contains a hole or a malformed entry.
+/// - `Error::IoError`: A block of the directory could not be read.
+fn add_entry(
+    dir: &Inode,
+    state: &mut InodeState,
+    name: &str,
+    inode: u32,
+    file_type: u8,
+) -> Result<(), Error> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let block_size = fs.block_size();
+    let size = dir.metadata.lock().size;
+    let needed = DirectoryEntry::size(name.len());
+    let mut new = DirectoryEntry {
+        inode,
+        rec_len: 0,
+        name_len: name.len(),
+        file_type: if fs.filetype { file_type } else { 0 },
+    };
+    state.flags &= !disk::INDEX_FLAG;
+
+    for index in 0..(size / block_size) as u64 {
+        let block = info.map(state, index, false)?.ok_or(Error::Corrupted)?;
+        let buffer = fs.block(block)?;
+        let mut data = buffer.data();
+
+        let mut offset = 0;
+        while offset < block_size {
+            let mut entry = DirectoryEntry::parse(&data, offset).ok_or(Error::Corrupted)?;
+            let used = match entry.inode {
+                0 => 0,
+                _ => DirectoryEntry::size(entry.name_len),
+            };
+
+            if entry.rec_len - used >= needed {
+                new.rec_len = entry.rec_len - used;
+                if used > 0 {
+                    entry.rec_len = used;
+                    let name = entry.name(&data, offset).to_vec();
+                    DirectoryEntry::write(&mut data, offset, &entry, &name);
+                }
+                DirectoryEntry::write(&mut data, offset + used, &new, name.as_bytes());
+                drop(data);
+                buffer.mark_dirty();
+                return Ok(());
+            }
+            offset += entry.rec_len;
+        }
+    }
+
+    // No entry has enough unused space: append a new block to the directory,
+    // with the new entry covering the whole block.
+    let block = info
+        .map(state, (size / block_size) as u64, true)?
+        .ok_or(Error::NoSpace)?;
+    let buffer = fs.block(block)?;
+    new.rec_len = block_size;
+    DirectoryEntry::write(&mut buffer.data(), 0, &new, name.as_bytes());
+    buffer.mark_dirty();
+    dir.metadata.lock().size = size + block_size;
+    Ok(())
+}
+
+/// Remove the entry at the given position from the directory. The entry is
+/// merged into the previous entry of its block, or marked as unused if it is
+/// the first entry of its block.
+///
+/// # Errors
+/// Returns `Error::IoError` if the block containing the entry could not be read.
+fn remove_entry(fs: &Ext2, state: &mut InodeState, slot: &Slot) -> Result<(), Error> {
+    let buffer = fs.block(slot.block)?;
+    let mut data = buffer.data();
+    match slot.previous {
+        Some(previous) => {
+            let length = DirectoryEntry::rec_len(&data, previous) + slot.entry.rec_len;
+            DirectoryEntry::set_rec_len(&mut data, previous, length);
+        }
+        None => disk::set_u32(&mut data, slot.offset, 0),
+    }
+    drop(data);
+    buffer.mark_dirty();
+    state.flags &= !disk::INDEX_FLAG;
+    Ok(())
+}
+
+/// Returns the type stored in directory entries for the given file type bits.
+fn file_type(format: u16) -> u8 {
+    match format {
+        disk::S_IFREG => disk::FT_REG_FILE,
+        disk::S_IFDIR => disk::FT_DIR,
+        disk::S_IFCHR => disk::FT_CHRDEV,
+        disk::S_IFBLK => disk::FT_BLKDEV,
+        disk::S_IFIFO => disk::FT_FIFO,
+        disk::S_IFSOCK => disk::FT_SOCK,
+        disk::S_IFLNK => disk::FT_SYMLINK,
+        _ => disk::FT_UNKNOWN,
+    }
+}
+
+/// Update the modification and change times of the directory after one of its
+/// entries was added or removed, and write it into the inode table.
+fn touch(dir: &Inode, state: &InodeState) -> Result<(), Error> {
+    {
+        let mut metadata = dir.metadata.lock();
+        metadata.modification_time = vfs_now();
+        metadata.change_time = vfs_now();
+    }
+    info(dir).store(dir, state)
+}
+
+/// Returns the VFS inode of the entry at the given position.
+fn child(dir: &Inode, slot: &Slot) -> Result<Arc<Inode>, Error> {
+    dir.superblock
+        .upgrade()
+        .ok_or(Error::IoError)?
+        .get_inode(Identifier(u64::from(slot.entry.inode)))
+        .map_err(|_| Error::IoError)
+}
+
+/// Create a new inode that is not a directory, with the given mode and content,
+/// and add an entry pointing to it in the directory. If the entry cannot be
+/// added, the inode and its block are released.
+///
+/// # Errors
+/// - `Error::NoSpace`: There is no free inode or block left.
+/// - `Error::Corrupted`: The directory is corrupted.
+/// - `Error::IoError`: A block could not be read.
+fn insert(
+    dir: &Inode,
+    state: &mut InodeState,
+    name: &str,
+    mode: u16,
+    size: u64,
+    block: [u32; disk::BLOCK_POINTERS],
+    sectors: u32,
+) -> Result<Identifier, Error> {
+    let fs = &info(dir).fs;
+    let number = fs.new_inode(info(dir).number, mode, 1, size, block, sectors)?;
+    if let Err(error) = add_entry(dir, state, name, number, file_type(mode & disk::S_IFMT)) {
+        fs.discard_inode(
+            number,
+            mode & disk::S_IFMT == disk::S_IFDIR,
+            sectors > 0 && block[0] != 0,
+        );
+        return Err(error);
+    }
+    touch(dir, state)?;
+    Ok(Identifier(u64::from(number)))
+}
+
+/// Create a device node in the directory.
+///
+/// # Errors
+/// - `CreateError::AlreadyExists`: An entry with the same name already exists.
+/// - `CreateError::NoSpace`: There is no free inode or block left.
+/// - `CreateError::IoError`: The directory could not be read or is corrupted.
+///
+/// # Panics
+/// Panics if the device is [`Device::None`].
+fn mknod(dir: &Inode, name: &str, device: Device) -> Result<Identifier, CreateError> {
+    let (format, id) = match device {
+        Device::Block(id) => (disk::S_IFBLK, id),
+        Device::Char(id) => (disk::S_IFCHR, id),
+        Device::None => panic!("Cannot create a device file without a device"),
+    };
+
+    let info = info(dir);
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    if find(info, &mut state, size, name)?.is_some() {
+        return Err(CreateError::AlreadyExists);
+    }
+    let block = inode::encode_device(id);
+    Ok(insert(dir, &mut state, name, format | 0o644, 0, block, 0)?)
+}
+
+/// Create an empty regular file in the directory.
+///
+/// # Errors
+/// - `CreateError::AlreadyExists`: An entry with the same name already exists.
+/// - `CreateError::NoSpace`: There is no free inode or block left.
+/// - `CreateError::IoError`: The directory could not be read or is corrupted.
+fn create(dir: &Inode, name: &str) -> Result<Identifier, CreateError> {
+    let info = info(dir);
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    if find(info, &mut state, size, name)?.is_some() {
+        return Err(CreateError::AlreadyExists);
+    }
+    let block = [0; disk::BLOCK_POINTERS];
+    Ok(insert(
+        dir,
+        &mut state,
+        name,
+        disk::S_IFREG | 0o644,
+        0,
+        block,
+        0,
+    )?)
+}
+
+/// Create a symbolic link in the directory. A target shorter than the block
+/// pointers of an inode is stored directly in them, and a longer target is
+/// stored in a single data block.
+///
+/// # Errors
+/// - `CreateError::AlreadyExists`: An entry with the same name already exists.
+/// - `CreateError::NoSpace`: There is no free inode or block left, or the target
+///   does not fit in a block.
+/// - `CreateError::IoError`: The directory could not be read or is corrupted.
+fn symlink(dir: &Inode, name: &str, target: &str) -> Result<Identifier, CreateError> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    if find(info, &mut state, size, name)?.is_some() {
+        return Err(CreateError::AlreadyExists);
+    }
+
+    let target = target.as_bytes();
+    let mut block = [0; disk::BLOCK_POINTERS];
+    let mut sectors = 0;
+    if target.len() <= disk::FAST_SYMLINK_MAX {
+        let mut bytes = [0; disk::BLOCK_POINTERS * 4];
+        bytes[..target.len()].copy_from_slice(target);
+        for (pointer, chunk) in block.iter_mut().zip(bytes.chunks_exact(4)) {
+            *pointer = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+    } else if target.len() < fs.block_size() {
+        let data = fs.alloc_block(fs.group_start(info.number))?;
+        match fs.zeroed_block(data) {
+            Ok(buffer) => buffer.data()[..target.len()].copy_from_slice(target),
+            Err(error) => {
+                _ = fs.free_block(data);
+                return Err(error.into());
+            }
+        }
+        block[0] = data;
+        sectors = fs.sectors_per_block();
+    } else {
+        return Err(CreateError::NoSpace);
+    }
+
+    let mode = disk::S_IFLNK | 0o777;
+    Ok(insert(
+        dir,
+        &mut state,
+        name,
+        mode,
+        target.len() as u64,
+        block,
+        sectors,
+    )?)
+}
+
+/// Look up the entry with the given name in the directory.
+///
+/// # Errors
+/// - `LookupError::NoSuchEntry`: There is no entry with the given name.
+/// - `LookupError::IoError`: The directory could not be read or is corrupted.
+fn lookup(dir: &Inode, name: &str) -> Result<Identifier, LookupError> {
+    let info = info(dir);
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    find(info, &mut state, size, name)?
+        .map(|slot| Identifier(u64::from(slot.entry.inode)))
+        .ok_or(LookupError::NoSuchEntry)
+}
+
+/// Remove the entry with the given name from the directory, and drop a link to
+/// the inode it points to. The inode is released when its last link is removed
+/// and it is not used anymore.
+///
+/// # Errors
+/// - `UnlinkError::NoSuchEntry`: There is no entry with the given name.
+/// - `UnlinkError::IsADirectory`: The entry is a directory.
+/// - `UnlinkError::IoError`: The directory or the inode could not be read.
+fn unlink(dir: &Inode, name: &str) -> Result<(), UnlinkError> {
+    let info = info(dir);
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    let slot = find(info, &mut state, size, name)?.ok_or(UnlinkError::NoSuchEntry)?;
+    let child = child(dir, &slot)?;
+    if child.kind == vfs::inode::Kind::Directory {
+        return Err(UnlinkError::IsADirectory);
+    }
+
+    let child_info = inode::info(&child);
+    let mut child_state = child_info.lock();
+    remove_entry(&info.fs, &mut state, &slot)?;
+    {
+        let mut metadata = child.metadata.lock();
+        metadata.links = metadata.links.saturating_sub(1);
+        metadata.change_time = vfs_now();
+        child_state.deleted = metadata.links == 0;
+    }
+    child_info.store(&child, &child_state)?;
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Create a directory in the directory, with its `.` and `..` entries.
+///
+/// # Errors
+/// - `MkdirError::AlreadyExists`: An entry with the same name already exists.
+/// - `MkdirError::NoSpace`: There is no free inode or block left, or the
+///   directory has too many links.
+/// - `MkdirError::IoError`: The directory could not be read or is corrupted.
+fn mkdir(dir: &Inode, name: &str) -> Result<Identifier, MkdirError> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    if find(info, &mut state, size, name)?.is_some() {
+        return Err(MkdirError::AlreadyExists);
+    }
+    if dir.metadata.lock().links >= MAX_LINKS {
+        return Err(MkdirError::NoSpace);
+    }
+
+    let data = fs.alloc_block(fs.group_start(info.number))?;
+    let mut block = [0; disk::BLOCK_POINTERS];
+    block[0] = data;
+
+    let block_size = fs.block_size();
+    let mode = disk::S_IFDIR | 0o755;
+    let sectors = fs.sectors_per_block();
+    let number = match fs.new_inode(info.number, mode, 2, block_size as u64, block, sectors) {
+        Ok(number) => number,
+        Err(error) => {
+            _ = fs.free_block(data);
+            return Err(error.into());
+        }
+    };
+
+    let initialize = || -> Result<(), Error> {
+        let buffer = fs.zeroed_block(data)?;
+        let mut data = buffer.data();
+        let file_type = if fs.filetype { disk::FT_DIR } else { 0 };
+        let dot = DirectoryEntry {
+            inode: number,
+            rec_len: DirectoryEntry::size(1),
+            name_len: 1,
+            file_type,
+        };
+        let dotdot = DirectoryEntry {
+            inode: info.number,
+            rec_len: block_size - dot.rec_len,
+            name_len: 2,
+            file_type,
+        };
+        DirectoryEntry::write(&mut data, 0, &dot, b".");
+        DirectoryEntry::write(&mut data, dot.rec_len, &dotdot, b"..");
+        Ok(())
+    };
+
+    if let Err(error) =
+        initialize().and_then(|()| add_entry(dir, &mut state, name, number, disk::FT_DIR))
+    {
+        fs.discard_inode(number, true, true);
+        return Err(error.into());
+    }
+
+    dir.metadata.lock().links += 1;
+    touch(dir, &state)?;
+    Ok(Identifier(u64::from(number)))
+}
+
+/// Remove the empty directory with the given name from the directory. The
+/// directory is released once it is not used anymore.
+///
+/// # Errors
+/// - `RmdirError::NoSuchEntry`: There is no entry with the given name.
+/// - `RmdirError::NotADirectory`: The entry is not a directory.
+/// - `RmdirError::NotEmpty`: The directory contains other entries than `.` and `..`.
+/// - `RmdirError::IoError`: One of the directories could not be read.
+fn rmdir(dir: &Inode, name: &str) -> Result<(), RmdirError> {
+    let info = info(dir);
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    let slot = find(info, &mut state, size, name)?.ok_or(RmdirError::NoSuchEntry)?;
+    let child = child(dir, &slot)?;
+    if child.kind != vfs::inode::Kind::Directory {
+        return Err(RmdirError::NotADirectory);
+    }
+
+    let child_info = inode::info(&child);
+    let mut child_state = child_info.lock();
+    let child_size = child.metadata.lock().size;
+    let other = scan(child_info, &mut child_state, child_size, 0, |slot, data| {
+        let name = slot.entry.name(data, slot.offset);
+        (name != b"." && name != b"..").then_some(())
+    })?;
+    if other.is_some() {
+        return Err(RmdirError::NotEmpty);
+    }
+
+    remove_entry(&info.fs, &mut state, &slot)?;
+    {
+        let mut metadata = child.metadata.lock();
+        metadata.links = 0;
+        metadata.change_time = vfs_now();
+        child_state.deleted = true;
+    }
+    child_info.store(&child, &child_state)?;
+
+    {
+        let mut metadata = dir.metadata.lock();
+        metadata.links = metadata.links.saturating_sub(1);
+    }
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Create a new entry in the directory pointing to the target inode.
+///
+/// # Errors
+/// - `LinkError::AlreadyExists`: An entry with the same name already exists.
+/// - `LinkError::IsADirectory`: The target is a directory.
+/// - `LinkError::NoSpace`: A block could not be allocated for the entry, or
+///   the target has too many links.
+/// - `LinkError::IoError`: The directory could not be read, or the target is
+///   not on the same filesystem.
+fn link(dir: &Inode, name: &str, target: &Inode) -> Result<(), LinkError> {
+    if target.kind == vfs::inode::Kind::Directory {
+        return Err(LinkError::IsADirectory);
+    }
+    let info = info(dir);
+    let Some(target_info) = target
+        .data
+        .downcast_ref::<InodeInfo>()
+        .filter(|target| Arc::ptr_eq(&target.fs, &info.fs))
+    else {
+        return Err(LinkError::IoError);
+    };
+
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    if find(info, &mut state, size, name)?.is_some() {
+        return Err(LinkError::AlreadyExists);
+    }
+    if target.metadata.lock().links >= MAX_LINKS {
+        return Err(LinkError::NoSpace);
+    }
+
+    let target_state = target_info.lock();
+    add_entry(
+        dir,
+        &mut state,
+        name,
+        target_info.number,
+        file_type(target_info.format),
+    )?;
+    {
+        let mut metadata = target.metadata.lock();
+        metadata.links += 1;
+        metadata.change_time = vfs_now();
+    }
+    target_info.store(target, &target_state)?;
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Rename an entry of the directory. The new entry is added before the old one
+/// is removed, so that the inode is never left without a link.
+///
+/// # Errors
+/// - `RenameError::NoSuchEntry`: There is no entry named `old`.
+/// - `RenameError::AlreadyExists`: An entry named `new` already exists.
+/// - `RenameError::NoSpace`: A block could not be allocated for the new entry.
+/// - `RenameError::IoError`: The directory could not be read or is corrupted.
+fn rename(dir: &Inode, old: &str, new: &str) -> Result<(), RenameError> {
+    let info = info(dir);
+    let mut state = info.lock();
+    let size = dir.metadata.lock().size;
+    if find(info, &mut state, size, new)?.is_some() {
+        return Err(RenameError::AlreadyExists);
+    }
+    let slot = find(info, &mut state, size, old)?.ok_or(RenameError::NoSuchEntry)?;
+    add_entry(dir, &mut state, new, slot.entry.inode, slot.entry.file_type)?;
+
+    // The old entry may have been shrunk to make room for the new one, so it
+    // is searched again before being removed.
+    let size = dir.metadata.lock().size;
+    let slot = find(info, &mut state, size, old)?.ok_or(Error::Corrupted)?;
+    remove_entry(&info.fs, &mut state, &slot)?;
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Read the first used entry of the directory at or after the given position,
+/// in bytes. The offset of the returned entry is the distance from the given
+/// position to the next entry, so that each call only scans a single block.
+///
+/// # Errors
+/// - `ReaddirError::EndOfDirectory`: There is no entry after the given position.
+/// - `ReaddirError::IoError`: The directory could not be read or is corrupted.
+fn readdir(
+    file: &vfs::file::File,
+    offset: vfs::file::Offset,
+) -> Result<vfs::dirent::DirectoryEntry, vfs::file::ReaddirError> {
+    let dir = file
+        .dentry
+        .as_ref()
+        .expect("Open file without dentry")
+        .inode();
+    let info = info(dir);
+
+    let found = {
+        let mut state = info.lock();
+        let size = dir.metadata.lock().size;
+        scan(info, &mut state, size, offset.0, |slot, data| {
+            let name = slot.entry.name(data, slot.offset);
+            Some((*slot, String::from_utf8_lossy(name).into_owned()))
+        })
+    };
+    let (slot, name) = found
+        .map_err(|_| vfs::file::ReaddirError::IoError)?
+        .ok_or(vfs::file::ReaddirError::EndOfDirectory)?;
+
+    // The type of the entry is read from the inode when it is not stored in the
+    // entry itself.
+    let format = match slot.entry.file_type {
+        disk::FT_DIR => disk::S_IFDIR,
+        disk::FT_CHRDEV => disk::S_IFCHR,
+        disk::FT_BLKDEV => disk::S_IFBLK,
+        disk::FT_SYMLINK => disk::S_IFLNK,
+        disk::FT_REG_FILE | disk::FT_FIFO | disk::FT_SOCK => disk::S_IFREG,
+        _ => info
+            .fs
+            .read_disk_inode(slot.entry.inode)
+            .map_or(disk::S_IFREG, |inode| inode.mode & disk::S_IFMT),
+    };
+    let kind = match format {
+        disk::S_IFDIR => vfs::dirent::Kind::Directory,
+        disk::S_IFCHR => vfs::dirent::Kind::CharDevice,
+        disk::S_IFBLK => vfs::dirent::Kind::BlockDevice,
+        disk::S_IFLNK => vfs::dirent::Kind::Symlink,
+        _ => vfs::dirent::Kind::File,
+    };
+
+    dir.metadata.lock().access_time = vfs_now();
+    Ok(vfs::dirent::DirectoryEntry {
+        name,
+        offset: slot.position + slot.entry.rec_len - offset.0,
+        kind,
+        inode: Identifier(u64::from(slot.entry.inode)),
+    })
+}
+
+impl Ext2 {
+    /// Release an inode that was just created but could not be linked into its
+    /// directory, and its first data block if it has one.
+    fn discard_inode(&self, inode: u32, directory: bool, has_block: bool) {
+        if has_block {
+            if let Ok(raw) = self.read_disk_inode(inode) {
+                _ = self.free_block(raw.block[0]);
+            }
+        }
+        if let Ok((buffer, offset)) = self.inode_location(inode) {
+            let mut data = buffer.data();
+            let raw = &mut data[offset..offset + self.inode_size];
+            let mut disk = disk::Inode::parse(raw);
+            disk.links_count = 0;
+            disk.dtime = super::now();
+            disk.blocks = 0;
+            disk.block = [0; disk::BLOCK_POINTERS];
+            disk.write(raw);
+            drop(data);
+            buffer.mark_dirty();
+        }
+        _ = self.free_inode(inode, directory);
+    }
+}
+
+impl From<Error> for CreateError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for MkdirError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for LinkError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for RenameError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for LookupError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
+
+impl From<Error> for UnlinkError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
+
+impl From<Error> for RmdirError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
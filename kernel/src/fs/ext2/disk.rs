@@ -0,0 +1,339 @@
+//! The on-disk structures of ext2. All fields are stored in little-endian, and
+//! are read and written directly in the buffers of the buffer cache, so that the
+//! fields that the driver does not know about are preserved when a structure is
+//! written back.
+
+/// The magic number stored in the superblock of an ext2 filesystem.
+pub const MAGIC: u16 = 0xEF53;
+
+/// The offset of the superblock from the start of the device, in bytes.
+pub const SUPERBLOCK_OFFSET: usize = 1024;
+
+/// The size of the superblock, in bytes.
+pub const SUPERBLOCK_SIZE: usize = 1024;
+
+/// The size of a group descriptor, in bytes.
+pub const GROUP_DESCRIPTOR_SIZE: usize = 32;
+
+/// The inode number of the root directory.
+pub const ROOT_INODE: u32 = 2;
+
+/// The size of an inode and the first non-reserved inode on revision 0
+/// filesystems, which do not store them in the superblock.
+pub const GOOD_OLD_INODE_SIZE: usize = 128;
+pub const GOOD_OLD_FIRST_INODE: u32 = 11;
+
+/// The number of block pointers of an inode, and the number of direct ones. The
+/// three last pointers are the single, double and triple indirect blocks.
+pub const BLOCK_POINTERS: usize = 15;
+pub const DIRECT_BLOCKS: usize = 12;
+
+/// The filesystem was cleanly unmounted.
+pub const STATE_VALID: u16 = 1;
+
+/// Errors were detected in the filesystem.
+pub const STATE_ERRORS: u16 = 2;
+
+/// The directory entries store the type of the inode they point to.
+pub const INCOMPAT_FILETYPE: u32 = 0x0002;
+
+/// The groups whose number is not 0, 1 or a power of 3, 5 or 7 do not contain
+/// a backup of the superblock and of the group descriptors.
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+
+/// Regular files can be larger than 4 GiB.
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+
+/// The incompatible features supported by the driver. A filesystem using any
+/// other incompatible feature cannot be mounted.
+pub const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE;
+
+/// The read-only compatible features supported by the driver. A filesystem using
+/// any other of those features cannot be mounted, since the driver would not be
+/// able to keep them consistent when writing.
+pub const SUPPORTED_RO_COMPAT: u32 = RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE;
+
+/// The directory is indexed with a hashed B-tree. The driver does not maintain
+/// the index, and clears this flag when it modifies such a directory: the blocks
+/// of the index look like empty directory blocks and remain valid.
+pub const INDEX_FLAG: u32 = 0x1000;
+
+/// The magic number of an extended attribute block.
+pub const XATTR_MAGIC: u32 = 0xEA02_0000;
+
+/// The file type bits of the mode of an inode.
+pub const S_IFMT: u16 = 0xF000;
+pub const S_IFSOCK: u16 = 0xC000;
+pub const S_IFLNK: u16 = 0xA000;
+pub const S_IFREG: u16 = 0x8000;
+pub const S_IFBLK: u16 = 0x6000;
+pub const S_IFDIR: u16 = 0x4000;
+pub const S_IFCHR: u16 = 0x2000;
+pub const S_IFIFO: u16 = 0x1000;
+
+/// The file types stored in directory entries when the filetype feature is
+/// enabled.
+pub const FT_UNKNOWN: u8 = 0;
+pub const FT_REG_FILE: u8 = 1;
+pub const FT_DIR: u8 = 2;
+pub const FT_CHRDEV: u8 = 3;
+pub const FT_BLKDEV: u8 = 4;
+pub const FT_FIFO: u8 = 5;
+pub const FT_SOCK: u8 = 6;
+pub const FT_SYMLINK: u8 = 7;
+
+/// The maximum length of a symbolic link target stored directly in the block
+/// pointers of its inode instead of in a data block.
+pub const FAST_SYMLINK_MAX: usize = BLOCK_POINTERS * 4 - 1;
+
+/// Read a little-endian `u16` at the given offset.
+#[must_use]
+pub fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Read a little-endian `u32` at the given offset.
+#[must_use]
+pub fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Write a little-endian `u16` at the given offset.
+pub fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Write a little-endian `u32` at the given offset.
+pub fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// The fields of the superblock used by the driver.
+#[derive(Debug, Clone)]
+pub struct Superblock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub free_blocks_count: u32,
+    pub free_inodes_count: u32,
+    pub first_data_block: u32,
+    pub log_block_size: u32,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub magic: u16,
+    pub state: u16,
+    pub rev_level: u32,
+    pub first_ino: u32,
+    pub inode_size: u16,
+    pub feature_incompat: u32,
+    pub feature_ro_compat: u32,
+}
+
+impl Superblock {
+    pub const FREE_BLOCKS_COUNT: usize = 12;
+    pub const FREE_INODES_COUNT: usize = 16;
+    pub const MTIME: usize = 44;
+    pub const WTIME: usize = 48;
+    pub const MNT_COUNT: usize = 52;
+    pub const STATE: usize = 58;
+
+    /// Parse the superblock from the given bytes.
+    #[must_use]
+    pub fn parse(data: &[u8]) -> Self {
+        Self {
+            inodes_count: get_u32(data, 0),
+            blocks_count: get_u32(data, 4),
+            free_blocks_count: get_u32(data, Self::FREE_BLOCKS_COUNT),
+            free_inodes_count: get_u32(data, Self::FREE_INODES_COUNT),
+            first_data_block: get_u32(data, 20),
+            log_block_size: get_u32(data, 24),
+            blocks_per_group: get_u32(data, 32),
+            inodes_per_group: get_u32(data, 40),
+            magic: get_u16(data, 56),
+            state: get_u16(data, Self::STATE),
+            rev_level: get_u32(data, 76),
+            first_ino: get_u32(data, 84),
+            inode_size: get_u16(data, 88),
+            feature_incompat: get_u32(data, 96),
+            feature_ro_compat: get_u32(data, 100),
+        }
+    }
+}
+
+/// The offsets of the fields of a group descriptor.
+pub mod group {
+    pub const BLOCK_BITMAP: usize = 0;
+    pub const INODE_BITMAP: usize = 4;
+    pub const INODE_TABLE: usize = 8;
+    pub const FREE_BLOCKS_COUNT: usize = 12;
+    pub const FREE_INODES_COUNT: usize = 14;
+    pub const USED_DIRS_COUNT: usize = 16;
+}
+
+/// The fields of an inode used by the driver. The other fields of the on-disk
+/// inode, like the OS-dependent ones, are left untouched when it is written.
+#[derive(Debug, Clone, Default)]
+pub struct Inode {
+    pub mode: u16,
+    pub uid: u32,
+    pub size: u64,
+    pub atime: u32,
+    pub ctime: u32,
+    pub mtime: u32,
+    pub dtime: u32,
+    pub gid: u32,
+    pub links_count: u16,
+    pub blocks: u32,
+    pub flags: u32,
+    pub block: [u32; BLOCK_POINTERS],
+    pub generation: u32,
+    pub file_acl: u32,
+}
+
+impl Inode {
+    /// Parse an inode from the given bytes.
+    #[must_use]
+    pub fn parse(data: &[u8]) -> Self {
+        let mode = get_u16(data, 0);
+        let mut size = u64::from(get_u32(data, 4));
+        if mode & S_IFMT == S_IFREG {
+            size |= u64::from(get_u32(data, 108)) << 32;
+        }
+
+        let mut block = [0; BLOCK_POINTERS];
+        for (i, pointer) in block.iter_mut().enumerate() {
+            *pointer = get_u32(data, 40 + i * 4);
+        }
+
+        Self {
+            mode,
+            uid: u32::from(get_u16(data, 2)) | u32::from(get_u16(data, 120)) << 16,
+            size,
+            atime: get_u32(data, 8),
+            ctime: get_u32(data, 12),
+            mtime: get_u32(data, 16),
+            dtime: get_u32(data, 20),
+            gid: u32::from(get_u16(data, 24)) | u32::from(get_u16(data, 122)) << 16,
+            links_count: get_u16(data, 26),
+            blocks: get_u32(data, 28),
+            flags: get_u32(data, 32),
+            block,
+            generation: get_u32(data, 100),
+            file_acl: get_u32(data, 104),
+        }
+    }
+
+    /// Write this inode into the given bytes.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write(&self, data: &mut [u8]) {
+        set_u16(data, 0, self.mode);
+        set_u16(data, 2, self.uid as u16);
+        set_u32(data, 4, self.size as u32);
+        set_u32(data, 8, self.atime);
+        set_u32(data, 12, self.ctime);
+        set_u32(data, 16, self.mtime);
+        set_u32(data, 20, self.dtime);
+        set_u16(data, 24, self.gid as u16);
+        set_u16(data, 26, self.links_count);
+        set_u32(data, 28, self.blocks);
+        set_u32(data, 32, self.flags);
+        for (i, pointer) in self.block.iter().enumerate() {
+            set_u32(data, 40 + i * 4, *pointer);
+        }
+        set_u32(data, 100, self.generation);
+        set_u32(data, 104, self.file_acl);
+        if self.mode & S_IFMT == S_IFREG {
+            set_u32(data, 108, (self.size >> 32) as u32);
+        }
+        set_u16(data, 120, (self.uid >> 16) as u16);
+        set_u16(data, 122, (self.gid >> 16) as u16);
+    }
+}
+
+/// The header of a directory entry. The name of the entry follows the header,
+/// and the entry is padded to a multiple of 4 bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectoryEntry {
+    pub inode: u32,
+    pub rec_len: usize,
+    pub name_len: usize,
+    pub file_type: u8,
+}
+
+impl DirectoryEntry {
+    /// The size of the header of a directory entry.
+    pub const HEADER_SIZE: usize = 8;
+
+    /// The value stored in the record length of an entry spanning a whole block
+    /// of 64 KiB, whose length does not fit in 16 bits. Like Linux, a record
+    /// length of 0 is also read as 64 KiB.
+    pub const MAX_REC_LEN: u16 = 65535;
+
+    /// Parse the directory entry at the given offset of a directory block, or
+    /// return `None` if the entry is malformed.
+    #[must_use]
+    pub fn parse(block: &[u8], offset: usize) -> Option<Self> {
+        if offset + Self::HEADER_SIZE > block.len() {
+            return None;
+        }
+
+        let entry = Self {
+            inode: get_u32(block, offset),
+            rec_len: Self::rec_len(block, offset),
+            name_len: usize::from(block[offset + 6]),
+            file_type: block[offset + 7],
+        };
+
+        let valid = entry.rec_len >= Self::HEADER_SIZE
+            && entry.rec_len % 4 == 0
+            && offset + entry.rec_len <= block.len()
+            && Self::HEADER_SIZE + entry.name_len <= entry.rec_len;
+        valid.then_some(entry)
+    }
+
+    /// Returns the record length of the entry at the given offset of a
+    /// directory block.
+    #[must_use]
+    pub fn rec_len(block: &[u8], offset: usize) -> usize {
+        match get_u16(block, offset + 4) {
+            0 | Self::MAX_REC_LEN => 65536,
+            len => usize::from(len),
+        }
+    }
+
+    /// Sets the record length of the entry at the given offset of a directory
+    /// block.
+    pub fn set_rec_len(block: &mut [u8], offset: usize, len: usize) {
+        let len = u16::try_from(len).unwrap_or(Self::MAX_REC_LEN);
+        set_u16(block, offset + 4, len);
+    }
+
+    /// Write a directory entry at the given offset of a directory block.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write(block: &mut [u8], offset: usize, entry: &Self, name: &[u8]) {
+        set_u32(block, offset, entry.inode);
+        Self::set_rec_len(block, offset, entry.rec_len);
+        block[offset + 6] = entry.name_len as u8;
+        block[offset + 7] = entry.file_type;
+        block[offset + Self::HEADER_SIZE..offset + Self::HEADER_SIZE + name.len()]
+            .copy_from_slice(name);
+    }
+
+    /// Returns the name of this entry, stored in the given directory block at
+    /// the given offset.
+    #[must_use]
+    pub fn name<'a>(&self, block: &'a [u8], offset: usize) -> &'a [u8] {
+        let start = offset + Self::HEADER_SIZE;
+        &block[start..start + self.name_len]
+    }
+
+    /// Returns the minimal size of an entry with a name of the given length.
+    #[must_use]
+    pub const fn size(name_len: usize) -> usize {
+        (Self::HEADER_SIZE + name_len + 3) & !3
+    }
+}
@@ -0,0 +1,177 @@
+//! Regular files and symbolic links of an ext2 filesystem. Their content is read
+//! and written through the buffer cache, block by block: holes read as zeroes,
+//! and are filled with newly allocated blocks when written.
+use super::{disk, inode::info, vfs_now, Error};
+use crate::vfs::{
+    self,
+    file::{File, Offset, ReadError, SeekError, Whence, WriteError},
+};
+
+/// Operations that can be performed on an opened regular file or symbolic link.
+pub static FILE_OPS: vfs::file::FileOperation = vfs::file::FileOperation {
+    write,
+    read,
+    seek,
+    poll: vfs::poll::always_ready,
+    ioctl: vfs::file::no_ioctl,
+    mmap: vfs::file::no_mmap,
+};
+
+/// Read from the file at the given offset into the given buffer, and return the
+/// number of bytes read. Reading stops at the end of the file.
+///
+/// # Errors
+/// Returns `ReadError::IoError` if a block of the file could not be read, or if
+/// the file is corrupted.
+fn read(file: &File, buf: &mut [u8], offset: Offset) -> Result<usize, ReadError> {
+    let inode = file
+        .dentry
+        .as_ref()
+        .expect("Open file without dentry")
+        .inode();
+    let info = info(inode);
+    let size = inode.metadata.lock().size;
+    let len = core::cmp::min(buf.len(), size.saturating_sub(offset.0));
+    let mut state = info.lock();
+
+    if info.inline {
+        let mut bytes = [0; disk::BLOCK_POINTERS * 4];
+        for (chunk, pointer) in bytes.chunks_exact_mut(4).zip(state.block) {
+            chunk.copy_from_slice(&pointer.to_le_bytes());
+        }
+        if offset.0 >= bytes.len() {
+            return Ok(0);
+        }
+        let len = core::cmp::min(len, bytes.len() - offset.0);
+        buf[..len].copy_from_slice(&bytes[offset.0..offset.0 + len]);
+        return Ok(len);
+    }
+
+    let block_size = info.fs.block_size();
+    let mut done = 0;
+    while done < len {
+        let position = offset.0 + done;
+        let start = position % block_size;
+        let count = core::cmp::min(block_size - start, len - done);
+        let chunk = &mut buf[done..done + count];
+
+        match info.map(&mut state, (position / block_size) as u64, false)? {
+            Some(block) => {
+                let buffer = info.fs.block(block)?;
+                chunk.copy_from_slice(&buffer.data()[start..start + count]);
+            }
+            None => chunk.fill(0),
+        }
+        done += count;
+    }
+
+    drop(state);
+    inode.metadata.lock().access_time = vfs_now();
+    Ok(len)
+}
+
+/// Write the given buffer to the file at the given offset, and return the number
+/// of bytes written. The file is extended if the write goes beyond its end, and
+/// the blocks of the holes written are allocated. Symbolic links cannot be
+/// written, since their target is set when they are created.
+///
+/// # Errors
+/// - `WriteError::NotImplemented`: The file is a symbolic link.
+/// - `WriteError::NoSpace`: The file would exceed its maximal size, or there
+///   is no free block left.
+/// - `WriteError::IoError`: A block of the file could not be read, or the file
+///   is corrupted.
+fn write(file: &File, buf: &[u8], offset: Offset) -> Result<usize, WriteError> {
+    let inode = file
+        .dentry
+        .as_ref()
+        .expect("Open file without dentry")
+        .inode();
+    let info = info(inode);
+    if info.format != disk::S_IFREG {
+        return Err(WriteError::NotImplemented);
+    }
+
+    let end = offset.0.checked_add(buf.len()).ok_or(WriteError::NoSpace)?;
+    if end as u64 > info.fs.max_file_size() {
+        return Err(WriteError::NoSpace);
+    }
+
+    let block_size = info.fs.block_size();
+    let mut state = info.lock();
+    let mut done = 0;
+    let result = loop {
+        if done == buf.len() {
+            break Ok(());
+        }
+
+        let position = offset.0 + done;
+        let start = position % block_size;
+        let count = core::cmp::min(block_size - start, buf.len() - done);
+        let block = match info.map(&mut state, (position / block_size) as u64, true) {
+            Ok(block) => block.expect("Hole after a block allocation"),
+            Err(error) => break Err(error),
+        };
+        let buffer = match info.fs.block(block) {
+            Ok(buffer) => buffer,
+            Err(error) => break Err(error),
+        };
+
+        buffer.data()[start..start + count].copy_from_slice(&buf[done..done + count]);
+        buffer.mark_dirty();
+        done += count;
+    };
+
+    {
+        let mut metadata = inode.metadata.lock();
+        metadata.size = core::cmp::max(metadata.size, offset.0 + done);
+        metadata.modification_time = vfs_now();
+        metadata.change_time = vfs_now();
+    }
+    info.store(inode, &state)?;
+
+    match result {
+        Err(error) if done == 0 => Err(error.into()),
+        _ => Ok(done),
+    }
+}
+
+/// Seek into the file and return the new offset. Seeking beyond the end of the
+/// file is allowed, and a write there creates a hole.
+///
+/// # Errors
+/// Returns `SeekError::Overflow` if the new offset overflows.
+fn seek(file: &File, offset: isize, whence: Whence) -> Result<Offset, SeekError> {
+    let base = match whence {
+        Whence::Start => 0,
+        Whence::Current => file.state.lock().offset.0,
+        Whence::End => {
+            file.dentry
+                .as_ref()
+                .expect("Open file without dentry")
+                .inode()
+                .metadata
+                .lock()
+                .size
+        }
+    };
+
+    base.checked_add_signed(offset)
+        .map(Offset)
+        .ok_or(SeekError::Overflow)
+}
+
+impl From<Error> for ReadError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
+
+impl From<Error> for WriteError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
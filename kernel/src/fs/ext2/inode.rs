@@ -0,0 +1,693 @@
+//! Inodes of an ext2 filesystem. The metadata of an inode shared with the VFS,
+//! like its size, mode or link count, is kept in the VFS inode, while the block
+//! pointers are kept in an [`InodeInfo`] attached to it. Both are written back
+//! together into the inode table when the inode is written.
+//!
+//! The data blocks of a file are referenced by the twelve direct pointers of its
+//! inode, then by a single, a double and a triple indirect block. An inode whose
+//! last link is removed is only released when the VFS drops it, since it may
+//! still be used by an open file until then.
+use super::{
+    dir,
+    disk::{self, group},
+    ext2, file, now, time, vfs_now, Error, Ext2,
+};
+use crate::{
+    device::{self, block::cache::Buffer, Device},
+    fs::ramfs::interface::SPECIAL_FILE_OPS,
+    user::task::mutex::{Mutex, MutexGuard},
+    vfs::{
+        self,
+        inode::{InodeCreateInfo, InodeMetadata, Kind},
+        mount::{ReadInodeError, Super, WriteInodeError},
+    },
+};
+
+/// Operations that can be performed on a file inode.
+pub static INODE_FILE_OPS: vfs::inode::FileOperation = vfs::inode::FileOperation { truncate };
+
+/// The ext2 specific data of an inode.
+pub struct InodeInfo {
+    /// The filesystem of the inode.
+    pub fs: Arc<Ext2>,
+
+    /// The number of the inode.
+    pub number: u32,
+
+    /// The file type bits of the mode of the inode.
+    pub format: u16,
+
+    /// Whether the content of the inode is stored directly in its block pointers
+    /// instead of in data blocks. This is the case of short symbolic links.
+    pub inline: bool,
+
+    /// The part of the inode that can be modified by the driver.
+    state: Mutex<InodeState>,
+}
+
+/// The part of an inode that is not shared with the VFS, protected by a sleeping
+/// mutex since most operations on it need to read blocks from the device.
+pub struct InodeState {
+    /// The block pointers of the inode, or its content if it is inline.
+    pub block: [u32; disk::BLOCK_POINTERS],
+
+    /// The number of 512-byte sectors used by the inode, including the indirect
+    /// blocks and the extended attribute block.
+    pub sectors: u32,
+
+    /// The flags of the inode.
+    pub flags: u32,
+
+    /// Whether the last link to the inode has been removed, and the inode must be
+    /// released when it is dropped.
+    pub deleted: bool,
+}
+
+impl InodeInfo {
+    /// Lock the state of the inode.
+    pub fn lock(&self) -> MutexGuard<'_, InodeState> {
+        self.state.lock()
+    }
+
+    /// Returns whether the inode content is stored in data blocks, meaning that
+    /// its block pointers must be followed.
+    #[must_use]
+    pub fn has_blocks(&self) -> bool {
+        !self.inline && matches!(self.format, disk::S_IFREG | disk::S_IFDIR | disk::S_IFLNK)
+    }
+
+    /// Returns the block of the device containing the block `index` of the inode,
+    /// or `None` if there is a hole at this position. If `create` is true, holes
+    /// are filled with newly allocated zeroed blocks, so `None` is never returned.
+    ///
+    /// # Errors
+    /// - `Error::NoSpace`: The index is beyond the maximal size of a file, or a
+    ///   block could not be allocated.
+    /// - `Error::Corrupted`: A block pointer is outside of the filesystem.
+    /// - `Error::IoError`: An indirect block could not be read.
+    pub fn map(
+        &self,
+        state: &mut InodeState,
+        index: u64,
+        create: bool,
+    ) -> Result<Option<u32>, Error> {
+        let Some((slot, depth, offsets)) = self.fs.path(index) else {
+            return if create {
+                Err(Error::NoSpace)
+            } else {
+                Ok(None)
+            };
+        };
+
+        let mut block = state.block[slot];
+        if block == 0 {
+            if !create {
+                return Ok(None);
+            }
+            let goal = match slot.checked_sub(1).map(|previous| state.block[previous]) {
+                None | Some(0) => self.fs.group_start(self.number),
+                Some(previous) => previous + 1,
+            };
+            block = self.allocate(state, goal)?;
+            state.block[slot] = block;
+        }
+
+        for &offset in &offsets[..depth] {
+            let buffer = self.fs.checked_block(block)?;
+            let next = disk::get_u32(&buffer.data(), offset * 4);
+            block = if next != 0 {
+                next
+            } else if create {
+                let next = self.allocate(state, block + 1)?;
+                disk::set_u32(&mut buffer.data(), offset * 4, next);
+                buffer.mark_dirty();
+                next
+            } else {
+                return Ok(None);
+            };
+        }
+
+        if block >= self.fs.blocks_count {
+            return Err(Error::Corrupted);
+        }
+        Ok(Some(block))
+    }
+
+    /// Release all the blocks of the inode after the `keep` first ones, and clear
+    /// the pointers to them.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: A block pointer is invalid or the block is already free.
+    /// - `Error::IoError`: An indirect block or a bitmap could not be read.
+    pub fn truncate_blocks(&self, state: &mut InodeState, keep: u64) -> Result<(), Error> {
+        let per = self.fs.pointers_per_block();
+        let mut first = disk::DIRECT_BLOCKS as u64;
+        let mut span = 1;
+
+        for slot in 0..disk::BLOCK_POINTERS {
+            let depth = slot.saturating_sub(disk::DIRECT_BLOCKS - 1);
+            let start = if depth == 0 { slot as u64 } else { first };
+            let block = state.block[slot];
+            if block != 0 && self.free_tree(state, block, depth, keep.saturating_sub(start))? {
+                state.block[slot] = 0;
+            }
+            if depth > 0 {
+                first += span * per;
+                span *= per;
+            }
+        }
+        Ok(())
+    }
+
+    /// Release the blocks of the tree of the given depth rooted at `block`, whose
+    /// index in the tree is at least `keep`. Returns whether the whole tree was
+    /// released, including its root.
+    #[allow(clippy::cast_possible_truncation)]
+    fn free_tree(
+        &self,
+        state: &mut InodeState,
+        block: u32,
+        depth: usize,
+        keep: u64,
+    ) -> Result<bool, Error> {
+        if depth > 0 {
+            let per = self.fs.pointers_per_block();
+            let span = per.pow(depth as u32 - 1);
+            let buffer = self.fs.checked_block(block)?;
+
+            for i in keep / span..per {
+                let offset = i as usize * 4;
+                let child = disk::get_u32(&buffer.data(), offset);
+                let child_keep = keep.saturating_sub(i * span);
+                if child != 0 && self.free_tree(state, child, depth - 1, child_keep)? {
+                    disk::set_u32(&mut buffer.data(), offset, 0);
+                    buffer.mark_dirty();
+                }
+            }
+        }
+
+        if keep > 0 {
+            return Ok(false);
+        }
+        self.fs.free_block(block)?;
+        state.sectors = state.sectors.saturating_sub(self.fs.sectors_per_block());
+        Ok(true)
+    }
+
+    /// Allocate a zeroed block for the inode, as close as possible to the `goal`
+    /// block, and account it in the number of sectors used by the inode.
+    fn allocate(&self, state: &mut InodeState, goal: u32) -> Result<u32, Error> {
+        let sectors = state
+            .sectors
+            .checked_add(self.fs.sectors_per_block())
+            .ok_or(Error::NoSpace)?;
+        let block = self.fs.alloc_block(goal)?;
+        if let Err(error) = self.fs.zeroed_block(block) {
+            _ = self.fs.free_block(block);
+            return Err(error);
+        }
+        state.sectors = sectors;
+        Ok(block)
+    }
+
+    /// Write the given VFS inode and its state into the inode table.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the block of the inode table could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn store(&self, inode: &vfs::inode::Inode, state: &InodeState) -> Result<(), Error> {
+        let (buffer, offset) = self.fs.inode_location(self.number)?;
+        let (mode, size, links, accessed, modified, changed) = {
+            let metadata = inode.metadata.lock();
+            (
+                metadata.mode,
+                metadata.size as u64,
+                core::cmp::min(metadata.links, u64::from(u16::MAX)) as u16,
+                u64::from(metadata.access_time) as u32,
+                u64::from(metadata.modification_time) as u32,
+                u64::from(metadata.change_time) as u32,
+            )
+        };
+
+        let mut data = buffer.data();
+        let raw = &mut data[offset..offset + self.fs.inode_size];
+        let mut disk = disk::Inode::parse(raw);
+        disk.mode = self.format | (mode & 0o7777);
+        disk.size = size;
+        disk.links_count = links;
+        disk.atime = accessed;
+        disk.mtime = modified;
+        disk.ctime = changed;
+        disk.dtime = 0;
+        disk.blocks = state.sectors;
+        disk.flags = state.flags;
+        disk.block = state.block;
+        disk.write(raw);
+        drop(data);
+        buffer.mark_dirty();
+        Ok(())
+    }
+
+    /// Release the inode: its blocks, its extended attribute block and its bit in
+    /// the inode bitmap. The inode is kept in the inode table with a deletion
+    /// time, like Linux does.
+    fn delete(&self) -> Result<(), Error> {
+        let mut state = self.state.lock();
+        if self.has_blocks() {
+            self.truncate_blocks(&mut state, 0)?;
+        }
+
+        let (buffer, offset) = self.fs.inode_location(self.number)?;
+        let acl = disk::get_u32(&buffer.data(), offset + 104);
+        if acl != 0 {
+            self.fs.release_xattr(acl)?;
+        }
+
+        let mut data = buffer.data();
+        let raw = &mut data[offset..offset + self.fs.inode_size];
+        let mut disk = disk::Inode::parse(raw);
+        disk.links_count = 0;
+        disk.dtime = now();
+        disk.size = 0;
+        disk.blocks = 0;
+        disk.block = [0; disk::BLOCK_POINTERS];
+        disk.file_acl = 0;
+        disk.write(raw);
+        drop(data);
+        buffer.mark_dirty();
+
+        self.fs
+            .free_inode(self.number, self.format == disk::S_IFDIR)
+    }
+}
+
+impl Drop for InodeInfo {
+    fn drop(&mut self) {
+        if self.state.lock().deleted {
+            if let Err(error) = self.delete() {
+                log::error!("ext2: failed to release inode {}: {:?}", self.number, error);
+            }
+        }
+    }
+}
+
+impl Ext2 {
+    /// Returns the buffer of the inode table containing the given inode, and the
+    /// offset of the inode in that buffer.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The inode number is outside of the filesystem.
+    /// - `Error::IoError`: The group descriptor or the inode table could not be read.
+    pub fn inode_location(&self, inode: u32) -> Result<(Arc<Buffer>, usize), Error> {
+        if inode == 0 || inode > self.inodes_count {
+            return Err(Error::Corrupted);
+        }
+
+        let group = (inode - 1) / self.inodes_per_group;
+        let index = (inode - 1) % self.inodes_per_group;
+        let (descriptor, offset) = self.group_descriptor(group)?;
+        let table = disk::get_u32(&descriptor.data(), offset + group::INODE_TABLE);
+
+        let position = index as usize * self.inode_size;
+        let block = u32::try_from(position / self.block_size)
+            .ok()
+            .and_then(|block| table.checked_add(block))
+            .ok_or(Error::Corrupted)?;
+        Ok((self.checked_block(block)?, position % self.block_size))
+    }
+
+    /// Read the given inode from the inode table.
+    ///
+    /// # Errors
+    /// See [`Ext2::inode_location`].
+    pub fn read_disk_inode(&self, inode: u32) -> Result<disk::Inode, Error> {
+        let (buffer, offset) = self.inode_location(inode)?;
+        let data = buffer.data();
+        Ok(disk::Inode::parse(&data[offset..offset + self.inode_size]))
+    }
+
+    /// Allocate a new inode near the directory `parent` and initialize it in the
+    /// inode table with the given mode, link count, size and block pointers, the
+    /// blocks pointed to using the given number of sectors. Returns the number of
+    /// the new inode.
+    ///
+    /// # Errors
+    /// - `Error::NoSpace`: There is no free inode left.
+    /// - `Error::IoError`: A bitmap or the inode table could not be read.
+    pub fn new_inode(
+        &self,
+        parent: u32,
+        mode: u16,
+        links: u16,
+        size: u64,
+        block: [u32; disk::BLOCK_POINTERS],
+        sectors: u32,
+    ) -> Result<u32, Error> {
+        let directory = mode & disk::S_IFMT == disk::S_IFDIR;
+        let number = self.alloc_inode((parent - 1) / self.inodes_per_group, directory)?;
+        let (buffer, offset) = match self.inode_location(number) {
+            Ok(location) => location,
+            Err(error) => {
+                _ = self.free_inode(number, directory);
+                return Err(error);
+            }
+        };
+
+        let time = now();
+        let inode = disk::Inode {
+            mode,
+            size,
+            atime: time,
+            ctime: time,
+            mtime: time,
+            links_count: links,
+            blocks: sectors,
+            block,
+            generation: time ^ number,
+            ..Default::default()
+        };
+
+        let mut data = buffer.data();
+        let raw = &mut data[offset..offset + self.inode_size];
+        raw.fill(0);
+        inode.write(raw);
+
+        // Large inodes have their extra fields initialized like `mkfs.ext2` does.
+        #[allow(clippy::cast_possible_truncation)]
+        if self.inode_size > disk::GOOD_OLD_INODE_SIZE {
+            let extra = core::cmp::min(32, self.inode_size - disk::GOOD_OLD_INODE_SIZE);
+            disk::set_u16(raw, disk::GOOD_OLD_INODE_SIZE, extra as u16);
+        }
+        drop(data);
+        buffer.mark_dirty();
+        Ok(number)
+    }
+
+    /// Drop a reference to the given extended attribute block, and release it
+    /// if it was the last one.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The block is not an extended attribute block.
+    /// - `Error::IoError`: The block could not be read.
+    fn release_xattr(&self, block: u32) -> Result<(), Error> {
+        let buffer = self.checked_block(block)?;
+        let mut data = buffer.data();
+        if disk::get_u32(&data, 0) != disk::XATTR_MAGIC {
+            return Err(Error::Corrupted);
+        }
+
+        let references = disk::get_u32(&data, 4);
+        if references > 1 {
+            disk::set_u32(&mut data, 4, references - 1);
+            drop(data);
+            buffer.mark_dirty();
+            return Ok(());
+        }
+        drop(data);
+        self.free_block(block)
+    }
+
+    /// Returns the buffer containing the given block, after checking that the
+    /// block is inside the filesystem.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The block is outside of the filesystem.
+    /// - `Error::IoError`: The block could not be read.
+    pub fn checked_block(&self, block: u32) -> Result<Arc<Buffer>, Error> {
+        if block < self.first_data_block || block >= self.blocks_count {
+            return Err(Error::Corrupted);
+        }
+        self.block(block)
+    }
+
+    /// Returns the first block of the group containing the given inode, used as
+    /// the allocation goal for the first block of the inode.
+    #[must_use]
+    pub fn group_start(&self, inode: u32) -> u32 {
+        self.first_data_block + (inode - 1) / self.inodes_per_group * self.blocks_per_group
+    }
+
+    /// Returns the number of block pointers in an indirect block.
+    #[must_use]
+    pub fn pointers_per_block(&self) -> u64 {
+        self.block_size as u64 / 4
+    }
+
+    /// Returns the number of 512-byte sectors in a block, the unit in which the
+    /// blocks used by an inode are counted.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn sectors_per_block(&self) -> u32 {
+        (self.block_size / 512) as u32
+    }
+
+    /// Returns the maximal size of a regular file, in bytes. It is limited by the
+    /// number of blocks reachable from an inode, and by the 32 bits size field
+    /// when the filesystem does not support large files.
+    #[must_use]
+    pub fn max_file_size(&self) -> u64 {
+        let per = self.pointers_per_block();
+        let blocks = disk::DIRECT_BLOCKS as u64 + per + per * per + per * per * per;
+        let limit = if self.large_file {
+            u64::from(u32::MAX) * 512
+        } else {
+            u64::from(i32::MAX as u32)
+        };
+        core::cmp::min(blocks * self.block_size as u64, limit)
+    }
+
+    /// Returns the path to the block `index` of an inode: the slot of the block
+    /// pointers of the inode where the path starts, the number of indirect
+    /// blocks to follow, and the offset of the pointer to follow in each of
+    /// them. Returns `None` if the index is beyond the triple indirect block.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn path(&self, index: u64) -> Option<(usize, usize, [usize; 3])> {
+        if index < disk::DIRECT_BLOCKS as u64 {
+            return Some((index as usize, 0, [0; 3]));
+        }
+
+        let per = self.pointers_per_block();
+        let mut index = index - disk::DIRECT_BLOCKS as u64;
+        let mut span = 1;
+        for depth in 1..=3 {
+            span *= per;
+            if index < span {
+                let mut offsets = [0; 3];
+                for offset in offsets[..depth].iter_mut().rev() {
+                    *offset = (index % per) as usize;
+                    index /= per;
+                }
+                return Some((disk::DIRECT_BLOCKS - 1 + depth, depth, offsets));
+            }
+            index -= span;
+        }
+        None
+    }
+}
+
+/// Returns the ext2 data of the given VFS inode.
+///
+/// # Panics
+/// Panics if the inode is not an ext2 inode.
+#[must_use]
+pub fn info(inode: &vfs::inode::Inode) -> &InodeInfo {
+    inode
+        .data
+        .downcast_ref::<InodeInfo>()
+        .expect("Inode is not an ext2 inode")
+}
+
+/// Decode the device number stored in the block pointers of a device inode. The
+/// old 16 bits encoding is used when it fits, and the new 32 bits encoding is
+/// stored in the second pointer otherwise, like on Linux.
+#[must_use]
+pub fn decode_device(block: &[u32; disk::BLOCK_POINTERS]) -> device::Identifier {
+    if block[0] == 0 {
+        device::Identifier {
+            major: (block[1] & 0xFFF00) >> 8,
+            minor: (block[1] & 0xFF) | ((block[1] >> 12) & 0xFFF00),
+        }
+    } else {
+        device::Identifier {
+            major: (block[0] >> 8) & 0xFF,
+            minor: block[0] & 0xFF,
+        }
+    }
+}
+
+/// Encode a device number into the block pointers of a device inode. See
+/// [`decode_device`] for the encoding.
+#[must_use]
+pub fn encode_device(id: device::Identifier) -> [u32; disk::BLOCK_POINTERS] {
+    let mut block = [0; disk::BLOCK_POINTERS];
+    if id.major < 256 && id.minor < 256 {
+        block[0] = (id.major << 8) | id.minor;
+    } else {
+        block[1] = (id.minor & 0xFF) | (id.major << 8) | ((id.minor & !0xFF) << 12);
+    }
+    block
+}
+
+/// Read the inode with the given identifier from the inode table and create its
+/// VFS inode. The kind and the operations of the VFS inode depend on the type of
+/// the inode: FIFOs and sockets are exposed as regular files that cannot be read
+/// or written, since the VFS has no named pipes or sockets.
+///
+/// # Errors
+/// Returns `ReadInodeError::IoError` if the inode could not be read, or if it is
+/// not in use or corrupted: an identifier always comes from a directory entry,
+/// so such an inode means that the filesystem is inconsistent.
+pub fn read_inode(
+    superblock: &Super,
+    id: vfs::inode::Identifier,
+) -> Result<Arc<vfs::inode::Inode>, ReadInodeError> {
+    let fs = ext2(superblock);
+    let number = u32::try_from(id.0).map_err(|_| ReadInodeError::DoesNotExist)?;
+    let disk = fs
+        .read_disk_inode(number)
+        .map_err(|_| ReadInodeError::IoError)?;
+
+    if disk.links_count == 0 {
+        log::warn!("ext2: inode {} is referenced but not in use", number);
+        return Err(ReadInodeError::IoError);
+    }
+
+    let format = disk.mode & disk::S_IFMT;
+    let file = vfs::file::Operation::File(&file::FILE_OPS);
+    let special = vfs::file::Operation::File(&SPECIAL_FILE_OPS);
+    let regular = vfs::inode::Operation::File(&INODE_FILE_OPS);
+    let (kind, inode_ops, file_ops) = match format {
+        disk::S_IFDIR => (
+            Kind::Directory,
+            vfs::inode::Operation::Directory(&dir::INODE_OPS),
+            vfs::file::Operation::Directory(&dir::FILE_OPS),
+        ),
+        disk::S_IFREG => (Kind::File, regular, file),
+        disk::S_IFLNK => (Kind::Symlink, regular, file),
+        disk::S_IFBLK => (
+            Kind::BlockDevice(decode_device(&disk.block)),
+            regular,
+            vfs::file::Operation::File(&device::block::file::FILE_OPS),
+        ),
+        disk::S_IFCHR => (
+            Kind::CharDevice(decode_device(&disk.block)),
+            regular,
+            vfs::file::Operation::File(&device::character::file::FILE_OPS),
+        ),
+        disk::S_IFIFO | disk::S_IFSOCK => (Kind::File, regular, special),
+        _ => {
+            log::warn!(
+                "ext2: inode {} has an invalid mode {:#o}",
+                number,
+                disk.mode
+            );
+            return Err(ReadInodeError::IoError);
+        }
+    };
+
+    // A symbolic link is stored in its block pointers when it does not use any
+    // block, except its extended attribute block.
+    let acl_sectors = match disk.file_acl {
+        0 => 0,
+        _ => fs.sectors_per_block(),
+    };
+    let inline = format == disk::S_IFLNK && disk.blocks == acl_sectors;
+
+    #[allow(clippy::cast_possible_truncation)]
+    let metadata = InodeMetadata {
+        modification_time: time(disk.mtime),
+        access_time: time(disk.atime),
+        change_time: time(disk.ctime),
+        links: u64::from(disk.links_count),
+        size: disk.size as usize,
+        mode: disk.mode & 0o7777,
+    };
+
+    let info = InodeInfo {
+        fs: Arc::clone(fs),
+        number,
+        format,
+        inline,
+        state: Mutex::new(InodeState {
+            block: disk.block,
+            sectors: disk.blocks,
+            flags: disk.flags,
+            deleted: false,
+        }),
+    };
+
+    Ok(Arc::new(vfs::inode::Inode::new(
+        fs.superblock(),
+        InodeCreateInfo {
+            id,
+            device: Device::Block(fs.device),
+            kind,
+            inode_ops,
+            file_ops,
+            metadata,
+            data: Box::new(info),
+        },
+    )))
+}
+
+/// Write the given inode into the inode table.
+///
+/// # Errors
+/// Returns `WriteInodeError::IoError` if the inode table could not be read.
+pub fn write_inode(inode: &vfs::inode::Inode) -> Result<(), WriteInodeError> {
+    let info = info(inode);
+    let state = info.lock();
+    info.store(inode, &state)
+        .map_err(|_| WriteInodeError::IoError)
+}
+
+/// Truncate the inode to the given size. The blocks beyond the new size are
+/// released, and the end of the last block is zeroed so that the file reads as
+/// zeroes if it is extended again. Extending a file does not allocate any block:
+/// the new part of the file is a hole. Inodes that do not store their content
+/// in data blocks, like device nodes or short symbolic links, are left as is.
+///
+/// # Errors
+/// - `TruncateError::NoSpace`: The size exceeds the maximal size of a file.
+/// - `TruncateError::IoError`: The inode or one of its blocks could not be read.
+fn truncate(inode: &vfs::inode::Inode, size: usize) -> Result<usize, vfs::inode::TruncateError> {
+    let info = info(inode);
+    if info.format != disk::S_IFREG {
+        return Ok(inode.metadata.lock().size);
+    }
+    if size as u64 > info.fs.max_file_size() {
+        return Err(vfs::inode::TruncateError::NoSpace);
+    }
+
+    let mut state = info.lock();
+    let block_size = info.fs.block_size();
+    let blocks = ((size + block_size - 1) / block_size) as u64;
+    info.truncate_blocks(&mut state, blocks)?;
+
+    if size % block_size != 0 {
+        if let Some(block) = info.map(&mut state, blocks - 1, false)? {
+            let buffer = info.fs.checked_block(block)?;
+            buffer.data()[size % block_size..].fill(0);
+            buffer.mark_dirty();
+        }
+    }
+
+    {
+        let mut metadata = inode.metadata.lock();
+        metadata.modification_time = vfs_now();
+        metadata.change_time = vfs_now();
+        metadata.size = size;
+    }
+    info.store(inode, &state)?;
+    Ok(size)
+}
+
+impl From<Error> for vfs::inode::TruncateError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! The second extended filesystem. The driver reads and writes the filesystem
+//! through the buffer cache, using buffers of the size of a block of the
+//! filesystem: metadata is modified in place in the cached blocks, and reaches
+//! the device when the buffers are written back.
+//!
+//! Only the features of the original ext2 are supported, with the `filetype`,
+//! `sparse_super` and `large_file` features enabled by default by `mkfs.ext2`.
+//! Backup superblocks and group descriptors are never updated, like on Linux,
+//! and the hashed directory indexes are dropped when a directory is modified.
+use crate::{
+    device::{
+        self,
+        block::{cache, IoError},
+        Device, Identifier,
+    },
+    time::{units::Second, unix::UnixTime},
+    user::task::mutex::Mutex,
+    vfs::{
+        self,
+        mount::{Super, SuperCreationInfo},
+    },
+};
+use alloc::sync::Weak;
+use disk::Superblock;
+
+pub mod bitmap;
+pub mod dir;
+pub mod disk;
+pub mod file;
+pub mod inode;
+
+/// Operations that can be performed on the filesystem.
+pub static FS_OPS: vfs::fs::Operation = vfs::fs::Operation { read_super };
+
+/// Operations that can be performed on the superblock.
+pub static SUPER_OPS: vfs::mount::Operation = vfs::mount::Operation {
+    write_super,
+    write_inode: inode::write_inode,
+    read_inode: inode::read_inode,
+};
+
+/// A mounted ext2 filesystem.
+pub struct Ext2 {
+    /// The block device on which the filesystem is stored.
+    device: Identifier,
+
+    /// The size of a block, in bytes.
+    block_size: usize,
+
+    /// The size of an inode in the inode tables, in bytes.
+    inode_size: usize,
+
+    /// The total number of inodes and blocks of the filesystem.
+    inodes_count: u32,
+    blocks_count: u32,
+
+    /// The number of inodes and blocks in each group.
+    inodes_per_group: u32,
+    blocks_per_group: u32,
+
+    /// The first block of the first group, which is 1 with 1 KiB blocks since
+    /// the block 0 contains the boot sector and the superblock.
+    first_data_block: u32,
+
+    /// The first inode that is not reserved for the filesystem.
+    first_inode: u32,
+
+    /// The number of groups of the filesystem.
+    groups: u32,
+
+    /// Whether directory entries store the type of their inode.
+    filetype: bool,
+
+    /// Whether regular files can be larger than 4 GiB.
+    large_file: bool,
+
+    /// The VFS superblock of the filesystem, needed to create VFS inodes.
+    superblock: Once<Weak<Super>>,
+
+    /// Serializes the allocations and releases of blocks and inodes, which
+    /// update the bitmaps and the free counters of the groups and superblock.
+    allocation: Mutex<()>,
+}
+
+impl Ext2 {
+    /// Returns the size of a block of the filesystem, in bytes.
+    #[must_use]
+    pub const fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the buffer containing the given block of the filesystem.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the block could not be read.
+    pub fn block(&self, block: u32) -> Result<Arc<cache::Buffer>, Error> {
+        Ok(cache::read(self.device, u64::from(block), self.block_size)?)
+    }
+
+    /// Returns the buffer containing the given block of the filesystem, filled
+    /// with zeroes and marked dirty. This is used for newly allocated blocks.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the block is out of the device.
+    pub fn zeroed_block(&self, block: u32) -> Result<Arc<cache::Buffer>, Error> {
+        let buffer = cache::get_zeroed(self.device, u64::from(block), self.block_size)?;
+        buffer.mark_dirty();
+        Ok(buffer)
+    }
+
+    /// Returns the buffer containing the superblock and the offset of the
+    /// superblock in that buffer.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the block could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn superblock_buffer(&self) -> Result<(Arc<cache::Buffer>, usize), Error> {
+        let block = disk::SUPERBLOCK_OFFSET / self.block_size;
+        let buffer = self.block(block as u32)?;
+        Ok((buffer, disk::SUPERBLOCK_OFFSET % self.block_size))
+    }
+
+    /// Returns the buffer containing the descriptor of the given group and the
+    /// offset of the descriptor in that buffer. The group descriptor table
+    /// starts on the block following the superblock.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the block could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn group_descriptor(&self, group: u32) -> Result<(Arc<cache::Buffer>, usize), Error> {
+        let position = group as usize * disk::GROUP_DESCRIPTOR_SIZE;
+        let block = self.first_data_block + 1 + (position / self.block_size) as u32;
+        Ok((self.block(block)?, position % self.block_size))
+    }
+
+    /// Returns the VFS superblock of the filesystem.
+    #[must_use]
+    pub fn superblock(&self) -> Weak<Super> {
+        self.superblock.get().cloned().unwrap_or_default()
+    }
+}
+
+impl Drop for Ext2 {
+    /// Mark the filesystem as cleanly unmounted. This happens once all the inodes
+    /// of the filesystem have been written back and released.
+    fn drop(&mut self) {
+        if let Ok((buffer, offset)) = self.superblock_buffer() {
+            let mut data = buffer.data();
+            let state = disk::get_u16(&data, offset + Superblock::STATE);
+            disk::set_u16(
+                &mut data,
+                offset + Superblock::STATE,
+                state | disk::STATE_VALID,
+            );
+            disk::set_u32(&mut data, offset + Superblock::WTIME, now());
+            drop(data);
+            buffer.mark_dirty();
+        }
+    }
+}
+
+/// The errors that can occur inside the driver. They are converted to the errors
+/// of the VFS operations at the boundary of the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// There is no free block or inode left, or the file is too large.
+    NoSpace,
+
+    /// A structure of the filesystem is invalid.
+    Corrupted,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
+}
+
+impl From<IoError> for Error {
+    fn from(_: IoError) -> Self {
+        Error::IoError
+    }
+}
+
+/// Register the ext2 filesystem into the VFS.
+pub fn register() {
+    vfs::fs::register(vfs::fs::Filesystem::new("ext2", &FS_OPS, Box::new(())));
+}
+
+/// Returns the ext2 filesystem of the given VFS superblock.
+///
+/// # Panics
+/// Panics if the superblock is not an ext2 superblock.
+#[must_use]
+pub fn ext2(superblock: &Super) -> &Arc<Ext2> {
+    superblock
+        .data()
+        .downcast_ref::<Arc<Ext2>>()
+        .expect("Superblock is not an ext2 superblock")
+}
+
+/// Returns the current time as stored in the ext2 structures.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn now() -> u32 {
+    u64::from(UnixTime::now()) as u32
+}
+
+/// Returns the current time, truncated to the precision of the timestamps of
+/// ext2, so that the timestamps of the VFS inodes match the stored ones.
+#[must_use]
+pub fn vfs_now() -> UnixTime {
+    time(now())
+}
+
+/// Convert a time stored in the ext2 structures into an Unix time.
+#[must_use]
+pub fn time(time: u32) -> UnixTime {
+    UnixTime(Second(u64::from(time)))
+}
+
+/// Read the superblock of the ext2 filesystem stored on the given block device,
+/// and mark the filesystem as mounted.
+///
+/// # Errors
+/// - `ReadSuperError::InvalidDevice`: The device is not a registered block device.
+/// - `ReadSuperError::IoError`: The superblock could not be read.
+/// - `ReadSuperError::InvalidFileSystem`: The device does not contain an ext2
+///   filesystem, or it uses features not supported by the driver.
+/// - `ReadSuperError::CorruptedFileSystem`: The superblock is inconsistent.
+#[allow(clippy::cast_possible_truncation)]
+fn read_super(
+    _: &vfs::fs::Filesystem,
+    device: Device,
+) -> Result<Arc<Super>, vfs::fs::ReadSuperError> {
+    let Device::Block(id) = device else {
+        return Err(vfs::fs::ReadSuperError::InvalidDevice);
+    };
+    let block_device = device::block::get(id).ok_or(vfs::fs::ReadSuperError::InvalidDevice)?;
+
+    // The superblock is read directly from the device, since the size of the
+    // buffers used with the cache is only known once it has been read.
+    let sector = block_device.sector_size();
+    let first = disk::SUPERBLOCK_OFFSET / sector;
+    let last = (disk::SUPERBLOCK_OFFSET + disk::SUPERBLOCK_SIZE + sector - 1) / sector;
+    let data = device::block::read(&block_device, first as u64, (last - first) * sector)
+        .map_err(|_| vfs::fs::ReadSuperError::IoError)?;
+    let sb = Superblock::parse(&data[disk::SUPERBLOCK_OFFSET - first * sector..]);
+
+    if sb.magic != disk::MAGIC {
+        return Err(vfs::fs::ReadSuperError::InvalidFileSystem);
+    }
+
+    let unsupported = (
+        sb.feature_incompat & !disk::SUPPORTED_INCOMPAT,
+        sb.feature_ro_compat & !disk::SUPPORTED_RO_COMPAT,
+    );
+    if sb.rev_level > 0 && unsupported != (0, 0) {
+        log::warn!(
+            "ext2: unsupported features (incompat {:#x}, ro_compat {:#x})",
+            unsupported.0,
+            unsupported.1
+        );
+        return Err(vfs::fs::ReadSuperError::InvalidFileSystem);
+    }
+
+    let (inode_size, first_inode) = if sb.rev_level == 0 {
+        (disk::GOOD_OLD_INODE_SIZE, disk::GOOD_OLD_FIRST_INODE)
+    } else {
+        (usize::from(sb.inode_size), sb.first_ino)
+    };
+
+    let block_size = 1024usize
+        .checked_shl(sb.log_block_size)
+        .filter(|&size| size <= 65536 && size % sector == 0)
+        .ok_or(vfs::fs::ReadSuperError::InvalidFileSystem)?;
+
+    if sb.blocks_per_group == 0
+        || sb.inodes_per_group == 0
+        || sb.first_data_block >= sb.blocks_count
+        || !inode_size.is_power_of_two()
+        || inode_size < disk::GOOD_OLD_INODE_SIZE
+        || inode_size > block_size
+    {
+        return Err(vfs::fs::ReadSuperError::CorruptedFileSystem);
+    }
+
+    let blocks = sb.blocks_count - sb.first_data_block;
+    let groups = (blocks + sb.blocks_per_group - 1) / sb.blocks_per_group;
+    if u64::from(groups) * u64::from(sb.inodes_per_group) < u64::from(sb.inodes_count) {
+        return Err(vfs::fs::ReadSuperError::CorruptedFileSystem);
+    }
+
+    if sb.state & disk::STATE_VALID == 0 || sb.state & disk::STATE_ERRORS != 0 {
+        log::warn!("ext2: mounting an unchecked filesystem, running e2fsck is recommended");
+    }
+
+    let ext2 = Arc::new(Ext2 {
+        device: id,
+        block_size,
+        inode_size,
+        inodes_count: sb.inodes_count,
+        blocks_count: sb.blocks_count,
+        inodes_per_group: sb.inodes_per_group,
+        blocks_per_group: sb.blocks_per_group,
+        first_data_block: sb.first_data_block,
+        first_inode,
+        groups,
+        filetype: sb.feature_incompat & disk::INCOMPAT_FILETYPE != 0,
+        large_file: sb.feature_ro_compat & disk::RO_COMPAT_LARGE_FILE != 0,
+        superblock: Once::new(),
+        allocation: Mutex::new(()),
+    });
+
+    // Mark the filesystem as not cleanly unmounted until it is unmounted, so
+    // that e2fsck checks it if the system crashes in the meantime.
+    let (buffer, offset) = ext2
+        .superblock_buffer()
+        .map_err(|_| vfs::fs::ReadSuperError::IoError)?;
+    let mut data = buffer.data();
+    let mounts = disk::get_u16(&data, offset + Superblock::MNT_COUNT);
+    disk::set_u16(
+        &mut data,
+        offset + Superblock::MNT_COUNT,
+        mounts.wrapping_add(1),
+    );
+    disk::set_u16(
+        &mut data,
+        offset + Superblock::STATE,
+        sb.state & !disk::STATE_VALID,
+    );
+    disk::set_u32(&mut data, offset + Superblock::MTIME, now());
+    drop(data);
+    buffer.mark_dirty();
+
+    log::info!(
+        "ext2: {} blocks of {} bytes, {} inodes in {} groups",
+        sb.blocks_count,
+        block_size,
+        sb.inodes_count,
+        groups
+    );
+
+    let superblock = Arc::new(Super::new(SuperCreationInfo {
+        device,
+        operation: &SUPER_OPS,
+        root: vfs::inode::Identifier(u64::from(disk::ROOT_INODE)),
+        data: Box::new(Arc::clone(&ext2)),
+    }));
+    ext2.superblock.call_once(|| Arc::downgrade(&superblock));
+    Ok(superblock)
+}
+
+/// Write the superblock of the filesystem. The free counters are updated in the
+/// cached superblock each time a block or an inode is allocated or released, so
+/// only the write time needs to be updated here.
+///
+/// # Errors
+/// Returns `WriteSuperError::IoError` if the superblock could not be read.
+fn write_super(superblock: &Super) -> Result<(), vfs::mount::WriteSuperError> {
+    let (buffer, offset) = ext2(superblock)
+        .superblock_buffer()
+        .map_err(|_| vfs::mount::WriteSuperError::IoError)?;
+    disk::set_u32(&mut buffer.data(), offset + Superblock::WTIME, now());
+    buffer.mark_dirty();
+    Ok(())
+}
@@ -0,0 +1,936 @@
+//! Directories of a FAT filesystem. A directory is an array of 32-byte entries,
+//! stored in a cluster chain, or in a fixed region before the data region for
+//! the root directory of FAT12 and FAT16 filesystems. Each file has a short
+//! entry, holding its metadata and an 8.3 upper case name, preceded by long
+//! name entries when its name cannot be stored in the short entry.
+//!
+//! Names are compared without case, like on Windows, and a file can also be
+//! found by its short name. The short name of a new file is derived from its
+//! long name, with a numeric tail (`~1`, `~2`, ...) when characters were lost.
+//!
+//! All the operations on a directory are serialized by the lock of its state.
+//! When an operation also modifies a child inode, the child is locked after its
+//! parent directory.
+use super::{
+    disk::{self, DirectoryEntry},
+    inode::{self, info, InodeState},
+    timestamp, vfs_now, Error, Fat,
+};
+use crate::{
+    device::Device,
+    time::unix::UnixTime,
+    vfs::{
+        self,
+        inode::{
+            CreateError, Identifier, Inode, LinkError, LookupError, MkdirError, RenameError,
+            RmdirError, UnlinkError,
+        },
+    },
+};
+use alloc::{format, vec};
+use core::ops::ControlFlow;
+
+/// Operations that can be performed on a directory inode.
+pub static INODE_OPS: vfs::inode::DirectoryOperation = vfs::inode::DirectoryOperation {
+    mknod,
+    create,
+    lookup,
+    unlink,
+    mkdir,
+    rmdir,
+    link,
+    rename,
+    symlink,
+};
+
+/// Operations that can be performed on an opened directory.
+pub static FILE_OPS: vfs::file::DirectoryOperation = vfs::file::DirectoryOperation { readdir };
+
+/// The characters that cannot be used in a name, in addition to the control
+/// characters.
+const INVALID_CHARS: &str = "\"*/:<>?\\|";
+
+/// The characters that can be used in a short name, in addition to upper case
+/// letters and digits.
+const SHORT_CHARS: &[u8] = b"!#$%&'()-@^_`{}~";
+
+/// A used entry of a directory.
+struct Slot {
+    /// The locations of the long name entries of the entry, followed by the
+    /// location of its short entry.
+    locations: Vec<u64>,
+
+    /// The short entry.
+    entry: DirectoryEntry,
+
+    /// The long name of the entry, or its short name if it has none.
+    name: String,
+}
+
+impl Slot {
+    /// Returns the location of the short entry.
+    fn location(&self) -> u64 {
+        self.locations[self.locations.len() - 1]
+    }
+
+    /// Returns whether this is the `.` or the `..` entry of a subdirectory.
+    fn is_dot(&self) -> bool {
+        self.entry.name == disk::DOT || self.entry.name == disk::DOTDOT
+    }
+
+    /// Returns whether the entry has the given name, either as its long name or
+    /// as its short name.
+    fn matches(&self, name: &str) -> bool {
+        same_name(&self.name, name) || same_name(&self.entry.short_name(), name)
+    }
+}
+
+/// Returns whether the two names are the same, ignoring the case.
+fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b) || (!a.is_ascii() && a.to_lowercase() == b.to_lowercase())
+}
+
+/// A long name being read. Its entries are stored in reverse order before the
+/// short entry, the first one having the [`disk::LAST_LONG_ENTRY`] flag.
+#[derive(Default)]
+struct LongName {
+    /// The characters of the name.
+    chars: Vec<u16>,
+
+    /// The locations of the entries read so far.
+    locations: Vec<u64>,
+
+    /// The checksum of the short name stored in the entries.
+    checksum: u8,
+
+    /// The order of the next expected entry, or 0 if the name is complete.
+    next: u8,
+}
+
+impl LongName {
+    /// Add the given long name entry to the name. An entry that does not follow
+    /// the previous one discards the name.
+    fn push(&mut self, location: u64, raw: &[u8]) {
+        let order = raw[0] & disk::LONG_ORDER_MASK;
+        if raw[0] & disk::LAST_LONG_ENTRY != 0 {
+            if order == 0 || usize::from(order) > disk::MAX_LONG_ENTRIES {
+                self.clear();
+                return;
+            }
+            self.chars = vec![0; usize::from(order) * disk::LONG_NAME_CHARS];
+            self.locations.clear();
+            self.checksum = raw[13];
+            self.next = order;
+        }
+
+        if self.next == 0 || order != self.next || raw[13] != self.checksum {
+            self.clear();
+            return;
+        }
+
+        let start = usize::from(order - 1) * disk::LONG_NAME_CHARS;
+        for (i, &offset) in disk::LONG_NAME_OFFSETS.iter().enumerate() {
+            self.chars[start + i] = disk::get_u16(raw, offset);
+        }
+        self.locations.push(location);
+        self.next -= 1;
+    }
+
+    /// Discard the name being read.
+    fn clear(&mut self) {
+        self.chars.clear();
+        self.locations.clear();
+        self.next = 0;
+    }
+
+    /// Returns the name and the locations of its entries if it is complete and
+    /// belongs to the short entry with the given checksum, and start a new name.
+    fn take(&mut self, checksum: u8) -> Option<(String, Vec<u64>)> {
+        let complete = self.next == 0 && !self.locations.is_empty() && self.checksum == checksum;
+        let chars = core::mem::take(&mut self.chars);
+        let locations = core::mem::take(&mut self.locations);
+        self.next = 0;
+
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+        (complete && len > 0).then(|| {
+            let name = char::decode_utf16(chars[..len].iter().copied())
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect();
+            (name, locations)
+        })
+    }
+}
+
+/// Call the given closure on each entry of the directory starting at the given
+/// cluster, or of the fixed root directory if the cluster is 0, with the location
+/// of the entry, until the closure breaks. Returns the value it broke with.
+///
+/// # Errors
+/// - `Error::Corrupted`: The cluster chain of the directory is invalid.
+/// - `Error::IoError`: A sector of the directory could not be read.
+fn walk<T>(
+    fs: &Fat,
+    cluster: u32,
+    mut f: impl FnMut(u64, &[u8]) -> ControlFlow<T>,
+) -> Result<Option<T>, Error> {
+    let sector_size = fs.sector_size();
+    let mut visit = |sector: u32| -> Result<Option<T>, Error> {
+        let buffer = fs.sector(sector)?;
+        let data = buffer.data();
+        let base = u64::from(sector) * sector_size as u64;
+        for offset in (0..sector_size).step_by(disk::ENTRY_SIZE) {
+            let raw = &data[offset..offset + disk::ENTRY_SIZE];
+            if let ControlFlow::Break(value) = f(base + offset as u64, raw) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    };
+
+    if cluster == 0 {
+        for sector in fs.root_start..fs.root_start + fs.root_sectors {
+            if let Some(value) = visit(sector)? {
+                return Ok(Some(value));
+            }
+        }
+        return Ok(None);
+    }
+
+    let mut current = Some(cluster);
+    let mut size = 0;
+    while let Some(cluster) = current {
+        if size >= disk::MAX_DIRECTORY_SIZE {
+            return Err(Error::Corrupted);
+        }
+        let first = fs.cluster_sector(cluster);
+        for sector in first..first + fs.sectors_per_cluster {
+            if let Some(value) = visit(sector)? {
+                return Ok(Some(value));
+            }
+        }
+        size += fs.cluster_size();
+        current = fs.next(cluster)?;
+    }
+    Ok(None)
+}
+
+/// Call the given closure on each used entry of the directory starting at the
+/// given cluster, until the closure returns a value. Returns that value, or
+/// `None` if the closure returned `None` for all entries. Long names whose
+/// checksum does not match the short entry following them are ignored.
+///
+/// # Errors
+/// See [`walk`].
+fn scan<T>(
+    fs: &Fat,
+    cluster: u32,
+    mut f: impl FnMut(Slot) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    let mut long = LongName::default();
+    let found = walk(fs, cluster, |location, raw| {
+        match raw[0] {
+            disk::ENTRY_END => return ControlFlow::Break(None),
+            disk::ENTRY_FREE => {
+                long.clear();
+                return ControlFlow::Continue(());
+            }
+            _ => {}
+        }
+        if disk::is_long_entry(raw) {
+            long.push(location, raw);
+            return ControlFlow::Continue(());
+        }
+        if raw[11] & disk::ATTR_VOLUME_ID != 0 {
+            long.clear();
+            return ControlFlow::Continue(());
+        }
+
+        let entry = DirectoryEntry::parse(raw);
+        let (name, mut locations) = long
+            .take(disk::checksum(&entry.name))
+            .unwrap_or_else(|| (entry.short_name(), Vec::new()));
+        locations.push(location);
+        match f(Slot {
+            locations,
+            entry,
+            name,
+        }) {
+            Some(value) => ControlFlow::Break(Some(value)),
+            None => ControlFlow::Continue(()),
+        }
+    })?;
+    Ok(found.flatten())
+}
+
+/// Find the entry with the given name in the directory starting at the given
+/// cluster. The `.` and `..` entries are never returned.
+///
+/// # Errors
+/// See [`walk`].
+fn find(fs: &Fat, cluster: u32, name: &str) -> Result<Option<Slot>, Error> {
+    scan(fs, cluster, |slot| {
+        if !slot.is_dot() && slot.matches(name) {
+            Some(slot)
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the number of subdirectories of the directory starting at the given
+/// cluster, without its `.` and `..` entries.
+///
+/// # Errors
+/// See [`walk`].
+pub fn count_subdirectories(fs: &Fat, cluster: u32) -> Result<u64, Error> {
+    let mut count = 0;
+    scan(fs, cluster, |slot| {
+        if slot.entry.is_directory() && !slot.is_dot() {
+            count += 1;
+        }
+        None::<()>
+    })?;
+    Ok(count)
+}
+
+/// The entries needed to store a name.
+struct Names {
+    /// The short name, without its numeric tail.
+    short: [u8; 11],
+
+    /// The case flags of the short name.
+    case: u8,
+
+    /// The characters of the long name, or nothing if the short name is enough
+    /// to store the name.
+    long: Vec<u16>,
+
+    /// Whether characters were lost in the short name, which must then be made
+    /// unique with a numeric tail.
+    lossy: bool,
+}
+
+/// Convert a part of a name to the characters of a short name, up to the given
+/// length. Returns the characters, whether characters were lost, and whether
+/// the part has lower and upper case letters.
+#[allow(clippy::cast_possible_truncation)]
+fn shorten(part: &str, max: usize) -> (Vec<u8>, bool, bool, bool) {
+    let (mut short, mut lossy, mut lower, mut upper) = (Vec::new(), false, false, false);
+    for c in part.chars() {
+        if c == '.' || c == ' ' {
+            lossy = true;
+            continue;
+        }
+        let byte =
+            if c.is_ascii_alphanumeric() || (c.is_ascii() && SHORT_CHARS.contains(&(c as u8))) {
+                c as u8
+            } else {
+                lossy = true;
+                b'_'
+            };
+        lower |= byte.is_ascii_lowercase();
+        upper |= byte.is_ascii_uppercase();
+        if short.len() < max {
+            short.push(byte.to_ascii_uppercase());
+        } else {
+            lossy = true;
+        }
+    }
+    (short, lossy, lower, upper)
+}
+
+/// Returns the entries needed to store the given name. The name is stored in
+/// the short entry only if it is a valid 8.3 name whose base name and extension
+/// are each entirely in upper or lower case, like Windows NT does.
+///
+/// # Errors
+/// Returns `Error::InvalidName` if the name contains characters forbidden on
+/// FAT, ends with a dot or a space, or is too long.
+fn names(name: &str) -> Result<Names, Error> {
+    let long = name.encode_utf16().collect::<Vec<_>>();
+    if long.is_empty()
+        || long.len() > disk::MAX_LONG_ENTRIES * disk::LONG_NAME_CHARS - 5
+        || name.ends_with(['.', ' '])
+        || name.chars().any(|c| c < ' ' || INVALID_CHARS.contains(c))
+    {
+        return Err(Error::InvalidName);
+    }
+
+    // The extension follows the last dot, unless only dots precede it.
+    let (base, extension) = match name.rfind('.') {
+        Some(dot) if name[..dot].chars().any(|c| c != '.' && c != ' ') => {
+            (&name[..dot], &name[dot + 1..])
+        }
+        _ => (name, ""),
+    };
+    let (base, base_lossy, base_lower, base_upper) = shorten(base, 8);
+    let (extension, extension_lossy, extension_lower, extension_upper) = shorten(extension, 3);
+
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(&base);
+    short[8..8 + extension.len()].copy_from_slice(&extension);
+
+    let lossy = base_lossy || extension_lossy;
+    let mixed = (base_lower && base_upper) || (extension_lower && extension_upper);
+    if lossy || mixed {
+        return Ok(Names {
+            short,
+            case: 0,
+            long,
+            lossy,
+        });
+    }
+
+    let mut flags = 0;
+    if base_lower {
+        flags |= disk::CASE_LOWER_BASE;
+    }
+    if extension_lower {
+        flags |= disk::CASE_LOWER_EXT;
+    }
+    Ok(Names {
+        short,
+        case: flags,
+        long: Vec::new(),
+        lossy,
+    })
+}
+
+/// Returns the given short name with the given numeric tail, shortening its base
+/// name if needed.
+fn with_tail(short: &[u8; 11], number: u32) -> [u8; 11] {
+    let tail = format!("~{number}");
+    let base = short[..8].iter().position(|&c| c == b' ').unwrap_or(8);
+    let keep = core::cmp::min(base, 8 - tail.len());
+
+    let mut name = *short;
+    name[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+    name[keep + tail.len()..8].fill(b' ');
+    name
+}
+
+/// Returns a new short entry with the given attributes and first cluster, created
+/// now.
+#[allow(clippy::cast_possible_truncation)]
+fn new_entry(attributes: u8, cluster: u32) -> DirectoryEntry {
+    let now = UnixTime::now();
+    let (date, time) = timestamp(now);
+    DirectoryEntry {
+        attributes,
+        created_tenth: (u64::from(now) % 2 * 100) as u8,
+        created_time: time,
+        created_date: date,
+        accessed_date: date,
+        cluster,
+        modified_time: time,
+        modified_date: date,
+        ..DirectoryEntry::default()
+    }
+}
+
+/// Add an entry with the given name to the directory, with the given short entry
+/// whose name is replaced. The entries are written in the first run of enough
+/// free entries, or in new clusters appended to the directory. The size of the
+/// directory is updated in its VFS inode. Returns the location of the new short
+/// entry.
+///
+/// # Errors
+/// - `Error::InvalidName`: The name cannot be stored on FAT.
+/// - `Error::NoSpace`: The directory is full, or a cluster could not be allocated.
+/// - `Error::Corrupted`: The cluster chain of the directory is invalid.
+/// - `Error::IoError`: A sector of the directory could not be read.
+#[allow(clippy::cast_possible_truncation)]
+fn add_entry(
+    dir: &Inode,
+    state: &mut InodeState,
+    name: &str,
+    mut entry: DirectoryEntry,
+) -> Result<u64, Error> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let mut names = names(name)?;
+    let needed = (names.long.len() + disk::LONG_NAME_CHARS - 1) / disk::LONG_NAME_CHARS + 1;
+
+    // Collect the short names used in the directory, and find a run of enough
+    // free entries. All the entries after the end marker are free.
+    let mut shorts = Vec::new();
+    let mut run = Vec::new();
+    let mut end = false;
+    walk(fs, state.cluster, |location, raw| {
+        end |= raw[0] == disk::ENTRY_END;
+        if end || raw[0] == disk::ENTRY_FREE {
+            if run.len() < needed {
+                run.push(location);
+            }
+        } else {
+            if run.len() < needed {
+                run.clear();
+            }
+            if !disk::is_long_entry(raw) {
+                let mut short = [0; 11];
+                short.copy_from_slice(&raw[..11]);
+                shorts.push(short);
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    })?;
+
+    if run.len() < needed {
+        // The root directory of FAT12 and FAT16 cannot grow.
+        if state.cluster == 0 {
+            return Err(Error::NoSpace);
+        }
+
+        let cluster_size = fs.cluster_size();
+        let mut size = dir.metadata.lock().size;
+        let index = (size / cluster_size) as u32;
+        let mut last = info
+            .cluster(state, index.saturating_sub(1), false)?
+            .ok_or(Error::Corrupted)?;
+        while run.len() < needed {
+            if size + cluster_size > disk::MAX_DIRECTORY_SIZE {
+                return Err(Error::NoSpace);
+            }
+            last = fs.alloc_cluster(Some(last))?;
+            let start = u64::from(fs.cluster_sector(last)) * fs.sector_size() as u64;
+            run.extend(
+                (0..cluster_size)
+                    .step_by(disk::ENTRY_SIZE)
+                    .map(|offset| start + offset as u64)
+                    .take(needed - run.len()),
+            );
+            size += cluster_size;
+            dir.metadata.lock().size = size;
+        }
+    }
+
+    if names.lossy {
+        names.short = (1..1_000_000)
+            .map(|number| with_tail(&names.short, number))
+            .find(|short| !shorts.contains(short))
+            .ok_or(Error::NoSpace)?;
+    }
+
+    let checksum = disk::checksum(&names.short);
+    let count = needed - 1;
+    for (i, &location) in run[..count].iter().enumerate() {
+        let order = count - i;
+        let start = (order - 1) * disk::LONG_NAME_CHARS;
+        let mut chars = [0xFFFF; disk::LONG_NAME_CHARS];
+        for (j, c) in chars.iter_mut().enumerate() {
+            match names.long.get(start + j) {
+                Some(&long) => *c = long,
+                None if start + j == names.long.len() => *c = 0,
+                None => {}
+            }
+        }
+
+        let flag = if i == 0 { disk::LAST_LONG_ENTRY } else { 0 };
+        let (buffer, offset) = fs.entry(location)?;
+        disk::write_long_entry(
+            &mut buffer.data()[offset..offset + disk::ENTRY_SIZE],
+            order as u8 | flag,
+            checksum,
+            &chars,
+        );
+        buffer.mark_dirty();
+    }
+
+    entry.name = names.short;
+    entry.case = names.case;
+    let location = run[count];
+    let (buffer, offset) = fs.entry(location)?;
+    entry.write(&mut buffer.data()[offset..offset + disk::ENTRY_SIZE]);
+    buffer.mark_dirty();
+    Ok(location)
+}
+
+/// Mark the entries of the given slot as free.
+///
+/// # Errors
+/// Returns `Error::IoError` if a sector of the directory could not be read.
+fn remove_entries(fs: &Fat, slot: &Slot) -> Result<(), Error> {
+    for &location in &slot.locations {
+        let (buffer, offset) = fs.entry(location)?;
+        buffer.data()[offset] = disk::ENTRY_FREE;
+        buffer.mark_dirty();
+    }
+    Ok(())
+}
+
+/// Update the modification and change times of the directory after one of its
+/// entries was added or removed, and write it into its directory entry.
+fn touch(dir: &Inode, state: &InodeState) -> Result<(), Error> {
+    {
+        let mut metadata = dir.metadata.lock();
+        metadata.modification_time = vfs_now();
+        metadata.change_time = vfs_now();
+    }
+    info(dir).store(dir, state)
+}
+
+/// Returns the VFS inode of the given entry.
+fn child(dir: &Inode, slot: &Slot) -> Result<Arc<Inode>, Error> {
+    dir.superblock
+        .upgrade()
+        .ok_or(Error::IoError)?
+        .get_inode(info(dir).fs.identifier(slot.location()))
+        .map_err(|_| Error::IoError)
+}
+
+/// Device nodes cannot be stored on FAT.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn mknod(_: &Inode, _: &str, _: Device) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// Create an empty regular file in the directory.
+///
+/// # Errors
+/// - `CreateError::AlreadyExists`: An entry with the same name already exists.
+/// - `CreateError::InvalidName`: The name cannot be stored on FAT.
+/// - `CreateError::NoSpace`: The directory is full.
+/// - `CreateError::IoError`: The directory could not be read or is corrupted.
+fn create(dir: &Inode, name: &str) -> Result<Identifier, CreateError> {
+    let info = info(dir);
+    let mut state = info.lock();
+    if find(&info.fs, state.cluster, name)?.is_some() {
+        return Err(CreateError::AlreadyExists);
+    }
+    let entry = new_entry(disk::ATTR_ARCHIVE, 0);
+    let location = add_entry(dir, &mut state, name, entry)?;
+    touch(dir, &state)?;
+    Ok(info.fs.identifier(location))
+}
+
+/// Symbolic links cannot be stored on FAT.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn symlink(_: &Inode, _: &str, _: &str) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// Look up the entry with the given name in the directory.
+///
+/// # Errors
+/// - `LookupError::NoSuchEntry`: There is no entry with the given name.
+/// - `LookupError::IoError`: The directory could not be read or is corrupted.
+fn lookup(dir: &Inode, name: &str) -> Result<Identifier, LookupError> {
+    let info = info(dir);
+    let state = info.lock();
+    find(&info.fs, state.cluster, name)?
+        .map(|slot| info.fs.identifier(slot.location()))
+        .ok_or(LookupError::NoSuchEntry)
+}
+
+/// Remove the file with the given name from the directory. Its clusters are
+/// released once it is not used anymore.
+///
+/// # Errors
+/// - `UnlinkError::NoSuchEntry`: There is no entry with the given name.
+/// - `UnlinkError::IsADirectory`: The entry is a directory.
+/// - `UnlinkError::IoError`: The directory could not be read or is corrupted.
+fn unlink(dir: &Inode, name: &str) -> Result<(), UnlinkError> {
+    let info = info(dir);
+    let state = info.lock();
+    let slot = find(&info.fs, state.cluster, name)?.ok_or(UnlinkError::NoSuchEntry)?;
+    let child = child(dir, &slot)?;
+    if child.kind == vfs::inode::Kind::Directory {
+        return Err(UnlinkError::IsADirectory);
+    }
+
+    let mut child_state = inode::info(&child).lock();
+    remove_entries(&info.fs, &slot)?;
+    child_state.entry = None;
+    child_state.deleted = true;
+    info.fs.removed(child.id, slot.location());
+    {
+        let mut metadata = child.metadata.lock();
+        metadata.links = 0;
+        metadata.change_time = vfs_now();
+    }
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Create a directory in the directory, with its `.` and `..` entries. The `..`
+/// entry of a directory created in the root directory points to the cluster 0,
+/// even on FAT32.
+///
+/// # Errors
+/// - `MkdirError::AlreadyExists`: An entry with the same name already exists.
+/// - `MkdirError::InvalidName`: The name cannot be stored on FAT.
+/// - `MkdirError::NoSpace`: There is no free cluster left, or the directory is full.
+/// - `MkdirError::IoError`: The directory could not be read or is corrupted.
+fn mkdir(dir: &Inode, name: &str) -> Result<Identifier, MkdirError> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let mut state = info.lock();
+    if find(fs, state.cluster, name)?.is_some() {
+        return Err(MkdirError::AlreadyExists);
+    }
+
+    let cluster = fs.alloc_cluster(None)?;
+    let entry = new_entry(disk::ATTR_DIRECTORY, cluster);
+    let parent = if info.is_root() { 0 } else { state.cluster };
+    let initialize = || -> Result<(), Error> {
+        let buffer = fs.sector(fs.cluster_sector(cluster))?;
+        let mut data = buffer.data();
+        let dot = DirectoryEntry {
+            name: disk::DOT,
+            ..entry
+        };
+        let dotdot = DirectoryEntry {
+            name: disk::DOTDOT,
+            cluster: parent,
+            ..entry
+        };
+        dot.write(&mut data[..disk::ENTRY_SIZE]);
+        dotdot.write(&mut data[disk::ENTRY_SIZE..2 * disk::ENTRY_SIZE]);
+        drop(data);
+        buffer.mark_dirty();
+        Ok(())
+    };
+
+    let location = match initialize().and_then(|()| add_entry(dir, &mut state, name, entry)) {
+        Ok(location) => location,
+        Err(error) => {
+            _ = fs.free_chain(cluster);
+            return Err(error.into());
+        }
+    };
+
+    dir.metadata.lock().links += 1;
+    touch(dir, &state)?;
+    Ok(fs.identifier(location))
+}
+
+/// Remove the empty directory with the given name from the directory. Its
+/// cluster is released once it is not used anymore.
+///
+/// # Errors
+/// - `RmdirError::NoSuchEntry`: There is no entry with the given name.
+/// - `RmdirError::NotADirectory`: The entry is not a directory.
+/// - `RmdirError::NotEmpty`: The directory contains other entries than `.` and `..`.
+/// - `RmdirError::IoError`: One of the directories could not be read.
+fn rmdir(dir: &Inode, name: &str) -> Result<(), RmdirError> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let state = info.lock();
+    let slot = find(fs, state.cluster, name)?.ok_or(RmdirError::NoSuchEntry)?;
+    let child = child(dir, &slot)?;
+    if child.kind != vfs::inode::Kind::Directory {
+        return Err(RmdirError::NotADirectory);
+    }
+
+    let mut child_state = inode::info(&child).lock();
+    if scan(fs, child_state.cluster, |slot| {
+        (!slot.is_dot()).then_some(())
+    })?
+    .is_some()
+    {
+        return Err(RmdirError::NotEmpty);
+    }
+
+    remove_entries(fs, &slot)?;
+    child_state.entry = None;
+    child_state.deleted = true;
+    fs.removed(child.id, slot.location());
+    {
+        let mut metadata = child.metadata.lock();
+        metadata.links = 0;
+        metadata.change_time = vfs_now();
+    }
+
+    {
+        let mut metadata = dir.metadata.lock();
+        metadata.links = metadata.links.saturating_sub(1);
+    }
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Hard links cannot be stored on FAT, since the metadata of a file is stored
+/// in its directory entry.
+///
+/// # Errors
+/// Always returns `LinkError::NotSupported`, or `LinkError::IsADirectory` if
+/// the target is a directory.
+fn link(_: &Inode, _: &str, target: &Inode) -> Result<(), LinkError> {
+    match target.kind {
+        vfs::inode::Kind::Directory => Err(LinkError::IsADirectory),
+        _ => Err(LinkError::NotSupported),
+    }
+}
+
+/// Rename an entry of the directory. The old entries are removed before the new
+/// ones are added, so that they can be reused, and are restored if the new
+/// entries cannot be added. The inode keeps its identifier, even though its
+/// short entry may have moved.
+///
+/// # Errors
+/// - `RenameError::NoSuchEntry`: There is no entry named `old`.
+/// - `RenameError::AlreadyExists`: Another entry named `new` already exists.
+/// - `RenameError::InvalidName`: The new name cannot be stored on FAT.
+/// - `RenameError::NoSpace`: The directory is full.
+/// - `RenameError::IoError`: The directory could not be read or is corrupted.
+fn rename(dir: &Inode, old: &str, new: &str) -> Result<(), RenameError> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let mut state = info.lock();
+    let slot = find(fs, state.cluster, old)?.ok_or(RenameError::NoSuchEntry)?;
+
+    // Changing the case of a name finds the entry itself.
+    if let Some(existing) = find(fs, state.cluster, new)? {
+        if existing.location() != slot.location() {
+            return Err(RenameError::AlreadyExists);
+        }
+    }
+
+    let child = child(dir, &slot)?;
+    let child_info = inode::info(&child);
+    let mut child_state = child_info.lock();
+
+    let saved = slot
+        .locations
+        .iter()
+        .map(|&location| {
+            let (buffer, offset) = fs.entry(location)?;
+            let mut raw = [0; disk::ENTRY_SIZE];
+            raw.copy_from_slice(&buffer.data()[offset..offset + disk::ENTRY_SIZE]);
+            Ok((location, raw))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    remove_entries(fs, &slot)?;
+    let location = match add_entry(dir, &mut state, new, slot.entry) {
+        Ok(location) => location,
+        Err(error) => {
+            for (location, raw) in &saved {
+                if let Ok((buffer, offset)) = fs.entry(*location) {
+                    buffer.data()[offset..offset + disk::ENTRY_SIZE].copy_from_slice(raw);
+                    buffer.mark_dirty();
+                }
+            }
+            return Err(error.into());
+        }
+    };
+
+    fs.moved(child.id, slot.location(), location);
+    child_state.entry = Some(location);
+    child.metadata.lock().change_time = vfs_now();
+    child_info.store(&child, &child_state)?;
+    touch(dir, &state)?;
+    Ok(())
+}
+
+/// Read the entry at the given index of the directory. The `.` and `..` entries
+/// are always the two first entries, even in the root directory which does not
+/// have them on the device.
+///
+/// # Errors
+/// Returns `ReaddirError::EndOfDirectory` if there is no entry at the given
+/// index, or if the directory could not be read.
+fn readdir(
+    file: &vfs::file::File,
+    offset: vfs::file::Offset,
+) -> Result<vfs::dirent::DirectoryEntry, vfs::file::ReaddirError> {
+    let dentry = file.dentry.as_ref().expect("Open file without dentry");
+    let dir = dentry.inode();
+    let info = info(dir);
+
+    let dot = |name: &str, inode| vfs::dirent::DirectoryEntry {
+        name: String::from(name),
+        offset: 1,
+        kind: vfs::dirent::Kind::Directory,
+        inode,
+    };
+    match offset.0 {
+        0 => return Ok(dot(".", dir.id)),
+        1 if info.is_root() => return Ok(dot("..", dir.id)),
+        1 => {
+            let parent = dentry.parent().map_or(dir.id, |parent| parent.inode().id);
+            return Ok(dot("..", parent));
+        }
+        _ => {}
+    }
+
+    let mut index = 1;
+    let found = {
+        let state = info.lock();
+        scan(&info.fs, state.cluster, |slot| {
+            if slot.is_dot() {
+                return None;
+            }
+            index += 1;
+            (index >= offset.0).then_some(slot)
+        })
+    };
+    let Ok(Some(slot)) = found else {
+        return Err(vfs::file::ReaddirError::EndOfDirectory);
+    };
+
+    let kind = if slot.entry.is_directory() {
+        vfs::dirent::Kind::Directory
+    } else {
+        vfs::dirent::Kind::File
+    };
+    dir.metadata.lock().access_time = vfs_now();
+    Ok(vfs::dirent::DirectoryEntry {
+        inode: info.fs.identifier(slot.location()),
+        name: slot.name,
+        offset: 1,
+        kind,
+    })
+}
+
+impl From<Error> for CreateError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::InvalidName => Self::InvalidName,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for MkdirError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::InvalidName => Self::InvalidName,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for RenameError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::InvalidName => Self::InvalidName,
+            Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<Error> for LookupError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
+
+impl From<Error> for UnlinkError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
+
+impl From<Error> for RmdirError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
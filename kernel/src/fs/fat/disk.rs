@@ -0,0 +1,380 @@
+//! The on-disk structures of FAT. All fields are stored in little-endian, and
+//! directory entries are read and written directly in the buffers of the buffer
+//! cache, so that the fields that the driver does not know about are preserved
+//! when an entry is written back.
+
+/// The signature at the end of the boot sector.
+pub const BOOT_SIGNATURE: u16 = 0xAA55;
+
+/// The signatures of the `FSInfo` sector of FAT32 filesystems.
+pub const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+pub const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+
+/// The offsets of the free cluster count and of the next free cluster hint in
+/// the `FSInfo` sector, and the value stored when they are unknown.
+pub const FSINFO_FREE_COUNT: usize = 488;
+pub const FSINFO_NEXT_FREE: usize = 492;
+pub const FSINFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// The extended boot signatures. When one of them is present, the byte before
+/// it is used by Linux and Windows to mark the filesystem as dirty while it is
+/// mounted.
+pub const EXTENDED_BOOT_SIGNATURES: [u8; 2] = [0x28, 0x29];
+pub const STATE_DIRTY: u8 = 0x01;
+
+/// The FAT32 mirroring of the FATs is disabled, and only the active FAT, whose
+/// index is stored in the low bits of the flags, is used.
+pub const MIRRORING_DISABLED: u16 = 0x0080;
+pub const ACTIVE_FAT_MASK: u16 = 0x000F;
+
+/// The attributes of a directory entry.
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+
+/// The attributes of a long name entry, which cannot be used by a valid short
+/// entry, and the mask of the attributes compared to detect them.
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+pub const ATTR_LONG_NAME_MASK: u8 = 0x3F;
+
+/// The size of a directory entry, in bytes.
+pub const ENTRY_SIZE: usize = 32;
+
+/// The first byte of the name of a free entry, of the entry marking the end of
+/// a directory, and of a name starting with 0xE5.
+pub const ENTRY_FREE: u8 = 0xE5;
+pub const ENTRY_END: u8 = 0x00;
+pub const ENTRY_KANJI: u8 = 0x05;
+
+/// The maximal size of a directory, in bytes.
+pub const MAX_DIRECTORY_SIZE: usize = 65536 * ENTRY_SIZE;
+
+/// The flag set in the order of the last long name entry of a name, which is
+/// stored first, and the mask of the order.
+pub const LAST_LONG_ENTRY: u8 = 0x40;
+pub const LONG_ORDER_MASK: u8 = 0x1F;
+
+/// The number of UCS-2 characters stored in each long name entry, and the
+/// maximal number of entries of a long name.
+pub const LONG_NAME_CHARS: usize = 13;
+pub const MAX_LONG_ENTRIES: usize = 20;
+
+/// The offsets of the characters of a long name entry.
+pub const LONG_NAME_OFFSETS: [usize; LONG_NAME_CHARS] =
+    [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// The base name or the extension of a short name is displayed in lower case.
+/// These flags are set by Windows NT for names that do not need a long name.
+pub const CASE_LOWER_BASE: u8 = 0x08;
+pub const CASE_LOWER_EXT: u8 = 0x10;
+
+/// The short names of the `.` and `..` entries of a subdirectory.
+pub const DOT: [u8; 11] = *b".          ";
+pub const DOTDOT: [u8; 11] = *b"..         ";
+
+/// The variant of FAT used by a filesystem, determined by its number of clusters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl Kind {
+    /// Returns the variant of FAT used by a filesystem with the given number of
+    /// clusters, as specified by Microsoft.
+    #[must_use]
+    pub const fn from_clusters(clusters: u32) -> Self {
+        if clusters < 4085 {
+            Self::Fat12
+        } else if clusters < 65525 {
+            Self::Fat16
+        } else {
+            Self::Fat32
+        }
+    }
+
+    /// Returns the size of an entry of the FAT, in bits.
+    #[must_use]
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::Fat12 => 12,
+            Self::Fat16 => 16,
+            Self::Fat32 => 32,
+        }
+    }
+
+    /// Returns the first value of an entry of the FAT marking a bad cluster. The
+    /// values above it mark the end of a cluster chain.
+    #[must_use]
+    pub const fn bad(self) -> u32 {
+        match self {
+            Self::Fat12 => 0x0FF7,
+            Self::Fat16 => 0xFFF7,
+            Self::Fat32 => 0x0FFF_FFF7,
+        }
+    }
+
+    /// Returns the value stored in the FAT for the last cluster of a chain.
+    #[must_use]
+    pub const fn end_of_chain(self) -> u32 {
+        match self {
+            Self::Fat12 => 0x0FFF,
+            Self::Fat16 => 0xFFFF,
+            Self::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+}
+
+/// Read a little-endian `u16` at the given offset.
+#[must_use]
+pub fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Read a little-endian `u32` at the given offset.
+#[must_use]
+pub fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Write a little-endian `u16` at the given offset.
+pub fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Write a little-endian `u32` at the given offset.
+pub fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// The fields of the boot sector and of the BIOS parameter block used by the
+/// driver. The fields specific to FAT32 are zero on other filesystems.
+#[derive(Debug, Clone)]
+pub struct BootSector {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub fats: u8,
+    pub root_entries: u16,
+    pub total_sectors_16: u16,
+    pub media: u8,
+    pub fat_size_16: u16,
+    pub total_sectors_32: u32,
+    pub fat_size_32: u32,
+    pub ext_flags: u16,
+    pub fs_version: u16,
+    pub root_cluster: u32,
+    pub fs_info: u16,
+    pub signature: u16,
+}
+
+impl BootSector {
+    /// The offsets of the state byte on FAT12/16 and FAT32 filesystems. The
+    /// extended boot signature follows it.
+    pub const STATE_16: usize = 37;
+    pub const STATE_32: usize = 65;
+
+    /// Parse the boot sector from the given bytes.
+    #[must_use]
+    pub fn parse(data: &[u8]) -> Self {
+        let fat_size_16 = get_u16(data, 22);
+        let fat32 = fat_size_16 == 0;
+        let field = |offset: usize| if fat32 { get_u32(data, offset) } else { 0 };
+        let half = |offset: usize| if fat32 { get_u16(data, offset) } else { 0 };
+
+        Self {
+            bytes_per_sector: get_u16(data, 11),
+            sectors_per_cluster: data[13],
+            reserved_sectors: get_u16(data, 14),
+            fats: data[16],
+            root_entries: get_u16(data, 17),
+            total_sectors_16: get_u16(data, 19),
+            media: data[21],
+            fat_size_16,
+            total_sectors_32: get_u32(data, 32),
+            fat_size_32: field(36),
+            ext_flags: half(40),
+            fs_version: half(42),
+            root_cluster: field(44),
+            fs_info: half(48),
+            signature: get_u16(data, 510),
+        }
+    }
+
+    /// Returns the total number of sectors of the filesystem.
+    #[must_use]
+    pub fn total_sectors(&self) -> u32 {
+        match self.total_sectors_16 {
+            0 => self.total_sectors_32,
+            sectors => u32::from(sectors),
+        }
+    }
+
+    /// Returns the number of sectors of each FAT.
+    #[must_use]
+    pub fn fat_size(&self) -> u32 {
+        match self.fat_size_16 {
+            0 => self.fat_size_32,
+            sectors => u32::from(sectors),
+        }
+    }
+}
+
+/// A short directory entry, describing a file or a directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DirectoryEntry {
+    pub name: [u8; 11],
+    pub attributes: u8,
+    pub case: u8,
+    pub created_tenth: u8,
+    pub created_time: u16,
+    pub created_date: u16,
+    pub accessed_date: u16,
+    pub cluster: u32,
+    pub modified_time: u16,
+    pub modified_date: u16,
+    pub size: u32,
+}
+
+impl DirectoryEntry {
+    /// Parse a short entry from the given bytes.
+    #[must_use]
+    pub fn parse(data: &[u8]) -> Self {
+        let mut name = [0; 11];
+        name.copy_from_slice(&data[..11]);
+        Self {
+            name,
+            attributes: data[11],
+            case: data[12],
+            created_tenth: data[13],
+            created_time: get_u16(data, 14),
+            created_date: get_u16(data, 16),
+            accessed_date: get_u16(data, 18),
+            cluster: u32::from(get_u16(data, 20)) << 16 | u32::from(get_u16(data, 26)),
+            modified_time: get_u16(data, 22),
+            modified_date: get_u16(data, 24),
+            size: get_u32(data, 28),
+        }
+    }
+
+    /// Write this entry into the given bytes.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write(&self, data: &mut [u8]) {
+        data[..11].copy_from_slice(&self.name);
+        data[11] = self.attributes;
+        data[12] = self.case;
+        data[13] = self.created_tenth;
+        set_u16(data, 14, self.created_time);
+        set_u16(data, 16, self.created_date);
+        set_u16(data, 18, self.accessed_date);
+        set_u16(data, 20, (self.cluster >> 16) as u16);
+        set_u16(data, 22, self.modified_time);
+        set_u16(data, 24, self.modified_date);
+        set_u16(data, 26, self.cluster as u16);
+        set_u32(data, 28, self.size);
+    }
+
+    /// Returns the short name of this entry as it is displayed, with a dot
+    /// between the base name and the extension, and with the case flags of
+    /// Windows NT applied. Bytes outside of ASCII are decoded as Latin-1, since
+    /// the code page used to create the name is unknown.
+    #[must_use]
+    pub fn short_name(&self) -> String {
+        let mut name = self.name;
+        if name[0] == ENTRY_KANJI {
+            name[0] = ENTRY_FREE;
+        }
+
+        let decode = |part: &[u8], lower: bool| {
+            part.iter()
+                .take(
+                    part.iter()
+                        .rposition(|&c| c != b' ')
+                        .map_or(0, |last| last + 1),
+                )
+                .map(|&c| char::from(if lower { c.to_ascii_lowercase() } else { c }))
+                .collect::<String>()
+        };
+
+        let mut short = decode(&name[..8], self.case & CASE_LOWER_BASE != 0);
+        let extension = decode(&name[8..], self.case & CASE_LOWER_EXT != 0);
+        if !extension.is_empty() {
+            short.push('.');
+            short.push_str(&extension);
+        }
+        short
+    }
+
+    /// Returns whether this entry is a subdirectory.
+    #[must_use]
+    pub const fn is_directory(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+}
+
+/// Returns whether the given raw entry is a long name entry.
+#[must_use]
+pub fn is_long_entry(data: &[u8]) -> bool {
+    data[11] & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME
+}
+
+/// Write a long name entry with the given order and checksum, containing the
+/// given characters of the name, into the given bytes.
+pub fn write_long_entry(data: &mut [u8], order: u8, checksum: u8, chars: &[u16]) {
+    data.fill(0);
+    data[0] = order;
+    data[11] = ATTR_LONG_NAME;
+    data[13] = checksum;
+    for (&offset, &c) in LONG_NAME_OFFSETS.iter().zip(chars) {
+        set_u16(data, offset, c);
+    }
+}
+
+/// Returns the checksum of a short name, stored in the long name entries of the
+/// short entry to detect long names orphaned by systems unaware of them.
+#[must_use]
+pub fn checksum(name: &[u8; 11]) -> u8 {
+    name.iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Encode a date and a time of the day into the FAT format. Dates are stored
+/// from 1980 to 2107 with a resolution of 2 seconds.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn encode_timestamp(date: &crate::time::date::Date) -> (u16, u16) {
+    if date.year < 1980 {
+        return (0x21, 0);
+    } else if date.year > 2107 {
+        return (0xFF9F, 0xBF7D);
+    }
+    let day = ((date.year - 1980) << 9) | u16::from(date.month) << 5 | u16::from(date.day);
+    let time =
+        u16::from(date.hour) << 11 | u16::from(date.minute) << 5 | u16::from(date.second / 2);
+    (day, time)
+}
+
+/// Decode a date and a time of the day stored in the FAT format. The fields out
+/// of range are clamped, so that an uninitialized date is read as the first
+/// day of 1980.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn decode_timestamp(date: u16, time: u16) -> crate::time::date::Date {
+    crate::time::date::Date {
+        year: 1980 + (date >> 9),
+        month: ((date >> 5) & 0x0F).clamp(1, 12) as u8,
+        day: (date & 0x1F).max(1) as u8,
+        hour: ((time >> 11) as u8).min(23),
+        minute: (((time >> 5) & 0x3F) as u8).min(59),
+        second: (((time & 0x1F) * 2) as u8).min(59),
+    }
+}
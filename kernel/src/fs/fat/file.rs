@@ -0,0 +1,153 @@
+//! Regular files of a FAT filesystem. Their content is read and written through
+//! the buffer cache, sector by sector. FAT files cannot have holes, so a write
+//! beyond the end of a file first fills the gap with zeroes.
+use super::{disk, inode::info, vfs_now, Error};
+use crate::vfs::{
+    self,
+    file::{File, Offset, ReadError, SeekError, Whence, WriteError},
+};
+
+/// Operations that can be performed on an opened regular file.
+pub static FILE_OPS: vfs::file::FileOperation = vfs::file::FileOperation {
+    write,
+    read,
+    seek,
+    poll: vfs::poll::always_ready,
+    ioctl: vfs::file::no_ioctl,
+    mmap: vfs::file::no_mmap,
+};
+
+/// Read from the file at the given offset into the given buffer, and return the
+/// number of bytes read. Reading stops at the end of the file.
+///
+/// # Errors
+/// Returns `ReadError::IoError` if a sector of the file could not be read, or if
+/// its cluster chain is shorter than its size.
+fn read(file: &File, buf: &mut [u8], offset: Offset) -> Result<usize, ReadError> {
+    let inode = file
+        .dentry
+        .as_ref()
+        .expect("Open file without dentry")
+        .inode();
+    let info = info(inode);
+    let size = inode.metadata.lock().size;
+    let len = core::cmp::min(buf.len(), size.saturating_sub(offset.0));
+    let mut state = info.lock();
+
+    let mut done = 0;
+    while done < len {
+        let (buffer, start) = info
+            .sector(&mut state, offset.0 + done, false)?
+            .ok_or(ReadError::IoError)?;
+        let data = buffer.data();
+        let count = core::cmp::min(data.len() - start, len - done);
+        buf[done..done + count].copy_from_slice(&data[start..start + count]);
+        done += count;
+    }
+
+    drop(state);
+    inode.metadata.lock().access_time = vfs_now();
+    Ok(len)
+}
+
+/// Write the given buffer to the file at the given offset, and return the number
+/// of bytes written. The file is extended if the write goes beyond its end, and
+/// the archive attribute of its entry is set.
+///
+/// # Errors
+/// - `WriteError::NoSpace`: The file would exceed 4 GiB, or there is no free
+///   cluster left.
+/// - `WriteError::IoError`: A sector of the file could not be read, or its
+///   cluster chain is invalid.
+fn write(file: &File, buf: &[u8], offset: Offset) -> Result<usize, WriteError> {
+    let inode = file
+        .dentry
+        .as_ref()
+        .expect("Open file without dentry")
+        .inode();
+    let info = info(inode);
+    let end = offset.0.checked_add(buf.len()).ok_or(WriteError::NoSpace)?;
+    if end as u64 > info.fs.max_file_size() {
+        return Err(WriteError::NoSpace);
+    }
+
+    let mut state = info.lock();
+    let size = inode.metadata.lock().size;
+    if offset.0 > size {
+        info.zero(&mut state, size, offset.0)?;
+        inode.metadata.lock().size = offset.0;
+    }
+
+    let mut done = 0;
+    let result = loop {
+        if done == buf.len() {
+            break Ok(());
+        }
+
+        let (buffer, start) = match info.sector(&mut state, offset.0 + done, true) {
+            Ok(Some(sector)) => sector,
+            Ok(None) => break Err(Error::Corrupted),
+            Err(error) => break Err(error),
+        };
+        let mut data = buffer.data();
+        let count = core::cmp::min(data.len() - start, buf.len() - done);
+        data[start..start + count].copy_from_slice(&buf[done..done + count]);
+        drop(data);
+        buffer.mark_dirty();
+        done += count;
+    };
+
+    state.attributes |= disk::ATTR_ARCHIVE;
+    {
+        let mut metadata = inode.metadata.lock();
+        metadata.size = core::cmp::max(metadata.size, offset.0 + done);
+        metadata.modification_time = vfs_now();
+        metadata.change_time = vfs_now();
+    }
+    info.store(inode, &state)?;
+
+    match result {
+        Err(error) if done == 0 => Err(error.into()),
+        _ => Ok(done),
+    }
+}
+
+/// Seek into the file and return the new offset. Seeking beyond the end of the
+/// file is allowed, and a write there fills the gap with zeroes.
+///
+/// # Errors
+/// Returns `SeekError::Overflow` if the new offset overflows.
+fn seek(file: &File, offset: isize, whence: Whence) -> Result<Offset, SeekError> {
+    let base = match whence {
+        Whence::Start => 0,
+        Whence::Current => file.state.lock().offset.0,
+        Whence::End => {
+            file.dentry
+                .as_ref()
+                .expect("Open file without dentry")
+                .inode()
+                .metadata
+                .lock()
+                .size
+        }
+    };
+
+    base.checked_add_signed(offset)
+        .map(Offset)
+        .ok_or(SeekError::Overflow)
+}
+
+impl From<Error> for ReadError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
+
+impl From<Error> for WriteError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::InvalidName | Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
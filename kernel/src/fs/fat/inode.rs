@@ -0,0 +1,544 @@
+//! Inodes of a FAT filesystem. The metadata of an inode shared with the VFS,
+//! like its size or timestamps, is kept in the VFS inode, while the location of
+//! its directory entry and its first cluster are kept in an [`InodeInfo`]
+//! attached to it. Both are written back together into the directory entry
+//! when the inode is written.
+//!
+//! FAT has no permissions nor owners: directories have the mode `0755` and
+//! files the mode `0644`, without the write permissions if the entry has the
+//! read-only attribute. Clearing the write permissions of an inode sets that
+//! attribute. A file whose entry is removed while it is still in use keeps its
+//! clusters until the VFS drops it, like on ext2.
+use super::{
+    dir,
+    disk::{self, DirectoryEntry},
+    fat, file, time, timestamp, vfs_now, Error, Fat, ROOT,
+};
+use crate::{
+    device::{block::cache::Buffer, Device},
+    time::{units::Second, unix::UnixTime},
+    user::task::mutex::{Mutex, MutexGuard},
+    vfs::{
+        self,
+        inode::{Identifier, InodeCreateInfo, InodeMetadata, Kind},
+        mount::{ReadInodeError, Super, WriteInodeError},
+    },
+};
+use alloc::collections::BTreeMap;
+
+/// Operations that can be performed on a file inode.
+pub static INODE_FILE_OPS: vfs::inode::FileOperation = vfs::inode::FileOperation { truncate };
+
+/// The first identifier given to an inode whose short entry is at a location
+/// that is still reserved by another inode. Locations divided by the size of an
+/// entry never reach it.
+const FIRST_SYNTHETIC: u64 = 1 << 60;
+
+/// The identifiers of the inodes that do not match the location of their short
+/// entry. The identifier of an inode is the location of its short entry divided
+/// by the size of an entry, and is kept when the entry moves to another location
+/// because the file is renamed. The original location then stays reserved by
+/// the inode until it is removed, and a new entry created there is given a
+/// synthetic identifier instead.
+pub struct Identifiers {
+    /// The identifier of the inode whose entry is at a location, for the
+    /// locations whose entry does not match their identifier.
+    moved: BTreeMap<u64, u64>,
+
+    /// The current location of the entry of the inodes whose identifier does not
+    /// match it, or `None` if the entry was removed but the inode is still used.
+    reserved: BTreeMap<u64, Option<u64>>,
+
+    /// The next synthetic identifier.
+    next: u64,
+}
+
+impl Identifiers {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            moved: BTreeMap::new(),
+            reserved: BTreeMap::new(),
+            next: FIRST_SYNTHETIC,
+        }
+    }
+}
+
+/// The FAT specific data of an inode.
+pub struct InodeInfo {
+    /// The filesystem of the inode.
+    pub fs: Arc<Fat>,
+
+    /// The identifier of the inode.
+    pub id: Identifier,
+
+    /// The part of the inode that can be modified by the driver.
+    state: Mutex<InodeState>,
+}
+
+/// The part of an inode that is not shared with the VFS, protected by a sleeping
+/// mutex since most operations on it need to read sectors from the device.
+pub struct InodeState {
+    /// The location of the short entry of the inode on the device, in bytes, or
+    /// `None` for the root directory and for removed inodes.
+    pub entry: Option<u64>,
+
+    /// The first cluster of the inode, or 0 if it has no cluster. The root
+    /// directory of FAT12 and FAT16 filesystems has no cluster.
+    pub cluster: u32,
+
+    /// The attributes of the directory entry of the inode.
+    pub attributes: u8,
+
+    /// The index in the chain and the number of the last cluster looked up, to
+    /// avoid following the chain from its start on sequential accesses.
+    pub cursor: Option<(u32, u32)>,
+
+    /// Whether the entry of the inode has been removed, and its clusters must be
+    /// released when it is dropped.
+    pub deleted: bool,
+}
+
+impl InodeInfo {
+    /// Lock the state of the inode.
+    pub fn lock(&self) -> MutexGuard<'_, InodeState> {
+        self.state.lock()
+    }
+
+    /// Returns whether the inode is the root directory.
+    #[must_use]
+    pub fn is_root(&self) -> bool {
+        self.id == ROOT
+    }
+
+    /// Returns the cluster at the given index of the chain of the inode, or
+    /// `None` if the chain is shorter. If `create` is true, the chain is extended
+    /// with newly allocated zeroed clusters, so `None` is never returned.
+    ///
+    /// # Errors
+    /// - `Error::NoSpace`: A cluster could not be allocated.
+    /// - `Error::Corrupted`: The chain is invalid.
+    /// - `Error::IoError`: The FAT could not be read.
+    pub fn cluster(
+        &self,
+        state: &mut InodeState,
+        index: u32,
+        create: bool,
+    ) -> Result<Option<u32>, Error> {
+        let (mut position, mut cluster) = match state.cursor {
+            Some((position, cluster)) if position <= index => (position, cluster),
+            _ if state.cluster != 0 => (0, state.cluster),
+            _ if create => {
+                state.cluster = self.fs.alloc_cluster(None)?;
+                (0, state.cluster)
+            }
+            _ => return Ok(None),
+        };
+
+        while position < index {
+            cluster = match self.fs.next(cluster)? {
+                Some(next) => next,
+                None if create => self.fs.alloc_cluster(Some(cluster))?,
+                None => return Ok(None),
+            };
+            position += 1;
+        }
+
+        state.cursor = Some((index, cluster));
+        Ok(Some(cluster))
+    }
+
+    /// Returns the buffer of the sector containing the byte at the given position
+    /// of the content of the inode, and the offset of the byte in that buffer,
+    /// or `None` if the position is beyond the clusters of the inode. If `create`
+    /// is true, clusters are allocated up to the position.
+    ///
+    /// # Errors
+    /// See [`InodeInfo::cluster`].
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn sector(
+        &self,
+        state: &mut InodeState,
+        position: usize,
+        create: bool,
+    ) -> Result<Option<(Arc<Buffer>, usize)>, Error> {
+        let cluster_size = self.fs.cluster_size();
+        let sector_size = self.fs.sector_size();
+        let index = u32::try_from(position / cluster_size).map_err(|_| Error::NoSpace)?;
+        let Some(cluster) = self.cluster(state, index, create)? else {
+            return Ok(None);
+        };
+
+        let offset = position % cluster_size;
+        let sector = self.fs.cluster_sector(cluster) + (offset / sector_size) as u32;
+        Ok(Some((self.fs.sector(sector)?, offset % sector_size)))
+    }
+
+    /// Fill the content of the inode with zeroes between the given positions,
+    /// allocating the clusters needed. This is used to extend a file, since FAT
+    /// files cannot have holes.
+    ///
+    /// # Errors
+    /// See [`InodeInfo::cluster`].
+    pub fn zero(&self, state: &mut InodeState, from: usize, to: usize) -> Result<(), Error> {
+        let mut position = from;
+        while position < to {
+            let (buffer, offset) = self
+                .sector(state, position, true)?
+                .ok_or(Error::Corrupted)?;
+            let count = core::cmp::min(buffer.data().len() - offset, to - position);
+            buffer.data()[offset..offset + count].fill(0);
+            buffer.mark_dirty();
+            position += count;
+        }
+        Ok(())
+    }
+
+    /// Write the given VFS inode and its state into its directory entry. The root
+    /// directory and the removed inodes have no entry, and are not written.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the sector of the entry could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn store(&self, inode: &vfs::inode::Inode, state: &InodeState) -> Result<(), Error> {
+        let Some(location) = state.entry else {
+            return Ok(());
+        };
+
+        let (buffer, offset) = self.fs.entry(location)?;
+        let (mode, size, accessed, modified) = {
+            let metadata = inode.metadata.lock();
+            (
+                metadata.mode,
+                metadata.size,
+                metadata.access_time,
+                metadata.modification_time,
+            )
+        };
+
+        let mut data = buffer.data();
+        let raw = &mut data[offset..offset + disk::ENTRY_SIZE];
+        let mut entry = DirectoryEntry::parse(raw);
+        entry.attributes = match mode & 0o222 {
+            0 => state.attributes | disk::ATTR_READ_ONLY,
+            _ => state.attributes & !disk::ATTR_READ_ONLY,
+        };
+        entry.cluster = state.cluster;
+        entry.size = match inode.kind {
+            Kind::Directory => 0,
+            _ => size as u32,
+        };
+        (entry.modified_date, entry.modified_time) = timestamp(modified);
+        entry.accessed_date = timestamp(accessed).0;
+        entry.write(raw);
+        drop(data);
+        buffer.mark_dirty();
+        Ok(())
+    }
+}
+
+impl Drop for InodeInfo {
+    fn drop(&mut self) {
+        let (deleted, cluster) = {
+            let state = self.state.lock();
+            (state.deleted, state.cluster)
+        };
+        if !deleted {
+            return;
+        }
+        if cluster != 0 {
+            if let Err(error) = self.fs.free_chain(cluster) {
+                log::error!(
+                    "fat: failed to release the clusters of inode {}: {:?}",
+                    self.id.0,
+                    error
+                );
+            }
+        }
+        self.fs.release(self.id);
+    }
+}
+
+impl Fat {
+    /// Returns the identifier of the inode whose short entry is at the given
+    /// location.
+    pub fn identifier(&self, location: u64) -> Identifier {
+        let index = location / disk::ENTRY_SIZE as u64;
+        let mut identifiers = self.identifiers.lock();
+        if let Some(&id) = identifiers.moved.get(&index) {
+            return Identifier(id);
+        }
+        if !identifiers.reserved.contains_key(&index) {
+            return Identifier(index);
+        }
+
+        let id = identifiers.next;
+        identifiers.next += 1;
+        identifiers.moved.insert(index, id);
+        identifiers.reserved.insert(id, Some(index));
+        Identifier(id)
+    }
+
+    /// Returns the location of the short entry of the inode with the given
+    /// identifier, or `None` if its entry has been removed.
+    pub fn location(&self, id: Identifier) -> Option<u64> {
+        let index = match self.identifiers.lock().reserved.get(&id.0) {
+            Some(&index) => index?,
+            None => id.0,
+        };
+        Some(index * disk::ENTRY_SIZE as u64)
+    }
+
+    /// Record that the short entry of the inode with the given identifier moved
+    /// between the given locations.
+    pub fn moved(&self, id: Identifier, from: u64, to: u64) {
+        let (from, to) = (from / disk::ENTRY_SIZE as u64, to / disk::ENTRY_SIZE as u64);
+        let mut identifiers = self.identifiers.lock();
+        identifiers.moved.remove(&from);
+        if to == id.0 {
+            identifiers.reserved.remove(&id.0);
+        } else {
+            identifiers.moved.insert(to, id.0);
+            identifiers.reserved.insert(id.0, Some(to));
+        }
+    }
+
+    /// Record that the short entry of the inode with the given identifier, at the
+    /// given location, was removed. The identifier stays reserved until the
+    /// inode is released.
+    pub fn removed(&self, id: Identifier, location: u64) {
+        let mut identifiers = self.identifiers.lock();
+        identifiers
+            .moved
+            .remove(&(location / disk::ENTRY_SIZE as u64));
+        identifiers.reserved.insert(id.0, None);
+    }
+
+    /// Release the identifier of a removed inode, once it is not used anymore.
+    pub fn release(&self, id: Identifier) {
+        self.identifiers.lock().reserved.remove(&id.0);
+    }
+
+    /// Returns the maximal size of a file, in bytes.
+    #[must_use]
+    pub const fn max_file_size(&self) -> u64 {
+        u32::MAX as u64
+    }
+}
+
+/// Returns the FAT specific data of the given inode.
+///
+/// # Panics
+/// Panics if the inode is not a FAT inode.
+#[must_use]
+pub fn info(inode: &vfs::inode::Inode) -> &InodeInfo {
+    inode
+        .data
+        .downcast_ref::<InodeInfo>()
+        .expect("Inode is not a FAT inode")
+}
+
+/// Returns the mode of an inode with the given attributes.
+const fn mode(attributes: u8) -> u16 {
+    let mode = match attributes & disk::ATTR_DIRECTORY {
+        0 => 0o644,
+        _ => 0o755,
+    };
+    match attributes & disk::ATTR_READ_ONLY {
+        0 => mode,
+        _ => mode & !0o222,
+    }
+}
+
+/// Read the inode with the given identifier from its directory entry and create
+/// its VFS inode. The size of a directory is the size of its clusters, and its
+/// number of links counts its subdirectories like on Linux.
+///
+/// # Errors
+/// - `ReadInodeError::DoesNotExist`: The entry of the inode has been removed.
+/// - `ReadInodeError::IoError`: The entry could not be read, or it is not in use
+///   or corrupted: an identifier always comes from a directory entry, so such an
+///   entry means that the filesystem is inconsistent.
+pub fn read_inode(
+    superblock: &Super,
+    id: Identifier,
+) -> Result<Arc<vfs::inode::Inode>, ReadInodeError> {
+    let fs = fat(superblock);
+    let (entry, location) = if id == ROOT {
+        let entry = DirectoryEntry {
+            attributes: disk::ATTR_DIRECTORY,
+            cluster: fs.root_cluster,
+            ..DirectoryEntry::default()
+        };
+        (entry, None)
+    } else {
+        let location = fs.location(id).ok_or(ReadInodeError::DoesNotExist)?;
+        let (buffer, offset) = fs.entry(location).map_err(|_| ReadInodeError::IoError)?;
+        let data = buffer.data();
+        let raw = &data[offset..offset + disk::ENTRY_SIZE];
+        if matches!(raw[0], disk::ENTRY_FREE | disk::ENTRY_END)
+            || disk::is_long_entry(raw)
+            || raw[11] & disk::ATTR_VOLUME_ID != 0
+        {
+            log::warn!("fat: inode {} is referenced but not in use", id.0);
+            return Err(ReadInodeError::IoError);
+        }
+        (DirectoryEntry::parse(raw), Some(location))
+    };
+
+    let cluster = fs.first_cluster(&entry);
+    if cluster != 0 && !fs.is_valid(cluster) {
+        log::warn!("fat: inode {} has an invalid cluster {}", id.0, cluster);
+        return Err(ReadInodeError::IoError);
+    }
+
+    let (kind, inode_ops, file_ops, size, links) = if entry.is_directory() {
+        let size = match cluster {
+            0 if id == ROOT => fs.root_sectors as usize * fs.sector_size(),
+            0 => {
+                log::warn!("fat: directory {} has no cluster", id.0);
+                return Err(ReadInodeError::IoError);
+            }
+            _ => {
+                let clusters = fs
+                    .chain_length(cluster)
+                    .map_err(|_| ReadInodeError::IoError)?;
+                clusters as usize * fs.cluster_size()
+            }
+        };
+        let subdirectories =
+            dir::count_subdirectories(fs, cluster).map_err(|_| ReadInodeError::IoError)?;
+        (
+            Kind::Directory,
+            vfs::inode::Operation::Directory(&dir::INODE_OPS),
+            vfs::file::Operation::Directory(&dir::FILE_OPS),
+            size,
+            2 + subdirectories,
+        )
+    } else {
+        (
+            Kind::File,
+            vfs::inode::Operation::File(&INODE_FILE_OPS),
+            vfs::file::Operation::File(&file::FILE_OPS),
+            entry.size as usize,
+            1,
+        )
+    };
+
+    let (modification_time, access_time) = match location {
+        Some(_) => (
+            time(entry.modified_date, entry.modified_time),
+            time(entry.accessed_date, 0),
+        ),
+        None => (UnixTime(Second(0)), UnixTime(Second(0))),
+    };
+
+    let metadata = InodeMetadata {
+        modification_time,
+        access_time,
+        change_time: modification_time,
+        links,
+        size,
+        mode: mode(entry.attributes),
+    };
+
+    let info = InodeInfo {
+        fs: Arc::clone(fs),
+        id,
+        state: Mutex::new(InodeState {
+            entry: location,
+            cluster,
+            attributes: entry.attributes & !disk::ATTR_READ_ONLY,
+            cursor: None,
+            deleted: false,
+        }),
+    };
+
+    Ok(Arc::new(vfs::inode::Inode::new(
+        fs.superblock(),
+        InodeCreateInfo {
+            id,
+            device: Device::Block(fs.device),
+            kind,
+            inode_ops,
+            file_ops,
+            metadata,
+            data: Box::new(info),
+        },
+    )))
+}
+
+/// Write the given inode into its directory entry.
+///
+/// # Errors
+/// Returns `WriteInodeError::IoError` if the entry could not be read.
+pub fn write_inode(inode: &vfs::inode::Inode) -> Result<(), WriteInodeError> {
+    let info = info(inode);
+    let state = info.lock();
+    info.store(inode, &state)
+        .map_err(|_| WriteInodeError::IoError)
+}
+
+/// Truncate the file to the given size. The clusters beyond the new size are
+/// released, and a file that is extended is filled with zeroes, since FAT files
+/// cannot have holes.
+///
+/// # Errors
+/// - `TruncateError::NoSpace`: The size exceeds the maximal size of a file, or
+///   there is no free cluster left to extend the file.
+/// - `TruncateError::IoError`: The FAT or the entry could not be read.
+#[allow(clippy::cast_possible_truncation)]
+fn truncate(inode: &vfs::inode::Inode, size: usize) -> Result<usize, vfs::inode::TruncateError> {
+    let info = info(inode);
+    if size as u64 > info.fs.max_file_size() {
+        return Err(vfs::inode::TruncateError::NoSpace);
+    }
+
+    let mut state = info.lock();
+    let current = inode.metadata.lock().size;
+    if size > current {
+        let result = info.zero(&mut state, current, size);
+        if result.is_err() {
+            // Release the clusters allocated before the error.
+            state.cursor = None;
+            let keep = (current + info.fs.cluster_size() - 1) / info.fs.cluster_size();
+            shrink(info, &mut state, keep as u32)?;
+        }
+        result?;
+    } else {
+        let keep = (size + info.fs.cluster_size() - 1) / info.fs.cluster_size();
+        shrink(info, &mut state, keep as u32)?;
+    }
+
+    {
+        let mut metadata = inode.metadata.lock();
+        metadata.modification_time = vfs_now();
+        metadata.change_time = vfs_now();
+        metadata.size = size;
+    }
+    info.store(inode, &state)?;
+    Ok(size)
+}
+
+/// Release the clusters of the inode after the `keep` first ones.
+fn shrink(info: &InodeInfo, state: &mut InodeState, keep: u32) -> Result<(), Error> {
+    state.cursor = None;
+    if keep == 0 {
+        if state.cluster != 0 {
+            info.fs.free_chain(state.cluster)?;
+            state.cluster = 0;
+        }
+    } else if let Some(last) = info.cluster(state, keep - 1, false)? {
+        info.fs.truncate_chain(last)?;
+    }
+    Ok(())
+}
+
+impl From<Error> for vfs::inode::TruncateError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NoSpace => Self::NoSpace,
+            Error::InvalidName | Error::Corrupted | Error::IoError => Self::IoError,
+        }
+    }
+}
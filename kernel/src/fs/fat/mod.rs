@@ -0,0 +1,467 @@
+//! The FAT filesystem, in its FAT12, FAT16 and FAT32 variants, with the long
+//! file names of VFAT. The driver reads and writes the filesystem through the
+//! buffer cache, using buffers of the size of a sector of the filesystem: the
+//! FATs and the directory entries are modified in place in the cached sectors,
+//! and reach the device when the buffers are written back.
+//!
+//! FAT has no inodes: the metadata of a file is stored in its directory entry.
+//! The identifier of an inode is therefore derived from the position of its
+//! short entry on the device, which does not change unless the file is renamed
+//! (see [`inode::Identifiers`]). The root directory, which has no entry, uses
+//! the identifier [`ROOT`].
+use crate::{
+    device::{
+        self,
+        block::{cache, IoError},
+        Device, Identifier,
+    },
+    time::{date::Date, unix::UnixTime},
+    user::task::mutex::Mutex,
+    vfs::{
+        self,
+        mount::{Super, SuperCreationInfo},
+    },
+};
+use alloc::sync::Weak;
+use disk::{BootSector, Kind};
+
+pub mod dir;
+pub mod disk;
+pub mod file;
+pub mod inode;
+pub mod table;
+
+/// Operations that can be performed on the filesystem.
+pub static FS_OPS: vfs::fs::Operation = vfs::fs::Operation { read_super };
+
+/// Operations that can be performed on the superblock.
+pub static SUPER_OPS: vfs::mount::Operation = vfs::mount::Operation {
+    write_super,
+    write_inode: inode::write_inode,
+    read_inode: inode::read_inode,
+};
+
+/// The identifier of the root directory. No directory entry can be stored at
+/// the start of the device, since it contains the boot sector.
+pub const ROOT: vfs::inode::Identifier = vfs::inode::Identifier(0);
+
+/// A mounted FAT filesystem.
+pub struct Fat {
+    /// The block device on which the filesystem is stored.
+    device: Identifier,
+
+    /// The variant of FAT used by the filesystem.
+    kind: Kind,
+
+    /// The size of a sector of the filesystem, in bytes. It may be larger than
+    /// the size of a sector of the device.
+    sector_size: usize,
+
+    /// The number of sectors of a cluster, and the size of a cluster in bytes.
+    sectors_per_cluster: u32,
+    cluster_size: usize,
+
+    /// The first sector of the first FAT, the size of a FAT in sectors, and the
+    /// number of FATs.
+    fat_start: u32,
+    fat_size: u32,
+    fats: u32,
+
+    /// The FAT in use if the FATs are not mirrored, or `None` if all the FATs
+    /// are kept identical.
+    active_fat: Option<u32>,
+
+    /// The first sector and the number of sectors of the root directory of
+    /// FAT12 and FAT16 filesystems, which is not stored in clusters.
+    root_start: u32,
+    root_sectors: u32,
+
+    /// The first cluster of the root directory of FAT32 filesystems, or 0 on
+    /// FAT12 and FAT16 filesystems.
+    root_cluster: u32,
+
+    /// The first sector of the cluster 2, the first cluster of the data region.
+    data_start: u32,
+
+    /// The number of clusters of the data region.
+    clusters: u32,
+
+    /// The sector of the `FSInfo` structure of FAT32 filesystems, if any.
+    fs_info: Option<u32>,
+
+    /// The offset of the state byte in the boot sector, if any.
+    state: Option<usize>,
+
+    /// The VFS superblock of the filesystem, needed to create VFS inodes.
+    superblock: Once<Weak<Super>>,
+
+    /// Serializes the allocations and releases of clusters, and keeps track of
+    /// the free clusters.
+    allocation: Mutex<table::Allocation>,
+
+    /// The identifiers of the inodes that moved away from their original short
+    /// entry.
+    identifiers: Spinlock<inode::Identifiers>,
+}
+
+impl Fat {
+    /// Returns the size of a cluster, in bytes.
+    #[must_use]
+    pub const fn cluster_size(&self) -> usize {
+        self.cluster_size
+    }
+
+    /// Returns the size of a sector of the filesystem, in bytes.
+    #[must_use]
+    pub const fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    /// Returns the buffer containing the given sector of the filesystem.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the sector could not be read.
+    pub fn sector(&self, sector: u32) -> Result<Arc<cache::Buffer>, Error> {
+        Ok(cache::read(
+            self.device,
+            u64::from(sector),
+            self.sector_size,
+        )?)
+    }
+
+    /// Returns the buffer containing the given sector of the filesystem, filled
+    /// with zeroes and marked dirty.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the sector is out of the device.
+    pub fn zeroed_sector(&self, sector: u32) -> Result<Arc<cache::Buffer>, Error> {
+        let buffer = cache::get_zeroed(self.device, u64::from(sector), self.sector_size)?;
+        buffer.mark_dirty();
+        Ok(buffer)
+    }
+
+    /// Returns the first sector of the given cluster.
+    #[must_use]
+    pub const fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.sectors_per_cluster
+    }
+
+    /// Returns the buffer containing the directory entry at the given location
+    /// on the device, and the offset of the entry in that buffer.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the sector could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn entry(&self, location: u64) -> Result<(Arc<cache::Buffer>, usize), Error> {
+        let sector = location / self.sector_size as u64;
+        let offset = location % self.sector_size as u64;
+        Ok((self.sector(sector as u32)?, offset as usize))
+    }
+
+    /// Returns the first cluster stored in the given directory entry. The high
+    /// half of the cluster number is only used on FAT32.
+    #[must_use]
+    pub fn first_cluster(&self, entry: &disk::DirectoryEntry) -> u32 {
+        match self.kind {
+            Kind::Fat32 => entry.cluster & 0x0FFF_FFFF,
+            Kind::Fat12 | Kind::Fat16 => entry.cluster & 0xFFFF,
+        }
+    }
+
+    /// Returns the VFS superblock of the filesystem.
+    #[must_use]
+    pub fn superblock(&self) -> Weak<Super> {
+        self.superblock.get().cloned().unwrap_or_default()
+    }
+
+    /// Write the free cluster count and the next free cluster hint into the
+    /// `FSInfo` sector, if the filesystem has one.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the `FSInfo` sector could not be read.
+    fn write_fs_info(&self) -> Result<(), Error> {
+        let Some(sector) = self.fs_info else {
+            return Ok(());
+        };
+        let allocation = self.allocation.lock();
+        let buffer = self.sector(sector)?;
+        let mut data = buffer.data();
+        disk::set_u32(
+            &mut data,
+            disk::FSINFO_FREE_COUNT,
+            allocation.free.unwrap_or(disk::FSINFO_UNKNOWN),
+        );
+        disk::set_u32(&mut data, disk::FSINFO_NEXT_FREE, allocation.next);
+        drop(data);
+        buffer.mark_dirty();
+        Ok(())
+    }
+}
+
+impl Drop for Fat {
+    /// Update the `FSInfo` sector and mark the filesystem as cleanly unmounted.
+    /// This happens once all the inodes of the filesystem have been written
+    /// back and released.
+    fn drop(&mut self) {
+        _ = self.write_fs_info();
+        if let (Some(offset), Ok(buffer)) = (self.state, self.sector(0)) {
+            buffer.data()[offset] &= !disk::STATE_DIRTY;
+            buffer.mark_dirty();
+        }
+    }
+}
+
+/// The errors that can occur inside the driver. They are converted to the errors
+/// of the VFS operations at the boundary of the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// There is no free cluster or directory entry left, or the file is too large.
+    NoSpace,
+
+    /// The name cannot be stored in a directory entry.
+    InvalidName,
+
+    /// A structure of the filesystem is invalid.
+    Corrupted,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
+}
+
+impl From<IoError> for Error {
+    fn from(_: IoError) -> Self {
+        Error::IoError
+    }
+}
+
+/// Register the FAT filesystem into the VFS.
+pub fn register() {
+    vfs::fs::register(vfs::fs::Filesystem::new("vfat", &FS_OPS, Box::new(())));
+}
+
+/// Returns the FAT filesystem of the given VFS superblock.
+///
+/// # Panics
+/// Panics if the superblock is not a FAT superblock.
+#[must_use]
+pub fn fat(superblock: &Super) -> &Arc<Fat> {
+    superblock
+        .data()
+        .downcast_ref::<Arc<Fat>>()
+        .expect("Superblock is not a FAT superblock")
+}
+
+/// Returns the current time, truncated to the precision of the modification
+/// times of FAT, so that the timestamps of the VFS inodes match the stored ones.
+#[must_use]
+pub fn vfs_now() -> UnixTime {
+    let (date, time) = timestamp(UnixTime::now());
+    self::time(date, time)
+}
+
+/// Convert a date and a time of the day stored in the FAT format into an Unix
+/// time. Like the CMOS clock, FAT stores local times, which the kernel assumes
+/// to be the same as UTC.
+#[must_use]
+pub fn time(date: u16, time: u16) -> UnixTime {
+    disk::decode_timestamp(date, time).to_unix_time()
+}
+
+/// Convert an Unix time into a date and a time of the day in the FAT format.
+#[must_use]
+pub fn timestamp(time: UnixTime) -> (u16, u16) {
+    disk::encode_timestamp(&Date::from(time))
+}
+
+/// Read the boot sector of the FAT filesystem stored on the given block device,
+/// and mark the filesystem as mounted.
+///
+/// # Errors
+/// - `ReadSuperError::InvalidDevice`: The device is not a registered block device.
+/// - `ReadSuperError::IoError`: The boot sector could not be read.
+/// - `ReadSuperError::InvalidFileSystem`: The device does not contain a FAT
+///   filesystem, or its sectors are smaller than the sectors of the device.
+/// - `ReadSuperError::CorruptedFileSystem`: The boot sector is inconsistent.
+#[allow(clippy::too_many_lines, clippy::cast_possible_truncation)]
+fn read_super(
+    _: &vfs::fs::Filesystem,
+    device: Device,
+) -> Result<Arc<Super>, vfs::fs::ReadSuperError> {
+    let Device::Block(id) = device else {
+        return Err(vfs::fs::ReadSuperError::InvalidDevice);
+    };
+    let block_device = device::block::get(id).ok_or(vfs::fs::ReadSuperError::InvalidDevice)?;
+
+    // The boot sector is read directly from the device, since the size of the
+    // buffers used with the cache is only known once it has been read.
+    let device_sector = block_device.sector_size();
+    let data = device::block::read(&block_device, 0, core::cmp::max(device_sector, 512))
+        .map_err(|_| vfs::fs::ReadSuperError::IoError)?;
+    let boot = BootSector::parse(&data);
+
+    let sector_size = usize::from(boot.bytes_per_sector);
+    if boot.signature != disk::BOOT_SIGNATURE
+        || !(512..=4096).contains(&sector_size)
+        || !sector_size.is_power_of_two()
+        || !boot.sectors_per_cluster.is_power_of_two()
+        || boot.reserved_sectors == 0
+        || boot.fats == 0
+        || (boot.media != 0xF0 && boot.media < 0xF8)
+    {
+        return Err(vfs::fs::ReadSuperError::InvalidFileSystem);
+    }
+    if sector_size % device_sector != 0 {
+        log::warn!(
+            "fat: sectors of {} bytes are smaller than the device sectors",
+            sector_size
+        );
+        return Err(vfs::fs::ReadSuperError::InvalidFileSystem);
+    }
+
+    let total = boot.total_sectors();
+    let fat_size = boot.fat_size();
+    let fats = u32::from(boot.fats);
+    let root_bytes = u32::from(boot.root_entries) * disk::ENTRY_SIZE as u32;
+    let root_sectors = (root_bytes + sector_size as u32 - 1) / sector_size as u32;
+    let (root_start, data_start) = fats
+        .checked_mul(fat_size)
+        .and_then(|size| size.checked_add(u32::from(boot.reserved_sectors)))
+        .and_then(|root_start| Some((root_start, root_start.checked_add(root_sectors)?)))
+        .ok_or(vfs::fs::ReadSuperError::CorruptedFileSystem)?;
+    if fat_size == 0 || data_start >= total {
+        return Err(vfs::fs::ReadSuperError::CorruptedFileSystem);
+    }
+
+    let sectors_per_cluster = u32::from(boot.sectors_per_cluster);
+    let clusters = (total - data_start) / sectors_per_cluster;
+    let kind = Kind::from_clusters(clusters);
+    let layout_valid = match kind {
+        Kind::Fat32 => {
+            boot.root_entries == 0
+                && boot.fat_size_16 == 0
+                && boot.fs_version == 0
+                && (2..=clusters + 1).contains(&boot.root_cluster)
+        }
+        Kind::Fat12 | Kind::Fat16 => boot.root_entries != 0,
+    };
+
+    // The FATs must be able to describe all the clusters, and the filesystem
+    // must fit on the device.
+    let fat_entries = u64::from(fat_size) * sector_size as u64 * 8 / u64::from(kind.bits());
+    let device_bytes = block_device.capacity() * device_sector as u64;
+    if !layout_valid
+        || fat_entries < u64::from(clusters) + 2
+        || u64::from(total) * sector_size as u64 > device_bytes
+    {
+        return Err(vfs::fs::ReadSuperError::CorruptedFileSystem);
+    }
+
+    let active_fat = match boot.ext_flags & disk::MIRRORING_DISABLED {
+        0 => None,
+        _ => Some(u32::from(boot.ext_flags & disk::ACTIVE_FAT_MASK)),
+    };
+    if active_fat.is_some_and(|active| active >= fats) {
+        return Err(vfs::fs::ReadSuperError::CorruptedFileSystem);
+    }
+
+    let state = match kind {
+        Kind::Fat32 => BootSector::STATE_32,
+        Kind::Fat12 | Kind::Fat16 => BootSector::STATE_16,
+    };
+    let state = disk::EXTENDED_BOOT_SIGNATURES
+        .contains(&data[state + 1])
+        .then_some(state);
+
+    // The free cluster count and the next free cluster hint of the FSInfo sector
+    // are only hints, and are ignored if they are out of range.
+    let mut allocation = table::Allocation::new();
+    let fs_info = match kind {
+        Kind::Fat32 => Some(u32::from(boot.fs_info))
+            .filter(|&sector| sector != 0 && sector < u32::from(boot.reserved_sectors)),
+        Kind::Fat12 | Kind::Fat16 => None,
+    };
+    let fs_info = match fs_info {
+        Some(sector) => {
+            let buffer = cache::read(id, u64::from(sector), sector_size)
+                .map_err(|_| vfs::fs::ReadSuperError::IoError)?;
+            let data = buffer.data();
+            let valid = disk::get_u32(&data, 0) == disk::FSINFO_LEAD_SIGNATURE
+                && disk::get_u32(&data, 484) == disk::FSINFO_STRUCT_SIGNATURE;
+            if valid {
+                let free = disk::get_u32(&data, disk::FSINFO_FREE_COUNT);
+                let next = disk::get_u32(&data, disk::FSINFO_NEXT_FREE);
+                allocation.free = Some(free).filter(|&free| free <= clusters);
+                if (2..clusters + 2).contains(&next) {
+                    allocation.next = next;
+                }
+            }
+            valid.then_some(sector)
+        }
+        None => None,
+    };
+
+    let fat = Arc::new(Fat {
+        device: id,
+        kind,
+        sector_size,
+        sectors_per_cluster,
+        cluster_size: sector_size * sectors_per_cluster as usize,
+        fat_start: u32::from(boot.reserved_sectors),
+        fat_size,
+        fats,
+        active_fat,
+        root_start,
+        root_sectors,
+        root_cluster: boot.root_cluster,
+        data_start,
+        clusters,
+        fs_info,
+        state,
+        superblock: Once::new(),
+        allocation: Mutex::new(allocation),
+        identifiers: Spinlock::new(inode::Identifiers::new()),
+    });
+
+    // Mark the filesystem as dirty until it is unmounted, so that fsck.fat
+    // checks it if the system crashes in the meantime.
+    if let Some(offset) = fat.state {
+        let buffer = fat
+            .sector(0)
+            .map_err(|_| vfs::fs::ReadSuperError::IoError)?;
+        let mut data = buffer.data();
+        if data[offset] & disk::STATE_DIRTY != 0 {
+            log::warn!("fat: mounting an unchecked filesystem, running fsck.fat is recommended");
+        }
+        data[offset] |= disk::STATE_DIRTY;
+        drop(data);
+        buffer.mark_dirty();
+    }
+
+    log::info!(
+        "fat: {:?} filesystem with {} clusters of {} bytes",
+        kind,
+        clusters,
+        fat.cluster_size
+    );
+
+    let superblock = Arc::new(Super::new(SuperCreationInfo {
+        device,
+        operation: &SUPER_OPS,
+        root: ROOT,
+        data: Box::new(Arc::clone(&fat)),
+    }));
+    fat.superblock.call_once(|| Arc::downgrade(&superblock));
+    Ok(superblock)
+}
+
+/// Write the `FSInfo` sector of the filesystem. The FATs and the directory entries
+/// are modified in place in the cached sectors, so this is the only structure
+/// that needs to be written here.
+///
+/// # Errors
+/// Returns `WriteSuperError::IoError` if the `FSInfo` sector could not be read.
+fn write_super(superblock: &Super) -> Result<(), vfs::mount::WriteSuperError> {
+    fat(superblock)
+        .write_fs_info()
+        .map_err(|_| vfs::mount::WriteSuperError::IoError)
+}
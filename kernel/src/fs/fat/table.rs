@@ -0,0 +1,263 @@
+//! The file allocation table. Each cluster of the data region has an entry in
+//! the FAT, containing the next cluster of the chain it belongs to, an end of
+//! chain marker, or zero if the cluster is free. Entries are 12, 16 or 32 bits
+//! wide depending on the variant of FAT, and the 4 high bits of the FAT32
+//! entries are reserved and preserved when an entry is written.
+//!
+//! All the FATs are updated when an entry is written, unless the mirroring is
+//! disabled on a FAT32 filesystem, in which case only the active FAT is used.
+use super::{disk::Kind, Error, Fat};
+
+/// The state of the cluster allocator.
+pub struct Allocation {
+    /// The number of free clusters, if known.
+    pub free: Option<u32>,
+
+    /// The cluster from which the search for a free cluster starts.
+    pub next: u32,
+}
+
+impl Allocation {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            free: None,
+            next: 2,
+        }
+    }
+}
+
+impl Fat {
+    /// Returns the sector and the offset in that sector of the given byte of the
+    /// given FAT.
+    #[allow(clippy::cast_possible_truncation)]
+    fn fat_position(&self, fat: u32, byte: usize) -> (u32, usize) {
+        let sector = self.fat_start + fat * self.fat_size + (byte / self.sector_size) as u32;
+        (sector, byte % self.sector_size)
+    }
+
+    /// Returns the byte offset of the entry of the given cluster in a FAT.
+    fn fat_offset(&self, cluster: u32) -> usize {
+        let cluster = cluster as usize;
+        match self.kind {
+            Kind::Fat12 => cluster + cluster / 2,
+            Kind::Fat16 => cluster * 2,
+            Kind::Fat32 => cluster * 4,
+        }
+    }
+
+    /// Read the given bytes of the given FAT. A FAT12 entry may span two sectors,
+    /// so the bytes are read one by one.
+    fn read_fat(&self, fat: u32, offset: usize, bytes: &mut [u8]) -> Result<(), Error> {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let (sector, position) = self.fat_position(fat, offset + i);
+            *byte = self.sector(sector)?.data()[position];
+        }
+        Ok(())
+    }
+
+    /// Write the given bytes into the given FAT, keeping the bits of the existing
+    /// bytes that are not set in the given masks.
+    fn write_fat(&self, fat: u32, offset: usize, bytes: &[u8], masks: &[u8]) -> Result<(), Error> {
+        for (i, (&byte, &mask)) in bytes.iter().zip(masks).enumerate() {
+            let (sector, position) = self.fat_position(fat, offset + i);
+            let buffer = self.sector(sector)?;
+            let mut data = buffer.data();
+            data[position] = (data[position] & !mask) | (byte & mask);
+            drop(data);
+            buffer.mark_dirty();
+        }
+        Ok(())
+    }
+
+    /// Returns the value of the FAT entry of the given cluster.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the FAT could not be read.
+    pub fn get(&self, cluster: u32) -> Result<u32, Error> {
+        let fat = self.active_fat.unwrap_or(0);
+        let offset = self.fat_offset(cluster);
+        let mut bytes = [0; 4];
+        match self.kind {
+            Kind::Fat12 => {
+                self.read_fat(fat, offset, &mut bytes[..2])?;
+                let value = u32::from(u16::from_le_bytes([bytes[0], bytes[1]]));
+                Ok(match cluster % 2 {
+                    0 => value & 0x0FFF,
+                    _ => value >> 4,
+                })
+            }
+            Kind::Fat16 => {
+                self.read_fat(fat, offset, &mut bytes[..2])?;
+                Ok(u32::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+            }
+            Kind::Fat32 => {
+                self.read_fat(fat, offset, &mut bytes)?;
+                Ok(u32::from_le_bytes(bytes) & 0x0FFF_FFFF)
+            }
+        }
+    }
+
+    /// Set the FAT entry of the given cluster in all the FATs in use.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if a FAT could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn set(&self, cluster: u32, value: u32) -> Result<(), Error> {
+        let offset = self.fat_offset(cluster);
+        let (bytes, masks, len): ([u8; 4], [u8; 4], usize) = match self.kind {
+            Kind::Fat12 if cluster % 2 == 0 => {
+                let value = (value & 0x0FFF) as u16;
+                (
+                    [value as u8, (value >> 8) as u8, 0, 0],
+                    [0xFF, 0x0F, 0, 0],
+                    2,
+                )
+            }
+            Kind::Fat12 => {
+                let value = ((value & 0x0FFF) << 4) as u16;
+                (
+                    [value as u8, (value >> 8) as u8, 0, 0],
+                    [0xF0, 0xFF, 0, 0],
+                    2,
+                )
+            }
+            Kind::Fat16 => (u32::from(value as u16).to_le_bytes(), [0xFF, 0xFF, 0, 0], 2),
+            Kind::Fat32 => (
+                (value & 0x0FFF_FFFF).to_le_bytes(),
+                [0xFF, 0xFF, 0xFF, 0x0F],
+                4,
+            ),
+        };
+
+        match self.active_fat {
+            Some(fat) => self.write_fat(fat, offset, &bytes[..len], &masks[..len]),
+            None => (0..self.fats)
+                .try_for_each(|fat| self.write_fat(fat, offset, &bytes[..len], &masks[..len])),
+        }
+    }
+
+    /// Returns whether the given cluster is a cluster of the data region.
+    #[must_use]
+    pub fn is_valid(&self, cluster: u32) -> bool {
+        (2..self.clusters + 2).contains(&cluster)
+    }
+
+    /// Returns the cluster following the given one in its chain, or `None` if
+    /// it is the last cluster of the chain.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The cluster is free, bad, or its entry points
+    ///   outside of the data region.
+    /// - `Error::IoError`: The FAT could not be read.
+    pub fn next(&self, cluster: u32) -> Result<Option<u32>, Error> {
+        match self.get(cluster)? {
+            next if next > self.kind.bad() => Ok(None),
+            next if self.is_valid(next) => Ok(Some(next)),
+            _ => Err(Error::Corrupted),
+        }
+    }
+
+    /// Returns the number of clusters of the chain starting at the given cluster.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The chain is invalid or loops.
+    /// - `Error::IoError`: The FAT could not be read.
+    pub fn chain_length(&self, first: u32) -> Result<u32, Error> {
+        if !self.is_valid(first) {
+            return Err(Error::Corrupted);
+        }
+        let mut length = 1;
+        let mut cluster = first;
+        while let Some(next) = self.next(cluster)? {
+            if length >= self.clusters {
+                return Err(Error::Corrupted);
+            }
+            length += 1;
+            cluster = next;
+        }
+        Ok(length)
+    }
+
+    /// Allocate a free cluster, fill it with zeroes and mark it as the end of a
+    /// chain. If `previous` is given, the new cluster is appended after it.
+    ///
+    /// # Errors
+    /// - `Error::NoSpace`: There is no free cluster left.
+    /// - `Error::IoError`: The FAT could not be read.
+    pub fn alloc_cluster(&self, previous: Option<u32>) -> Result<u32, Error> {
+        let mut allocation = self.allocation.lock();
+        if allocation.free == Some(0) {
+            return Err(Error::NoSpace);
+        }
+
+        let start = allocation.next;
+        let mut cluster = start;
+        loop {
+            if self.get(cluster)? == 0 {
+                break;
+            }
+            cluster = if cluster + 1 < self.clusters + 2 {
+                cluster + 1
+            } else {
+                2
+            };
+            if cluster == start {
+                allocation.free = Some(0);
+                return Err(Error::NoSpace);
+            }
+        }
+
+        for sector in 0..self.sectors_per_cluster {
+            self.zeroed_sector(self.cluster_sector(cluster) + sector)?;
+        }
+        self.set(cluster, self.kind.end_of_chain())?;
+        if let Some(previous) = previous {
+            self.set(previous, cluster)?;
+        }
+
+        allocation.next = if cluster + 1 < self.clusters + 2 {
+            cluster + 1
+        } else {
+            2
+        };
+        allocation.free = allocation.free.map(|free| free.saturating_sub(1));
+        Ok(cluster)
+    }
+
+    /// Release all the clusters of the chain starting at the given cluster.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The chain is invalid.
+    /// - `Error::IoError`: The FAT could not be read.
+    pub fn free_chain(&self, first: u32) -> Result<(), Error> {
+        let mut allocation = self.allocation.lock();
+        let mut cluster = Some(first);
+        let mut freed = 0;
+        while let Some(current) = cluster {
+            if !self.is_valid(current) || freed >= self.clusters {
+                return Err(Error::Corrupted);
+            }
+            cluster = self.next(current)?;
+            self.set(current, 0)?;
+            allocation.free = allocation.free.map(|free| free + 1);
+            freed += 1;
+        }
+        Ok(())
+    }
+
+    /// Make the given cluster the last cluster of its chain, and release the
+    /// clusters following it.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: The chain is invalid.
+    /// - `Error::IoError`: The FAT could not be read.
+    pub fn truncate_chain(&self, last: u32) -> Result<(), Error> {
+        let next = self.next(last)?;
+        self.set(last, self.kind.end_of_chain())?;
+        match next {
+            Some(next) => self.free_chain(next),
+            None => Ok(()),
+        }
+    }
+}
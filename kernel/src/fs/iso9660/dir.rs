@@ -0,0 +1,303 @@
+//! Directories of an ISO9660 filesystem. A directory is an extent of records,
+//! each describing a file. Records never span two logical sectors: the end of a
+//! sector that cannot hold the next record is filled with zeroes.
+//!
+//! The `.` and `..` records, associated files and relocated directories are
+//! hidden. A file stored in several extents has a record for each of them, and
+//! only its first record is shown.
+use super::{disk, inode::info, rock, Error, Extension, Iso9660};
+use crate::{
+    device::Device,
+    vfs::{
+        self,
+        inode::{
+            CreateError, Identifier, Inode, LinkError, LookupError, MkdirError, RenameError,
+            RmdirError, UnlinkError,
+        },
+    },
+};
+
+/// Operations that can be performed on a directory inode.
+pub static INODE_OPS: vfs::inode::DirectoryOperation = vfs::inode::DirectoryOperation {
+    mknod,
+    create,
+    lookup,
+    unlink,
+    mkdir,
+    rmdir,
+    link,
+    rename,
+    symlink,
+};
+
+/// Operations that can be performed on an opened directory.
+pub static FILE_OPS: vfs::file::DirectoryOperation = vfs::file::DirectoryOperation { readdir };
+
+/// A file of a directory.
+pub struct Entry {
+    /// The location of the record of the file on the device.
+    pub location: u64,
+
+    /// The record of the file.
+    pub record: disk::DirectoryRecord,
+
+    /// The Rock Ridge attributes of the file, if the volume uses Rock Ridge.
+    pub rock: Option<rock::Attributes>,
+
+    /// The name of the file.
+    pub name: String,
+}
+
+impl Entry {
+    /// Returns whether the entry is a directory, including the files standing
+    /// for a relocated directory.
+    #[must_use]
+    pub fn is_directory(&self) -> bool {
+        match &self.rock {
+            Some(rock) if rock.child.is_some() => true,
+            Some(rock) if rock.mode.is_some() => {
+                rock.mode.map(|mode| mode & disk::S_IFMT) == Some(disk::S_IFDIR)
+            }
+            _ => self.record.is_directory(),
+        }
+    }
+}
+
+/// Call the given closure on each visible file of the directory whose extent
+/// starts at the given logical block and has the given size, until the closure
+/// returns a value. Returns that value, or `None` if the closure returned `None`
+/// for all files.
+///
+/// # Errors
+/// - `Error::Corrupted`: A record of the directory is invalid.
+/// - `Error::IoError`: A sector of the directory could not be read.
+#[allow(clippy::cast_possible_truncation)]
+pub fn scan<T>(
+    fs: &Iso9660,
+    extent: u32,
+    size: u32,
+    mut f: impl FnMut(Entry) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    let sector_size = disk::SECTOR_SIZE as u64;
+    let start = fs.position(extent);
+    let end = start + u64::from(size);
+    let mut position = start;
+    let mut multi_extent = false;
+
+    while position < end {
+        let sector = position / sector_size;
+        let last = core::cmp::min(end - sector * sector_size, sector_size) as usize;
+
+        // The records are parsed before being processed, since processing them
+        // may read a continuation area stored in the same sector.
+        let mut records = Vec::new();
+        {
+            let buffer = fs.sector(sector)?;
+            let data = buffer.data();
+            let mut offset = (position % sector_size) as usize;
+            while offset < last && data[offset] != 0 {
+                let record =
+                    disk::DirectoryRecord::parse(&data[offset..last]).ok_or(Error::Corrupted)?;
+                records.push((sector * sector_size + offset as u64, record));
+                offset += usize::from(data[offset]);
+            }
+        }
+
+        for (location, record) in records {
+            let continuation = multi_extent;
+            multi_extent = record.flags & disk::FLAG_MULTI_EXTENT != 0;
+            if continuation || record.is_dot() || record.flags & disk::FLAG_ASSOCIATED != 0 {
+                continue;
+            }
+
+            let rock = fs.rock_ridge(&record)?;
+            if rock.as_ref().is_some_and(|rock| rock.relocated) {
+                continue;
+            }
+            let name = rock
+                .as_ref()
+                .and_then(|rock| rock.name.clone())
+                .unwrap_or_else(|| fs.name(&record.identifier));
+            let entry = Entry {
+                location,
+                record,
+                rock,
+                name,
+            };
+            if let Some(value) = f(entry) {
+                return Ok(Some(value));
+            }
+        }
+        position = (sector + 1) * sector_size;
+    }
+    Ok(None)
+}
+
+/// Returns the number of subdirectories of the given directory.
+///
+/// # Errors
+/// See [`scan`].
+pub fn count_subdirectories(fs: &Iso9660, extent: u32, size: u32) -> Result<u64, Error> {
+    let mut count = 0;
+    scan(fs, extent, size, |entry| {
+        if entry.is_directory() {
+            count += 1;
+        }
+        None::<()>
+    })?;
+    Ok(count)
+}
+
+/// Look up the file with the given name in the directory. Without Rock Ridge
+/// nor Joliet, names are compared without case since they are stored in upper
+/// case.
+///
+/// # Errors
+/// - `LookupError::NoSuchEntry`: There is no file with the given name.
+/// - `LookupError::IoError`: The directory could not be read or is corrupted.
+fn lookup(dir: &Inode, name: &str) -> Result<Identifier, LookupError> {
+    let info = info(dir);
+    let fs = &info.fs;
+    let exact = fs.extension != Extension::None;
+    scan(fs, info.extent, info.size, |entry| {
+        let found = if exact {
+            entry.name == name
+        } else {
+            entry.name.eq_ignore_ascii_case(name)
+        };
+        found.then_some(Identifier(entry.location))
+    })?
+    .ok_or(LookupError::NoSuchEntry)
+}
+
+/// Read the entry at the given index of the directory. The `.` and `..` entries
+/// are always the two first entries.
+///
+/// # Errors
+/// Returns `ReaddirError::EndOfDirectory` if there is no entry at the given
+/// index, or if the directory could not be read.
+fn readdir(
+    file: &vfs::file::File,
+    offset: vfs::file::Offset,
+) -> Result<vfs::dirent::DirectoryEntry, vfs::file::ReaddirError> {
+    let dentry = file.dentry.as_ref().expect("Open file without dentry");
+    let dir = dentry.inode();
+    let info = info(dir);
+
+    let dot = |name: &str, inode| vfs::dirent::DirectoryEntry {
+        name: String::from(name),
+        offset: 1,
+        kind: vfs::dirent::Kind::Directory,
+        inode,
+    };
+    match offset.0 {
+        0 => return Ok(dot(".", dir.id)),
+        1 if dir.id == info.fs.root_id => return Ok(dot("..", dir.id)),
+        1 => {
+            let parent = dentry.parent().map_or(dir.id, |parent| parent.inode().id);
+            return Ok(dot("..", parent));
+        }
+        _ => {}
+    }
+
+    let mut index = 1;
+    let found = scan(&info.fs, info.extent, info.size, |entry| {
+        index += 1;
+        (index >= offset.0).then_some(entry)
+    });
+    let Ok(Some(found)) = found else {
+        return Err(vfs::file::ReaddirError::EndOfDirectory);
+    };
+
+    let kind = if found.is_directory() {
+        vfs::dirent::Kind::Directory
+    } else {
+        match found
+            .rock
+            .as_ref()
+            .and_then(|rock| rock.mode)
+            .map(|mode| mode & disk::S_IFMT)
+        {
+            Some(disk::S_IFLNK) => vfs::dirent::Kind::Symlink,
+            Some(disk::S_IFBLK) => vfs::dirent::Kind::BlockDevice,
+            Some(disk::S_IFCHR) => vfs::dirent::Kind::CharDevice,
+            _ => vfs::dirent::Kind::File,
+        }
+    };
+    Ok(vfs::dirent::DirectoryEntry {
+        name: found.name,
+        offset: 1,
+        kind,
+        inode: Identifier(found.location),
+    })
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn mknod(_: &Inode, _: &str, _: Device) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn create(_: &Inode, _: &str) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `CreateError::NotSupported`.
+fn symlink(_: &Inode, _: &str, _: &str) -> Result<Identifier, CreateError> {
+    Err(CreateError::NotSupported)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `UnlinkError::IoError`.
+fn unlink(_: &Inode, _: &str) -> Result<(), UnlinkError> {
+    Err(UnlinkError::IoError)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `MkdirError::IoError`.
+fn mkdir(_: &Inode, _: &str) -> Result<Identifier, MkdirError> {
+    Err(MkdirError::IoError)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `RmdirError::IoError`.
+fn rmdir(_: &Inode, _: &str) -> Result<(), RmdirError> {
+    Err(RmdirError::IoError)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `LinkError::NotSupported`.
+fn link(_: &Inode, _: &str, _: &Inode) -> Result<(), LinkError> {
+    Err(LinkError::NotSupported)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `RenameError::IoError`.
+fn rename(_: &Inode, _: &str, _: &str) -> Result<(), RenameError> {
+    Err(RenameError::IoError)
+}
+
+impl From<Error> for LookupError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
@@ -0,0 +1,209 @@
+//! On-disk structures of ISO9660. Numbers are stored in both byte orders, and
+//! only their little-endian half is read.
+use crate::time::{date::Date, units::Second, unix::UnixTime};
+
+/// The size of a logical sector, in bytes. Volume descriptors are one logical
+/// sector long, and directory records never span two logical sectors.
+pub const SECTOR_SIZE: usize = 2048;
+
+/// The logical sector of the first volume descriptor. The sectors before it are
+/// the system area, unused by ISO9660.
+pub const FIRST_DESCRIPTOR: u64 = 16;
+
+/// The identifier found in all volume descriptors.
+pub const STANDARD_ID: &[u8; 5] = b"CD001";
+
+/// The types of volume descriptors.
+pub const DESCRIPTOR_PRIMARY: u8 = 1;
+pub const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+pub const DESCRIPTOR_TERMINATOR: u8 = 255;
+
+/// The offsets of the fields of a primary or supplementary volume descriptor.
+pub const DESCRIPTOR_VOLUME_SIZE: usize = 80;
+pub const DESCRIPTOR_ESCAPES: usize = 88;
+pub const DESCRIPTOR_BLOCK_SIZE: usize = 128;
+pub const DESCRIPTOR_ROOT: usize = 156;
+
+/// The escape sequences identifying the Joliet supplementary volume descriptor,
+/// for its three levels of UCS-2.
+pub const JOLIET_ESCAPES: [&[u8; 3]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+/// The flags of a directory record.
+pub const FLAG_HIDDEN: u8 = 0x01;
+pub const FLAG_DIRECTORY: u8 = 0x02;
+pub const FLAG_ASSOCIATED: u8 = 0x04;
+pub const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+/// The size of the fixed part of a directory record, before its identifier.
+pub const RECORD_HEADER_SIZE: usize = 33;
+
+/// The identifiers of the records of a directory describing itself and its
+/// parent.
+pub const IDENTIFIER_DOT: u8 = 0;
+pub const IDENTIFIER_DOTDOT: u8 = 1;
+
+/// The file type bits of a POSIX mode, as stored in the Rock Ridge `PX` entry.
+pub const S_IFMT: u32 = 0o170_000;
+pub const S_IFSOCK: u32 = 0o140_000;
+pub const S_IFLNK: u32 = 0o120_000;
+pub const S_IFREG: u32 = 0o100_000;
+pub const S_IFBLK: u32 = 0o060_000;
+pub const S_IFDIR: u32 = 0o040_000;
+pub const S_IFCHR: u32 = 0o020_000;
+pub const S_IFIFO: u32 = 0o010_000;
+
+/// Read a little-endian `u16` at the given offset.
+#[must_use]
+pub fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Read a little-endian `u32` at the given offset. This also reads the numbers
+/// stored in both byte orders, whose little-endian half comes first.
+#[must_use]
+pub fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// A directory record, describing a file or a directory.
+#[derive(Debug, Clone)]
+pub struct DirectoryRecord {
+    /// The first logical block of the content of the file.
+    pub extent: u32,
+
+    /// The size of the content of the file, in bytes.
+    pub size: u32,
+
+    /// The time at which the file was recorded.
+    pub recorded: UnixTime,
+
+    /// The flags of the record.
+    pub flags: u8,
+
+    /// The identifier of the file, in the character set of its volume descriptor.
+    pub identifier: Vec<u8>,
+
+    /// The system use area of the record, holding the SUSP entries.
+    pub system_use: Vec<u8>,
+}
+
+impl DirectoryRecord {
+    /// Parse the directory record at the start of the given data, or return
+    /// `None` if it is not a valid record. The data may be longer than the record.
+    #[must_use]
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let length = usize::from(*data.first()?);
+        if length < RECORD_HEADER_SIZE || length > data.len() {
+            return None;
+        }
+        let data = &data[..length];
+        let identifier_length = usize::from(data[32]);
+        if RECORD_HEADER_SIZE + identifier_length > length {
+            return None;
+        }
+
+        // The identifier is padded to an even length before the system use area.
+        let system_use = RECORD_HEADER_SIZE + identifier_length + (1 - identifier_length % 2);
+        Some(Self {
+            extent: get_u32(data, 2),
+            size: get_u32(data, 10),
+            recorded: short_time(&data[18..25]),
+            flags: data[25],
+            identifier: data[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + identifier_length].to_vec(),
+            system_use: data.get(system_use..).unwrap_or_default().to_vec(),
+        })
+    }
+
+    /// Returns whether the record describes a directory.
+    #[must_use]
+    pub fn is_directory(&self) -> bool {
+        self.flags & FLAG_DIRECTORY != 0
+    }
+
+    /// Returns whether the record is the `.` or the `..` record of a directory.
+    #[must_use]
+    pub fn is_dot(&self) -> bool {
+        matches!(
+            self.identifier.as_slice(),
+            [IDENTIFIER_DOT | IDENTIFIER_DOTDOT]
+        )
+    }
+}
+
+/// Convert a local date with an offset from GMT, in intervals of 15 minutes,
+/// into an Unix time.
+fn local_time(date: Date, offset: i8) -> UnixTime {
+    if !(1..=12).contains(&date.month) || date.day == 0 {
+        return UnixTime(Second(0));
+    }
+    let local = u64::from(date.to_unix_time());
+    let offset = i64::from(offset) * 15 * 60;
+    UnixTime(Second(local.saturating_add_signed(-offset)))
+}
+
+/// Decode the 7-byte time of a directory record or of a Rock Ridge `TF` entry.
+#[must_use]
+#[allow(clippy::cast_possible_wrap)]
+pub fn short_time(raw: &[u8]) -> UnixTime {
+    let date = Date {
+        year: 1900 + u16::from(raw[0]),
+        month: raw[1],
+        day: raw[2],
+        hour: raw[3],
+        minute: raw[4],
+        second: raw[5],
+    };
+    local_time(date, raw[6] as i8)
+}
+
+/// Decode the 17-byte time of a volume descriptor or of a Rock Ridge `TF`
+/// entry, made of decimal digits followed by the offset from GMT.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub fn long_time(raw: &[u8]) -> UnixTime {
+    let number = |digits: &[u8]| {
+        digits.iter().fold(0, |n, &digit| {
+            n * 10 + u16::from(digit.wrapping_sub(b'0') % 10)
+        })
+    };
+    let date = Date {
+        year: number(&raw[0..4]),
+        month: number(&raw[4..6]) as u8,
+        day: number(&raw[6..8]) as u8,
+        hour: number(&raw[8..10]) as u8,
+        minute: number(&raw[10..12]) as u8,
+        second: number(&raw[12..14]) as u8,
+    };
+    local_time(date, raw[16] as i8)
+}
+
+/// Decode the identifier of a file of the primary volume descriptor: its
+/// version number and the dot of a name without extension are removed, and it
+/// is shown in lower case since d-characters are upper case letters.
+#[must_use]
+pub fn iso_name(identifier: &[u8]) -> String {
+    let name = identifier.split(|&c| c == b';').next().unwrap_or_default();
+    let name = name.strip_suffix(b".").unwrap_or(name);
+    name.iter()
+        .map(|&c| char::from(c.to_ascii_lowercase()))
+        .collect()
+}
+
+/// Decode the UCS-2 big-endian identifier of a file of the Joliet volume
+/// descriptor, without its version number.
+#[must_use]
+pub fn joliet_name(identifier: &[u8]) -> String {
+    let chars = identifier
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    let name = char::decode_utf16(chars)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect::<String>();
+    let name = name.split(';').next().unwrap_or_default();
+    String::from(name.strip_suffix('.').unwrap_or(name))
+}
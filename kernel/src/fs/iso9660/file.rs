@@ -0,0 +1,82 @@
+//! Regular files and symbolic links of an ISO9660 filesystem. The content of a
+//! regular file is a single extent of contiguous logical blocks, read through
+//! the buffer cache, and the target of a symbolic link comes from its Rock Ridge
+//! entries.
+use super::{inode::info, Error};
+use crate::vfs::{
+    self,
+    file::{File, Offset, ReadError, SeekError, Whence, WriteError},
+};
+
+/// Operations that can be performed on an opened regular file or symbolic link.
+pub static FILE_OPS: vfs::file::FileOperation = vfs::file::FileOperation {
+    write,
+    read,
+    seek,
+    poll: vfs::poll::always_ready,
+    ioctl: vfs::file::no_ioctl,
+    mmap: vfs::file::no_mmap,
+};
+
+/// Read from the file at the given offset into the given buffer, and return the
+/// number of bytes read. Reading stops at the end of the file.
+///
+/// # Errors
+/// Returns `ReadError::IoError` if a sector of the file could not be read.
+fn read(file: &File, buf: &mut [u8], offset: Offset) -> Result<usize, ReadError> {
+    let inode = file
+        .dentry
+        .as_ref()
+        .expect("Open file without dentry")
+        .inode();
+    let info = info(inode);
+    let size = inode.metadata.lock().size;
+    let len = core::cmp::min(buf.len(), size.saturating_sub(offset.0));
+
+    if let Some(target) = &info.symlink {
+        buf[..len].copy_from_slice(&target.as_bytes()[offset.0..offset.0 + len]);
+        return Ok(len);
+    }
+
+    let position = info.fs.position(info.extent) + offset.0 as u64;
+    info.fs.read(position, &mut buf[..len])?;
+    Ok(len)
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `WriteError::NotImplemented`.
+fn write(_: &File, _: &[u8], _: Offset) -> Result<usize, WriteError> {
+    Err(WriteError::NotImplemented)
+}
+
+/// Seek into the file and return the new offset.
+///
+/// # Errors
+/// Returns `SeekError::Overflow` if the new offset overflows.
+fn seek(file: &File, offset: isize, whence: Whence) -> Result<Offset, SeekError> {
+    let base = match whence {
+        Whence::Start => 0,
+        Whence::Current => file.state.lock().offset.0,
+        Whence::End => {
+            file.dentry
+                .as_ref()
+                .expect("Open file without dentry")
+                .inode()
+                .metadata
+                .lock()
+                .size
+        }
+    };
+
+    base.checked_add_signed(offset)
+        .map(Offset)
+        .ok_or(SeekError::Overflow)
+}
+
+impl From<Error> for ReadError {
+    fn from(_: Error) -> Self {
+        Self::IoError
+    }
+}
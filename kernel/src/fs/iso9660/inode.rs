@@ -0,0 +1,192 @@
+//! Inodes of an ISO9660 filesystem, read from directory records and their Rock
+//! Ridge attributes. Without Rock Ridge, directories have the mode `0555` and
+//! files the mode `0444`, and all times are the recording time of the file.
+use super::{dir, disk, file, iso9660, Iso9660};
+use crate::{
+    device::{self, Device},
+    fs::ramfs::interface::SPECIAL_FILE_OPS,
+    vfs::{
+        self,
+        inode::{Identifier, InodeCreateInfo, InodeMetadata, Kind},
+        mount::{ReadInodeError, Super, WriteInodeError},
+    },
+};
+
+/// Operations that can be performed on a file inode.
+pub static INODE_FILE_OPS: vfs::inode::FileOperation = vfs::inode::FileOperation { truncate };
+
+/// The ISO9660 specific data of an inode.
+pub struct InodeInfo {
+    /// The filesystem of the inode.
+    pub fs: Arc<Iso9660>,
+
+    /// The first logical block and the size in bytes of the extent of the
+    /// inode.
+    pub extent: u32,
+    pub size: u32,
+
+    /// The target of the inode if it is a symbolic link.
+    pub symlink: Option<String>,
+}
+
+/// Returns the ISO9660 specific data of the given inode.
+///
+/// # Panics
+/// Panics if the inode is not an ISO9660 inode.
+#[must_use]
+pub fn info(inode: &vfs::inode::Inode) -> &InodeInfo {
+    inode
+        .data
+        .downcast_ref::<InodeInfo>()
+        .expect("Inode is not an ISO9660 inode")
+}
+
+/// Read the inode with the given identifier from its directory record and create
+/// its VFS inode. The root directory and the relocated directories are read from
+/// their `.` record, which holds their Rock Ridge attributes.
+///
+/// # Errors
+/// Returns `ReadInodeError::IoError` if the record could not be read, or if it
+/// is corrupted.
+#[allow(clippy::too_many_lines, clippy::cast_possible_truncation)]
+pub fn read_inode(
+    superblock: &Super,
+    id: Identifier,
+) -> Result<Arc<vfs::inode::Inode>, ReadInodeError> {
+    let fs = iso9660(superblock);
+    let location = if id == fs.root_id {
+        fs.position(fs.root.extent)
+    } else {
+        id.0
+    };
+
+    let mut record = fs.record(location).map_err(|_| ReadInodeError::IoError)?;
+    let mut rock = fs
+        .rock_ridge(&record)
+        .map_err(|_| ReadInodeError::IoError)?;
+    if let Some(child) = rock.as_ref().and_then(|rock| rock.child) {
+        record = fs
+            .record(fs.position(child))
+            .map_err(|_| ReadInodeError::IoError)?;
+        rock = fs
+            .rock_ridge(&record)
+            .map_err(|_| ReadInodeError::IoError)?;
+    }
+
+    let rock = rock.unwrap_or_default();
+    let format = match rock.mode {
+        Some(mode) => mode & disk::S_IFMT,
+        None if record.is_directory() => disk::S_IFDIR,
+        None => disk::S_IFREG,
+    };
+
+    let regular = vfs::inode::Operation::File(&INODE_FILE_OPS);
+    let special = vfs::file::Operation::File(&SPECIAL_FILE_OPS);
+    let device = rock
+        .device
+        .unwrap_or(device::Identifier { major: 0, minor: 0 });
+    let (kind, inode_ops, file_ops) = match format {
+        disk::S_IFDIR => (
+            Kind::Directory,
+            vfs::inode::Operation::Directory(&dir::INODE_OPS),
+            vfs::file::Operation::Directory(&dir::FILE_OPS),
+        ),
+        disk::S_IFREG => (
+            Kind::File,
+            regular,
+            vfs::file::Operation::File(&file::FILE_OPS),
+        ),
+        disk::S_IFLNK => (
+            Kind::Symlink,
+            regular,
+            vfs::file::Operation::File(&file::FILE_OPS),
+        ),
+        disk::S_IFBLK => (
+            Kind::BlockDevice(device),
+            regular,
+            vfs::file::Operation::File(&device::block::file::FILE_OPS),
+        ),
+        disk::S_IFCHR => (
+            Kind::CharDevice(device),
+            regular,
+            vfs::file::Operation::File(&device::character::file::FILE_OPS),
+        ),
+        disk::S_IFIFO | disk::S_IFSOCK => (Kind::File, regular, special),
+        _ => {
+            log::warn!("iso9660: inode {} has an invalid mode {:#o}", id.0, format);
+            return Err(ReadInodeError::IoError);
+        }
+    };
+
+    let symlink = match kind {
+        Kind::Symlink => Some(rock.symlink.unwrap_or_default()),
+        _ => None,
+    };
+    let size = match &symlink {
+        Some(target) => target.len(),
+        None => record.size as usize,
+    };
+    let links = match rock.links {
+        Some(links) => u64::from(links),
+        None if kind == Kind::Directory => {
+            let subdirectories = dir::count_subdirectories(fs, record.extent, record.size)
+                .map_err(|_| ReadInodeError::IoError)?;
+            2 + subdirectories
+        }
+        None => 1,
+    };
+    let default_mode = match kind {
+        Kind::Directory => 0o555,
+        _ => 0o444,
+    };
+
+    let modification_time = rock.modified.unwrap_or(record.recorded);
+    let metadata = InodeMetadata {
+        modification_time,
+        access_time: rock.accessed.unwrap_or(modification_time),
+        change_time: rock.changed.unwrap_or(modification_time),
+        links,
+        size,
+        mode: rock
+            .mode
+            .map_or(default_mode, |mode| (mode & 0o7777) as u16),
+    };
+
+    let info = InodeInfo {
+        fs: Arc::clone(fs),
+        extent: record.extent,
+        size: record.size,
+        symlink,
+    };
+
+    Ok(Arc::new(vfs::inode::Inode::new(
+        fs.superblock(),
+        InodeCreateInfo {
+            id,
+            device: Device::Block(fs.device),
+            kind,
+            inode_ops,
+            file_ops,
+            metadata,
+            data: Box::new(info),
+        },
+    )))
+}
+
+/// Nothing is written to the filesystem: changes to the metadata of an inode,
+/// like its access time, only last while it is in the inode cache.
+///
+/// # Errors
+/// This function never fails.
+#[allow(clippy::unnecessary_wraps)]
+pub fn write_inode(_: &vfs::inode::Inode) -> Result<(), WriteInodeError> {
+    Ok(())
+}
+
+/// The filesystem is read-only.
+///
+/// # Errors
+/// Always returns `TruncateError::IoError`.
+fn truncate(_: &vfs::inode::Inode, _: usize) -> Result<usize, vfs::inode::TruncateError> {
+    Err(vfs::inode::TruncateError::IoError)
+}
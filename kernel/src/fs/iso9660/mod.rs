@@ -0,0 +1,306 @@
+//! The ISO9660 filesystem of CD-ROMs, with the Rock Ridge extensions that add
+//! POSIX attributes, long names and symbolic links, and the Joliet extension
+//! that adds Unicode names. Rock Ridge is used when present, and Joliet is the
+//! fallback for images created without it. Without either, the 8.3 names are
+//! shown in lower case without their version number, like on Linux.
+//!
+//! The filesystem is read-only: every operation that would modify it fails.
+//! The driver reads it through the buffer cache, using buffers of the size of
+//! a logical sector (2 KiB), in which directory records are always contained.
+//!
+//! ISO9660 has no inodes: the identifier of an inode is the location of its
+//! directory record on the device, in bytes, which never changes. The root
+//! directory uses the location of its record in the volume descriptor.
+use crate::{
+    device::{
+        self,
+        block::{cache, IoError},
+        Device, Identifier,
+    },
+    vfs::{
+        self,
+        mount::{Super, SuperCreationInfo},
+    },
+};
+use alloc::sync::Weak;
+use disk::DirectoryRecord;
+
+pub mod dir;
+pub mod disk;
+pub mod file;
+pub mod inode;
+pub mod rock;
+
+/// Operations that can be performed on the filesystem.
+pub static FS_OPS: vfs::fs::Operation = vfs::fs::Operation { read_super };
+
+/// Operations that can be performed on the superblock.
+pub static SUPER_OPS: vfs::mount::Operation = vfs::mount::Operation {
+    write_super,
+    write_inode: inode::write_inode,
+    read_inode: inode::read_inode,
+};
+
+/// The maximal number of volume descriptors read before giving up on finding
+/// the terminator.
+const MAX_DESCRIPTORS: u64 = 64;
+
+/// The extension used to name the files of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// Rock Ridge entries are stored in the system use area of the records of
+    /// the primary volume descriptor, after the given number of bytes.
+    RockRidge { skip: usize },
+
+    /// The directories of the Joliet supplementary volume descriptor are used,
+    /// with their UCS-2 names.
+    Joliet,
+
+    /// Only the names of the primary volume descriptor are available.
+    None,
+}
+
+/// A mounted ISO9660 filesystem.
+pub struct Iso9660 {
+    /// The block device on which the filesystem is stored.
+    device: Identifier,
+
+    /// The size of a logical block, in which extents are counted, in bytes.
+    block_size: usize,
+
+    /// The extension used to name the files.
+    extension: Extension,
+
+    /// The record of the root directory, from the volume descriptor in use.
+    root: DirectoryRecord,
+
+    /// The identifier of the root directory.
+    root_id: vfs::inode::Identifier,
+
+    /// The VFS superblock of the filesystem, needed to create VFS inodes.
+    superblock: Once<Weak<Super>>,
+}
+
+impl Iso9660 {
+    /// Returns the position on the device of the given logical block, in bytes.
+    #[must_use]
+    pub const fn position(&self, block: u32) -> u64 {
+        block as u64 * self.block_size as u64
+    }
+
+    /// Returns the buffer containing the given logical sector.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if the sector could not be read.
+    pub fn sector(&self, sector: u64) -> Result<Arc<cache::Buffer>, Error> {
+        Ok(cache::read(self.device, sector, disk::SECTOR_SIZE)?)
+    }
+
+    /// Read the bytes of the device starting at the given position into the
+    /// given buffer.
+    ///
+    /// # Errors
+    /// Returns `Error::IoError` if a sector could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn read(&self, position: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let mut done = 0;
+        while done < buf.len() {
+            let current = position + done as u64;
+            let start = (current % disk::SECTOR_SIZE as u64) as usize;
+            let count = core::cmp::min(disk::SECTOR_SIZE - start, buf.len() - done);
+            let buffer = self.sector(current / disk::SECTOR_SIZE as u64)?;
+            buf[done..done + count].copy_from_slice(&buffer.data()[start..start + count]);
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Returns the directory record at the given location on the device.
+    ///
+    /// # Errors
+    /// - `Error::Corrupted`: There is no valid record at that location.
+    /// - `Error::IoError`: The sector of the record could not be read.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn record(&self, location: u64) -> Result<DirectoryRecord, Error> {
+        let buffer = self.sector(location / disk::SECTOR_SIZE as u64)?;
+        let offset = (location % disk::SECTOR_SIZE as u64) as usize;
+        let record = DirectoryRecord::parse(&buffer.data()[offset..]);
+        record.ok_or(Error::Corrupted)
+    }
+
+    /// Returns the Rock Ridge attributes of the given record, or `None` if the
+    /// volume does not use Rock Ridge.
+    ///
+    /// # Errors
+    /// See [`rock::parse`].
+    pub fn rock_ridge(&self, record: &DirectoryRecord) -> Result<Option<rock::Attributes>, Error> {
+        match self.extension {
+            Extension::RockRidge { skip } => rock::parse(self, &record.system_use, skip).map(Some),
+            Extension::Joliet | Extension::None => Ok(None),
+        }
+    }
+
+    /// Returns the name of a file from the identifier of its record.
+    #[must_use]
+    pub fn name(&self, identifier: &[u8]) -> String {
+        match self.extension {
+            Extension::Joliet => disk::joliet_name(identifier),
+            Extension::RockRidge { .. } | Extension::None => disk::iso_name(identifier),
+        }
+    }
+
+    /// Returns the VFS superblock of the filesystem.
+    #[must_use]
+    pub fn superblock(&self) -> Weak<Super> {
+        self.superblock.get().cloned().unwrap_or_default()
+    }
+}
+
+/// The errors that can occur inside the driver. They are converted to the errors
+/// of the VFS operations at the boundary of the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// A structure of the filesystem is invalid.
+    Corrupted,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
+}
+
+impl From<IoError> for Error {
+    fn from(_: IoError) -> Self {
+        Error::IoError
+    }
+}
+
+/// Register the ISO9660 filesystem into the VFS.
+pub fn register() {
+    vfs::fs::register(vfs::fs::Filesystem::new("iso9660", &FS_OPS, Box::new(())));
+}
+
+/// Returns the ISO9660 filesystem of the given VFS superblock.
+///
+/// # Panics
+/// Panics if the superblock is not an ISO9660 superblock.
+#[must_use]
+pub fn iso9660(superblock: &Super) -> &Arc<Iso9660> {
+    superblock
+        .data()
+        .downcast_ref::<Arc<Iso9660>>()
+        .expect("Superblock is not an ISO9660 superblock")
+}
+
+/// Read the volume descriptors of the ISO9660 filesystem stored on the given
+/// block device, and choose the extension used to name its files.
+///
+/// # Errors
+/// - `ReadSuperError::InvalidDevice`: The device is not a registered block device.
+/// - `ReadSuperError::IoError`: A volume descriptor could not be read.
+/// - `ReadSuperError::InvalidFileSystem`: The device does not contain an ISO9660
+///   filesystem, or its sectors are larger than a logical sector.
+/// - `ReadSuperError::CorruptedFileSystem`: The primary volume descriptor is
+///   inconsistent.
+fn read_super(
+    _: &vfs::fs::Filesystem,
+    device: Device,
+) -> Result<Arc<Super>, vfs::fs::ReadSuperError> {
+    let Device::Block(id) = device else {
+        return Err(vfs::fs::ReadSuperError::InvalidDevice);
+    };
+    let block_device = device::block::get(id).ok_or(vfs::fs::ReadSuperError::InvalidDevice)?;
+    if disk::SECTOR_SIZE % block_device.sector_size() != 0 {
+        return Err(vfs::fs::ReadSuperError::InvalidFileSystem);
+    }
+
+    let mut primary = None;
+    let mut joliet = None;
+    for sector in disk::FIRST_DESCRIPTOR..disk::FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+        let buffer = cache::read(id, sector, disk::SECTOR_SIZE)
+            .map_err(|_| vfs::fs::ReadSuperError::IoError)?;
+        let data = buffer.data();
+        if &data[1..6] != disk::STANDARD_ID {
+            break;
+        }
+        match data[0] {
+            disk::DESCRIPTOR_PRIMARY if primary.is_none() => {
+                primary = Some((sector, data.to_vec()));
+            }
+            disk::DESCRIPTOR_SUPPLEMENTARY
+                if joliet.is_none()
+                    && disk::JOLIET_ESCAPES
+                        .iter()
+                        .any(|escape| &data[disk::DESCRIPTOR_ESCAPES..][..3] == *escape) =>
+            {
+                joliet = Some((sector, data.to_vec()));
+            }
+            disk::DESCRIPTOR_TERMINATOR => break,
+            _ => {}
+        }
+    }
+
+    let (sector, descriptor) = primary.ok_or(vfs::fs::ReadSuperError::InvalidFileSystem)?;
+    let block_size = usize::from(disk::get_u16(&descriptor, disk::DESCRIPTOR_BLOCK_SIZE));
+    let blocks = disk::get_u32(&descriptor, disk::DESCRIPTOR_VOLUME_SIZE);
+    if !(512..=disk::SECTOR_SIZE).contains(&block_size) || !block_size.is_power_of_two() {
+        return Err(vfs::fs::ReadSuperError::CorruptedFileSystem);
+    }
+    let root = DirectoryRecord::parse(&descriptor[disk::DESCRIPTOR_ROOT..])
+        .filter(DirectoryRecord::is_directory)
+        .ok_or(vfs::fs::ReadSuperError::CorruptedFileSystem)?;
+
+    let mut iso = Iso9660 {
+        device: id,
+        block_size,
+        extension: Extension::None,
+        root,
+        root_id: vfs::inode::Identifier(
+            sector * disk::SECTOR_SIZE as u64 + disk::DESCRIPTOR_ROOT as u64,
+        ),
+        superblock: Once::new(),
+    };
+
+    // Rock Ridge is announced by a `SP` entry at the start of the system use area
+    // of the first record of the root directory.
+    let first = iso
+        .record(iso.position(iso.root.extent))
+        .map_err(|_| vfs::fs::ReadSuperError::CorruptedFileSystem)?;
+    if let Some(skip) = rock::sharing_protocol(&first.system_use) {
+        iso.extension = Extension::RockRidge { skip };
+    } else if let Some((sector, descriptor)) = joliet {
+        if let Some(root) = DirectoryRecord::parse(&descriptor[disk::DESCRIPTOR_ROOT..])
+            .filter(DirectoryRecord::is_directory)
+        {
+            iso.extension = Extension::Joliet;
+            iso.root = root;
+            iso.root_id = vfs::inode::Identifier(
+                sector * disk::SECTOR_SIZE as u64 + disk::DESCRIPTOR_ROOT as u64,
+            );
+        }
+    }
+
+    log::info!(
+        "iso9660: {} blocks of {} bytes, with {:?} names",
+        blocks,
+        block_size,
+        iso.extension
+    );
+
+    let iso = Arc::new(iso);
+    let superblock = Arc::new(Super::new(SuperCreationInfo {
+        device,
+        operation: &SUPER_OPS,
+        root: iso.root_id,
+        data: Box::new(Arc::clone(&iso)),
+    }));
+    iso.superblock.call_once(|| Arc::downgrade(&superblock));
+    Ok(superblock)
+}
+
+/// Nothing is ever written to the filesystem.
+///
+/// # Errors
+/// This function never fails.
+#[allow(clippy::unnecessary_wraps)]
+fn write_super(_: &Super) -> Result<(), vfs::mount::WriteSuperError> {
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//! The Rock Ridge extensions (RRIP), stored as entries of the System Use Sharing
+//! Protocol (SUSP) in the system use area of directory records. An entry starts
+//! with a two-letter signature, its length and its version. The entries of a
+//! record may continue in a continuation area, pointed to by a `CE` entry.
+//!
+//! Directories nested deeper than ISO9660 allows are relocated: the relocated
+//! directory has a `RE` entry and is hidden, and a file with a `CL` entry stands
+//! for it in its original parent.
+use super::{
+    disk::{self, get_u32},
+    Error, Iso9660,
+};
+use crate::{device, time::unix::UnixTime};
+use alloc::vec;
+
+/// The maximal number of continuation areas followed for a record, to avoid
+/// looping on a corrupted filesystem.
+const MAX_CONTINUATIONS: usize = 16;
+
+/// The flags of a `NM` entry naming the current or the parent directory.
+const NM_CURRENT: u8 = 0x02;
+const NM_PARENT: u8 = 0x04;
+
+/// The flags of a component of a `SL` entry.
+const SL_CONTINUE: u8 = 0x01;
+const SL_CURRENT: u8 = 0x02;
+const SL_PARENT: u8 = 0x04;
+const SL_ROOT: u8 = 0x08;
+
+/// The flags of a `TF` entry, telling which times are recorded, in this order,
+/// and whether they use the 17-byte format.
+const TF_MODIFY: u8 = 1;
+const TF_ACCESS: u8 = 2;
+const TF_ATTRIBUTES: u8 = 3;
+const TF_LONG_FORM: u8 = 0x80;
+
+/// The attributes of a file stored in its Rock Ridge entries.
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    /// The name of the file (`NM`).
+    pub name: Option<String>,
+
+    /// The POSIX mode and number of links of the file (`PX`).
+    pub mode: Option<u32>,
+    pub links: Option<u32>,
+
+    /// The device of a device file (`PN`).
+    pub device: Option<device::Identifier>,
+
+    /// The target of a symbolic link (`SL`).
+    pub symlink: Option<String>,
+
+    /// The modification, access and attribute change times (`TF`).
+    pub modified: Option<UnixTime>,
+    pub accessed: Option<UnixTime>,
+    pub changed: Option<UnixTime>,
+
+    /// The logical block of the relocated directory this file stands for (`CL`).
+    pub child: Option<u32>,
+
+    /// Whether this directory was relocated, and must be hidden (`RE`).
+    pub relocated: bool,
+}
+
+/// Returns the number of bytes to skip in the system use area of each record
+/// if the given system use area, from the first record of the root directory,
+/// starts with the `SP` entry announcing the use of SUSP.
+#[must_use]
+pub fn sharing_protocol(system_use: &[u8]) -> Option<usize> {
+    match system_use {
+        [b'S', b'P', length, _, 0xBE, 0xEF, skip, ..] if *length >= 7 => Some(usize::from(*skip)),
+        _ => None,
+    }
+}
+
+/// Parse the Rock Ridge entries of the given system use area, skipping its
+/// first `skip` bytes, and the entries of its continuation areas. Unknown and
+/// truncated entries are ignored.
+///
+/// # Errors
+/// - `Error::Corrupted`: There are too many continuation areas.
+/// - `Error::IoError`: A continuation area could not be read.
+pub fn parse(fs: &Iso9660, system_use: &[u8], skip: usize) -> Result<Attributes, Error> {
+    let mut attributes = Attributes::default();
+    let mut name = Vec::new();
+    let mut symlink = String::new();
+    let mut continued = false;
+
+    let mut area = system_use.get(skip..).unwrap_or_default().to_vec();
+    let mut continuations = 0;
+    loop {
+        let mut next = None;
+        let mut offset = 0;
+        while offset + 4 <= area.len() {
+            let length = usize::from(area[offset + 2]);
+            if length < 4 || offset + length > area.len() {
+                break;
+            }
+            let entry = &area[offset..offset + length];
+            offset += length;
+
+            match (&entry[..2], length) {
+                (b"ST", _) => break,
+                (b"CE", 28..) => {
+                    next = Some((get_u32(entry, 4), get_u32(entry, 12), get_u32(entry, 20)));
+                }
+                (b"PX", 36..) => {
+                    attributes.mode = Some(get_u32(entry, 4));
+                    attributes.links = Some(get_u32(entry, 12));
+                }
+                (b"PN", 20..) => {
+                    attributes.device = Some(decode_device(get_u32(entry, 12)));
+                }
+                (b"NM", 5..) if entry[4] & (NM_CURRENT | NM_PARENT) == 0 => {
+                    name.extend_from_slice(&entry[5..]);
+                }
+                (b"SL", 5..) => push_components(&mut symlink, &mut continued, &entry[5..]),
+                (b"TF", 5..) => read_times(&mut attributes, entry),
+                (b"CL", 12..) => attributes.child = Some(get_u32(entry, 4)),
+                (b"RE", _) => attributes.relocated = true,
+                _ => {}
+            }
+        }
+
+        let Some((block, offset, length)) = next else {
+            break;
+        };
+        continuations += 1;
+        if continuations > MAX_CONTINUATIONS || length as usize > disk::SECTOR_SIZE {
+            return Err(Error::Corrupted);
+        }
+        area = vec![0; length as usize];
+        fs.read(fs.position(block) + u64::from(offset), &mut area)?;
+    }
+
+    if !name.is_empty() {
+        attributes.name = Some(String::from_utf8_lossy(&name).into_owned());
+    }
+    if !symlink.is_empty() {
+        attributes.symlink = Some(symlink);
+    }
+    Ok(attributes)
+}
+
+/// Append the components of a `SL` entry to the target of a symbolic link.
+/// Components are separated by slashes, unless the previous one continues in
+/// the next one.
+fn push_components(target: &mut String, continued: &mut bool, mut data: &[u8]) {
+    while let [flags, length, rest @ ..] = data {
+        let length = usize::from(*length);
+        if length > rest.len() {
+            break;
+        }
+        if !*continued && !target.is_empty() && !target.ends_with('/') {
+            target.push('/');
+        }
+        if flags & SL_ROOT != 0 {
+            target.push('/');
+        } else if flags & SL_PARENT != 0 {
+            target.push_str("..");
+        } else if flags & SL_CURRENT != 0 {
+            target.push('.');
+        } else {
+            target.push_str(&String::from_utf8_lossy(&rest[..length]));
+        }
+        *continued = flags & SL_CONTINUE != 0;
+        data = &rest[length..];
+    }
+}
+
+/// Read the times recorded in a `TF` entry.
+fn read_times(attributes: &mut Attributes, entry: &[u8]) {
+    let flags = entry[4];
+    let size = if flags & TF_LONG_FORM == 0 { 7 } else { 17 };
+    let mut offset = 5;
+    for bit in 0..7 {
+        if flags & (1 << bit) == 0 {
+            continue;
+        }
+        let Some(data) = entry.get(offset..offset + size) else {
+            break;
+        };
+        let time = if size == 7 {
+            disk::short_time(data)
+        } else {
+            disk::long_time(data)
+        };
+        match bit {
+            TF_MODIFY => attributes.modified = Some(time),
+            TF_ACCESS => attributes.accessed = Some(time),
+            TF_ATTRIBUTES => attributes.changed = Some(time),
+            _ => {}
+        }
+        offset += size;
+    }
+}
+
+/// Decode the low 32 bits of the device number of a `PN` entry, encoded like
+/// the device numbers of Linux.
+fn decode_device(number: u32) -> device::Identifier {
+    device::Identifier {
+        major: (number >> 8) & 0xFFF,
+        minor: (number & 0xFF) | ((number >> 12) & 0xF_FF00),
+    }
+}
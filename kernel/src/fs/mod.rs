@@ -1,7 +1,15 @@
+pub mod devpts;
+pub mod ext2;
+pub mod fat;
+pub mod iso9660;
 pub mod ramfs;
 
 /// Register all supported filesystems.
 #[init]
 pub fn register_all() {
     ramfs::register();
+    ext2::register();
+    fat::register();
+    iso9660::register();
+    devpts::register();
 }
@@ -1,6 +1,6 @@
 use super::{generate_inode_id, InodeDirectory, InodeFile, Superblock};
 use crate::{
-    device::Device,
+    device::{self, Device},
     fs::ramfs,
     time::unix::UnixTime,
     vfs::{self, mount::SuperCreationInfo},
@@ -27,14 +27,33 @@ pub static INODE_DIR_OPS: vfs::inode::DirectoryOperation = vfs::inode::Directory
     rmdir,
     link,
     rename,
+    symlink,
 };
 
 /// Operations that can be performed on a file inode.
 pub static INODE_FILE_OPS: vfs::inode::FileOperation = vfs::inode::FileOperation { truncate };
 
 /// Operations that can be performed on a opened regular file.
-pub static REGULAR_FILE_OPS: vfs::file::FileOperation =
-    vfs::file::FileOperation { write, read, seek };
+pub static REGULAR_FILE_OPS: vfs::file::FileOperation = vfs::file::FileOperation {
+    write,
+    read,
+    seek,
+    poll: vfs::poll::always_ready,
+    ioctl: vfs::file::no_ioctl,
+    mmap: vfs::file::no_mmap,
+};
+
+/// Operations that can be performed on a opened special file, such as a named pipe
+/// or a socket, which are not supported yet: these files can be created but not
+/// read or written.
+pub static SPECIAL_FILE_OPS: vfs::file::FileOperation = vfs::file::FileOperation {
+    write: |_, _, _| Err(vfs::file::WriteError::NotImplemented),
+    read: |_, _, _| Err(vfs::file::ReadError::NotImplemented),
+    seek: |_, _, _| Err(vfs::file::SeekError::NotSeekable),
+    poll: vfs::poll::always_ready,
+    ioctl: vfs::file::no_ioctl,
+    mmap: vfs::file::no_mmap,
+};
 
 /// Operations that can be performed on a opened directory.
 pub static FILE_DIRECTORY_OPS: vfs::file::DirectoryOperation =
@@ -75,6 +94,7 @@ fn read_super(
                 change_time: UnixTime::now(),
                 links: 0,
                 size: 0,
+                mode: 0o755,
             },
             data: Box::new(Spinlock::new(InodeDirectory::empty())),
         },
@@ -158,12 +178,37 @@ fn truncate(inode: &vfs::inode::Inode, size: usize) -> Result<usize, vfs::inode:
     Ok(size)
 }
 
+/// Create a new device file in the directory.
+///
+/// # Errors
+/// If a file with the same name already exists, an error is returned.
+///
+/// # Panics
+/// Panics if the device is [`Device::None`].
 fn mknod(
-    _inode: &vfs::inode::Inode,
-    _name: &str,
-    _device: Device,
+    inode: &vfs::inode::Inode,
+    name: &str,
+    device: Device,
 ) -> Result<vfs::inode::Identifier, vfs::inode::CreateError> {
-    todo!()
+    let (kind, operation) = match device {
+        Device::Block(id) => (
+            vfs::inode::Kind::BlockDevice(id),
+            &device::block::file::FILE_OPS,
+        ),
+        Device::Char(id) => (
+            vfs::inode::Kind::CharDevice(id),
+            &device::character::file::FILE_OPS,
+        ),
+        Device::None => panic!("Cannot create a device file without a device"),
+    };
+
+    insert(
+        inode,
+        name,
+        kind,
+        vfs::file::Operation::File(operation),
+        InodeFile::empty(),
+    )
 }
 
 /// Create a new file in the directory.
@@ -206,6 +251,7 @@ fn create(
                 change_time: UnixTime::now(),
                 links: 1,
                 size: 0,
+                mode: 0o644,
             },
             data: Box::new(Spinlock::new(InodeFile::empty())),
         },
@@ -352,6 +398,7 @@ fn mkdir(
                 change_time: UnixTime::now(),
                 links: 1,
                 size: 0,
+                mode: 0o755,
             },
             data: Box::new(Spinlock::new(InodeDirectory::empty())),
         },
@@ -445,14 +492,45 @@ fn rmdir(inode: &vfs::inode::Inode, name: &str) -> Result<(), vfs::inode::RmdirE
     Ok(())
 }
 
-/// Create a new hard link to the inode. If a file with the same name already
-/// exists, an error is returned.
+/// Create a new hard link in the directory to the target inode, and increment
+/// the links counter of the target inode.
+///
+/// # Errors
+/// If an entry with the same name already exists or if the target is a directory,
+/// an error is returned.
 fn link(
-    _inode: &vfs::inode::Inode,
-    _name: &str,
-    _target: &vfs::inode::Inode,
+    inode: &vfs::inode::Inode,
+    name: &str,
+    target: &vfs::inode::Inode,
 ) -> Result<(), vfs::inode::LinkError> {
-    todo!()
+    let ramfs_inode = inode
+        .data
+        .downcast_ref::<Spinlock<InodeDirectory>>()
+        .expect("Inode is not a ramfs inode");
+
+    if target.kind == vfs::inode::Kind::Directory {
+        return Err(vfs::inode::LinkError::IsADirectory);
+    }
+
+    // Check if a file with the same name already exists.
+    let mut locked_dir = ramfs_inode.lock();
+    if locked_dir.entries.iter().any(|entry| entry.name == name) {
+        return Err(vfs::inode::LinkError::AlreadyExists);
+    }
+
+    locked_dir.add_entry(target, String::from(name));
+
+    // Update the metadata of the target inode.
+    let mut metadata = target.metadata.lock();
+    metadata.change_time = UnixTime::now();
+    metadata.links += 1;
+
+    // Update the metadata of the parent directory.
+    let mut metadata = inode.metadata.lock();
+    metadata.size = locked_dir.entries.len() * core::mem::size_of::<vfs::dirent::DirectoryEntry>();
+    metadata.modification_time = UnixTime::now();
+    metadata.change_time = UnixTime::now();
+    Ok(())
 }
 
 /// Rename an entry in the directory.
@@ -486,6 +564,88 @@ fn rename(inode: &vfs::inode::Inode, old: &str, new: &str) -> Result<(), vfs::in
     Ok(())
 }
 
+/// Create a new symbolic link in the directory. The target path is stored as the
+/// content of the link, like the content of a regular file.
+///
+/// # Errors
+/// If a file with the same name already exists, an error is returned.
+fn symlink(
+    inode: &vfs::inode::Inode,
+    name: &str,
+    target: &str,
+) -> Result<vfs::inode::Identifier, vfs::inode::CreateError> {
+    let mut content = InodeFile::empty();
+    content.content_mut().extend_from_slice(target.as_bytes());
+    insert(
+        inode,
+        name,
+        vfs::inode::Kind::Symlink,
+        vfs::file::Operation::File(&REGULAR_FILE_OPS),
+        content,
+    )
+}
+
+/// Create a new inode that is not a directory with the given content, and add it
+/// to the directory. This is used to create device files and symbolic links, that
+/// only differ by their kind and the operations used when they are opened.
+///
+/// # Errors
+/// If a file with the same name already exists, an error is returned.
+fn insert(
+    inode: &vfs::inode::Inode,
+    name: &str,
+    kind: vfs::inode::Kind,
+    file_ops: vfs::file::Operation,
+    content: InodeFile,
+) -> Result<vfs::inode::Identifier, vfs::inode::CreateError> {
+    let superblock = inode.superblock.upgrade().unwrap();
+    let ramfs_super = superblock
+        .data()
+        .downcast_ref::<Spinlock<Superblock>>()
+        .expect("Superblock is not a ramfs superblock");
+    let ramfs_inode = inode
+        .data
+        .downcast_ref::<Spinlock<InodeDirectory>>()
+        .expect("Inode is not a ramfs inode");
+
+    // Check if a file with the same name already exists.
+    let mut locked_dir = ramfs_inode.lock();
+    if locked_dir.entries.iter().any(|entry| entry.name == name) {
+        return Err(vfs::inode::CreateError::AlreadyExists);
+    }
+
+    let id = ramfs::generate_inode_id();
+    let child = Arc::new(vfs::inode::Inode::new(
+        Weak::clone(&inode.superblock),
+        vfs::inode::InodeCreateInfo {
+            id,
+            device: Device::None,
+            kind,
+            inode_ops: vfs::inode::Operation::File(&INODE_FILE_OPS),
+            file_ops,
+            metadata: vfs::inode::InodeMetadata {
+                modification_time: UnixTime::now(),
+                access_time: UnixTime::now(),
+                change_time: UnixTime::now(),
+                links: 1,
+                size: content.content().len(),
+                mode: 0o777,
+            },
+            data: Box::new(Spinlock::new(content)),
+        },
+    ));
+
+    ramfs_super.lock().inodes.insert(id, Arc::clone(&child));
+    locked_dir.add_entry(&child, String::from(name));
+
+    // Update the metadata of the parent directory.
+    let mut metadata = inode.metadata.lock();
+    metadata.size = locked_dir.entries.len() * core::mem::size_of::<vfs::dirent::DirectoryEntry>();
+    metadata.modification_time = UnixTime::now();
+    metadata.change_time = UnixTime::now();
+    Ok(id)
+}
+
 /// Read the directory entry at the given offset.
 ///
 /// # Errors
@@ -0,0 +1,448 @@
+use crate::{
+    device::{self, Device},
+    module,
+    time::{units::Second, unix::UnixTime},
+    vfs::{self, dentry::Dentry},
+};
+use alloc::collections::BTreeMap;
+
+/// The path of the Limine module containing the initramfs archive.
+const MODULE_PATH: &str = "/boot/initramfs.cpio";
+
+/// The magic number of a newc archive entry without checksum.
+const MAGIC: &[u8] = b"070701";
+
+/// The magic number of a newc archive entry with a checksum of its data.
+const MAGIC_CRC: &[u8] = b"070702";
+
+/// The size of the header of a newc entry: a 6 bytes magic number followed by
+/// 13 fields of 8 hexadecimal digits.
+const HEADER_SIZE: usize = 110;
+
+/// The name of the last entry of an archive.
+const TRAILER: &str = "TRAILER!!!";
+
+/// The names of the fields of a newc header, in the order they are stored.
+const FIELDS: [&str; 13] = [
+    "ino",
+    "mode",
+    "uid",
+    "gid",
+    "nlink",
+    "mtime",
+    "filesize",
+    "devmajor",
+    "devminor",
+    "rdevmajor",
+    "rdevminor",
+    "namesize",
+    "check",
+];
+
+/// The file type bits of the mode of an entry, and the types supported.
+const S_IFMT: u32 = 0o170_000;
+const S_IFSOCK: u32 = 0o140_000;
+const S_IFLNK: u32 = 0o120_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFBLK: u32 = 0o060_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFCHR: u32 = 0o020_000;
+const S_IFIFO: u32 = 0o010_000;
+
+/// Unpack the initramfs archive provided as a Limine module into the root
+/// filesystem, then free the module. If there is no initramfs module, the root
+/// filesystem is left empty. If the archive is malformed, the entries preceding
+/// the error are kept and the error is logged.
+///
+/// This must be called after the module system and the virtual filesystem have
+/// been initialized.
+#[init]
+pub fn setup() {
+    let Some(archive) = module::read(MODULE_PATH) else {
+        log::info!("initramfs: no archive found at {MODULE_PATH}");
+        return;
+    };
+
+    let root = vfs::dentry::ROOT
+        .get()
+        .expect("Root dentry not initialized");
+    match unpack(archive, root) {
+        Ok(count) => log::info!("initramfs: unpacked {count} entries"),
+        Err(error) => log::error!("initramfs: malformed archive: {error:?}"),
+    }
+
+    // SAFETY: The archive is not used anymore after being unpacked, since the
+    // content of the files was copied into the root filesystem.
+    unsafe {
+        module::free(MODULE_PATH);
+    }
+}
+
+/// Unpack a newc cpio archive into the directory `root`, and return the number of
+/// entries unpacked. Several archives can be concatenated, optionally separated
+/// by null bytes: they are unpacked in order, and directories present in multiple
+/// archives are merged.
+///
+/// # Errors
+/// Returns an [`UnpackError`] describing the first malformed entry of the archive.
+/// The entries preceding it are unpacked.
+pub fn unpack(archive: &[u8], root: &Arc<Dentry>) -> Result<usize, UnpackError> {
+    let mut unpacker = Unpacker {
+        root,
+        links: BTreeMap::new(),
+        directories: Vec::new(),
+    };
+
+    let mut offset = 0;
+    let mut count = 0;
+    let result = loop {
+        // Skip the padding between concatenated archives.
+        while archive.get(offset) == Some(&0) {
+            offset += 1;
+        }
+        if offset >= archive.len() {
+            break Ok(count);
+        }
+
+        let entry = match Entry::parse(archive, offset) {
+            Ok(entry) => entry,
+            Err(error) => break Err(error),
+        };
+
+        offset = entry.next;
+        if entry.name == TRAILER {
+            unpacker.links.clear();
+            continue;
+        }
+        if let Err(error) = unpacker.unpack(&entry) {
+            break Err(error);
+        }
+        count += 1;
+    };
+
+    // The modification time of the directories is restored once all entries are
+    // unpacked, since creating an entry in a directory updates it.
+    for (dentry, mtime) in unpacker.directories {
+        dentry.inode().metadata.lock().modification_time = mtime;
+    }
+    result
+}
+
+/// The state kept while unpacking an archive.
+struct Unpacker<'a> {
+    /// The directory where the archive is unpacked.
+    root: &'a Arc<Dentry>,
+
+    /// The regular files with multiple hard links already unpacked, indexed by
+    /// their device and inode numbers in the archive.
+    links: BTreeMap<(u32, u32, u32), Arc<Dentry>>,
+
+    /// The directories unpacked, with the modification time to restore.
+    directories: Vec<(Arc<Dentry>, UnixTime)>,
+}
+
+impl Unpacker<'_> {
+    /// Create the file described by the given entry.
+    ///
+    /// # Errors
+    /// See [`UnpackError`] for the possible errors.
+    fn unpack(&mut self, entry: &Entry) -> Result<(), UnpackError> {
+        let Some(path) = entry.path()? else {
+            // The entry is the root directory itself
+            self.apply_metadata(self.root, entry);
+            return Ok(());
+        };
+
+        let parent = vfs::lookup(
+            &path,
+            self.root,
+            self.root,
+            vfs::LookupFlags::PARENT | vfs::LookupFlags::DIRECTORY,
+        )
+        .map_err(|_| UnpackError::MissingParent(String::from(entry.name)))?;
+        let name = path.components.last().unwrap();
+        let directory = parent.inode().as_directory().unwrap();
+
+        let created = match entry.mode & S_IFMT {
+            S_IFDIR => {
+                // The directory may already exist when concatenated archives are
+                // merged: it is checked below that the existing entry is a directory.
+                _ = directory.mkdir(parent.inode(), name.as_str());
+                parent.forget_negative(name);
+                let existing = Dentry::fetch(&parent, name).map_err(|_| entry.io_error())?;
+                if existing.inode().kind != vfs::inode::Kind::Directory {
+                    return Err(UnpackError::AlreadyExists(String::from(entry.name)));
+                }
+                existing
+            }
+            S_IFREG => self.unpack_file(entry, &parent, name)?,
+            S_IFLNK => {
+                let target = core::str::from_utf8(entry.data)
+                    .map_err(|_| UnpackError::BadName(entry.offset))?;
+                directory
+                    .symlink(parent.inode(), name.as_str(), target)
+                    .map_err(|_| UnpackError::AlreadyExists(String::from(entry.name)))?;
+                parent.forget_negative(name);
+                Dentry::fetch(&parent, name).map_err(|_| entry.io_error())?
+            }
+            kind @ (S_IFCHR | S_IFBLK) => {
+                let device = match kind {
+                    S_IFCHR => Device::Char(entry.rdev),
+                    _ => Device::Block(entry.rdev),
+                };
+                directory
+                    .mknod(parent.inode(), name.as_str(), device)
+                    .map_err(|_| UnpackError::AlreadyExists(String::from(entry.name)))?;
+                parent.forget_negative(name);
+                Dentry::fetch(&parent, name).map_err(|_| entry.io_error())?
+            }
+            S_IFIFO | S_IFSOCK => {
+                log::warn!(
+                    "initramfs: skipping unsupported special file {}",
+                    entry.name
+                );
+                return Ok(());
+            }
+            _ => return Err(UnpackError::BadMode(String::from(entry.name), entry.mode)),
+        };
+
+        self.apply_metadata(&created, entry);
+        Ok(())
+    }
+
+    /// Create the regular file described by the given entry, or link it to a
+    /// previous entry with the same inode number. In an archive, the content of
+    /// a file with multiple hard links is only stored with the last link.
+    ///
+    /// # Errors
+    /// See [`UnpackError`] for the possible errors.
+    fn unpack_file(
+        &mut self,
+        entry: &Entry,
+        parent: &Arc<Dentry>,
+        name: &vfs::Name,
+    ) -> Result<Arc<Dentry>, UnpackError> {
+        let key = (entry.dev.major, entry.dev.minor, entry.ino);
+        let created = match self.links.get(&key).filter(|_| entry.nlink > 1) {
+            Some(target) => {
+                parent
+                    .inode()
+                    .as_directory()
+                    .unwrap()
+                    .link(parent.inode(), name.as_str(), target.inode())
+                    .map_err(|_| UnpackError::AlreadyExists(String::from(entry.name)))?;
+                parent.forget_negative(name);
+                Dentry::fetch(parent, name).map_err(|_| entry.io_error())?
+            }
+            None => Dentry::create_and_fetch_file(parent, name.clone())
+                .map_err(|_| UnpackError::AlreadyExists(String::from(entry.name)))?,
+        };
+
+        if entry.nlink > 1 {
+            self.links.insert(key, Arc::clone(&created));
+        }
+
+        if !entry.data.is_empty() {
+            let file = created
+                .open(vfs::file::OpenFlags::WRITE)
+                .map_err(|_| entry.io_error())?;
+            let operation = file.as_file().ok_or_else(|| entry.io_error())?;
+
+            let mut written = 0;
+            while written < entry.data.len() {
+                written += operation
+                    .write(&file, &entry.data[written..], vfs::file::Offset(written))
+                    .ok()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(|| entry.io_error())?;
+            }
+        }
+        Ok(created)
+    }
+
+    /// Apply the permissions and the modification time of the entry to the
+    /// given dentry.
+    fn apply_metadata(&mut self, target: &Arc<Dentry>, entry: &Entry) {
+        let mtime = UnixTime(Second::new(u64::from(entry.mtime)));
+        let mut metadata = target.inode().metadata.lock();
+        metadata.mode = (entry.mode & 0o7777) as u16;
+        metadata.modification_time = mtime;
+        metadata.access_time = mtime;
+        drop(metadata);
+
+        if target.inode().kind == vfs::inode::Kind::Directory {
+            self.directories.push((Arc::clone(target), mtime));
+        }
+        target.dirtying_inode();
+    }
+}
+
+/// An entry of a newc archive.
+struct Entry<'a> {
+    /// The offset of the entry in the archive.
+    offset: usize,
+
+    /// The offset of the next entry in the archive.
+    next: usize,
+
+    /// The inode number of the file, used to identify hard links.
+    ino: u32,
+
+    /// The file type and permissions of the file.
+    mode: u32,
+
+    /// The number of hard links to the file.
+    nlink: u32,
+
+    /// The modification time of the file, in seconds since the Unix epoch.
+    mtime: u32,
+
+    /// The device containing the file, used to identify hard links.
+    dev: device::Identifier,
+
+    /// The device represented by the file, if it is a device file.
+    rdev: device::Identifier,
+
+    /// The path of the file in the archive.
+    name: &'a str,
+
+    /// The content of the file, or the target of a symbolic link.
+    data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Parse the entry starting at the given offset of the archive.
+    ///
+    /// # Errors
+    /// See [`UnpackError`] for the possible errors.
+    fn parse(archive: &'a [u8], offset: usize) -> Result<Self, UnpackError> {
+        let header = archive
+            .get(offset..offset + HEADER_SIZE)
+            .ok_or(UnpackError::Truncated(offset))?;
+
+        let checksum = match &header[..MAGIC.len()] {
+            MAGIC => false,
+            MAGIC_CRC => true,
+            _ => return Err(UnpackError::BadMagic(offset)),
+        };
+
+        let mut fields = [0; FIELDS.len()];
+        for (i, field) in fields.iter_mut().enumerate() {
+            let start = MAGIC.len() + i * 8;
+            *field = core::str::from_utf8(&header[start..start + 8])
+                .ok()
+                .filter(|hex| hex.bytes().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(UnpackError::BadField(offset, FIELDS[i]))?;
+        }
+
+        // The name is null-terminated, and both the name and the data are padded
+        // to a multiple of 4 bytes from the start of the entry.
+        let name_start = offset + HEADER_SIZE;
+        let name_size = fields[11] as usize;
+        let name = archive
+            .get(name_start..name_start + name_size)
+            .ok_or(UnpackError::Truncated(offset))?
+            .split_last()
+            .filter(|(&nul, name)| nul == 0 && !name.is_empty())
+            .and_then(|(_, name)| core::str::from_utf8(name).ok())
+            .ok_or(UnpackError::BadName(offset))?;
+
+        let data_start = align(name_start + name_size);
+        let data_size = fields[6] as usize;
+        let data = archive
+            .get(data_start..data_start + data_size)
+            .ok_or(UnpackError::Truncated(offset))?;
+
+        if checksum {
+            let sum = data
+                .iter()
+                .fold(0u32, |sum, &byte| sum.wrapping_add(u32::from(byte)));
+            if sum != fields[12] {
+                return Err(UnpackError::BadChecksum(String::from(name)));
+            }
+        }
+
+        Ok(Self {
+            offset,
+            next: align(data_start + data_size),
+            ino: fields[0],
+            mode: fields[1],
+            nlink: fields[4],
+            mtime: fields[5],
+            dev: device::Identifier {
+                major: fields[7],
+                minor: fields[8],
+            },
+            rdev: device::Identifier {
+                major: fields[9],
+                minor: fields[10],
+            },
+            name,
+            data,
+        })
+    }
+
+    /// Returns the path of the entry relative to the directory where the archive
+    /// is unpacked, or `None` if the entry is this directory itself.
+    ///
+    /// # Errors
+    /// Returns [`UnpackError::BadName`] if the name is not a valid path.
+    fn path(&self) -> Result<Option<vfs::Path>, UnpackError> {
+        let mut name = self.name;
+        while let Some(rest) = name.strip_prefix("./").or_else(|| name.strip_prefix('/')) {
+            name = rest;
+        }
+        if name.is_empty() || name == "." {
+            return Ok(None);
+        }
+        vfs::Path::new(name)
+            .map(Some)
+            .map_err(|_| UnpackError::BadName(self.offset))
+    }
+
+    /// Returns the error reported when the file of this entry could not be
+    /// created or written.
+    fn io_error(&self) -> UnpackError {
+        UnpackError::IoError(String::from(self.name))
+    }
+}
+
+/// Round the given offset up to the next multiple of 4.
+const fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UnpackError {
+    /// The archive ends in the middle of the entry starting at the given offset.
+    Truncated(usize),
+
+    /// The entry at the given offset does not start with a newc magic number.
+    BadMagic(usize),
+
+    /// The given header field of the entry at the given offset is not a valid
+    /// hexadecimal number.
+    BadField(usize, &'static str),
+
+    /// The name of the entry at the given offset, or the target of a symbolic
+    /// link, is not a valid null-terminated UTF-8 path.
+    BadName(usize),
+
+    /// The data of the given entry does not match the checksum of its header.
+    BadChecksum(String),
+
+    /// The file type in the mode of the given entry is unknown.
+    BadMode(String, u32),
+
+    /// The parent directory of the given entry does not exist in the archive,
+    /// or is not a directory.
+    MissingParent(String),
+
+    /// A file with the same path as the given entry already exists, and is not
+    /// a directory that could be merged with the entry.
+    AlreadyExists(String),
+
+    /// The file of the given entry could not be created or written.
+    IoError(String),
+}
@@ -16,3 +16,12 @@ pub static LIMINE_FRAMEBUFFER: limine::request::FramebufferRequest =
 
 /// The Limine module request. This tells Limine to load modules for us.
 pub static LIMINE_MODULES: limine::request::ModuleRequest = limine::request::ModuleRequest::new();
+
+/// The Limine kernel file request. This gives us the kernel file, and most importantly the
+/// command line passed to the kernel in the Limine configuration file.
+pub static LIMINE_KERNEL_FILE: limine::request::KernelFileRequest =
+    limine::request::KernelFileRequest::new();
+
+/// The Limine RSDP request. This gives us the address of the ACPI root system description
+/// pointer, from which all the ACPI tables can be found.
+pub static LIMINE_RSDP: limine::request::RsdpRequest = limine::request::RsdpRequest::new();
@@ -1,9 +1,23 @@
-use crate::x86_64::serial::{Port, Serial};
-use core::fmt::Write;
+use crate::{
+    device::tty::{serial, vt},
+    x86_64::{
+        self,
+        serial::{Port, Serial},
+    },
+};
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
+/// The first serial port, written with polling. It is used for the logs until
+/// the serial console is set up, and after the kernel has panicked.
 pub static SERIAL: Lazy<Spinlock<Serial>> =
     Lazy::new(|| Spinlock::new(unsafe { Serial::new(Port::COM1) }));
 
+/// Set when the kernel panics, to write the logs with polling.
+static POLLED: AtomicBool = AtomicBool::new(false);
+
 struct Logger;
 
 impl log::Log for Logger {
@@ -21,10 +35,25 @@ impl log::Log for Logger {
                 log::Level::Trace => "\x1b[1m[~]\x1b[0m",
             };
 
-            // Write the log message to the serial port, ignoring any error
-            _ = SERIAL
-                .lock()
-                .write_fmt(format_args!("{} {}\n", level, record.args()));
+            // Write the log message to the serial console, or directly to the
+            // serial port, ignoring any error, and to the framebuffer console.
+            // The serial port is also used by the interrupt handler of the
+            // serial console, so interrupts are disabled while it is locked.
+            x86_64::irq::without(|| {
+                match serial::console() {
+                    Some(console) if !POLLED.load(Ordering::Relaxed) => {
+                        console.log(format_args!("{} {}\n", level, record.args()));
+                    }
+                    _ => {
+                        _ = SERIAL
+                            .lock()
+                            .write_fmt(format_args!("{} {}\n", level, record.args()));
+                    }
+                }
+                if let Some(console) = vt::console() {
+                    console.log(format_args!("{} {}\n", level, record.args()));
+                }
+            });
         }
     }
 
@@ -44,7 +73,9 @@ pub fn setup() {
 
 /// Called when the kernel panics. This function force the unlock of the serial port, because the
 /// panic handle could be called while the serial port is locked, which would cause a deadlock and
-/// prevent the panic message from being printed
+/// prevent the panic message from being printed. The logs already buffered by the serial console
+/// are sent, and the following logs are written with polling. The framebuffer console is unlocked
+/// too, so that the panic message is also drawn on the screen.
 ///
 /// # Safety
 /// This function is unsafe because it force the unlock of the serial port, which could cause a
@@ -53,5 +84,8 @@ pub fn setup() {
 /// thread after this function call.
 #[cold]
 pub unsafe fn on_panic() {
+    POLLED.store(true, Ordering::Relaxed);
+    serial::on_panic();
+    vt::on_panic();
     SERIAL.force_unlock();
 }
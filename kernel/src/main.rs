@@ -23,15 +23,19 @@ extern crate alloc;
 #[cfg(not(target_arch = "x86_64"))]
 compile_error!("Helium only supports x86_64 computers");
 
+pub mod acpi;
+pub mod cmdline;
 pub mod config;
 pub mod device;
 pub mod fs;
+pub mod initramfs;
 pub mod limine;
 pub mod logger;
 pub mod mm;
 pub mod module;
 pub mod panic;
 pub mod qemu;
+pub mod random;
 pub mod syscall;
 pub mod time;
 pub mod user;
@@ -75,28 +79,87 @@ pub unsafe extern "C" fn _start() -> ! {
     // Initialize the memory manager and the allocators
     mm::setup();
 
+    // Draw the logs on the framebuffer console
+    device::tty::vt::early_setup();
+
+    // Retrieve the kernel command line
+    cmdline::setup();
+
     // Initialize the x86_64 architecture dependent code that
     // needs the memory manager to be initialized first
     x86_64::setup();
 
+    // Find the ACPI tables, needed to locate the century register of the CMOS
+    acpi::setup();
+
     // Initialize date and time
     time::date::setup();
 
     // Initialize dynamic timers
     time::timer::setup();
 
+    // Seed the random number generator
+    random::setup();
+
     // Initialize the module system
     module::setup();
 
+    // Route the IRQs through the I/O APICs when there are some
+    x86_64::irq::setup();
+
+    // Initialize the block device layer
+    device::block::setup();
+
     // Register all the filesystems drivers
     fs::register_all();
 
     // Initialize the virtual file system
     vfs::setup();
 
+    // Populate the root filesystem from the initramfs archive
+    initramfs::setup();
+
+    // Create the RAM disks and the loop devices
+    device::block::ram::setup();
+    device::block::loopback::setup();
+
+    // Probe the ATA drives
+    device::block::ata::setup();
+
+    // Enumerate the PCI bus and bind the drivers of its devices
+    device::pci::setup();
+    device::block::virtio::setup();
+
+    // Create /dev/fb0 for the framebuffer set up by the bootloader
+    device::fb::setup();
+
+    // Create /dev/random and /dev/urandom
+    device::random::setup();
+
+    // Create /dev/rtc for the real-time clock
+    device::rtc::setup();
+
+    // Create the serial console
+    device::tty::serial::setup();
+
+    // Create /dev/tty0 for the framebuffer console
+    device::tty::vt::setup();
+
+    // Create /dev/ptmx and mount devpts on /dev/pts
+    device::tty::pty::setup();
+
+    // Drive the PS/2 keyboard and mouse
+    device::input::ps2::setup();
+
     // Setup the userland environment
     user::setup();
 
+    // Start reclaiming kernel caches when running low on memory
+    mm::shrinker::setup();
+
+    // Start writing back dirty inodes periodically
+    vfs::writeback::setup();
+
     // Run the APs
     x86_64::smp::go();
 
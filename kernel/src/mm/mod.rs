@@ -7,6 +7,7 @@ use frame::{
 
 pub mod frame;
 pub mod heap;
+pub mod shrinker;
 
 /// The heap allocator used by the kernel
 #[global_allocator]
@@ -62,9 +63,8 @@ pub unsafe fn reclaim_boot_memory() {
 
     // TODO: Reclaim the memory used by .init section
 
-    // The shell was copied into the ramfs and isn't needed anymore
-    // The init task was loaded into memory and isn't needed anymore
-    size += module::free("/boot/shell.elf").unwrap_or_default();
+    // The init task was loaded into memory and isn't needed anymore. The
+    // initramfs archive was already freed after being unpacked.
     size += module::free("/boot/init.elf").unwrap_or_default();
     log::info!("{size} bytes of boot memory was reclaimed by the kernel");
 }
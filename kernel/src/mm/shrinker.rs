@@ -0,0 +1,91 @@
+use super::FRAME_ALLOCATOR;
+use crate::{
+    time::units::Millisecond,
+    user::{
+        scheduler::{Scheduler, SCHEDULER},
+        task::{self, Priority, Task},
+    },
+};
+
+/// The list of all registered shrinkers.
+static SHRINKERS: Spinlock<Vec<&'static Shrinker>> = Spinlock::new(Vec::new());
+
+/// The interval between two checks of the number of free frames.
+const POLL_INTERVAL: Millisecond = Millisecond::new(100);
+
+/// The number of objects that each shrinker is asked to evict at each pass.
+const BATCH: usize = 128;
+
+/// A shrinker is a kernel cache that can release some of its objects when the
+/// kernel is running out of memory, such as the dentry cache or the inode cache.
+pub struct Shrinker {
+    /// The name of the shrinker, only used for debugging purposes.
+    pub name: &'static str,
+
+    /// Evict at most the given number of unused objects from the cache, and
+    /// return the number of objects effectively evicted.
+    pub scan: fn(count: usize) -> usize,
+}
+
+/// Register a shrinker. Shrinkers are called in the order of registration, so a
+/// cache whose objects pin objects of another cache should be registered first.
+pub fn register(shrinker: &'static Shrinker) {
+    SHRINKERS.lock().push(shrinker);
+}
+
+/// Ask every registered shrinker to evict at most `count` objects, and return
+/// the total number of evicted objects.
+pub fn shrink(count: usize) -> usize {
+    // Copy the list to avoid holding the lock while the shrinkers are running,
+    // allowing them to register other shrinkers if needed.
+    let shrinkers = SHRINKERS.lock().clone();
+    shrinkers
+        .iter()
+        .map(|shrinker| {
+            let evicted = (shrinker.scan)(count);
+            log::trace!("Shrinker {}: {} objects evicted", shrinker.name, evicted);
+            evicted
+        })
+        .sum()
+}
+
+/// Returns the number of free frames, and the number of free frames under which
+/// the kernel is considered to be running out of memory.
+fn watermark() -> (usize, usize) {
+    let allocator = FRAME_ALLOCATOR.lock();
+    let stats = &allocator.state.statistics;
+    let free = stats.usable.0.saturating_sub(stats.allocated.0);
+    let low = core::cmp::max(stats.usable.0 / 32, 256);
+    (free, low)
+}
+
+/// The reclaim kernel thread. It periodically checks the number of free frames
+/// and runs the shrinkers until the number of free frames is above the low
+/// watermark again, or until the shrinkers cannot evict anything anymore.
+///
+/// The shrinkers are not called directly by the frame allocator because they
+/// need to take many locks, and the frame allocator lock may be held by the
+/// caller of the allocator.
+fn reclaim() -> ! {
+    loop {
+        loop {
+            let (free, low) = watermark();
+            if free >= low {
+                break;
+            }
+
+            log::debug!("Low on memory ({} free frames): shrinking caches", free);
+            if shrink(BATCH) == 0 {
+                break;
+            }
+        }
+        task::sleep_for(POLL_INTERVAL);
+    }
+}
+
+/// Spawn the reclaim kernel thread. This must be called after the scheduler
+/// and the virtual filesystem have been initialized.
+#[init]
+pub fn setup() {
+    SCHEDULER.add_task(Task::kernel(reclaim, Priority::Normal));
+}
@@ -0,0 +1,75 @@
+//! The `ChaCha20` stream cipher of Daniel J. Bernstein, used as the generator of
+//! the random bytes, and its permutation, used to mix the entropy pool. This
+//! is the original variant with a 64 bits block counter and a 64 bits nonce.
+
+/// The number of words of a key.
+pub const KEY_WORDS: usize = 8;
+
+/// The number of bytes of a block of the key stream.
+pub const BLOCK_SIZE: usize = 64;
+
+/// The constant words of the state, "expand 32-byte k".
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646E, 0x7962_2D32, 0x6B20_6574];
+
+/// A key of the cipher.
+pub type Key = [u32; KEY_WORDS];
+
+/// The `ChaCha` quarter round, applied to the given words of the state.
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// Apply the 20 rounds of `ChaCha` to the given state. Without the final addition
+/// of the input of the block function, this is a permutation of the state.
+pub fn permute(state: &mut [u32; 16]) {
+    for _ in 0..10 {
+        quarter_round(state, 0, 4, 8, 12);
+        quarter_round(state, 1, 5, 9, 13);
+        quarter_round(state, 2, 6, 10, 14);
+        quarter_round(state, 3, 7, 11, 15);
+        quarter_round(state, 0, 5, 10, 15);
+        quarter_round(state, 1, 6, 11, 12);
+        quarter_round(state, 2, 7, 8, 13);
+        quarter_round(state, 3, 4, 9, 14);
+    }
+}
+
+/// Returns the block of the key stream with the given counter, for the given
+/// key and nonce.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn block(key: &Key, counter: u64, nonce: u64) -> [u32; 16] {
+    let mut input = [0; 16];
+    input[..4].copy_from_slice(&CONSTANTS);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter as u32;
+    input[13] = (counter >> 32) as u32;
+    input[14] = nonce as u32;
+    input[15] = (nonce >> 32) as u32;
+
+    let mut state = input;
+    permute(&mut state);
+    for (word, input) in state.iter_mut().zip(input) {
+        *word = word.wrapping_add(input);
+    }
+    state
+}
+
+/// Fill the given buffer with the key stream of the given key and nonce,
+/// starting at the block with the given counter.
+pub fn fill(key: &Key, counter: u64, nonce: u64, buf: &mut [u8]) {
+    for (index, chunk) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+        let block = block(key, counter.wrapping_add(index as u64), nonce);
+        let bytes = block.iter().flat_map(|word| word.to_le_bytes());
+        for (dst, src) in chunk.iter_mut().zip(bytes) {
+            *dst = src;
+        }
+    }
+}
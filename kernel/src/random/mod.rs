@@ -0,0 +1,349 @@
+//! The kernel random number generator. Entropy is collected into a pool from
+//! the `RDSEED` and `RDRAND` instructions when the CPU has them, from the
+//! jitter of the time stamp counter measured during the boot, and from the
+//! time stamp counter read at each interrupt. The pool is a sponge built on the
+//! `ChaCha20` permutation: the samples are added to half of its state, which is
+//! permuted each time this half is filled.
+//!
+//! The random bytes are produced by a `ChaCha20` generator, whose key is mixed
+//! with a key extracted from the pool when the generator becomes ready, and
+//! then at most once every [`RESEED_INTERVAL`]. The key is replaced by the
+//! first half of a block generated for each request, the second half being the
+//! key of the bytes returned, so that the bytes already returned cannot be
+//! recovered from the state of the generator.
+//!
+//! The generator is ready once the pool has been credited with [`READY_BITS`]
+//! bits of entropy. The bytes returned before are still unpredictable if the
+//! estimation of the entropy is pessimistic, but the interfaces that must not
+//! return weak random bytes wait until the generator is ready.
+use crate::{
+    time::{units::Nanosecond, uptime_fast},
+    user::task::queue::{Interrupted, WaitQueue},
+    vfs,
+    x86_64::{self, instruction},
+};
+use chacha::{Key, KEY_WORDS};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub mod chacha;
+
+/// The number of bits of entropy the pool must be credited with before the
+/// generator is ready.
+pub const READY_BITS: usize = 256;
+
+/// The minimum delay between two reseeds of the generator.
+pub const RESEED_INTERVAL: Nanosecond = Nanosecond::new(60_000_000_000);
+
+/// The number of words of the state of the pool that the samples are added
+/// to, the rest of the state being never exposed.
+const RATE: usize = 8;
+
+/// The number of samples of the jitter of the time stamp counter taken during
+/// the boot, and the number of samples credited as one bit of entropy.
+const JITTER_SAMPLES: usize = 4096;
+const JITTER_SAMPLES_PER_BIT: usize = 32;
+
+/// The number of interrupts credited as one bit of entropy.
+const INTERRUPTS_PER_BIT: usize = 16;
+
+/// The number of times the `RDRAND` and `RDSEED` instructions are retried
+/// when they have no random number available.
+const HARDWARE_RETRIES: usize = 10;
+
+/// The state of the random number generator.
+static STATE: Spinlock<State> = Spinlock::new(State::new());
+
+/// Set once the generator is ready.
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// The tasks waiting for the generator to be ready.
+static WAITERS: Lazy<WaitQueue> = Lazy::new(WaitQueue::new);
+
+/// Set if the CPU supports the `RDRAND` and the `RDSEED` instructions.
+static RDRAND: AtomicBool = AtomicBool::new(false);
+static RDSEED: AtomicBool = AtomicBool::new(false);
+
+/// The entropy pool.
+struct Pool {
+    /// The state of the sponge.
+    state: [u32; 16],
+
+    /// The index of the next word of the state a sample is added to.
+    position: usize,
+
+    /// The number of bits of entropy credited since the last extraction.
+    entropy: usize,
+}
+
+impl Pool {
+    const fn new() -> Self {
+        Self {
+            state: [0; 16],
+            position: 0,
+            entropy: 0,
+        }
+    }
+
+    /// Add the given sample to the pool, without crediting any entropy.
+    #[allow(clippy::cast_possible_truncation)]
+    fn mix(&mut self, sample: u64) {
+        for word in [sample as u32, (sample >> 32) as u32] {
+            self.state[self.position] ^= word;
+            self.position += 1;
+            if self.position == RATE {
+                chacha::permute(&mut self.state);
+                self.position = 0;
+            }
+        }
+    }
+
+    /// Extract a key from the pool. The part of the state the key is read from
+    /// is cleared and the state permuted again, so that the key cannot be
+    /// recovered from the state of the pool.
+    fn extract(&mut self) -> Key {
+        chacha::permute(&mut self.state);
+        let mut key = [0; KEY_WORDS];
+        key.copy_from_slice(&self.state[..KEY_WORDS]);
+        self.state[..KEY_WORDS].fill(0);
+        chacha::permute(&mut self.state);
+        self.position = 0;
+        self.entropy = 0;
+        key
+    }
+}
+
+/// The state of the random number generator.
+struct State {
+    /// The entropy pool.
+    pool: Pool,
+
+    /// The key of the generator.
+    key: Key,
+
+    /// The time of the last reseed of the generator.
+    reseeded: Nanosecond,
+
+    /// The number of interrupts added to the pool not credited yet.
+    interrupts: usize,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            pool: Pool::new(),
+            key: [0; KEY_WORDS],
+            reseeded: Nanosecond::new(0),
+            interrupts: 0,
+        }
+    }
+
+    /// Mix the key of the generator with a key extracted from the pool, after
+    /// adding the random numbers of the CPU to the pool.
+    fn reseed(&mut self) {
+        for _ in 0..KEY_WORDS / 2 {
+            if let Some(sample) = hardware() {
+                self.pool.mix(sample);
+            }
+        }
+        let seed = self.pool.extract();
+        for (word, seed) in self.key.iter_mut().zip(seed) {
+            *word ^= seed;
+        }
+        self.reseeded = uptime_fast();
+    }
+
+    /// Credit the pool with the given number of bits of entropy, and returns
+    /// `true` if the generator became ready.
+    fn credit(&mut self, bits: usize) -> bool {
+        self.pool.entropy = self.pool.entropy.saturating_add(bits);
+        if READY.load(Ordering::Relaxed) || self.pool.entropy < READY_BITS {
+            return false;
+        }
+        self.reseed();
+        READY.store(true, Ordering::Release);
+        true
+    }
+}
+
+/// Returns `true` if the generator is ready, meaning that the pool has been
+/// credited with enough entropy.
+#[must_use]
+pub fn ready() -> bool {
+    READY.load(Ordering::Acquire)
+}
+
+/// A stream of random bytes, generated with a key used only for this stream.
+/// Large requests are generated with a single stream, so that the state of the
+/// generator is locked and its key replaced only once per request.
+pub struct Stream {
+    key: Key,
+    counter: u64,
+}
+
+impl Stream {
+    /// Fill the given buffer with the next random bytes of the stream. The
+    /// bytes of a partial block are discarded.
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        chacha::fill(&self.key, self.counter, 0, buf);
+        let blocks = (buf.len() + chacha::BLOCK_SIZE - 1) / chacha::BLOCK_SIZE;
+        self.counter = self.counter.wrapping_add(blocks as u64);
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.key.fill(0);
+    }
+}
+
+/// Start a new stream of random bytes. This never blocks, even if the
+/// generator is not ready yet.
+#[must_use]
+pub fn stream() -> Stream {
+    let key = x86_64::irq::without(|| {
+        let mut state = STATE.lock();
+        if ready() && uptime_fast() >= state.reseeded + RESEED_INTERVAL {
+            state.reseed();
+        }
+
+        let block = chacha::block(&state.key, 0, 0);
+        state.key.copy_from_slice(&block[..KEY_WORDS]);
+        let mut key = [0; KEY_WORDS];
+        key.copy_from_slice(&block[KEY_WORDS..]);
+        key
+    });
+
+    // The bytes are generated without the state locked since the request can
+    // be large, with a key used only for this stream.
+    Stream { key, counter: 0 }
+}
+
+/// Fill the given buffer with random bytes. This never blocks, even if the
+/// generator is not ready yet.
+pub fn fill(buf: &mut [u8]) {
+    stream().fill(buf);
+}
+
+/// Put the current task to sleep until the generator is ready. The generator
+/// may become ready in an interrupt handler running on another CPU, so it is
+/// checked again once the task is added to the wait queue.
+///
+/// # Errors
+/// Returns [`Interrupted`] if the task was interrupted while waiting.
+pub fn wait() -> Result<(), Interrupted> {
+    WAITERS.sleep_interruptible_until(|| ready().then_some(()))
+}
+
+/// Add the given bytes to the pool without crediting any entropy, like the
+/// bytes written to `/dev/random`.
+pub fn add_bytes(data: &[u8]) {
+    x86_64::irq::without(|| {
+        let mut state = STATE.lock();
+        for chunk in data.chunks(8) {
+            let mut bytes = [0; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            state.pool.mix(u64::from_le_bytes(bytes));
+        }
+    });
+}
+
+/// Add the timing of an interrupt to the pool. This is called by the interrupt
+/// handlers, with interrupts disabled, and must therefore be fast.
+pub fn add_interrupt(irq: u8) {
+    let sample = instruction::rdtsc() ^ (u64::from(irq) << 56);
+    let ready = {
+        let mut state = STATE.lock();
+        state.pool.mix(sample);
+        state.interrupts += 1;
+        if state.interrupts == INTERRUPTS_PER_BIT {
+            state.interrupts = 0;
+            state.credit(1)
+        } else {
+            false
+        }
+    };
+    if ready {
+        wake_up();
+    }
+}
+
+/// Returns a random number from the CPU, from `RDSEED` if possible or else from
+/// `RDRAND`, or `None` if the CPU has no random number generator or if it has
+/// no random number available.
+fn hardware() -> Option<u64> {
+    let retry = |instruction: unsafe fn() -> Option<u64>| {
+        // SAFETY: The instruction is only used if the CPU supports it.
+        (0..HARDWARE_RETRIES).find_map(|_| unsafe { instruction() })
+    };
+    RDSEED
+        .load(Ordering::Relaxed)
+        .then(|| retry(instruction::rdseed))
+        .flatten()
+        .or_else(|| {
+            RDRAND
+                .load(Ordering::Relaxed)
+                .then(|| retry(instruction::rdrand))
+                .flatten()
+        })
+}
+
+/// Wake up the tasks waiting for the generator to be ready.
+fn wake_up() {
+    log::info!("random: generator ready");
+    WAITERS.wake_up_all();
+    vfs::poll::notify();
+}
+
+/// Detect the random number generators of the CPU and seed the pool with them
+/// and with the jitter of the time stamp counter. The random numbers of the CPU
+/// are trusted and fully credited, so that the generator is ready immediately
+/// on the CPUs that have them. Otherwise, the generator becomes ready once
+/// enough interrupts are received.
+#[init]
+pub fn setup() {
+    // SAFETY: The `CPUID` instruction is supported by all x86_64 CPUs.
+    let (rdrand, rdseed) = unsafe {
+        (
+            core::arch::x86_64::__cpuid(1).ecx & (1 << 30) != 0,
+            core::arch::x86_64::__cpuid_count(7, 0).ebx & (1 << 18) != 0,
+        )
+    };
+    RDRAND.store(rdrand, Ordering::Relaxed);
+    RDSEED.store(rdseed, Ordering::Relaxed);
+    Lazy::force(&WAITERS);
+
+    // Measure the time taken by a small computation, whose variations come
+    // from the caches, the pipeline and the interrupts of the hypervisor.
+    let mut last = instruction::rdtsc();
+    for index in 0..JITTER_SAMPLES {
+        let mut value = core::hint::black_box(index as u64);
+        for _ in 0..(last & 0x0F) {
+            value = core::hint::black_box(value.rotate_left(7) ^ last);
+        }
+        let now = instruction::rdtsc();
+        let sample = now.wrapping_sub(last) ^ value;
+        x86_64::irq::without(|| STATE.lock().pool.mix(sample));
+        last = now;
+    }
+
+    let ready = x86_64::irq::without(|| {
+        let mut state = STATE.lock();
+        let mut bits = JITTER_SAMPLES / JITTER_SAMPLES_PER_BIT;
+        for _ in 0..READY_BITS / 64 {
+            if let Some(sample) = hardware() {
+                state.pool.mix(sample);
+                bits += 64;
+            }
+        }
+        state.credit(bits)
+    });
+
+    log::info!(
+        "random: rdrand {}, rdseed {}",
+        if rdrand { "available" } else { "unavailable" },
+        if rdseed { "available" } else { "unavailable" },
+    );
+    if ready {
+        wake_up();
+    }
+}
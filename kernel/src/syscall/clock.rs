@@ -1,18 +1,29 @@
 use crate::{
     time::{
         self,
+        unix::UnixTime,
         units::{Nanosecond, Second},
     },
     user,
 };
 
 ///
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Timespec {
     pub seconds: u64,
     pub nanoseconds: u64,
 }
 
+impl From<UnixTime> for Timespec {
+    fn from(time: UnixTime) -> Self {
+        Self {
+            seconds: u64::from(time),
+            nanoseconds: 0,
+        }
+    }
+}
+
 /// Get the clock monotonic time. This is the time since the kernel booted.
 ///
 /// # Errors
@@ -54,3 +65,73 @@ impl From<GetTimeError> for isize {
         -(error as isize)
     }
 }
+
+/// The clock measuring the time since the Unix epoch, which can be set.
+pub const CLOCK_REALTIME: usize = 0;
+
+/// The clock measuring the time since the boot, which cannot be set.
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// The first second that cannot be stored in the CMOS, the 1st of January 2100.
+const MAX_SECONDS: u64 = 4_102_444_800;
+
+/// Set the time of the given clock to the timespec at the given address. Only
+/// `CLOCK_REALTIME` can be set, and the time is also written to the real-time
+/// clock of the CMOS so that it persists across reboots.
+///
+/// # Errors
+/// See [`SetTimeError`] for details.
+pub fn set_time(clock: usize, buffer: usize) -> Result<usize, SetTimeError> {
+    match clock {
+        CLOCK_REALTIME => (),
+        CLOCK_MONOTONIC => return Err(SetTimeError::NotSettable),
+        _ => return Err(SetTimeError::InvalidClock),
+    }
+
+    let ptr = user::Pointer::new(buffer as *mut Timespec).ok_or(SetTimeError::BadAddress)?;
+
+    // SAFETY: `ptr` is a valid pointer to a [`Timespec`] object.
+    let time = unsafe { user::Object::read(&ptr) };
+    if time.nanoseconds >= 1_000_000_000 {
+        return Err(SetTimeError::InvalidTime);
+    }
+
+    // The CMOS only stores the years up to 2099, and from 2000 if it has no
+    // century register, which is checked when the date is written.
+    if time.seconds >= MAX_SECONDS {
+        return Err(SetTimeError::InvalidTime);
+    }
+
+    let seconds = Nanosecond::from(Second::new(time.seconds));
+    time::date::set_realtime(Nanosecond::new(seconds.0 + time.nanoseconds))
+        .map_err(|_| SetTimeError::InvalidTime)?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum SetTimeError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The timespec is not in the user address space
+    BadAddress,
+
+    /// The clock does not exist
+    InvalidClock,
+
+    /// The clock cannot be set
+    NotSettable,
+
+    /// The time is invalid or cannot be stored in the real-time clock
+    InvalidTime,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<SetTimeError> for isize {
+    fn from(error: SetTimeError) -> Self {
+        -(error as isize)
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    user::{
+        self,
+        scheduler::{Scheduler, SCHEDULER},
+        string::SyscallString,
+    },
+    vfs::{
+        self,
+        inotify::{Instance, Mask, INOTIFY_FILE_OPS},
+    },
+};
+
+/// Create a new inotify instance and return a file descriptor referring to it.
+/// The only supported flag is `IN_NONBLOCK`, which makes reading an instance
+/// without pending events fail instead of blocking.
+///
+/// # Errors
+/// See [`InitError`] for more details.
+pub fn init1(flags: usize) -> Result<usize, InitError> {
+    const IN_NONBLOCK: usize = 0x800;
+    const IN_CLOEXEC: usize = 0x80000;
+
+    if flags & !(IN_NONBLOCK | IN_CLOEXEC) != 0 {
+        return Err(InitError::InvalidFlags);
+    }
+
+    let file = vfs::file::File::new(vfs::file::FileCreateInfo {
+        dentry: None,
+        operation: vfs::file::Operation::File(&INOTIFY_FILE_OPS),
+        open_flags: vfs::file::OpenFlags::READ,
+        data: Box::new(Instance::new(flags & IN_NONBLOCK != 0)),
+    });
+
+    let fd = SCHEDULER
+        .current_task()
+        .files()
+        .lock()
+        .insert(Arc::new(file))
+        .ok_or(InitError::TooManyFilesOpen)?;
+    Ok(fd.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum InitError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// Unknown flags were passed as an argument
+    InvalidFlags,
+
+    /// The task has too many files open
+    TooManyFilesOpen,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<InitError> for isize {
+    fn from(error: InitError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Watch the file designated by `path` for the events in `mask` with the inotify
+/// instance referred by `fd`, and return the watch descriptor. If the file is
+/// already watched by the instance, the existing watch is updated.
+///
+/// # Errors
+/// See [`AddWatchError`] for more details.
+#[allow(clippy::cast_sign_loss)]
+pub fn add_watch(fd: usize, path: usize, mask: usize) -> Result<usize, AddWatchError> {
+    let mask = u32::try_from(mask)
+        .ok()
+        .and_then(Mask::from_bits)
+        .ok_or(AddWatchError::InvalidMask)?;
+    if !mask.intersects(Mask::ALL_EVENTS) {
+        return Err(AddWatchError::InvalidMask);
+    }
+
+    let ptr = user::Pointer::<SyscallString>::from_usize(path).ok_or(AddWatchError::BadAddress)?;
+    let path = user::String::from_raw_ptr(&ptr)
+        .ok_or(AddWatchError::BadAddress)?
+        .fetch()?;
+    let path = vfs::Path::new(&path)?;
+
+    let current_task = SCHEDULER.current_task();
+    let file = current_task
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(AddWatchError::BadFileDescriptor)?
+        .clone();
+    let instance = file
+        .data
+        .downcast_ref::<Arc<Instance>>()
+        .ok_or(AddWatchError::NotAnInstance)?;
+
+    let root = current_task.root();
+    let cwd = current_task.cwd();
+    let flags = if mask.contains(Mask::ONLYDIR) {
+        vfs::LookupFlags::FOLLOW | vfs::LookupFlags::DIRECTORY
+    } else {
+        vfs::LookupFlags::FOLLOW
+    };
+
+    let dentry = vfs::lookup(&path, &root, &cwd, flags)?;
+    if mask.contains(Mask::ONLYDIR) && dentry.inode().kind != vfs::inode::Kind::Directory {
+        return Err(AddWatchError::NotADirectory);
+    }
+
+    let descriptor = instance.add_watch(dentry.inode(), mask);
+    Ok(descriptor as usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum AddWatchError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The path passed as an argument is at an invalid address
+    BadAddress,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The file descriptor does not refer to an inotify instance
+    NotAnInstance,
+
+    /// The mask contains unknown flags or does not contain any event
+    InvalidMask,
+
+    /// The path is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// The path is invalid
+    InvalidPath,
+
+    /// The path is too long
+    PathTooLong,
+
+    /// The path does not exist
+    NoSuchEntry,
+
+    /// A component of the path is not a directory, or the path does not point
+    /// to a directory while `IN_ONLYDIR` was specified
+    NotADirectory,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<vfs::InvalidPath> for AddWatchError {
+    fn from(_: vfs::InvalidPath) -> Self {
+        AddWatchError::InvalidPath
+    }
+}
+
+impl From<vfs::LookupError> for AddWatchError {
+    fn from(error: vfs::LookupError) -> Self {
+        match error {
+            vfs::LookupError::NotADirectory => AddWatchError::NotADirectory,
+            vfs::LookupError::NotFound(_, _) => AddWatchError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => AddWatchError::TooManySymlinks,
+            vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
+                AddWatchError::UnknownError
+            }
+        }
+    }
+}
+
+impl From<user::string::FetchError> for AddWatchError {
+    fn from(e: user::string::FetchError) -> Self {
+        match e {
+            user::string::FetchError::InvalidMemory => AddWatchError::BadAddress,
+            user::string::FetchError::StringTooLong => AddWatchError::PathTooLong,
+            user::string::FetchError::StringNotUtf8 => AddWatchError::InvalidUtf8,
+        }
+    }
+}
+
+impl From<AddWatchError> for isize {
+    fn from(error: AddWatchError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Remove the watch `wd` from the inotify instance referred by `fd`. An
+/// `IN_IGNORED` event is queued on the instance for the removed watch.
+///
+/// # Errors
+/// See [`RemoveWatchError`] for more details.
+pub fn rm_watch(fd: usize, wd: usize) -> Result<usize, RemoveWatchError> {
+    let wd = i32::try_from(wd).map_err(|_| RemoveWatchError::InvalidWatch)?;
+    let file = SCHEDULER
+        .current_task()
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(RemoveWatchError::BadFileDescriptor)?
+        .clone();
+
+    file.data
+        .downcast_ref::<Arc<Instance>>()
+        .ok_or(RemoveWatchError::NotAnInstance)?
+        .remove_watch(wd)
+        .map_err(|e| match e {
+            vfs::inotify::RemoveWatchError::InvalidWatch => RemoveWatchError::InvalidWatch,
+        })?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum RemoveWatchError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The file descriptor does not refer to an inotify instance
+    NotAnInstance,
+
+    /// The watch descriptor is not valid for this instance
+    InvalidWatch,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<RemoveWatchError> for isize {
+    fn from(error: RemoveWatchError) -> Self {
+        -(error as isize)
+    }
+}
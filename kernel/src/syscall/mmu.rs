@@ -1,6 +1,7 @@
 use crate::user;
 use crate::user::scheduler::{Scheduler, SCHEDULER};
 use crate::user::vmm::area::{self, Area, Type};
+use crate::vfs::{self, file::Offset};
 use addr::user::{InvalidUserVirtual, UserVirtual};
 
 /// Map a range of virtual addresses.
@@ -23,7 +24,7 @@ use addr::user::{InvalidUserVirtual, UserVirtual};
 pub fn map(addr: usize, len: usize, access: usize, flags: usize) -> Result<usize, MmapError> {
     let access = area::Access::from_bits(access as u64).ok_or(MmapError::InvalidFlags)?;
     let flags = area::Flags::from_bits(flags as u64).ok_or(MmapError::InvalidFlags)?;
-    let end = UserVirtual::try_new(addr + len)?;
+    let end = UserVirtual::try_new(addr.checked_add(len).ok_or(MmapError::InvalidRange)?)?;
     let start = UserVirtual::try_new(addr)?;
 
     #[cfg(feature = "trace-syscalls")]
@@ -62,6 +63,94 @@ pub fn map(addr: usize, len: usize, access: usize, flags: usize) -> Result<usize
     Ok(range.start.as_usize())
 }
 
+/// Map the memory of the file opened with the given file descriptor, from its
+/// start, in a range of virtual addresses. Only the files of devices whose
+/// memory is directly accessible, like the framebuffer, can be mapped. The
+/// pages are mapped when accessed, and the memory of the device is never given
+/// back to the frame allocator when the range is unmapped.
+///
+/// # Errors
+/// On success, the syscall returns the start address of the mapped area. If the
+/// syscall fails, it can return the same errors as [`map`], and the following
+/// errors:
+/// - `BadFileDescriptor`: the file descriptor is not valid
+/// - `AccessDenied`: the access requested is not allowed by the flags used to
+///                   open the file
+/// - `NoSuchDevice`: the file cannot be mapped
+///
+/// # Panics
+/// This function may panic if the current task does not have a VMM (probably
+/// a kernel task that tried to make a syscall).
+pub fn map_file(
+    addr: usize,
+    len: usize,
+    access: usize,
+    flags: usize,
+    fd: usize,
+) -> Result<usize, MmapError> {
+    let access = area::Access::from_bits(access as u64).ok_or(MmapError::InvalidFlags)?;
+    let flags = area::Flags::from_bits(flags as u64).ok_or(MmapError::InvalidFlags)?;
+    let end = UserVirtual::try_new(addr.checked_add(len).ok_or(MmapError::InvalidRange)?)?;
+    let start = UserVirtual::try_new(addr)?;
+
+    #[cfg(feature = "trace-syscalls")]
+    log::trace!(
+        "mmap: addr = {:#x}, len = {:#x}, access = {:?}, flags = {:?}, fd = {}",
+        addr,
+        len,
+        access,
+        flags,
+        fd
+    );
+
+    let file = SCHEDULER
+        .current_task()
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(MmapError::BadFileDescriptor)?
+        .clone();
+
+    let open_flags = file.open_flags;
+    if (access.contains(area::Access::WRITE) && !open_flags.contains(vfs::file::OpenFlags::WRITE))
+        || !open_flags.contains(vfs::file::OpenFlags::READ)
+    {
+        return Err(MmapError::AccessDenied);
+    }
+
+    let base = file
+        .as_file()
+        .ok_or(MmapError::NoSuchDevice)?
+        .mmap(&file, Offset(0), len)?;
+
+    let area = Area::builder()
+        .kind(Type::Device(base))
+        .range(start..end)
+        .access(access)
+        .flags(flags)
+        .offset(0)
+        .build();
+
+    let range = SCHEDULER
+        .current_task()
+        .thread()
+        .lock()
+        .vmm()
+        .unwrap()
+        .lock()
+        .mmap(area)?;
+
+    #[cfg(feature = "trace-syscalls")]
+    log::trace!(
+        "mmap: mapped {:#x} bytes of fd {} at {:#x}",
+        len,
+        fd,
+        range.start.as_usize()
+    );
+
+    Ok(range.start.as_usize())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(usize)]
 pub enum MmapError {
@@ -71,9 +160,23 @@ pub enum MmapError {
     InvalidRange,
     WouldOverlap,
     OutOfMemory,
+    BadFileDescriptor,
+    AccessDenied,
+    NoSuchDevice,
     UnknownError,
 }
 
+impl From<vfs::file::MmapError> for MmapError {
+    fn from(e: vfs::file::MmapError) -> Self {
+        match e {
+            vfs::file::MmapError::NotSupported | vfs::file::MmapError::NoSuchDevice => {
+                Self::NoSuchDevice
+            }
+            vfs::file::MmapError::InvalidRange => Self::InvalidRange,
+        }
+    }
+}
+
 impl From<InvalidUserVirtual> for MmapError {
     fn from(_: InvalidUserVirtual) -> Self {
         Self::InvalidAddress
@@ -1,10 +1,18 @@
 pub mod clock;
+pub mod inotify;
 pub mod mmu;
+pub mod power;
+pub mod random;
 pub mod serial;
 pub mod task;
 pub mod vfs;
 pub mod video;
 
+use crate::user::{
+    scheduler::{Scheduler, SCHEDULER},
+    task::signal,
+};
+
 /// The type of the return value of a syscall. All syscalls must return a value that fits
 /// in an usize. However, some values are reserved for indicating an error: values between
 /// -1 and -4095 are reserved for indicating an error (see `SyscallError` for more details).
@@ -38,6 +46,28 @@ pub enum Syscall {
     VfsTruncate = 21,
     VfsStat = 22,
     VfsReaddir = 23,
+    VfsGetdents64 = 24,
+    VfsFstat = 25,
+    VfsFstatAt = 26,
+    VfsStatx = 27,
+    VfsSync = 28,
+    VfsSyncFs = 29,
+    VfsFsync = 30,
+    VfsFdatasync = 31,
+    VfsFlock = 32,
+    VfsFcntl = 33,
+    VfsRename = 34,
+    InotifyInit1 = 35,
+    InotifyAddWatch = 36,
+    InotifyRmWatch = 37,
+    VfsPoll = 38,
+    VfsIoctl = 39,
+    VfsMount = 40,
+    VfsUmount = 41,
+    MmuMapFile = 42,
+    Getrandom = 43,
+    ClockSetTime = 44,
+    Reboot = 45,
 }
 
 impl Syscall {
@@ -70,6 +100,28 @@ impl Syscall {
             21 => Some(Self::VfsTruncate),
             22 => Some(Self::VfsStat),
             23 => Some(Self::VfsReaddir),
+            24 => Some(Self::VfsGetdents64),
+            25 => Some(Self::VfsFstat),
+            26 => Some(Self::VfsFstatAt),
+            27 => Some(Self::VfsStatx),
+            28 => Some(Self::VfsSync),
+            29 => Some(Self::VfsSyncFs),
+            30 => Some(Self::VfsFsync),
+            31 => Some(Self::VfsFdatasync),
+            32 => Some(Self::VfsFlock),
+            33 => Some(Self::VfsFcntl),
+            34 => Some(Self::VfsRename),
+            35 => Some(Self::InotifyInit1),
+            36 => Some(Self::InotifyAddWatch),
+            37 => Some(Self::InotifyRmWatch),
+            38 => Some(Self::VfsPoll),
+            39 => Some(Self::VfsIoctl),
+            40 => Some(Self::VfsMount),
+            41 => Some(Self::VfsUmount),
+            42 => Some(Self::MmuMapFile),
+            43 => Some(Self::Getrandom),
+            44 => Some(Self::ClockSetTime),
+            45 => Some(Self::Reboot),
             _ => None,
         }
     }
@@ -111,9 +163,42 @@ fn syscall(id: usize, a: usize, b: usize, c: usize, d: usize, e: usize) -> isize
         Some(Syscall::VfsTruncate) => vfs::truncate(a, b).map_err(Into::into),
         Some(Syscall::VfsStat) => vfs::stat(a, b, c).map_err(Into::into),
         Some(Syscall::VfsReaddir) => vfs::readdir(a, b).map_err(Into::into),
+        Some(Syscall::VfsGetdents64) => vfs::getdents64(a, b, c).map_err(Into::into),
+        Some(Syscall::VfsFstat) => vfs::fstat(a, b).map_err(Into::into),
+        Some(Syscall::VfsFstatAt) => vfs::fstatat(a, b, c, d).map_err(Into::into),
+        Some(Syscall::VfsStatx) => vfs::statx(a, b, c, d, e).map_err(Into::into),
+        Some(Syscall::VfsSync) => vfs::sync(),
+        Some(Syscall::VfsSyncFs) => vfs::syncfs(a).map_err(Into::into),
+        Some(Syscall::VfsFsync) => vfs::fsync(a).map_err(Into::into),
+        Some(Syscall::VfsFdatasync) => vfs::fdatasync(a).map_err(Into::into),
+        Some(Syscall::VfsFlock) => vfs::flock(a, b).map_err(Into::into),
+        Some(Syscall::VfsFcntl) => vfs::fcntl(a, b, c).map_err(Into::into),
+        Some(Syscall::VfsRename) => vfs::rename(a, b, c).map_err(Into::into),
+        Some(Syscall::InotifyInit1) => inotify::init1(a).map_err(Into::into),
+        Some(Syscall::InotifyAddWatch) => inotify::add_watch(a, b, c).map_err(Into::into),
+        Some(Syscall::InotifyRmWatch) => inotify::rm_watch(a, b).map_err(Into::into),
+        Some(Syscall::VfsPoll) => vfs::poll(a, b, c).map_err(Into::into),
+        Some(Syscall::VfsIoctl) => vfs::ioctl(a, b, c).map_err(Into::into),
+        Some(Syscall::VfsMount) => vfs::mount(a, b, c).map_err(Into::into),
+        Some(Syscall::VfsUmount) => vfs::umount(a).map_err(Into::into),
+        Some(Syscall::MmuMapFile) => mmu::map_file(a, b, c, d, e).map_err(Into::into),
+        Some(Syscall::Getrandom) => random::getrandom(a, b, c).map_err(Into::into),
+        Some(Syscall::ClockSetTime) => clock::set_time(a, b).map_err(Into::into),
+        Some(Syscall::Reboot) => power::reboot().map_err(Into::into),
         None => Err(-1), // NoSuchSyscall,
     };
 
+    // Deliver the signals received by the task during the syscall, or before
+    // it if the task was running in the userland.
+    if let Some(signal) = signal::take_fatal() {
+        log::debug!(
+            "Task {} killed by signal {}",
+            SCHEDULER.current_task().id(),
+            signal.number()
+        );
+        task::exit(128 + usize::from(signal.number()));
+    }
+
     #[cfg(feature = "trace-syscalls")]
     {
         if let Ok(value) = result {
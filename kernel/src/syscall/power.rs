@@ -0,0 +1,37 @@
+use crate::{acpi::fadt, vfs};
+
+/// Write all the dirty data back to the disks and reset the computer with the
+/// reset register of the FADT. This only returns if the computer cannot be
+/// reset this way.
+///
+/// # Errors
+/// See [`RebootError`] for more details.
+pub fn reboot() -> Result<usize, RebootError> {
+    vfs::writeback::sync_all();
+    log::info!("Rebooting the computer");
+
+    // SAFETY: All the dirty data was just written back to the disks.
+    unsafe {
+        fadt::reset();
+    }
+    Err(RebootError::NotSupported)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum RebootError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The computer has no reset register that the kernel can use
+    NotSupported,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<RebootError> for isize {
+    fn from(error: RebootError) -> Self {
+        -(error as isize)
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    random,
+    user::buffer::{BufferError, UserStandardBuffer},
+};
+
+/// Do not block if the random number generator is not ready yet, and fail
+/// instead.
+pub const GRND_NONBLOCK: usize = 0x0001;
+
+/// Use the source of `/dev/random` instead of the one of `/dev/urandom`. Both
+/// are the same generator and wait for it to be ready, so this flag has no
+/// effect, like on Linux since its version 5.6.
+pub const GRND_RANDOM: usize = 0x0002;
+
+/// The number of bytes returned at most by a single call, the same as on
+/// Linux.
+const MAX_LEN: usize = 0x01FF_FFFF;
+
+/// Fill the buffer of `len` bytes at `buf` with random bytes, and return the
+/// number of bytes written, which may be less than `len` for large requests.
+/// The task sleeps until the random number generator is ready, unless the
+/// `GRND_NONBLOCK` flag is set.
+///
+/// # Errors
+/// See [`GetRandomError`] for more details.
+pub fn getrandom(buf: usize, len: usize, flags: usize) -> Result<usize, GetRandomError> {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM) != 0 {
+        return Err(GetRandomError::InvalidFlags);
+    }
+
+    if !random::ready() {
+        if flags & GRND_NONBLOCK != 0 {
+            return Err(GetRandomError::WouldBlock);
+        }
+        random::wait().map_err(|_| GetRandomError::Interrupted)?;
+    }
+
+    let len = len.min(MAX_LEN);
+    let mut buffer = UserStandardBuffer::new(buf, len)?;
+    let mut stream = random::stream();
+    let mut chunk = [0; 256];
+    while buffer.remaning() > 0 {
+        let data = &mut chunk[..buffer.remaning().min(256)];
+        stream.fill(data);
+        buffer
+            .write_buffered(data)
+            .ok_or(GetRandomError::BadAddress)?;
+    }
+    chunk.fill(0);
+    Ok(len)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum GetRandomError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The buffer is not in the user address space or is not writable
+    BadAddress,
+
+    /// Unknown flags were passed as an argument
+    InvalidFlags,
+
+    /// The generator is not ready and `GRND_NONBLOCK` was set
+    WouldBlock,
+
+    /// The task was interrupted while waiting for the generator to be ready
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<BufferError> for GetRandomError {
+    fn from(e: BufferError) -> Self {
+        match e {
+            BufferError::NotInUserSpace => Self::BadAddress,
+        }
+    }
+}
+
+impl From<GetRandomError> for isize {
+    fn from(error: GetRandomError) -> Self {
+        -(error as isize)
+    }
+}
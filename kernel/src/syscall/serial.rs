@@ -1,26 +1,33 @@
 use crate::{
-    logger::SERIAL,
+    device::tty::serial,
     user::buffer::{BufferError, UserStandardBuffer},
 };
 use addr::user::InvalidUserVirtual;
+use alloc::vec;
 
-/// Write data to the serial port and return the number of bytes written.
-/// This functions is very basic and does not support any kind of timeout
-/// nor does it support serial ports other than the first one. It does not
-/// even detect if the serial port is prsent !
-/// FIXME: This should obviously be fixed.
+/// Write data to the serial console and return the number of bytes written.
+/// The data goes through the terminal of the serial console, like the data
+/// written to `/dev/console`.
 ///
 /// # Errors
+/// - `SyscallError::NoSerialPort`: there is no serial console.
 /// - `SyscallError::BadAddress`: the buffer is not in the user address space
 ///    or the buffer is not readable.
+/// - `SyscallError::Interrupted`: the task was interrupted while the output of
+///    the console was stopped, before anything was written.
 pub fn write(buffer: usize, len: usize) -> Result<usize, WriteError> {
+    let tty = serial::tty().ok_or(WriteError::NoSerialPort)?;
     let mut buffer = UserStandardBuffer::new(buffer, len)?;
-    let serial = SERIAL.lock();
+    let mut written = 0;
     while let Some(buf) = buffer.read_buffered() {
-        buf.iter().for_each(|&byte| serial.write(byte));
+        match tty.write(buf) {
+            Ok(count) => written += count,
+            Err(_) if written > 0 => break,
+            Err(_) => return Err(WriteError::Interrupted),
+        }
     }
 
-    Ok(buffer.len())
+    Ok(written)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -28,9 +35,15 @@ pub fn write(buffer: usize, len: usize) -> Result<usize, WriteError> {
 pub enum WriteError {
     NoSuchSyscall = 1,
 
+    /// There is no serial port on the system
+    NoSerialPort,
+
     /// The buffer is not in the user address space or the buffer is not readable
     BadAddress,
 
+    /// The task was interrupted while the output was stopped
+    Interrupted,
+
     UnknownError,
 }
 
@@ -54,27 +67,27 @@ impl From<WriteError> for isize {
     }
 }
 
-/// Read data from the serial port. The function will block until some data
-/// is available, and the function will return the number of bytes read.
-///
-/// # Warning
-/// The function is very simple and does not support any kind of timeout.
-/// The caller can block indefinitely if no data is available or even worse,
-/// if the serial port is not present ! Even worse, the thread will be blocked
-/// in an active busy loop, consuming CPU cycles and preventing any preemption
-/// in the core !
-/// FIXME: This should obviously be fixed.
+/// Read data from the serial console, and return the number of bytes read.
+/// The data comes from the terminal of the serial console, like the data read
+/// from `/dev/console`: by default, the task sleeps until a line is typed, and
+/// at most one line is read.
 ///
 /// # Errors
+/// - `SyscallError::NoSerialPort`: there is no serial console.
 /// - `SyscallError::BadAddress`: the buffer is not in the user address space
 ///   or the buffer is not writable.
+/// - `SyscallError::Interrupted`: the task was interrupted while waiting for
+///   data to read.
 pub fn read(buffer: usize, size: usize) -> Result<usize, ReadError> {
+    let tty = serial::tty().ok_or(ReadError::NoSerialPort)?;
     let mut buffer = UserStandardBuffer::new(buffer, size)?;
-    let serial = SERIAL.lock();
+    let mut data = vec![0; core::cmp::min(size, 4096)];
+    let count = tty.read(&mut data).map_err(|_| ReadError::Interrupted)?;
 
-    while buffer.write(serial.read()).is_some() {}
-
-    Ok(buffer.len())
+    buffer
+        .write_buffered(&data[..count])
+        .ok_or(ReadError::BadAddress)?;
+    Ok(count)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -88,6 +101,9 @@ pub enum ReadError {
     /// The buffer is not in the user address space or the buffer is not writable
     BadAddress,
 
+    /// The task was interrupted while waiting for data to read
+    Interrupted,
+
     UnknownError,
 }
 
@@ -1,5 +1,5 @@
 use crate::{
-    time::{timer::Timer, units::Nanosecond, uptime_fast},
+    time::units::Nanosecond,
     user::{
         self,
         scheduler::{self, round_robin::CURRENT_TASK, Scheduler, SCHEDULER},
@@ -21,6 +21,7 @@ pub fn exit(code: usize) -> ! {
         SCHEDULER.current_task().id(),
         code
     );
+    vfs::lock::release_task(&SCHEDULER.current_task());
     scheduler::terminate(code as u64);
     unsafe { SCHEDULER.schedule() };
     unreachable!("Task should never be scheduled again after exiting");
@@ -49,20 +50,7 @@ pub fn id() -> Result<usize, isize> {
 /// This function will never return an error, but it is declared as returning a `Result`
 /// to be consistent with the other syscalls. It always returns `0`.
 pub fn sleep(nano: usize) -> Result<usize, isize> {
-    let expiration = uptime_fast() + Nanosecond::new(nano as u64);
-
-    // Create a timer that will wake up the task when it expires.
-    let current = SCHEDULER.current_task();
-    let timer = Timer::new(expiration, move |_| {
-        if current.state() == task::State::Blocked {
-            current.change_state(task::State::Ready);
-        }
-    });
-
-    // Put the task to sleep if the timer is active (i.e nanoseconds > 0)
-    if timer.active() {
-        task::sleep();
-    }
+    task::sleep_for(Nanosecond::new(nano as u64));
     Ok(0)
 }
 
@@ -137,6 +125,9 @@ pub enum SpawnError {
     /// The kernel ran out of memory while spawning the task
     OutOfMemory,
 
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -152,6 +143,7 @@ impl From<vfs::ReadAllError> for SpawnError {
         match error {
             vfs::ReadAllError::LookupError(e) => match e {
                 vfs::LookupError::NotFound(_, _) => SpawnError::NoSuchFile,
+                vfs::LookupError::TooManySymlinks => SpawnError::TooManySymlinks,
                 vfs::LookupError::NotADirectory => SpawnError::InvalidArgument,
                 vfs::LookupError::CorruptedFilesystem | vfs::LookupError::IoError => {
                     SpawnError::IoError
@@ -1,4 +1,10 @@
 use crate::{
+    device::{self, Device},
+    time::{
+        self,
+        units::{Millisecond, Nanosecond, Second},
+        unix::UnixTime,
+    },
     user::{
         self,
         scheduler::{Scheduler, SCHEDULER},
@@ -45,7 +51,7 @@ pub fn open(dirfd: usize, path: usize, flags: usize) -> Result<usize, OpenError>
         .map_err(|_| OpenError::BadAddress)?;
     let path = vfs::Path::new(&path)?;
 
-    let dentry = match vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::empty()) {
+    let dentry = match vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::FOLLOW) {
         Ok(dentry) => {
             // If the file exists and the `MUST_CREATE` flag is set, we return an error,
             // because the user has specified that the file must be created during the
@@ -131,6 +137,15 @@ pub enum OpenError {
     /// The process has too many files open and cannot open any more
     TooManyFilesOpen,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// The file is a device that does not exist
+    NoSuchDevice,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -147,6 +162,7 @@ impl From<vfs::LookupError> for OpenError {
             vfs::LookupError::NotADirectory => OpenError::InvalidPath,
             vfs::LookupError::CorruptedFilesystem => OpenError::UnknownError,
             vfs::LookupError::NotFound(_, _) => OpenError::NoSuchFile,
+            vfs::LookupError::TooManySymlinks => OpenError::TooManySymlinks,
             vfs::LookupError::IoError => OpenError::IoError,
         }
     }
@@ -157,6 +173,8 @@ impl From<vfs::dentry::CreateFetchError> for OpenError {
         match error {
             vfs::dentry::CreateFetchError::NotADirectory => OpenError::NotADirectory,
             vfs::dentry::CreateFetchError::AlreadyExists => OpenError::AlreadyExists,
+            vfs::dentry::CreateFetchError::InvalidName => OpenError::InvalidPath,
+            vfs::dentry::CreateFetchError::NoSpace => OpenError::NoSpace,
             vfs::dentry::CreateFetchError::IoError => OpenError::IoError,
         }
     }
@@ -164,7 +182,10 @@ impl From<vfs::dentry::CreateFetchError> for OpenError {
 
 impl From<vfs::dentry::OpenError> for OpenError {
     fn from(error: vfs::dentry::OpenError) -> Self {
-        match error {}
+        match error {
+            vfs::dentry::OpenError::NoSuchDevice => OpenError::NoSuchDevice,
+            vfs::dentry::OpenError::IoError => OpenError::IoError,
+        }
     }
 }
 
@@ -180,12 +201,18 @@ impl From<OpenError> for isize {
 /// This function return an error if the file descriptor is invalid.
 pub fn close(fd: usize) -> Result<usize, CloseError> {
     let current_task = SCHEDULER.current_task();
-    current_task
+    let file = current_task
         .files()
         .lock()
         .remove(vfs::fd::Descriptor(fd))
         .ok_or(CloseError::InvalidFileDescriptor)?;
 
+    // Like on Linux, closing any descriptor referring to a file releases all
+    // the record locks held by the task on this file.
+    if let Some(dentry) = &file.dentry {
+        let owner = vfs::lock::Owner::Task(current_task.id());
+        dentry.inode().locks.release(owner);
+    }
     Ok(0)
 }
 
@@ -230,7 +257,7 @@ pub fn read(fd: usize, buf: usize, len: usize) -> Result<usize, ReadError> {
         return Err(ReadError::NotReadable);
     }
 
-    let mut read_buffer = vec![0; 256].into_boxed_slice();
+    let mut read_buffer = vec![0; 4096].into_boxed_slice();
     let mut buffer = user::buffer::UserStandardBuffer::new(buf, len)?;
 
     let mut state = file.state.lock();
@@ -239,10 +266,11 @@ pub fn read(fd: usize, buf: usize, len: usize) -> Result<usize, ReadError> {
     let mut readed = 0;
 
     while remaning > 0 {
+        let chunk = core::cmp::min(remaning, read_buffer.len());
         let bytes_read =
             file.as_file()
                 .ok_or(ReadError::NotAFile)?
-                .read(&file, &mut read_buffer, offset)?;
+                .read(&file, &mut read_buffer[..chunk], offset)?;
 
         // If there is nothing left to read, we break out of the loop
         if bytes_read == 0 {
@@ -257,6 +285,12 @@ pub fn read(fd: usize, buf: usize, len: usize) -> Result<usize, ReadError> {
         offset.0 += bytes_read;
         remaning -= bytes_read;
         readed += bytes_read;
+
+        // A short read means that there is nothing more to read for now. Trying
+        // to read again could block on files that produce data over time.
+        if bytes_read < chunk {
+            break;
+        }
     }
 
     state.offset = offset;
@@ -286,6 +320,18 @@ pub enum ReadError {
     /// anymore
     BrokenPipe,
 
+    /// There is nothing to read and the file is in non-blocking mode
+    WouldBlock,
+
+    /// The task was interrupted while waiting for data to read
+    Interrupted,
+
+    /// The buffer is too small to hold the data to read
+    BufferTooSmall,
+
+    /// An I/O error occurred while reading from the device
+    IoError,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -303,6 +349,10 @@ impl From<vfs::file::ReadError> for ReadError {
         match error {
             vfs::file::ReadError::NotImplemented => Self::NotReadable,
             vfs::file::ReadError::BrokenPipe => Self::BrokenPipe,
+            vfs::file::ReadError::WouldBlock => Self::WouldBlock,
+            vfs::file::ReadError::Interrupted => Self::Interrupted,
+            vfs::file::ReadError::BufferTooSmall => Self::BufferTooSmall,
+            vfs::file::ReadError::IoError => Self::IoError,
         }
     }
 }
@@ -317,10 +367,6 @@ impl From<ReadError> for isize {
 ///
 /// # Errors
 /// See [`WriteError`] for more details.
-///
-/// # Panics
-/// This function panics if a partial write occurs in the filesystem: this is not yet
-/// supported by this syscall.
 pub fn write(fd: usize, buf: usize, len: usize) -> Result<usize, WriteError> {
     let current_task = SCHEDULER.current_task();
     let file = current_task
@@ -341,24 +387,33 @@ pub fn write(fd: usize, buf: usize, len: usize) -> Result<usize, WriteError> {
     let mut offset = state.offset;
     let mut written = 0;
 
+    // A short write or an error stops the loop, and the bytes already written
+    // are returned: the error is only reported if nothing could be written.
     while let Some(data) = buffer.read_buffered() {
-        let bytes_written = file
+        let result = file
             .as_file()
             .ok_or(WriteError::NotAFile)?
-            .write(&file, data, offset)?;
+            .write(&file, data, offset);
+        let bytes_written = match result {
+            Ok(bytes) => bytes,
+            Err(_) if written > 0 => break,
+            Err(error) => return Err(error.into()),
+        };
 
-        assert!(
-            bytes_written == data.len(),
-            "Partial writes are not supported"
-        );
         offset.0 += bytes_written;
         written += bytes_written;
+        if bytes_written < data.len() {
+            break;
+        }
     }
 
     // If the file is associated with an inode, mark it as dirty since the inode
-    // may has been modified
+    // may has been modified, and notify the watchers of the file
     if let Some(dentry) = &file.dentry {
         dentry.dirtying_inode();
+        if written > 0 {
+            vfs::inotify::notify_dentry(dentry, vfs::inotify::Mask::MODIFY);
+        }
     }
 
     state.offset = offset;
@@ -388,6 +443,12 @@ pub enum WriteError {
     /// anymore
     BrokenPipe,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while writing to the device
+    IoError,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -405,6 +466,8 @@ impl From<vfs::file::WriteError> for WriteError {
         match error {
             vfs::file::WriteError::NotImplemented => Self::NotWritable,
             vfs::file::WriteError::BrokenPipe => Self::BrokenPipe,
+            vfs::file::WriteError::NoSpace => Self::NoSpace,
+            vfs::file::WriteError::IoError => Self::IoError,
         }
     }
 }
@@ -434,14 +497,15 @@ pub fn seek(fd: usize, offset: usize, whence: usize) -> Result<usize, SeekError>
         .ok_or(SeekError::InvalidFileDescriptor)?
         .clone();
 
-    let mut state = file.state.lock();
+    // The state of the file must not be locked while seeking, since seeking
+    // relatively to the current offset needs to read it.
     #[allow(clippy::cast_possible_wrap)]
     let offset =
         file.as_file()
             .ok_or(SeekError::NotSeekable)?
             .seek(&file, offset as isize, whence)?;
 
-    state.offset = offset;
+    file.state.lock().offset = offset;
     Ok(offset.0)
 }
 
@@ -574,7 +638,8 @@ pub fn change_cwd(path: usize) -> Result<usize, ChangeCwdError> {
     let root = current_task.root();
     let cwd = current_task.cwd();
 
-    let dentry = vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::DIRECTORY)?;
+    let flags = vfs::LookupFlags::FOLLOW | vfs::LookupFlags::DIRECTORY;
+    let dentry = vfs::lookup(&path, &root, &cwd, flags)?;
     current_task.set_cwd(dentry);
     Ok(0)
 }
@@ -606,6 +671,9 @@ pub enum ChangeCwdError {
     /// The path does not point to a directory
     NotADirectory,
 
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -621,6 +689,7 @@ impl From<vfs::LookupError> for ChangeCwdError {
         match error {
             vfs::LookupError::NotADirectory => ChangeCwdError::NotADirectory,
             vfs::LookupError::NotFound(_, _) => ChangeCwdError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => ChangeCwdError::TooManySymlinks,
             vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
                 ChangeCwdError::UnknownError
             }
@@ -692,7 +761,13 @@ pub fn mkdir(dirfd: usize, path: usize) -> Result<usize, MkdirError> {
         .ok_or(MkdirError::NotADirectory)?
         .mkdir(parent.inode(), name.as_str())?;
 
+    // The directory was created without going through the dentry cache, so
+    // a negative dentry may still claim that the entry does not exist.
+    parent.forget_negative(name);
     parent.inode().mark_dirty();
+
+    let mask = vfs::inotify::Mask::CREATE | vfs::inotify::Mask::ISDIR;
+    vfs::inotify::notify_child(parent.inode(), mask, 0, name);
     Ok(0)
 }
 
@@ -729,6 +804,15 @@ pub enum MkdirError {
     /// The path does not point to a directory
     NotADirectory,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -744,6 +828,7 @@ impl From<vfs::LookupError> for MkdirError {
         match error {
             vfs::LookupError::NotADirectory => MkdirError::NotADirectory,
             vfs::LookupError::NotFound(_, _) => MkdirError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => MkdirError::TooManySymlinks,
             vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
                 MkdirError::UnknownError
             }
@@ -765,6 +850,9 @@ impl From<vfs::inode::MkdirError> for MkdirError {
     fn from(error: vfs::inode::MkdirError) -> Self {
         match error {
             vfs::inode::MkdirError::AlreadyExists => MkdirError::AlreadyExists,
+            vfs::inode::MkdirError::InvalidName => MkdirError::InvalidPath,
+            vfs::inode::MkdirError::NoSpace => MkdirError::NoSpace,
+            vfs::inode::MkdirError::IoError => MkdirError::IoError,
         }
     }
 }
@@ -808,8 +896,11 @@ pub fn rmdir(dirfd: usize, path: usize) -> Result<usize, RmdirError> {
     };
 
     let dentry = vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::DIRECTORY)?;
-    let parent = dentry.parent().unwrap();
+    if dentry.is_mount_root() {
+        return Err(RmdirError::Busy);
+    }
 
+    let parent = dentry.parent().unwrap();
     parent
         .inode()
         .as_directory()
@@ -855,6 +946,15 @@ pub enum RmdirError {
     /// The directory is not empty
     NotEmpty,
 
+    /// The directory is the root of a mounted filesystem
+    Busy,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -870,6 +970,7 @@ impl From<vfs::LookupError> for RmdirError {
         match error {
             vfs::LookupError::NotADirectory => RmdirError::NotADirectory,
             vfs::LookupError::NotFound(_, _) => RmdirError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => RmdirError::TooManySymlinks,
             vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
                 RmdirError::UnknownError
             }
@@ -893,6 +994,7 @@ impl From<vfs::inode::RmdirError> for RmdirError {
             vfs::inode::RmdirError::NotADirectory => RmdirError::NotADirectory,
             vfs::inode::RmdirError::NoSuchEntry => RmdirError::NoSuchEntry,
             vfs::inode::RmdirError::NotEmpty => RmdirError::NotEmpty,
+            vfs::inode::RmdirError::IoError => RmdirError::IoError,
         }
     }
 }
@@ -948,6 +1050,12 @@ pub enum UnlinkError {
     /// The path does not point to a directory
     IsADirectory,
 
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -963,6 +1071,7 @@ impl From<vfs::LookupError> for UnlinkError {
         match error {
             vfs::LookupError::NotADirectory => UnlinkError::ComponentNotADirectory,
             vfs::LookupError::NotFound(_, _) => UnlinkError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => UnlinkError::TooManySymlinks,
             vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
                 UnlinkError::UnknownError
             }
@@ -986,6 +1095,7 @@ impl From<vfs::inode::UnlinkError> for UnlinkError {
             vfs::inode::UnlinkError::ReservedEntry => UnlinkError::UnknownError,
             vfs::inode::UnlinkError::IsADirectory => UnlinkError::IsADirectory,
             vfs::inode::UnlinkError::NoSuchEntry => UnlinkError::NoSuchEntry,
+            vfs::inode::UnlinkError::IoError => UnlinkError::IoError,
         }
     }
 }
@@ -1052,6 +1162,157 @@ pub fn unlink(dirfd: usize, path: usize) -> Result<usize, UnlinkError> {
     Ok(0)
 }
 
+/// Rename an entry of a directory. Both paths are resolved relatively to the
+/// directory pointed by `dirfd`, and must designate an entry in the same parent
+/// directory: moving an entry to another directory is not supported yet.
+///
+/// # Errors
+/// See [`RenameError`] for more details.
+pub fn rename(dirfd: usize, old: usize, new: usize) -> Result<usize, RenameError> {
+    let ptr = user::Pointer::<SyscallString>::from_usize(old).ok_or(RenameError::BadAddress)?;
+    let old = user::String::from_raw_ptr(&ptr)
+        .ok_or(RenameError::BadAddress)?
+        .fetch()?;
+    let old = vfs::Path::new(&old)?;
+
+    let ptr = user::Pointer::<SyscallString>::from_usize(new).ok_or(RenameError::BadAddress)?;
+    let new = user::String::from_raw_ptr(&ptr)
+        .ok_or(RenameError::BadAddress)?
+        .fetch()?;
+    let new = vfs::Path::new(&new)?;
+
+    let current_task = SCHEDULER.current_task();
+    let root = current_task.root();
+
+    // This is the dentry pointed by the file descriptor `dirfd`. If `dirfd` is
+    // `AT_FDCWD`, then the current working directory is used.
+    let cwd = match dirfd {
+        vfs::fd::Descriptor::AT_FDCWD => current_task.cwd(),
+        _ => current_task
+            .files()
+            .lock()
+            .get(vfs::fd::Descriptor(dirfd))
+            .ok_or(RenameError::BadFileDescriptor)?
+            .dentry
+            .clone()
+            .ok_or(RenameError::NotADirectory)?,
+    };
+
+    let flags = vfs::LookupFlags::PARENT | vfs::LookupFlags::DIRECTORY;
+    let old_parent = vfs::lookup(&old, &root, &cwd, flags)?;
+    let new_parent = vfs::lookup(&new, &root, &cwd, flags)?;
+    if !Arc::ptr_eq(&old_parent, &new_parent) {
+        return Err(RenameError::CrossDirectory);
+    }
+
+    let old_name = old.components.last().ok_or(RenameError::InvalidPath)?;
+    let new_name = new.components.last().ok_or(RenameError::InvalidPath)?;
+    if old_name == new_name {
+        return Ok(0);
+    }
+
+    Dentry::rename(&old_parent, old_name, new_name)?;
+    old_parent.dirtying_inode();
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum RenameError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// One of the paths passed as an argument is at an invalid address
+    BadAddress,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// One of the paths is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// One of the paths is invalid
+    InvalidPath,
+
+    /// One of the paths is too long
+    PathTooLong,
+
+    /// A component of one of the paths is too long
+    ComponentTooLong,
+
+    /// The entry to rename does not exist
+    NoSuchEntry,
+
+    /// An entry with the new name already exists
+    AlreadyExists,
+
+    /// A component of one of the paths is not a directory
+    NotADirectory,
+
+    /// The old and the new paths are not in the same directory
+    CrossDirectory,
+
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<vfs::InvalidPath> for RenameError {
+    fn from(_: vfs::InvalidPath) -> Self {
+        RenameError::InvalidPath
+    }
+}
+
+impl From<vfs::LookupError> for RenameError {
+    fn from(error: vfs::LookupError) -> Self {
+        match error {
+            vfs::LookupError::NotADirectory => RenameError::NotADirectory,
+            vfs::LookupError::NotFound(_, _) => RenameError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => RenameError::TooManySymlinks,
+            vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
+                RenameError::UnknownError
+            }
+        }
+    }
+}
+
+impl From<user::string::FetchError> for RenameError {
+    fn from(e: user::string::FetchError) -> Self {
+        match e {
+            user::string::FetchError::InvalidMemory => RenameError::BadAddress,
+            user::string::FetchError::StringTooLong => RenameError::PathTooLong,
+            user::string::FetchError::StringNotUtf8 => RenameError::InvalidUtf8,
+        }
+    }
+}
+
+impl From<vfs::dentry::RenameError> for RenameError {
+    fn from(error: vfs::dentry::RenameError) -> Self {
+        match error {
+            vfs::dentry::RenameError::NotADirectory => RenameError::NotADirectory,
+            vfs::dentry::RenameError::NotFound => RenameError::NoSuchEntry,
+            vfs::dentry::RenameError::AlreadyExists => RenameError::AlreadyExists,
+            vfs::dentry::RenameError::InvalidName => RenameError::InvalidPath,
+            vfs::dentry::RenameError::NoSpace => RenameError::NoSpace,
+            vfs::dentry::RenameError::IoError => RenameError::IoError,
+        }
+    }
+}
+
+impl From<RenameError> for isize {
+    fn from(error: RenameError) -> Self {
+        -(error as isize)
+    }
+}
+
 /// Truncate a file to the given length.
 ///
 /// # Errors
@@ -1067,7 +1328,7 @@ pub fn truncate(path: usize, len: usize) -> Result<usize, TruncateError> {
     let root = current_task.root();
     let cwd = current_task.cwd();
 
-    let dentry = vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::empty())?;
+    let dentry = vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::FOLLOW)?;
 
     dentry
         .inode()
@@ -1076,6 +1337,7 @@ pub fn truncate(path: usize, len: usize) -> Result<usize, TruncateError> {
         .truncate(dentry.inode(), len)?;
 
     dentry.dirtying_inode();
+    vfs::inotify::notify_dentry(&dentry, vfs::inotify::Mask::MODIFY);
     Ok(0)
 }
 
@@ -1109,6 +1371,15 @@ pub enum TruncateError {
     /// The path does not point to a file
     NotAFile,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -1124,6 +1395,7 @@ impl From<vfs::LookupError> for TruncateError {
         match error {
             vfs::LookupError::NotADirectory => TruncateError::NotADirectory,
             vfs::LookupError::NotFound(_, _) => TruncateError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => TruncateError::TooManySymlinks,
             vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
                 TruncateError::UnknownError
             }
@@ -1143,7 +1415,10 @@ impl From<user::string::FetchError> for TruncateError {
 
 impl From<vfs::inode::TruncateError> for TruncateError {
     fn from(error: vfs::inode::TruncateError) -> Self {
-        match error {}
+        match error {
+            vfs::inode::TruncateError::NoSpace => TruncateError::NoSpace,
+            vfs::inode::TruncateError::IoError => TruncateError::IoError,
+        }
     }
 }
 
@@ -1153,6 +1428,33 @@ impl From<TruncateError> for isize {
     }
 }
 
+bitflags::bitflags! {
+    /// Flags that can be passed to the `*at` family of syscalls to control how the path
+    /// is resolved. The values are the same as the Linux ones.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct AtFlags: usize {
+        /// Do not follow the last component of the path if it is a symbolic link.
+        const SYMLINK_NOFOLLOW = 0x100;
+
+        /// If the path is empty, operate on the file referred by the `dirfd` file
+        /// descriptor instead of resolving a path. If `dirfd` is `AT_FDCWD`, the
+        /// current working directory is used.
+        const EMPTY_PATH = 0x1000;
+
+        /// Do whatever `stat` usually does to synchronize the attributes with the
+        /// filesystem. Only meaningful for `statx`.
+        const STATX_FORCE_SYNC = 0x2000;
+
+        /// Do not synchronize the attributes with the filesystem. Only meaningful
+        /// for `statx`.
+        const STATX_DONT_SYNC = 0x4000;
+    }
+}
+
+/// Informations about a file. This structure has exactly the same layout as the
+/// `struct stat` of Linux on `x86_64`, so it can directly be used by programs
+/// written against the Linux ABI.
+#[derive(Debug, Clone)]
 #[repr(C)]
 pub struct Stat {
     /// Device ID of device containing file
@@ -1161,14 +1463,32 @@ pub struct Stat {
     /// Inode number
     pub ino: u64,
 
+    /// Number of hard links
+    pub nlink: u64,
+
+    /// File type and mode
+    pub mode: u32,
+
+    /// User ID of the owner
+    pub uid: u32,
+
+    /// Group ID of the owner
+    pub gid: u32,
+
+    /// Padding to align the next field
+    pub pad0: u32,
+
+    /// Device ID of the file if it is a special file
+    pub rdev: u64,
+
     /// Size of the file in bytes
-    pub size: u64,
+    pub size: i64,
 
-    /// File type
-    pub kind: u64,
+    /// Preferred block size for I/O operations
+    pub blksize: i64,
 
-    /// Number of hard links
-    pub nlink: u64,
+    /// Number of 512 bytes blocks allocated
+    pub blocks: i64,
 
     /// Unix timestamp of the last access
     pub atime: Timespec,
@@ -1178,61 +1498,147 @@ pub struct Stat {
 
     /// Unix timestamp of the last status change
     pub ctime: Timespec,
+
+    /// Reserved for future use
+    pub unused: [i64; 3],
+}
+
+impl Stat {
+    /// Bit mask for the file type bits of the `mode` field.
+    pub const S_IFMT: u32 = 0o170_000;
+    pub const S_IFIFO: u32 = 0o010_000;
+    pub const S_IFCHR: u32 = 0o020_000;
+    pub const S_IFDIR: u32 = 0o040_000;
+    pub const S_IFBLK: u32 = 0o060_000;
+    pub const S_IFREG: u32 = 0o100_000;
+    pub const S_IFLNK: u32 = 0o120_000;
+
+    /// The preferred block size for I/O operations reported to the userland.
+    pub const BLOCK_SIZE: i64 = 4096;
+
+    /// Returns the `mode` field of an inode of the given kind with the given
+    /// permission bits.
+    #[must_use]
+    pub const fn mode(kind: vfs::inode::Kind, permissions: u16) -> u32 {
+        let kind = match kind {
+            vfs::inode::Kind::BlockDevice(_) => Self::S_IFBLK,
+            vfs::inode::Kind::CharDevice(_) => Self::S_IFCHR,
+            vfs::inode::Kind::Directory => Self::S_IFDIR,
+            vfs::inode::Kind::Pipe => Self::S_IFIFO,
+            vfs::inode::Kind::File => Self::S_IFREG,
+            vfs::inode::Kind::Symlink => Self::S_IFLNK,
+        };
+        kind | (permissions as u32 & 0o7777)
+    }
+
+    /// Returns the device identifier of the special file represented by the given
+    /// inode kind, or [`Device::None`] if the inode is not a special file.
+    #[must_use]
+    pub const fn rdev(kind: vfs::inode::Kind) -> Device {
+        match kind {
+            vfs::inode::Kind::BlockDevice(id) => Device::Block(id),
+            vfs::inode::Kind::CharDevice(id) => Device::Char(id),
+            _ => Device::None,
+        }
+    }
+
+    /// Create the stat structure of an anonymous pipe. Anonymous pipes are not
+    /// backed by an inode, so most of the fields are zeroed.
+    #[must_use]
+    pub fn pipe() -> Self {
+        Self {
+            dev: 0,
+            ino: 0,
+            nlink: 1,
+            mode: Self::mode(vfs::inode::Kind::Pipe, 0o600),
+            uid: 0,
+            gid: 0,
+            pad0: 0,
+            rdev: 0,
+            size: 0,
+            blksize: Self::BLOCK_SIZE,
+            blocks: 0,
+            atime: Timespec::from(UnixTime::now()),
+            mtime: Timespec::from(UnixTime::now()),
+            ctime: Timespec::from(UnixTime::now()),
+            unused: [0; 3],
+        }
+    }
 }
 
-/// Get information about a file.
+impl From<&vfs::inode::Inode> for Stat {
+    #[allow(clippy::cast_possible_wrap)]
+    fn from(inode: &vfs::inode::Inode) -> Self {
+        let metadata = inode.metadata.lock();
+        Self {
+            dev: inode.device.encode(),
+            ino: inode.id.0,
+            nlink: metadata.links,
+            mode: Self::mode(inode.kind, metadata.mode),
+            uid: 0,
+            gid: 0,
+            pad0: 0,
+            rdev: Self::rdev(inode.kind).encode(),
+            size: metadata.size as i64,
+            blksize: Self::BLOCK_SIZE,
+            blocks: ((metadata.size + 511) / 512) as i64,
+            atime: Timespec::from(metadata.access_time),
+            mtime: Timespec::from(metadata.modification_time),
+            ctime: Timespec::from(metadata.change_time),
+            unused: [0; 3],
+        }
+    }
+}
+
+/// Get information about a file. This is the same as [`fstatat`] without any flags
+/// and is kept for compatibility with existing programs.
 ///
 /// # Errors
 /// See [`StatError`] for more details.
 pub fn stat(dirfd: usize, path: usize, stat: usize) -> Result<usize, StatError> {
-    let ptr = user::Pointer::<SyscallString>::from_usize(path).ok_or(StatError::BadAddress)?;
-    let path = user::String::from_raw_ptr(&ptr)
-        .ok_or(StatError::BadAddress)?
-        .fetch()?;
-    let path = vfs::Path::new(&path)?;
+    fstatat(dirfd, path, stat, 0)
+}
 
+/// Get information about the file opened with the file descriptor `fd`, and write
+/// them in the `stat` structure pointed by `stat`.
+///
+/// # Errors
+/// See [`StatError`] for more details.
+pub fn fstat(fd: usize, stat: usize) -> Result<usize, StatError> {
     let ptr = user::Pointer::<Stat>::from_usize(stat).ok_or(StatError::BadAddress)?;
+    let file = SCHEDULER
+        .current_task()
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(StatError::BadFileDescriptor)?
+        .clone();
 
-    let current_task = SCHEDULER.current_task();
-    let root = current_task.root();
+    let stat = file
+        .dentry
+        .as_ref()
+        .map_or_else(Stat::pipe, |dentry| Stat::from(dentry.inode().as_ref()));
 
-    // This is the dentry pointed by the file descriptor `dirfd`. If `dirfd` is
-    // `AT_FDCWD`, then the current working directory is used.
-    let cwd = match dirfd {
-        vfs::fd::Descriptor::AT_FDCWD => current_task.cwd(),
-        _ => current_task
-            .files()
-            .lock()
-            .get(vfs::fd::Descriptor(dirfd))
-            .ok_or(StatError::BadFileDescriptor)?
-            .dentry
-            .clone()
-            .ok_or(StatError::NotADirectory)?,
-    };
+    unsafe {
+        user::Object::write(&ptr, &stat);
+    }
+    Ok(0)
+}
 
-    let dentry = vfs::lookup(&path, &root, &cwd, vfs::LookupFlags::empty())?;
+/// Get information about a file, specified by `path`. If the path is relative, it is
+/// resolved relative to the directory referred by `dirfd`. If the `AT_EMPTY_PATH` flag
+/// is set and the path is empty, the file referred by `dirfd` is used instead.
+///
+/// # Errors
+/// See [`StatError`] for more details.
+pub fn fstatat(dirfd: usize, path: usize, stat: usize, flags: usize) -> Result<usize, StatError> {
+    let flags = AtFlags::from_bits(flags)
+        .filter(|flags| (AtFlags::SYMLINK_NOFOLLOW | AtFlags::EMPTY_PATH).contains(*flags))
+        .ok_or(StatError::InvalidFlag)?;
+    let ptr = user::Pointer::<Stat>::from_usize(stat).ok_or(StatError::BadAddress)?;
 
-    let inode = dentry.inode();
-    let state = inode.metadata.lock();
-    let stat = Stat {
-        dev: 0,  // TODO
-        kind: 0, // TODO
-        ino: inode.id.0,
-        size: state.size as u64,
-        nlink: state.links,
-        atime: Timespec {
-            seconds: state.access_time.0 .0,
-            nanoseconds: 0,
-        },
-        ctime: Timespec {
-            seconds: state.access_time.0 .0,
-            nanoseconds: 0,
-        },
-        mtime: Timespec {
-            seconds: state.access_time.0 .0,
-            nanoseconds: 0,
-        },
-    };
+    let stat = stat_target(dirfd, path, flags)?
+        .map_or_else(Stat::pipe, |inode| Stat::from(inode.as_ref()));
 
     unsafe {
         user::Object::write(&ptr, &stat);
@@ -1240,41 +1646,307 @@ pub fn stat(dirfd: usize, path: usize, stat: usize) -> Result<usize, StatError>
     Ok(0)
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(usize)]
-pub enum StatError {
-    /// The syscall number is invalid.
-    NoSuchSyscall = 1,
+/// A timestamp in the [`Statx`] structure.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct StatxTimestamp {
+    /// Seconds since the Unix epoch
+    pub seconds: i64,
 
-    /// The path passed as an argument
-    BadAddress,
+    /// Nanoseconds since `seconds`
+    pub nanoseconds: u32,
 
-    /// An invalid file descriptor was passed as an argument
-    BadFileDescriptor,
+    /// Reserved for future use
+    pub reserved: i32,
+}
 
-    /// The path is not a valid UTF-8 string
-    InvalidUtf8,
+impl From<UnixTime> for StatxTimestamp {
+    #[allow(clippy::cast_possible_wrap)]
+    fn from(time: UnixTime) -> Self {
+        Self {
+            seconds: u64::from(time) as i64,
+            nanoseconds: 0,
+            reserved: 0,
+        }
+    }
+}
 
-    /// The path is invalid
-    InvalidPath,
+/// Extended informations about a file. This structure has exactly the same layout as
+/// the `struct statx` of Linux.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct Statx {
+    /// Mask of the fields that have been filled by the kernel
+    pub mask: u32,
 
-    /// The path is too long
-    PathTooLong,
+    /// Preferred block size for I/O operations
+    pub blksize: u32,
 
-    /// A component of the path is too long
-    ComponentTooLong,
+    /// Additional file attributes
+    pub attributes: u64,
 
-    /// The path does not exist
-    NoSuchEntry,
+    /// Number of hard links
+    pub nlink: u32,
 
-    /// A component of the path prefix is not a directory
-    NotADirectory,
+    /// User ID of the owner
+    pub uid: u32,
 
-    /// An unknown error occurred
-    UnknownError,
-}
+    /// Group ID of the owner
+    pub gid: u32,
 
-impl From<vfs::InvalidPath> for StatError {
+    /// File type and mode
+    pub mode: u16,
+
+    /// Padding to align the next field
+    pub spare0: u16,
+
+    /// Inode number
+    pub ino: u64,
+
+    /// Size of the file in bytes
+    pub size: u64,
+
+    /// Number of 512 bytes blocks allocated
+    pub blocks: u64,
+
+    /// Mask of the attributes supported by the filesystem
+    pub attributes_mask: u64,
+
+    /// Timestamp of the last access
+    pub atime: StatxTimestamp,
+
+    /// Timestamp of the creation of the file
+    pub btime: StatxTimestamp,
+
+    /// Timestamp of the last status change
+    pub ctime: StatxTimestamp,
+
+    /// Timestamp of the last modification
+    pub mtime: StatxTimestamp,
+
+    /// Major number of the device if the file is a special file
+    pub rdev_major: u32,
+
+    /// Minor number of the device if the file is a special file
+    pub rdev_minor: u32,
+
+    /// Major number of the device containing the file
+    pub dev_major: u32,
+
+    /// Minor number of the device containing the file
+    pub dev_minor: u32,
+
+    /// Mount identifier
+    pub mnt_id: u64,
+
+    /// Memory alignment required for direct I/O
+    pub dio_mem_align: u32,
+
+    /// File offset alignment required for direct I/O
+    pub dio_offset_align: u32,
+
+    /// Reserved for future use
+    pub spare3: [u64; 12],
+}
+
+impl Statx {
+    pub const STATX_TYPE: u32 = 0x0001;
+    pub const STATX_MODE: u32 = 0x0002;
+    pub const STATX_NLINK: u32 = 0x0004;
+    pub const STATX_UID: u32 = 0x0008;
+    pub const STATX_GID: u32 = 0x0010;
+    pub const STATX_ATIME: u32 = 0x0020;
+    pub const STATX_MTIME: u32 = 0x0040;
+    pub const STATX_CTIME: u32 = 0x0080;
+    pub const STATX_INO: u32 = 0x0100;
+    pub const STATX_SIZE: u32 = 0x0200;
+    pub const STATX_BLOCKS: u32 = 0x0400;
+    pub const STATX_BASIC_STATS: u32 = 0x07FF;
+}
+
+impl From<Stat> for Statx {
+    /// Convert a [`Stat`] structure into a [`Statx`] structure. All the basic fields
+    /// are filled, and the creation time is not reported since the VFS does not keep
+    /// track of it.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_possible_wrap)]
+    fn from(stat: Stat) -> Self {
+        let timestamp = |time: Timespec| StatxTimestamp {
+            seconds: time.seconds as i64,
+            nanoseconds: time.nanoseconds as u32,
+            reserved: 0,
+        };
+
+        Self {
+            mask: Self::STATX_BASIC_STATS,
+            blksize: stat.blksize as u32,
+            attributes: 0,
+            nlink: stat.nlink as u32,
+            uid: stat.uid,
+            gid: stat.gid,
+            mode: stat.mode as u16,
+            spare0: 0,
+            ino: stat.ino,
+            size: stat.size as u64,
+            blocks: stat.blocks as u64,
+            attributes_mask: 0,
+            atime: timestamp(stat.atime),
+            btime: StatxTimestamp::from(UnixTime(Second::new(0))),
+            ctime: timestamp(stat.ctime),
+            mtime: timestamp(stat.mtime),
+            rdev_major: Device::decode_major(stat.rdev),
+            rdev_minor: Device::decode_minor(stat.rdev),
+            dev_major: Device::decode_major(stat.dev),
+            dev_minor: Device::decode_minor(stat.dev),
+            mnt_id: 0,
+            dio_mem_align: 0,
+            dio_offset_align: 0,
+            spare3: [0; 12],
+        }
+    }
+}
+
+/// Get extended information about a file, specified by `path` and `dirfd` in the
+/// same way as [`fstatat`]. The `mask` argument tells which fields the caller is
+/// interested in, but the kernel is free to fill more fields than requested: the
+/// `mask` field of the returned structure tells which fields have been filled.
+///
+/// # Errors
+/// See [`StatError`] for more details.
+pub fn statx(
+    dirfd: usize,
+    path: usize,
+    flags: usize,
+    _mask: usize,
+    statx: usize,
+) -> Result<usize, StatError> {
+    let flags = AtFlags::from_bits(flags).ok_or(StatError::InvalidFlag)?;
+    let ptr = user::Pointer::<Statx>::from_usize(statx).ok_or(StatError::BadAddress)?;
+
+    // Both `AT_STATX_FORCE_SYNC` and `AT_STATX_DONT_SYNC` cannot be set at the
+    // same time. They are otherwise ignored because the inodes metadata are always
+    // up to date in memory.
+    if flags.contains(AtFlags::STATX_FORCE_SYNC | AtFlags::STATX_DONT_SYNC) {
+        return Err(StatError::InvalidFlag);
+    }
+
+    let stat = stat_target(dirfd, path, flags)?
+        .map_or_else(Stat::pipe, |inode| Stat::from(inode.as_ref()));
+
+    unsafe {
+        user::Object::write(&ptr, &Statx::from(stat));
+    }
+    Ok(0)
+}
+
+/// Resolve the inode targeted by the stat family of syscalls. If the `AT_EMPTY_PATH`
+/// flag is set and the path is empty (or null), the file referred by `dirfd` is used
+/// instead of resolving the path. Returns `None` if the target is an anonymous pipe,
+/// which does not have an inode.
+fn stat_target(
+    dirfd: usize,
+    path: usize,
+    flags: AtFlags,
+) -> Result<Option<Arc<vfs::inode::Inode>>, StatError> {
+    let current_task = SCHEDULER.current_task();
+    let root = current_task.root();
+
+    let path = if path == 0 && flags.contains(AtFlags::EMPTY_PATH) {
+        String::new()
+    } else {
+        let ptr = user::Pointer::<SyscallString>::from_usize(path).ok_or(StatError::BadAddress)?;
+        user::String::from_raw_ptr(&ptr)
+            .ok_or(StatError::BadAddress)?
+            .fetch()?
+    };
+
+    // With an empty path, the target is directly the file referred by `dirfd`,
+    // which can be any kind of file and not only a directory.
+    if path.is_empty() {
+        if !flags.contains(AtFlags::EMPTY_PATH) {
+            return Err(StatError::NoSuchEntry);
+        }
+        return match dirfd {
+            vfs::fd::Descriptor::AT_FDCWD => Ok(Some(Arc::clone(current_task.cwd().inode()))),
+            _ => Ok(current_task
+                .files()
+                .lock()
+                .get(vfs::fd::Descriptor(dirfd))
+                .ok_or(StatError::BadFileDescriptor)?
+                .dentry
+                .as_ref()
+                .map(|dentry| Arc::clone(dentry.inode()))),
+        };
+    }
+
+    // This is the dentry pointed by the file descriptor `dirfd`. If `dirfd` is
+    // `AT_FDCWD`, then the current working directory is used.
+    let cwd = match dirfd {
+        vfs::fd::Descriptor::AT_FDCWD => current_task.cwd(),
+        _ => current_task
+            .files()
+            .lock()
+            .get(vfs::fd::Descriptor(dirfd))
+            .ok_or(StatError::BadFileDescriptor)?
+            .dentry
+            .clone()
+            .ok_or(StatError::NotADirectory)?,
+    };
+
+    // The last component of the path is followed if it is a symbolic link,
+    // unless the `AT_SYMLINK_NOFOLLOW` flag is set.
+    let lookup = if flags.contains(AtFlags::SYMLINK_NOFOLLOW) {
+        vfs::LookupFlags::empty()
+    } else {
+        vfs::LookupFlags::FOLLOW
+    };
+    let path = vfs::Path::new(&path)?;
+    let dentry = vfs::lookup(&path, &root, &cwd, lookup)?;
+    Ok(Some(Arc::clone(dentry.inode())))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum StatError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The path passed as an argument
+    BadAddress,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The path is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// The path is invalid
+    InvalidPath,
+
+    /// The path is too long
+    PathTooLong,
+
+    /// A component of the path is too long
+    ComponentTooLong,
+
+    /// The path does not exist
+    NoSuchEntry,
+
+    /// A component of the path prefix is not a directory
+    NotADirectory,
+
+    /// An invalid flag or flags combination was passed to the syscall
+    InvalidFlag,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<vfs::InvalidPath> for StatError {
     fn from(_: vfs::InvalidPath) -> Self {
         StatError::InvalidPath
     }
@@ -1285,6 +1957,7 @@ impl From<vfs::LookupError> for StatError {
         match error {
             vfs::LookupError::NotADirectory => StatError::NotADirectory,
             vfs::LookupError::NotFound(_, _) => StatError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => StatError::TooManySymlinks,
             vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
                 StatError::UnknownError
             }
@@ -1322,6 +1995,7 @@ impl Dirent {
     pub const DIRECTORY: u16 = 2;
     pub const CHAR_DEVICE: u16 = 3;
     pub const BLOCK_DEVICE: u16 = 4;
+    pub const SYMLINK: u16 = 5;
 
     #[must_use]
     pub const fn convert_inode_type(kind: vfs::dirent::Kind) -> u16 {
@@ -1330,6 +2004,7 @@ impl Dirent {
             vfs::dirent::Kind::Directory => Self::DIRECTORY,
             vfs::dirent::Kind::CharDevice => Self::CHAR_DEVICE,
             vfs::dirent::Kind::BlockDevice => Self::BLOCK_DEVICE,
+            vfs::dirent::Kind::Symlink => Self::SYMLINK,
         }
     }
 }
@@ -1360,7 +2035,7 @@ pub fn readdir(fd: usize, dirent: usize) -> Result<usize, ReaddirError> {
         .ok_or(ReaddirError::NotADirectory)?
         .readdir(&file, file.state.lock().offset)?;
 
-    file.state.lock().offset.0 += 1;
+    file.state.lock().offset.0 += dirent.offset;
 
     unsafe {
         #[allow(clippy::cast_possible_truncation)]
@@ -1398,6 +2073,9 @@ pub enum ReaddirError {
     /// The directory has no entries remaning
     EndOfDirectory,
 
+    /// An I/O error occurred while reading the directory
+    IoError,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -1406,6 +2084,7 @@ impl From<vfs::file::ReaddirError> for ReaddirError {
     fn from(error: vfs::file::ReaddirError) -> Self {
         match error {
             vfs::file::ReaddirError::EndOfDirectory => ReaddirError::EndOfDirectory,
+            vfs::file::ReaddirError::IoError => ReaddirError::IoError,
         }
     }
 }
@@ -1415,3 +2094,1059 @@ impl From<ReaddirError> for isize {
         -(error as isize)
     }
 }
+
+/// The header of a directory entry returned by the [`getdents64`] syscall. This structure
+/// has exactly the same layout as the `struct linux_dirent64` of Linux. The header is
+/// immediately followed by the null-terminated name of the entry, and the whole record
+/// is padded to a multiple of 8 bytes.
+#[derive(Debug, Clone)]
+#[repr(C, packed)]
+pub struct LinuxDirent64 {
+    /// Inode number
+    pub ino: u64,
+
+    /// Offset to the next entry in the directory
+    pub off: i64,
+
+    /// Length of this record, including the name and the padding
+    pub reclen: u16,
+
+    /// File type
+    pub kind: u8,
+}
+
+impl LinuxDirent64 {
+    pub const DT_UNKNOWN: u8 = 0;
+    pub const DT_FIFO: u8 = 1;
+    pub const DT_CHR: u8 = 2;
+    pub const DT_DIR: u8 = 4;
+    pub const DT_BLK: u8 = 6;
+    pub const DT_REG: u8 = 8;
+    pub const DT_LNK: u8 = 10;
+
+    /// The size of the header, without the name of the entry.
+    pub const HEADER_SIZE: usize = core::mem::size_of::<Self>();
+
+    #[must_use]
+    pub const fn convert_inode_type(kind: vfs::dirent::Kind) -> u8 {
+        match kind {
+            vfs::dirent::Kind::File => Self::DT_REG,
+            vfs::dirent::Kind::Directory => Self::DT_DIR,
+            vfs::dirent::Kind::CharDevice => Self::DT_CHR,
+            vfs::dirent::Kind::BlockDevice => Self::DT_BLK,
+            vfs::dirent::Kind::Symlink => Self::DT_LNK,
+        }
+    }
+
+    /// Returns the length of the record needed to store an entry with a name of
+    /// `name_len` bytes, including the null terminator and the padding.
+    #[must_use]
+    pub const fn record_len(name_len: usize) -> usize {
+        (Self::HEADER_SIZE + name_len + 1 + 7) & !7
+    }
+}
+
+/// Read as many directory entries as possible from the directory descriptor `fd` into
+/// the buffer `buf` of `len` bytes, starting from the current position of the directory.
+/// Each entry is written as a [`LinuxDirent64`] record. Returns the number of bytes
+/// written in the buffer, or 0 if the end of the directory has been reached.
+///
+/// # Errors
+/// See [`Getdents64Error`] for more details.
+///
+/// # Panics
+/// This function panics if a record is written past the end of the user buffer. This
+/// should never happen, and is a serious bug in this function if it does.
+pub fn getdents64(fd: usize, buf: usize, len: usize) -> Result<usize, Getdents64Error> {
+    let current_task = SCHEDULER.current_task();
+    let file = current_task
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(Getdents64Error::InvalidFileDescriptor)?
+        .clone();
+
+    // Check that the file was opened for reading
+    if !file.open_flags.contains(vfs::file::OpenFlags::READ) {
+        return Err(Getdents64Error::NotReadable);
+    }
+
+    let directory = file.as_directory().ok_or(Getdents64Error::NotADirectory)?;
+    let mut buffer = user::buffer::UserStandardBuffer::new(buf, len)?;
+    let mut state = file.state.lock();
+    let mut written = 0;
+
+    loop {
+        let dirent = match directory.readdir(&file, state.offset) {
+            Err(vfs::file::ReaddirError::EndOfDirectory) => break,
+            Err(vfs::file::ReaddirError::IoError) if written > 0 => break,
+            Err(vfs::file::ReaddirError::IoError) => return Err(Getdents64Error::IoError),
+            Ok(dirent) => dirent,
+        };
+
+        // If the record does not fit in the remaining space of the buffer, stop
+        // here: the entry will be returned by the next call. If not even a single
+        // entry could be written, the buffer is too small.
+        let reclen = LinuxDirent64::record_len(dirent.name.len());
+        if reclen > buffer.remaning() {
+            if written == 0 {
+                return Err(Getdents64Error::BufferTooSmall);
+            }
+            break;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_possible_wrap)]
+        let header = LinuxDirent64 {
+            ino: dirent.inode.0,
+            off: (state.offset.0 + dirent.offset) as i64,
+            reclen: reclen as u16,
+            kind: LinuxDirent64::convert_inode_type(dirent.kind),
+        };
+
+        // SAFETY: The header is a plain old data structure without any padding
+        // bytes, so it can safely be viewed as a slice of bytes.
+        let header = unsafe {
+            core::slice::from_raw_parts(
+                core::ptr::addr_of!(header).cast::<u8>(),
+                LinuxDirent64::HEADER_SIZE,
+            )
+        };
+
+        let mut record = Vec::with_capacity(reclen);
+        record.extend_from_slice(header);
+        record.extend_from_slice(dirent.name.as_bytes());
+        record.resize(reclen, 0);
+
+        // Write the record into the user buffer. This cannot fail since we have
+        // checked that the record fits in the remaining space of the buffer.
+        buffer.write_buffered(&record).unwrap();
+        state.offset.0 += dirent.offset;
+        written += reclen;
+    }
+
+    Ok(written)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum Getdents64Error {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The file descriptor is invalid
+    InvalidFileDescriptor,
+
+    /// The buffer passed as an argument is invalid
+    BadAddress,
+
+    /// The descriptor is not a directory
+    NotADirectory,
+
+    /// The directory is not readable
+    NotReadable,
+
+    /// The buffer is too small to hold the next directory entry
+    BufferTooSmall,
+
+    /// An I/O error occurred while reading the directory
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<user::buffer::BufferError> for Getdents64Error {
+    fn from(error: user::buffer::BufferError) -> Self {
+        match error {
+            user::buffer::BufferError::NotInUserSpace => Self::BadAddress,
+        }
+    }
+}
+
+impl From<Getdents64Error> for isize {
+    fn from(error: Getdents64Error) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Synchronize all mounted filesystems with their underlying device.
+///
+/// # Errors
+/// This function never fails, as errors are kept by each superblock to be reported
+/// to the next `fsync` caller on the inodes that failed to be written. It returns a
+/// `Result` only to be consistent with the other syscalls and always returns `0`.
+pub fn sync() -> Result<usize, isize> {
+    vfs::writeback::sync_all();
+    Ok(0)
+}
+
+/// Synchronize the filesystem containing the file referred by the file descriptor
+/// `fd` with its underlying device.
+///
+/// # Errors
+/// See [`SyncFsError`] for more details.
+///
+/// # Panics
+/// This function panics an inode does not have a corresponding superblock. This
+/// should never happen, and is a serious bug in the kernel if it does.
+pub fn syncfs(fd: usize) -> Result<usize, SyncFsError> {
+    let current_task = SCHEDULER.current_task();
+    let dentry = current_task
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(SyncFsError::InvalidFileDescriptor)?
+        .dentry
+        .clone()
+        .ok_or(SyncFsError::NotSupported)?;
+
+    let superblock = dentry
+        .inode()
+        .superblock
+        .upgrade()
+        .expect("Inode without superblock");
+
+    let inodes = superblock.sync_inodes();
+    let sync = superblock.sync();
+    inodes.map_err(|_| SyncFsError::IoError)?;
+    sync.map_err(|_| SyncFsError::IoError)?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum SyncFsError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The file descriptor does not refer to a file on a filesystem
+    NotSupported,
+
+    /// An I/O error occurred while writing to the device
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<SyncFsError> for isize {
+    fn from(error: SyncFsError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Synchronize the inode of the file referred by the file descriptor `fd` with
+/// its underlying device. If a previous writeback of this inode failed, even if
+/// it was not initiated by the caller, the error is reported by this function.
+/// The inode is written to the buffer cache of the device of the filesystem, so
+/// the dirty buffers of this device are then written back and the device is
+/// flushed. If the file is a block device node, the dirty buffers of the device
+/// are written back instead.
+///
+/// # Errors
+/// See [`FsyncError`] for more details.
+///
+/// # Panics
+/// This function panics an inode does not have a corresponding superblock. This
+/// should never happen, and is a serious bug in the kernel if it does.
+pub fn fsync(fd: usize) -> Result<usize, FsyncError> {
+    let current_task = SCHEDULER.current_task();
+    let dentry = current_task
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(FsyncError::InvalidFileDescriptor)?
+        .dentry
+        .clone()
+        .ok_or(FsyncError::NotSupported)?;
+
+    let inode = dentry.inode();
+    if let vfs::inode::Kind::BlockDevice(id) = inode.kind {
+        device::block::cache::sync_device(id).map_err(|_| FsyncError::IoError)?;
+        return Ok(0);
+    }
+
+    let superblock = inode.superblock.upgrade().expect("Inode without superblock");
+    let result = superblock.sync_inode(inode);
+    if let device::Device::Block(id) = superblock.device() {
+        device::block::cache::sync_device(id).map_err(|_| FsyncError::IoError)?;
+    }
+    result.map_err(|_| FsyncError::IoError)?;
+    Ok(0)
+}
+
+/// Synchronize the data of the file referred by the file descriptor `fd` with
+/// its underlying device. There is no page cache yet and the data of a file is
+/// written with its inode, so this is currently the same as [`fsync`].
+///
+/// # Errors
+/// See [`FsyncError`] for more details.
+pub fn fdatasync(fd: usize) -> Result<usize, FsyncError> {
+    fsync(fd)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum FsyncError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The file descriptor does not refer to a file that can be synchronized
+    NotSupported,
+
+    /// An I/O error occurred while writing to the device, now or during a
+    /// previous writeback of the file
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<FsyncError> for isize {
+    fn from(error: FsyncError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Apply or remove a whole-file advisory lock on the open file referred by the
+/// file descriptor `fd`. The lock is owned by the open file, and is therefore
+/// shared by all file descriptors referring to it. It is released when the
+/// last of them is closed.
+///
+/// # Errors
+/// See [`FlockError`] for more details.
+pub fn flock(fd: usize, operation: usize) -> Result<usize, FlockError> {
+    const LOCK_SH: usize = 1;
+    const LOCK_EX: usize = 2;
+    const LOCK_NB: usize = 4;
+    const LOCK_UN: usize = 8;
+
+    let kind = match operation & !LOCK_NB {
+        LOCK_SH => Some(vfs::lock::Kind::Shared),
+        LOCK_EX => Some(vfs::lock::Kind::Exclusive),
+        LOCK_UN => None,
+        _ => return Err(FlockError::InvalidOperation),
+    };
+
+    let current_task = SCHEDULER.current_task();
+    let file = current_task
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(FlockError::InvalidFileDescriptor)?
+        .clone();
+
+    let dentry = file.dentry.as_ref().ok_or(FlockError::NotSupported)?;
+    dentry
+        .inode()
+        .locks
+        .flock(
+            vfs::lock::Owner::file(&file),
+            kind,
+            operation & LOCK_NB == 0,
+        )
+        .map_err(|e| match e {
+            vfs::lock::LockError::WouldBlock => FlockError::WouldBlock,
+            vfs::lock::LockError::Interrupted => FlockError::Interrupted,
+            vfs::lock::LockError::Deadlock => FlockError::UnknownError,
+        })?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum FlockError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The operation is not a valid lock operation
+    InvalidOperation,
+
+    /// The file descriptor does not refer to a file that can be locked
+    NotSupported,
+
+    /// The file is locked and a non-blocking operation was requested
+    WouldBlock,
+
+    /// The task was interrupted while waiting for the lock
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<FlockError> for isize {
+    fn from(error: FlockError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// The description of a record lock, as used by the lock commands of [`fcntl`].
+/// It has the same layout as the `flock` structure of Linux, but the `whence`
+/// field uses the same encoding as the [`seek`] syscall.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Flock {
+    pub kind: i16,
+    pub whence: i16,
+    pub start: i64,
+    pub len: i64,
+    pub pid: i32,
+}
+
+impl Flock {
+    pub const F_RDLCK: i16 = 0;
+    pub const F_WRLCK: i16 = 1;
+    pub const F_UNLCK: i16 = 2;
+
+    /// Compute the range of bytes described by this structure, relative to the
+    /// given offset in the file and to the given file size.
+    fn range(&self, offset: u64, size: u64) -> Result<vfs::lock::Range, FcntlError> {
+        let whence = usize::try_from(self.whence)
+            .ok()
+            .and_then(|whence| vfs::file::Whence::try_from(whence).ok())
+            .ok_or(FcntlError::InvalidArgument)?;
+
+        let base = match whence {
+            vfs::file::Whence::Start => 0,
+            vfs::file::Whence::Current => offset,
+            vfs::file::Whence::End => size,
+        };
+
+        let start = i64::try_from(base)
+            .ok()
+            .and_then(|base| base.checked_add(self.start))
+            .ok_or(FcntlError::InvalidArgument)?;
+
+        // A positive length locks the bytes after the start, a negative length
+        // locks the bytes before it, and a zero length locks until the end of
+        // the file, whatever its size.
+        let (start, end) = match self.len {
+            0 => (start, None),
+            len if len > 0 => (start, start.checked_add(len - 1)),
+            len => {
+                let end = start.checked_sub(1).ok_or(FcntlError::InvalidArgument)?;
+                let start = start.checked_add(len).ok_or(FcntlError::InvalidArgument)?;
+                (start, Some(end))
+            }
+        };
+
+        let start = u64::try_from(start).map_err(|_| FcntlError::InvalidArgument)?;
+        let end = match end {
+            Some(end) => u64::try_from(end).map_err(|_| FcntlError::InvalidArgument)?,
+            None => u64::MAX,
+        };
+        Ok(vfs::lock::Range { start, end })
+    }
+}
+
+/// Perform the command `cmd` on the file descriptor `fd`. For now, only the
+/// record lock commands are supported:
+/// - `F_GETLK`, `F_SETLK` and `F_SETLKW` manage record locks owned by the
+///   current task. They are released when the task closes any descriptor
+///   referring to the file, or when it exits.
+/// - `F_OFD_GETLK`, `F_OFD_SETLK` and `F_OFD_SETLKW` manage record locks owned
+///   by the open file, released when the last descriptor referring to it is
+///   closed.
+///
+/// For all these commands, `arg` is a pointer to a [`Flock`] structure.
+///
+/// # Errors
+/// See [`FcntlError`] for more details.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> Result<usize, FcntlError> {
+    const F_GETLK: usize = 5;
+    const F_SETLK: usize = 6;
+    const F_SETLKW: usize = 7;
+    const F_OFD_GETLK: usize = 36;
+    const F_OFD_SETLK: usize = 37;
+    const F_OFD_SETLKW: usize = 38;
+
+    let current_task = SCHEDULER.current_task();
+    let file = current_task
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(FcntlError::InvalidFileDescriptor)?
+        .clone();
+
+    let (owner, get, wait) = match cmd {
+        F_GETLK => (vfs::lock::Owner::Task(current_task.id()), true, false),
+        F_SETLK => (vfs::lock::Owner::Task(current_task.id()), false, false),
+        F_SETLKW => (vfs::lock::Owner::Task(current_task.id()), false, true),
+        F_OFD_GETLK => (vfs::lock::Owner::file(&file), true, false),
+        F_OFD_SETLK => (vfs::lock::Owner::file(&file), false, false),
+        F_OFD_SETLKW => (vfs::lock::Owner::file(&file), false, true),
+        _ => return Err(FcntlError::InvalidCommand),
+    };
+
+    let ptr = user::Pointer::<Flock>::from_usize(arg).ok_or(FcntlError::BadAddress)?;
+    let mut flock = unsafe { user::Object::read(&ptr) };
+
+    // Open file description locks do not have a process owner, and the
+    // userland must set the `pid` field to 0 as Linux requires it.
+    if matches!(owner, vfs::lock::Owner::File(_)) && flock.pid != 0 {
+        return Err(FcntlError::InvalidArgument);
+    }
+
+    let dentry = file.dentry.as_ref().ok_or(FcntlError::NotSupported)?;
+    let inode = dentry.inode();
+    let size = inode.metadata.lock().size as u64;
+    let offset = file.state.lock().offset.0 as u64;
+    let range = flock.range(offset, size)?;
+
+    let kind = match flock.kind {
+        Flock::F_RDLCK if get || file.open_flags.contains(vfs::file::OpenFlags::READ) => {
+            Some(vfs::lock::Kind::Shared)
+        }
+        Flock::F_WRLCK if get || file.open_flags.contains(vfs::file::OpenFlags::WRITE) => {
+            Some(vfs::lock::Kind::Exclusive)
+        }
+        Flock::F_RDLCK | Flock::F_WRLCK => return Err(FcntlError::BadFileMode),
+        Flock::F_UNLCK if !get => None,
+        _ => return Err(FcntlError::InvalidArgument),
+    };
+
+    if get {
+        let kind = kind.ok_or(FcntlError::InvalidArgument)?;
+        match inode.locks.conflicting(owner, kind, range) {
+            None => flock.kind = Flock::F_UNLCK,
+            Some(record) => {
+                flock.kind = match record.kind {
+                    vfs::lock::Kind::Shared => Flock::F_RDLCK,
+                    vfs::lock::Kind::Exclusive => Flock::F_WRLCK,
+                };
+                flock.whence = 1; // Whence::Start
+                flock.start = record.range.start as i64;
+                flock.len = match record.range.end {
+                    u64::MAX => 0,
+                    end => (end - record.range.start + 1) as i64,
+                };
+                flock.pid = match record.owner {
+                    vfs::lock::Owner::Task(id) => id.0 as i32,
+                    vfs::lock::Owner::File(_) => -1,
+                };
+            }
+        }
+
+        unsafe {
+            user::Object::write(&ptr, &flock);
+        }
+        return Ok(0);
+    }
+
+    inode
+        .locks
+        .lock_range(owner, kind, range, wait)
+        .map_err(|e| match e {
+            vfs::lock::LockError::WouldBlock => FcntlError::WouldBlock,
+            vfs::lock::LockError::Deadlock => FcntlError::Deadlock,
+            vfs::lock::LockError::Interrupted => FcntlError::Interrupted,
+        })?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum FcntlError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The argument pointer is invalid
+    BadAddress,
+
+    /// The command is not supported
+    InvalidCommand,
+
+    /// The lock description is invalid
+    InvalidArgument,
+
+    /// The file was not opened with the mode required by the requested lock
+    BadFileMode,
+
+    /// The file descriptor does not refer to a file that can be locked
+    NotSupported,
+
+    /// The range is locked and a non-blocking command was requested
+    WouldBlock,
+
+    /// Waiting for the lock would cause a deadlock
+    Deadlock,
+
+    /// The task was interrupted while waiting for the lock
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<FcntlError> for isize {
+    fn from(error: FcntlError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// A file descriptor to poll, with the same layout as the `pollfd` structure
+/// used by Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct PollFd {
+    /// The file descriptor to poll. Negative descriptors are ignored.
+    pub fd: i32,
+
+    /// The events the caller is interested in.
+    pub events: i16,
+
+    /// The events that occurred, filled by the kernel.
+    pub revents: i16,
+}
+
+/// Wait for one of the `count` file descriptors in `array` to become ready
+/// for the requested events, or until `timeout` milliseconds have elapsed. A
+/// negative timeout waits forever, and a null timeout never waits. Returns the
+/// number of file descriptors with at least one event reported.
+///
+/// # Errors
+/// See [`PollError`] for more details.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+pub fn poll(array: usize, count: usize, timeout: usize) -> Result<usize, PollError> {
+    if count > vfs::fd::OpenedFiles::CAPACITY * 4 {
+        return Err(PollError::InvalidArgument);
+    }
+
+    let pointers = (0..count)
+        .map(|i| {
+            i.checked_mul(core::mem::size_of::<PollFd>())
+                .and_then(|offset| array.checked_add(offset))
+                .and_then(user::Pointer::<PollFd>::from_usize)
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(PollError::BadAddress)?;
+    let mut polled = pointers
+        .iter()
+        .map(|ptr| unsafe { user::Object::read(ptr) })
+        .collect::<Vec<_>>();
+
+    let deadline = match timeout as isize {
+        timeout if timeout < 0 => None,
+        timeout => {
+            let timeout = Nanosecond::from(Millisecond::new(timeout as u64));
+            Some(time::uptime_fast() + timeout)
+        }
+    };
+
+    let current_task = SCHEDULER.current_task();
+    let ready = loop {
+        let generation = vfs::poll::generation();
+        let files = current_task.files().lock();
+        let mut ready = 0;
+        for pollfd in &mut polled {
+            pollfd.revents = 0;
+            let Ok(fd) = usize::try_from(pollfd.fd) else {
+                continue;
+            };
+
+            // Errors and hangups are always reported, even if the caller did
+            // not ask for them.
+            let requested = vfs::poll::Events::from_bits_truncate(pollfd.events as u16)
+                | vfs::poll::Events::ERR
+                | vfs::poll::Events::HUP;
+            let events = match files.get(vfs::fd::Descriptor(fd)) {
+                None => vfs::poll::Events::NVAL,
+                Some(file) => {
+                    let events = file.as_file().map_or_else(
+                        || vfs::poll::Events::IN | vfs::poll::Events::OUT,
+                        |operation| operation.poll(file),
+                    );
+                    events & requested
+                }
+            };
+
+            pollfd.revents = events.bits() as i16;
+            if !events.is_empty() {
+                ready += 1;
+            }
+        }
+        drop(files);
+
+        if ready > 0 || deadline.map_or(false, |deadline| time::uptime_fast() >= deadline) {
+            break ready;
+        }
+        vfs::poll::wait(generation, deadline).map_err(|_| PollError::Interrupted)?;
+    };
+
+    for (ptr, pollfd) in pointers.iter().zip(&polled) {
+        unsafe {
+            user::Object::write(ptr, pollfd);
+        }
+    }
+    Ok(ready)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum PollError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The array of file descriptors is at an invalid address
+    BadAddress,
+
+    /// The number of file descriptors is too large
+    InvalidArgument,
+
+    /// The task was interrupted while waiting
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<PollError> for isize {
+    fn from(error: PollError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Perform the device-specific request `request` on the file referred by the file
+/// descriptor `fd`. The meaning of `arg` and of the returned value depends on the
+/// request, and is defined by the driver of the device.
+///
+/// # Errors
+/// See [`IoctlError`] for more details.
+pub fn ioctl(fd: usize, request: usize, arg: usize) -> Result<usize, IoctlError> {
+    let file = SCHEDULER
+        .current_task()
+        .files()
+        .lock()
+        .get(vfs::fd::Descriptor(fd))
+        .ok_or(IoctlError::BadFileDescriptor)?
+        .clone();
+
+    Ok(file
+        .as_file()
+        .ok_or(IoctlError::NotATerminal)?
+        .ioctl(&file, request, arg)?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum IoctlError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The request is not supported by the file. The name comes from the Linux
+    /// `ENOTTY` error, returned for historical reasons
+    NotATerminal,
+
+    /// The argument of the request is invalid
+    InvalidArgument,
+
+    /// The argument of the request points to an invalid address
+    BadAddress,
+
+    /// The device is busy
+    Busy,
+
+    /// The device does not exist anymore
+    NoSuchDevice,
+
+    /// An I/O error occurred while handling the request
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<vfs::file::IoctlError> for IoctlError {
+    fn from(error: vfs::file::IoctlError) -> Self {
+        match error {
+            vfs::file::IoctlError::NotSupported => Self::NotATerminal,
+            vfs::file::IoctlError::InvalidArgument => Self::InvalidArgument,
+            vfs::file::IoctlError::BadAddress => Self::BadAddress,
+            vfs::file::IoctlError::BadFileDescriptor => Self::BadFileDescriptor,
+            vfs::file::IoctlError::Busy => Self::Busy,
+            vfs::file::IoctlError::NoSuchDevice => Self::NoSuchDevice,
+            vfs::file::IoctlError::IoError => Self::IoError,
+        }
+    }
+}
+
+impl From<IoctlError> for isize {
+    fn from(error: IoctlError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Mount the filesystem `fstype` stored on the block device node at `source` on
+/// the directory at `target`. Filesystems that are not stored on a device, like
+/// `ramfs`, are mounted with an empty `source`. Relative paths are resolved from
+/// the current working directory.
+///
+/// # Errors
+/// See [`MountError`] for more details.
+pub fn mount(source: usize, target: usize, fstype: usize) -> Result<usize, MountError> {
+    let ptr = user::Pointer::<SyscallString>::from_usize(source).ok_or(MountError::BadAddress)?;
+    let source = user::String::from_raw_ptr(&ptr)
+        .ok_or(MountError::BadAddress)?
+        .fetch()?;
+
+    let ptr = user::Pointer::<SyscallString>::from_usize(target).ok_or(MountError::BadAddress)?;
+    let target = user::String::from_raw_ptr(&ptr)
+        .ok_or(MountError::BadAddress)?
+        .fetch()?;
+    let target = vfs::Path::new(&target)?;
+
+    let ptr = user::Pointer::<SyscallString>::from_usize(fstype).ok_or(MountError::BadAddress)?;
+    let fstype = user::String::from_raw_ptr(&ptr)
+        .ok_or(MountError::BadAddress)?
+        .fetch()?;
+
+    let current_task = SCHEDULER.current_task();
+    let root = current_task.root();
+    let cwd = current_task.cwd();
+
+    let device = if source.is_empty() {
+        Device::None
+    } else {
+        let source = vfs::Path::new(&source)?;
+        let node = vfs::lookup(&source, &root, &cwd, vfs::LookupFlags::FOLLOW)?;
+        match node.inode().kind {
+            vfs::inode::Kind::BlockDevice(id) => Device::Block(id),
+            _ => return Err(MountError::NotABlockDevice),
+        }
+    };
+
+    let flags = vfs::LookupFlags::FOLLOW | vfs::LookupFlags::DIRECTORY;
+    let mountpoint = vfs::lookup(&target, &root, &cwd, flags)?;
+    vfs::fs::mount(&fstype, device, &mountpoint)?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum MountError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// One of the strings passed as an argument is at an invalid address
+    BadAddress,
+
+    /// One of the strings is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// One of the paths is invalid
+    InvalidPath,
+
+    /// One of the strings is too long
+    PathTooLong,
+
+    /// The source or the target does not exist
+    NoSuchEntry,
+
+    /// The target, or a component of one of the paths, is not a directory
+    NotADirectory,
+
+    /// The source is not a block device node
+    NotABlockDevice,
+
+    /// No filesystem with the given type is registered
+    NoSuchFilesystem,
+
+    /// The device does not contain a valid filesystem of the given type
+    InvalidFilesystem,
+
+    /// The target or the device is already used by another mount
+    Busy,
+
+    /// An I/O error occurred while reading the filesystem
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<vfs::InvalidPath> for MountError {
+    fn from(_: vfs::InvalidPath) -> Self {
+        MountError::InvalidPath
+    }
+}
+
+impl From<vfs::LookupError> for MountError {
+    fn from(error: vfs::LookupError) -> Self {
+        match error {
+            vfs::LookupError::NotADirectory => MountError::NotADirectory,
+            vfs::LookupError::NotFound(_, _) => MountError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => MountError::TooManySymlinks,
+            vfs::LookupError::IoError => MountError::IoError,
+            vfs::LookupError::CorruptedFilesystem => MountError::UnknownError,
+        }
+    }
+}
+
+impl From<user::string::FetchError> for MountError {
+    fn from(e: user::string::FetchError) -> Self {
+        match e {
+            user::string::FetchError::InvalidMemory => MountError::BadAddress,
+            user::string::FetchError::StringTooLong => MountError::PathTooLong,
+            user::string::FetchError::StringNotUtf8 => MountError::InvalidUtf8,
+        }
+    }
+}
+
+impl From<vfs::fs::MountError> for MountError {
+    fn from(error: vfs::fs::MountError) -> Self {
+        match error {
+            vfs::fs::MountError::NoSuchFilesystem => MountError::NoSuchFilesystem,
+            vfs::fs::MountError::NotADirectory => MountError::NotADirectory,
+            vfs::fs::MountError::Busy => MountError::Busy,
+            vfs::fs::MountError::NotMounted => MountError::UnknownError,
+            vfs::fs::MountError::ReadSuper(error) => match error {
+                vfs::fs::ReadSuperError::CorruptedFileSystem
+                | vfs::fs::ReadSuperError::InvalidFileSystem => MountError::InvalidFilesystem,
+                vfs::fs::ReadSuperError::InvalidDevice | vfs::fs::ReadSuperError::IoError => {
+                    MountError::IoError
+                }
+                vfs::fs::ReadSuperError::NotImplemented => MountError::UnknownError,
+            },
+        }
+    }
+}
+
+impl From<MountError> for isize {
+    fn from(error: MountError) -> Self {
+        -(error as isize)
+    }
+}
+
+/// Unmount the filesystem mounted on the directory at `target`. The dirty data
+/// of the filesystem is written back to its device before it is unmounted.
+///
+/// # Errors
+/// See [`UmountError`] for more details.
+pub fn umount(target: usize) -> Result<usize, UmountError> {
+    let ptr = user::Pointer::<SyscallString>::from_usize(target).ok_or(UmountError::BadAddress)?;
+    let target = user::String::from_raw_ptr(&ptr)
+        .ok_or(UmountError::BadAddress)?
+        .fetch()?;
+    let target = vfs::Path::new(&target)?;
+
+    let current_task = SCHEDULER.current_task();
+    let root = current_task.root();
+    let cwd = current_task.cwd();
+    let flags = vfs::LookupFlags::FOLLOW | vfs::LookupFlags::DIRECTORY;
+    let dentry = vfs::lookup(&target, &root, &cwd, flags)?;
+
+    // Release the references obtained from the current task, so that they are
+    // not counted as uses of the filesystem.
+    drop(root);
+    drop(cwd);
+    drop(current_task);
+
+    vfs::fs::unmount(dentry)?;
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum UmountError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The path passed as an argument is at an invalid address
+    BadAddress,
+
+    /// The path is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// The path is invalid
+    InvalidPath,
+
+    /// The path is too long
+    PathTooLong,
+
+    /// The path does not exist
+    NoSuchEntry,
+
+    /// A component of the path is not a directory
+    NotADirectory,
+
+    /// The path is not the root of a mounted filesystem
+    NotMounted,
+
+    /// A file of the filesystem is still in use
+    Busy,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<vfs::InvalidPath> for UmountError {
+    fn from(_: vfs::InvalidPath) -> Self {
+        UmountError::InvalidPath
+    }
+}
+
+impl From<vfs::LookupError> for UmountError {
+    fn from(error: vfs::LookupError) -> Self {
+        match error {
+            vfs::LookupError::NotADirectory => UmountError::NotADirectory,
+            vfs::LookupError::NotFound(_, _) => UmountError::NoSuchEntry,
+            vfs::LookupError::TooManySymlinks => UmountError::TooManySymlinks,
+            vfs::LookupError::IoError | vfs::LookupError::CorruptedFilesystem => {
+                UmountError::UnknownError
+            }
+        }
+    }
+}
+
+impl From<user::string::FetchError> for UmountError {
+    fn from(e: user::string::FetchError) -> Self {
+        match e {
+            user::string::FetchError::InvalidMemory => UmountError::BadAddress,
+            user::string::FetchError::StringTooLong => UmountError::PathTooLong,
+            user::string::FetchError::StringNotUtf8 => UmountError::InvalidUtf8,
+        }
+    }
+}
+
+impl From<vfs::fs::MountError> for UmountError {
+    fn from(error: vfs::fs::MountError) -> Self {
+        match error {
+            vfs::fs::MountError::NotMounted => UmountError::NotMounted,
+            vfs::fs::MountError::Busy => UmountError::Busy,
+            _ => UmountError::UnknownError,
+        }
+    }
+}
+
+impl From<UmountError> for isize {
+    fn from(error: UmountError) -> Self {
+        -(error as isize)
+    }
+}
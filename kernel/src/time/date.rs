@@ -1,11 +1,18 @@
-use super::{units::Second, unix::UnixTime};
-use crate::x86_64;
+use super::{
+    units::{Nanosecond, Second},
+    unix::UnixTime,
+    uptime,
+};
+use crate::x86_64::{self, cmos::UnsupportedDate};
+use core::sync::atomic::{AtomicU64, Ordering};
 
 /// The date at which the kernel was started.
 static STARTUP_DATE: Once<Date> = Once::new();
 
-/// The Unix time at which the kernel was started.
-static STARTUP_TIME: Once<UnixTime> = Once::new();
+/// The Unix time at which the kernel was started, in nanoseconds. It is moved
+/// when the time is set, so that the current time is always this time plus the
+/// uptime.
+static STARTUP_TIME: AtomicU64 = AtomicU64::new(0);
 
 /// Number of days elased since the beginning of the year, excluding the current month.
 const ELAPSED_DAYS_MONTHS: [usize; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
@@ -21,6 +28,22 @@ pub struct Date {
 }
 
 impl Date {
+    /// Returns the number of days of the given month, from 1 to 12, of the
+    /// given year. Like [`Date::to_unix_time`], every year divisible by 4 is
+    /// considered as a leap year.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn days_in_month(year: u16, month: u8) -> u8 {
+        let month = usize::from(month);
+        let next = ELAPSED_DAYS_MONTHS.get(month).copied().unwrap_or(365);
+        let days = next - ELAPSED_DAYS_MONTHS[month - 1];
+        if month == 2 && year % 4 == 0 {
+            days as u8 + 1
+        } else {
+            days as u8
+        }
+    }
+
     //// Converts the date to a Unix time. If the date is before January 1st, 1970,
     /// the Unix time returned will be 0.
     #[must_use]
@@ -47,6 +70,46 @@ impl Date {
 
         UnixTime(Second(seconds))
     }
+
+    /// Converts an Unix time to a date. Like [`Date::to_unix_time`], every year
+    /// divisible by 4 is considered as a leap year, which is only true between
+    /// 1901 and 2099.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_unix_time(time: UnixTime) -> Self {
+        let seconds = u64::from(time);
+        let mut days = seconds / (60 * 60 * 24);
+        let mut year = 1970;
+        loop {
+            let length = if year % 4 == 0 { 366 } else { 365 };
+            if days < length {
+                break;
+            }
+            days -= length;
+            year += 1;
+        }
+
+        // Find the last month starting before the current day, taking into
+        // account the 29th of February of leap years.
+        let leap = u64::from(year % 4 == 0);
+        let start = |month: usize| ELAPSED_DAYS_MONTHS[month] as u64 + leap * u64::from(month >= 2);
+        let month = (0..12).rev().find(|&month| start(month) <= days).unwrap_or(0);
+
+        Self {
+            year,
+            month: month as u8 + 1,
+            day: (days - start(month)) as u8 + 1,
+            hour: (seconds / (60 * 60) % 24) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+}
+
+impl From<UnixTime> for Date {
+    fn from(time: UnixTime) -> Self {
+        Date::from_unix_time(time)
+    }
 }
 
 /// Initializes the startup date. The kernel assume that the date is configured
@@ -58,24 +121,34 @@ impl Date {
 /// reasons, we are not the same (insert breaking bad meme here).
 #[init]
 pub fn setup() {
-    STARTUP_DATE.call_once(read_slow);
-    STARTUP_TIME.call_once(|| STARTUP_DATE.get().unwrap().to_unix_time());
+    let date = STARTUP_DATE.call_once(read_slow);
+    let time = Nanosecond::from(date.to_unix_time().0);
+    STARTUP_TIME.store(time.0.saturating_sub(uptime().0), Ordering::Relaxed);
 }
 
 /// Reads the date from the CMOS. This function is "slow" because it reads multiple
 /// slow I/O ports in order to get the date.
 #[must_use]
 pub fn read_slow() -> Date {
-    let years = 2000 + u16::from(x86_64::cmos::read(x86_64::cmos::Register::Year));
-
-    Date {
-        year: years,
-        month: x86_64::cmos::read(x86_64::cmos::Register::Month),
-        day: x86_64::cmos::read(x86_64::cmos::Register::Day),
-        hour: x86_64::cmos::read(x86_64::cmos::Register::Hours),
-        minute: x86_64::cmos::read(x86_64::cmos::Register::Minutes),
-        second: x86_64::cmos::read(x86_64::cmos::Register::Seconds),
-    }
+    x86_64::cmos::read_date()
+}
+
+/// Returns the current time since the Unix epoch, in nanoseconds.
+#[must_use]
+pub fn realtime() -> Nanosecond {
+    Nanosecond::new(STARTUP_TIME.load(Ordering::Relaxed) + uptime().0)
+}
+
+/// Sets the current time since the Unix epoch, in nanoseconds, and writes it
+/// to the CMOS so that it persists across reboots.
+///
+/// # Errors
+/// Returns [`UnsupportedDate`] without changing the time if the date cannot
+/// be stored in the CMOS.
+pub fn set_realtime(time: Nanosecond) -> Result<(), UnsupportedDate> {
+    x86_64::cmos::write_date(&Date::from_unix_time(UnixTime(Second::from(time))))?;
+    STARTUP_TIME.store(time.0.saturating_sub(uptime().0), Ordering::Relaxed);
+    Ok(())
 }
 
 /// Returns the date at which the kernel was started.
@@ -89,12 +162,11 @@ pub fn startup_date() -> Date {
     *STARTUP_DATE.get().expect("Startup date not initialized")
 }
 
-/// Returns the Unix time at which the kernel was started.
-///
-/// # Panics
-/// Panics if the startup time has not been initialized. This
-/// happens if the [`setup`] function has not been called before
-/// calling this function.
+/// Returns the Unix time at which the kernel was started, moved by the changes
+/// of the current time since then.
+#[must_use]
 pub fn startup_time() -> UnixTime {
-    *STARTUP_TIME.get().expect("Startup time not initialized")
+    UnixTime(Second::from(Nanosecond::new(
+        STARTUP_TIME.load(Ordering::Relaxed),
+    )))
 }
@@ -128,7 +128,7 @@ impl<const N: usize> UserBuffer<N> {
     /// - `None` if the user buffer is full.
     #[must_use]
     pub fn write_buffered(&mut self, buf: &[u8]) -> Option<()> {
-        if self.offset + buf.len() >= self.len {
+        if self.offset + buf.len() > self.len {
             return None;
         }
 
@@ -20,6 +20,10 @@ pub trait Scheduler {
     /// Return the current task running on the CPU.
     fn current_task(&self) -> Arc<Task>;
 
+    /// Return the current task running on the CPU, or `None` if no task is running
+    /// yet, for example during the kernel initialization.
+    fn try_current_task(&self) -> Option<Arc<Task>>;
+
     /// Set the current task running on the CPU, and set the task state to `Running`.
     fn set_current_task(&self, task: Arc<Task>);
 
@@ -58,6 +62,7 @@ pub trait Scheduler {
     /// undefined behavior.
     unsafe fn engage_cpu(&self) -> ! {
         let next = self.pick_next();
+        wait_off_cpu(&next);
         self.set_current_task(Arc::clone(&next));
 
         // Here, we manually decrement the strong count of the next task. This is needed
@@ -96,8 +101,10 @@ pub trait Scheduler {
         assert!(!x86_64::irq::enabled());
         assert!(task::preempt::enabled());
 
+        // The current task cannot be checked to not be running here: a blocked
+        // task may have been woken up and picked by another CPU since it was
+        // blocked, which is handled below.
         let current_task = self.current_task();
-        assert!(current_task.state() != task::State::Running);
         let next_task = self.pick_next();
 
         // If the next thread is the same as the current one, we do not need to switch threads
@@ -111,6 +118,7 @@ pub trait Scheduler {
             current_task.id().0,
             next_task.id().0
         );
+        wait_off_cpu(&next_task);
         self.set_current_task(Arc::clone(&next_task));
 
         // Here, we manually decrement the strong count of the next task. This is needed
@@ -135,13 +143,18 @@ pub trait Scheduler {
             // If the current task is rescheduled, we change its state to ready
             task::State::Rescheduled => current_task.change_state(task::State::Ready),
 
-            // If the current task is blocked, we do not need to do anything
-            task::State::Blocked => (),
+            // If the current task is blocked, we do not need to do anything. It may
+            // also have been woken up since it was blocked, and even picked by
+            // another CPU, which waits for its thread to be saved below before
+            // running it.
+            task::State::Blocked | task::State::Ready | task::State::Running => (),
 
-            // Other states are not supposed to be scheduled and it is a bug if we are
-            // here. We panic in this case, because this is a bug in the kernel that
+            // A task that was never run is not supposed to be scheduled and it is a bug
+            // if we are here. We panic in this case, because this is a bug in the kernel that
             // we cannot recover from and should be fixed.
-            _ => unreachable!("scheduler: invalid task state {:#?}", current_task.state()),
+            task::State::Created => {
+                unreachable!("scheduler: invalid task state {:#?}", current_task.state())
+            }
         }
 
         // The strong count of the current task is decremented here and not above with
@@ -180,6 +193,15 @@ pub trait Scheduler {
     }
 }
 
+/// Wait until the given task is not running on another CPU anymore. A blocked
+/// task can be woken up and picked by a CPU while another CPU is still switching
+/// it out, and its context must be saved before it runs again.
+fn wait_off_cpu(task: &Task) {
+    while task.on_cpu() {
+        core::hint::spin_loop();
+    }
+}
+
 /// Setup the scheduler
 #[init]
 pub fn setup() {}
@@ -229,5 +251,6 @@ unsafe extern "C" fn unlock_threads() {
     SCHEDULER.current_task().thread().force_unlock();
     if let Some(saved) = SAVED_TASK.local_mut().take() {
         saved.thread().force_unlock();
+        saved.set_on_cpu(false);
     }
 }
@@ -85,18 +85,20 @@ impl super::Scheduler for RoundRobin {
     /// This function panics if no task is running on the CPU. This should never happen and
     /// indicates a bug in the kernel.
     fn current_task(&self) -> Arc<Task> {
-        CURRENT_TASK
-            .local()
-            .borrow()
-            .as_ref()
-            .map(Arc::clone)
-            .expect("No task running on the CPU")
+        self.try_current_task().expect("No task running on the CPU")
+    }
+
+    /// Return the current task running on the CPU, or `None` if no task has been
+    /// started on this CPU yet.
+    fn try_current_task(&self) -> Option<Arc<Task>> {
+        CURRENT_TASK.local().borrow().as_ref().map(Arc::clone)
     }
 
     /// Sets the task passed as argument as the current task running on the CPU and changes its
     /// state to `Running`. The Arc counter of the previous task is decremented in this function
     /// and can be dropped if the counter reaches 0 (take care of this !)
     fn set_current_task(&self, task: Arc<Task>) {
+        task.set_on_cpu(true);
         CURRENT_TASK
             .local()
             .borrow_mut()
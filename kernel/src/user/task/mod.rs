@@ -3,19 +3,22 @@ use super::{
     scheduler::{Scheduler, SCHEDULER},
 };
 use crate::{
+    time::{timer::Timer, units::Nanosecond, uptime_fast},
     user::vmm,
     vfs::{self, fd::OpenedFiles},
+    x86_64,
 };
 use crate::{
     vfs::dentry::Dentry,
     x86_64::thread::{KernelThreadFn, Thread},
 };
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 pub mod elf;
 pub mod mutex;
 pub mod preempt;
 pub mod queue;
+pub mod signal;
 
 /// By default, all task stacks as the same base address. This is because we don't have a
 /// user memory manager yet, so we can't dynamically allocate stacks. This means that we
@@ -38,7 +41,7 @@ static TASK_LIST: Spinlock<Vec<Arc<Task>>> = Spinlock::new(Vec::new());
 ///
 /// There is no risk of overflow because the counter is 64 bits wide, which means that
 /// we can create 2^64 tasks before overflowing (and it won't happen anytime soon).
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Identifier(pub u64);
 impl Identifier {
     /// Create a new identifier with the given value.
@@ -163,6 +166,20 @@ pub struct Task {
     /// The current working directory of the task. This is used by the VFS subsystem to
     /// know the current working directory of the task.
     cwd: Spinlock<Arc<Dentry>>,
+
+    /// Set when the task has been interrupted while sleeping in an interruptible
+    /// wait, and cleared when the interrupted wait returns. This allows blocking
+    /// operations to be cancelled instead of waiting forever.
+    interrupted: AtomicBool,
+
+    /// The signals sent to the task and not delivered yet, as a bit set indexed
+    /// by the number of the signals.
+    signals: AtomicU64,
+
+    /// Set while the task is the current task of a CPU, until its context has
+    /// been saved by the switch to another task. A task woken up while it was
+    /// still being switched out must not run on another CPU before that.
+    on_cpu: AtomicBool,
 }
 
 impl Task {
@@ -182,6 +199,9 @@ impl Task {
             files: Spinlock::new(OpenedFiles::empty()),
             root: Spinlock::new(vfs::dentry::ROOT.get().unwrap().clone()),
             cwd: Spinlock::new(vfs::dentry::ROOT.get().unwrap().clone()),
+            interrupted: AtomicBool::new(false),
+            signals: AtomicU64::new(0),
+            on_cpu: AtomicBool::new(false),
         });
         TASK_LIST.lock().push(Arc::clone(&task));
         task
@@ -204,6 +224,9 @@ impl Task {
             files: Spinlock::new(OpenedFiles::empty()),
             root: Spinlock::new(vfs::dentry::ROOT.get().unwrap().clone()),
             cwd: Spinlock::new(vfs::dentry::ROOT.get().unwrap().clone()),
+            interrupted: AtomicBool::new(false),
+            signals: AtomicU64::new(0),
+            on_cpu: AtomicBool::new(false),
         });
         TASK_LIST.lock().push(Arc::clone(&task));
         task
@@ -233,6 +256,56 @@ impl Task {
         *self.state.lock() = state;
     }
 
+    /// Change the state of the task to `to` only if it is `from`, atomically,
+    /// and return `true` if the state was changed.
+    pub fn change_state_if(&self, from: State, to: State) -> bool {
+        let mut state = self.state.lock();
+        if *state == from {
+            *state = to;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Interrupt the task. If the task is sleeping in an interruptible wait, it is
+    /// woken up and the wait is cancelled. Otherwise, the next interruptible wait
+    /// of the task will be cancelled immediately.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+        let mut state = self.state.lock();
+        if *state == State::Blocked {
+            *state = State::Ready;
+        }
+    }
+
+    /// Returns `true` if the task is the current task of a CPU, or is still
+    /// being switched out of a CPU.
+    #[must_use]
+    pub fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    /// Set whether the task is the current task of a CPU. This should only be
+    /// used by the scheduler.
+    pub fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
+    }
+
+    /// Returns `true` if the task has been interrupted, without clearing the
+    /// interruption.
+    #[must_use]
+    pub fn interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if the task has been interrupted, and clear the interruption
+    /// so that it is reported only once.
+    #[must_use]
+    pub fn take_interrupt(&self) -> bool {
+        self.interrupted.swap(false, Ordering::SeqCst)
+    }
+
     /// Set the current working directory of the task.
     pub fn set_cwd(&self, cwd: Arc<Dentry>) {
         *self.cwd.lock() = cwd;
@@ -312,3 +385,25 @@ pub fn sleep() {
         SCHEDULER.schedule();
     }
 }
+
+/// Sleep the current task for at least the given duration. A timer is armed to wake
+/// up the task when the duration has elapsed, and the task is put to sleep until the
+/// timer expires. If the duration is zero, this function returns immediately.
+pub fn sleep_for<T: Into<Nanosecond>>(duration: T) {
+    let expiration = uptime_fast() + duration.into();
+
+    // Interrupts are disabled to prevent the timer from expiring before the
+    // task is effectively put to sleep, which would make it sleep forever.
+    x86_64::irq::without(|| {
+        let current = SCHEDULER.current_task();
+        let timer = Timer::new(expiration, move |_| {
+            if current.state() == State::Blocked {
+                current.change_state(State::Ready);
+            }
+        });
+
+        if timer.active() {
+            sleep();
+        }
+    });
+}
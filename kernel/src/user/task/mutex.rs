@@ -1,5 +1,8 @@
 use super::queue::WaitQueue;
-use core::mem::ManuallyDrop;
+use core::{
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
 
 /// A mutex that can be used to synchronize access to a resource between
 /// multiple threads. It is similar to the [`std::sync::Mutex`] but it is
@@ -30,12 +33,8 @@ impl<T> Mutex<T> {
     /// woken up externally, for example, by a signal.
     #[must_use]
     pub fn lock(&self) -> MutexGuard<'_, T> {
-        loop {
-            if let Some(guard) = self.try_lock() {
-                break guard;
-            }
-            self.queue.sleep();
-        }
+        self.try_lock()
+            .unwrap_or_else(|| self.queue.sleep_until(|| self.try_lock()))
     }
 
     /// Try to lock the mutex. If the mutex is already locked, this method
@@ -69,3 +68,17 @@ impl<T> Drop for MutexGuard<'_, T> {
         self.mutex.queue.wake_up_someone();
     }
 }
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
@@ -1,5 +1,8 @@
 use super::{sleep, State, Task};
-use crate::user::scheduler::{Scheduler, SCHEDULER};
+use crate::{
+    user::scheduler::{Scheduler, SCHEDULER},
+    x86_64,
+};
 use alloc::collections::VecDeque;
 
 pub struct WaitQueue {
@@ -31,6 +34,96 @@ impl WaitQueue {
         self.tasks.lock().retain(|task| task.id() != id);
     }
 
+    /// Same as [`WaitQueue::sleep`], but the sleep can be interrupted with
+    /// [`Task::interrupt`]. If the task was interrupted before or while sleeping,
+    /// the wait is cancelled and an error is returned.
+    ///
+    /// # Errors
+    /// Returns [`Interrupted`] if the task was interrupted.
+    pub fn sleep_interruptible(&self) -> Result<(), Interrupted> {
+        let current = SCHEDULER.current_task();
+        if current.take_interrupt() {
+            return Err(Interrupted);
+        }
+
+        self.sleep();
+        if current.take_interrupt() {
+            return Err(Interrupted);
+        }
+        Ok(())
+    }
+
+    /// Sleep until the given condition returns `Some`, and return its value.
+    ///
+    /// The current task is added to the wait queue and blocked before the
+    /// condition is checked, so a wake up happening between the check and the
+    /// sleep, possibly from another CPU, only makes the task ready again and is
+    /// never lost. Because the task is already blocked, the condition must not
+    /// sleep, and it is called with interrupts disabled.
+    pub fn sleep_until<T>(&self, mut condition: impl FnMut() -> Option<T>) -> T {
+        x86_64::irq::without(|| loop {
+            if let Some(value) = self.block_until(&mut condition, false) {
+                return value;
+            }
+        })
+    }
+
+    /// Same as [`WaitQueue::sleep_until`], but the sleep can be interrupted with
+    /// [`Task::interrupt`]. The condition is checked before the interruption, so
+    /// the value is returned if the condition is met when the task is interrupted.
+    ///
+    /// # Errors
+    /// Returns [`Interrupted`] if the task was interrupted before the condition
+    /// was met.
+    pub fn sleep_interruptible_until<T>(
+        &self,
+        mut condition: impl FnMut() -> Option<T>,
+    ) -> Result<T, Interrupted> {
+        let current = SCHEDULER.current_task();
+        x86_64::irq::without(|| loop {
+            if let Some(value) = self.block_until(&mut condition, true) {
+                return Ok(value);
+            }
+            if current.take_interrupt() {
+                return Err(Interrupted);
+            }
+        })
+    }
+
+    /// Add the current task to the wait queue and block it, then check the
+    /// condition and sleep if it is not met, unless the sleep is interruptible
+    /// and the task was interrupted. The task is removed from the wait queue
+    /// before returning. Interrupts must be disabled.
+    fn block_until<T>(
+        &self,
+        condition: &mut impl FnMut() -> Option<T>,
+        interruptible: bool,
+    ) -> Option<T> {
+        let current = SCHEDULER.current_task();
+        let id = current.id();
+
+        // The task is blocked before being added to the wait queue, so that a
+        // task found in the queue by a wake up is always woken up.
+        current.change_state(State::Blocked);
+        self.tasks.lock().push_back(Arc::clone(&current));
+
+        // If the task does not need to sleep but was woken up in the meantime,
+        // it may already have been picked by another CPU, and must be switched
+        // out to let it run there.
+        let value = condition();
+        let stay = value.is_some() || (interruptible && current.interrupted());
+        if !stay || !current.change_state_if(State::Blocked, State::Running) {
+            // SAFETY: Interrupts are disabled and the task is not running, which
+            // is the expected state to schedule another task.
+            unsafe {
+                SCHEDULER.schedule();
+            }
+        }
+
+        self.tasks.lock().retain(|task| task.id() != id);
+        value
+    }
+
     /// Wake up a blocked task in the wait queue. If there is no blocked task in the
     /// wait queue, this function does nothing.
     pub fn wake_up_someone(&self) -> Option<Arc<Task>> {
@@ -38,15 +131,24 @@ impl WaitQueue {
         // We need to check if the task is blocked because it may have been woken up by another
         // method that the `wake_up_someone` method, for example, when receiving a signal.
         while let Some(task) = self.tasks.lock().pop_front() {
-            if task.state() == State::Blocked {
-                task.change_state(State::Ready);
+            if task.change_state_if(State::Blocked, State::Ready) {
                 return Some(task);
             }
         }
         None
     }
+
+    /// Wake up all blocked tasks in the wait queue. This is useful when the
+    /// condition the tasks are waiting for may satisfy multiple tasks at once.
+    pub fn wake_up_all(&self) {
+        while self.wake_up_someone().is_some() {}
+    }
 }
 
+/// The error returned when an interruptible wait was interrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interrupted;
+
 impl Default for WaitQueue {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,124 @@
+//! Signals. Only the default actions of the signals are supported for now: a
+//! task cannot install a handler, nor block or ignore a signal. A signal sent to
+//! a task is recorded as pending and interrupts the interruptible wait of the
+//! task, if any, and the pending signals are delivered when the task returns
+//! from a syscall. A task that never makes a syscall, such as a task stuck in a
+//! loop in the userland, never receives its signals.
+//!
+//! Signals are numbered like on Linux. The signals stopping a task are ignored,
+//! since a task cannot be stopped yet.
+use super::Task;
+use crate::user::scheduler::{Scheduler, SCHEDULER};
+use core::sync::atomic::Ordering;
+
+/// A signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Signal {
+    /// The terminal controlling the task was closed.
+    Hangup = 1,
+
+    /// The interrupt character (usually `^C`) was typed on the terminal.
+    Interrupt = 2,
+
+    /// The quit character (usually `^\`) was typed on the terminal.
+    Quit = 3,
+
+    /// The task must be terminated immediately.
+    Kill = 9,
+
+    /// The task is politely asked to terminate.
+    Terminate = 15,
+
+    /// The suspend character (usually `^Z`) was typed on the terminal.
+    TerminalStop = 20,
+
+    /// A task not in the foreground tried to read from its terminal.
+    TerminalInput = 21,
+
+    /// A task not in the foreground tried to write to its terminal.
+    TerminalOutput = 22,
+
+    /// The size of the window of the terminal changed.
+    WindowChange = 28,
+}
+
+/// The action taken when a signal is delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// The task is terminated.
+    Terminate,
+
+    /// Nothing happens.
+    Ignore,
+}
+
+impl Signal {
+    /// All the signals, in increasing order of their number.
+    const ALL: [Signal; 9] = [
+        Signal::Hangup,
+        Signal::Interrupt,
+        Signal::Quit,
+        Signal::Kill,
+        Signal::Terminate,
+        Signal::TerminalStop,
+        Signal::TerminalInput,
+        Signal::TerminalOutput,
+        Signal::WindowChange,
+    ];
+
+    /// Returns the number of the signal.
+    #[must_use]
+    pub const fn number(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the action taken when the signal is delivered to a task.
+    #[must_use]
+    pub const fn action(self) -> Action {
+        match self {
+            Signal::Hangup
+            | Signal::Interrupt
+            | Signal::Quit
+            | Signal::Kill
+            | Signal::Terminate => Action::Terminate,
+            Signal::TerminalStop
+            | Signal::TerminalInput
+            | Signal::TerminalOutput
+            | Signal::WindowChange => Action::Ignore,
+        }
+    }
+
+    /// Returns the bit representing this signal in a set of pending signals.
+    #[must_use]
+    const fn mask(self) -> u64 {
+        1 << self.number()
+    }
+}
+
+impl Task {
+    /// Send the given signal to the task. Signals ignored by the task are
+    /// discarded immediately, and the other ones interrupt the task so that a
+    /// task sleeping in an interruptible wait returns from its syscall and
+    /// receives the signal.
+    pub fn send_signal(&self, signal: Signal) {
+        if signal.action() == Action::Ignore {
+            return;
+        }
+        self.signals.fetch_or(signal.mask(), Ordering::SeqCst);
+        self.interrupt();
+    }
+}
+
+/// Take the pending signals of the current task, and returns the first one
+/// whose action is to terminate the task, if any. The caller must then terminate
+/// the task.
+#[must_use]
+pub fn take_fatal() -> Option<Signal> {
+    let pending = SCHEDULER.current_task().signals.swap(0, Ordering::SeqCst);
+
+    Signal::ALL
+        .into_iter()
+        .filter(|signal| pending & signal.mask() != 0)
+        .find(|signal| signal.action() == Action::Terminate)
+}
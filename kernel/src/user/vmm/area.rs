@@ -2,7 +2,7 @@ use crate::{
     vfs::file::File,
     x86_64::paging::table::{PageEntryFlags, PageFaultErrorCode},
 };
-use addr::user::UserVirtual;
+use addr::{phys::Physical, user::UserVirtual};
 use bitflags::bitflags;
 use core::ops::Range;
 use typed_builder::TypedBuilder;
@@ -36,6 +36,14 @@ impl Area {
         self.range = range;
     }
 
+    /// Move the start of this area to the given address, which must be inside
+    /// the area, and advance the offset of the area by the same amount so that
+    /// the remaining pages are still backed by the same part of the ressource.
+    pub fn trim_start(&mut self, start: UserVirtual) {
+        self.offset += usize::from(start) - usize::from(self.range.start);
+        self.range.start = start;
+    }
+
     /// Return the range of virtal memory used by this area.
     #[must_use]
     pub fn range(&self) -> &Range<UserVirtual> {
@@ -96,6 +104,12 @@ pub enum Type {
     /// back to the file when the area is unmapped if some flags are set
     /// during the mapping.
     File(Arc<File>),
+
+    /// A device area maps the memory of a device, like the framebuffer, that
+    /// starts at the given physical address. The offset of the area is added
+    /// to this address. The frames of the device are not owned by the area, and
+    /// are never deallocated when the area is unmapped.
+    Device(Physical),
 }
 
 bitflags! {
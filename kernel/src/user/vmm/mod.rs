@@ -10,7 +10,7 @@ use crate::{
         MapError, PAGE_SIZE,
     },
 };
-use addr::{user::UserVirtual, virt::Virtual};
+use addr::{frame::Frame, user::UserVirtual, virt::Virtual};
 use alloc::collections::BTreeMap;
 use core::ops::Range;
 
@@ -179,6 +179,7 @@ impl Manager {
         for mut area in areas {
             let area_start = area.range().start;
             let area_end = area.range().end;
+            let device = matches!(area.kind(), Type::Device(_));
 
             let unmap_range = if range_contains(&range, area.range()) {
                 // The area contains the range, so we need to split the area
@@ -188,7 +189,7 @@ impl Manager {
                 let mut split = area.clone();
 
                 area.set_range(area_start..range_start);
-                split.set_range(range_end_aligned..area_end);
+                split.trim_start(range_end_aligned);
 
                 self.insert_area(split);
                 self.insert_area(area);
@@ -204,7 +205,7 @@ impl Manager {
                 // then return the range that we need to unmap.
                 let range = if range.end > area.range().start {
                     // Unmap the start of the area
-                    area.set_range(range_end_aligned..area_end);
+                    area.trim_start(range_end_aligned);
                     area_start..range_end_aligned
                 } else if range.start < area.range().end {
                     // Unmap the end of the area
@@ -220,9 +221,9 @@ impl Manager {
                 unreachable!("Unmap: algorithm implementation error");
             };
 
-            // TODO: The framebuffer will probably need a special treatment
-            // here because it frames must not be deallocated.
-            self.unmap_range(unmap_range);
+            // The frames of a device are not owned by the area and must not
+            // be given back to the frame allocator.
+            self.unmap_range(unmap_range, !device);
         }
 
         Ok(())
@@ -269,6 +270,20 @@ impl Manager {
 
                 paging::map(&self.table, virt, frame, flags)?;
             },
+            Type::Device(base) => unsafe {
+                let offset = area.offset() + (usize::from(address) - usize::from(area.base()));
+                let frame = Frame::new(*base + offset);
+
+                // Writes to device memory go through the cache to reach the
+                // device without delay.
+                let flags = PageEntryFlags::from(area.access())
+                    | PageEntryFlags::USER
+                    | PageEntryFlags::DEVICE
+                    | PageEntryFlags::WRITE_THROUGH;
+                let virt = Virtual::from(address);
+
+                paging::map(&self.table, virt, frame, flags)?;
+            },
             Type::File(_file) => {
                 // Allocate a frame
                 // Compute the offset of the page in the file
@@ -281,12 +296,14 @@ impl Manager {
         Ok(())
     }
 
-    /// Unmap the range of user virtual addresses and deallocate the frames that
-    /// were mapped at these addresses.
-    fn unmap_range(&mut self, range: Range<UserVirtual>) {
+    /// Unmap the range of user virtual addresses, and deallocate the frames that
+    /// were mapped at these addresses if `deallocate` is true.
+    fn unmap_range(&mut self, range: Range<UserVirtual>, deallocate: bool) {
         range.step_by(PAGE_SIZE).for_each(|address| unsafe {
             if let Ok(frame) = paging::unmap(&self.table, Virtual::from(address)) {
-                FRAME_ALLOCATOR.lock().deallocate_frame(frame);
+                if deallocate {
+                    FRAME_ALLOCATOR.lock().deallocate_frame(frame);
+                }
             }
         });
     }
@@ -1,14 +1,23 @@
 use super::{
     file::{self, File, FileCreateInfo, OpenFlags},
     inode::{self, Inode},
+    inotify,
+    lru::Lru,
     mount,
     name::Name,
 };
+use crate::device;
 use alloc::sync::Weak;
+use hashbrown::HashMap;
 
 /// The root dentry of the filesystem tree.
 pub static ROOT: Once<Arc<Dentry>> = Once::new();
 
+/// The LRU list of all cached dentries, except the root dentry. Dentries that are
+/// still in use are also in this list, but are skipped by the shrinker until they
+/// are not used anymore.
+static LRU: Spinlock<Lru<LruEntry>> = Spinlock::new(Lru::new());
+
 /// A dentry is a directory entry. It is a node in the filesystem tree, and
 /// contains the name of the file, the inode associated with the file, and
 /// pointers to its parent and children.
@@ -19,10 +28,17 @@ pub static ROOT: Once<Arc<Dentry>> = Once::new();
 #[derive(Debug)]
 pub struct Dentry {
     /// The inode associated with this dentry. An same inode may be associated
-    /// with multiple dentries (hard links).
-    inode: Arc<Inode>,
+    /// with multiple dentries (hard links). If this is `None`, the dentry is a
+    /// negative dentry: it caches the fact that no entry with this name exists
+    /// in the parent directory, avoiding to ask the filesystem again.
+    inode: Option<Arc<Inode>>,
 
     tree: Spinlock<DentryTree>,
+
+    /// The root dentry of the filesystem mounted on this dentry, if any. When a
+    /// filesystem is mounted on a dentry, the lookups that reach this dentry
+    /// continue in the root of the mounted filesystem instead.
+    mounted: Spinlock<Option<Arc<Dentry>>>,
 }
 
 impl Dentry {
@@ -31,12 +47,29 @@ impl Dentry {
     #[must_use]
     pub fn new(name: Name, inode: Arc<Inode>) -> Self {
         Self {
-            inode,
+            inode: Some(inode),
             tree: Spinlock::new(DentryTree {
                 name,
                 parent: Weak::default(),
-                children: Vec::new(),
+                children: HashMap::new(),
             }),
+            mounted: Spinlock::new(None),
+        }
+    }
+
+    /// Create a new negative dentry with the given name. Negative dentries are
+    /// never returned outside of this module: they only exist in the dentry
+    /// cache to remember that an entry does not exist.
+    #[must_use]
+    fn negative(name: Name) -> Self {
+        Self {
+            inode: None,
+            tree: Spinlock::new(DentryTree {
+                name,
+                parent: Weak::default(),
+                children: HashMap::new(),
+            }),
+            mounted: Spinlock::new(None),
         }
     }
 
@@ -52,17 +85,29 @@ impl Dentry {
     /// Open the inode associated with this dentry.
     ///
     /// Please note that this function does not perform any checks: this is the caller
-    /// responsibility to ensure that.
+    /// responsibility to ensure that. When a character device is opened, its driver
+    /// is told and provides the data attached to the opened file.
     ///
     /// # Errors
-    /// Currently, this function does not return any error. However, this may change
-    /// in the future.
+    /// Returns `OpenError::NoSuchDevice` if the inode is a character device that
+    /// is not registered or that refused to be opened, or `OpenError::IoError`
+    /// if the device cannot be opened now.
     pub fn open(self: &Arc<Self>, flags: OpenFlags) -> Result<File, OpenError> {
+        let data = match self.inode().kind {
+            inode::Kind::CharDevice(id) => {
+                device::character::open(id, flags).map_err(|error| match error {
+                    device::character::OpenError::NoSuchDevice => OpenError::NoSuchDevice,
+                    device::character::OpenError::IoError => OpenError::IoError,
+                })?
+            }
+            _ => Box::new(()),
+        };
+
         Ok(file::File::new(FileCreateInfo {
-            operation: self.inode.file_ops.clone(),
+            operation: self.inode().file_ops.clone(),
             dentry: Some(self.clone()),
             open_flags: flags,
-            data: Box::new(()),
+            data,
         }))
     }
 
@@ -93,7 +138,7 @@ impl Dentry {
     /// Panics if the inode does not have a superblock. This should never happen
     /// and is a serious kernel bug.
     pub fn dirtying_inode(&self) {
-        self.inode.mark_dirty();
+        self.inode().mark_dirty();
     }
 
     /// Get the tree info of this dentry.
@@ -109,9 +154,20 @@ impl Dentry {
     }
 
     /// Get the inode associated with this dentry.
+    ///
+    /// # Panics
+    /// Panics if this dentry is a negative dentry. This should never happen since
+    /// negative dentries never leave the dentry cache, and is a serious kernel bug.
     #[must_use]
     pub fn inode(&self) -> &Arc<Inode> {
-        &self.inode
+        self.inode.as_ref().expect("Negative dentry used outside the cache")
+    }
+
+    /// Check if this dentry is a negative dentry, i.e. a dentry that caches the
+    /// fact that an entry does not exist.
+    #[must_use]
+    pub fn is_negative(&self) -> bool {
+        self.inode.is_none()
     }
 
     /// Get the name of this dentry.
@@ -140,27 +196,42 @@ impl Dentry {
     pub fn fetch(dentry: &Arc<Self>, name: &Name) -> Result<Arc<Self>, FetchError> {
         loop {
             match dentry.lookup(name) {
-                Ok(dentry) => return Ok(dentry),
+                Ok(dentry) => return Ok(dentry.follow_mounts()),
                 Err(e) => match e {
                     LookupError::NotADirectory => return Err(FetchError::NotADirectory),
+                    LookupError::Negative => return Err(FetchError::NotFound),
                     LookupError::NotFound => {}
                 },
             }
 
             let superblock = dentry
-                .inode
+                .inode()
                 .superblock
                 .upgrade()
                 .expect("Inode without superblock");
 
-            let id = dentry
-                .inode
+            let id = match dentry
+                .inode()
                 .as_directory()
                 .ok_or(FetchError::NotADirectory)?
-                .lookup(&dentry.inode, name.as_str())
-                .map_err(|e| match e {
-                    inode::LookupError::NoSuchEntry => FetchError::NotFound,
-                })?;
+                .lookup(dentry.inode(), name.as_str())
+            {
+                Ok(id) => id,
+                Err(inode::LookupError::IoError) => return Err(FetchError::IoError),
+                Err(inode::LookupError::NoSuchEntry) => {
+                    // Remember that the entry does not exist to avoid asking the
+                    // filesystem again the next time. If an entry with the same
+                    // name was created in the meantime, we try to fetch it again.
+                    let negative = Arc::new(Self::negative(name.clone()));
+                    match Self::connect_child(dentry, negative) {
+                        Err(ConnectError::AlreadyConnected | ConnectError::NotADirectory) => {
+                            unreachable!()
+                        }
+                        Err(ConnectError::AlreadyExists) => continue,
+                        Ok(_) => return Err(FetchError::NotFound),
+                    }
+                }
+            };
 
             let inode = superblock.get_inode(id)?;
             let name = name.clone();
@@ -172,11 +243,132 @@ impl Dentry {
                     unreachable!()
                 }
                 Err(ConnectError::AlreadyExists) => continue,
-                Ok(dentry) => return Ok(dentry),
+                Ok(dentry) => return Ok(dentry.follow_mounts()),
+            }
+        }
+    }
+
+    /// Returns the root dentry of the filesystem mounted on this dentry, or this
+    /// dentry itself if nothing is mounted on it. If several filesystems are
+    /// stacked on the same dentry, the root of the last mounted one is returned.
+    #[must_use]
+    pub fn follow_mounts(self: Arc<Self>) -> Arc<Self> {
+        let mut dentry = self;
+        loop {
+            let mounted = dentry.mounted.lock().clone();
+            match mounted {
+                Some(root) => dentry = root,
+                None => return dentry,
             }
         }
     }
 
+    /// Check if this dentry is the root of a mounted filesystem, i.e. if it does
+    /// not belong to the same superblock as its parent.
+    #[must_use]
+    pub fn is_mount_root(&self) -> bool {
+        self.parent().is_some_and(|parent| {
+            !Weak::ptr_eq(&parent.inode().superblock, &self.inode().superblock)
+        })
+    }
+
+    /// Mount the filesystem whose root dentry is `root` on the `mountpoint` dentry.
+    /// The root dentry takes the name and the parent of the mountpoint, so that the
+    /// `..` entry of the root of the mounted filesystem leads to the parent of the
+    /// mountpoint, and the paths built by walking up the tree stay correct. The
+    /// mountpoint is pinned in the dentry cache until the filesystem is unmounted.
+    ///
+    /// # Errors
+    /// - `MountError::NotADirectory`: The mountpoint is not a directory.
+    /// - `MountError::Busy`: The mountpoint is the root of the dentry tree, or a
+    /// filesystem is already mounted on it.
+    pub fn mount(mountpoint: &Arc<Self>, root: &Arc<Self>) -> Result<(), MountError> {
+        if mountpoint.inode().kind != inode::Kind::Directory {
+            return Err(MountError::NotADirectory);
+        }
+
+        let parent = mountpoint.parent().ok_or(MountError::Busy)?;
+        if Arc::ptr_eq(&parent, mountpoint) {
+            return Err(MountError::Busy);
+        }
+
+        let mut mounted = mountpoint.mounted.lock();
+        if mounted.is_some() {
+            return Err(MountError::Busy);
+        }
+
+        let mut tree = root.tree.lock();
+        tree.name = mountpoint.name();
+        tree.parent = Arc::downgrade(&parent);
+        drop(tree);
+
+        *mounted = Some(Arc::clone(root));
+        Ok(())
+    }
+
+    /// Unmount the filesystem mounted on the `mountpoint` dentry and return its
+    /// root dentry. The filesystem cannot be unmounted if one of its dentries is
+    /// still in use, for example by an open file or as the current directory of
+    /// a task, or if another filesystem is mounted on one of its directories.
+    /// A single reference to the root dentry, usually obtained by the caller to
+    /// find the mountpoint, is not counted as a use of the filesystem.
+    ///
+    /// # Errors
+    /// - `MountError::NotMounted`: Nothing is mounted on the mountpoint.
+    /// - `MountError::Busy`: The mounted filesystem is still in use.
+    pub fn unmount(mountpoint: &Arc<Self>) -> Result<Arc<Self>, MountError> {
+        let mut mounted = mountpoint.mounted.lock();
+        let root = mounted.as_ref().ok_or(MountError::NotMounted)?;
+
+        // The mountpoint and the caller hold the only allowed references to the
+        // root dentry, and the other dentries must only be referenced by their
+        // parent.
+        if Arc::strong_count(root) > 2 || root.is_busy() {
+            return Err(MountError::Busy);
+        }
+
+        let root = Arc::clone(root);
+        *mounted = None;
+        root.tree.lock().parent = Weak::default();
+        Ok(root)
+    }
+
+    /// Returns the dentry on which the filesystem whose root is this dentry is
+    /// mounted, or `None` if this dentry is not the root of a mounted filesystem.
+    #[must_use]
+    pub fn mountpoint(self: &Arc<Self>) -> Option<Arc<Self>> {
+        if !self.is_mount_root() {
+            return None;
+        }
+
+        let parent = self.parent()?;
+        let tree = parent.tree.lock();
+        tree.children
+            .get(&self.name())
+            .filter(|child| {
+                child
+                    .mounted
+                    .lock()
+                    .as_ref()
+                    .is_some_and(|root| Arc::ptr_eq(root, self))
+            })
+            .cloned()
+    }
+
+    /// Check if one of the children of this dentry, or one of their descendants,
+    /// is still in use or is a mountpoint. A child is in use if it is referenced by
+    /// something else than the children map of its parent.
+    fn is_busy(&self) -> bool {
+        self.tree
+            .lock()
+            .children
+            .values()
+            .filter(|child| !child.is_negative())
+            .any(|child| {
+                Arc::strong_count(child) > 1 || child.mounted.lock().is_some() || child.is_busy()
+            })
+    }
+
     /// Create a new file in this dentry with the given name, load it into the
     /// dentry cache and return it.
     ///
@@ -203,16 +395,16 @@ impl Dentry {
         }
 
         let superblock = dentry
-            .inode
+            .inode()
             .superblock
             .upgrade()
             .expect("Inode without superblock");
 
         let id = dentry
-            .inode
+            .inode()
             .as_directory()
             .ok_or(CreateFetchError::NotADirectory)?
-            .create(&dentry.inode, name.as_str())?;
+            .create(dentry.inode(), name.as_str())?;
 
         let inode = superblock.get_inode(id)?;
         let child = Arc::new(Dentry::new(name, inode));
@@ -222,11 +414,16 @@ impl Dentry {
         match Self::connect_child(dentry, Arc::clone(&child)) {
             Err(ConnectError::AlreadyConnected | ConnectError::NotADirectory) => unreachable!(),
             Err(ConnectError::AlreadyExists) => Err(CreateFetchError::AlreadyExists),
-            Ok(_) => Ok(child),
+            Ok(_) => {
+                inotify::notify_child(dentry.inode(), inotify::Mask::CREATE, 0, &child.name());
+                Ok(child)
+            }
         }
     }
 
-    /// Find a child of this dentry by name.
+    /// Find a child of this dentry by name. The children are hashed by name, so
+    /// this is a constant time operation. If the child is found, it is marked as
+    /// the most recently used dentry in the LRU list.
     ///
     /// The dentry cache only contains parts of the filesystem tree. If the child is not found
     /// in the cache, it MUST be looked up in the filesystem tree to ensure that the entry really
@@ -237,60 +434,90 @@ impl Dentry {
     /// # Errors
     ///  - `LookupError::NotADirectory`: The inode associated with this dentry
     ///   is not a directory, and therefore cannot have children.
+    ///  - `LookupError::Negative`: The cache knows that the child does not exist.
     ///  - `LookupError::NotFound`: The child could not be found.
-    ///
-    /// # Panics
-    /// Panics if this entry does not have a parent. This should never happen
-    /// because every dentry must have a parent, even the root dentry, and is
-    /// a serious kernel bug.
     pub fn lookup(&self, name: &Name) -> Result<Arc<Dentry>, LookupError> {
-        if self.inode.kind != inode::Kind::Directory {
+        if self.inode().kind != inode::Kind::Directory {
             return Err(LookupError::NotADirectory);
         }
 
-        self.tree
+        let child = self
+            .tree
             .lock()
             .children
-            .iter()
-            .find(|child| child.tree.lock().name == *name)
+            .get(name)
             .cloned()
-            .ok_or(LookupError::NotFound)
+            .ok_or(LookupError::NotFound)?;
+
+        LRU.lock().touch(LruEntry(Arc::downgrade(&child)));
+        if child.is_negative() {
+            return Err(LookupError::Negative);
+        }
+        Ok(child)
     }
 
-    /// Connect a child to this dentry.
+    /// Connect a child to this dentry. If a negative dentry with the same name
+    /// is connected to this dentry, it is replaced by the given child.
     ///
     /// # Errors
     /// - `ConnectError::NotADirectory`: The inode associated with this dentry
     ///  is not a directory, and therefore cannot have children.
     /// - `ConnectError::AlreadyExists`: The parent already has a child with the
     /// same name.
+    /// - `ConnectError::AlreadyConnected`: The child is already connected to a
+    /// parent.
     pub fn connect_child(dentry: &Arc<Dentry>, child: Arc<Dentry>) -> Result<(), ConnectError> {
-        if dentry.inode.kind != inode::Kind::Directory {
+        if dentry.inode().kind != inode::Kind::Directory {
             return Err(ConnectError::NotADirectory);
         }
 
         let mut dentry_tree = dentry.tree.lock();
-        {
+        let name = {
             let mut child_tree = child.tree.lock();
             if child_tree.parent.upgrade().is_some() {
                 return Err(ConnectError::AlreadyConnected);
             }
 
-            if child_tree
+            if dentry_tree
                 .children
-                .iter()
-                .any(|entry| entry.tree.lock().name == dentry_tree.name)
+                .get(&child_tree.name)
+                .is_some_and(|entry| !entry.is_negative())
             {
                 return Err(ConnectError::AlreadyExists);
             }
 
             child_tree.parent = Arc::downgrade(dentry);
-        }
+            child_tree.name.clone()
+        };
 
-        dentry_tree.children.push(child);
+        let lru_entry = LruEntry(Arc::downgrade(&child));
+        let replaced = dentry_tree.children.insert(name, child);
+        drop(dentry_tree);
+
+        let mut lru = LRU.lock();
+        if let Some(negative) = replaced {
+            lru.remove(&LruEntry(Arc::downgrade(&negative)));
+        }
+        lru.touch(lru_entry);
         Ok(())
     }
 
+    /// Forget the negative dentry with the given name, if any. This must be called
+    /// when an entry is created in the directory without going through the dentry
+    /// cache, otherwise a stale negative dentry could hide the new entry.
+    pub fn forget_negative(&self, name: &Name) {
+        let mut tree = self.tree.lock();
+        let negative = match tree.children.get(name) {
+            Some(child) if child.is_negative() => tree.children.remove(name),
+            _ => None,
+        };
+        drop(tree);
+
+        if let Some(negative) = negative {
+            LRU.lock().remove(&LruEntry(Arc::downgrade(&negative)));
+        }
+    }
+
     /// Create a new dentry with the given name and inode, connect it to this dentry
     /// and return the created dentry. This is simply a shortcut for creating a new
     /// dentry and calling [`Self::connect_child`] on it.
@@ -331,24 +558,203 @@ impl Dentry {
     /// All these cases should never happen and are serious kernel bugs.
     pub fn disconnect_child(&self, name: &Name) -> Result<Arc<Self>, DisconnectError> {
         let mut tree = self.tree.lock();
-        let index = tree
+        let child = tree
             .children
-            .iter()
-            .position(|child| child.tree.lock().name == *name)
+            .get(name)
+            .filter(|child| !child.is_negative())
             .ok_or(DisconnectError::NotFound)?;
 
         {
-            let mut child_tree = tree.children[index].tree.lock();
-            if !child_tree.children.is_empty() {
+            // Negative dentries do not prevent a directory from being removed
+            // since they do not represent real entries. They are simply dropped
+            // with the directory dentry.
+            let mut child_tree = child.tree.lock();
+            if child_tree.children.values().any(|entry| !entry.is_negative()) {
                 return Err(DisconnectError::Busy);
             }
             child_tree.parent = Weak::default();
         }
 
-        Ok(tree.children.swap_remove(index))
+        let child = tree.children.remove(name).unwrap();
+        drop(tree);
+
+        let mask = match child.inode().kind {
+            inode::Kind::Directory => inotify::Mask::DELETE | inotify::Mask::ISDIR,
+            _ => inotify::Mask::DELETE,
+        };
+        inotify::notify_child(self.inode(), mask, 0, name);
+
+        let negatives = child
+            .tree
+            .lock()
+            .children
+            .values()
+            .map(|negative| LruEntry(Arc::downgrade(negative)))
+            .collect::<Vec<_>>();
+
+        let mut lru = LRU.lock();
+        for negative in &negatives {
+            lru.remove(negative);
+        }
+        lru.remove(&LruEntry(Arc::downgrade(&child)));
+        Ok(child)
+    }
+
+    /// Rename the child `old` of this dentry to `new`. The child is renamed in the
+    /// filesystem, then in the dentry cache, and the watchers of the directory are
+    /// notified with a pair of `MOVED_FROM` and `MOVED_TO` events.
+    ///
+    /// # Errors
+    /// - `RenameError::NotADirectory`: This dentry is not a directory.
+    /// - `RenameError::NotFound`: There is no child named `old`.
+    /// - `RenameError::AlreadyExists`: A child named `new` already exists.
+    /// - `RenameError::IoError`: An I/O error occurred while fetching the child.
+    pub fn rename(dentry: &Arc<Self>, old: &Name, new: &Name) -> Result<(), RenameError> {
+        let child = Self::fetch(dentry, old).map_err(|e| match e {
+            FetchError::NotFound => RenameError::NotFound,
+            FetchError::NotADirectory => RenameError::NotADirectory,
+            FetchError::IoError => RenameError::IoError,
+        })?;
+
+        dentry
+            .inode()
+            .as_directory()
+            .ok_or(RenameError::NotADirectory)?
+            .rename(dentry.inode(), old.as_str(), new.as_str())
+            .map_err(|e| match e {
+                inode::RenameError::NoSuchEntry => RenameError::NotFound,
+                inode::RenameError::AlreadyExists => RenameError::AlreadyExists,
+                inode::RenameError::InvalidName => RenameError::InvalidName,
+                inode::RenameError::NoSpace => RenameError::NoSpace,
+                inode::RenameError::IoError => RenameError::IoError,
+            })?;
+
+        // Move the child under its new name in the dentry cache. A negative
+        // dentry may exist for the new name and is replaced by the child.
+        let mut tree = dentry.tree.lock();
+        let replaced = tree.children.remove(old).and_then(|child| {
+            child.tree.lock().name = new.clone();
+            tree.children.insert(new.clone(), child)
+        });
+        drop(tree);
+
+        if let Some(negative) = replaced {
+            LRU.lock().remove(&LruEntry(Arc::downgrade(&negative)));
+        }
+
+        let isdir = match child.inode().kind {
+            inode::Kind::Directory => inotify::Mask::ISDIR,
+            _ => inotify::Mask::empty(),
+        };
+        let cookie = inotify::next_cookie();
+        let directory = dentry.inode();
+        inotify::notify_child(directory, inotify::Mask::MOVED_FROM | isdir, cookie, old);
+        inotify::notify_child(directory, inotify::Mask::MOVED_TO | isdir, cookie, new);
+        Ok(())
+    }
+
+    /// Try to evict this dentry from the dentry cache. A dentry can only be evicted
+    /// if it is not used by anyone else than its parent (and the caller), and if it
+    /// does not have any children. Returns `true` if the dentry was evicted.
+    fn try_evict(self: &Arc<Self>) -> bool {
+        let Some(parent) = self.parent() else {
+            // The dentry was already disconnected from the tree, so it can
+            // simply be forgotten by the cache.
+            return true;
+        };
+
+        if Arc::ptr_eq(self, &parent) {
+            return false;
+        }
+
+        let mut parent_tree = parent.tree.lock();
+        let mut tree = self.tree.lock();
+
+        // The parent children map and the caller are the only allowed users of
+        // the dentry. Since new references can only be created from the parent
+        // children map or from an existing reference, the dentry cannot become
+        // used while the parent tree lock is held.
+        if Arc::strong_count(self) > 2
+            || !tree.children.is_empty()
+            || self.mounted.lock().is_some()
+        {
+            return false;
+        }
+
+        tree.parent = Weak::default();
+        parent_tree.children.remove(&tree.name);
+        true
+    }
+}
+
+/// Evict at most `count` unused dentries from the dentry cache, starting from the
+/// least recently used ones, and return the number of evicted dentries. Dentries
+/// still in use (opened files, current working directories...) or with children
+/// are skipped and moved to the end of the list. Evicting a dentry releases its
+/// reference to its inode, which can then be evicted from the inode cache of its
+/// superblock.
+pub fn shrink(count: usize) -> usize {
+    // Collect the candidates without holding the LRU lock while evicting them,
+    // because evicting a dentry requires to lock the dentry tree.
+    let candidates = LRU
+        .lock()
+        .oldest()
+        .take(count.saturating_mul(2))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut evicted = 0;
+    for entry in candidates {
+        if evicted >= count {
+            break;
+        }
+
+        match entry.0.upgrade() {
+            // Skipped dentries are moved to the most recently used end of the
+            // list, so that the dentries pinned at its start do not hide the
+            // unused ones from the next scans.
+            Some(dentry) if !dentry.try_evict() => {
+                let mut lru = LRU.lock();
+                if lru.remove(&entry) {
+                    lru.touch(entry);
+                }
+                continue;
+            }
+            Some(_) => evicted += 1,
+            None => {}
+        }
+        LRU.lock().remove(&entry);
+    }
+    evicted
+}
+
+/// An entry of the dentry LRU list. It holds a weak reference to a dentry and is
+/// compared by address, since a dentry is unique in the cache. The weak reference
+/// keeps the allocation alive, so the address cannot be reused by another dentry
+/// while the entry is in the list.
+#[derive(Debug, Clone)]
+struct LruEntry(Weak<Dentry>);
+
+impl Ord for LruEntry {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.0.as_ptr().cmp(&other.0.as_ptr())
+    }
+}
+
+impl PartialOrd for LruEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+impl PartialEq for LruEntry {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for LruEntry {}
+
 /// Represents a dentry in the filesystem tree. This structure is used to
 /// contains fields that can be modified by the filesystem driver, like the
 /// children list or the dentr name. The [`Dentry`] structure contains fields
@@ -364,13 +770,21 @@ pub struct DentryTree {
     /// root dentry, which has itself as its parent.
     parent: Weak<Dentry>,
 
-    /// The children of this dentry. This is a list of all dentries that have
-    /// this dentry as their parent.
-    children: Vec<Arc<Dentry>>,
+    /// The children of this dentry, hashed by name. This contains all the
+    /// cached dentries that have this dentry as their parent, including the
+    /// negative ones.
+    children: HashMap<Name, Arc<Dentry>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum OpenError {}
+pub enum OpenError {
+    /// The inode is a character device that is not registered, or that cannot
+    /// be opened.
+    NoSuchDevice,
+
+    /// The inode is a character device that cannot be opened now.
+    IoError,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LookupError {
@@ -378,10 +792,34 @@ pub enum LookupError {
     /// cannot have children.
     NotADirectory,
 
-    /// The child could not be found.
+    /// The child is known to not exist, thanks to a negative dentry.
+    Negative,
+
+    /// The child could not be found in the cache.
     NotFound,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenameError {
+    /// The dentry is not a directory.
+    NotADirectory,
+
+    /// The entry to rename does not exist.
+    NotFound,
+
+    /// An entry with the new name already exists.
+    AlreadyExists,
+
+    /// The new name cannot be stored by the filesystem.
+    InvalidName,
+
+    /// There is no space left on the device.
+    NoSpace,
+
+    /// An I/O error occurred.
+    IoError,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FetchError {
     /// The inode associated with this dentry is not a directory, and therefore
@@ -399,6 +837,7 @@ impl From<mount::ReadInodeError> for FetchError {
     fn from(error: mount::ReadInodeError) -> Self {
         match error {
             mount::ReadInodeError::DoesNotExist => panic!("Filesystem corrupted"),
+            mount::ReadInodeError::IoError => FetchError::IoError,
         }
     }
 }
@@ -412,7 +851,13 @@ pub enum CreateFetchError {
     /// A child with the same name already exists.
     AlreadyExists,
 
-    /// The child could not be fetched because of an I/O error.
+    /// The name of the child cannot be stored by the filesystem.
+    InvalidName,
+
+    /// There is no space left on the device to create the child.
+    NoSpace,
+
+    /// The child could not be created or fetched because of an I/O error.
     IoError,
 }
 
@@ -420,6 +865,11 @@ impl From<inode::CreateError> for CreateFetchError {
     fn from(error: inode::CreateError) -> Self {
         match error {
             inode::CreateError::AlreadyExists => CreateFetchError::AlreadyExists,
+            inode::CreateError::InvalidName => CreateFetchError::InvalidName,
+            inode::CreateError::NoSpace => CreateFetchError::NoSpace,
+            inode::CreateError::NotSupported | inode::CreateError::IoError => {
+                CreateFetchError::IoError
+            }
         }
     }
 }
@@ -428,6 +878,7 @@ impl From<mount::ReadInodeError> for CreateFetchError {
     fn from(error: mount::ReadInodeError) -> Self {
         match error {
             mount::ReadInodeError::DoesNotExist => panic!("Filesystem corrupted"),
+            mount::ReadInodeError::IoError => CreateFetchError::IoError,
         }
     }
 }
@@ -445,6 +896,18 @@ pub enum ConnectError {
     AlreadyExists,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MountError {
+    /// The mountpoint is not a directory.
+    NotADirectory,
+
+    /// Nothing is mounted on the mountpoint.
+    NotMounted,
+
+    /// The mountpoint or the mounted filesystem is in use.
+    Busy,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DisconnectError {
     /// There is no dentry with the given name in the children list.
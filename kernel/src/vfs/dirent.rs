@@ -23,6 +23,7 @@ pub enum Kind {
     CharDevice,
     Directory,
     File,
+    Symlink,
 }
 
 impl From<inode::Kind> for Kind {
@@ -33,6 +34,7 @@ impl From<inode::Kind> for Kind {
             inode::Kind::Directory => Self::Directory,
             inode::Kind::Pipe => panic!("Pipe cannot be a directory entry"),
             inode::Kind::File => Self::File,
+            inode::Kind::Symlink => Self::Symlink,
         }
     }
 }
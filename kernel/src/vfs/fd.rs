@@ -26,10 +26,13 @@ impl Descriptor {
 /// The descriptor of a file is its index in this table.
 #[derive(Default, Debug, Clone)]
 pub struct OpenedFiles {
-    files: [Option<Arc<File>>; 32],
+    files: [Option<Arc<File>>; Self::CAPACITY],
 }
 
 impl OpenedFiles {
+    /// The maximum number of files that can be opened at the same time.
+    pub const CAPACITY: usize = 32;
+
     /// Create a new empty table of opened files.
     #[must_use]
     pub fn empty() -> Self {
@@ -52,13 +55,18 @@ impl OpenedFiles {
     /// Remove the file corresponding to the given descriptor. Returns the file
     /// if the descriptor is valid, `None` otherwise.
     pub fn remove(&mut self, fd: Descriptor) -> Option<Arc<File>> {
-        self.files[fd.0].take()
+        self.files.get_mut(fd.0)?.take()
     }
 
     /// Get the file corresponding to the given descriptor. Returns the file if
     /// the descriptor is valid, `None` otherwise.
     #[must_use]
     pub fn get(&self, fd: Descriptor) -> Option<&Arc<File>> {
-        self.files[fd.0].as_ref()
+        self.files.get(fd.0).and_then(Option::as_ref)
+    }
+
+    /// Returns an iterator over all the opened files of the table.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<File>> {
+        self.files.iter().flatten()
     }
 }
@@ -1,4 +1,5 @@
-use super::{dentry::Dentry, dirent::DirectoryEntry};
+use super::{dentry::Dentry, dirent::DirectoryEntry, inotify, lock, poll};
+use addr::phys::Physical;
 use core::any::Any;
 
 #[derive(Debug)]
@@ -51,6 +52,20 @@ impl File {
     }
 }
 
+impl Drop for File {
+    /// Release the `flock` locks and the open file description record locks
+    /// owned by this file, since nobody can release them anymore, and notify
+    /// the watchers of the file if it was opened for writing.
+    fn drop(&mut self) {
+        if let Some(dentry) = &self.dentry {
+            dentry.inode().locks.release(lock::Owner::file(self));
+            if self.open_flags.contains(OpenFlags::WRITE) {
+                inotify::notify_dentry(dentry, inotify::Mask::CLOSE_WRITE);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct FileCreateInfo {
     pub dentry: Option<Arc<Dentry>>,
@@ -179,11 +194,38 @@ pub struct FileOperation {
     /// # Errors
     /// If the seek failed, an error is returned, described by the [`SeekError`] enum.
     pub seek: fn(file: &File, offset: isize, whence: Whence) -> Result<Offset, SeekError>,
+
+    /// Returns the events that are currently ready on the file, without blocking.
+    /// Files that may block must call [`poll::notify`] when their state changes.
+    pub poll: fn(file: &File) -> poll::Events,
+
+    /// Performs a device-specific request on the file, and returns a value whose
+    /// meaning depends on the request. The argument is usually a pointer to a
+    /// structure in the userland memory. Files that do not support any request
+    /// should use [`no_ioctl`].
+    ///
+    /// # Errors
+    /// If the request failed, an error is returned, described by the
+    /// [`IoctlError`] enum.
+    pub ioctl: fn(file: &File, request: usize, arg: usize) -> Result<usize, IoctlError>,
+
+    /// Returns the physical address of the memory to map in the userland for
+    /// the given range of the file, which must be contiguous. Only the files of
+    /// devices whose memory is directly accessible, like the framebuffer, can
+    /// be mapped: the memory is not owned by the mapping and is never given
+    /// back to the frame allocator. Other files should use [`no_mmap`].
+    ///
+    /// # Errors
+    /// If the range cannot be mapped, an error is returned, described by the
+    /// [`MmapError`] enum.
+    pub mmap: fn(file: &File, offset: Offset, len: usize) -> Result<Physical, MmapError>,
 }
 
 impl FileOperation {
     /// Writes the given buffer to the file at the given offset, and returns the number
-    /// of bytes written.
+    /// of bytes written. This can be less than the size of the buffer: if an error
+    /// occurs after some bytes were written, they are kept and their count is
+    /// returned instead of the error.
     ///
     /// # Errors
     /// If the buffer could not be written to the file, an error is returned,
@@ -209,6 +251,49 @@ impl FileOperation {
     pub fn seek(&self, file: &File, offset: isize, whence: Whence) -> Result<Offset, SeekError> {
         (self.seek)(file, offset, whence)
     }
+
+    /// Returns the events that are currently ready on the file, without blocking.
+    #[must_use]
+    pub fn poll(&self, file: &File) -> poll::Events {
+        (self.poll)(file)
+    }
+
+    /// Performs a device-specific request on the file.
+    ///
+    /// # Errors
+    /// If the request failed, an error is returned, described by the
+    /// [`IoctlError`] enum.
+    pub fn ioctl(&self, file: &File, request: usize, arg: usize) -> Result<usize, IoctlError> {
+        (self.ioctl)(file, request, arg)
+    }
+
+    /// Returns the physical address of the memory to map for the given range
+    /// of the file.
+    ///
+    /// # Errors
+    /// If the range cannot be mapped, an error is returned, described by the
+    /// [`MmapError`] enum.
+    pub fn mmap(&self, file: &File, offset: Offset, len: usize) -> Result<Physical, MmapError> {
+        (self.mmap)(file, offset, len)
+    }
+}
+
+/// The ioctl operation used by files that do not support any request, which is
+/// the case of most files that are not devices.
+///
+/// # Errors
+/// Always returns [`IoctlError::NotSupported`].
+pub fn no_ioctl(_: &File, _: usize, _: usize) -> Result<usize, IoctlError> {
+    Err(IoctlError::NotSupported)
+}
+
+/// The mmap operation used by files that cannot be mapped in memory, which is
+/// the case of all files that are not devices.
+///
+/// # Errors
+/// Always returns [`MmapError::NotSupported`].
+pub fn no_mmap(_: &File, _: Offset, _: usize) -> Result<Physical, MmapError> {
+    Err(MmapError::NotSupported)
 }
 
 /// The error returned when reading a directory fails.
@@ -216,6 +301,9 @@ impl FileOperation {
 pub enum ReaddirError {
     /// There is no more entry to read.
     EndOfDirectory,
+
+    /// An I/O error occurred while reading the directory.
+    IoError,
 }
 
 /// The error returned when reading from a file fails.
@@ -227,6 +315,18 @@ pub enum ReadError {
     /// The pipe is empty and there are no writers, meaning that the file
     /// will never be written to again and the reader should stop reading.
     BrokenPipe,
+
+    /// There is nothing to read and the file was opened in non-blocking mode.
+    WouldBlock,
+
+    /// The task was interrupted while waiting for data to read.
+    Interrupted,
+
+    /// The buffer is too small to hold the data to read, which cannot be split.
+    BufferTooSmall,
+
+    /// An I/O error occurred while reading from the underlying device.
+    IoError,
 }
 
 /// The error returned when writing to a file fails.
@@ -238,6 +338,13 @@ pub enum WriteError {
     /// The pipe is full and there are no readers, meaning that the file
     /// will never be read from again and the writer should stop writing.
     BrokenPipe,
+
+    /// There is no space left to write the data, for example because the write
+    /// goes beyond the end of a block device.
+    NoSpace,
+
+    /// An I/O error occurred while writing to the underlying device.
+    IoError,
 }
 
 /// The error returned when seeking into a file fails.
@@ -250,3 +357,41 @@ pub enum SeekError {
     /// opened file is a pipe or a character device, for example.
     NotSeekable,
 }
+
+/// The error returned when an ioctl request fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IoctlError {
+    /// The request is not supported by this file.
+    NotSupported,
+
+    /// The argument of the request is invalid.
+    InvalidArgument,
+
+    /// The argument of the request points to an invalid userland address.
+    BadAddress,
+
+    /// The argument of the request is an invalid file descriptor.
+    BadFileDescriptor,
+
+    /// The device is busy and cannot handle the request now.
+    Busy,
+
+    /// The device behind the file does not exist anymore.
+    NoSuchDevice,
+
+    /// An I/O error occurred while handling the request.
+    IoError,
+}
+
+/// The error returned when mapping a file in memory fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MmapError {
+    /// The file cannot be mapped in memory.
+    NotSupported,
+
+    /// The range to map is outside of the file.
+    InvalidRange,
+
+    /// The device behind the file does not exist anymore.
+    NoSuchDevice,
+}
@@ -1,5 +1,8 @@
-use super::{dentry, mount::Super};
-use crate::device::Device;
+use super::{
+    dentry::{self, Dentry},
+    mount::Super,
+};
+use crate::device::{self, Device};
 use core::any::Any;
 
 /// The list of all registered filesystems.
@@ -46,6 +49,24 @@ impl Filesystem {
             superblock
         })
     }
+
+    /// Returns the name of this filesystem.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the filesystem-specific data.
+    #[must_use]
+    pub fn data(&self) -> &dyn Any {
+        &*self.data
+    }
+
+    /// Returns the mounted superblocks of this filesystem.
+    #[must_use]
+    pub fn supers(&self) -> Vec<Arc<Super>> {
+        self.supers.lock().clone()
+    }
 }
 
 /// The operation table for a filesystem.
@@ -77,6 +98,34 @@ pub enum ReadSuperError {
     IoError,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MountError {
+    /// No filesystem with the given name is registered.
+    NoSuchFilesystem,
+
+    /// The mountpoint is not a directory.
+    NotADirectory,
+
+    /// The mountpoint or the device is already used by another mount.
+    Busy,
+
+    /// The filesystem is not mounted.
+    NotMounted,
+
+    /// The superblock could not be read from the device.
+    ReadSuper(ReadSuperError),
+}
+
+impl From<dentry::MountError> for MountError {
+    fn from(error: dentry::MountError) -> Self {
+        match error {
+            dentry::MountError::NotADirectory => MountError::NotADirectory,
+            dentry::MountError::NotMounted => MountError::NotMounted,
+            dentry::MountError::Busy => MountError::Busy,
+        }
+    }
+}
+
 /// Registers a filesystem
 ///
 /// # Panics
@@ -91,6 +140,88 @@ pub fn exists(name: &str) -> bool {
     FILESYSTEMS.lock().iter().any(|fs| fs.name == name)
 }
 
+/// Returns the list of all mounted superblocks, of all filesystem types.
+pub fn supers() -> Vec<Arc<Super>> {
+    FILESYSTEMS
+        .lock()
+        .iter()
+        .flat_map(|fs| fs.supers.lock().clone())
+        .collect()
+}
+
+/// Returns the registered filesystem with the given name, if any.
+pub fn get(name: &str) -> Option<Arc<Filesystem>> {
+    FILESYSTEMS.lock().iter().find(|fs| fs.name == name).cloned()
+}
+
+/// Mount the filesystem with the given name stored on the given device on the
+/// `mountpoint` directory. The superblock is read from the device, and the root
+/// directory of the filesystem replaces the mountpoint in the following lookups.
+///
+/// # Errors
+/// See [`MountError`] for the possible errors.
+pub fn mount(name: &str, device: Device, mountpoint: &Arc<Dentry>) -> Result<(), MountError> {
+    let fs = get(name).ok_or(MountError::NoSuchFilesystem)?;
+    if device != Device::None && supers().iter().any(|sb| sb.device() == device) {
+        return Err(MountError::Busy);
+    }
+
+    let superblock = fs.read_super(device).map_err(MountError::ReadSuper)?;
+    let root = superblock
+        .get_inode(superblock.root())
+        .map_err(|_| MountError::ReadSuper(ReadSuperError::CorruptedFileSystem))
+        .and_then(|inode| {
+            let root = Arc::new(Dentry::new(mountpoint.name(), inode));
+            Dentry::mount(mountpoint, &root)?;
+            superblock.set_root_dentry(&root);
+            Ok(root)
+        });
+
+    if let Err(error) = root {
+        fs.supers.lock().retain(|sb| !Arc::ptr_eq(sb, &superblock));
+        return Err(error);
+    }
+
+    log::info!("vfs: mounted {} ({:?}) on {}", name, device, mountpoint.name().as_str());
+    Ok(())
+}
+
+/// Unmount the filesystem whose root directory is the given dentry. The dentries
+/// and the inodes of the filesystem are released and all dirty inodes are written
+/// back before the superblock is released, then the buffers of the device are
+/// written back to the device.
+///
+/// # Errors
+/// - `MountError::NotMounted`: The dentry is not the root of a mounted filesystem.
+/// - `MountError::Busy`: A file of the filesystem is still in use.
+pub fn unmount(root: Arc<Dentry>) -> Result<(), MountError> {
+    let mountpoint = root.mountpoint().ok_or(MountError::NotMounted)?;
+    let superblock = root
+        .inode()
+        .superblock
+        .upgrade()
+        .ok_or(MountError::NotMounted)?;
+    Dentry::unmount(&mountpoint)?;
+    drop(root);
+
+    // Errors are already logged, and the filesystem cannot be kept mounted
+    // since its root has already been detached from the tree.
+    _ = superblock.sync_inodes();
+    _ = superblock.sync();
+    for fs in FILESYSTEMS.lock().iter() {
+        fs.supers.lock().retain(|sb| !Arc::ptr_eq(sb, &superblock));
+    }
+
+    let device = superblock.device();
+    drop(superblock);
+    if let Device::Block(id) = device {
+        _ = device::block::cache::sync_device(id);
+    }
+
+    log::info!("vfs: unmounted {:?} from {}", device, mountpoint.name().as_str());
+    Ok(())
+}
+
 /// Mount the filesystem with the given name on the given device and initialize
 /// the root dentry
 #[init]
@@ -107,4 +238,7 @@ pub fn mount_root(name: &str, device: Device) {
         .get_inode(superblock.root())
         .expect("Failed to read root inode");
     dentry::setup(inode);
+    if let Some(root) = dentry::ROOT.get() {
+        superblock.set_root_dentry(root);
+    }
 }
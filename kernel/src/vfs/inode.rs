@@ -1,4 +1,4 @@
-use super::{dirent, file, inode, mount::Super};
+use super::{dirent, file, inode, inotify::Watches, lock::Locks, mount::Super};
 use crate::{
     device::{self, Device},
     time::unix::UnixTime,
@@ -54,6 +54,12 @@ pub struct Inode {
 
     /// The superblock of this inode.
     pub superblock: Weak<Super>,
+
+    /// The advisory locks held on this inode, by `flock` or by record locks.
+    pub locks: Locks,
+
+    /// The inotify watches placed on this inode.
+    pub watches: Watches,
 }
 
 impl Inode {
@@ -68,6 +74,8 @@ impl Inode {
             kind: info.kind,
             id: info.id,
             superblock,
+            locks: Locks::new(),
+            watches: Watches::new(),
         }
     }
 
@@ -155,6 +163,11 @@ pub struct InodeMetadata {
 
     /// The size of this inode, in bytes.
     pub size: usize,
+
+    /// The permission bits of this inode, including the set-user-ID, set-group-ID
+    /// and sticky bits. The file type bits are not stored here, but are deduced
+    /// from the kind of the inode.
+    pub mode: u16,
 }
 
 /// The type of an inode.
@@ -165,6 +178,7 @@ pub enum Kind {
     Directory,
     Pipe,
     File,
+    Symlink,
 }
 
 impl From<Kind> for u64 {
@@ -175,6 +189,7 @@ impl From<Kind> for u64 {
             Kind::Directory => 2,
             Kind::Pipe => 3,
             Kind::File => 4,
+            Kind::Symlink => 5,
         }
     }
 }
@@ -281,6 +296,14 @@ pub struct DirectoryOperation {
     /// If the inode could not be renamed, an error is returned, described by
     /// the [`RenameError`] enum.
     pub rename: fn(inode: &Inode, old: &str, new: &str) -> Result<(), RenameError>,
+
+    /// Creates a new symbolic link with the given name in the given directory, pointing
+    /// to the given target path, and returns the identifier of the new inode.
+    ///
+    /// # Errors
+    /// If the inode could not be created, an error is returned, described by
+    /// the [`CreateError`] enum.
+    pub symlink: fn(inode: &Inode, name: &str, target: &str) -> Result<Identifier, CreateError>,
 }
 
 impl DirectoryOperation {
@@ -365,6 +388,21 @@ impl DirectoryOperation {
     pub fn rename(&self, inode: &Inode, old: &str, new: &str) -> Result<(), RenameError> {
         (self.rename)(inode, old, new)
     }
+
+    /// Creates a new symbolic link with the given name in the given directory, pointing
+    /// to the given target path, and returns the identifier of the new inode.
+    ///
+    /// # Errors
+    /// If the inode could not be created, an error is returned, described by
+    /// the [`CreateError`] enum.
+    pub fn symlink(
+        &self,
+        inode: &Inode,
+        name: &str,
+        target: &str,
+    ) -> Result<Identifier, CreateError> {
+        (self.symlink)(inode, name, target)
+    }
 }
 
 #[derive(Debug)]
@@ -394,13 +432,31 @@ impl FileOperation {
 
 /// The error returned when an inode could not be truncated.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum TruncateError {}
+pub enum TruncateError {
+    /// There is no space left on the device.
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
+}
 
 /// The error returned when an file inode could not be created.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CreateError {
     /// An entry with the same name already exists in the directory.
     AlreadyExists,
+
+    /// The name cannot be stored by the filesystem.
+    InvalidName,
+
+    /// The filesystem cannot store this kind of inode.
+    NotSupported,
+
+    /// There is no space left on the device.
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
 }
 
 /// The error returned when an inode could not be created.
@@ -412,6 +468,9 @@ pub enum MknodError {}
 pub enum LookupError {
     /// The entry does not exist in the directory.
     NoSuchEntry,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
 }
 
 /// The error returned when a directory could not be removed.
@@ -426,6 +485,9 @@ pub enum RmdirError {
 
     /// The directory is not empty.
     NotEmpty,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
 }
 
 /// The error returned when a directory could not be created.
@@ -433,6 +495,15 @@ pub enum RmdirError {
 pub enum MkdirError {
     /// An entry with the same name already exists in the directory.
     AlreadyExists,
+
+    /// The name cannot be stored by the filesystem.
+    InvalidName,
+
+    /// There is no space left on the device.
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
 }
 
 /// The error returned when an inode could not be renamed.
@@ -443,11 +514,35 @@ pub enum RenameError {
 
     /// An entry with the same name already exists in the directory.
     AlreadyExists,
+
+    /// The new name cannot be stored by the filesystem.
+    InvalidName,
+
+    /// There is no space left on the device.
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
 }
 
 /// The error returned when a link could not be created.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum LinkError {}
+pub enum LinkError {
+    /// An entry with the same name already exists in the directory.
+    AlreadyExists,
+
+    /// The target is a directory, and hard links to directories are not allowed.
+    IsADirectory,
+
+    /// The filesystem does not support hard links.
+    NotSupported,
+
+    /// There is no space left on the device.
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
+}
 
 /// The error returned when an inode could not be unlinked.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -463,4 +558,7 @@ pub enum UnlinkError {
 
     /// The entry does not exist in the directory.
     NoSuchEntry,
+
+    /// An I/O error occurred while accessing the device.
+    IoError,
 }
@@ -0,0 +1,432 @@
+use super::{
+    dentry::Dentry,
+    file::{self, File},
+    inode::{self, Inode},
+    name::Name,
+    poll,
+};
+use crate::user::task::queue::WaitQueue;
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Weak,
+};
+use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+/// The operation table of an inotify instance file.
+pub static INOTIFY_FILE_OPS: file::FileOperation = file::FileOperation {
+    write,
+    read,
+    seek,
+    poll,
+    ioctl: file::no_ioctl,
+    mmap: file::no_mmap,
+};
+
+/// The counter used to generate the cookies shared by the `MOVED_FROM` and
+/// `MOVED_TO` events of the same rename, allowing the userland to pair them.
+static COOKIE: AtomicU32 = AtomicU32::new(1);
+
+bitflags::bitflags! {
+    /// The events and flags of an inotify watch. The values are the same as
+    /// the ones used by Linux.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Mask: u32 {
+        /// A file was modified.
+        const MODIFY = 0x0000_0002;
+
+        /// A file opened for writing was closed.
+        const CLOSE_WRITE = 0x0000_0008;
+
+        /// A file was renamed from the name given in the event.
+        const MOVED_FROM = 0x0000_0040;
+
+        /// A file was renamed to the name given in the event.
+        const MOVED_TO = 0x0000_0080;
+
+        /// A file was created in the watched directory.
+        const CREATE = 0x0000_0100;
+
+        /// A file was deleted from the watched directory.
+        const DELETE = 0x0000_0200;
+
+        /// The event queue overflowed and some events were lost.
+        const Q_OVERFLOW = 0x0000_4000;
+
+        /// The watch was removed, explicitly or because it was a one-shot watch.
+        const IGNORED = 0x0000_8000;
+
+        /// Only watch the path if it is a directory.
+        const ONLYDIR = 0x0100_0000;
+
+        /// Add the events to the mask of an existing watch instead of replacing it.
+        const MASK_ADD = 0x2000_0000;
+
+        /// The subject of the event is a directory.
+        const ISDIR = 0x4000_0000;
+
+        /// Remove the watch after the first event.
+        const ONESHOT = 0x8000_0000;
+
+        /// All the events that can be watched.
+        const ALL_EVENTS = Self::MODIFY.bits()
+            | Self::CLOSE_WRITE.bits()
+            | Self::MOVED_FROM.bits()
+            | Self::MOVED_TO.bits()
+            | Self::CREATE.bits()
+            | Self::DELETE.bits();
+    }
+}
+
+/// Returns a new cookie to pair the `MOVED_FROM` and `MOVED_TO` events of a rename.
+#[must_use]
+pub fn next_cookie() -> u32 {
+    COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Raise an event about the given dentry. The event is reported to the watchers
+/// of the dentry inode, and to the watchers of its parent directory with the name
+/// of the dentry.
+pub fn notify_dentry(dentry: &Dentry, mask: Mask) {
+    let mask = match dentry.inode().kind {
+        inode::Kind::Directory => mask | Mask::ISDIR,
+        _ => mask,
+    };
+
+    dentry.inode().watches.notify(mask, 0, None);
+    if let Some(parent) = dentry.parent() {
+        if !core::ptr::eq(parent.as_ref(), dentry) {
+            parent.inode().watches.notify(mask, 0, Some(&dentry.name()));
+        }
+    }
+}
+
+/// Raise an event about the entry with the given name in the given directory.
+/// The event is only reported to the watchers of the directory.
+pub fn notify_child(directory: &Inode, mask: Mask, cookie: u32, name: &Name) {
+    directory.watches.notify(mask, cookie, Some(name));
+}
+
+/// A watch placed by an inotify instance on an inode.
+struct Watch {
+    instance: Weak<Instance>,
+    descriptor: i32,
+    mask: Mask,
+}
+
+/// The list of the watches placed on an inode.
+#[derive(Default)]
+pub struct Watches(Spinlock<Vec<Watch>>);
+
+impl Watches {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the given event to all the watches interested in it. One-shot
+    /// watches are removed after reporting the event.
+    fn notify(&self, mask: Mask, cookie: u32, name: Option<&Name>) {
+        // The instances are collected before reporting the event, because
+        // removing a one-shot watch or dropping the last reference to an
+        // instance requires to lock the list of watches.
+        let targets = self
+            .0
+            .lock()
+            .iter()
+            .filter(|watch| watch.mask.intersects(mask & Mask::ALL_EVENTS))
+            .filter_map(|watch| {
+                let oneshot = watch.mask.contains(Mask::ONESHOT);
+                let instance = watch.instance.upgrade()?;
+                Some((instance, watch.descriptor, oneshot))
+            })
+            .collect::<Vec<_>>();
+
+        for (instance, descriptor, oneshot) in targets {
+            instance.push(Event {
+                name: name.cloned(),
+                descriptor,
+                cookie,
+                mask,
+            });
+
+            if oneshot {
+                _ = instance.remove_watch(descriptor);
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for Watches {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Watches")
+            .field("count", &self.0.lock().len())
+            .finish()
+    }
+}
+
+/// An event waiting to be read from an inotify instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    descriptor: i32,
+    mask: Mask,
+    cookie: u32,
+    name: Option<Name>,
+}
+
+impl Event {
+    /// The size of the header of an event, before the name.
+    const HEADER_SIZE: usize = 16;
+
+    /// The length of the name field of the event. Like on Linux, the name is
+    /// terminated by at least one null byte, and padded to keep the events
+    /// aligned on the size of the header.
+    fn name_len(&self) -> usize {
+        self.name.as_ref().map_or(0, |name| {
+            (name.as_str().len() + Self::HEADER_SIZE) & !(Self::HEADER_SIZE - 1)
+        })
+    }
+
+    /// The total size of the event, once encoded.
+    fn size(&self) -> usize {
+        Self::HEADER_SIZE + self.name_len()
+    }
+
+    /// Encode the event into the given buffer, using the same layout as the
+    /// `inotify_event` structure of Linux.
+    ///
+    /// # Panics
+    /// Panics if the buffer is too small to hold the event.
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode(&self, buf: &mut [u8]) {
+        let buf = &mut buf[..self.size()];
+        buf.fill(0);
+        buf[0..4].copy_from_slice(&self.descriptor.to_ne_bytes());
+        buf[4..8].copy_from_slice(&self.mask.bits().to_ne_bytes());
+        buf[8..12].copy_from_slice(&self.cookie.to_ne_bytes());
+        buf[12..16].copy_from_slice(&(self.name_len() as u32).to_ne_bytes());
+        if let Some(name) = &self.name {
+            let name = name.as_str().as_bytes();
+            buf[16..16 + name.len()].copy_from_slice(name);
+        }
+    }
+}
+
+/// An inotify instance. It owns a set of watches placed on inodes, and a queue
+/// of the events reported by these watches, waiting to be read by the userland.
+pub struct Instance {
+    /// The events waiting to be read.
+    events: Spinlock<VecDeque<Event>>,
+
+    /// The tasks waiting for an event to be available.
+    readers: WaitQueue,
+
+    /// The inodes watched by this instance, indexed by watch descriptor. The
+    /// inodes are kept in memory while they are watched, otherwise the watch
+    /// would be lost if the inode was evicted from the inode cache.
+    watched: Spinlock<BTreeMap<i32, Arc<Inode>>>,
+
+    /// The next watch descriptor to allocate.
+    next_descriptor: AtomicI32,
+
+    /// If set, reading an empty instance fails instead of blocking.
+    nonblock: bool,
+}
+
+impl Instance {
+    /// The maximum number of events in the queue. Once reached, further events
+    /// are dropped and a `Q_OVERFLOW` event is queued.
+    const MAX_EVENTS: usize = 16384;
+
+    #[must_use]
+    pub fn new(nonblock: bool) -> Arc<Self> {
+        Arc::new(Self {
+            events: Spinlock::new(VecDeque::new()),
+            readers: WaitQueue::new(),
+            watched: Spinlock::new(BTreeMap::new()),
+            next_descriptor: AtomicI32::new(1),
+            nonblock,
+        })
+    }
+
+    /// Watch the given inode for the events in the given mask, and return the
+    /// watch descriptor. If the inode is already watched by this instance, the
+    /// existing watch is updated and its descriptor is returned.
+    pub fn add_watch(self: &Arc<Self>, inode: &Arc<Inode>, mask: Mask) -> i32 {
+        let flags = mask & (Mask::ALL_EVENTS | Mask::ONESHOT);
+        let mut watches = inode.watches.0.lock();
+        let existing = watches
+            .iter_mut()
+            .find(|watch| Weak::as_ptr(&watch.instance) == Arc::as_ptr(self));
+
+        if let Some(watch) = existing {
+            if mask.contains(Mask::MASK_ADD) {
+                watch.mask |= flags;
+            } else {
+                watch.mask = flags;
+            }
+            return watch.descriptor;
+        }
+
+        let descriptor = self.next_descriptor.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch {
+            instance: Arc::downgrade(self),
+            mask: flags,
+            descriptor,
+        });
+        drop(watches);
+
+        self.watched.lock().insert(descriptor, Arc::clone(inode));
+        descriptor
+    }
+
+    /// Remove the watch with the given descriptor, and queue an `IGNORED` event.
+    ///
+    /// # Errors
+    /// Returns [`RemoveWatchError::InvalidWatch`] if there is no watch with the
+    /// given descriptor in this instance.
+    pub fn remove_watch(&self, descriptor: i32) -> Result<(), RemoveWatchError> {
+        let inode = self
+            .watched
+            .lock()
+            .remove(&descriptor)
+            .ok_or(RemoveWatchError::InvalidWatch)?;
+
+        self.forget(&inode, descriptor);
+        self.push(Event {
+            mask: Mask::IGNORED,
+            name: None,
+            cookie: 0,
+            descriptor,
+        });
+        Ok(())
+    }
+
+    /// Remove the watch with the given descriptor from the watches of the inode.
+    fn forget(&self, inode: &Inode, descriptor: i32) {
+        inode.watches.0.lock().retain(|watch| {
+            watch.descriptor != descriptor || !core::ptr::eq(watch.instance.as_ptr(), self)
+        });
+    }
+
+    /// Queue an event and wake up the tasks waiting for it. If the event is the
+    /// same as the last queued event, it is merged with it as Linux does.
+    fn push(&self, event: Event) {
+        let mut events = self.events.lock();
+        if events.back() == Some(&event) {
+            return;
+        }
+
+        if events.len() >= Self::MAX_EVENTS {
+            let overflow = Event {
+                mask: Mask::Q_OVERFLOW,
+                descriptor: -1,
+                name: None,
+                cookie: 0,
+            };
+            if events.back() != Some(&overflow) {
+                events.push_back(overflow);
+            }
+        } else {
+            events.push_back(event);
+        }
+        drop(events);
+
+        self.readers.wake_up_all();
+        poll::notify();
+    }
+}
+
+impl Drop for Instance {
+    /// Remove all the watches of this instance from the inodes.
+    fn drop(&mut self) {
+        let watched = core::mem::take(&mut *self.watched.lock());
+        for (descriptor, inode) in watched {
+            self.forget(&inode, descriptor);
+        }
+    }
+}
+
+/// Returns the inotify instance associated with the given file.
+///
+/// # Panics
+/// Panics if the file is not an inotify instance.
+fn instance(file: &File) -> &Arc<Instance> {
+    file.data
+        .downcast_ref::<Arc<Instance>>()
+        .expect("File is not an inotify instance")
+}
+
+/// Reads as many events as possible into the given buffer. If there is no event
+/// to read, the current task sleeps until an event is available, unless the
+/// instance is non-blocking.
+///
+/// An event is never split: the buffer must be large enough to hold at least the
+/// first event.
+///
+/// # Errors
+/// - `ReadError::WouldBlock`: There is no event and the instance is non-blocking.
+/// - `ReadError::Interrupted`: The task was interrupted while waiting.
+/// - `ReadError::BufferTooSmall`: The buffer cannot hold the first event.
+fn read(file: &File, buf: &mut [u8], _: file::Offset) -> Result<usize, file::ReadError> {
+    let instance = instance(file);
+    let mut events = loop {
+        let events = instance.events.lock();
+        if !events.is_empty() {
+            break events;
+        }
+        drop(events);
+        if instance.nonblock {
+            return Err(file::ReadError::WouldBlock);
+        }
+
+        // The events are checked again after the task is added to the wait
+        // queue, so that an event queued in the meantime is not missed. The
+        // lock is not kept while sleeping, so another reader may have taken
+        // the events when the task wakes up, in which case it sleeps again.
+        instance
+            .readers
+            .sleep_interruptible_until(|| (!instance.events.lock().is_empty()).then_some(()))
+            .map_err(|_| file::ReadError::Interrupted)?;
+    };
+
+    let mut written = 0;
+    while let Some(event) = events.front() {
+        let size = event.size();
+        if written + size > buf.len() {
+            break;
+        }
+        event.encode(&mut buf[written..]);
+        events.pop_front();
+        written += size;
+    }
+
+    match written {
+        0 => Err(file::ReadError::BufferTooSmall),
+        _ => Ok(written),
+    }
+}
+
+/// Writing to an inotify instance is not supported.
+fn write(_: &File, _: &[u8], _: file::Offset) -> Result<usize, file::WriteError> {
+    Err(file::WriteError::NotImplemented)
+}
+
+/// Seeking into an inotify instance is not supported.
+fn seek(_: &File, _: isize, _: file::Whence) -> Result<file::Offset, file::SeekError> {
+    Err(file::SeekError::NotSeekable)
+}
+
+/// An inotify instance is readable when there is at least one event queued.
+fn poll(file: &File) -> poll::Events {
+    if instance(file).events.lock().is_empty() {
+        poll::Events::empty()
+    } else {
+        poll::Events::IN
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemoveWatchError {
+    /// There is no watch with the given descriptor in the instance.
+    InvalidWatch,
+}
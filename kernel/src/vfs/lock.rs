@@ -0,0 +1,388 @@
+use super::file::File;
+use crate::user::task::{self, queue::WaitQueue, Task};
+use alloc::collections::BTreeMap;
+
+/// The tasks currently waiting for a record lock, associated with the owner
+/// of the lock they are waiting for. This is used to detect deadlocks between
+/// tasks waiting for each other.
+static WAITING: Spinlock<BTreeMap<Owner, Owner>> = Spinlock::new(BTreeMap::new());
+
+/// Release all the record locks held by the given task on the files it has
+/// opened. This must be called when a task exits.
+pub fn release_task(task: &Task) {
+    let owner = Owner::Task(task.id());
+    for file in task.files().lock().iter() {
+        if let Some(dentry) = &file.dentry {
+            dentry.inode().locks.release(owner);
+        }
+    }
+}
+
+/// The owner of a lock. POSIX record locks are owned by a task, while `flock`
+/// locks and open file description record locks are owned by an open file, and
+/// are therefore shared by all descriptors referring to this open file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Owner {
+    Task(task::Identifier),
+    File(usize),
+}
+
+impl Owner {
+    /// Create an owner from an open file. The address of the file is used
+    /// as an identifier, since it is unique while the file is alive and the
+    /// locks owned by a file are released when it is dropped.
+    #[must_use]
+    pub fn file(file: &File) -> Self {
+        Self::File(file as *const File as usize)
+    }
+}
+
+/// The kind of a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A shared lock, also known as a read lock. Multiple owners can hold a
+    /// shared lock on the same range at the same time.
+    Shared,
+
+    /// An exclusive lock, also known as a write lock. Only one owner can hold
+    /// an exclusive lock on a range, and no shared lock can coexist with it.
+    Exclusive,
+}
+
+/// A range of bytes in a file, with both bounds included. A range that ends
+/// at `u64::MAX` extends to the end of the file, whatever its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Range {
+    /// Returns `true` if the two ranges have at least one byte in common.
+    #[must_use]
+    pub const fn overlaps(&self, other: &Range) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Returns `true` if the two ranges overlap or are directly next to each
+    /// other, meaning that they can be merged into a single range.
+    #[must_use]
+    pub const fn touches(&self, other: &Range) -> bool {
+        self.start <= other.end.saturating_add(1) && other.start <= self.end.saturating_add(1)
+    }
+}
+
+/// A byte-range lock held on an inode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub owner: Owner,
+    pub kind: Kind,
+    pub range: Range,
+}
+
+impl Record {
+    /// Returns `true` if this record prevents the given owner from locking the
+    /// given range with the given kind of lock.
+    #[must_use]
+    pub fn conflicts(&self, owner: Owner, kind: Kind, range: &Range) -> bool {
+        self.owner != owner
+            && self.range.overlaps(range)
+            && (self.kind == Kind::Exclusive || kind == Kind::Exclusive)
+    }
+}
+
+/// The advisory locks held on an inode: the whole-file `flock` locks and the
+/// byte-range record locks. Tasks waiting for a lock to be released sleep in
+/// a wait queue shared by all the locks of the inode.
+pub struct Locks {
+    state: Spinlock<State>,
+    queue: WaitQueue,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The `flock` locks held on the inode.
+    flocks: Vec<(Owner, Kind)>,
+
+    /// The record locks held on the inode. The records of the same owner never
+    /// overlap, since locking a range replaces the previous locks of the owner
+    /// on this range.
+    records: Vec<Record>,
+}
+
+impl Locks {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            state: Spinlock::new(State::default()),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    /// Lock or unlock the whole file with a `flock` lock. If `kind` is `None`,
+    /// the lock held by the owner is released. Otherwise, the lock held by the
+    /// owner, if any, is converted to the given kind. Like on Linux, converting
+    /// a lock is not atomic: the previous lock is released before acquiring the
+    /// new one, and may therefore be lost if the new lock cannot be acquired.
+    ///
+    /// # Errors
+    /// - `LockError::WouldBlock`: The lock is held by another owner and `wait`
+    ///   is `false`.
+    /// - `LockError::Interrupted`: The task was interrupted while waiting.
+    pub fn flock(&self, owner: Owner, kind: Option<Kind>, wait: bool) -> Result<(), LockError> {
+        loop {
+            let mut state = self.state.lock();
+            let released = state.release_flock(owner);
+
+            let Some(kind) = kind else {
+                drop(state);
+                self.queue.wake_up_all();
+                return Ok(());
+            };
+
+            if !state.flock_conflicts(owner, kind) {
+                state.flocks.push((owner, kind));
+                drop(state);
+                if released {
+                    self.queue.wake_up_all();
+                }
+                return Ok(());
+            }
+
+            drop(state);
+            if released {
+                self.queue.wake_up_all();
+            }
+            if !wait {
+                return Err(LockError::WouldBlock);
+            }
+
+            // The conflict is checked again after the task was added to the
+            // wait queue, so that a lock released in the meantime, maybe by
+            // another CPU, does not make it sleep forever.
+            self.queue
+                .sleep_interruptible_until(|| {
+                    let state = self.state.lock();
+                    (!state.flock_conflicts(owner, kind)).then_some(())
+                })
+                .map_err(|_| LockError::Interrupted)?;
+        }
+    }
+
+    /// Returns the first record lock that prevents the given owner from locking
+    /// the given range with the given kind of lock, if any.
+    #[must_use]
+    pub fn conflicting(&self, owner: Owner, kind: Kind, range: Range) -> Option<Record> {
+        self.state
+            .lock()
+            .records
+            .iter()
+            .find(|record| record.conflicts(owner, kind, &range))
+            .copied()
+    }
+
+    /// Lock or unlock a range of bytes with a record lock. If `kind` is `None`,
+    /// the locks held by the owner on the range are released, splitting them if
+    /// necessary. Otherwise, the range is locked with the given kind of lock,
+    /// replacing the locks previously held by the owner on this range.
+    ///
+    /// If `wait` is `true` and the range is locked by another owner, the current
+    /// task sleeps until the range is unlocked. Before sleeping, the kernel checks
+    /// that the owner of the lock is not itself waiting, directly or not, for a
+    /// lock held by the current task, which would never happen.
+    ///
+    /// # Errors
+    /// - `LockError::WouldBlock`: The range is locked by another owner and `wait`
+    ///   is `false`.
+    /// - `LockError::Deadlock`: Waiting for the range would cause a deadlock.
+    /// - `LockError::Interrupted`: The task was interrupted while waiting.
+    pub fn lock_range(
+        &self,
+        owner: Owner,
+        kind: Option<Kind>,
+        range: Range,
+        wait: bool,
+    ) -> Result<(), LockError> {
+        loop {
+            let mut state = self.state.lock();
+            let Some(kind) = kind else {
+                state.unlock_range(owner, range);
+                drop(state);
+                self.queue.wake_up_all();
+                return Ok(());
+            };
+
+            let Some(blocker) = state.blocker(owner, kind, &range) else {
+                state.unlock_range(owner, range);
+                state.insert(Record { owner, kind, range });
+                drop(state);
+
+                // Converting an exclusive lock into a shared one, or releasing
+                // a part of a range, may allow other tasks to progress.
+                self.queue.wake_up_all();
+                return Ok(());
+            };
+
+            drop(state);
+            if !wait {
+                return Err(LockError::WouldBlock);
+            }
+            self.wait_for(owner, blocker, kind, range)?;
+        }
+    }
+
+    /// Release all the locks, `flock` and records, held by the given owner.
+    pub fn release(&self, owner: Owner) {
+        let mut state = self.state.lock();
+        let flock = state.release_flock(owner);
+        let count = state.records.len();
+        state.records.retain(|record| record.owner != owner);
+
+        if flock || state.records.len() != count {
+            drop(state);
+            self.queue.wake_up_all();
+        }
+    }
+
+    /// Sleep until `blocker` does not prevent `owner` from locking the given
+    /// range with the given kind of lock anymore, after recording that `owner`
+    /// waits for `blocker` to release a lock.
+    ///
+    /// # Errors
+    /// - `LockError::Deadlock`: The blocker is waiting, directly or through other
+    ///   owners, for a lock held by `owner`.
+    /// - `LockError::Interrupted`: The task was interrupted while waiting.
+    fn wait_for(
+        &self,
+        owner: Owner,
+        blocker: Owner,
+        kind: Kind,
+        range: Range,
+    ) -> Result<(), LockError> {
+        {
+            let mut waiting = WAITING.lock();
+
+            // Follow the chain of owners waiting for each other. The chain
+            // length is bounded by the number of waiters, which also protects
+            // against cycles that do not involve the current owner.
+            let mut next = Some(blocker);
+            for _ in 0..=waiting.len() {
+                match next {
+                    Some(current) if current == owner => return Err(LockError::Deadlock),
+                    Some(current) => next = waiting.get(&current).copied(),
+                    None => break,
+                }
+            }
+            waiting.insert(owner, blocker);
+        }
+
+        let result = self.queue.sleep_interruptible_until(|| {
+            let state = self.state.lock();
+            (state.blocker(owner, kind, &range) != Some(blocker)).then_some(())
+        });
+        WAITING.lock().remove(&owner);
+        result.map_err(|_| LockError::Interrupted)
+    }
+}
+
+impl State {
+    /// Returns `true` if a `flock` lock held by another owner prevents the given
+    /// owner from acquiring the given kind of lock.
+    fn flock_conflicts(&self, owner: Owner, kind: Kind) -> bool {
+        self.flocks.iter().any(|&(other, held)| {
+            other != owner && (kind == Kind::Exclusive || held == Kind::Exclusive)
+        })
+    }
+
+    /// Returns the owner of the first record lock that prevents the given owner
+    /// from locking the given range with the given kind of lock, if any.
+    fn blocker(&self, owner: Owner, kind: Kind, range: &Range) -> Option<Owner> {
+        self.records
+            .iter()
+            .find(|record| record.conflicts(owner, kind, range))
+            .map(|record| record.owner)
+    }
+
+    /// Release the `flock` lock held by the given owner. Returns `true` if the
+    /// owner held a lock.
+    fn release_flock(&mut self, owner: Owner) -> bool {
+        self.flocks
+            .iter()
+            .position(|&(other, _)| other == owner)
+            .map(|index| self.flocks.swap_remove(index))
+            .is_some()
+    }
+
+    /// Release the locks held by the given owner on the given range. Records
+    /// partially covered by the range are split or shrunk.
+    fn unlock_range(&mut self, owner: Owner, range: Range) {
+        let (unlocked, mut kept): (Vec<_>, Vec<_>) = self
+            .records
+            .drain(..)
+            .partition(|record| record.owner == owner && record.range.overlaps(&range));
+
+        for record in unlocked {
+            if record.range.start < range.start {
+                kept.push(Record {
+                    range: Range {
+                        start: record.range.start,
+                        end: range.start - 1,
+                    },
+                    ..record
+                });
+            }
+            if record.range.end > range.end {
+                kept.push(Record {
+                    range: Range {
+                        start: range.end + 1,
+                        end: record.range.end,
+                    },
+                    ..record
+                });
+            }
+        }
+        self.records = kept;
+    }
+
+    /// Insert the given record, merging it with the records of the same owner
+    /// and of the same kind that overlap or are next to it.
+    fn insert(&mut self, mut record: Record) {
+        self.records.retain(|other| {
+            let merge = other.owner == record.owner
+                && other.kind == record.kind
+                && other.range.touches(&record.range);
+            if merge {
+                record.range.start = core::cmp::min(record.range.start, other.range.start);
+                record.range.end = core::cmp::max(record.range.end, other.range.end);
+            }
+            !merge
+        });
+        self.records.push(record);
+    }
+}
+
+impl Default for Locks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for Locks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Locks")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockError {
+    /// The lock is held by another owner, and the caller does not want to wait.
+    WouldBlock,
+
+    /// Waiting for the lock would cause a deadlock.
+    Deadlock,
+
+    /// The task was interrupted while waiting for the lock.
+    Interrupted,
+}
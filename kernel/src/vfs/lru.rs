@@ -0,0 +1,77 @@
+use alloc::collections::BTreeMap;
+
+/// A least recently used list. Each key is associated with a monotonic stamp that is
+/// updated every time the key is touched, allowing to iterate over the keys from the
+/// least recently used to the most recently used one.
+///
+/// Both touching and removing a key are done in logarithmic time, because the list is
+/// implemented with two ordered maps: one mapping keys to their stamp, and one mapping
+/// stamps to their key.
+#[derive(Debug)]
+pub struct Lru<K> {
+    /// The stamp that will be given to the next touched key.
+    clock: u64,
+
+    /// The stamp of each key in the list.
+    stamps: BTreeMap<K, u64>,
+
+    /// The keys in the list, ordered by their stamp.
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Ord + Clone> Lru<K> {
+    /// Create a new empty LRU list.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            clock: 0,
+            stamps: BTreeMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Mark the given key as the most recently used one. If the key is not in the
+    /// list, it is inserted.
+    pub fn touch(&mut self, key: K) {
+        if let Some(stamp) = self.stamps.remove(&key) {
+            self.order.remove(&stamp);
+        }
+
+        self.clock += 1;
+        self.order.insert(self.clock, key.clone());
+        self.stamps.insert(key, self.clock);
+    }
+
+    /// Remove the given key from the list. Returns `true` if the key was in the list,
+    /// or `false` otherwise.
+    pub fn remove(&mut self, key: &K) -> bool {
+        self.stamps
+            .remove(key)
+            .map(|stamp| self.order.remove(&stamp))
+            .is_some()
+    }
+
+    /// Returns an iterator over the keys of the list, from the least recently used to
+    /// the most recently used one.
+    pub fn oldest(&self) -> impl Iterator<Item = &K> {
+        self.order.values()
+    }
+
+    /// Returns the number of keys in the list.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.stamps.len()
+    }
+
+    /// Returns `true` if the list is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.stamps.is_empty()
+    }
+}
+
+impl<K: Ord + Clone> Default for Lru<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
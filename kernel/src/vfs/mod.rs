@@ -1,5 +1,5 @@
 use self::{dentry::Dentry, mount::ReadInodeError};
-use crate::device::Device;
+use crate::{device::Device, mm::shrinker::{self, Shrinker}};
 use alloc::vec;
 
 pub mod dentry;
@@ -8,20 +8,49 @@ pub mod fd;
 pub mod file;
 pub mod fs;
 pub mod inode;
+pub mod inotify;
+pub mod lock;
+pub mod lru;
 pub mod mount;
 pub mod name;
 pub mod path;
 pub mod pipe;
+pub mod poll;
+pub mod writeback;
 
 pub use self::name::*;
 pub use self::path::*;
 
+/// The shrinker of the dentry cache. It must be registered before the inode
+/// cache shrinker, since evicting a dentry may unpin its inode.
+static DENTRY_SHRINKER: Shrinker = Shrinker {
+    name: "dentry",
+    scan: dentry::shrink,
+};
+
+/// The shrinker of the inode caches of all mounted superblocks.
+static INODE_SHRINKER: Shrinker = Shrinker {
+    name: "inode",
+    scan: |count| {
+        fs::supers()
+            .iter()
+            .map(|superblock| superblock.shrink(count))
+            .sum()
+    },
+};
+
 /// Setup the virtual filesystem
 #[init]
 pub fn setup() {
     fs::mount_root("ramfs", Device::None);
+    shrinker::register(&DENTRY_SHRINKER);
+    shrinker::register(&INODE_SHRINKER);
 }
 
+/// The maximum number of symbolic links followed while resolving a path, the
+/// same as on Linux.
+const MAX_SYMLINKS: usize = 40;
+
 bitflags::bitflags! {
     /// Flags to control the behavior of the lookup operation.
     #[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -35,10 +64,16 @@ bitflags::bitflags! {
         /// combination with the `PARENT` flag to ensure that the parent of the
         /// last component of the path is a directory.
         const DIRECTORY = 1 << 1;
+
+        /// Follow the last component of the path if it is a symbolic link. The
+        /// other components are always followed.
+        const FOLLOW = 1 << 2;
     }
 }
 
-/// Resolve the given path to a dentry.
+/// Resolve the given path to a dentry. Symbolic links found in the middle of
+/// the path are always followed, and the last component is only followed if
+/// the `FOLLOW` flag is set.
 ///
 /// # Errors
 /// This function can fail in many ways, and each of them is described by the
@@ -62,30 +97,60 @@ pub fn lookup(
         Arc::clone(cwd)
     };
 
-    // The components of the path to resolve. If the `PARENT` flag is set, the
-    // last component is not resolved because this is the parent component that
-    // we want to return.
-    let components = if flags.contains(LookupFlags::PARENT) {
-        &path.components[..path.components.len() - 1]
+    // The number of components of the path to resolve. If the `PARENT` flag
+    // is set, the last component is not resolved because this is the parent
+    // component that we want to return.
+    let count = if flags.contains(LookupFlags::PARENT) {
+        path.components.len() - 1
     } else {
-        &path.components
+        path.components.len()
     };
 
-    for (i, name) in components.iter().enumerate() {
+    // The components that remain to be resolved, in reverse order so that the
+    // components of the target of a symbolic link can be pushed on top of them.
+    let mut remaining = path.components[..count]
+        .iter()
+        .rev()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut links = 0;
+
+    while let Some(name) = remaining.pop() {
         let dentry = match name.as_str() {
-            "." => parent,
+            "." => Arc::clone(&parent),
             ".." => parent.parent().expect("Dentry without alive parent found"),
-            _ => Dentry::fetch(&parent, name).map_err(|e| match e {
+            _ => Dentry::fetch(&parent, &name).map_err(|e| match e {
                 dentry::FetchError::NotFound => {
-                    let remaining = Path::from(path.components[i..].to_vec());
-                    LookupError::NotFound(Arc::clone(&parent), remaining)
+                    let unresolved = core::iter::once(&name)
+                        .chain(remaining.iter().rev())
+                        .chain(&path.components[count..])
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    LookupError::NotFound(Arc::clone(&parent), Path::from(unresolved))
                 }
                 dentry::FetchError::NotADirectory => LookupError::NotADirectory,
                 dentry::FetchError::IoError => LookupError::IoError,
             })?,
         };
 
-        parent = dentry;
+        let follow =
+            !remaining.is_empty() || flags.intersects(LookupFlags::FOLLOW | LookupFlags::PARENT);
+        if follow && dentry.inode().kind == inode::Kind::Symlink {
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(LookupError::TooManySymlinks);
+            }
+
+            // The target of the link is resolved relatively to the directory
+            // containing the link, unless it is absolute.
+            let target = read_link(&dentry)?;
+            if target.is_absolute() {
+                parent = Arc::clone(root);
+            }
+            remaining.extend(target.components.into_iter().rev());
+        } else {
+            parent = dentry;
+        }
     }
 
     if flags.contains(LookupFlags::DIRECTORY) && parent.inode().kind != inode::Kind::Directory {
@@ -95,6 +160,33 @@ pub fn lookup(
     Ok(parent)
 }
 
+/// Read the target of the given symbolic link.
+///
+/// # Errors
+/// Returns [`LookupError::IoError`] if the link could not be read, and
+/// [`LookupError::CorruptedFilesystem`] if its target is not a valid path.
+fn read_link(dentry: &Arc<Dentry>) -> Result<Path, LookupError> {
+    let len = dentry.inode().metadata.lock().size;
+    if len > Path::MAX_LEN {
+        return Err(LookupError::CorruptedFilesystem);
+    }
+
+    let file = dentry
+        .open(file::OpenFlags::READ)
+        .map_err(|_| LookupError::IoError)?;
+    let mut target = vec![0; len];
+    let readed = file
+        .as_file()
+        .ok_or(LookupError::CorruptedFilesystem)?
+        .read(&file, &mut target, file::Offset(0))
+        .map_err(|_| LookupError::IoError)?;
+
+    core::str::from_utf8(&target[..readed])
+        .ok()
+        .and_then(|target| Path::new(target).ok())
+        .ok_or(LookupError::CorruptedFilesystem)
+}
+
 /// Read all the data of the file at the given path.
 ///
 /// # Errors
@@ -109,8 +201,7 @@ pub fn read_all(
     root: &Arc<Dentry>,
     cwd: &Arc<Dentry>,
 ) -> Result<Box<[u8]>, ReadAllError> {
-    let dentry =
-        lookup(path, root, cwd, LookupFlags::empty()).map_err(ReadAllError::LookupError)?;
+    let dentry = lookup(path, root, cwd, LookupFlags::FOLLOW).map_err(ReadAllError::LookupError)?;
     let file = dentry
         .open(file::OpenFlags::READ)
         .map_err(|_| ReadAllError::OpenError)?;
@@ -166,6 +257,10 @@ pub enum LookupError {
     /// An component of the path used as a directory is not a directory.
     NotADirectory,
 
+    /// Too many symbolic links were followed while resolving the path, which
+    /// probably means that they form a loop.
+    TooManySymlinks,
+
     /// The filesystem is corrupted.
     CorruptedFilesystem,
 
@@ -180,6 +275,7 @@ impl From<ReadInodeError> for LookupError {
             // exist, it means the filesystem is corrupted because the inode
             // identifier was found searching the parent directory.
             ReadInodeError::DoesNotExist => LookupError::CorruptedFilesystem,
+            ReadInodeError::IoError => LookupError::IoError,
         }
     }
 }
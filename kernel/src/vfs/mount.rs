@@ -1,6 +1,13 @@
-use super::inode::{self, Inode};
-use crate::device::Device;
-use alloc::collections::{BTreeMap, BTreeSet};
+use super::{
+    dentry::Dentry,
+    inode::{self, Inode},
+    lru::Lru,
+};
+use crate::{
+    device::Device,
+    time::{units::Nanosecond, uptime_fast},
+};
+use alloc::{collections::BTreeMap, sync::Weak};
 use core::any::Any;
 
 /// The superblock of a filesystem. It contains all informations about the
@@ -9,7 +16,9 @@ use core::any::Any;
 ///
 /// When the superblock is dropped, it synchronizes all dirty inodes with the
 /// underlying device, and then synchronizes itself with the underlying device
-/// to ensure that all informations are up-to-date on the device.
+/// to ensure that all informations are up-to-date on the device. Dirty inodes
+/// are also periodically written back by the writeback kernel thread (see the
+/// [`super::writeback`] module), or explicitly with the sync family syscalls.
 pub struct Super {
     /// The device on which this filesystem is mounted.
     device: Device,
@@ -23,21 +32,41 @@ pub struct Super {
     /// Custom data, freely usable by the filesystem driver.
     data: Box<dyn Any + Send + Sync>,
 
+    /// The root dentry of this filesystem once mounted. It allows filesystems
+    /// whose content changes without going through the VFS, like `devpts`, to
+    /// update the dentry cache.
+    root_dentry: Spinlock<Weak<Dentry>>,
+
     /// The list of all used inodes of this filesystem. This improves performances,
     /// but is also required to prevent the kernel from having multiple instances
     /// of the same inode in memory.
     used_inodes: Spinlock<BTreeMap<inode::Identifier, Arc<Inode>>>,
 
-    /// The list of all dirty inodes of this filesystem.
-    dirty_inodes: Spinlock<BTreeSet<Arc<Inode>>>,
+    /// The LRU list of the cached inodes, used to evict the least recently
+    /// used inodes from the cache when the kernel is running out of memory.
+    lru: Spinlock<Lru<inode::Identifier>>,
+
+    /// The list of all dirty inodes of this filesystem, with the time at which
+    /// each inode was first marked as dirty since its last synchronization.
+    dirty_inodes: Spinlock<BTreeMap<Arc<Inode>, Nanosecond>>,
+
+    /// The write errors that occurred while synchronizing inodes and that were
+    /// not yet reported to the user. They are reported, and then forgotten, by
+    /// the next call to [`Super::sync_inode`] for the inode that failed.
+    #[allow(clippy::zero_sized_map_values)]
+    write_errors: Spinlock<BTreeMap<inode::Identifier, WriteInodeError>>,
 }
 
 impl Super {
     #[must_use]
+    #[allow(clippy::zero_sized_map_values)]
     pub fn new(info: SuperCreationInfo) -> Self {
         Self {
-            dirty_inodes: Spinlock::new(BTreeSet::new()),
+            dirty_inodes: Spinlock::new(BTreeMap::new()),
+            write_errors: Spinlock::new(BTreeMap::new()),
             used_inodes: Spinlock::new(BTreeMap::new()),
+            lru: Spinlock::new(Lru::new()),
+            root_dentry: Spinlock::new(Weak::new()),
             operation: info.operation,
             device: info.device,
             root: info.root,
@@ -57,6 +86,17 @@ impl Super {
         self.root
     }
 
+    /// Returns the root dentry of this filesystem, if it is mounted.
+    #[must_use]
+    pub fn root_dentry(&self) -> Option<Arc<Dentry>> {
+        self.root_dentry.lock().upgrade()
+    }
+
+    /// Set the root dentry of this filesystem, when it is mounted.
+    pub fn set_root_dentry(&self, root: &Arc<Dentry>) {
+        *self.root_dentry.lock() = Arc::downgrade(root);
+    }
+
     /// Returns the custom data of this filesystem.
     #[must_use]
     pub fn data(&self) -> &dyn Any {
@@ -76,7 +116,9 @@ impl Super {
     /// Panics if the inode is already cached by the superblock. It is a bug in the
     /// kernel and should be reported.
     pub fn cache_inode(&self, inode: Arc<Inode>) {
-        assert!(self.used_inodes.lock().insert(inode.id, inode).is_none());
+        let id = inode.id;
+        assert!(self.used_inodes.lock().insert(id, inode).is_none());
+        self.lru.lock().touch(id);
     }
 
     /// Get the inode with the given identifier. IT first checks if the inode
@@ -89,6 +131,7 @@ impl Super {
     pub fn get_inode(&self, id: inode::Identifier) -> Result<Arc<Inode>, ReadInodeError> {
         // Check if the inode is cached by the superblock.
         if let Some(inode) = self.used_inodes.lock().get(&id) {
+            self.lru.lock().touch(id);
             return Ok(Arc::clone(inode));
         }
 
@@ -100,39 +143,141 @@ impl Super {
 
     /// Inserts the given inode in the list of dirty inodes of this filesystem. If
     /// the inode is already in the list, it is not added again and this function
-    /// does nothing.
+    /// does nothing: the inode keeps the time at which it was first dirtied.
     pub fn make_inode_dirty(&self, inode: Arc<Inode>) {
-        self.dirty_inodes.lock().insert(inode);
+        self.dirty_inodes
+            .lock()
+            .entry(inode)
+            .or_insert_with(uptime_fast);
     }
 
-    /// Synchronize all dirty inodes with the underlying device. If an error
-    /// occurs, it is logged and the inode is kept in the list of dirty inodes.
-    pub fn sync_inodes(&self) {
-        self.dirty_inodes
+    /// Synchronize all dirty inodes with the underlying device.
+    ///
+    /// # Errors
+    /// If an inode could not be written to the device, the first error that
+    /// occurred is returned, described by the [`WriteInodeError`] enum. The
+    /// other inodes are still synchronized, and the error is also kept to be
+    /// reported to the next [`Super::sync_inode`] caller for the failed inode.
+    pub fn sync_inodes(&self) -> Result<(), WriteInodeError> {
+        self.sync_expired_inodes(Nanosecond::zero())
+    }
+
+    /// Synchronize all inodes that have been dirty for at least the given
+    /// duration with the underlying device. This is used by the writeback
+    /// thread to avoid writing inodes that are frequently modified too often.
+    ///
+    /// # Errors
+    /// See [`Super::sync_inodes`] for more details.
+    pub fn sync_expired_inodes(&self, age: Nanosecond) -> Result<(), WriteInodeError> {
+        let now = uptime_fast();
+        let expired = self
+            .dirty_inodes
             .lock()
-            .retain(|inode| match (self.operation.write_inode)(inode) {
-                Err(err) => {
-                    log::error!("Failed to write inode: {:?}", err);
-                    log::error!("Retrying later...");
-                    true
-                }
-                Ok(_) => false,
-            });
+            .extract_if(|_, dirtied| now.saturated_sub(*dirtied) >= age)
+            .map(|(inode, _)| inode)
+            .collect::<Vec<_>>();
+
+        // Inodes are written without holding the dirty list lock, allowing
+        // them to be dirtied again while they are being written.
+        expired
+            .iter()
+            .map(|inode| self.write_inode(inode))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Synchronize the given inode with the underlying device if it is dirty,
+    /// and report any write error that occurred since the last call to this
+    /// function for this inode, even if it occurred during a writeback that
+    /// was not initiated by the caller.
+    ///
+    /// # Errors
+    /// If the inode could not be written to the device, now or during a
+    /// previous writeback, an error is returned, described by the
+    /// [`WriteInodeError`] enum.
+    pub fn sync_inode(&self, inode: &Arc<Inode>) -> Result<(), WriteInodeError> {
+        if self.dirty_inodes.lock().remove(inode).is_some() {
+            // The error, if any, is recorded and reported below.
+            _ = self.write_inode(inode);
+        }
+
+        match self.write_errors.lock().remove(&inode.id) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the given inode to the underlying device. If an error occurs, it is
+    /// logged and recorded to be reported later to the user. The inode is not
+    /// kept in the list of dirty inodes, since retrying forever to write an inode
+    /// to a failing device is pointless.
+    fn write_inode(&self, inode: &Arc<Inode>) -> Result<(), WriteInodeError> {
+        (self.operation.write_inode)(inode).map_err(|err| {
+            log::error!("Failed to write inode {:?}: {:?}", inode.id, err);
+            self.write_errors.lock().insert(inode.id, err);
+            err
+        })
+    }
+
+    /// Evict at most `count` unused inodes from the inode cache, starting from the
+    /// least recently used ones, and return the number of evicted inodes.
+    ///
+    /// An inode is considered unused when the cache holds the only reference to
+    /// it. Inodes referenced by a dentry, an open file or by the list of dirty
+    /// inodes are therefore pinned in the cache and are never evicted.
+    pub fn shrink(&self, count: usize) -> usize {
+        let candidates = self
+            .lru
+            .lock()
+            .oldest()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut evicted = 0;
+        for id in candidates {
+            if evicted >= count {
+                break;
+            }
+
+            let mut used = self.used_inodes.lock();
+            if used
+                .get(&id)
+                .is_some_and(|inode| Arc::strong_count(inode) > 1)
+            {
+                continue;
+            }
+
+            // Drop the inode after releasing the lock, since nobody else can
+            // obtain a reference to it once removed from the cache.
+            let inode = used.remove(&id);
+            drop(used);
+
+            self.lru.lock().remove(&id);
+            if inode.is_some() {
+                evicted += 1;
+            }
+        }
+        evicted
     }
 
     /// Sync the superblock with the underlying device. If an error occurs, it is
-    /// logged but ignored, because there is nothing we can do about it.
-    pub fn sync(&self) {
-        _ = (self.operation.write_super)(self).map_err(|err| {
+    /// logged and returned to the caller.
+    ///
+    /// # Errors
+    /// If the superblock could not be written to the device, an error is
+    /// returned, described by the [`WriteSuperError`] enum.
+    pub fn sync(&self) -> Result<(), WriteSuperError> {
+        (self.operation.write_super)(self).map_err(|err| {
             log::error!("Failed to write superblock: {:?}", err);
-        });
+            err
+        })
     }
 }
 
 impl Drop for Super {
     fn drop(&mut self) {
-        self.sync_inodes();
-        self.sync();
+        // Errors are already logged, and there is nobody left to report them to.
+        _ = self.sync_inodes();
+        _ = self.sync();
     }
 }
 
@@ -170,13 +315,22 @@ pub struct Operation {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum WriteSuperError {}
+pub enum WriteSuperError {
+    /// An I/O error occurred while writing the superblock to the device.
+    IoError,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum WriteInodeError {}
+pub enum WriteInodeError {
+    /// An I/O error occurred while writing the inode to the device.
+    IoError,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ReadInodeError {
     /// The inode does not exist.
     DoesNotExist,
+
+    /// An I/O error occurred while reading the inode from the device.
+    IoError,
 }
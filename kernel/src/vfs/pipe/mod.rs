@@ -1,10 +1,17 @@
 use self::{reader::PipeReader, writer::PipeWriter};
-use super::file;
+use super::{file, poll};
 use crate::user::task::queue::WaitQueue;
 use circular_buffer::CircularBuffer;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-static PIPE_FILE_OPS: file::FileOperation = file::FileOperation { write, read, seek };
+static PIPE_FILE_OPS: file::FileOperation = file::FileOperation {
+    write,
+    read,
+    seek,
+    poll,
+    ioctl: file::no_ioctl,
+    mmap: file::no_mmap,
+};
 
 pub mod reader;
 pub mod writer;
@@ -176,6 +183,7 @@ fn write(file: &file::File, buf: &[u8], _offset: file::Offset) -> Result<usize,
 
     // Signal one reader to wake up since there is new data in the pipe.
     pipe_writer.signal_one_reader();
+    poll::notify();
     Ok(written)
 }
 
@@ -226,6 +234,7 @@ fn read(
 
     // Signal one writer to wake up since there is new space in the pipe.
     pipe_reader.signal_one_writer();
+    poll::notify();
     Ok(readed)
 }
 
@@ -240,3 +249,36 @@ fn seek(
 ) -> Result<file::Offset, file::SeekError> {
     Err(file::SeekError::NotSeekable)
 }
+
+/// Returns the events ready on a pipe end. The read end is readable when there
+/// is data in the pipe, and the write end is writable when there is space left
+/// in the pipe. When the other end of the pipe is closed, the read end reports
+/// a hang up and the write end reports an error.
+///
+/// # Panics
+/// Panics if the file is neither a pipe reader nor a pipe writer.
+fn poll(file: &file::File) -> poll::Events {
+    if let Some(reader) = file.data.downcast_ref::<PipeReader>() {
+        let pipe = reader.pipe();
+        let mut events = poll::Events::empty();
+        if !pipe.buffer.lock().is_empty() {
+            events |= poll::Events::IN;
+        }
+        if pipe.writer_count() == 0 {
+            events |= poll::Events::HUP;
+        }
+        events
+    } else if let Some(writer) = file.data.downcast_ref::<PipeWriter>() {
+        let pipe = writer.pipe();
+        let mut events = poll::Events::empty();
+        if !pipe.buffer.lock().is_full() {
+            events |= poll::Events::OUT;
+        }
+        if pipe.reader_count() == 0 {
+            events |= poll::Events::ERR;
+        }
+        events
+    } else {
+        panic!("Trying to poll a file that is not a pipe");
+    }
+}
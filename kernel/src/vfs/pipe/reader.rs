@@ -1,4 +1,5 @@
 use super::Pipe;
+use crate::vfs::poll;
 
 /// A pipe reader. This is a wrapper around a pipe that provides a safe interface
 /// for reading from the pipe.
@@ -12,6 +13,12 @@ impl PipeReader {
         Self { pipe }
     }
 
+    /// Returns the pipe this reader is attached to.
+    #[must_use]
+    pub(super) fn pipe(&self) -> &Pipe {
+        &self.pipe
+    }
+
     /// Reads a byte from the pipe. If the pipe is empty, the current thread
     /// will be put to sleep until a byte is available. After the byte is read,
     /// it is removed from the pipe.
@@ -55,6 +62,7 @@ impl Drop for PipeReader {
     fn drop(&mut self) {
         self.pipe.decrement_readers();
         self.pipe.waiting_writers.wake_up_someone();
+        poll::notify();
     }
 }
 
@@ -1,4 +1,5 @@
 use super::Pipe;
+use crate::vfs::poll;
 
 /// A pipe writer. This is a wrapper around a pipe that provides a safe interface
 /// for writing to the pipe.
@@ -12,6 +13,12 @@ impl PipeWriter {
         Self { pipe }
     }
 
+    /// Returns the pipe this writer is attached to.
+    #[must_use]
+    pub(super) fn pipe(&self) -> &Pipe {
+        &self.pipe
+    }
+
     /// Writes a byte to the pipe. If the pipe is full, the current thread will
     /// be put to sleep until a byte is removed from the pipe.
     ///
@@ -53,6 +60,7 @@ impl Drop for PipeWriter {
     fn drop(&mut self) {
         self.pipe.decrement_writers();
         self.pipe.waiting_readers.wake_up_someone();
+        poll::notify();
     }
 }
 
@@ -0,0 +1,94 @@
+use super::file::File;
+use crate::{
+    time::{timer::Timer, units::Nanosecond, uptime_fast},
+    user::{
+        scheduler::{Scheduler, SCHEDULER},
+        task::{
+            self,
+            queue::{Interrupted, WaitQueue},
+        },
+    },
+    x86_64,
+};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The tasks waiting in the `poll` syscall. Since a task may wait for many files
+/// at once, there is a single queue for all pollable files: every time the state
+/// of a pollable file changes, all the waiting tasks are woken up and check again
+/// the state of the files they are interested in.
+static WAITERS: Lazy<WaitQueue> = Lazy::new(WaitQueue::new);
+
+/// The number of notifications since the boot. A task reads it before checking
+/// the state of its files, and only sleeps if no notification happened since,
+/// so that a change between the check and the sleep is not missed.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+bitflags::bitflags! {
+    /// The events that can be polled on a file. The values are the same as the
+    /// ones used by Linux.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Events: u16 {
+        /// There is data to read.
+        const IN = 0x0001;
+
+        /// There is urgent data to read.
+        const PRI = 0x0002;
+
+        /// Writing is now possible without blocking.
+        const OUT = 0x0004;
+
+        /// An error condition occurred.
+        const ERR = 0x0008;
+
+        /// The other end of the file was closed.
+        const HUP = 0x0010;
+
+        /// The file descriptor is invalid.
+        const NVAL = 0x0020;
+    }
+}
+
+/// The poll operation used by files that never block, such as regular files
+/// stored in memory: they are always ready for reading and writing.
+#[must_use]
+pub fn always_ready(_: &File) -> Events {
+    Events::IN | Events::OUT
+}
+
+/// Notify the tasks waiting in the `poll` syscall that the state of a pollable
+/// file has changed. This must be called by every pollable file when it becomes
+/// readable or writable, otherwise waiting tasks may never be woken up.
+pub fn notify() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    WAITERS.wake_up_all();
+}
+
+/// Returns the current generation of the notifications, to be read before
+/// checking the state of the files and given to [`wait`].
+#[must_use]
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Put the current task to sleep until a pollable file changes its state after
+/// the given generation was read, or until the given deadline is reached, if
+/// any.
+///
+/// # Errors
+/// Returns [`Interrupted`] if the task was interrupted while waiting.
+pub fn wait(generation: u64, deadline: Option<Nanosecond>) -> Result<(), Interrupted> {
+    let changed = move || GENERATION.load(Ordering::SeqCst) != generation;
+    let Some(deadline) = deadline else {
+        return WAITERS.sleep_interruptible_until(|| changed().then_some(()));
+    };
+
+    // The deadline is checked with the notifications, so that a timer expiring
+    // before the task is effectively put to sleep does not make it sleep forever.
+    x86_64::irq::without(|| {
+        let current = SCHEDULER.current_task();
+        let _timer = Timer::new(deadline, move |_| {
+            current.change_state_if(task::State::Blocked, task::State::Ready);
+        });
+        WAITERS.sleep_interruptible_until(|| (changed() || uptime_fast() >= deadline).then_some(()))
+    })
+}
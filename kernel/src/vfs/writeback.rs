@@ -0,0 +1,74 @@
+use super::fs;
+use crate::{
+    cmdline,
+    device::block::cache,
+    time::units::{Millisecond, Nanosecond, Second},
+    user::{
+        scheduler::{Scheduler, SCHEDULER},
+        task::{self, Priority, Task},
+    },
+};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The interval between two wake up of the writeback thread.
+const INTERVAL: Second = Second::new(5);
+
+/// The default age, in milliseconds, after which a dirty inode is written back
+/// to the underlying device by the writeback thread.
+const DEFAULT_EXPIRE: u64 = 30_000;
+
+/// The age, in milliseconds, after which a dirty inode is written back to the
+/// underlying device by the writeback thread.
+static EXPIRE: AtomicU64 = AtomicU64::new(DEFAULT_EXPIRE);
+
+/// Returns the age after which a dirty inode is written back to the underlying
+/// device by the writeback thread.
+#[must_use]
+pub fn expire() -> Millisecond {
+    Millisecond::new(EXPIRE.load(Ordering::Relaxed))
+}
+
+/// Set the age after which a dirty inode is written back to the underlying device
+/// by the writeback thread. A shorter age reduces the amount of data lost in case
+/// of a crash, but increases the number of writes to the devices.
+pub fn set_expire(age: Millisecond) {
+    EXPIRE.store(age.0, Ordering::Relaxed);
+}
+
+/// Synchronize all mounted filesystems with their underlying device, then write
+/// back the dirty buffers of the block devices. Errors are not reported to the
+/// caller, but are kept by each superblock to be reported to the next `fsync`
+/// caller on the inodes that failed to be written.
+pub fn sync_all() {
+    for superblock in fs::supers() {
+        _ = superblock.sync_inodes();
+        _ = superblock.sync();
+    }
+    cache::sync_all();
+}
+
+/// The writeback kernel thread. It periodically writes back to the underlying
+/// devices the inodes and the buffers that have been dirty for longer than
+/// [`expire`].
+fn writeback() -> ! {
+    loop {
+        let age = Nanosecond::from(expire());
+        for superblock in fs::supers() {
+            _ = superblock.sync_expired_inodes(age);
+        }
+        cache::sync_expired(age);
+        task::sleep_for(INTERVAL);
+    }
+}
+
+/// Spawn the writeback kernel thread. This must be called after the scheduler
+/// and the virtual filesystem have been initialized. The age after which dirty
+/// inodes are written back can be set in milliseconds with the
+/// `writeback.expire` option of the command line.
+#[init]
+pub fn setup() {
+    if let Some(age) = cmdline::parse("writeback.expire") {
+        set_expire(Millisecond::new(age));
+    }
+    SCHEDULER.add_task(Task::kernel(writeback, Priority::Low));
+}
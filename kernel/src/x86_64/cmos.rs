@@ -1,19 +1,176 @@
+//! The CMOS memory and its real-time clock (RTC). The registers of the clock
+//! hold the date in binary-coded decimal (BCD) or in binary, and the hours in
+//! the 12 or 24 hours format, as configured by the status register B. The
+//! clock can also raise the IRQ 8 periodically, when an alarm rings, or each
+//! time it has updated its date. The register C must then be read to know the
+//! cause of the interrupt and to allow the next ones.
+//!
+//! The date is only read while no update is in progress, and read again until
+//! two reads agree. While the date is written, the updates of the clock are
+//! halted so that the date written is not updated halfway.
 use super::{io::Port, irq};
+use crate::{acpi::fadt, time::date::Date};
+use bitflags::bitflags;
 
 static IO_ADDRESS: Port<u8> = Port::new(0x70);
 static IO_DATA: Port<u8> = Port::new(0x71);
 
-/// Represents a CMOS register. Each register is 1 byte wide and contains a binary-coded
-/// decimal (BCD) value.
+/// Serializes the accesses to the CMOS, which need to write the address of
+/// the register before reading or writing it. It is always locked with
+/// interrupts disabled.
+static LOCK: Spinlock<()> = Spinlock::new(());
+
+/// The IRQ raised by the real-time clock.
+pub const IRQ: u8 = 8;
+
+/// The frequency of the oscillator of the clock, from which the frequency of
+/// the periodic interrupt is divided.
+pub const BASE_FREQUENCY: u32 = 32768;
+
+/// The mask of the rate selecting the frequency of the periodic interrupt in
+/// the status register A.
+const RATE_MASK: u8 = 0x0F;
+
+/// Set in the status register A while the clock updates its date.
+const UPDATE_IN_PROGRESS: u8 = 1 << 7;
+
+/// Set in the hours register when the hour is after noon, in the 12 hours
+/// format.
+const PM: u8 = 1 << 7;
+
+/// The value of the alarm registers matching any value.
+const ALARM_ANY: u8 = 0xC0;
+
+/// The index of the century register used by most firmwares, assumed when
+/// there is no FADT to give it.
+const DEFAULT_CENTURY: u8 = 0x32;
+
+/// The error returned when a date cannot be stored in the real-time clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnsupportedDate;
+
+/// Represents a CMOS register.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Register {
     Seconds = 0x00,
+    SecondsAlarm = 0x01,
     Minutes = 0x02,
+    MinutesAlarm = 0x03,
     Hours = 0x04,
+    HoursAlarm = 0x05,
+    Weekday = 0x06,
     Day = 0x07,
     Month = 0x08,
     Year = 0x09,
+    StatusA = 0x0A,
+    StatusB = 0x0B,
+    StatusC = 0x0C,
+}
+
+bitflags! {
+    /// The flags of the status register B.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct StatusB : u8 {
+        /// Daylight saving time is enabled.
+        const DAYLIGHT_SAVING = 1 << 0;
+
+        /// The hours are in the 24 hours format instead of the 12 hours one.
+        const HOURS_24 = 1 << 1;
+
+        /// The date is in binary instead of BCD.
+        const BINARY = 1 << 2;
+
+        /// The square wave output is enabled.
+        const SQUARE_WAVE = 1 << 3;
+
+        /// Raise an interrupt each time the clock has updated its date.
+        const UPDATE_INTERRUPT = 1 << 4;
+
+        /// Raise an interrupt when the time matches the alarm.
+        const ALARM_INTERRUPT = 1 << 5;
+
+        /// Raise an interrupt periodically, at the frequency set in the status
+        /// register A.
+        const PERIODIC_INTERRUPT = 1 << 6;
+
+        /// Halt the updates of the clock, while the date is written.
+        const SET = 1 << 7;
+    }
+
+    /// The flags of the status register C, giving the causes of an interrupt.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Interrupts : u8 {
+        /// The clock has updated its date.
+        const UPDATE = 1 << 4;
+
+        /// The alarm rang.
+        const ALARM = 1 << 5;
+
+        /// The periodic interrupt.
+        const PERIODIC = 1 << 6;
+
+        /// Set if an interrupt was raised for any of the causes above.
+        const IRQ = 1 << 7;
+    }
+}
+
+/// The time of the alarm of the clock, which rings every day. A field set to
+/// `None` matches any value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Alarm {
+    pub hour: Option<u8>,
+    pub minute: Option<u8>,
+    pub second: Option<u8>,
+}
+
+/// The format of the registers of the clock, given by the status register B.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Format(StatusB);
+
+impl Format {
+    /// Decode the value of a register other than the hours.
+    fn decode(self, value: u8) -> u8 {
+        if self.0.contains(StatusB::BINARY) {
+            value
+        } else {
+            bcd2bin(value)
+        }
+    }
+
+    /// Encode the value of a register other than the hours.
+    fn encode(self, value: u8) -> u8 {
+        if self.0.contains(StatusB::BINARY) {
+            value
+        } else {
+            bin2bcd(value)
+        }
+    }
+
+    /// Decode the value of an hours register, in the 24 hours format.
+    fn decode_hour(self, value: u8) -> u8 {
+        if self.0.contains(StatusB::HOURS_24) {
+            return self.decode(value);
+        }
+        let hour = self.decode(value & !PM) % 12;
+        if value & PM == 0 {
+            hour
+        } else {
+            hour + 12
+        }
+    }
+
+    /// Encode an hour in the 24 hours format for an hours register.
+    fn encode_hour(self, hour: u8) -> u8 {
+        if self.0.contains(StatusB::HOURS_24) {
+            return self.encode(hour);
+        }
+        let pm = if hour >= 12 { PM } else { 0 };
+        match hour % 12 {
+            0 => self.encode(12) | pm,
+            hour => self.encode(hour) | pm,
+        }
+    }
 }
 
 /// Converts a binary-coded decimal (BCD) value to a binary value.
@@ -26,19 +183,213 @@ const fn bin2bcd(value: u8) -> u8 {
     ((value / 10) << 4) + (value % 10)
 }
 
-/// Returns the value of the given CMOS register. The value is converted from
-/// binary-coded decimal (BCD) to binary.
+/// Returns the raw value of the given CMOS register.
 #[must_use]
 pub fn read(reg: Register) -> u8 {
-    irq::without(|| unsafe {
-        IO_ADDRESS.write(mni_bit() | reg as u8);
-        bcd2bin(IO_DATA.read())
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        read_locked(reg)
+    })
+}
+
+/// Writes the given raw value to the given CMOS register.
+pub fn write(reg: Register, value: u8) {
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        write_locked(reg, value);
+    });
+}
+
+/// Reads the date of the real-time clock. The year is assumed to be in the
+/// 21st century if the century register does not hold a valid century.
+#[must_use]
+pub fn read_date() -> Date {
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        let format = Format(StatusB::from_bits_retain(read_locked(Register::StatusB)));
+        let mut date = read_date_locked(format);
+        loop {
+            let again = read_date_locked(format);
+            if again == date {
+                break date;
+            }
+            date = again;
+        }
+    })
+}
+
+/// Writes the given date to the real-time clock.
+///
+/// # Errors
+/// Returns [`UnsupportedDate`] if the year cannot be stored in the clock: it
+/// must be between 1900 and 2099, and in the 21st century if there is no
+/// century register.
+#[allow(clippy::cast_possible_truncation)]
+pub fn write_date(date: &Date) -> Result<(), UnsupportedDate> {
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        let status = StatusB::from_bits_retain(read_locked(Register::StatusB));
+        let format = Format(status);
+        let century = century_locked();
+        let years = if century.is_some() { 1900..2100 } else { 2000..2100 };
+        if !years.contains(&date.year) {
+            return Err(UnsupportedDate);
+        }
+
+        write_locked(Register::StatusB, (status | StatusB::SET).bits());
+        write_locked(Register::Seconds, format.encode(date.second));
+        write_locked(Register::Minutes, format.encode(date.minute));
+        write_locked(Register::Hours, format.encode_hour(date.hour));
+        write_locked(Register::Weekday, format.encode(weekday(*date) + 1));
+        write_locked(Register::Day, format.encode(date.day));
+        write_locked(Register::Month, format.encode(date.month));
+        write_locked(Register::Year, format.encode((date.year % 100) as u8));
+        if let Some((index, _)) = century {
+            write_index_locked(index, format.encode((date.year / 100) as u8));
+        }
+        write_locked(Register::StatusB, (status - StatusB::SET).bits());
+        Ok(())
+    })
+}
+
+/// Reads the time of the alarm of the real-time clock.
+#[must_use]
+pub fn read_alarm() -> Alarm {
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        let format = Format(StatusB::from_bits_retain(read_locked(Register::StatusB)));
+        let field = |reg: Register, hour: bool| {
+            let value = read_locked(reg);
+            match (value & ALARM_ANY == ALARM_ANY, hour) {
+                (true, _) => None,
+                (false, true) => Some(format.decode_hour(value)),
+                (false, false) => Some(format.decode(value)),
+            }
+        };
+        Alarm {
+            hour: field(Register::HoursAlarm, true),
+            minute: field(Register::MinutesAlarm, false),
+            second: field(Register::SecondsAlarm, false),
+        }
     })
 }
 
-/// Writes the given value to the given CMOS register.
-pub fn write(_reg: Register, _value: u8) {
-    unimplemented!("Write to CMOS may be dangerous and is not implemented");
+/// Sets the time of the alarm of the real-time clock.
+pub fn write_alarm(alarm: &Alarm) {
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        let format = Format(StatusB::from_bits_retain(read_locked(Register::StatusB)));
+        let hour = alarm
+            .hour
+            .map_or(ALARM_ANY, |hour| format.encode_hour(hour));
+        let minute = alarm
+            .minute
+            .map_or(ALARM_ANY, |minute| format.encode(minute));
+        let second = alarm
+            .second
+            .map_or(ALARM_ANY, |second| format.encode(second));
+        write_locked(Register::HoursAlarm, hour);
+        write_locked(Register::MinutesAlarm, minute);
+        write_locked(Register::SecondsAlarm, second);
+    });
+}
+
+/// Enables or disables the given interrupts of the real-time clock, among
+/// [`StatusB::UPDATE_INTERRUPT`], [`StatusB::ALARM_INTERRUPT`] and
+/// [`StatusB::PERIODIC_INTERRUPT`].
+pub fn set_interrupts(interrupts: StatusB, enabled: bool) {
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        let mut status = StatusB::from_bits_retain(read_locked(Register::StatusB));
+        status.set(interrupts, enabled);
+        write_locked(Register::StatusB, status.bits());
+    });
+}
+
+/// Sets the rate of the periodic interrupt, whose frequency is
+/// `BASE_FREQUENCY >> (rate - 1)`. The rate must be between 3 and 15.
+pub fn set_rate(rate: u8) {
+    debug_assert!((3..=15).contains(&rate));
+    irq::without(|| {
+        let _lock = LOCK.lock();
+        let status = read_locked(Register::StatusA);
+        write_locked(Register::StatusA, (status & !RATE_MASK) | rate);
+    });
+}
+
+/// Reads the status register C, which acknowledges the interrupt of the clock
+/// so that it can raise the next one, and returns the causes of the interrupt.
+#[must_use]
+pub fn acknowledge() -> Interrupts {
+    Interrupts::from_bits_truncate(read(Register::StatusC))
+}
+
+/// Reads the date of the clock, after waiting for the end of the update in
+/// progress, if any.
+fn read_date_locked(format: Format) -> Date {
+    while read_locked(Register::StatusA) & UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+
+    let century = century_locked().map_or(20, |(_, century)| century);
+    let year = u16::from(format.decode(read_locked(Register::Year)));
+    Date {
+        year: u16::from(century) * 100 + year,
+        month: format.decode(read_locked(Register::Month)),
+        day: format.decode(read_locked(Register::Day)),
+        hour: format.decode_hour(read_locked(Register::Hours)),
+        minute: format.decode(read_locked(Register::Minutes)),
+        second: format.decode(read_locked(Register::Seconds)),
+    }
+}
+
+/// Returns the index of the century register, given by the FADT, or `None` if
+/// the FADT tells that there is none.
+fn century_register() -> Option<u8> {
+    match fadt::get() {
+        Some(fadt) => (fadt.century != 0).then_some(fadt.century),
+        None => Some(DEFAULT_CENTURY),
+    }
+}
+
+/// Returns the index of the century register and the century it holds, or
+/// `None` if there is no century register or if it does not hold a plausible
+/// century.
+fn century_locked() -> Option<(u8, u8)> {
+    let index = century_register()?;
+    let status = StatusB::from_bits_retain(read_locked(Register::StatusB));
+    let century = Format(status).decode(read_index_locked(index));
+    (19..=20).contains(&century).then_some((index, century))
+}
+
+/// Returns the day of the week of the given date, 0 being Sunday.
+#[allow(clippy::cast_possible_truncation)]
+fn weekday(date: Date) -> u8 {
+    // The 1st of January 1970 was a Thursday.
+    let days = u64::from(date.to_unix_time()) / (60 * 60 * 24);
+    ((days + 4) % 7) as u8
+}
+
+fn read_locked(reg: Register) -> u8 {
+    read_index_locked(reg as u8)
+}
+
+fn write_locked(reg: Register, value: u8) {
+    write_index_locked(reg as u8, value);
+}
+
+fn read_index_locked(index: u8) -> u8 {
+    unsafe {
+        IO_ADDRESS.write(mni_bit() | index);
+        IO_DATA.read()
+    }
+}
+
+fn write_index_locked(index: u8, value: u8) {
+    unsafe {
+        IO_ADDRESS.write(mni_bit() | index);
+        IO_DATA.write(value);
+    }
 }
 
 #[must_use]
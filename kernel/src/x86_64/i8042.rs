@@ -0,0 +1,304 @@
+use super::io::Port;
+use bitflags::bitflags;
+
+/// The data port, used to read the bytes sent by the devices and the responses of the controller,
+/// and to write the bytes sent to the devices and the parameters of the commands.
+static DATA: Port<u8> = Port::new(0x60);
+
+/// The status register when read, and the command register when written.
+static STATUS: Port<u8> = Port::new(0x64);
+static COMMAND: Port<u8> = Port::new(0x64);
+
+/// The number of times the status register is read before giving up when waiting for the
+/// controller. Each read takes about a microsecond, so this is roughly a tenth of a second.
+const TIMEOUT: usize = 100_000;
+
+/// The IRQ raised by the first port, usually connected to the keyboard.
+pub const KEYBOARD_IRQ: u8 = 1;
+
+/// The IRQ raised by the second port, usually connected to the mouse.
+pub const MOUSE_IRQ: u8 = 12;
+
+/// The response sent by the controller to a successful self test.
+const SELF_TEST_PASSED: u8 = 0x55;
+
+/// The response sent by the controller to a successful port test.
+const PORT_TEST_PASSED: u8 = 0x00;
+
+/// The byte sent by a device to acknowledge a command.
+pub const ACK: u8 = 0xFA;
+
+/// The byte sent by a device that wants the last byte to be sent again.
+pub const RESEND: u8 = 0xFE;
+
+bitflags! {
+    /// The status register of the controller.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Status: u8 {
+        /// A byte can be read from the data port.
+        const OUTPUT_FULL = 1 << 0;
+
+        /// The controller has not processed the last byte written yet.
+        const INPUT_FULL = 1 << 1;
+
+        /// The byte in the data port was sent by the second port.
+        const AUX_DATA = 1 << 5;
+    }
+
+    /// The configuration byte of the controller.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Config: u8 {
+        /// Raise IRQ 1 when the first port sends a byte.
+        const FIRST_INTERRUPT = 1 << 0;
+
+        /// Raise IRQ 12 when the second port sends a byte.
+        const SECOND_INTERRUPT = 1 << 1;
+
+        /// The system passed the power-on self test.
+        const SYSTEM = 1 << 2;
+
+        /// The clock of the first port is disabled.
+        const FIRST_CLOCK_DISABLED = 1 << 4;
+
+        /// The clock of the second port is disabled.
+        const SECOND_CLOCK_DISABLED = 1 << 5;
+
+        /// The scancodes of the first port are translated from set 2 to set 1.
+        const TRANSLATION = 1 << 6;
+    }
+}
+
+/// The commands of the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+enum Command {
+    ReadConfig = 0x20,
+    WriteConfig = 0x60,
+    DisableSecond = 0xA7,
+    EnableSecond = 0xA8,
+    TestSecond = 0xA9,
+    SelfTest = 0xAA,
+    TestFirst = 0xAB,
+    DisableFirst = 0xAD,
+    EnableFirst = 0xAE,
+    WriteSecond = 0xD4,
+}
+
+/// The ports of the controller, to which a device can be connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// The first port, usually connected to the keyboard.
+    First,
+
+    /// The second port, usually connected to the mouse.
+    Second,
+}
+
+/// The error returned when the controller does not respond in time or reports an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// The controller did not respond in time, or there is no controller.
+    Timeout,
+
+    /// The controller or the device sent an unexpected response, like a failed self test.
+    UnexpectedResponse(u8),
+}
+
+/// Returns the status register of the controller.
+#[must_use]
+pub fn status() -> Status {
+    // SAFETY: Reading the status register has no side effects.
+    Status::from_bits_retain(unsafe { STATUS.read() })
+}
+
+/// Read a byte from the data port without waiting, and returns it with the port that sent it, or
+/// `None` if there is nothing to read.
+///
+/// # Safety
+/// This function is unsafe because it consumes the byte, which may be expected by a driver waiting
+/// for a response.
+#[must_use]
+pub unsafe fn receive() -> Option<(Channel, u8)> {
+    let status = status();
+    if !status.contains(Status::OUTPUT_FULL) {
+        return None;
+    }
+    let channel = if status.contains(Status::AUX_DATA) {
+        Channel::Second
+    } else {
+        Channel::First
+    };
+    Some((channel, DATA.read()))
+}
+
+/// Read a byte from the data port, waiting until one is available.
+///
+/// # Errors
+/// Returns `Error::Timeout` if no byte was received in time.
+///
+/// # Safety
+/// See [`receive`].
+pub unsafe fn read() -> Result<u8, Error> {
+    for _ in 0..TIMEOUT {
+        if let Some((_, byte)) = receive() {
+            return Ok(byte);
+        }
+        core::hint::spin_loop();
+    }
+    Err(Error::Timeout)
+}
+
+/// Discard the bytes waiting in the output buffer of the controller.
+///
+/// # Safety
+/// See [`receive`].
+pub unsafe fn flush() {
+    while receive().is_some() {}
+}
+
+/// Write a byte to the data port, waiting until the controller is ready to accept it.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the controller did not accept the byte in time.
+///
+/// # Safety
+/// This function is unsafe because the byte is sent to a device or used as the parameter of the
+/// last command, which can have any effect on the system.
+pub unsafe fn write(byte: u8) -> Result<(), Error> {
+    wait_input()?;
+    DATA.write(byte);
+    Ok(())
+}
+
+/// Send a byte to the device connected to the given port, without waiting for its response.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the controller did not accept the byte in time.
+///
+/// # Safety
+/// See [`write`].
+pub unsafe fn send(channel: Channel, byte: u8) -> Result<(), Error> {
+    if channel == Channel::Second {
+        command(Command::WriteSecond)?;
+    }
+    write(byte)
+}
+
+/// Send a command to the device connected to the given port, and wait for its acknowledgement.
+/// The command is sent again a few times if the device asks for it.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the device did not acknowledge the command in time, or
+/// `Error::UnexpectedResponse` if it sent something else.
+///
+/// # Safety
+/// See [`write`]. The interrupts of the port must be disabled, otherwise the acknowledgement
+/// would be consumed by the interrupt handler.
+pub unsafe fn execute(channel: Channel, byte: u8) -> Result<(), Error> {
+    for _ in 0..3 {
+        send(channel, byte)?;
+        match read()? {
+            ACK => return Ok(()),
+            RESEND => continue,
+            response => return Err(Error::UnexpectedResponse(response)),
+        }
+    }
+    Err(Error::Timeout)
+}
+
+/// Returns the configuration byte of the controller.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the controller did not respond in time.
+///
+/// # Safety
+/// The interrupts of the ports must be disabled, otherwise the response could be consumed by an
+/// interrupt handler.
+pub unsafe fn config() -> Result<Config, Error> {
+    command(Command::ReadConfig)?;
+    read().map(Config::from_bits_retain)
+}
+
+/// Write the configuration byte of the controller.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the controller did not accept the configuration in time.
+///
+/// # Safety
+/// This function is unsafe because enabling the interrupts of a port without a handler, or
+/// disabling the system flag, can have unexpected effects on the system.
+pub unsafe fn set_config(config: Config) -> Result<(), Error> {
+    command(Command::WriteConfig)?;
+    write(config.bits())
+}
+
+/// Enable or disable the given port. A disabled port does not send anything.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the controller did not accept the command in time.
+///
+/// # Safety
+/// This function is unsafe because the device connected to the port may start sending bytes that
+/// must be handled.
+pub unsafe fn enable(channel: Channel, enabled: bool) -> Result<(), Error> {
+    command(match (channel, enabled) {
+        (Channel::First, true) => Command::EnableFirst,
+        (Channel::First, false) => Command::DisableFirst,
+        (Channel::Second, true) => Command::EnableSecond,
+        (Channel::Second, false) => Command::DisableSecond,
+    })
+}
+
+/// Run the self test of the controller. On some controllers, this resets the configuration byte,
+/// which must be written again after the test.
+///
+/// # Errors
+/// Returns `Error::Timeout` if there is no controller, or `Error::UnexpectedResponse` if the
+/// test failed.
+///
+/// # Safety
+/// The interrupts of the ports must be disabled, and the ports disabled.
+pub unsafe fn self_test() -> Result<(), Error> {
+    command(Command::SelfTest)?;
+    match read()? {
+        SELF_TEST_PASSED => Ok(()),
+        response => Err(Error::UnexpectedResponse(response)),
+    }
+}
+
+/// Test the given port.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the controller did not respond in time, or
+/// `Error::UnexpectedResponse` if the port is broken.
+///
+/// # Safety
+/// The interrupts of the ports must be disabled, and the ports disabled.
+pub unsafe fn test(channel: Channel) -> Result<(), Error> {
+    command(match channel {
+        Channel::First => Command::TestFirst,
+        Channel::Second => Command::TestSecond,
+    })?;
+    match read()? {
+        PORT_TEST_PASSED => Ok(()),
+        response => Err(Error::UnexpectedResponse(response)),
+    }
+}
+
+/// Send a command to the controller.
+unsafe fn command(command: Command) -> Result<(), Error> {
+    wait_input()?;
+    COMMAND.write(command as u8);
+    Ok(())
+}
+
+/// Wait until the controller has processed the last byte written.
+fn wait_input() -> Result<(), Error> {
+    for _ in 0..TIMEOUT {
+        if !status().contains(Status::INPUT_FULL) {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(Error::Timeout)
+}
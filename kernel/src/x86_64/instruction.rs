@@ -150,3 +150,46 @@ pub fn invlpg(addr: u64) {
         core::arch::asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags));
     }
 }
+
+/// Read the time stamp counter of the CPU, which counts the cycles since its reset.
+#[must_use]
+#[inline(always)]
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        core::arch::asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack));
+    }
+    u64::from(high) << 32 | u64::from(low)
+}
+
+/// Get a random number from the hardware random number generator of the CPU, which is a
+/// CSPRNG periodically reseeded by an entropy source. Returns `None` if no random number
+/// was available.
+///
+/// # Safety
+/// This function is unsafe because the `RDRAND` instruction must be supported by the CPU,
+/// which is reported by the CPUID leaf 1.
+#[must_use]
+#[inline(always)]
+pub unsafe fn rdrand() -> Option<u64> {
+    let value: u64;
+    let valid: u8;
+    core::arch::asm!("rdrand {}", "setc {}", out(reg) value, out(reg_byte) valid, options(nomem, nostack));
+    (valid != 0).then_some(value)
+}
+
+/// Get a random number directly from the entropy source of the CPU. Returns `None` if no
+/// random number was available, which happens more often than with [`rdrand`].
+///
+/// # Safety
+/// This function is unsafe because the `RDSEED` instruction must be supported by the CPU,
+/// which is reported by the CPUID leaf 7.
+#[must_use]
+#[inline(always)]
+pub unsafe fn rdseed() -> Option<u64> {
+    let value: u64;
+    let valid: u8;
+    core::arch::asm!("rdseed {}", "setc {}", out(reg) value, out(reg_byte) valid, options(nomem, nostack));
+    (valid != 0).then_some(value)
+}
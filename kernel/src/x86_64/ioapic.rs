@@ -0,0 +1,230 @@
+//! The I/O APICs, which replace the 8259 PICs on systems with local APICs. Each
+//! I/O APIC has a number of input pins, connected to consecutive global system
+//! interrupts (GSI) starting at the base given by the MADT. A redirection entry
+//! per pin gives the vector raised by the interrupt, its polarity and trigger
+//! mode and the local APIC it is sent to. The interrupts are acknowledged with
+//! an EOI to the local APIC of the CPU that received them.
+//!
+//! The registers of an I/O APIC are accessed indirectly: the index of the
+//! register is written to the selector, and the register is then read or
+//! written through the data window.
+use crate::{acpi::madt, device::pci::config::HHDM_LIMIT};
+use addr::{phys::Physical, virt::Virtual};
+use bitflags::bitflags;
+
+/// The offsets of the register selector and of the data window.
+const IOREGSEL: usize = 0x00;
+const IOWIN: usize = 0x10;
+
+/// The size of the registers of an I/O APIC.
+const REGISTERS_SIZE: u64 = 0x20;
+
+/// The indexes of the version register and of the first redirection entry.
+/// Each redirection entry is made of two 32-bit registers.
+const VERSION: u32 = 0x01;
+const REDIRECTION_TABLE: u32 = 0x10;
+
+/// The I/O APICs found at boot.
+static IOAPICS: Once<Vec<IoApic>> = Once::new();
+
+bitflags! {
+    /// The flags of a redirection entry. The vector is stored in the bits 0 to
+    /// 7, and the local APIC ID of the destination in the bits 56 to 63. The
+    /// other fields are left to zero: fixed delivery and physical destination.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Entry : u64 {
+        const ACTIVE_LOW = 1 << 13;
+        const LEVEL = 1 << 15;
+        const MASKED = 1 << 16;
+    }
+}
+
+/// Where and how an interrupt is delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Redirection {
+    /// The vector raised by the interrupt.
+    pub vector: u8,
+
+    /// Set if the interrupt is active low, and set if it is level triggered.
+    pub active_low: bool,
+    pub level: bool,
+
+    /// The local APIC ID of the CPU receiving the interrupt.
+    pub destination: u8,
+}
+
+/// An I/O APIC.
+struct IoApic {
+    /// The ID of the I/O APIC.
+    id: u8,
+
+    /// The first GSI handled by the I/O APIC, and the number of its pins.
+    gsi_base: u32,
+    pins: u32,
+
+    /// The registers of the I/O APIC.
+    registers: Spinlock<Registers>,
+}
+
+impl IoApic {
+    /// Returns `true` if the I/O APIC handles the given GSI.
+    fn handles(&self, gsi: u32) -> bool {
+        (self.gsi_base..self.gsi_base + self.pins).contains(&gsi)
+    }
+
+    /// Write the given redirection entry for the given pin, without the risk of
+    /// raising a spurious interrupt with a partially written entry: the entry
+    /// is masked until both of its halves are written.
+    ///
+    /// # Safety
+    /// The entry must not make the I/O APIC raise an interrupt that is not
+    /// handled by the IDT.
+    #[allow(clippy::cast_possible_truncation)]
+    unsafe fn write_entry(&self, pin: u32, entry: u64) {
+        let index = REDIRECTION_TABLE + pin * 2;
+        let registers = self.registers.lock();
+        let low = registers.read(index);
+        registers.write(index, low | Entry::MASKED.bits() as u32);
+        registers.write(index + 1, (entry >> 32) as u32);
+        registers.write(index, entry as u32);
+    }
+
+    /// Read the redirection entry of the given pin.
+    unsafe fn read_entry(&self, pin: u32) -> u64 {
+        let index = REDIRECTION_TABLE + pin * 2;
+        let registers = self.registers.lock();
+        u64::from(registers.read(index)) | u64::from(registers.read(index + 1)) << 32
+    }
+}
+
+/// The registers of an I/O APIC, mapped in the HHDM.
+struct Registers {
+    base: Virtual,
+}
+
+impl Registers {
+    /// Read the register with the given index.
+    ///
+    /// # Safety
+    /// The registers must be those of an I/O APIC.
+    unsafe fn read(&self, index: u32) -> u32 {
+        self.register(IOREGSEL).write_volatile(index);
+        self.register(IOWIN).read_volatile()
+    }
+
+    /// Write the register with the given index.
+    ///
+    /// # Safety
+    /// The registers must be those of an I/O APIC, and writing the register
+    /// must not break the memory safety of the kernel.
+    unsafe fn write(&self, index: u32, value: u32) {
+        self.register(IOREGSEL).write_volatile(index);
+        self.register(IOWIN).write_volatile(value);
+    }
+
+    /// Returns a pointer to the register at the given offset.
+    fn register(&self, offset: usize) -> *mut u32 {
+        self.base
+            .as_mut_ptr::<u32>()
+            .wrapping_add(offset / core::mem::size_of::<u32>())
+    }
+}
+
+/// Find the I/O APICs described by the MADT and mask all their pins. Returns
+/// `false` if there is no usable I/O APIC, in which case the PICs must be used.
+///
+/// # Safety
+/// The MADT must describe the I/O APICs of the system. This function must be
+/// called only once, with interrupts disabled.
+#[init]
+#[allow(clippy::cast_possible_truncation)]
+pub unsafe fn setup() -> bool {
+    let Some(madt) = madt::get() else {
+        return false;
+    };
+
+    let ioapics: Vec<_> = madt
+        .io_apics
+        .iter()
+        .filter(|ioapic| {
+            let mapped = ioapic.address + REGISTERS_SIZE <= HHDM_LIMIT;
+            if !mapped {
+                log::warn!("ioapic: I/O APIC {} is not in the HHDM, ignored", ioapic.id);
+            }
+            mapped
+        })
+        .map(|ioapic| {
+            let registers = Registers {
+                base: Virtual::from(Physical::new(ioapic.address as usize)),
+            };
+            let pins = ((registers.read(VERSION) >> 16) & 0xFF) + 1;
+            IoApic {
+                id: ioapic.id,
+                gsi_base: ioapic.gsi_base,
+                pins,
+                registers: Spinlock::new(registers),
+            }
+        })
+        .collect();
+
+    for ioapic in &ioapics {
+        for pin in 0..ioapic.pins {
+            ioapic.write_entry(pin, Entry::MASKED.bits());
+        }
+        log::info!(
+            "ioapic: I/O APIC {} handles GSI {} to {}",
+            ioapic.id,
+            ioapic.gsi_base,
+            ioapic.gsi_base + ioapic.pins - 1
+        );
+    }
+
+    let found = !ioapics.is_empty();
+    IOAPICS.call_once(|| ioapics);
+    found
+}
+
+/// Returns the I/O APIC handling the given GSI and the pin of the GSI on this
+/// I/O APIC, if any.
+fn find(gsi: u32) -> Option<(&'static IoApic, u32)> {
+    IOAPICS
+        .get()?
+        .iter()
+        .find(|ioapic| ioapic.handles(gsi))
+        .map(|ioapic| (ioapic, gsi - ioapic.gsi_base))
+}
+
+/// Redirect the given GSI as described by the given redirection. The GSI is
+/// masked until it is unmasked with [`set_masked`]. Returns `false` if no I/O
+/// APIC handles the GSI.
+///
+/// # Safety
+/// The vector of the redirection must be handled by the IDT.
+#[must_use]
+pub unsafe fn redirect(gsi: u32, redirection: &Redirection) -> bool {
+    let Some((ioapic, pin)) = find(gsi) else {
+        return false;
+    };
+
+    let mut flags = Entry::MASKED;
+    flags.set(Entry::ACTIVE_LOW, redirection.active_low);
+    flags.set(Entry::LEVEL, redirection.level);
+    let entry =
+        flags.bits() | u64::from(redirection.vector) | u64::from(redirection.destination) << 56;
+    ioapic.write_entry(pin, entry);
+    true
+}
+
+/// Mask or unmask the given GSI. A masked GSI never raises an interrupt. This
+/// does nothing if no I/O APIC handles the GSI.
+///
+/// # Safety
+/// The GSI must have been redirected to a vector handled by the IDT before
+/// being unmasked.
+pub unsafe fn set_masked(gsi: u32, masked: bool) {
+    if let Some((ioapic, pin)) = find(gsi) {
+        let mut entry = Entry::from_bits_retain(ioapic.read_entry(pin));
+        entry.set(Entry::MASKED, masked);
+        ioapic.write_entry(pin, entry.bits());
+    }
+}
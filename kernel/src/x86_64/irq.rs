@@ -1,13 +1,18 @@
 use super::{
     cpu::InterruptFrame,
-    idt, instruction, lapic,
+    idt, instruction,
+    ioapic::{self, Redirection},
+    lapic,
     pic::{self, IRQ_BASE},
-    pit,
+    pit, smp,
 };
 use crate::{
+    acpi::madt::{self, Polarity, Trigger},
+    random,
     time::timer,
     user::scheduler::{Scheduler, SCHEDULER},
 };
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// The IPI vector used to inform the CPU that a new timer tick is available.
 const CLOCK_VECTOR: u8 = 0x7E;
@@ -15,8 +20,50 @@ const CLOCK_VECTOR: u8 = 0x7E;
 /// The IRQ number of the PIT.
 const PIT_IRQ: u8 = 0;
 
-/// The IRQ number of the keyboard.
-const KEYBOARD_IRQ: u8 = 1;
+/// The number of IRQs of the ISA bus. They are connected to the PICs, or to the
+/// I/O APICs as described by the interrupt source overrides of the MADT.
+const ISA_IRQS: u8 = 16;
+
+/// The number of IRQs, each raising the vector `IRQ_BASE + irq`. The first 16
+/// IRQs are the ISA IRQs, and the following ones are the GSIs with the same
+/// number, only available with the I/O APICs.
+#[allow(clippy::cast_possible_truncation)]
+pub const IRQ_COUNT: u8 = IRQ_STUBS.len() as u8;
+
+/// A handler of an IRQ, called with the number of the IRQ raised.
+pub type Handler = fn(u8);
+
+/// The handlers of the IRQs requested by the drivers, with the IRQ they handle.
+/// Several handlers can share the same IRQ, and are all called when it is raised.
+static HANDLERS: Spinlock<Vec<(u8, Handler)>> = Spinlock::new(Vec::new());
+
+/// The local APIC ID of the CPU receiving each requested IRQ.
+static DESTINATIONS: Spinlock<[Option<u8>; IRQ_COUNT as usize]> =
+    Spinlock::new([None; IRQ_COUNT as usize]);
+
+/// Set when the IRQs are routed through the I/O APICs instead of the PICs.
+static IOAPIC: AtomicBool = AtomicBool::new(false);
+
+/// The CPU receiving an IRQ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Affinity {
+    /// The CPU that booted the kernel.
+    Bsp,
+
+    /// The CPU with the given local APIC ID.
+    Cpu(u32),
+}
+
+/// An error returned when requesting an IRQ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestError {
+    /// The IRQ does not exist, is reserved by the kernel, or is not connected
+    /// to any interrupt controller.
+    InvalidIrq,
+
+    /// The CPU of the affinity does not exist, or cannot receive IRQs.
+    InvalidCpu,
+}
 
 /// Install the IRQ handlers.
 ///
@@ -26,25 +73,43 @@ const KEYBOARD_IRQ: u8 = 1;
 /// an interrupt.
 #[init]
 pub unsafe fn install() {
-    idt::register_interruption(IRQ_BASE, irq_0);
-    idt::register_interruption(IRQ_BASE + 1, irq_1);
-    idt::register_interruption(IRQ_BASE + 2, irq_2);
-    idt::register_interruption(IRQ_BASE + 3, irq_3);
-    idt::register_interruption(IRQ_BASE + 4, irq_4);
-    idt::register_interruption(IRQ_BASE + 5, irq_5);
-    idt::register_interruption(IRQ_BASE + 6, irq_6);
-    idt::register_interruption(IRQ_BASE + 7, irq_7);
-    idt::register_interruption(IRQ_BASE + 8, irq_8);
-    idt::register_interruption(IRQ_BASE + 9, irq_9);
-    idt::register_interruption(IRQ_BASE + 10, irq_10);
-    idt::register_interruption(IRQ_BASE + 11, irq_11);
-    idt::register_interruption(IRQ_BASE + 12, irq_12);
-    idt::register_interruption(IRQ_BASE + 13, irq_13);
-    idt::register_interruption(IRQ_BASE + 14, irq_14);
-    idt::register_interruption(IRQ_BASE + 15, irq_15);
+    for (irq, stub) in (0..IRQ_COUNT).zip(IRQ_STUBS) {
+        idt::register_interruption(IRQ_BASE + irq, stub);
+    }
     idt::register_interruption(CLOCK_VECTOR, clock_handler);
 }
 
+/// Route the IRQs through the I/O APICs described by the MADT and mask the PICs
+/// or, if there is no I/O APIC, keep using the PICs. The IRQ of the PIT is then
+/// unmasked and received by the BSP.
+///
+/// # Safety
+/// This function must be called only once, after the ACPI tables were found,
+/// and with interrupts disabled.
+#[init]
+pub unsafe fn setup() {
+    if ioapic::setup() {
+        pic::mask_all();
+        IOAPIC.store(true, Ordering::Relaxed);
+
+        // Route the IRQs requested before the switch to the I/O APICs
+        let destinations = *DESTINATIONS.lock();
+        for (irq, destination) in (0..IRQ_COUNT).zip(destinations) {
+            if destination.is_some_and(|destination| !route(irq, destination)) {
+                log::warn!("irq: IRQ {} is not connected to an I/O APIC", irq);
+            }
+        }
+        log::info!("irq: using the I/O APICs");
+    } else {
+        log::info!("irq: no I/O APIC, using the 8259 PICs");
+    }
+
+    let bsp = destination(Affinity::Bsp).expect("The BSP cannot receive IRQs");
+    if !route(PIT_IRQ, bsp) {
+        log::warn!("irq: the IRQ of the PIT is not connected");
+    }
+}
+
 /// Disables interrupts on the current core.
 ///
 /// # Safety
@@ -132,29 +197,205 @@ where
     }
 }
 
+/// Request the given IRQ: the given handler is called with the IRQ number each
+/// time it is raised, on the CPU given by the affinity, and the IRQ is unmasked.
+/// A handler sharing its IRQ with other devices must check that its device
+/// raised the interrupt, and a shared IRQ keeps the affinity of its first
+/// request. With the PICs, all IRQs are received by the BSP and the affinity is
+/// only used if the I/O APICs are used later.
+///
+/// The IRQs below 16 are the ISA IRQs, routed to the I/O APICs as described by
+/// the MADT, and the following IRQs are the GSIs with the same number.
+///
+/// # Errors
+/// Returns `RequestError::InvalidIrq` if the IRQ does not exist, is used by the
+/// kernel or is not connected to an interrupt controller, and returns
+/// `RequestError::InvalidCpu` if the CPU of the affinity does not exist.
+pub fn request(irq: u8, handler: Handler, affinity: Affinity) -> Result<(), RequestError> {
+    if irq >= IRQ_COUNT || irq == PIT_IRQ {
+        return Err(RequestError::InvalidIrq);
+    }
+    let destination = destination(affinity).ok_or(RequestError::InvalidCpu)?;
+
+    without(|| {
+        let mut destinations = DESTINATIONS.lock();
+        if destinations[usize::from(irq)].is_none() {
+            if !unsafe { route(irq, destination) } {
+                return Err(RequestError::InvalidIrq);
+            }
+            destinations[usize::from(irq)] = Some(destination);
+        }
+        HANDLERS.lock().push((irq, handler));
+        Ok(())
+    })
+}
+
+/// Mask the given IRQ: it will not be raised until it is unmasked.
+pub fn mask(irq: u8) {
+    unsafe {
+        set_masked(irq, true);
+    }
+}
+
+/// Unmask the given IRQ, previously masked with [`mask`]. This does nothing if
+/// the IRQ was not requested.
+pub fn unmask(irq: u8) {
+    let requested = without(|| {
+        DESTINATIONS
+            .lock()
+            .get(usize::from(irq))
+            .is_some_and(Option::is_some)
+    });
+    if requested {
+        unsafe {
+            set_masked(irq, false);
+        }
+    }
+}
+
+/// Returns the local APIC ID of the CPU of the given affinity, or `None` if the
+/// CPU does not exist or cannot be the destination of an IRQ.
+fn destination(affinity: Affinity) -> Option<u8> {
+    let id = match affinity {
+        Affinity::Bsp => smp::bsp_lapic_id(),
+        Affinity::Cpu(id) => {
+            let exists = madt::get().map_or(id == smp::bsp_lapic_id(), |madt| {
+                madt.processors
+                    .iter()
+                    .any(|cpu| cpu.apic_id == id && (cpu.enabled || cpu.online_capable))
+            });
+            exists.then_some(id)?
+        }
+    };
+
+    // The destination of a redirection entry is only 8 bits wide
+    u8::try_from(id).ok()
+}
+
+/// Returns the GSI to which the given IRQ is connected, with its polarity and
+/// trigger mode, or `None` if the IRQ is not connected to an I/O APIC.
+fn gsi(irq: u8) -> Option<(u32, bool, bool)> {
+    let madt = madt::get()?;
+    let (gsi, polarity, trigger) = if irq < ISA_IRQS {
+        // An ISA IRQ without override whose GSI is used by another ISA IRQ is not
+        // connected, as the IRQ 2 when the PIT is connected to the GSI 2.
+        let route = madt.isa_interrupt(irq);
+        let taken = madt
+            .overrides
+            .iter()
+            .any(|other| other.source != irq && other.gsi == route.gsi);
+        if taken {
+            return None;
+        }
+        (route.gsi, route.polarity, route.trigger)
+    } else {
+        (u32::from(irq), Polarity::Conforming, Trigger::Conforming)
+    };
+
+    // The ISA IRQs are edge triggered and active high, while the PCI IRQs
+    // connected to the other GSIs are level triggered and active low.
+    let active_low = match polarity {
+        Polarity::Conforming => irq >= ISA_IRQS,
+        Polarity::ActiveLow => true,
+        Polarity::ActiveHigh => false,
+    };
+    let level = match trigger {
+        Trigger::Conforming => irq >= ISA_IRQS,
+        Trigger::Level => true,
+        Trigger::Edge => false,
+    };
+    Some((gsi, active_low, level))
+}
+
+/// Connect the given IRQ to its vector on the CPU with the given local APIC ID,
+/// and unmask it. Returns `false` if the IRQ is not connected to the interrupt
+/// controller in use.
+///
+/// # Safety
+/// The vector of the IRQ must be handled by the IDT.
+unsafe fn route(irq: u8, destination: u8) -> bool {
+    if !IOAPIC.load(Ordering::Relaxed) {
+        if irq >= ISA_IRQS {
+            return false;
+        }
+        pic::unmask(irq);
+        return true;
+    }
+
+    let Some((gsi, active_low, level)) = gsi(irq) else {
+        return false;
+    };
+    let redirection = Redirection {
+        vector: IRQ_BASE + irq,
+        active_low,
+        level,
+        destination,
+    };
+    if !ioapic::redirect(gsi, &redirection) {
+        return false;
+    }
+    ioapic::set_masked(gsi, false);
+    true
+}
+
+/// Mask or unmask the given IRQ on the interrupt controller in use.
+///
+/// # Safety
+/// An IRQ must be routed to its vector before being unmasked.
+unsafe fn set_masked(irq: u8, masked: bool) {
+    if IOAPIC.load(Ordering::Relaxed) {
+        if let Some((gsi, ..)) = gsi(irq) {
+            ioapic::set_masked(gsi, masked);
+        }
+    } else if masked {
+        pic::mask(irq);
+    } else {
+        pic::unmask(irq);
+    }
+}
+
+/// Send an EOI for the given IRQ to the interrupt controller in use: the local
+/// APIC of the current CPU with the I/O APICs, or the PICs.
+///
+/// # Safety
+/// This function must only be called at the end of the handling of the IRQ.
+unsafe fn send_eoi(irq: u8) {
+    if IOAPIC.load(Ordering::Relaxed) {
+        lapic::send_eoi();
+    } else {
+        pic::send_eoi(irq);
+    }
+}
+
 /// The IRQ manager. This function is called by the IRQ handlers after they have saved the CPU
 /// state, and passed the state to this function. The IRQ triggered is passed as an argument in
-/// the `code` field of the `state` argument.
-#[irq_handler]
+/// the `code` field of the `state` argument. The EOI is sent after the handlers
+/// have run, so that a level triggered IRQ is not raised again before its device
+/// was serviced.
+#[irq_handler(48)]
 unsafe fn irq_handler(state: &mut InterruptFrame) {
     let irq = (state.code & 0xFF) as u8;
-    pic::send_eoi(irq);
-    match irq {
-        PIT_IRQ => {
-            pit::timer_tick();
-            timer::tick();
-            lapic::send_ipi(
-                lapic::IpiDestination::All,
-                lapic::IpiPriority::Normal,
-                CLOCK_VECTOR,
-            );
+    random::add_interrupt(irq);
+    if irq == PIT_IRQ {
+        pit::timer_tick();
+        timer::tick();
+        lapic::send_ipi(
+            lapic::IpiDestination::All,
+            lapic::IpiPriority::Normal,
+            CLOCK_VECTOR,
+        );
+    } else {
+        let handlers = HANDLERS.lock();
+        let mut handled = false;
+        for (_, handler) in handlers.iter().filter(|(line, _)| *line == irq) {
+            handler(irq);
+            handled = true;
         }
-
-        KEYBOARD_IRQ => {
-            log::info!("Keyboard IRQ");
+        if !handled {
+            log::error!("Unhandled IRQ: {}", state.code);
         }
-        _ => log::error!("Unhandled IRQ: {}", state.code),
     }
+    send_eoi(irq);
 }
 
 /// The clock handler. This function is for each CPU by the PIT interrupt handler, and is
@@ -6,10 +6,12 @@ pub mod cmos;
 pub mod cpu;
 pub mod exception;
 pub mod fpu;
+pub mod i8042;
 pub mod gdt;
 pub mod idt;
 pub mod instruction;
 pub mod io;
+pub mod ioapic;
 pub mod irq;
 pub mod lapic;
 pub mod msr;
@@ -56,8 +58,7 @@ pub unsafe fn early_setup() {
 /// features that need the use of `unsafe` code to work properly.
 #[init]
 pub unsafe fn setup() {
-    // Assume that the BSP is CPU 0
-    smp::per_cpu_setup(0);
+    smp::per_cpu_setup(smp::bsp_lapic_id());
 
     tss::install();
     paging::setup();
@@ -182,7 +182,8 @@ pub fn resolve(root: &PageTableRoot, address: Virtual) -> Option<Physical> {
 /// Even if only an subset of the page table is specified, the page table will be still
 /// deallocated by the function: specify an subset of the page table only prevents the function
 /// from deallocating the frames recursively in the non specified part of the page table (see
-/// the `Drop` implementation of `PageTableRoot` for more details).
+/// the `Drop` implementation of `PageTableRoot` for more details). The frames of the pages that
+/// map device memory are not deallocated, since they do not belong to the frame allocator.
 ///
 /// # Safety
 /// This function is unsafe because the caller must ensure that the page table is not used
@@ -190,12 +191,13 @@ pub fn resolve(root: &PageTableRoot, address: Virtual) -> Option<Physical> {
 unsafe fn deallocate_recursive(table: &mut [PageEntry], level: Level) {
     table
         .iter()
-        .filter_map(PageEntry::address)
-        .for_each(|address| match level {
+        .filter_map(|entry| Some((entry.address()?, entry.flags())))
+        .for_each(|(address, flags)| match level {
             Level::Pml4 | Level::Pdpt | Level::Pd => {
                 let table = PageTable::from_page_mut(Virtual::from(address));
                 deallocate_recursive(table, level.next().unwrap());
             }
+            Level::Pt if flags.contains(PageEntryFlags::DEVICE) => {}
             Level::Pt => {
                 FRAME_ALLOCATOR.lock().deallocate_frame(Frame::new(address));
             }
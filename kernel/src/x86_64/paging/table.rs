@@ -58,7 +58,11 @@ bitflags::bitflags! {
         /// modified. This is often used for kernel pages, and can improves performance.
         const GLOBAL = 1 << 8;
 
-        const BIT_9  = 1 << 9;
+        /// Ignored by the processor. If set, the page maps device memory, like the framebuffer,
+        /// whose frames do not belong to the frame allocator and must never be deallocated when
+        /// the page is unmapped or when the page table is dropped.
+        const DEVICE = 1 << 9;
+
         const BIT_10 = 1 << 10;
         const BIT_11 = 1 << 11;
         const BIT_52 = 1 << 52;
@@ -21,11 +21,14 @@ pub const IRQ_COUNT: usize = 16;
 /// The end-of-interrupt (EOI) command for the PICs.
 const PIC_EOI: u8 = 0x20;
 
+/// The IRQ of the master PIC to which the slave PIC is connected.
+const CASCADE_IRQ: u8 = 2;
+
 /// Remap the PICs from their default IRQs (0-15) to the given base IRQ. This is necessary because
 /// the default IRQs conflict with the CPU exceptions, which are the first 32 IRQs (0-31). The
 /// master PIC will use IRQs [base, base + 7] and the slave PIC will use IRQs [base + 8, base + 15].
-/// After remapping, all interrupts are unmasked, but no interrupts will occur until the interrupts
-/// are enabled with the `sti` instruction.
+/// After remapping, all interrupts are masked except the cascade line of the slave PIC, and each
+/// IRQ is unmasked when a handler is requested for it.
 ///
 /// # Safety
 /// This function is unsafe because it writes to the PICs with I/O ports, which can cause undefined
@@ -48,8 +51,9 @@ pub unsafe fn remap() {
     MASTER_PIC_DATA.write_and_pause(0x01);
     SLAVE_PIC_DATA.write_and_pause(0x01);
 
-    // OCW1: Enable all interrupts
-    unmask_all();
+    // OCW1: Mask all interrupts except the cascade line
+    mask_all();
+    unmask(CASCADE_IRQ);
 }
 
 /// Check if the given IRQ number is in the range of the PICs. This is useful for checking if an
@@ -86,7 +90,42 @@ pub unsafe fn send_eoi(irq: u8) {
     }
 }
 
-/// Unmask all interrupts on the PICs. This is the default state after remapping the PICs.
+/// Mask the given IRQ on the PICs. If the IRQ number is not in the range of the PICs, this
+/// function does nothing.
+///
+/// # Safety
+/// This function is unsafe because it writes to the PICs with I/O ports, which can cause undefined
+/// behavior if the PICs do not exist or are not in the expected state.
+pub unsafe fn mask(irq: u8) {
+    if let Some((port, bit)) = line(irq) {
+        port.write_and_pause(port.read() | 1 << bit);
+    }
+}
+
+/// Unmask the given IRQ on the PICs. If the IRQ number is not in the range of the PICs, this
+/// function does nothing.
+///
+/// # Safety
+/// This function is unsafe because it writes to the PICs with I/O ports, which can cause undefined
+/// behavior if the PICs do not exist or are not in the expected state, or if the PICs raise an
+/// interrupt that is not handled by the IDT.
+pub unsafe fn unmask(irq: u8) {
+    if let Some((port, bit)) = line(irq) {
+        port.write_and_pause(port.read() & !(1 << bit));
+    }
+}
+
+/// Returns the data port of the PIC handling the given IRQ and the bit of the IRQ in its mask
+/// register, or `None` if the IRQ is not in the range of the PICs.
+fn line(irq: u8) -> Option<(&'static Port<u8>, u8)> {
+    match irq {
+        0..=7 => Some((&MASTER_PIC_DATA, irq)),
+        8..=15 => Some((&SLAVE_PIC_DATA, irq - IRQ_PER_PIC)),
+        _ => None,
+    }
+}
+
+/// Unmask all interrupts on the PICs.
 ///
 /// # Safety
 /// This function is unsafe because it writes to the PICs with I/O ports, which can cause undefined
@@ -1,10 +1,18 @@
 use super::io;
+use bitflags::bitflags;
+
+/// The frequency of the clock of the UART divided by 16, which is the fastest baud rate that can
+/// be used. The baud rate is selected by dividing this frequency by a 16-bit divisor.
+pub const MAX_BAUD_RATE: u32 = 115_200;
+
+/// The size of the transmit and receive FIFOs of a 16550 UART.
+pub const FIFO_SIZE: usize = 16;
 
 /// Represents a serial port. Currently, only COM1-4 are supported, and are statically mapped to
 /// their respective addresses. This should be a safe assumption, as most x86-64 systems try to
 /// keep compatibility with the original IBM PC. Furthermore, the serial ports are only used for
 /// debugging purposes, and are not required for the kernel to function properly.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Port {
     COM1 = 0x3F8,
     COM2 = 0x2F8,
@@ -12,11 +20,110 @@ pub enum Port {
     COM4 = 0x2E8,
 }
 
+impl Port {
+    /// All the serial ports, in order.
+    pub const ALL: [Port; 4] = [Port::COM1, Port::COM2, Port::COM3, Port::COM4];
+
+    /// Returns the IRQ raised by the serial port. COM1 and COM3 share IRQ 4, and COM2 and COM4
+    /// share IRQ 3.
+    #[must_use]
+    pub const fn irq(self) -> u8 {
+        match self {
+            Port::COM1 | Port::COM3 => 4,
+            Port::COM2 | Port::COM4 => 3,
+        }
+    }
+}
+
+/// The parity bit sent after the data bits of each character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+/// The configuration of the line of a serial port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Config {
+    /// The speed of the line, in bits per second. It must divide [`MAX_BAUD_RATE`], otherwise
+    /// the nearest slower speed is used.
+    pub baud_rate: u32,
+
+    /// The number of data bits of each character, between 5 and 8.
+    pub data_bits: u8,
+
+    /// The parity bit of each character.
+    pub parity: Parity,
+
+    /// Use two stop bits instead of one (or one and a half with 5 data bits).
+    pub two_stop_bits: bool,
+}
+
+impl Config {
+    /// The configuration used when a serial port is initialized: 38400 bauds, 8 data bits, no
+    /// parity and one stop bit.
+    pub const DEFAULT: Config = Config {
+        baud_rate: 38400,
+        data_bits: 8,
+        parity: Parity::None,
+        two_stop_bits: false,
+    };
+
+    /// Returns the value of the divisor latch for the baud rate of this configuration.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn divisor(&self) -> u16 {
+        (MAX_BAUD_RATE / self.baud_rate.clamp(1, MAX_BAUD_RATE)).min(0xFFFF) as u16
+    }
+
+    /// Returns the value of the line control register for the framing of this configuration.
+    #[must_use]
+    pub fn line_control(&self) -> u8 {
+        let mut lcr = self.data_bits.clamp(5, 8) - 5;
+        if self.two_stop_bits {
+            lcr |= 0x04;
+        }
+        match self.parity {
+            Parity::None => {}
+            Parity::Odd => lcr |= 0x08,
+            Parity::Even => lcr |= 0x18,
+        }
+        lcr
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+bitflags! {
+    /// The interrupts that a serial port can raise.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Interrupts : u8 {
+        /// Raised when some data has been received.
+        const RECEIVE = 1 << 0;
+
+        /// Raised when the transmit FIFO becomes empty.
+        const TRANSMIT = 1 << 1;
+
+        /// Raised when an error is detected on the line.
+        const LINE_STATUS = 1 << 2;
+
+        /// Raised when the state of the modem lines changes.
+        const MODEM_STATUS = 1 << 3;
+    }
+}
+
 /// Represents a serial channel. This is used to interact with a serial port safely.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Serial {
     data: io::Port<u8>,
     interrupt_enable: io::Port<u8>,
+    /// The FIFO control register when written, and the interrupt identification register when
+    /// read.
     fifo_control: io::Port<u8>,
     line_control: io::Port<u8>,
     modem_control: io::Port<u8>,
@@ -26,9 +133,10 @@ pub struct Serial {
 }
 
 impl Serial {
-    /// Create a new serial channel and initialize the serial port. Currently, serial port are only
-    /// used for debugging using QEMU's serial port, and this function even required to print
-    /// anything to the QEMU console, so this function probably doesn't work on real hardware.
+    /// Create a new serial channel and initialize the serial port with the default
+    /// configuration (see [`Config::DEFAULT`]). Currently, serial port are only used for
+    /// debugging using QEMU's serial port, and this function even required to print anything to
+    /// the QEMU console, so this function probably doesn't work on real hardware.
     ///
     /// # Safety
     /// This function is unsafe because it writes to the serial port, which could cause a undefined
@@ -49,16 +157,86 @@ impl Serial {
         };
 
         serial.interrupt_enable.write(0x00);
-        serial.line_control.write(0x80);
-        serial.data.write(0x03);
-        serial.interrupt_enable.write(0x00);
-        serial.line_control.write(0x03);
+        serial.configure(Config::DEFAULT);
         serial.fifo_control.write(0xC7);
         serial.modem_control.write(0x0B);
         // We don't test if the line is ready to be written to here (I'm lazy)
         serial
     }
 
+    /// Check if the serial port really exists, by sending a byte in loopback mode and checking
+    /// that the same byte is received, a missing port usually reading as `0xFF`. The port is
+    /// then put back in its normal mode. This should be called before enabling the interrupts of
+    /// the port, since the received byte could be stolen by the interrupt handler.
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        // SAFETY: This is safe because the loopback mode disconnects the port from the line and
+        // from its IRQ while testing.
+        unsafe {
+            self.modem_control.write(0x1E);
+            self.data.write(0xAE);
+            let mut timeout = 1000;
+            while !self.data_pending() && timeout > 0 {
+                core::hint::spin_loop();
+                timeout -= 1;
+            }
+            let found = self.data_pending() && self.data.read() == 0xAE;
+            self.modem_control.write(0x0B);
+            found
+        }
+    }
+
+    /// Set the baud rate and the framing of the serial port. This should be done while the port
+    /// is idle, because the characters being sent or received could be garbled.
+    pub fn configure(&self, config: Config) {
+        let [low, high] = config.divisor().to_le_bytes();
+
+        // SAFETY: This is safe because the divisor latch is only selected while the divisor is
+        // written, and the interrupt enable register is therefore not modified.
+        unsafe {
+            self.line_control.write(0x80);
+            self.data.write(low);
+            self.interrupt_enable.write(high);
+            self.line_control.write(config.line_control());
+        }
+    }
+
+    /// Select the interrupts raised by the serial port. All interrupts are disabled when the
+    /// serial port is initialized.
+    pub fn set_interrupts(&self, interrupts: Interrupts) {
+        // SAFETY: This is safe because enabling the interrupts of the serial port only makes it
+        // raise its IRQ, which must have a handler.
+        unsafe {
+            self.interrupt_enable.write(interrupts.bits());
+        }
+    }
+
+    /// Returns the interrupts currently enabled on the serial port.
+    #[must_use]
+    pub fn interrupts(&self) -> Interrupts {
+        // SAFETY: This is safe because reading the interrupt enable register
+        // should not cause any side effects nor undefined behavior.
+        Interrupts::from_bits_truncate(unsafe { self.interrupt_enable.read() })
+    }
+
+    /// Check if the serial port has an interrupt pending. Reading the interrupt identification
+    /// register acknowledges the transmit interrupt, which is raised again when the transmit FIFO
+    /// becomes empty.
+    #[must_use]
+    pub fn interrupt_pending(&self) -> bool {
+        // SAFETY: See above, the only side effect is the acknowledgement of the transmit
+        // interrupt, which the caller expects.
+        unsafe { self.fifo_control.read() & 0x01 == 0 }
+    }
+
+    /// Discard the data in the receive and transmit FIFOs.
+    pub fn clear_fifos(&self) {
+        // SAFETY: This is safe because clearing the FIFOs only discards data.
+        unsafe {
+            self.fifo_control.write(0xC7);
+        }
+    }
+
     /// Check if the serial port is ready to be written to.
     #[must_use]
     pub fn is_transmit_empty(&self) -> bool {
@@ -75,31 +253,44 @@ impl Serial {
         unsafe { self.line_status.read() & 0x01 != 0 }
     }
 
+    /// Write a byte to the serial port without waiting. When the transmit FIFO is empty (see
+    /// [`Serial::is_transmit_empty`]), up to [`FIFO_SIZE`] bytes can be written at once; the
+    /// bytes written when the FIFO is full are lost.
+    pub fn send(&self, byte: u8) {
+        // SAFETY: This is safe because writing to the serial port should not
+        // cause any side effects nor undefined behavior.
+        unsafe {
+            self.data.write(byte);
+        }
+    }
+
+    /// Read a byte from the serial port without waiting, if there is data to be read.
+    #[must_use]
+    pub fn receive(&self) -> Option<u8> {
+        // SAFETY: This is safe because reading from the serial port should not
+        // cause any side effects nor undefined behavior.
+        self.data_pending().then(|| unsafe { self.data.read() })
+    }
+
     /// Write a byte to the serial port. If the serial port is not ready to be written to, this
     /// function will block until it is.
     pub fn write(&self, byte: u8) {
         while !self.is_transmit_empty() {
             core::hint::spin_loop();
         }
-
-        // SAFETY: This is safe because writing to the serial port should not
-        // cause any side effects nor undefined behavior.
-        unsafe {
-            self.data.write(byte);
-        }
+        self.send(byte);
     }
 
     /// Read a byte from the serial port. If there is no data to be read, this function will
     /// block until there is.
     #[must_use]
     pub fn read(&self) -> u8 {
-        while !self.data_pending() {
+        loop {
+            if let Some(byte) = self.receive() {
+                return byte;
+            }
             core::hint::spin_loop();
         }
-
-        // SAFETY: This is safe because reading from the serial port should not
-        // cause any side effects nor undefined behavior.
-        unsafe { self.data.read() }
     }
 }
 
@@ -51,11 +51,13 @@ pub unsafe fn start_cpus() {
     assert!(reponse.cpus().len() <= MAX_CPUS, "Too many core found");
     assert!(!reponse.cpus().is_empty(), "No core found");
 
-    // Start the APs
+    // Start the APs. The BSP is not always the CPU with the LAPIC ID 0, so
+    // use the ID given by Limine to skip it.
+    let bsp = bsp_lapic_id();
     reponse
         .cpus()
         .iter()
-        .filter(|cpu| cpu.lapic_id != 0)
+        .filter(|cpu| cpu.lapic_id != bsp)
         .for_each(|cpu| {
             cpu.goto_address.write(ap_start);
         });
@@ -106,6 +108,15 @@ pub fn ap_booted() -> bool {
     AP_BOOTED.load(Ordering::Relaxed)
 }
 
+/// Return the LAPIC ID of the BSP, as given by Limine, or 0 if Limine did not give
+/// us the list of the CPUs.
+#[must_use]
+pub fn bsp_lapic_id() -> u32 {
+    LIMINE_SMP
+        .get_response()
+        .map_or(0, limine::response::SmpResponse::bsp_lapic_id)
+}
+
 /// Return the ID of the current core.
 #[must_use]
 pub fn core_id() -> u32 {
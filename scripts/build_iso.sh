@@ -40,8 +40,18 @@ else
     die "No kernel executable found"
 fi
 
-# Create the ISO
-xorriso -as mkisofs -b boot/limine-bios-cd.bin			  \
+# Create the initramfs archive from the `initramfs` directory. If there is no such
+# directory, an empty archive is created since Limine refuses to boot when a module
+# is missing.
+if [ -d initramfs ]; then
+    (cd initramfs && find . | cpio --quiet -o -H newc) > iso/boot/initramfs.cpio
+else
+    cpio --quiet -o -H newc < /dev/null > iso/boot/initramfs.cpio
+fi
+
+# Create the ISO, with Rock Ridge and Joliet names so that it can be mounted
+# by the kernel once booted
+xorriso -as mkisofs -R -J -b boot/limine-bios-cd.bin	  \
 		-no-emul-boot -boot-load-size 4 -boot-info-table 	\
 		--efi-boot boot/limine-uefi-cd.bin 					      \
 		-efi-boot-part --efi-boot-image  					        \
@@ -8,7 +8,7 @@ die() {
     || die "you must run this script from the root of the repository"
 
 qemu-system-x86_64 -m 128                                   \
-    -drive format=raw,media=cdrom,file=bin/helium.iso       \
+    -drive format=raw,media=cdrom,index=2,file=bin/helium.iso \
     -device isa-debug-exit                                  \
     -rtc base=localtime                                     \
     -no-reboot                                              \
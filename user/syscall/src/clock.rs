@@ -1,6 +1,13 @@
 use super::{syscall_return, Errno, Syscall};
 
+/// The clock of the date, which can be set.
+pub const CLOCK_REALTIME: usize = 0;
+
+/// The clock of the time since the boot, which cannot be set.
+pub const CLOCK_MONOTONIC: usize = 1;
+
 /// The timespec struct is used to represent time in seconds and nanoseconds.
+#[derive(Debug, Clone, Default)]
 #[repr(C)]
 pub struct Timespec {
     pub seconds: u64,
@@ -60,3 +67,63 @@ pub fn get_time() -> Result<Timespec, GetTimeError> {
         Ok(_) => Ok(timespec),
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum SetTimeError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The given address for the timespec is not a valid address.
+    BadAddress,
+
+    /// The given clock does not exist.
+    InvalidClock,
+
+    /// The given clock cannot be set.
+    NotSettable,
+
+    /// The given time is not a valid time.
+    InvalidTime,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for SetTimeError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Set the given clock to the given time. Only `CLOCK_REALTIME` can be set,
+/// and the new date is also written to the real-time clock of the computer.
+///
+/// # Errors
+/// - `SetTimeError::BadAddress`: The given address for the timespec is not a valid address.
+/// - `SetTimeError::InvalidClock`: The given clock does not exist.
+/// - `SetTimeError::NotSettable`: The given clock cannot be set.
+/// - `SetTimeError::InvalidTime`: The number of nanoseconds is greater than a second, or the
+/// date is after the year 2099.
+pub fn set_time(clock: usize, time: &Timespec) -> Result<(), SetTimeError> {
+    let ret: usize;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::ClockSetTime as u64,
+            in("rsi") clock,
+            in("rdx") time as *const Timespec as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(SetTimeError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
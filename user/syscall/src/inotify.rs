@@ -0,0 +1,238 @@
+use super::{syscall_return, vfs::FileDescriptor, Errno, Syscall, SyscallString};
+
+/// Reading an instance without pending events fails instead of blocking.
+pub const IN_NONBLOCK: usize = 0x800;
+
+/// The file was modified.
+pub const IN_MODIFY: u32 = 0x0000_0002;
+
+/// A file opened for writing was closed.
+pub const IN_CLOSE_WRITE: u32 = 0x0000_0008;
+
+/// An entry was renamed from the watched directory.
+pub const IN_MOVED_FROM: u32 = 0x0000_0040;
+
+/// An entry was renamed into the watched directory.
+pub const IN_MOVED_TO: u32 = 0x0000_0080;
+
+/// An entry was created in the watched directory.
+pub const IN_CREATE: u32 = 0x0000_0100;
+
+/// An entry was deleted from the watched directory.
+pub const IN_DELETE: u32 = 0x0000_0200;
+
+/// The event queue overflowed and events were lost.
+pub const IN_Q_OVERFLOW: u32 = 0x0000_4000;
+
+/// The watch was removed.
+pub const IN_IGNORED: u32 = 0x0000_8000;
+
+/// Only watch the path if it is a directory.
+pub const IN_ONLYDIR: u32 = 0x0100_0000;
+
+/// Add the events to the mask of an existing watch instead of replacing it.
+pub const IN_MASK_ADD: u32 = 0x2000_0000;
+
+/// The subject of the event is a directory.
+pub const IN_ISDIR: u32 = 0x4000_0000;
+
+/// Remove the watch after its first event.
+pub const IN_ONESHOT: u32 = 0x8000_0000;
+
+/// The header of an event read from an inotify instance, with the same layout as
+/// the Linux `inotify_event` structure. It is followed by `len` bytes containing
+/// the null-padded name of the entry the event is about, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Event {
+    /// The watch descriptor of the watch that reported the event.
+    pub wd: i32,
+
+    /// The event that occurred.
+    pub mask: u32,
+
+    /// The cookie shared by the two events of the same rename.
+    pub cookie: u32,
+
+    /// The length of the name following this header.
+    pub len: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum InitError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// Unknown flags were passed as an argument
+    InvalidFlags,
+
+    /// The task has too many files open
+    TooManyFilesOpen,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for InitError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Create a new inotify instance and return a file descriptor referring to it.
+/// Events are retrieved by reading the file descriptor.
+///
+/// # Errors
+/// See [`InitError`] for a list of possible errors.
+pub fn init1(flags: usize) -> Result<FileDescriptor, InitError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::InotifyInit1 as u64,
+            in("rsi") flags as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(InitError::from(errno)),
+        Ok(fd) => Ok(FileDescriptor(fd)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum AddWatchError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The path passed as an argument is at an invalid address
+    BadAddress,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The file descriptor does not refer to an inotify instance
+    NotAnInstance,
+
+    /// The mask contains unknown flags or does not contain any event
+    InvalidMask,
+
+    /// The path is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// The path is invalid
+    InvalidPath,
+
+    /// The path is too long
+    PathTooLong,
+
+    /// The path does not exist
+    NoSuchEntry,
+
+    /// A component of the path is not a directory, or the path does not point
+    /// to a directory while `IN_ONLYDIR` was specified
+    NotADirectory,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for AddWatchError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Watch the file at `path` for the events in `mask`, and return the watch
+/// descriptor identifying the watch in the events read from the instance.
+///
+/// # Errors
+/// See [`AddWatchError`] for a list of possible errors.
+pub fn add_watch(fd: &FileDescriptor, path: &str, mask: u32) -> Result<i32, AddWatchError> {
+    let str = SyscallString::from(path);
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::InotifyAddWatch as u64,
+            in("rsi") fd.0 as u64,
+            in("rdx") &str as *const _ as u64,
+            in("r10") u64::from(mask),
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(AddWatchError::from(errno)),
+        Ok(wd) => Ok(wd as i32),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum RemoveWatchError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The file descriptor does not refer to an inotify instance
+    NotAnInstance,
+
+    /// The watch descriptor is not valid for this instance
+    InvalidWatch,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for RemoveWatchError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Remove a watch from an inotify instance. An `IN_IGNORED` event is queued
+/// for the removed watch.
+///
+/// # Errors
+/// See [`RemoveWatchError`] for a list of possible errors.
+pub fn rm_watch(fd: &FileDescriptor, wd: i32) -> Result<(), RemoveWatchError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::InotifyRmWatch as u64,
+            in("rsi") fd.0 as u64,
+            in("rdx") wd as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(RemoveWatchError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
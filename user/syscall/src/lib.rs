@@ -1,7 +1,10 @@
 #![no_std]
 
 pub mod clock;
+pub mod inotify;
 pub mod mmu;
+pub mod power;
+pub mod random;
 pub mod serial;
 pub mod task;
 pub mod vfs;
@@ -81,6 +84,28 @@ pub enum Syscall {
     VfsTruncate = 21,
     VfsStat = 22,
     VfsReaddir = 23,
+    VfsGetdents64 = 24,
+    VfsFstat = 25,
+    VfsFstatAt = 26,
+    VfsStatx = 27,
+    VfsSync = 28,
+    VfsSyncFs = 29,
+    VfsFsync = 30,
+    VfsFdatasync = 31,
+    VfsFlock = 32,
+    VfsFcntl = 33,
+    VfsRename = 34,
+    InotifyInit1 = 35,
+    InotifyAddWatch = 36,
+    InotifyRmWatch = 37,
+    VfsPoll = 38,
+    VfsIoctl = 39,
+    VfsMount = 40,
+    VfsUmount = 41,
+    MmuMapFile = 42,
+    Getrandom = 43,
+    ClockSetTime = 44,
+    Reboot = 45,
 }
 
 /// Interpret the given syscall return code as either an error or a success
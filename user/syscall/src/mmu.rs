@@ -17,6 +17,9 @@ pub enum MapError {
     InvalidRange,
     WouldOverlap,
     OutOfMemory,
+    BadFileDescriptor,
+    AccessDenied,
+    NoSuchDevice,
     UnknownError,
 }
 
@@ -85,6 +88,44 @@ pub unsafe fn map(base: usize, len: usize, access: usize, flags: usize) -> Resul
     }
 }
 
+/// Map the memory of the file opened with the given file descriptor, from its start, with the
+/// given access and flags. Only the files of devices whose memory is directly accessible, like
+/// `/dev/fb0`, can be mapped.
+///
+/// # Errors
+///  Possible errors are the same as [`map`], and:
+///  - `Errno::BadFileDescriptor`: The file descriptor is not valid.
+///  - `Errno::AccessDenied`: The file was not opened for reading, or the write access is
+///    requested and the file was not opened for writing.
+///  - `Errno::NoSuchDevice`: The file cannot be mapped.
+///
+/// # Safety
+/// See [`map`]. The memory of a device can also be modified by the device or by another process
+/// at any time.
+pub unsafe fn map_file(
+    base: usize,
+    len: usize,
+    access: usize,
+    flags: usize,
+    fd: usize,
+) -> Result<usize, MapError> {
+    let ret: usize;
+    core::arch::asm!(
+        "syscall",
+        in("rax") Syscall::MmuMapFile as u64,
+        in("rsi") base,
+        in("rdx") len,
+        in("r10") access,
+        in("r8") flags,
+        in("r9") fd,
+        lateout("rax") ret,
+    );
+    match syscall_return(ret) {
+        Err(errno) => unsafe { Err(core::mem::transmute(errno)) },
+        Ok(ret) => Ok(ret),
+    }
+}
+
 /// Unmap a region of memory. If the region is not mapped, this function will do nothing.
 /// If multiple mappings exist for the same region, all parts included in the given range
 /// will be unmapped.
@@ -0,0 +1,47 @@
+use super::{syscall_return, Errno, Syscall};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum RebootError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The computer has no reset register that the kernel can use
+    NotSupported,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for RebootError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Write all the dirty data back to the disks and reset the computer. This
+/// only returns if the computer cannot be reset.
+///
+/// # Errors
+/// - `RebootError::NotSupported`: The computer has no reset register that the
+/// kernel can use.
+pub fn reboot() -> Result<(), RebootError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::Reboot as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(RebootError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
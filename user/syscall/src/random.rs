@@ -0,0 +1,67 @@
+use super::{syscall_return, Errno, Syscall};
+
+/// Do not block if the random number generator is not ready yet, and fail
+/// instead.
+pub const GRND_NONBLOCK: usize = 0x0001;
+
+/// Use the source of `/dev/random` instead of the one of `/dev/urandom`. Both
+/// are the same generator, so this flag has no effect.
+pub const GRND_RANDOM: usize = 0x0002;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum GetRandomError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The buffer is not in the user address space or is not writable
+    BadAddress,
+
+    /// Unknown flags were passed as an argument
+    InvalidFlags,
+
+    /// The generator is not ready and `GRND_NONBLOCK` was set
+    WouldBlock,
+
+    /// The task was interrupted while waiting for the generator to be ready
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for GetRandomError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Fill the given buffer with random bytes, and return the number of bytes
+/// written. Unless `GRND_NONBLOCK` is set, the task sleeps until the random
+/// number generator of the kernel is ready.
+///
+/// # Errors
+/// See [`GetRandomError`] for a list of possible errors.
+pub fn getrandom(buffer: &mut [u8], flags: usize) -> Result<usize, GetRandomError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::Getrandom as u64,
+            in("rsi") buffer.as_mut_ptr(),
+            in("rdx") buffer.len(),
+            in("r10") flags,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(GetRandomError::from(errno)),
+        Ok(size) => Ok(size),
+    }
+}
@@ -11,6 +11,9 @@ pub enum WriteError {
     /// The buffer is not in the user address space or the buffer is not writable
     BadAddress,
 
+    /// The task was interrupted while waiting for the serial console
+    Interrupted,
+
     UnknownError,
 }
 
@@ -35,6 +38,9 @@ pub enum ReadError {
     /// The buffer is not in the user address space or the buffer is not writable
     BadAddress,
 
+    /// The task was interrupted while waiting for the serial console
+    Interrupted,
+
     UnknownError,
 }
 
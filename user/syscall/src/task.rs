@@ -27,6 +27,9 @@ pub enum SpawnError {
     /// The kernel ran out of memory while trying to spawn the task.
     OutOfMemory,
 
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -18,7 +18,7 @@ pub const O_EXCL: usize = 1 << 4;
 /// A file descriptor. This is an opaque handle that can be used to refer to
 /// an open file.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct FileDescriptor(usize);
+pub struct FileDescriptor(pub(crate) usize);
 
 impl FileDescriptor {
     /// The standard input file descriptor.
@@ -49,6 +49,20 @@ pub enum Whence {
     End(isize),
 }
 
+/// Do not follow the last component of the path if it is a symbolic link.
+pub const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+
+/// If the path is empty, operate on the file referred by the directory file descriptor.
+pub const AT_EMPTY_PATH: usize = 0x1000;
+
+/// Force the attributes to be synchronized with the filesystem. Only valid with `statx`.
+pub const AT_STATX_FORCE_SYNC: usize = 0x2000;
+
+/// Do not synchronize the attributes with the filesystem. Only valid with `statx`.
+pub const AT_STATX_DONT_SYNC: usize = 0x4000;
+
+/// Informations about a file, with the same layout as the Linux `struct stat`.
+#[derive(Debug, Clone, Default)]
 #[repr(C)]
 pub struct Stat {
     /// Device ID of device containing file
@@ -57,14 +71,32 @@ pub struct Stat {
     /// Inode number
     pub ino: u64,
 
+    /// Number of hard links
+    pub nlink: u64,
+
+    /// File type and mode
+    pub mode: u32,
+
+    /// User ID of the owner
+    pub uid: u32,
+
+    /// Group ID of the owner
+    pub gid: u32,
+
+    /// Padding to align the next field
+    pub pad0: u32,
+
+    /// Device ID of the file if it is a special file
+    pub rdev: u64,
+
     /// Size of the file in bytes
-    pub size: u64,
+    pub size: i64,
 
-    /// File type
-    pub kind: u64,
+    /// Preferred block size for I/O operations
+    pub blksize: i64,
 
-    /// Number of hard links
-    pub nlink: u64,
+    /// Number of 512 bytes blocks allocated
+    pub blocks: i64,
 
     /// Unix timestamp of the last access
     pub atime: clock::Timespec,
@@ -74,6 +106,96 @@ pub struct Stat {
 
     /// Unix timestamp of the last status change
     pub ctime: clock::Timespec,
+
+    /// Reserved for future use
+    pub unused: [i64; 3],
+}
+
+impl Stat {
+    /// Bit mask for the file type bits of the `mode` field.
+    pub const S_IFMT: u32 = 0o170_000;
+    pub const S_IFIFO: u32 = 0o010_000;
+    pub const S_IFCHR: u32 = 0o020_000;
+    pub const S_IFDIR: u32 = 0o040_000;
+    pub const S_IFBLK: u32 = 0o060_000;
+    pub const S_IFREG: u32 = 0o100_000;
+    pub const S_IFLNK: u32 = 0o120_000;
+}
+
+/// A timestamp in the [`Statx`] structure.
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct StatxTimestamp {
+    pub seconds: i64,
+    pub nanoseconds: u32,
+    pub reserved: i32,
+}
+
+/// Extended informations about a file, with the same layout as the Linux `struct statx`.
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct Statx {
+    pub mask: u32,
+    pub blksize: u32,
+    pub attributes: u64,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u16,
+    pub spare0: u16,
+    pub ino: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub attributes_mask: u64,
+    pub atime: StatxTimestamp,
+    pub btime: StatxTimestamp,
+    pub ctime: StatxTimestamp,
+    pub mtime: StatxTimestamp,
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    pub mnt_id: u64,
+    pub dio_mem_align: u32,
+    pub dio_offset_align: u32,
+    pub spare3: [u64; 12],
+}
+
+impl Statx {
+    pub const STATX_TYPE: u32 = 0x0001;
+    pub const STATX_MODE: u32 = 0x0002;
+    pub const STATX_NLINK: u32 = 0x0004;
+    pub const STATX_UID: u32 = 0x0008;
+    pub const STATX_GID: u32 = 0x0010;
+    pub const STATX_ATIME: u32 = 0x0020;
+    pub const STATX_MTIME: u32 = 0x0040;
+    pub const STATX_CTIME: u32 = 0x0080;
+    pub const STATX_INO: u32 = 0x0100;
+    pub const STATX_SIZE: u32 = 0x0200;
+    pub const STATX_BLOCKS: u32 = 0x0400;
+    pub const STATX_BASIC_STATS: u32 = 0x07FF;
+}
+
+/// The header of a directory entry returned by [`getdents64`], with the same layout as
+/// the Linux `struct linux_dirent64`. The header is immediately followed by the name of
+/// the entry, terminated by a null byte.
+#[derive(Debug, Clone)]
+#[repr(C, packed)]
+pub struct LinuxDirent64 {
+    pub ino: u64,
+    pub off: i64,
+    pub reclen: u16,
+    pub kind: u8,
+}
+
+impl LinuxDirent64 {
+    pub const DT_UNKNOWN: u8 = 0;
+    pub const DT_FIFO: u8 = 1;
+    pub const DT_CHR: u8 = 2;
+    pub const DT_DIR: u8 = 4;
+    pub const DT_BLK: u8 = 6;
+    pub const DT_REG: u8 = 8;
+    pub const DT_LNK: u8 = 10;
 }
 
 /// A directory entry.
@@ -92,6 +214,7 @@ impl Dirent {
     pub const DIRECTORY: u16 = 2;
     pub const CHAR_DEVICE: u16 = 3;
     pub const BLOCK_DEVICE: u16 = 4;
+    pub const SYMLINK: u16 = 5;
 }
 
 /// Errors that can occur during the `open` syscall.
@@ -134,6 +257,15 @@ pub enum OpenError {
     /// The process has too many files open and cannot open any more
     TooManyFilesOpen,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// The file is a device that does not exist
+    NoSuchDevice,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -192,6 +324,18 @@ pub enum ReadError {
     /// The pipe is broken: there are no writers and the pipe is empty
     BrokenPipe,
 
+    /// There is nothing to read and the file is in non-blocking mode
+    WouldBlock,
+
+    /// The task was interrupted while waiting for data to read
+    Interrupted,
+
+    /// The buffer is too small to hold the data to read
+    BufferTooSmall,
+
+    /// An I/O error occurred while reading from the device
+    IoError,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -227,6 +371,12 @@ pub enum WriteError {
     /// The pipe is broken: there are no readers and the pipe is full
     BrokenPipe,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while writing to the device
+    IoError,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -329,6 +479,9 @@ pub enum ChangeCwdError {
     /// The path does not point to a directory
     NotADirectory,
 
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -376,6 +529,15 @@ pub enum MkdirError {
     /// The path does not point to a directory
     NotADirectory,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -423,6 +585,15 @@ pub enum RmdirError {
     /// The directory is not empty
     NotEmpty,
 
+    /// The directory is the root of a mounted filesystem
+    Busy,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -467,6 +638,15 @@ pub enum TruncateError {
     /// The path does not point to a file
     NotAFile,
 
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -490,6 +670,9 @@ pub enum StatError {
     /// The path passed as an argument
     BadAddress,
 
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
     /// The path is not a valid UTF-8 string
     InvalidUtf8,
 
@@ -508,6 +691,12 @@ pub enum StatError {
     /// A component of the path prefix is not a directory
     NotADirectory,
 
+    /// An invalid flag or flags combination was passed to the syscall
+    InvalidFlag,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -543,6 +732,9 @@ pub enum ReaddirError {
     /// The directory has no entries remaning
     EndOfDirectory,
 
+    /// An I/O error occurred while reading the directory
+    IoError,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -593,6 +785,12 @@ pub enum UnlinkError {
     /// The path does not point to a directory
     IsADirectory,
 
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
     /// An unknown error occurred
     UnknownError,
 }
@@ -874,28 +1072,9 @@ pub fn truncate(path: &str, lenght: usize) -> Result<(), TruncateError> {
 ///
 /// # Errors
 /// See [`StatError`] for a list of possible errors.
-pub fn stat(dir: &FileDescriptor, path: &str) -> Result<Stat, TruncateError> {
+pub fn stat(dir: &FileDescriptor, path: &str) -> Result<Stat, StatError> {
     let str = SyscallString::from(path);
-    let mut stat = Stat {
-        dev: 0,
-        ino: 0,
-        size: 0,
-        kind: 0,
-        nlink: 0,
-        atime: clock::Timespec {
-            seconds: 0,
-            nanoseconds: 0,
-        },
-        mtime: clock::Timespec {
-            seconds: 0,
-            nanoseconds: 0,
-        },
-        ctime: clock::Timespec {
-            seconds: 0,
-            nanoseconds: 0,
-        },
-    };
-
+    let mut stat = Stat::default();
     let ret;
 
     unsafe {
@@ -910,11 +1089,99 @@ pub fn stat(dir: &FileDescriptor, path: &str) -> Result<Stat, TruncateError> {
     }
 
     match syscall_return(ret) {
-        Err(errno) => Err(TruncateError::from(errno)),
+        Err(errno) => Err(StatError::from(errno)),
+        Ok(_) => Ok(stat),
+    }
+}
+
+/// Get informations about the file referred by the specified file descriptor.
+///
+/// # Errors
+/// See [`StatError`] for a list of possible errors.
+pub fn fstat(fd: &FileDescriptor) -> Result<Stat, StatError> {
+    let mut stat = Stat::default();
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsFstat as u64,
+            in("rsi") fd.0,
+            in("rdx") &mut stat as *mut _ as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(StatError::from(errno)),
+        Ok(_) => Ok(stat),
+    }
+}
+
+/// Get informations about the file at the specified path, like [`stat`], but with
+/// additional flags (`AT_EMPTY_PATH` and `AT_SYMLINK_NOFOLLOW`) to control how the
+/// path is resolved.
+///
+/// # Errors
+/// See [`StatError`] for a list of possible errors.
+pub fn fstatat(dir: &FileDescriptor, path: &str, flags: usize) -> Result<Stat, StatError> {
+    let str = SyscallString::from(path);
+    let mut stat = Stat::default();
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsFstatAt as u64,
+            in("rsi") dir.0,
+            in("rdx") &str as *const _ as u64,
+            in("r10") &mut stat as *mut _ as u64,
+            in("r8") flags as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(StatError::from(errno)),
         Ok(_) => Ok(stat),
     }
 }
 
+/// Get extended informations about the file at the specified path. The `mask` argument
+/// tells which fields the caller is interested in, and the `mask` field of the returned
+/// structure tells which fields have actually been filled by the kernel.
+///
+/// # Errors
+/// See [`StatError`] for a list of possible errors.
+pub fn statx(
+    dir: &FileDescriptor,
+    path: &str,
+    flags: usize,
+    mask: u32,
+) -> Result<Statx, StatError> {
+    let str = SyscallString::from(path);
+    let mut statx = Statx::default();
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsStatx as u64,
+            in("rsi") dir.0,
+            in("rdx") &str as *const _ as u64,
+            in("r10") flags as u64,
+            in("r8") mask as u64,
+            in("r9") &mut statx as *mut _ as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(StatError::from(errno)),
+        Ok(_) => Ok(statx),
+    }
+}
+
 /// Read the next directory entry from the specified directory and return it.
 ///
 /// # Errors
@@ -971,3 +1238,860 @@ pub fn unlink(dir: &FileDescriptor, path: &str) -> Result<(), UnlinkError> {
         Ok(_) => Ok(()),
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum Getdents64Error {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The file descriptor is invalid
+    InvalidFileDescriptor,
+
+    /// The buffer passed as an argument is invalid
+    BadAddress,
+
+    /// The descriptor is not a directory
+    NotADirectory,
+
+    /// The directory is not readable
+    NotReadable,
+
+    /// The buffer is too small to hold the next directory entry
+    BufferTooSmall,
+
+    /// An I/O error occurred while reading the directory
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for Getdents64Error {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Read as many directory entries as possible from the specified directory into the
+/// buffer. Each entry is a [`LinuxDirent64`] header followed by the null-terminated
+/// name of the entry, and the `reclen` field of the header gives the offset of the
+/// next entry. Returns the number of bytes written, or 0 at the end of the directory.
+///
+/// # Errors
+/// See [`Getdents64Error`] for a list of possible errors.
+pub fn getdents64(fd: &FileDescriptor, buffer: &mut [u8]) -> Result<usize, Getdents64Error> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsGetdents64 as u64,
+            in("rsi") fd.0 as u64,
+            in("rdx") buffer.as_mut_ptr() as u64,
+            in("r10") buffer.len() as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(Getdents64Error::from(errno)),
+        Ok(written) => Ok(written),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum SyncFsError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The file descriptor does not refer to a file on a filesystem
+    NotSupported,
+
+    /// An I/O error occurred while writing to the device
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for SyncFsError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum FsyncError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The file descriptor does not refer to a file that can be synchronized
+    NotSupported,
+
+    /// An I/O error occurred while writing to the device, now or during a
+    /// previous writeback of the file
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for FsyncError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Synchronize all mounted filesystems with their underlying device. Write errors
+/// are not reported by this function, but by the next `fsync` on the failed files.
+pub fn sync() {
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsSync as u64,
+            lateout("rax") _,
+        );
+    }
+}
+
+/// Synchronize the filesystem containing the given file with its underlying device.
+///
+/// # Errors
+/// See `SyncFsError` for a list of possible errors.
+pub fn syncfs(fd: &FileDescriptor) -> Result<(), SyncFsError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsSyncFs as u64,
+            in("rsi") fd.0 as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(SyncFsError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Synchronize the given file with its underlying device. If a previous writeback
+/// of the file failed, the error is reported by this function.
+///
+/// # Errors
+/// See `FsyncError` for a list of possible errors.
+pub fn fsync(fd: &FileDescriptor) -> Result<(), FsyncError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsFsync as u64,
+            in("rsi") fd.0 as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(FsyncError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Synchronize the data of the given file with its underlying device.
+///
+/// # Errors
+/// See `FsyncError` for a list of possible errors.
+pub fn fdatasync(fd: &FileDescriptor) -> Result<(), FsyncError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsFdatasync as u64,
+            in("rsi") fd.0 as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(FsyncError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum FlockError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The operation is not a valid lock operation
+    InvalidOperation,
+
+    /// The file descriptor does not refer to a file that can be locked
+    NotSupported,
+
+    /// The file is locked and a non-blocking operation was requested
+    WouldBlock,
+
+    /// The task was interrupted while waiting for the lock
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for FlockError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+/// Apply or remove a whole-file advisory lock on the given open file. The
+/// operation is one of `LOCK_SH`, `LOCK_EX` or `LOCK_UN`, optionally combined
+/// with `LOCK_NB` to fail instead of waiting if the file is already locked.
+///
+/// # Errors
+/// See `FlockError` for a list of possible errors.
+pub fn flock(fd: &FileDescriptor, operation: usize) -> Result<(), FlockError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsFlock as u64,
+            in("rsi") fd.0 as u64,
+            in("rdx") operation as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(FlockError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum FcntlError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    InvalidFileDescriptor,
+
+    /// The argument pointer is invalid
+    BadAddress,
+
+    /// The command is not supported
+    InvalidCommand,
+
+    /// The lock description is invalid
+    InvalidArgument,
+
+    /// The file was not opened with the mode required by the requested lock
+    BadFileMode,
+
+    /// The file descriptor does not refer to a file that can be locked
+    NotSupported,
+
+    /// The range is locked and a non-blocking command was requested
+    WouldBlock,
+
+    /// Waiting for the lock would cause a deadlock
+    Deadlock,
+
+    /// The task was interrupted while waiting for the lock
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for FcntlError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+pub const F_GETLK: usize = 5;
+pub const F_SETLK: usize = 6;
+pub const F_SETLKW: usize = 7;
+pub const F_OFD_GETLK: usize = 36;
+pub const F_OFD_SETLK: usize = 37;
+pub const F_OFD_SETLKW: usize = 38;
+
+/// The description of a record lock, used by the lock commands of `fcntl`. The
+/// `whence` field uses the same encoding as the `Whence` enum used by `seek`.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Flock {
+    pub kind: i16,
+    pub whence: i16,
+    pub start: i64,
+    pub len: i64,
+    pub pid: i32,
+}
+
+impl Flock {
+    pub const F_RDLCK: i16 = 0;
+    pub const F_WRLCK: i16 = 1;
+    pub const F_UNLCK: i16 = 2;
+}
+
+/// Perform a record lock command on the given file. The command is one of the
+/// `F_GETLK`, `F_SETLK`, `F_SETLKW` or their `F_OFD_*` counterparts. For the
+/// `F_GETLK` commands, the lock description is updated with the first lock that
+/// would prevent the lock from being placed, or its kind is set to `F_UNLCK`.
+///
+/// # Errors
+/// See `FcntlError` for a list of possible errors.
+pub fn fcntl(fd: &FileDescriptor, cmd: usize, lock: &mut Flock) -> Result<(), FcntlError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsFcntl as u64,
+            in("rsi") fd.0 as u64,
+            in("rdx") cmd as u64,
+            in("r10") lock as *mut Flock as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(FcntlError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum RenameError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// One of the paths passed as an argument is at an invalid address
+    BadAddress,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// One of the paths is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// One of the paths is invalid
+    InvalidPath,
+
+    /// One of the paths is too long
+    PathTooLong,
+
+    /// A component of one of the paths is too long
+    ComponentTooLong,
+
+    /// The entry to rename does not exist
+    NoSuchEntry,
+
+    /// An entry with the new name already exists
+    AlreadyExists,
+
+    /// A component of one of the paths is not a directory
+    NotADirectory,
+
+    /// The old and the new paths are not in the same directory
+    CrossDirectory,
+
+    /// There is no space left on the device
+    NoSpace,
+
+    /// An I/O error occurred while accessing the device
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for RenameError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Rename the entry at `old` to `new`. Relative paths are resolved from the
+/// specified directory, and both paths must be in the same directory.
+///
+/// # Errors
+/// See [`RenameError`] for a list of possible errors.
+pub fn rename(dir: &FileDescriptor, old: &str, new: &str) -> Result<(), RenameError> {
+    let old = SyscallString::from(old);
+    let new = SyscallString::from(new);
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsRename as u64,
+            in("rsi") dir.0,
+            in("rdx") &old as *const _ as u64,
+            in("r10") &new as *const _ as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(RenameError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// There is data to read.
+pub const POLLIN: i16 = 0x0001;
+
+/// There is urgent data to read.
+pub const POLLPRI: i16 = 0x0002;
+
+/// Writing is now possible without blocking.
+pub const POLLOUT: i16 = 0x0004;
+
+/// An error condition occurred. Always reported, even if not requested.
+pub const POLLERR: i16 = 0x0008;
+
+/// The other end of the file was closed. Always reported, even if not requested.
+pub const POLLHUP: i16 = 0x0010;
+
+/// The file descriptor is not open.
+pub const POLLNVAL: i16 = 0x0020;
+
+/// A file descriptor to poll, with the same layout as the Linux `pollfd` structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct PollFd {
+    /// The file descriptor to poll. Negative descriptors are ignored.
+    pub fd: i32,
+
+    /// The events the caller is interested in.
+    pub events: i16,
+
+    /// The events that occurred, filled by the kernel.
+    pub revents: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum PollError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The array of file descriptors is at an invalid address
+    BadAddress,
+
+    /// The number of file descriptors is too large
+    InvalidArgument,
+
+    /// The task was interrupted while waiting
+    Interrupted,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for PollError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Wait for one of the given file descriptors to become ready for the requested
+/// events, or until `timeout` milliseconds have elapsed. A negative timeout waits
+/// forever. Returns the number of file descriptors with at least one event.
+///
+/// # Errors
+/// See [`PollError`] for a list of possible errors.
+pub fn poll(fds: &mut [PollFd], timeout: isize) -> Result<usize, PollError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsPoll as u64,
+            in("rsi") fds.as_mut_ptr() as u64,
+            in("rdx") fds.len() as u64,
+            in("r10") timeout as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    syscall_return(ret).map_err(PollError::from)
+}
+
+/// Get the size of a block device in bytes, as an `u64`.
+pub const BLKGETSIZE64: usize = 0x8008_1272;
+
+/// Get the sector size of a block device, as an `i32`.
+pub const BLKSSZGET: usize = 0x1268;
+
+/// Write back the dirty buffers of a block device and drop them from the cache.
+pub const BLKFLSBUF: usize = 0x1261;
+
+/// Bind the file whose descriptor is given as argument to a loop device.
+pub const LOOP_SET_FD: usize = 0x4C00;
+
+/// Unbind the file bound to a loop device.
+pub const LOOP_CLR_FD: usize = 0x4C01;
+
+/// Update the capacity of a loop device after its bound file was resized.
+pub const LOOP_SET_CAPACITY: usize = 0x4C07;
+
+/// Get the attributes of a terminal, as a [`Termios`].
+pub const TCGETS: usize = 0x5401;
+
+/// Set the attributes of a terminal from a [`Termios`]. `TCSETSF` also discards
+/// the input not read yet.
+pub const TCSETS: usize = 0x5402;
+pub const TCSETSW: usize = 0x5403;
+pub const TCSETSF: usize = 0x5404;
+
+/// Get and set the foreground task of a terminal, as an `i32`.
+pub const TIOCGPGRP: usize = 0x540F;
+pub const TIOCSPGRP: usize = 0x5410;
+
+/// Get and set the window size of a terminal, as a [`WindowSize`].
+pub const TIOCGWINSZ: usize = 0x5413;
+pub const TIOCSWINSZ: usize = 0x5414;
+
+/// Get the number of bytes that can be read from a terminal, as an `i32`.
+pub const FIONREAD: usize = 0x541B;
+
+/// Get the number of a pseudo-terminal from its master side, as a `u32`. The
+/// slave side is `/dev/pts/<number>`.
+pub const TIOCGPTN: usize = 0x8004_5430;
+
+/// Lock (with a non-zero `i32`) or unlock the slave side of a pseudo-terminal
+/// from its master side. It must be unlocked before being opened.
+pub const TIOCSPTLCK: usize = 0x4004_5431;
+
+/// Get the version of the event interface of an input device, as an `i32`.
+pub const EVIOCGVERSION: usize = 0x8004_4501;
+
+/// Get the identifier of an input device, as an [`InputId`].
+pub const EVIOCGID: usize = 0x8008_4502;
+
+/// Grab (with a non-zero value) or release (with zero) an input device, passed
+/// by value. The events of a grabbed device are only read by the file which
+/// grabbed it, and a grabbed keyboard no longer feeds the console.
+pub const EVIOCGRAB: usize = 0x4004_4590;
+
+/// The identifier of an input device, as returned by the `EVIOCGID` request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct InputId {
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+}
+
+/// An event read from an input device. The types and codes of the events are
+/// the ones of Linux.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct InputEvent {
+    pub seconds: u64,
+    pub microseconds: u64,
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+/// The attributes of a terminal, as used by the `TCGETS` and `TCSETS` requests.
+/// The flags and the indexes of the control characters are the ones of Linux.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; 19],
+}
+
+/// The size of the window of a terminal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub columns: u16,
+    pub xpixel: u16,
+    pub ypixel: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum IoctlError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// An invalid file descriptor was passed as an argument
+    BadFileDescriptor,
+
+    /// The request is not supported by the file
+    NotATerminal,
+
+    /// The argument of the request is invalid
+    InvalidArgument,
+
+    /// The argument of the request points to an invalid address
+    BadAddress,
+
+    /// The device is busy
+    Busy,
+
+    /// The device does not exist anymore
+    NoSuchDevice,
+
+    /// An I/O error occurred while handling the request
+    IoError,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for IoctlError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Perform the device-specific request `request` on the given file. The meaning
+/// of `arg` and of the returned value depends on the request.
+///
+/// # Errors
+/// See [`IoctlError`] for a list of possible errors.
+pub fn ioctl(fd: &FileDescriptor, request: usize, arg: usize) -> Result<usize, IoctlError> {
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsIoctl as u64,
+            in("rsi") fd.0 as u64,
+            in("rdx") request as u64,
+            in("r10") arg as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    syscall_return(ret).map_err(IoctlError::from)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum MountError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// One of the strings passed as an argument is at an invalid address
+    BadAddress,
+
+    /// One of the strings is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// One of the paths is invalid
+    InvalidPath,
+
+    /// One of the strings is too long
+    PathTooLong,
+
+    /// The source or the target does not exist
+    NoSuchEntry,
+
+    /// The target, or a component of one of the paths, is not a directory
+    NotADirectory,
+
+    /// The source is not a block device node
+    NotABlockDevice,
+
+    /// No filesystem with the given type is registered
+    NoSuchFilesystem,
+
+    /// The device does not contain a valid filesystem of the given type
+    InvalidFilesystem,
+
+    /// The target or the device is already used by another mount
+    Busy,
+
+    /// An I/O error occurred while reading the filesystem
+    IoError,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for MountError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Mount the filesystem `fstype` stored on the block device node at `source` on
+/// the directory at `target`. Filesystems that are not stored on a device are
+/// mounted with an empty `source`.
+///
+/// # Errors
+/// See [`MountError`] for a list of possible errors.
+pub fn mount(source: &str, target: &str, fstype: &str) -> Result<(), MountError> {
+    let source = SyscallString::from(source);
+    let target = SyscallString::from(target);
+    let fstype = SyscallString::from(fstype);
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsMount as u64,
+            in("rsi") &source as *const _ as u64,
+            in("rdx") &target as *const _ as u64,
+            in("r10") &fstype as *const _ as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(MountError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(usize)]
+pub enum UmountError {
+    /// The syscall number is invalid.
+    NoSuchSyscall = 1,
+
+    /// The path passed as an argument is at an invalid address
+    BadAddress,
+
+    /// The path is not a valid UTF-8 string
+    InvalidUtf8,
+
+    /// The path is invalid
+    InvalidPath,
+
+    /// The path is too long
+    PathTooLong,
+
+    /// The path does not exist
+    NoSuchEntry,
+
+    /// A component of the path is not a directory
+    NotADirectory,
+
+    /// The path is not the root of a mounted filesystem
+    NotMounted,
+
+    /// A file of the filesystem is still in use
+    Busy,
+
+    /// Too many symbolic links were encountered while resolving the path
+    TooManySymlinks,
+
+    /// An unknown error occurred
+    UnknownError,
+}
+
+impl From<Errno> for UmountError {
+    fn from(error: Errno) -> Self {
+        if error.code() > -(Self::UnknownError as isize) {
+            unsafe { core::mem::transmute(error) }
+        } else {
+            Self::UnknownError
+        }
+    }
+}
+
+/// Unmount the filesystem mounted on the directory at `target`.
+///
+/// # Errors
+/// See [`UmountError`] for a list of possible errors.
+pub fn umount(target: &str) -> Result<(), UmountError> {
+    let target = SyscallString::from(target);
+    let ret;
+
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            in("rax") Syscall::VfsUmount as u64,
+            in("rsi") &target as *const _ as u64,
+            lateout("rax") ret,
+        );
+    }
+
+    match syscall_return(ret) {
+        Err(errno) => Err(UmountError::from(errno)),
+        Ok(_) => Ok(()),
+    }
+}
@@ -35,6 +35,81 @@ pub struct FramebufferInfo {
     pub bpp: u16,
 }
 
+/// Get the variable informations of `/dev/fb0`, as a [`VarScreenInfo`].
+pub const FBIOGET_VSCREENINFO: usize = 0x4600;
+
+/// Get the fixed informations of `/dev/fb0`, as a [`FixScreenInfo`].
+pub const FBIOGET_FSCREENINFO: usize = 0x4602;
+
+/// The position of a color channel in a pixel, as used by [`VarScreenInfo`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Bitfield {
+    pub offset: u32,
+    pub length: u32,
+    pub msb_right: u32,
+}
+
+/// The variable informations of a framebuffer device, with the layout of the
+/// Linux `fb_var_screeninfo` structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct VarScreenInfo {
+    pub xres: u32,
+    pub yres: u32,
+    pub xres_virtual: u32,
+    pub yres_virtual: u32,
+    pub xoffset: u32,
+    pub yoffset: u32,
+    pub bits_per_pixel: u32,
+    pub grayscale: u32,
+    pub red: Bitfield,
+    pub green: Bitfield,
+    pub blue: Bitfield,
+    pub transp: Bitfield,
+    pub nonstd: u32,
+    pub activate: u32,
+    pub height: u32,
+    pub width: u32,
+    pub accel_flags: u32,
+    pub pixclock: u32,
+    pub left_margin: u32,
+    pub right_margin: u32,
+    pub upper_margin: u32,
+    pub lower_margin: u32,
+    pub hsync_len: u32,
+    pub vsync_len: u32,
+    pub sync: u32,
+    pub vmode: u32,
+    pub rotate: u32,
+    pub colorspace: u32,
+    pub reserved: [u32; 4],
+}
+
+/// The fixed informations of a framebuffer device, with the layout of the
+/// Linux `fb_fix_screeninfo` structure. The `line_length` field is the number
+/// of bytes between the start of two lines, and `smem_len` the size of the
+/// memory that can be mapped with [`crate::mmu::map_file`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct FixScreenInfo {
+    pub id: [u8; 16],
+    pub smem_start: u64,
+    pub smem_len: u32,
+    pub kind: u32,
+    pub type_aux: u32,
+    pub visual: u32,
+    pub xpanstep: u16,
+    pub ypanstep: u16,
+    pub ywrapstep: u16,
+    pub line_length: u32,
+    pub mmio_start: u64,
+    pub mmio_len: u32,
+    pub accel: u32,
+    pub capabilities: u16,
+    pub reserved: [u16; 2],
+}
+
 /// Obtain information about the framebuffer.
 ///
 /// This function returns information about the framebuffer, such as its height,